@@ -0,0 +1,201 @@
+use serde::{Deserialize, Serialize};
+use solana_sdk::clock::Slot;
+use solana_sdk::signature::Signature;
+use std::str::FromStr;
+
+use crate::config::CommitmentHash;
+use crate::merkle::{verify_path, MerkleStep, MerkleTree};
+use crate::BlockProof;
+
+// A leaf of a block's sorted signature tree, with its position and path
+#[derive(Serialize, Deserialize)]
+pub struct SortedLeaf {
+    pub signature: String,
+    pub index: usize,
+    pub merkle_path: Vec<MerkleStep>,
+}
+
+// Evidence that `signature` is not in block `slot`: the two adjacent leaves of
+// the block's sorted signature tree it would fall between. Below the first
+// or above the last leaf only one neighbour exists, and an empty block has none.
+#[derive(Serialize, Deserialize)]
+pub struct AbsenceProof {
+    pub slot: Slot,
+    pub signature: String,
+    pub lower: Option<SortedLeaf>,
+    pub upper: Option<SortedLeaf>,
+}
+
+// A block's transaction signatures ordered by their raw bytes, the leaf order
+// of the sorted signature tree
+pub fn sorted_signatures<'a>(signatures: impl Iterator<Item = &'a String>) -> Vec<Signature> {
+    let mut sorted: Vec<Signature> = signatures
+        .map(|signature| Signature::from_str(signature).expect("Invalid transaction signature"))
+        .collect();
+    sorted.sort_unstable_by(|a, b| a.as_ref().cmp(b.as_ref()));
+    sorted
+}
+
+fn sorted_tree(hash: CommitmentHash, sorted: &[Signature]) -> MerkleTree {
+    let leaves: Vec<&[u8]> = sorted.iter().map(|signature| signature.as_ref()).collect();
+    MerkleTree::new(hash, &leaves)
+}
+
+pub fn sorted_root(hash: CommitmentHash, signatures: &[String]) -> [u8; 32] {
+    sorted_tree(hash, &sorted_signatures(signatures.iter())).root()
+}
+
+// Builds the absence proof for `signature`, or `None` if the block contains it
+pub fn prove(block_proof: &BlockProof, signature: &Signature) -> Option<AbsenceProof> {
+    let sorted = sorted_signatures(block_proof.transactions.iter().map(|transaction| &transaction.transaction_hash));
+    let position = match sorted.binary_search_by(|probe| probe.as_ref().cmp(signature.as_ref())) {
+        Ok(_) => return None,
+        Err(position) => position,
+    };
+
+    let tree = sorted_tree(block_proof.commitment_hash, &sorted);
+    let leaf = |index: usize| SortedLeaf {
+        signature: sorted[index].to_string(),
+        index,
+        merkle_path: tree.path(index),
+    };
+
+    Some(AbsenceProof {
+        slot: block_proof.slot,
+        signature: signature.to_string(),
+        lower: position.checked_sub(1).map(leaf),
+        upper: (position < sorted.len()).then(|| leaf(position)),
+    })
+}
+
+// Checks an absence proof against the sorted signature root of its block proof
+pub fn verify(block_proof: &BlockProof, absence: &AbsenceProof) -> bool {
+    let Some(root) = hex::decode(&block_proof.sorted_root).ok().and_then(|bytes| <[u8; 32]>::try_from(bytes).ok())
+    else {
+        return false;
+    };
+    let Ok(signature) = Signature::from_str(&absence.signature) else {
+        return false;
+    };
+    let leaf_count = block_proof.transactions.len();
+
+    // Each neighbour must be in the tree and on the correct side of the signature
+    let neighbour = |leaf: &SortedLeaf, below: bool| {
+        let Ok(neighbour) = Signature::from_str(&leaf.signature) else {
+            return false;
+        };
+        let ordered = if below {
+            neighbour.as_ref() < signature.as_ref()
+        } else {
+            neighbour.as_ref() > signature.as_ref()
+        };
+        let hash = block_proof.commitment_hash;
+        ordered && verify_path(hash, &root, neighbour.as_ref(), leaf.index, leaf_count, &leaf.merkle_path)
+    };
+
+    absence.slot == block_proof.slot
+        && match (&absence.lower, &absence.upper) {
+            (None, None) => leaf_count == 0 && root == [0u8; 32],
+            (None, Some(upper)) => upper.index == 0 && neighbour(upper, false),
+            (Some(lower), None) => lower.index + 1 == leaf_count && neighbour(lower, true),
+            (Some(lower), Some(upper)) => {
+                upper.index == lower.index + 1 && neighbour(lower, true) && neighbour(upper, false)
+            }
+        }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A proof file listing `signatures` with their sorted signature root
+    fn block_proof(signatures: &[Signature]) -> BlockProof {
+        let signatures: Vec<String> = signatures.iter().map(Signature::to_string).collect();
+        let transactions: Vec<_> = signatures
+            .iter()
+            .enumerate()
+            .map(|(index, signature)| {
+                serde_json::json!({ "transaction_hash": signature, "leaf_index": index, "merkle_path": [] })
+            })
+            .collect();
+        serde_json::from_value(serde_json::json!({
+            "slot": 1,
+            "block_hash": "hash",
+            "transactions_root": "00",
+            "sorted_root": hex::encode(sorted_root(CommitmentHash::Sha256, &signatures)),
+            "commitment": "00",
+            "proof": "00",
+            "old_root": "00",
+            "new_root": "00",
+            "transactions": transactions,
+        }))
+        .unwrap()
+    }
+
+    fn signature(byte: u8) -> Signature {
+        Signature::from([byte; 64])
+    }
+
+    #[test]
+    fn absent_signatures_are_proved_between_their_neighbours() {
+        // Listed out of order, as a block's transactions are
+        let block_proof = block_proof(&[signature(6), signature(2), signature(4)]);
+        assert!(prove(&block_proof, &signature(4)).is_none());
+
+        let between = prove(&block_proof, &signature(3)).unwrap();
+        assert_eq!(between.lower.as_ref().map(|leaf| leaf.index), Some(0));
+        assert_eq!(between.upper.as_ref().map(|leaf| leaf.index), Some(1));
+        assert!(verify(&block_proof, &between));
+
+        let first = prove(&block_proof, &signature(1)).unwrap();
+        assert!(first.lower.is_none());
+        assert_eq!(first.upper.as_ref().map(|leaf| leaf.index), Some(0));
+        assert!(verify(&block_proof, &first));
+
+        let last = prove(&block_proof, &signature(7)).unwrap();
+        assert_eq!(last.lower.as_ref().map(|leaf| leaf.index), Some(2));
+        assert!(last.upper.is_none());
+        assert!(verify(&block_proof, &last));
+    }
+
+    #[test]
+    fn absence_proofs_are_checked() {
+        let block_proof = block_proof(&[signature(2), signature(4), signature(6)]);
+
+        // A neighbour on the wrong side of the signature
+        let mut swapped = prove(&block_proof, &signature(3)).unwrap();
+        swapped.signature = signature(5).to_string();
+        assert!(!verify(&block_proof, &swapped));
+        // Dropping the upper neighbour to pass the lower one off as the last leaf
+        let mut truncated = prove(&block_proof, &signature(3)).unwrap();
+        truncated.upper = None;
+        assert!(!verify(&block_proof, &truncated));
+        // Dropping the lower neighbour to pass the upper one off as the first leaf
+        let mut truncated = prove(&block_proof, &signature(5)).unwrap();
+        truncated.lower = None;
+        assert!(!verify(&block_proof, &truncated));
+        // A path that does not lead to the sorted root
+        let mut tampered = prove(&block_proof, &signature(7)).unwrap();
+        tampered.lower.as_mut().unwrap().merkle_path.clear();
+        assert!(!verify(&block_proof, &tampered));
+        let mut other_slot = prove(&block_proof, &signature(1)).unwrap();
+        other_slot.slot = 2;
+        assert!(!verify(&block_proof, &other_slot));
+        // A proof that claims the block is empty
+        let mut emptied = prove(&block_proof, &signature(1)).unwrap();
+        emptied.upper = None;
+        assert!(!verify(&block_proof, &emptied));
+    }
+
+    #[test]
+    fn empty_blocks_contain_no_signature() {
+        let block_proof = block_proof(&[]);
+        let absence = prove(&block_proof, &signature(1)).unwrap();
+        assert!(absence.lower.is_none() && absence.upper.is_none());
+        assert!(verify(&block_proof, &absence));
+
+        let mut rooted = block_proof.clone();
+        rooted.sorted_root = hex::encode([1u8; 32]);
+        assert!(!verify(&rooted, &absence));
+    }
+}
@@ -0,0 +1,132 @@
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use solana_account_decoder::UiAccountEncoding;
+use solana_client::client_error::ClientError;
+use solana_client::rpc_client::RpcClient;
+use solana_client::rpc_config::RpcAccountInfoConfig;
+use solana_client::rpc_response::Response;
+use solana_sdk::account::Account;
+use solana_sdk::clock::Slot;
+use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::pubkey::Pubkey;
+use std::str::FromStr;
+
+use crate::config::CommitmentHash;
+use crate::merkle::{verify_path, MerkleStep, MerkleTree};
+
+// Accounts one getMultipleAccounts request may ask for
+pub const MAX_ACCOUNTS: usize = 100;
+
+// State of an account as the node returned it
+#[derive(Serialize, Deserialize, Clone)]
+pub struct AccountState {
+    pub pubkey: String,
+    pub owner: String,
+    pub lamports: u64,
+    // SHA-256 (hex) of the account data
+    pub data_hash: String,
+}
+
+impl AccountState {
+    // Leaf of the account in the accounts tree
+    fn leaf(&self) -> String {
+        format!("{}:{}:{}:{}", self.pubkey, self.owner, self.lamports, self.data_hash)
+    }
+}
+
+// The watched accounts read once a block was processed. The node answers at
+// `context_slot`, no earlier than the block's slot.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct AccountStates {
+    pub context_slot: Slot,
+    pub accounts: Vec<AccountState>,
+    // Watched accounts that did not exist
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub missing: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct AccountInclusion {
+    #[serde(flatten)]
+    pub state: AccountState,
+    pub leaf_index: usize,
+    pub merkle_path: Vec<MerkleStep>,
+}
+
+// Merkle root over the account states, with an inclusion path for each
+#[derive(Serialize, Deserialize, Clone)]
+pub struct AccountStateProof {
+    pub context_slot: Slot,
+    pub accounts_root: String,
+    pub accounts: Vec<AccountInclusion>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub missing: Vec<String>,
+}
+
+// Reads `accounts` in one request, at a context slot no earlier than `slot`,
+// so they are all seen at the same slot
+pub fn read(
+    client: &RpcClient,
+    accounts: &[String],
+    slot: Slot,
+) -> Result<Response<Vec<Option<Account>>>, Box<ClientError>> {
+    let pubkeys: Vec<Pubkey> = accounts.iter().map(|account| Pubkey::from_str(account).unwrap()).collect();
+    let config = RpcAccountInfoConfig {
+        encoding: Some(UiAccountEncoding::Base64),
+        commitment: Some(CommitmentConfig::confirmed()),
+        min_context_slot: Some(slot),
+        data_slice: None,
+    };
+    client.get_multiple_accounts_with_config(&pubkeys, config).map_err(Box::new)
+}
+
+pub fn fetch(client: &RpcClient, accounts: &[String], slot: Slot) -> Result<AccountStates, Box<ClientError>> {
+    let response = read(client, accounts, slot)?;
+    let mut states = AccountStates { context_slot: response.context.slot, accounts: Vec::new(), missing: Vec::new() };
+    for (pubkey, account) in accounts.iter().zip(response.value) {
+        match account {
+            Some(account) => states.accounts.push(AccountState {
+                pubkey: pubkey.clone(),
+                owner: account.owner.to_string(),
+                lamports: account.lamports,
+                data_hash: hex::encode(Sha256::digest(&account.data)),
+            }),
+            None => states.missing.push(pubkey.clone()),
+        }
+    }
+    Ok(states)
+}
+
+pub fn prove(states: AccountStates, hash: CommitmentHash) -> AccountStateProof {
+    let leaves: Vec<String> = states.accounts.iter().map(AccountState::leaf).collect();
+    let tree = MerkleTree::new(hash, &leaves);
+    AccountStateProof {
+        context_slot: states.context_slot,
+        accounts_root: hex::encode(tree.root()),
+        accounts: states
+            .accounts
+            .into_iter()
+            .enumerate()
+            .map(|(leaf_index, state)| AccountInclusion { state, leaf_index, merkle_path: tree.path(leaf_index) })
+            .collect(),
+        missing: states.missing,
+    }
+}
+
+// The states a proof was made from, for proving its block again
+pub fn observed(proof: AccountStateProof) -> AccountStates {
+    AccountStates {
+        context_slot: proof.context_slot,
+        accounts: proof.accounts.into_iter().map(|inclusion| inclusion.state).collect(),
+        missing: proof.missing,
+    }
+}
+
+// Checks the inclusion path of `inclusion` against the accounts root of `proof`
+pub fn verify(proof: &AccountStateProof, inclusion: &AccountInclusion, hash: CommitmentHash) -> bool {
+    let Some(root) = hex::decode(&proof.accounts_root).ok().and_then(|root| <[u8; 32]>::try_from(root).ok()) else {
+        return false;
+    };
+    let leaf = inclusion.state.leaf();
+    verify_path(hash, &root, leaf.as_bytes(), inclusion.leaf_index, proof.accounts.len(), &inclusion.merkle_path)
+}
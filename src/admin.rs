@@ -0,0 +1,224 @@
+use hyper::header::{AUTHORIZATION, CONTENT_TYPE};
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Method, Request, Response, Server, StatusCode};
+use log::{error, info, LevelFilter};
+use serde::Deserialize;
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use subtle::ConstantTimeEq;
+use tokio::signal::unix::{signal, SignalKind};
+
+use crate::config::AdminConfig;
+use crate::params::SharedKeys;
+
+// State of the running listeners that the admin API can change. Listeners
+// poll it between slots, so a request never interrupts a proof in flight.
+// Flushes and reloads are counted rather than flagged so that every listener
+// instance of the process acts on each request.
+pub struct Control {
+    paused: AtomicBool,
+    flushes: AtomicU64,
+    reloads: AtomicU64,
+    // `None` in witness-only mode
+    keys: Option<SharedKeys>,
+}
+
+impl Control {
+    pub fn new(keys: Option<SharedKeys>) -> Self {
+        Control {
+            paused: AtomicBool::new(false),
+            flushes: AtomicU64::new(0),
+            reloads: AtomicU64::new(0),
+            keys,
+        }
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::SeqCst)
+    }
+
+    // Whether a flush was requested since the listener last saw one; `seen`
+    // is the listener's own count
+    pub fn take_flush_request(&self, seen: &AtomicU64) -> bool {
+        let requested = self.flushes.load(Ordering::SeqCst);
+        seen.swap(requested, Ordering::SeqCst) != requested
+    }
+
+    // Asks for the config file and the proving parameters to be reloaded
+    // before the next slot
+    pub fn request_reload(&self) {
+        self.reloads.fetch_add(1, Ordering::SeqCst);
+        if let Some(keys) = &self.keys {
+            keys.request_reload();
+        }
+    }
+
+    pub fn take_reload_request(&self, seen: &AtomicU64) -> bool {
+        let requested = self.reloads.load(Ordering::SeqCst);
+        seen.swap(requested, Ordering::SeqCst) != requested
+    }
+
+    // Requests a reload whenever the process receives SIGHUP
+    pub fn reload_on_hangup(self: &Arc<Self>) {
+        let control = self.clone();
+        tokio::spawn(async move {
+            let mut hangup = signal(SignalKind::hangup()).expect("Unable to listen for SIGHUP");
+            while hangup.recv().await.is_some() {
+                info!("Received SIGHUP, reloading the configuration before the next slot");
+                control.request_reload();
+            }
+        });
+    }
+}
+
+#[derive(Deserialize)]
+struct LogLevelRequest {
+    level: String,
+}
+
+fn respond(status: StatusCode, body: serde_json::Value) -> Response<Body> {
+    Response::builder()
+        .status(status)
+        .header(CONTENT_TYPE, "application/json")
+        .body(Body::from(body.to_string()))
+        .unwrap()
+}
+
+// Tokens are compared by their digests in constant time, so response times
+// give away neither the token nor its length
+fn authorized(request: &Request<Body>, token: &str) -> bool {
+    request
+        .headers()
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .is_some_and(|presented| {
+            Sha256::digest(presented.as_bytes()).as_slice().ct_eq(Sha256::digest(token.as_bytes()).as_slice()).into()
+        })
+}
+
+async fn set_log_level(request: Request<Body>) -> Response<Body> {
+    let level = match hyper::body::to_bytes(request.into_body()).await {
+        Ok(body) => serde_json::from_slice::<LogLevelRequest>(&body).ok().and_then(|body| body.level.parse().ok()),
+        Err(_) => None,
+    };
+    let Some(level) = level else {
+        return respond(
+            StatusCode::BAD_REQUEST,
+            json!({"error": "expected {\"level\": \"off|error|warn|info|debug|trace\"}"}),
+        );
+    };
+    log::set_max_level(level);
+    info!("Log level set to {} through the admin API", level);
+    respond(StatusCode::OK, json!({"log_level": level.to_string().to_lowercase()}))
+}
+
+async fn handle(
+    request: Request<Body>,
+    control: Arc<Control>,
+    token: Arc<String>,
+) -> Result<Response<Body>, Infallible> {
+    if !authorized(&request, &token) {
+        return Ok(respond(StatusCode::UNAUTHORIZED, json!({"error": "missing or invalid token"})));
+    }
+    if request.method() != Method::POST {
+        return Ok(respond(StatusCode::METHOD_NOT_ALLOWED, json!({"error": "admin requests must be POST"})));
+    }
+
+    let response = match request.uri().path() {
+        "/admin/pause" => {
+            control.paused.store(true, Ordering::SeqCst);
+            info!("Paused through the admin API");
+            respond(StatusCode::OK, json!({"paused": true}))
+        }
+        "/admin/resume" => {
+            control.paused.store(false, Ordering::SeqCst);
+            info!("Resumed through the admin API");
+            respond(StatusCode::OK, json!({"paused": false}))
+        }
+        "/admin/set-log-level" => set_log_level(request).await,
+        // Applied by the listener before its next slot
+        "/admin/flush-checkpoint" => {
+            control.flushes.fetch_add(1, Ordering::SeqCst);
+            respond(StatusCode::ACCEPTED, json!({"flush_checkpoint": "scheduled"}))
+        }
+        "/admin/reload-config" => {
+            control.request_reload();
+            respond(StatusCode::ACCEPTED, json!({"reload_config": "scheduled"}))
+        }
+        "/admin/reload-keys" => match &control.keys {
+            Some(keys) => {
+                keys.request_reload();
+                respond(StatusCode::ACCEPTED, json!({"reload_keys": "scheduled"}))
+            }
+            None => respond(StatusCode::CONFLICT, json!({"error": "no proving keys in witness-only mode"})),
+        },
+        _ => respond(StatusCode::NOT_FOUND, json!({"error": "unknown admin endpoint"})),
+    };
+    Ok(response)
+}
+
+// Serves the admin API in the background
+pub fn spawn(config: &AdminConfig, control: Arc<Control>) {
+    let addr: SocketAddr = config.listen_addr.parse().expect("Invalid admin listen address");
+    let token = Arc::new(config.token.clone());
+    let make_service = make_service_fn(move |_| {
+        let (control, token) = (control.clone(), token.clone());
+        async move { Ok::<_, Infallible>(service_fn(move |request| handle(request, control.clone(), token.clone()))) }
+    });
+    let server = Server::try_bind(&addr).expect("Unable to bind admin API").serve(make_service);
+    info!("Admin API listening on {}", addr);
+
+    tokio::spawn(async move {
+        if let Err(e) = server.await {
+            error!("Admin API stopped: {:?}", e);
+        }
+    });
+}
+
+// Parses a configured log level, for startup
+pub fn parse_level(level: &str) -> LevelFilter {
+    level.parse().unwrap_or_else(|_| panic!("Invalid log level {:?}", level))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn send(method: Method, path: &str, authorization: Option<&str>) -> (Arc<Control>, StatusCode) {
+        let control = Arc::new(Control::new(None));
+        let mut request = Request::builder().method(method).uri(path);
+        if let Some(authorization) = authorization {
+            request = request.header(AUTHORIZATION, authorization);
+        }
+        let request = request.body(Body::empty()).unwrap();
+        let response = handle(request, control.clone(), Arc::new("secret".to_string())).await.unwrap();
+        (control, response.status())
+    }
+
+    #[tokio::test]
+    async fn requests_need_the_token() {
+        let (control, status) = send(Method::POST, "/admin/pause", Some("Bearer secret")).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(control.is_paused());
+
+        for authorization in [None, Some("Bearer wrong"), Some("Bearer secret2"), Some("Bearer "), Some("secret")] {
+            let (control, status) = send(Method::POST, "/admin/pause", authorization).await;
+            assert_eq!(status, StatusCode::UNAUTHORIZED);
+            assert!(!control.is_paused());
+        }
+    }
+
+    #[tokio::test]
+    async fn requests_must_be_posted() {
+        let (control, status) = send(Method::GET, "/admin/pause", Some("Bearer secret")).await;
+        assert_eq!(status, StatusCode::METHOD_NOT_ALLOWED);
+        assert!(!control.is_paused());
+        let (_, status) = send(Method::POST, "/admin/unknown", Some("Bearer secret")).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+}
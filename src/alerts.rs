@@ -0,0 +1,103 @@
+use log::warn;
+use serde_json::json;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Instant;
+use tokio::time::Duration;
+
+use crate::config::{AlertConfig, WebhookConfig, WebhookKind};
+
+// Conditions worth paging an operator about
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum AlertKind {
+    // A transaction signature was seen in more than one block
+    DuplicateSignature,
+    // The last recorded slot is too far behind the tip
+    Lagging,
+    // A slot was recorded as failed in the index
+    SlotFailed,
+    // A proof did not pass its self-check, so it was not published
+    VerificationFailed,
+}
+
+// Posts alerts to chat webhooks. Each kind of alert is sent at most once per
+// cooldown for an instance; alerts held back meanwhile are counted in the
+// next one sent.
+pub struct Alerter {
+    client: reqwest::Client,
+    webhooks: Vec<WebhookConfig>,
+    max_lag_slots: Option<u64>,
+    cooldown: Duration,
+    sent: Mutex<HashMap<(Option<String>, AlertKind), Sent>>,
+}
+
+// When an alert was last sent, and how many were held back since
+struct Sent {
+    at: Instant,
+    held_back: usize,
+}
+
+impl Alerter {
+    pub fn new(config: &AlertConfig) -> Self {
+        Alerter {
+            client: reqwest::Client::builder()
+                .timeout(Duration::from_secs(10))
+                .build()
+                .expect("Unable to build alert client"),
+            webhooks: config.webhooks.clone(),
+            max_lag_slots: config.max_lag_slots,
+            cooldown: Duration::from_secs(config.cooldown_secs),
+            sent: Mutex::new(HashMap::new()),
+        }
+    }
+
+    // Alerts if `last_slot` is more than `max_lag_slots` behind `tip`
+    pub fn check_lag(&self, instance: Option<&str>, last_slot: u64, tip: u64) {
+        let lag = tip.saturating_sub(last_slot);
+        if self.max_lag_slots.is_some_and(|max_lag_slots| lag > max_lag_slots) {
+            let message = format!("{} slots behind the tip: last recorded slot {}, tip {}", lag, last_slot, tip);
+            self.alert(instance, AlertKind::Lagging, message);
+        }
+    }
+
+    // Sends `message` to every webhook in the background, unless an alert of
+    // the same kind was sent within the cooldown
+    pub fn alert(&self, instance: Option<&str>, kind: AlertKind, message: String) {
+        let held_back = {
+            let mut sent = self.sent.lock().unwrap();
+            let key = (instance.map(str::to_string), kind);
+            match sent.get_mut(&key) {
+                Some(last) if last.at.elapsed() < self.cooldown => {
+                    last.held_back += 1;
+                    return;
+                }
+                _ => {
+                    let last = sent.insert(key, Sent { at: Instant::now(), held_back: 0 });
+                    last.map(|last| last.held_back).unwrap_or_default()
+                }
+            }
+        };
+
+        let mut text = match instance {
+            Some(name) => format!("solana-listener {}: {}", name, message),
+            None => format!("solana-listener: {}", message),
+        };
+        if held_back > 0 {
+            text.push_str(&format!(" ({} similar alerts held back)", held_back));
+        }
+        for webhook in &self.webhooks {
+            let body = match webhook.kind {
+                WebhookKind::Discord => json!({ "content": text }),
+                WebhookKind::Slack => json!({ "text": text }),
+                WebhookKind::Telegram => json!({ "chat_id": webhook.chat_id, "text": text }),
+            };
+            let request = self.client.post(&webhook.url).json(&body);
+            let kind = webhook.kind;
+            tokio::spawn(async move {
+                if let Err(e) = request.send().await.and_then(|response| response.error_for_status()) {
+                    warn!("Unable to send {:?} alert: {}", kind, e);
+                }
+            });
+        }
+    }
+}
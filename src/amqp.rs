@@ -0,0 +1,93 @@
+use futures::future::{BoxFuture, FutureExt};
+use lapin::options::{BasicPublishOptions, ConfirmSelectOptions};
+use lapin::publisher_confirm::Confirmation;
+use lapin::{BasicProperties, Channel, Connection, ConnectionProperties};
+use log::info;
+use solana_sdk::clock::Slot;
+use tokio::sync::Mutex;
+use tokio::time::{timeout, Duration};
+
+use crate::config::AmqpConfig;
+use crate::sink::ProofSink;
+use crate::BlockProof;
+
+// Delivery mode of messages the broker writes to disk
+const PERSISTENT: u8 = 2;
+
+// Publishes block proofs to an AMQP broker with publisher confirms: a proof is
+// only delivered once the broker confirms it. The connection is made with the
+// first delivery and again whenever the connection or channel is lost.
+pub struct AmqpPublisher {
+    config: AmqpConfig,
+    // Kept open alongside its channel
+    connection: Mutex<Option<(Connection, Channel)>>,
+}
+
+impl AmqpPublisher {
+    pub fn new(config: &AmqpConfig) -> Self {
+        AmqpPublisher { config: config.clone(), connection: Mutex::new(None) }
+    }
+
+    // The open channel, connecting first if there is none
+    async fn channel(&self) -> Result<Channel, lapin::Error> {
+        let mut connection = self.connection.lock().await;
+        if let Some((_, channel)) = connection.as_ref().filter(|(_, channel)| channel.status().connected()) {
+            return Ok(channel.clone());
+        }
+        let properties = ConnectionProperties::default()
+            .with_executor(tokio_executor_trait::Tokio::current())
+            .with_reactor(tokio_reactor_trait::Tokio);
+        let opened = Connection::connect(&self.config.url, properties).await?;
+        let channel = opened.create_channel().await?;
+        channel.confirm_select(ConfirmSelectOptions::default()).await?;
+        info!("Connected to AMQP broker, publishing to exchange {:?}", self.config.exchange);
+        *connection = Some((opened, channel.clone()));
+        Ok(channel)
+    }
+
+    // Publishes one message and waits for the broker to take responsibility for it
+    async fn publish(&self, exchange: &str, routing_key: &str, slot: Slot, json_data: &str) -> Result<(), String> {
+        let channel = self.channel().await.map_err(|e| format!("unable to connect: {}", e))?;
+        let properties = BasicProperties::default()
+            .with_delivery_mode(PERSISTENT)
+            .with_content_type("application/json".into())
+            .with_message_id(slot.to_string().into());
+        // Mandatory, so that a message no queue is bound for is returned rather than dropped
+        let options = BasicPublishOptions { mandatory: true, ..BasicPublishOptions::default() };
+
+        let confirm = channel
+            .basic_publish(exchange, routing_key, options, json_data.as_bytes(), properties)
+            .await
+            .map_err(|e| format!("unable to publish: {}", e))?;
+        match timeout(Duration::from_secs(self.config.confirm_timeout_secs), confirm).await {
+            Err(_) => Err("not confirmed in time".to_string()),
+            Ok(Err(e)) => Err(format!("unable to confirm: {}", e)),
+            Ok(Ok(Confirmation::Ack(None))) => Ok(()),
+            Ok(Ok(Confirmation::Ack(Some(_)))) => Err("no queue is bound for the routing key".to_string()),
+            Ok(Ok(Confirmation::Nack(_))) => Err("rejected by the broker".to_string()),
+            Ok(Ok(Confirmation::NotRequested)) => Err("publisher confirms are off".to_string()),
+        }
+    }
+}
+
+impl ProofSink for AmqpPublisher {
+    fn deliver<'a>(&'a self, block_proof: &'a BlockProof) -> BoxFuture<'a, Result<(), String>> {
+        async move {
+            let json_data = serde_json::to_string(block_proof).expect("Unable to serialize proof");
+            self.publish(&self.config.exchange, &self.config.routing_key, block_proof.slot, &json_data).await
+        }
+        .boxed()
+    }
+
+    fn dead_letter<'a>(&'a self, block_proof: &'a BlockProof, _reason: &'a str) -> BoxFuture<'a, Result<(), String>> {
+        async move {
+            let Some(exchange) = &self.config.dead_letter_exchange else {
+                return Err("no dead-letter exchange".to_string());
+            };
+            let routing_key = self.config.dead_letter_routing_key.as_ref().unwrap_or(&self.config.routing_key);
+            let json_data = serde_json::to_string(block_proof).expect("Unable to serialize proof");
+            self.publish(exchange, routing_key, block_proof.slot, &json_data).await
+        }
+        .boxed()
+    }
+}
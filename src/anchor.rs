@@ -0,0 +1,401 @@
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use sha2::{Digest, Sha256};
+use solana_sdk::bs58;
+use solana_transaction_status::option_serializer::OptionSerializer;
+use solana_transaction_status::{EncodedConfirmedBlock, EncodedTransactionWithStatusMeta};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+
+use crate::config::AnchorProgramConfig;
+use crate::instructions;
+
+// Subset of the Anchor IDL needed to decode instructions and events. Both the
+// legacy format (discriminators derived from names) and the 0.30 format
+// (explicit discriminators) are accepted.
+#[derive(Deserialize)]
+struct Idl {
+    #[serde(default)]
+    instructions: Vec<IdlInstruction>,
+    #[serde(default)]
+    events: Vec<IdlEvent>,
+    #[serde(default)]
+    types: Vec<IdlTypeDef>,
+}
+
+#[derive(Deserialize)]
+struct IdlInstruction {
+    name: String,
+    #[serde(default)]
+    discriminator: Option<Vec<u8>>,
+    #[serde(default)]
+    accounts: Vec<Value>,
+    #[serde(default)]
+    args: Vec<IdlField>,
+}
+
+#[derive(Deserialize)]
+struct IdlEvent {
+    name: String,
+    #[serde(default)]
+    discriminator: Option<Vec<u8>>,
+    // Legacy IDLs list the fields inline; newer ones refer to `types`
+    #[serde(default)]
+    fields: Option<Vec<IdlField>>,
+}
+
+#[derive(Deserialize, Clone)]
+struct IdlField {
+    name: String,
+    #[serde(rename = "type")]
+    ty: Value,
+}
+
+#[derive(Deserialize, Clone)]
+struct IdlTypeDef {
+    name: String,
+    #[serde(rename = "type")]
+    ty: IdlTypeDefBody,
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+enum IdlTypeDefBody {
+    Struct {
+        #[serde(default)]
+        fields: Vec<Value>,
+    },
+    Enum {
+        variants: Vec<IdlVariant>,
+    },
+}
+
+#[derive(Deserialize, Clone)]
+struct IdlVariant {
+    name: String,
+    #[serde(default)]
+    fields: Vec<Value>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RecordKind {
+    Instruction,
+    Event,
+}
+
+// An instruction or event of an Anchor program, decoded with its IDL
+#[derive(Serialize, Deserialize, Clone)]
+pub struct AnchorRecord {
+    pub program_id: String,
+    pub kind: RecordKind,
+    pub name: String,
+    pub data: Value,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub accounts: BTreeMap<String, String>,
+}
+
+// An instruction or event definition keyed by its discriminator. For
+// instructions, `accounts` are the flattened account names in IDL order.
+struct Definition {
+    tag: [u8; 8],
+    name: String,
+    accounts: Vec<String>,
+    fields: Vec<IdlField>,
+}
+
+struct DecodedProgram {
+    instructions: Vec<Definition>,
+    events: Vec<Definition>,
+    types: HashMap<String, IdlTypeDef>,
+}
+
+// Decodes instructions and events of the configured Anchor programs
+pub struct AnchorDecoder {
+    programs: HashMap<String, DecodedProgram>,
+}
+
+fn discriminator(namespace: &str, name: &str) -> [u8; 8] {
+    let digest = Sha256::digest(format!("{}:{}", namespace, name).as_bytes());
+    digest[..8].try_into().unwrap()
+}
+
+fn snake_case(name: &str) -> String {
+    let mut snake = String::new();
+    for (i, c) in name.chars().enumerate() {
+        if c.is_uppercase() {
+            if i > 0 {
+                snake.push('_');
+            }
+            snake.extend(c.to_lowercase());
+        } else {
+            snake.push(c);
+        }
+    }
+    snake
+}
+
+fn account_names(accounts: &[Value], names: &mut Vec<String>) {
+    for account in accounts {
+        match account.get("accounts").and_then(Value::as_array) {
+            Some(nested) => account_names(nested, names),
+            None => names.push(account.get("name").and_then(Value::as_str).unwrap_or_default().to_string()),
+        }
+    }
+}
+
+// Struct fields are named in IDLs, but tuple structs and variants list bare types
+fn fields_of(fields: &[Value]) -> Vec<IdlField> {
+    fields
+        .iter()
+        .enumerate()
+        .map(|(i, field)| match serde_json::from_value::<IdlField>(field.clone()) {
+            Ok(field) => field,
+            Err(_) => IdlField {
+                name: i.to_string(),
+                ty: field.clone(),
+            },
+        })
+        .collect()
+}
+
+fn to_discriminator(bytes: Option<Vec<u8>>) -> Option<[u8; 8]> {
+    bytes.and_then(|bytes| bytes.try_into().ok())
+}
+
+impl DecodedProgram {
+    fn new(idl: Idl) -> Self {
+        let types: HashMap<String, IdlTypeDef> = idl.types.into_iter().map(|ty| (ty.name.clone(), ty)).collect();
+
+        let instructions = idl
+            .instructions
+            .into_iter()
+            .map(|instruction| {
+                let tag = to_discriminator(instruction.discriminator)
+                    .unwrap_or_else(|| discriminator("global", &snake_case(&instruction.name)));
+                let mut accounts = Vec::new();
+                account_names(&instruction.accounts, &mut accounts);
+                Definition {
+                    tag,
+                    name: instruction.name,
+                    accounts,
+                    fields: instruction.args,
+                }
+            })
+            .collect();
+
+        let events = idl
+            .events
+            .into_iter()
+            .map(|event| {
+                let tag = to_discriminator(event.discriminator).unwrap_or_else(|| discriminator("event", &event.name));
+                let fields = event.fields.unwrap_or_else(|| match types.get(&event.name).map(|ty| &ty.ty) {
+                    Some(IdlTypeDefBody::Struct { fields }) => fields_of(fields),
+                    _ => Vec::new(),
+                });
+                Definition {
+                    tag,
+                    name: event.name,
+                    accounts: Vec::new(),
+                    fields,
+                }
+            })
+            .collect();
+
+        DecodedProgram {
+            instructions,
+            events,
+            types,
+        }
+    }
+
+    fn decode_fields(&self, fields: &[IdlField], data: &mut &[u8]) -> Option<Value> {
+        let mut object = Map::new();
+        for field in fields {
+            object.insert(field.name.clone(), self.decode(&field.ty, data)?);
+        }
+        Some(Value::Object(object))
+    }
+
+    // Borsh-decodes one value of IDL type `ty`, advancing `data`
+    fn decode(&self, ty: &Value, data: &mut &[u8]) -> Option<Value> {
+        fn take<'a>(data: &mut &'a [u8], len: usize) -> Option<&'a [u8]> {
+            if data.len() < len {
+                return None;
+            }
+            let (head, tail) = data.split_at(len);
+            *data = tail;
+            Some(head)
+        }
+        fn le<const N: usize>(data: &mut &[u8]) -> Option<[u8; N]> {
+            take(data, N)?.try_into().ok()
+        }
+
+        if let Some(name) = ty.as_str() {
+            return Some(match name {
+                "bool" => json!(le::<1>(data)?[0] != 0),
+                "u8" => json!(le::<1>(data)?[0]),
+                "i8" => json!(le::<1>(data)?[0] as i8),
+                "u16" => json!(u16::from_le_bytes(le(data)?)),
+                "i16" => json!(i16::from_le_bytes(le(data)?)),
+                "u32" => json!(u32::from_le_bytes(le(data)?)),
+                "i32" => json!(i32::from_le_bytes(le(data)?)),
+                "u64" => json!(u64::from_le_bytes(le(data)?)),
+                "i64" => json!(i64::from_le_bytes(le(data)?)),
+                "f32" => json!(f32::from_le_bytes(le(data)?)),
+                "f64" => json!(f64::from_le_bytes(le(data)?)),
+                // Too wide for JSON numbers
+                "u128" => json!(u128::from_le_bytes(le(data)?).to_string()),
+                "i128" => json!(i128::from_le_bytes(le(data)?).to_string()),
+                "publicKey" | "pubkey" => json!(bs58::encode(take(data, 32)?).into_string()),
+                "string" => {
+                    let len = u32::from_le_bytes(le(data)?) as usize;
+                    json!(String::from_utf8_lossy(take(data, len)?))
+                }
+                "bytes" => {
+                    let len = u32::from_le_bytes(le(data)?) as usize;
+                    json!(hex::encode(take(data, len)?))
+                }
+                _ => return None,
+            });
+        }
+
+        let object = ty.as_object()?;
+        if let Some(inner) = object.get("vec") {
+            let len = u32::from_le_bytes(le(data)?) as usize;
+            return (0..len).map(|_| self.decode(inner, data)).collect::<Option<Vec<_>>>().map(Value::Array);
+        }
+        if let Some(inner) = object.get("option").or_else(|| object.get("coption")) {
+            let tag = if object.contains_key("coption") {
+                u32::from_le_bytes(le(data)?)
+            } else {
+                le::<1>(data)?[0] as u32
+            };
+            return if tag == 0 { Some(Value::Null) } else { self.decode(inner, data) };
+        }
+        if let Some(array) = object.get("array").and_then(Value::as_array) {
+            let (inner, len) = (array.first()?, array.get(1)?.as_u64()? as usize);
+            return (0..len).map(|_| self.decode(inner, data)).collect::<Option<Vec<_>>>().map(Value::Array);
+        }
+        if let Some(defined) = object.get("defined") {
+            let name = defined.as_str().or_else(|| defined.get("name").and_then(Value::as_str))?;
+            return match &self.types.get(name)?.ty {
+                IdlTypeDefBody::Struct { fields } => self.decode_fields(&fields_of(fields), data),
+                IdlTypeDefBody::Enum { variants } => {
+                    let variant = variants.get(le::<1>(data)?[0] as usize)?;
+                    if variant.fields.is_empty() {
+                        Some(json!(variant.name))
+                    } else {
+                        let mut object = Map::new();
+                        object.insert(variant.name.clone(), self.decode_fields(&fields_of(&variant.fields), data)?);
+                        Some(Value::Object(object))
+                    }
+                }
+            };
+        }
+        None
+    }
+
+    fn decode_instruction(&self, program_id: &str, accounts: &[String], data: &[u8]) -> Option<AnchorRecord> {
+        let (tag, mut rest) = (data.get(..8)?, data.get(8..)?);
+        let definition = self.instructions.iter().find(|definition| definition.tag == tag)?;
+
+        Some(AnchorRecord {
+            program_id: program_id.to_string(),
+            kind: RecordKind::Instruction,
+            name: definition.name.clone(),
+            data: self.decode_fields(&definition.fields, &mut rest)?,
+            accounts: definition.accounts.iter().cloned().zip(accounts.iter().cloned()).collect(),
+        })
+    }
+
+    fn decode_event(&self, program_id: &str, data: &[u8]) -> Option<AnchorRecord> {
+        let (tag, mut rest) = (data.get(..8)?, data.get(8..)?);
+        let definition = self.events.iter().find(|definition| definition.tag == tag)?;
+
+        Some(AnchorRecord {
+            program_id: program_id.to_string(),
+            kind: RecordKind::Event,
+            name: definition.name.clone(),
+            data: self.decode_fields(&definition.fields, &mut rest)?,
+            accounts: BTreeMap::new(),
+        })
+    }
+}
+
+impl AnchorDecoder {
+    pub fn load(programs: &[AnchorProgramConfig]) -> Self {
+        let programs = programs
+            .iter()
+            .map(|program| {
+                let contents = fs::read_to_string(&program.idl_path)
+                    .unwrap_or_else(|e| panic!("Unable to read IDL {:?}: {}", program.idl_path, e));
+                let idl: Idl = serde_json::from_str(&contents)
+                    .unwrap_or_else(|e| panic!("Unable to parse IDL {:?}: {}", program.idl_path, e));
+                (program.program_id.clone(), DecodedProgram::new(idl))
+            })
+            .collect();
+
+        AnchorDecoder { programs }
+    }
+
+    // Decoded records of one transaction: instructions in execution order,
+    // then events emitted through `Program data:` log lines
+    fn decode_transaction(&self, transaction_with_meta: &EncodedTransactionWithStatusMeta) -> Option<(String, Vec<AnchorRecord>)> {
+        let transaction = instructions::decode(transaction_with_meta)?;
+        let mut records = Vec::new();
+        // Events emitted with `emit_cpi!` are self-invocations carrying this tag
+        let event_cpi_tag = discriminator("anchor", "event");
+
+        for instruction in &transaction.instructions {
+            let Some(program) = self.programs.get(&instruction.program_id) else {
+                continue;
+            };
+            let record = match instruction.data.strip_prefix(&event_cpi_tag) {
+                Some(event) => program.decode_event(&instruction.program_id, event),
+                None => program.decode_instruction(&instruction.program_id, &instruction.accounts, &instruction.data),
+            };
+            records.extend(record);
+        }
+
+        let logs = match transaction_with_meta.meta.as_ref().map(|meta| &meta.log_messages) {
+            Some(OptionSerializer::Some(logs)) => logs.as_slice(),
+            _ => &[],
+        };
+        // Attribute each log line to the program currently executing
+        let mut invoked: Vec<&str> = Vec::new();
+        for line in logs {
+            let Some(rest) = line.strip_prefix("Program ") else {
+                continue;
+            };
+            if let Some(data) = rest.strip_prefix("data: ") {
+                let (Some(program_id), Ok(data)) =
+                    (invoked.last(), base64::engine::general_purpose::STANDARD.decode(data))
+                else {
+                    continue;
+                };
+                if let Some(program) = self.programs.get(*program_id) {
+                    records.extend(program.decode_event(program_id, &data));
+                }
+            } else if let Some((program_id, status)) = rest.split_once(' ') {
+                if status.starts_with("invoke [") {
+                    invoked.push(program_id);
+                } else if status == "success" || status.starts_with("failed") {
+                    invoked.pop();
+                }
+            }
+        }
+
+        (!records.is_empty()).then_some((transaction.signature, records))
+    }
+
+    // Records per transaction signature, for the transactions with any
+    pub fn decode_block(&self, block: &EncodedConfirmedBlock) -> Vec<(String, Vec<AnchorRecord>)> {
+        block
+            .transactions
+            .iter()
+            .filter_map(|transaction_with_meta| self.decode_transaction(transaction_with_meta))
+            .collect()
+    }
+}
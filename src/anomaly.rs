@@ -0,0 +1,200 @@
+use serde::{Deserialize, Serialize};
+use solana_sdk::clock::Slot;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum AnomalyKind {
+    // A transaction signature in more than one block, or twice in one block.
+    // Signatures are unique on chain, so either the data source or the
+    // listener is wrong.
+    DuplicateSignature,
+}
+
+// Append-only record of what the sanity checks on the chain data found, kept
+// next to the proofs as `anomalies.jsonl`
+#[derive(Serialize, Deserialize)]
+pub struct Anomaly {
+    pub kind: AnomalyKind,
+    pub signature: String,
+    // Slots of the blocks the signature was seen in, earliest first
+    pub slots: Vec<Slot>,
+    pub detected_at: u64,
+}
+
+impl Anomaly {
+    fn duplicate_signature(signature: &str, slots: Vec<Slot>) -> Self {
+        Anomaly {
+            kind: AnomalyKind::DuplicateSignature,
+            signature: signature.to_string(),
+            slots,
+            detected_at: SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs(),
+        }
+    }
+}
+
+pub fn append(proofs_dir: &Path, anomaly: &Anomaly) {
+    let mut line = serde_json::to_string(anomaly).expect("Unable to serialize anomaly");
+    line.push('\n');
+
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(proofs_dir.join("anomalies.jsonl"))
+        .expect("Unable to open anomaly log");
+    file.write_all(line.as_bytes()).expect("Unable to write anomaly log");
+}
+
+// Signatures of the blocks of `window_slots` slots before the latest one
+// checked, and the slot each was seen in
+pub struct SignatureWindow {
+    window_slots: u64,
+    seen: HashMap<String, Slot>,
+    blocks: VecDeque<(Slot, Vec<String>)>,
+}
+
+impl SignatureWindow {
+    pub fn new(window_slots: u64) -> Self {
+        SignatureWindow { window_slots, seen: HashMap::new(), blocks: VecDeque::new() }
+    }
+
+    // Adds the signatures of the block of `slot`, returning those seen twice in
+    // it or already seen in another block. A block checked again, e.g. after
+    // the pipeline restarts, matches only itself.
+    pub fn check(&mut self, slot: Slot, signatures: Vec<String>) -> Vec<Anomaly> {
+        let mut anomalies = Vec::new();
+        let mut in_block = HashSet::new();
+        for signature in &signatures {
+            if !in_block.insert(signature) {
+                anomalies.push(Anomaly::duplicate_signature(signature, vec![slot, slot]));
+            } else if let Some(&first) = self.seen.get(signature).filter(|first| **first != slot) {
+                anomalies.push(Anomaly::duplicate_signature(signature, vec![first.min(slot), first.max(slot)]));
+            }
+        }
+        if self.blocks.iter().any(|(seen_slot, _)| *seen_slot == slot) {
+            return anomalies;
+        }
+
+        for signature in &signatures {
+            self.seen.entry(signature.clone()).or_insert(slot);
+        }
+        self.blocks.push_back((slot, signatures));
+        let latest = self.blocks.iter().map(|(seen_slot, _)| *seen_slot).max().unwrap_or(slot);
+        while let Some((oldest, _)) = self.blocks.front().filter(|(oldest, _)| oldest + self.window_slots < latest) {
+            let oldest = *oldest;
+            let (_, expired) = self.blocks.pop_front().unwrap();
+            for signature in expired {
+                if self.seen.get(&signature) == Some(&oldest) {
+                    self.seen.remove(&signature);
+                }
+            }
+        }
+        anomalies
+    }
+}
+
+// Every signature found in more than one block across `blocks`, or twice in
+// one block, with all the slots it was seen in
+pub fn scan(blocks: impl Iterator<Item = (Slot, Vec<String>)>) -> Vec<Anomaly> {
+    let mut seen: HashMap<String, Vec<Slot>> = HashMap::new();
+    for (slot, signatures) in blocks {
+        for signature in signatures {
+            seen.entry(signature).or_default().push(slot);
+        }
+    }
+    let mut anomalies: Vec<Anomaly> = seen
+        .into_iter()
+        .filter(|(_, slots)| slots.len() > 1)
+        .map(|(signature, mut slots)| {
+            slots.sort();
+            Anomaly::duplicate_signature(&signature, slots)
+        })
+        .collect();
+    anomalies.sort_by(|a, b| a.slots.cmp(&b.slots).then_with(|| a.signature.cmp(&b.signature)));
+    anomalies
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn signatures(signatures: &[&str]) -> Vec<String> {
+        signatures.iter().map(|signature| signature.to_string()).collect()
+    }
+
+    fn found(anomalies: &[Anomaly]) -> Vec<(&str, Vec<Slot>)> {
+        anomalies.iter().map(|anomaly| (anomaly.signature.as_str(), anomaly.slots.clone())).collect()
+    }
+
+    #[test]
+    fn signatures_seen_twice_are_anomalies() {
+        let mut window = SignatureWindow::new(100);
+        assert!(window.check(10, signatures(&["a", "b"])).is_empty());
+        assert!(window.check(11, signatures(&["c"])).is_empty());
+
+        let anomalies = window.check(12, signatures(&["b", "d", "d"]));
+        assert_eq!(found(&anomalies), vec![("b", vec![10, 12]), ("d", vec![12, 12])]);
+        assert!(anomalies.iter().all(|anomaly| anomaly.kind == AnomalyKind::DuplicateSignature));
+
+        // Blocks may be checked out of order; slots are still earliest first
+        assert_eq!(found(&window.check(5, signatures(&["c"]))), vec![("c", vec![5, 11])]);
+    }
+
+    #[test]
+    fn blocks_checked_again_match_only_themselves() {
+        let mut window = SignatureWindow::new(100);
+        assert!(window.check(10, signatures(&["a", "b"])).is_empty());
+        assert!(window.check(10, signatures(&["a", "b"])).is_empty());
+        // A duplicate within the block is still found
+        assert_eq!(found(&window.check(10, signatures(&["a", "a"]))), vec![("a", vec![10, 10])]);
+        assert_eq!(found(&window.check(11, signatures(&["a"]))), vec![("a", vec![10, 11])]);
+    }
+
+    #[test]
+    fn signatures_expire_with_their_block() {
+        let mut window = SignatureWindow::new(10);
+        window.check(100, signatures(&["a"]));
+        window.check(105, signatures(&["b"]));
+        assert_eq!(found(&window.check(110, signatures(&["a"]))), vec![("a", vec![100, 110])]);
+
+        // Slot 100 leaves the window once a block more than 10 slots after it
+        // is checked, and the signatures first seen in it go with it
+        assert!(window.check(111, signatures(&["c"])).is_empty());
+        assert_eq!(found(&window.check(112, signatures(&["a", "b"]))), vec![("b", vec![105, 112])]);
+        assert!(window.check(125, signatures(&["d"])).is_empty());
+        assert!(window.check(126, signatures(&["b"])).is_empty());
+    }
+
+    #[test]
+    fn scans_find_every_slot_of_a_duplicate() {
+        let blocks = vec![
+            (10, signatures(&["a", "b"])),
+            (12, signatures(&["c", "c"])),
+            (11, signatures(&["a"])),
+            (13, signatures(&["a", "d"])),
+        ];
+        assert_eq!(found(&scan(blocks.into_iter())), vec![("a", vec![10, 11, 13]), ("c", vec![12, 12])]);
+        assert!(scan(std::iter::empty()).is_empty());
+    }
+
+    #[test]
+    fn anomalies_are_appended_to_the_log() {
+        let dir = std::env::temp_dir().join(format!("solana-listener-anomaly-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let mut window = SignatureWindow::new(100);
+        window.check(10, signatures(&["a"]));
+        for anomaly in window.check(11, signatures(&["a", "b", "b"])) {
+            append(&dir, &anomaly);
+        }
+
+        let log = std::fs::read_to_string(dir.join("anomalies.jsonl")).unwrap();
+        let logged: Vec<Anomaly> = log.lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+        assert_eq!(found(&logged), vec![("a", vec![10, 11]), ("b", vec![11, 11])]);
+        assert!(log.lines().all(|line| line.contains("\"kind\":\"duplicate_signature\"")));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
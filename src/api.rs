@@ -0,0 +1,397 @@
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use hyper::header::{
+    HeaderMap, ACCEPT_ENCODING, CONTENT_DISPOSITION, CONTENT_ENCODING, CONTENT_RANGE, CONTENT_TYPE, ETAG,
+    IF_NONE_MATCH, LINK, RANGE, VARY,
+};
+use hyper::server::conn::AddrStream;
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Method, Request, Response, Server, StatusCode};
+use log::{error, info};
+use serde::Serialize;
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use solana_sdk::clock::Slot;
+use std::convert::Infallible;
+use std::fs;
+use std::io::Write;
+use std::net::SocketAddr;
+use std::ops::RangeInclusive;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tower::ServiceBuilder;
+
+use crate::bundle;
+use crate::config::ApiConfig;
+use crate::graphql::{self, ProofSchema};
+use crate::index;
+use crate::keyring;
+use crate::ratelimit::{RateLimitLayer, RateLimiter};
+use crate::serialization;
+use crate::{list_proof_slots, load_proof, locate_transaction, proof_file_name, BlockProof};
+
+const EXPLORER: &str = include_str!("explorer.html");
+
+// Proofs listed per request unless `limit` asks for fewer
+const MAX_LIST: usize = 100;
+
+// Smallest response worth compressing
+const MIN_GZIP_BYTES: usize = 1024;
+
+// Largest GraphQL request body accepted
+const MAX_QUERY_BYTES: usize = 64 * 1024;
+
+// Files of the proofs directory that can be downloaded
+const ARTIFACT_PREFIXES: [&str; 3] = ["block_proof_", "witness_", "epoch_summary_"];
+
+// A proof as listed, without its circuit proofs and inclusion paths
+#[derive(Serialize)]
+struct ProofListing<'a> {
+    slot: Slot,
+    block_hash: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    leader: Option<&'a str>,
+    transactions: usize,
+    new_root: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    proved_at: Option<i64>,
+    signatures: usize,
+}
+
+impl<'a> ProofListing<'a> {
+    fn new(block_proof: &'a BlockProof) -> Self {
+        ProofListing {
+            slot: block_proof.slot,
+            block_hash: &block_proof.block_hash,
+            leader: block_proof.leader.as_deref(),
+            transactions: block_proof.transactions.len(),
+            new_root: &block_proof.new_root,
+            proved_at: block_proof.latency.as_ref().map(|latency| latency.proved_at),
+            signatures: block_proof.signatures.len(),
+        }
+    }
+}
+
+fn query_param<'a>(request: &'a Request<Body>, name: &str) -> Option<&'a str> {
+    request.uri().query()?.split('&').find_map(|pair| pair.strip_prefix(name)?.strip_prefix('='))
+}
+
+fn json_response(status: StatusCode, body: &impl Serialize) -> Response<Body> {
+    let json_data = serde_json::to_vec(body).expect("Unable to serialize response");
+    Response::builder()
+        .status(status)
+        .header(CONTENT_TYPE, "application/json")
+        .body(Body::from(json_data))
+        .unwrap()
+}
+
+fn not_found(what: String) -> Response<Body> {
+    json_response(StatusCode::NOT_FOUND, &json!({ "error": what }))
+}
+
+fn bad_request(what: String) -> Response<Body> {
+    json_response(StatusCode::BAD_REQUEST, &json!({ "error": what }))
+}
+
+// Slots asked for by a `Range: slots=<first>-[<last>]` header. Ranges in
+// other units are ignored, as for any server that doesn't support them.
+fn slot_range(request: &Request<Body>) -> Result<Option<RangeInclusive<Slot>>, String> {
+    let Some(range) = request.headers().get(RANGE).and_then(|range| range.to_str().ok()) else {
+        return Ok(None);
+    };
+    let Some(range) = range.strip_prefix("slots=") else {
+        return Ok(None);
+    };
+    let invalid = || format!("{:?} is not a slot range such as slots=1000-2000", range);
+    let (first, last) = range.split_once('-').ok_or_else(invalid)?;
+    let first = first.trim().parse::<Slot>().map_err(|_| invalid())?;
+    let last = match last.trim() {
+        "" => Slot::MAX,
+        last => last.parse::<Slot>().map_err(|_| invalid())?,
+    };
+    if first > last {
+        return Err(invalid());
+    }
+    Ok(Some(first..=last))
+}
+
+// One page of proofs, newest first unless `order=asc` or a slot range asks
+// for oldest first. The page continues from the slot given as `cursor`, and
+// a full page links the next one, with the same range, in its `Link` header.
+fn list_proofs(proofs_dir: &Path, request: &Request<Body>) -> Response<Body> {
+    let limit = match query_param(request, "limit").map(str::parse::<usize>) {
+        None => MAX_LIST,
+        Some(Ok(limit)) => limit.clamp(1, MAX_LIST),
+        Some(Err(_)) => return bad_request("limit is not a number".to_string()),
+    };
+    let cursor = match query_param(request, "cursor").map(str::parse::<Slot>) {
+        None => None,
+        Some(Ok(cursor)) => Some(cursor),
+        Some(Err(_)) => return bad_request("cursor is not a slot".to_string()),
+    };
+    let range = match slot_range(request) {
+        Ok(range) => range,
+        Err(e) => return json_response(StatusCode::RANGE_NOT_SATISFIABLE, &json!({ "error": e })),
+    };
+    let ascending = match query_param(request, "order") {
+        None => range.is_some(),
+        Some("asc") => true,
+        Some("desc") => false,
+        Some(order) => return bad_request(format!("order {:?} is not asc or desc", order)),
+    };
+
+    let slots = list_proof_slots(proofs_dir);
+    let in_range = |slot: &&Slot| range.as_ref().is_none_or(|range| range.contains(*slot));
+    let past_cursor = |slot: &&Slot| match (cursor, ascending) {
+        (None, _) => true,
+        (Some(cursor), true) => **slot > cursor,
+        (Some(cursor), false) => **slot < cursor,
+    };
+    // One more than fits the page, to tell whether another follows
+    let mut page: Vec<Slot> = match ascending {
+        true => slots.iter().filter(in_range).filter(past_cursor).take(limit + 1).copied().collect(),
+        false => slots.iter().rev().filter(in_range).filter(past_cursor).take(limit + 1).copied().collect(),
+    };
+    let more = page.len() > limit;
+    page.truncate(limit);
+
+    let proofs: Vec<BlockProof> =
+        page.iter().map(|slot| load_proof(&proofs_dir.join(proof_file_name(*slot)))).collect();
+    let listings: Vec<_> = proofs.iter().map(ProofListing::new).collect();
+    let mut response = json_response(StatusCode::OK, &listings);
+    // A range is answered as partial content naming the slots returned
+    if let (Some(_), Some(first), Some(last)) = (&range, page.iter().min(), page.iter().max()) {
+        *response.status_mut() = StatusCode::PARTIAL_CONTENT;
+        let content_range = format!("slots {}-{}/*", first, last);
+        response.headers_mut().insert(CONTENT_RANGE, content_range.parse().unwrap());
+    }
+    if let Some(next) = page.last().filter(|_| more) {
+        let order = if ascending { "asc" } else { "desc" };
+        let link = format!("</api/proofs?order={}&limit={}&cursor={}>; rel=\"next\"", order, limit, next);
+        response.headers_mut().insert(LINK, link.parse().unwrap());
+    }
+    response
+}
+
+fn get_proof(proofs_dir: &Path, slot: &str) -> Response<Body> {
+    let Ok(slot) = slot.parse::<Slot>() else {
+        return bad_request(format!("{:?} is not a slot", slot));
+    };
+    let path = proofs_dir.join(proof_file_name(slot));
+    if !path.exists() {
+        // The slot index tells a slot with no block apart from one the
+        // listener failed on
+        let mut body = json!({ "error": format!("no proof of slot {}", slot) });
+        if let Some(entry) = index::lookup(proofs_dir, slot) {
+            body["status"] = json!(entry.status);
+            body["reason"] = json!(entry.reason);
+        }
+        return json_response(StatusCode::NOT_FOUND, &body);
+    }
+    json_response(StatusCode::OK, &load_proof(&path))
+}
+
+// The proved slot a query names, or the slot of the proof holding a
+// transaction signature
+fn search(proofs_dir: &Path, request: &Request<Body>) -> Response<Body> {
+    let Some(query) = query_param(request, "q").map(str::trim).filter(|query| !query.is_empty()) else {
+        return bad_request("nothing to search for".to_string());
+    };
+    if let Ok(slot) = query.parse::<Slot>() {
+        return match proofs_dir.join(proof_file_name(slot)).exists() {
+            true => json_response(StatusCode::OK, &json!({ "slot": slot })),
+            false => not_found(format!("no proof of slot {}", slot)),
+        };
+    }
+    match locate_transaction(proofs_dir, query) {
+        Ok((slot, transaction)) => {
+            json_response(StatusCode::OK, &json!({ "slot": slot, "transaction": transaction }))
+        }
+        Err(scanned) => not_found(format!("signature not found ({} block proofs scanned)", scanned)),
+    }
+}
+
+// A verifying key of the keyring by fingerprint, base64 in the uncompressed
+// encoding the fingerprint is taken over
+fn get_key(proofs_dir: &Path, fingerprint: &str) -> Response<Body> {
+    let fingerprint = fingerprint.to_ascii_lowercase();
+    let named = fingerprint.len() == 64 && fingerprint.bytes().all(|byte| byte.is_ascii_hexdigit());
+    let contents = named.then(|| fs::read(keyring::keyring_dir(proofs_dir).join(format!("{}.vk", fingerprint))).ok());
+    let Some(contents) = contents.flatten() else {
+        return not_found(format!("no verifying key {:?}", fingerprint));
+    };
+    match serialization::vk_from_bytes(&contents) {
+        Ok(vk) => json_response(
+            StatusCode::OK,
+            &json!({
+                "fingerprint": fingerprint,
+                "public_inputs": vk.ic.len() - 1,
+                "verifying_key": serialization::vk_to_base64(&vk),
+            }),
+        ),
+        Err(e) => {
+            error!("Verifying key {} in the keyring is unreadable: {}", fingerprint, e);
+            json_response(StatusCode::INTERNAL_SERVER_ERROR, &json!({ "error": e.to_string() }))
+        }
+    }
+}
+
+fn download(proofs_dir: &Path, name: &str) -> Response<Body> {
+    let artifact = ARTIFACT_PREFIXES.iter().any(|prefix| name.starts_with(prefix))
+        && name.ends_with(".json")
+        && !name.contains(['/', '\\'])
+        && !name.contains("..");
+    let contents = artifact.then(|| fs::read(proofs_dir.join(name)).ok()).flatten();
+    let Some(contents) = contents else {
+        return not_found(format!("no artifact named {:?}", name));
+    };
+    Response::builder()
+        .header(CONTENT_TYPE, "application/json")
+        .header(CONTENT_DISPOSITION, format!("attachment; filename=\"{}\"", name))
+        .body(Body::from(contents))
+        .unwrap()
+}
+
+// Runs a GraphQL request posted as JSON, which is answered with status 200
+// even when it has errors, as GraphQL clients expect
+async fn graphql(schema: &ProofSchema, request: Request<Body>) -> Response<Body> {
+    let body = match hyper::body::to_bytes(request.into_body()).await {
+        Ok(body) if body.len() <= MAX_QUERY_BYTES => body,
+        Ok(_) => return bad_request(format!("query is over {} bytes", MAX_QUERY_BYTES)),
+        Err(e) => return bad_request(format!("unable to read query: {}", e)),
+    };
+    let query: async_graphql::Request = match serde_json::from_slice(&body) {
+        Ok(query) => query,
+        Err(e) => return bad_request(format!("invalid GraphQL request: {}", e)),
+    };
+    json_response(StatusCode::OK, &schema.execute(query).await)
+}
+
+fn etag(body: &[u8]) -> String {
+    // Weak, so that it stays the same whether or not the body is compressed
+    format!("W/\"{}\"", hex::encode(&Sha256::digest(body)[..16]))
+}
+
+// Whether `If-None-Match` lists the tag, compared weakly
+fn none_match(headers: &HeaderMap, etag: &str) -> bool {
+    let Some(tags) = headers.get(IF_NONE_MATCH).and_then(|tags| tags.to_str().ok()) else {
+        return false;
+    };
+    let opaque = |tag: &str| tag.trim().trim_start_matches("W/").to_string();
+    tags.split(',').any(|tag| tag.trim() == "*" || opaque(tag) == opaque(etag))
+}
+
+fn accepts_gzip(headers: &HeaderMap) -> bool {
+    let Some(encodings) = headers.get(ACCEPT_ENCODING).and_then(|encodings| encodings.to_str().ok()) else {
+        return false;
+    };
+    encodings.split(',').any(|encoding| {
+        let mut parts = encoding.split(';').map(str::trim);
+        parts.next() == Some("gzip") && parts.all(|param| param.replace(' ', "") != "q=0")
+    })
+}
+
+// Tags a successful response with its ETag, answering 304 Not Modified when
+// the client already has it, and compresses it for clients that accept gzip
+async fn finish(headers: &HeaderMap, response: Response<Body>) -> Response<Body> {
+    if !matches!(response.status(), StatusCode::OK | StatusCode::PARTIAL_CONTENT) {
+        return response;
+    }
+    let (mut parts, body) = response.into_parts();
+    let body = hyper::body::to_bytes(body).await.expect("Unable to buffer response");
+    let etag = etag(&body);
+    parts.headers.insert(VARY, "Accept-Encoding".parse().unwrap());
+    parts.headers.insert(ETAG, etag.parse().unwrap());
+    if none_match(headers, &etag) {
+        parts.status = StatusCode::NOT_MODIFIED;
+        parts.headers.remove(CONTENT_TYPE);
+        return Response::from_parts(parts, Body::empty());
+    }
+    if body.len() < MIN_GZIP_BYTES || !accepts_gzip(headers) {
+        return Response::from_parts(parts, Body::from(body));
+    }
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(&body).expect("Unable to compress response");
+    parts.headers.insert(CONTENT_ENCODING, "gzip".parse().unwrap());
+    Response::from_parts(parts, Body::from(encoder.finish().expect("Unable to compress response")))
+}
+
+// Every proof from `from` to `to`, both included and either optional, in one
+// download. Streamed rather than buffered, so it bypasses `finish`.
+fn bundle(proofs_dir: &Path, request: &Request<Body>) -> Response<Body> {
+    let mut bounds = [("from", 0), ("to", Slot::MAX)];
+    for (name, bound) in &mut bounds {
+        match query_param(request, name).map(str::parse::<Slot>) {
+            None => {}
+            Some(Ok(slot)) => *bound = slot,
+            Some(Err(_)) => return bad_request(format!("{} is not a slot", name)),
+        }
+    }
+    let [(_, from), (_, to)] = bounds;
+    let slots: Vec<Slot> = list_proof_slots(proofs_dir).into_iter().filter(|slot| (from..=to).contains(slot)).collect();
+    let (Some(first), Some(last)) = (slots.first(), slots.last()) else {
+        return not_found(format!("no proofs from slot {} to {}", from, to));
+    };
+    let file_name = format!("block_proofs_{}_{}.tar.zst", first, last);
+    Response::builder()
+        .header(CONTENT_TYPE, "application/zstd")
+        .header(CONTENT_DISPOSITION, format!("attachment; filename=\"{}\"", file_name))
+        .body(bundle::stream(proofs_dir.to_path_buf(), slots))
+        .unwrap()
+}
+
+struct State {
+    proofs_dir: PathBuf,
+    schema: ProofSchema,
+}
+
+async fn handle(request: Request<Body>, state: Arc<State>) -> Result<Response<Body>, Infallible> {
+    let proofs_dir = &state.proofs_dir;
+    if request.method() == Method::POST && request.uri().path() == "/graphql" {
+        return Ok(graphql(&state.schema, request).await);
+    }
+    if request.method() != Method::GET {
+        return Ok(Response::builder().status(StatusCode::METHOD_NOT_ALLOWED).body(Body::empty()).unwrap());
+    }
+    let path = request.uri().path().to_string();
+    if path == "/bundle" {
+        return Ok(bundle(proofs_dir, &request));
+    }
+    let response = match path.as_str() {
+        "/" => Response::builder().header(CONTENT_TYPE, "text/html; charset=utf-8").body(Body::from(EXPLORER)).unwrap(),
+        "/api/proofs" => list_proofs(proofs_dir, &request),
+        "/api/search" => search(proofs_dir, &request),
+        path => match (path.strip_prefix("/api/proofs/"), path.strip_prefix("/files/")) {
+            (Some(slot), _) => get_proof(proofs_dir, slot),
+            (_, Some(name)) => download(proofs_dir, name),
+            _ if path.starts_with("/api/keys/") => get_key(proofs_dir, &path["/api/keys/".len()..]),
+            _ => not_found(format!("no route for {}", path)),
+        },
+    };
+    Ok(finish(request.headers(), response).await)
+}
+
+// Serves the proof archive in `proofs_dir` in the background: the explorer at
+// `/`, the JSON API under `/api`, GraphQL at `POST /graphql`, artifact
+// downloads under `/files` and proof bundles at `/bundle`
+pub fn spawn(config: &ApiConfig, proofs_dir: PathBuf) {
+    let addr: SocketAddr = config.listen_addr.parse().expect("Invalid API listen address");
+    let state = Arc::new(State { schema: graphql::schema(proofs_dir.clone()), proofs_dir });
+    let limiter = config.rate_limit.as_ref().map(|rate_limit| Arc::new(RateLimiter::new(rate_limit)));
+    let make_service = make_service_fn(move |connection: &AddrStream| {
+        let client = connection.remote_addr().ip();
+        let state = state.clone();
+        let service = ServiceBuilder::new()
+            .option_layer(limiter.clone().map(|limiter| RateLimitLayer::new(limiter, client)))
+            .service(service_fn(move |request| handle(request, state.clone())));
+        async move { Ok::<_, Infallible>(service) }
+    });
+    let server = Server::try_bind(&addr).expect("Unable to bind API endpoint").serve(make_service);
+    info!("Serving the proof explorer on http://{}", addr);
+
+    tokio::spawn(async move {
+        if let Err(e) = server.await {
+            error!("API endpoint stopped: {:?}", e);
+        }
+    });
+}
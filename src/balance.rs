@@ -0,0 +1,113 @@
+use serde::{Deserialize, Serialize};
+use solana_transaction_status::EncodedConfirmedBlock;
+
+use crate::instructions;
+use crate::SumProof;
+
+// Lamport balance of a watched account before and after one transaction.
+// Failed transactions are included, since they still charge the fee payer.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct BalanceChange {
+    pub signature: String,
+    pub account: String,
+    pub pre: u64,
+    pub post: u64,
+}
+
+// Balance of a watched account before the first and after the last transaction
+// of the block that touched it
+#[derive(Serialize, Deserialize, Clone)]
+pub struct AccountDelta {
+    pub account: String,
+    pub pre: u64,
+    pub post: u64,
+    pub delta: i64,
+}
+
+// Balance deltas of the watched accounts in a block. Sum circuits take unsigned
+// values, so increases and decreases are proved as two totals whose difference
+// is the summed delta; the changes are kept when proved so consumers can
+// recompute both values commitments.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct BalanceSummary {
+    pub deltas: Vec<AccountDelta>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub changes: Vec<BalanceChange>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub credit_proof: Option<SumProof>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub debit_proof: Option<SumProof>,
+}
+
+// Balance changes of `accounts` in block order, skipping transactions that left them unchanged
+pub fn changes(block: &EncodedConfirmedBlock, accounts: &[String]) -> Vec<BalanceChange> {
+    let mut changes = Vec::new();
+
+    for transaction_with_meta in &block.transactions {
+        let (Some(transaction), Some(meta)) =
+            (instructions::decode(transaction_with_meta), transaction_with_meta.meta.as_ref())
+        else {
+            continue;
+        };
+
+        for (index, account) in transaction.account_keys.iter().enumerate() {
+            if !accounts.contains(account) {
+                continue;
+            }
+            let (Some(&pre), Some(&post)) = (meta.pre_balances.get(index), meta.post_balances.get(index)) else {
+                continue;
+            };
+            if pre != post {
+                changes.push(BalanceChange {
+                    signature: transaction.signature.clone(),
+                    account: account.clone(),
+                    pre,
+                    post,
+                });
+            }
+        }
+    }
+
+    changes
+}
+
+// Balance increases with the signature of the transaction that made them
+pub fn credits(changes: &[BalanceChange]) -> impl Iterator<Item = (&str, u64)> {
+    changes
+        .iter()
+        .filter(|change| change.post > change.pre)
+        .map(|change| (change.signature.as_str(), change.post - change.pre))
+}
+
+// Balance decreases, likewise
+pub fn debits(changes: &[BalanceChange]) -> impl Iterator<Item = (&str, u64)> {
+    changes
+        .iter()
+        .filter(|change| change.post < change.pre)
+        .map(|change| (change.signature.as_str(), change.pre - change.post))
+}
+
+pub fn summarize(changes: Vec<BalanceChange>, keep_changes: bool) -> BalanceSummary {
+    let mut deltas: Vec<AccountDelta> = Vec::new();
+    for change in &changes {
+        match deltas.iter_mut().find(|delta| delta.account == change.account) {
+            Some(delta) => delta.post = change.post,
+            None => deltas.push(AccountDelta {
+                account: change.account.clone(),
+                pre: change.pre,
+                post: change.post,
+                delta: 0,
+            }),
+        }
+    }
+    for delta in &mut deltas {
+        delta.delta = delta.post as i64 - delta.pre as i64;
+    }
+
+    BalanceSummary {
+        deltas,
+        changes: if keep_changes { changes } else { Vec::new() },
+        credit_proof: None,
+        debit_proof: None,
+    }
+}
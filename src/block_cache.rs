@@ -0,0 +1,57 @@
+use solana_sdk::clock::Slot;
+use solana_transaction_status::EncodedConfirmedBlock;
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+// EncodedConfirmedBlock is not Clone, though all of its fields are
+fn copy(block: &EncodedConfirmedBlock) -> EncodedConfirmedBlock {
+    EncodedConfirmedBlock {
+        previous_blockhash: block.previous_blockhash.clone(),
+        blockhash: block.blockhash.clone(),
+        parent_slot: block.parent_slot,
+        transactions: block.transactions.clone(),
+        rewards: block.rewards.clone(),
+        block_time: block.block_time,
+        block_height: block.block_height,
+    }
+}
+
+struct Entry {
+    block: EncodedConfirmedBlock,
+    // Profiles that have yet to take the block
+    remaining: usize,
+}
+
+// Blocks fetched by one proving profile, kept for the others so each block
+// is only requested from the endpoint once. A block is dropped once every
+// profile has taken it or, when a profile falls behind, once `capacity`
+// later blocks are held; that profile then fetches it itself.
+pub struct BlockCache {
+    profiles: usize,
+    capacity: usize,
+    blocks: Mutex<BTreeMap<Slot, Entry>>,
+}
+
+impl BlockCache {
+    pub fn new(profiles: usize, capacity: usize) -> Self {
+        BlockCache { profiles, capacity: capacity.max(1), blocks: Mutex::new(BTreeMap::new()) }
+    }
+
+    pub fn insert(&self, slot: Slot, block: &EncodedConfirmedBlock) {
+        let mut blocks = self.blocks.lock().unwrap();
+        blocks.insert(slot, Entry { block: copy(block), remaining: self.profiles - 1 });
+        while blocks.len() > self.capacity {
+            blocks.pop_first();
+        }
+    }
+
+    pub fn take(&self, slot: Slot) -> Option<EncodedConfirmedBlock> {
+        let mut blocks = self.blocks.lock().unwrap();
+        let entry = blocks.get_mut(&slot)?;
+        entry.remaining -= 1;
+        match entry.remaining {
+            0 => blocks.remove(&slot).map(|entry| entry.block),
+            _ => Some(copy(&entry.block)),
+        }
+    }
+}
@@ -0,0 +1,102 @@
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+// Filter bits per transaction and probes per lookup, for a false positive
+// rate of about 1%
+const BITS_PER_ITEM: usize = 10;
+const HASHES: u32 = 7;
+
+// Bloom filter over a block's transaction signatures, letting consumers skip
+// block proofs that certainly do not contain a transaction. A hit still has to
+// be confirmed against the proof's transaction list.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct BloomFilter {
+    #[serde(with = "hex_bytes")]
+    bits: Vec<u8>,
+    hashes: u32,
+}
+
+// Bit positions probed for `item`, by double hashing one SHA-256 digest
+fn probes(item: &str, hashes: u32, bit_count: usize) -> impl Iterator<Item = usize> {
+    let digest = Sha256::digest(item.as_bytes());
+    let h1 = u64::from_le_bytes(digest[..8].try_into().unwrap());
+    let h2 = u64::from_le_bytes(digest[8..16].try_into().unwrap());
+    (0..hashes as u64).map(move |i| (h1.wrapping_add(i.wrapping_mul(h2)) % bit_count as u64) as usize)
+}
+
+impl BloomFilter {
+    pub fn new(items: &[String]) -> Self {
+        let mut filter = BloomFilter {
+            bits: vec![0u8; (items.len() * BITS_PER_ITEM).div_ceil(8).max(1)],
+            hashes: HASHES,
+        };
+        for item in items {
+            for bit in probes(item, filter.hashes, filter.bits.len() * 8) {
+                filter.bits[bit / 8] |= 1 << (bit % 8);
+            }
+        }
+        filter
+    }
+
+    pub fn might_contain(&self, item: &str) -> bool {
+        if self.bits.is_empty() {
+            return false;
+        }
+        probes(item, self.hashes, self.bits.len() * 8).all(|bit| self.bits[bit / 8] & (1 << (bit % 8)) != 0)
+    }
+}
+
+mod hex_bytes {
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&hex::encode(bytes))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        let data = String::deserialize(deserializer)?;
+        hex::decode(data).map_err(D::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn signatures(count: usize) -> Vec<String> {
+        (0..count).map(|index| format!("signature {}", index)).collect()
+    }
+
+    #[test]
+    fn inserted_signatures_hit() {
+        let inserted = signatures(500);
+        let filter = BloomFilter::new(&inserted);
+        assert!(inserted.iter().all(|signature| filter.might_contain(signature)));
+
+        // Sized for about 1% false positives
+        let false_positives = (0..1000).filter(|index| filter.might_contain(&format!("other {}", index))).count();
+        assert!(false_positives < 50, "{} false positives", false_positives);
+    }
+
+    #[test]
+    fn empty_filters_contain_nothing() {
+        let filter = BloomFilter::new(&[]);
+        assert!(!filter.might_contain("signature 0"));
+        let unset: BloomFilter = serde_json::from_str(r#"{"bits": "", "hashes": 7}"#).unwrap();
+        assert!(!unset.might_contain("signature 0"));
+    }
+
+    #[test]
+    fn filters_round_trip_as_hex() {
+        let inserted = signatures(20);
+        let filter = BloomFilter::new(&inserted);
+        let json = serde_json::to_value(&filter).unwrap();
+        assert_eq!(json["bits"], hex::encode(&filter.bits));
+        assert_eq!(json["hashes"], HASHES);
+
+        let read: BloomFilter = serde_json::from_value(json).unwrap();
+        assert_eq!((&read.bits, read.hashes), (&filter.bits, filter.hashes));
+        assert!(inserted.iter().all(|signature| read.might_contain(signature)));
+        assert!(serde_json::from_str::<BloomFilter>(r#"{"bits": "zz", "hashes": 7}"#).is_err());
+    }
+}
@@ -0,0 +1,76 @@
+use futures::stream;
+use hyper::body::{Body, Bytes};
+use log::warn;
+use solana_sdk::clock::Slot;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use tokio::sync::mpsc;
+
+use crate::proof_file_name;
+
+// Compressed output sent on as one chunk of the response
+const CHUNK_BYTES: usize = 64 * 1024;
+
+// Chunks written ahead of the client before the writer waits for it, which
+// bounds the memory a bundle takes to about CHUNK_BYTES * QUEUED_CHUNKS
+const QUEUED_CHUNKS: usize = 16;
+
+// Hands what is written to the response body in chunks, waiting while the
+// client is behind
+struct ChunkWriter {
+    chunk: Vec<u8>,
+    chunks: mpsc::Sender<Result<Bytes, io::Error>>,
+}
+
+impl ChunkWriter {
+    fn send(&mut self) -> io::Result<()> {
+        let chunk = Bytes::from(std::mem::replace(&mut self.chunk, Vec::with_capacity(CHUNK_BYTES)));
+        self.chunks
+            .blocking_send(Ok(chunk))
+            .map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "client went away"))
+    }
+}
+
+impl Write for ChunkWriter {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        self.chunk.extend_from_slice(data);
+        if self.chunk.len() >= CHUNK_BYTES {
+            self.send()?;
+        }
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self.chunk.is_empty() {
+            true => Ok(()),
+            false => self.send(),
+        }
+    }
+}
+
+fn write_bundle(proofs_dir: &Path, slots: &[Slot], writer: ChunkWriter) -> io::Result<()> {
+    let mut archive = tar::Builder::new(zstd::Encoder::new(writer, 0)?);
+    for slot in slots {
+        let name = proof_file_name(*slot);
+        archive.append_path_with_name(proofs_dir.join(&name), &name)?;
+    }
+    let mut writer = archive.into_inner()?.finish()?;
+    writer.flush()
+}
+
+// A tar archive of the proof files of `slots`, compressed with zstd, made as
+// the client reads it
+pub fn stream(proofs_dir: PathBuf, slots: Vec<Slot>) -> Body {
+    let (sender, receiver) = mpsc::channel(QUEUED_CHUNKS);
+    let writer = ChunkWriter { chunk: Vec::with_capacity(CHUNK_BYTES), chunks: sender.clone() };
+    tokio::task::spawn_blocking(move || {
+        if let Err(e) = write_bundle(&proofs_dir, &slots, writer) {
+            warn!("Proof bundle stopped: {}", e);
+            // Ends the body with an error so the client can tell the archive is cut short
+            let _ = sender.blocking_send(Err(e));
+        }
+    });
+    Body::wrap_stream(stream::unfold(receiver, |mut receiver| async move {
+        receiver.recv().await.map(|chunk| (chunk, receiver))
+    }))
+}
@@ -0,0 +1,266 @@
+use bellman::groth16;
+use blstrs::{pairing, Bls12, G1Affine, G1Projective, G2Affine, G2Projective, Scalar as Fr};
+use ff::Field;
+use group::prime::PrimeCurveAffine;
+use group::{Curve, Group};
+use rand::thread_rng;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+// Multi-party generation of Groth16 parameters (the circuit-specific "phase 2"
+// of a Groth16 setup). `init` produces parameters with delta set to one, and
+// each contribution multiplies delta by a secret that is thrown away, scaling
+// the H and L queries by its inverse. The parameters are sound as long as one
+// contributor discarded their secret.
+//
+// `init` still samples tau, alpha and beta itself, so whoever runs it has to
+// be trusted to forget them; only delta is protected by the ceremony.
+//
+// Every contribution publishes a proof of knowledge of its secret, chained
+// through a transcript hash so contributions cannot be replayed or reordered.
+
+const HASH_TO_G2_DST: &[u8] = b"solana-listener/ceremony/v1";
+
+#[derive(Serialize, Deserialize)]
+pub struct Contribution {
+    // Random s in G1 and s·secret, proving knowledge of the secret against r·secret
+    pub s_g1: String,
+    pub s_secret_g1: String,
+    pub r_secret_g2: String,
+    // Delta in G1 after this contribution
+    pub delta_g1: String,
+}
+
+// Record of a ceremony, kept next to its parameters file
+#[derive(Serialize, Deserialize)]
+pub struct Transcript {
+    // SHA-256 of the initial parameters file
+    pub initial_hash: String,
+    pub contributions: Vec<Contribution>,
+}
+
+#[derive(Debug)]
+pub enum CeremonyError {
+    InitialHashMismatch,
+    // The initial parameters do not start from delta = 1
+    InitialDelta,
+    // A query or key element the ceremony must leave untouched was changed
+    Modified(&'static str),
+    InvalidContribution(usize),
+    DeltaMismatch,
+    QueryMismatch(&'static str),
+}
+
+impl fmt::Display for CeremonyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CeremonyError::InitialHashMismatch => write!(f, "initial parameters do not match the transcript"),
+            CeremonyError::InitialDelta => write!(f, "initial parameters were not generated with delta = 1"),
+            CeremonyError::Modified(element) => write!(f, "{} differs from the initial parameters", element),
+            CeremonyError::InvalidContribution(index) => write!(f, "contribution {} does not verify", index + 1),
+            CeremonyError::DeltaMismatch => write!(f, "delta does not match the last contribution"),
+            CeremonyError::QueryMismatch(query) => write!(f, "{} query is not scaled by the contributed delta", query),
+        }
+    }
+}
+
+pub fn transcript_path(params_path: &Path) -> PathBuf {
+    params_path.with_extension("ceremony.json")
+}
+
+fn read_params(path: &Path) -> groth16::Parameters<Bls12> {
+    let file = File::open(path).expect("Unable to open parameters file");
+    groth16::Parameters::read(BufReader::new(file), true).expect("Unable to read parameters file")
+}
+
+// Written to a temporary file first so an interrupted contribution never leaves torn parameters
+fn write_params(path: &Path, params: &groth16::Parameters<Bls12>) {
+    let tmp_path = path.with_extension("tmp");
+    let mut writer = BufWriter::new(File::create(&tmp_path).expect("Unable to create parameters file"));
+    params.write(&mut writer).expect("Unable to write parameters file");
+    writer.flush().expect("Unable to write parameters file");
+    fs::rename(&tmp_path, path).expect("Unable to write parameters file");
+}
+
+fn file_hash(path: &Path) -> String {
+    hex::encode(Sha256::digest(fs::read(path).expect("Unable to read parameters file")))
+}
+
+fn load_transcript(params_path: &Path) -> Transcript {
+    let contents = fs::read_to_string(transcript_path(params_path)).expect("Unable to read ceremony transcript");
+    serde_json::from_str(&contents).expect("Unable to parse ceremony transcript")
+}
+
+fn save_transcript(params_path: &Path, transcript: &Transcript) {
+    let json_data = serde_json::to_string_pretty(transcript).expect("Unable to serialize ceremony transcript");
+    fs::write(transcript_path(params_path), json_data).expect("Unable to write ceremony transcript");
+}
+
+fn g1_from_hex(data: &str) -> Option<G1Affine> {
+    let bytes: [u8; 48] = hex::decode(data).ok()?.try_into().ok()?;
+    G1Affine::from_compressed(&bytes).into()
+}
+
+fn g2_from_hex(data: &str) -> Option<G2Affine> {
+    let bytes: [u8; 96] = hex::decode(data).ok()?.try_into().ok()?;
+    G2Affine::from_compressed(&bytes).into()
+}
+
+// Chains the transcript hash through one contribution
+fn next_hash(previous: &[u8], s_g1: &G1Affine, s_secret_g1: &G1Affine) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(previous);
+    hasher.update(s_g1.to_compressed());
+    hasher.update(s_secret_g1.to_compressed());
+    hasher.finalize().into()
+}
+
+// The point a contribution's secret is applied to in G2, bound to everything before it
+fn challenge(hash: &[u8; 32]) -> G2Affine {
+    G2Projective::hash_to_curve(hash, HASH_TO_G2_DST, &[]).to_affine()
+}
+
+// e(a, d) == e(b, c), i.e. b/a == d/c in the exponent
+fn same_ratio(a: &G1Affine, b: &G1Affine, c: &G2Affine, d: &G2Affine) -> bool {
+    pairing(a, d) == pairing(b, c)
+}
+
+fn scale(points: &[G1Affine], factor: &Fr) -> Vec<G1Affine> {
+    let projective: Vec<G1Projective> = points.iter().map(|point| point * factor).collect();
+    let mut affine = vec![G1Affine::identity(); projective.len()];
+    G1Projective::batch_normalize(&projective, &mut affine);
+    affine
+}
+
+// Random linear combination of a query, for checking it in one pairing
+fn combine(points: &[G1Affine], coefficients: &[Fr]) -> G1Affine {
+    points
+        .iter()
+        .zip(coefficients)
+        .map(|(point, coefficient)| point * coefficient)
+        .sum::<G1Projective>()
+        .to_affine()
+}
+
+// Writes the initial parameters of a ceremony and starts its transcript
+pub fn init(params_path: &Path, generate: fn() -> groth16::Parameters<Bls12>) {
+    println!("Generating initial ceremony parameters...");
+    write_params(params_path, &generate());
+    save_transcript(
+        params_path,
+        &Transcript { initial_hash: file_hash(params_path), contributions: Vec::new() },
+    );
+    println!("Saved initial parameters to {:?}; keep a copy for verification", params_path);
+}
+
+// Applies a fresh secret to the parameters and records it in the transcript
+pub fn contribute(params_path: &Path) {
+    let mut transcript = load_transcript(params_path);
+    let mut params = read_params(params_path);
+    let mut rng = thread_rng();
+
+    let secret = Fr::random(&mut rng);
+    let inverse = secret.invert().unwrap();
+    let s_g1 = G1Projective::random(&mut rng).to_affine();
+    let s_secret_g1 = (s_g1 * secret).to_affine();
+    let hash = transcript_hash(&transcript);
+    let r_secret_g2 = (challenge(&next_hash(&hash, &s_g1, &s_secret_g1)) * secret).to_affine();
+
+    params.vk.delta_g1 = (params.vk.delta_g1 * secret).to_affine();
+    params.vk.delta_g2 = (params.vk.delta_g2 * secret).to_affine();
+    params.h = Arc::new(scale(&params.h, &inverse));
+    params.l = Arc::new(scale(&params.l, &inverse));
+
+    transcript.contributions.push(Contribution {
+        s_g1: hex::encode(s_g1.to_compressed()),
+        s_secret_g1: hex::encode(s_secret_g1.to_compressed()),
+        r_secret_g2: hex::encode(r_secret_g2.to_compressed()),
+        delta_g1: hex::encode(params.vk.delta_g1.to_compressed()),
+    });
+    write_params(params_path, &params);
+    save_transcript(params_path, &transcript);
+    println!("Contribution {} applied to {:?}", transcript.contributions.len(), params_path);
+}
+
+fn transcript_hash(transcript: &Transcript) -> [u8; 32] {
+    let mut hash: [u8; 32] = Sha256::digest(transcript.initial_hash.as_bytes()).into();
+    for contribution in &transcript.contributions {
+        let s_g1 = g1_from_hex(&contribution.s_g1).expect("Invalid point in ceremony transcript");
+        let s_secret_g1 = g1_from_hex(&contribution.s_secret_g1).expect("Invalid point in ceremony transcript");
+        hash = next_hash(&hash, &s_g1, &s_secret_g1);
+    }
+    hash
+}
+
+// Checks the parameters at `params_path` against the initial parameters and
+// every contribution of the transcript, returning the number of contributions
+pub fn verify(initial_path: &Path, params_path: &Path) -> Result<usize, CeremonyError> {
+    let transcript = load_transcript(params_path);
+    if file_hash(initial_path) != transcript.initial_hash {
+        return Err(CeremonyError::InitialHashMismatch);
+    }
+    let initial = read_params(initial_path);
+    let params = read_params(params_path);
+
+    let g1 = G1Affine::generator();
+    let g2 = G2Affine::generator();
+    if initial.vk.delta_g1 != g1 || initial.vk.delta_g2 != g2 {
+        return Err(CeremonyError::InitialDelta);
+    }
+    let unchanged = [
+        ("alpha", initial.vk.alpha_g1 == params.vk.alpha_g1),
+        ("beta", initial.vk.beta_g1 == params.vk.beta_g1 && initial.vk.beta_g2 == params.vk.beta_g2),
+        ("gamma", initial.vk.gamma_g2 == params.vk.gamma_g2),
+        ("IC", initial.vk.ic == params.vk.ic),
+        ("A query", initial.a == params.a),
+        ("B query", initial.b_g1 == params.b_g1 && initial.b_g2 == params.b_g2),
+        ("H query length", initial.h.len() == params.h.len()),
+        ("L query length", initial.l.len() == params.l.len()),
+    ];
+    if let Some((element, _)) = unchanged.iter().find(|(_, unchanged)| !unchanged) {
+        return Err(CeremonyError::Modified(element));
+    }
+
+    // Each contribution knows its secret and moves delta by exactly that secret
+    let mut hash: [u8; 32] = Sha256::digest(transcript.initial_hash.as_bytes()).into();
+    let mut delta_g1 = g1;
+    for (index, contribution) in transcript.contributions.iter().enumerate() {
+        let points = (
+            g1_from_hex(&contribution.s_g1),
+            g1_from_hex(&contribution.s_secret_g1),
+            g2_from_hex(&contribution.r_secret_g2),
+            g1_from_hex(&contribution.delta_g1),
+        );
+        let (Some(s_g1), Some(s_secret_g1), Some(r_secret_g2), Some(next_delta_g1)) = points else {
+            return Err(CeremonyError::InvalidContribution(index));
+        };
+        hash = next_hash(&hash, &s_g1, &s_secret_g1);
+        let r_g2 = challenge(&hash);
+        if bool::from(s_g1.is_identity())
+            || !same_ratio(&s_g1, &s_secret_g1, &r_g2, &r_secret_g2)
+            || !same_ratio(&delta_g1, &next_delta_g1, &r_g2, &r_secret_g2)
+        {
+            return Err(CeremonyError::InvalidContribution(index));
+        }
+        delta_g1 = next_delta_g1;
+    }
+    if params.vk.delta_g1 != delta_g1 || !same_ratio(&g1, &delta_g1, &g2, &params.vk.delta_g2) {
+        return Err(CeremonyError::DeltaMismatch);
+    }
+
+    // H and L must be the initial queries divided by delta
+    let mut rng = thread_rng();
+    for (query, before, after) in [("H", &initial.h, &params.h), ("L", &initial.l, &params.l)] {
+        let coefficients: Vec<Fr> = (0..before.len()).map(|_| Fr::random(&mut rng)).collect();
+        if !same_ratio(&combine(after, &coefficients), &combine(before, &coefficients), &g2, &params.vk.delta_g2) {
+            return Err(CeremonyError::QueryMismatch(query));
+        }
+    }
+
+    Ok(transcript.contributions.len())
+}
@@ -0,0 +1,341 @@
+use log::LevelFilter;
+use serde_json::Value;
+use solana_client::nonblocking::pubsub_client::PubsubClient;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::read_keypair_file;
+use std::collections::HashSet;
+use std::fmt::Display;
+use std::fs;
+use std::net::SocketAddr;
+use std::path::Path;
+use std::str::FromStr;
+use tokio::time::{timeout, Duration};
+
+use crate::accounts;
+use crate::cluster::{self, Cluster};
+use crate::config::{Config, Ingestion, ParamsSource, StorageConfig, WebhookKind};
+use crate::da;
+use crate::ethereum;
+use crate::mqtt;
+use crate::listener::rpc_client;
+use crate::params;
+use crate::schedule;
+use crate::storage::ObjectStorage;
+
+// Checks run by `check-config`, printed one per line as they complete
+#[derive(Default)]
+struct Checks {
+    failed: usize,
+}
+
+impl Checks {
+    fn pass(&self, what: &str, detail: impl Display) {
+        println!("ok     {}: {}", what, detail);
+    }
+
+    fn warn(&self, what: &str, detail: impl Display) {
+        println!("warn   {}: {}", what, detail);
+    }
+
+    fn fail(&mut self, what: &str, detail: impl Display) {
+        println!("error  {}: {}", what, detail);
+        self.failed += 1;
+    }
+}
+
+// Checks the config file at `path`, or the defaults, and everything it points
+// at: endpoints, proofs directories, object storage, parameter files, keypair
+// and the public keys of the filters. Returns whether every check passed.
+pub async fn check_config(path: Option<&Path>, cluster: Option<Cluster>) -> bool {
+    let mut checks = Checks::default();
+    let mut config = match path {
+        Some(path) => match Config::try_load(path) {
+            Ok(config) => {
+                checks.pass("config", format!("parsed {:?}", path));
+                config
+            }
+            Err(e) => {
+                checks.fail("config", e);
+                return false;
+            }
+        },
+        None => {
+            checks.warn("config", "no --config given, checking the defaults");
+            Config::default()
+        }
+    };
+    if let Some(cluster) = cluster {
+        config.cluster = Some(cluster);
+    }
+
+    if config.log_level.parse::<LevelFilter>().is_err() {
+        checks.fail(
+            "log_level",
+            format!("{:?} is not one of off, error, warn, info, debug or trace", config.log_level),
+        );
+    }
+    let addresses = [
+        ("admin.listen_addr", config.admin.as_ref().map(|admin| &admin.listen_addr)),
+        ("metrics.listen_addr", config.metrics.as_ref().map(|metrics| &metrics.listen_addr)),
+        ("api.listen_addr", config.api.as_ref().map(|api| &api.listen_addr)),
+        ("gossip.listen_addr", config.gossip.as_ref().map(|gossip| &gossip.listen_addr)),
+    ];
+    for (what, addr) in addresses {
+        if let Some(addr) = addr.filter(|addr| addr.parse::<SocketAddr>().is_err()) {
+            checks.fail(what, format!("{:?} is not a socket address such as 127.0.0.1:9000", addr));
+        }
+    }
+
+    let webhooks = config.alerts.iter().flat_map(|alerts| &alerts.webhooks);
+    for webhook in webhooks.filter(|webhook| webhook.kind == WebhookKind::Telegram && webhook.chat_id.is_none()) {
+        checks.fail("alerts.webhooks", format!("Telegram webhook {} has no chat_id", webhook.url));
+    }
+
+    if let Some(e) = config.data_availability.as_ref().and_then(|da| da::parse_namespace(&da.namespace).err()) {
+        checks.fail("data_availability.namespace", e);
+    }
+    if let Some(ethereum_config) = &config.ethereum {
+        if let Err(e) = ethereum::parse_address(&ethereum_config.contract) {
+            checks.fail("ethereum.contract", e);
+        }
+        match ethereum::load_key(&ethereum_config.key_path) {
+            Ok(_) => checks.pass("ethereum.key_path", format!("read {:?}", ethereum_config.key_path)),
+            Err(e) => checks.fail("ethereum.key_path", e),
+        }
+    }
+    if let Some(e) = config.mqtt.as_ref().and_then(|mqtt_config| mqtt::parse_qos(mqtt_config.qos).err()) {
+        checks.fail("mqtt.qos", e);
+    }
+    if let Some(prover_cpu) = &config.prover_cpu {
+        let available = std::thread::available_parallelism().map(|cores| cores.get()).unwrap_or(1);
+        if let Some(core) = prover_cpu.cores.iter().find(|core| **core >= available) {
+            checks.fail("prover_cpu.cores", format!("core {} is not one of the {} of this host", core, available));
+        }
+        if let Some(nice) = prover_cpu.nice.filter(|nice| !(0..=19).contains(nice)) {
+            checks.fail("prover_cpu.nice", format!("{} is not from 0 to 19", nice));
+        }
+    }
+    if let Some(schedule_config) = &config.schedule {
+        let windows = [
+            ("schedule.realtime_windows", &schedule_config.realtime_windows),
+            ("schedule.backfill_windows", &schedule_config.backfill_windows),
+        ];
+        for (what, windows) in windows {
+            for e in windows.iter().filter_map(|window| schedule::parse_window(window).err()) {
+                checks.fail(what, e);
+            }
+        }
+    }
+    for plugin in config.plugins.iter().filter(|plugin| !plugin.is_file()) {
+        checks.fail("plugins", format!("{:?} does not exist", plugin));
+    }
+    if let Some(rate_limit) = config.api.as_ref().and_then(|api| api.rate_limit.as_ref()) {
+        let mut keys = HashSet::new();
+        if rate_limit.keys.iter().any(|key| !keys.insert(&key.key)) {
+            checks.fail("api.rate_limit.keys", "an API key is listed more than once");
+        }
+    }
+    let limit = format!("at most {} accounts can be read per block", accounts::MAX_ACCOUNTS);
+    if config.account_state.as_ref().is_some_and(|state| state.accounts.len() > accounts::MAX_ACCOUNTS) {
+        checks.fail("account_state.accounts", &limit);
+    }
+    if config.token_supply.as_ref().is_some_and(|supply| supply.mints.len() > accounts::MAX_ACCOUNTS) {
+        checks.fail("token_supply.mints", &limit);
+    }
+    if let Some(redis_config) = &config.redis {
+        if let Err(e) = redis::Client::open(redis_config.url.as_str()) {
+            checks.fail("redis.url", e);
+        }
+    }
+
+    check_params(&mut checks, "params_path", &config.params_path, config.params_source.as_ref());
+    check_params(&mut checks, "sum_params_path", &config.sum_params_path, config.sum_params_source.as_ref());
+    if config.prove_block_totals || config.prove_total_fees {
+        let source = config.totals_params_source.as_ref();
+        check_params(&mut checks, "totals_params_path", &config.totals_params_path, source);
+    }
+    if !config.volume_thresholds.is_empty() {
+        let source = config.threshold_params_source.as_ref();
+        check_params(&mut checks, "threshold_params_path", &config.threshold_params_path, source);
+    }
+    if config.prove_signatures {
+        let source = config.signature_params_source.as_ref();
+        check_params(&mut checks, "signature_params_path", &config.signature_params_path, source);
+    }
+    if let Some(path) = &config.signing_keypair {
+        match read_keypair_file(path) {
+            Ok(_) => checks.pass("signing_keypair", format!("read {:?}", path)),
+            Err(e) => checks.fail("signing_keypair", format!("unable to read {:?}: {}", path, e)),
+        }
+    }
+    check_pubkeys(&mut checks, &config);
+    for program in &config.anchor {
+        let idl = fs::read_to_string(&program.idl_path).map_err(|e| e.to_string()).and_then(|contents| {
+            serde_json::from_str::<Value>(&contents).map_err(|e| e.to_string())
+        });
+        if let Err(e) = idl {
+            checks.fail("anchor.idl_path", format!("unable to read IDL {:?}: {}", program.idl_path, e));
+        }
+    }
+
+    let mut names = HashSet::new();
+    let instances: Vec<(String, Config)> = if config.instances.is_empty() {
+        vec![(String::new(), config.clone())]
+    } else {
+        config
+            .instances
+            .iter()
+            .filter(|instance| names.insert(&instance.name))
+            .map(|instance| (format!("{}: ", instance.name), config.instance(&instance.name).unwrap()))
+            .collect()
+    };
+    if names.len() < config.instances.len() {
+        checks.fail("instances", "an instance name is used more than once; names must be unique");
+    }
+    if !config.profiles.is_empty() && !config.instances.is_empty() {
+        checks.fail("profiles", "profiles cannot be combined with instances");
+    }
+    let mut profile_names = HashSet::new();
+    for profile in config.profiles.iter().filter(|profile| profile_names.insert(&profile.name)) {
+        let label = format!("{}: ", profile.name);
+        let profile_config = config.profile(&profile.name).unwrap();
+        check_proofs_dir(&mut checks, &label, &profile_config.proofs_dir);
+        if let Some(storage_config) = &profile_config.storage {
+            check_storage(&mut checks, &label, &profile_config, storage_config).await;
+        }
+        let filter = profile.filter.iter();
+        let lists = [
+            ("filter.programs", filter.clone().flat_map(|filter| &filter.programs).collect::<Vec<_>>()),
+            ("filter.accounts", filter.clone().flat_map(|filter| &filter.accounts).collect()),
+            ("filter.mints", filter.flat_map(|filter| &filter.mints).collect()),
+        ];
+        for (what, keys) in lists {
+            for key in keys.into_iter().filter(|key| Pubkey::from_str(key).is_err()) {
+                checks.fail(&format!("{}{}", label, what), format!("{:?} is not a base58 public key", key));
+            }
+        }
+    }
+    if profile_names.len() < config.profiles.len() {
+        checks.fail("profiles", "a profile name is used more than once; names must be unique");
+    }
+    for (label, instance_config) in &instances {
+        check_endpoints(&mut checks, label, instance_config).await;
+        check_proofs_dir(&mut checks, label, &instance_config.proofs_dir);
+        if let Some(storage_config) = &instance_config.storage {
+            check_storage(&mut checks, label, instance_config, storage_config).await;
+        }
+    }
+
+    match checks.failed {
+        0 => println!("Configuration OK"),
+        failed => println!("{} of the checks failed", failed),
+    }
+    checks.failed == 0
+}
+
+// A missing file is only an error when it is pinned, since the listener
+// generates unpinned parameters on first run
+fn check_params(checks: &mut Checks, what: &str, path: &Path, source: Option<&ParamsSource>) {
+    if !path.exists() {
+        match source {
+            Some(source) => {
+                checks.warn(what, format!("{:?} is missing and will be downloaded from {}", path, source.url))
+            }
+            None => checks.warn(what, format!("{:?} is missing and will be generated on first run", path)),
+        }
+        return;
+    }
+    match params::check_file(path, source) {
+        Ok(fingerprint) => checks.pass(what, format!("{:?} has verifying key {}", path, fingerprint)),
+        Err(e) => checks.fail(what, format!("{:?}: {}", path, e)),
+    }
+}
+
+fn check_pubkeys(checks: &mut Checks, config: &Config) {
+    let filter = config.filter.as_ref();
+    let lists: [(&str, Vec<&String>); 12] = [
+        ("filter.programs", filter.iter().flat_map(|filter| &filter.programs).collect()),
+        ("filter.accounts", filter.iter().flat_map(|filter| &filter.accounts).collect()),
+        ("filter.mints", filter.iter().flat_map(|filter| &filter.mints).collect()),
+        ("tokens.mints", config.tokens.iter().flat_map(|tokens| &tokens.mints).collect()),
+        ("token_supply.mints", config.token_supply.iter().flat_map(|supply| &supply.mints).collect()),
+        ("volume_thresholds.mint", config.volume_thresholds.iter().flat_map(|threshold| &threshold.mint).collect()),
+        ("sol_transfers.accounts", config.sol_transfers.iter().flat_map(|sol| &sol.accounts).collect()),
+        ("balances.accounts", config.balances.iter().flat_map(|balances| &balances.accounts).collect()),
+        ("account_state.accounts", config.account_state.iter().flat_map(|state| &state.accounts).collect()),
+        (
+            "stake_activity.authorities",
+            config.stake_activity.iter().flat_map(|stake| &stake.authorities).collect(),
+        ),
+        ("votes.validators", config.votes.iter().flat_map(|votes| &votes.validators).collect()),
+        ("anchor.program_id", config.anchor.iter().map(|program| &program.program_id).collect()),
+    ];
+    for (what, keys) in lists {
+        for key in keys.into_iter().filter(|key| Pubkey::from_str(key).is_err()) {
+            checks.fail(what, format!("{:?} is not a base58 public key", key));
+        }
+    }
+}
+
+async fn check_endpoints(checks: &mut Checks, label: &str, config: &Config) {
+    match cluster::validate_genesis(config) {
+        Ok(genesis_hash) => {
+            checks.pass(&format!("{}rpc_url", label), format!("{} has genesis {}", config.rpc_url(), genesis_hash))
+        }
+        Err(e) => checks.fail(&format!("{}rpc_url", label), format!("{}: {}", config.rpc_url(), e)),
+    }
+
+    let others = [("cross_check_rpc_url", &config.cross_check_rpc_url), ("archive_rpc_url", &config.archive_rpc_url)];
+    for (what, url) in others {
+        let Some(url) = url else {
+            continue;
+        };
+        match rpc_client(url.clone(), config.rpc_timeouts.request_secs).get_slot() {
+            Ok(slot) => checks.pass(&format!("{}{}", label, what), format!("{} is at slot {}", url, slot)),
+            Err(e) => checks.fail(&format!("{}{}", label, what), format!("unable to reach {}: {}", url, e)),
+        }
+    }
+
+    if config.ingestion == Ingestion::Subscribe {
+        let ws_url = config.ws_url();
+        let idle = Duration::from_secs(config.rpc_timeouts.ws_idle_secs);
+        match timeout(idle, PubsubClient::new(&ws_url)).await {
+            Ok(Ok(_)) => checks.pass(&format!("{}ws_url", label), format!("connected to {}", ws_url)),
+            Ok(Err(e)) => checks.fail(&format!("{}ws_url", label), format!("unable to connect to {}: {}", ws_url, e)),
+            Err(_) => checks.fail(&format!("{}ws_url", label), format!("timed out connecting to {}", ws_url)),
+        }
+    }
+}
+
+fn check_proofs_dir(checks: &mut Checks, label: &str, proofs_dir: &Path) {
+    let probe = proofs_dir.join(".check-config");
+    let written = fs::create_dir_all(proofs_dir)
+        .and_then(|_| fs::write(&probe, b"check-config"))
+        .and_then(|_| fs::remove_file(&probe));
+    match written {
+        Ok(()) => checks.pass(&format!("{}proofs_dir", label), format!("{:?} is writable", proofs_dir)),
+        Err(e) => checks.fail(&format!("{}proofs_dir", label), format!("unable to write to {:?}: {}", proofs_dir, e)),
+    }
+}
+
+// Writes and removes a probe object, under the prefix files are uploaded to
+async fn check_storage(checks: &mut Checks, label: &str, config: &Config, storage_config: &StorageConfig) {
+    let what = format!("{}storage", label);
+    let genesis_hash = cluster::validate_genesis(config).ok();
+    let storage = match ObjectStorage::from_config(storage_config, genesis_hash.as_deref()) {
+        Ok(storage) => storage,
+        Err(e) => {
+            let bucket = &storage_config.bucket;
+            checks.fail(&what, format!("unable to configure {:?} bucket {}: {}", storage_config.backend, bucket, e));
+            return;
+        }
+    };
+    let written = match storage.put(".check-config", b"check-config".to_vec()).await {
+        Ok(()) => storage.delete(".check-config").await,
+        Err(e) => Err(e),
+    };
+    match written {
+        Ok(()) => checks.pass(&what, format!("bucket {} is writable", storage_config.bucket)),
+        Err(e) => checks.fail(&what, format!("unable to write to bucket {}: {}", storage_config.bucket, e)),
+    }
+}
@@ -0,0 +1,57 @@
+use blstrs::Scalar as Fr;
+use ff::Field;
+use serde::{Deserialize, Serialize};
+use solana_sdk::clock::Slot;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::field::{fr_from_hex, fr_to_hex};
+
+// Listener progress, persisted in the proofs directory so a restart picks up
+// where the previous run stopped. `accumulator_root` commits to every block
+// proved so far, in order.
+#[derive(Serialize, Deserialize)]
+pub struct Checkpoint {
+    pub last_slot: Slot,
+    pub accumulator_root: String,
+}
+
+impl Default for Checkpoint {
+    fn default() -> Self {
+        Checkpoint {
+            last_slot: 0,
+            accumulator_root: fr_to_hex(&Fr::ZERO),
+        }
+    }
+}
+
+fn checkpoint_path(proofs_dir: &Path) -> PathBuf {
+    proofs_dir.join("checkpoint.json")
+}
+
+impl Checkpoint {
+    pub fn load(proofs_dir: &Path) -> Checkpoint {
+        match fs::read_to_string(checkpoint_path(proofs_dir)) {
+            Ok(contents) => serde_json::from_str(&contents).expect("Unable to parse checkpoint"),
+            Err(_) => Checkpoint::default(),
+        }
+    }
+
+    // Written to a temporary file first so a crash never leaves a torn checkpoint
+    pub fn save(&self, proofs_dir: &Path) {
+        let path = checkpoint_path(proofs_dir);
+        let tmp_path = path.with_extension("json.tmp");
+        let json_data = serde_json::to_string_pretty(self).expect("Unable to serialize checkpoint");
+        fs::write(&tmp_path, json_data).expect("Unable to write checkpoint");
+        fs::rename(&tmp_path, &path).expect("Unable to write checkpoint");
+    }
+
+    pub fn root(&self) -> Fr {
+        fr_from_hex(&self.accumulator_root).expect("Invalid accumulator root in checkpoint")
+    }
+
+    pub fn advance(&mut self, slot: Slot, accumulator_root: String) {
+        self.last_slot = slot;
+        self.accumulator_root = accumulator_root;
+    }
+}
@@ -0,0 +1,844 @@
+use bellman::{groth16, Circuit, ConstraintSystem, LinearCombination, SynthesisError, Variable};
+use blstrs::{Bls12, G1Projective, G2Projective, Scalar as Fr};
+use ff::Field;
+use group::Group;
+use rand::thread_rng;
+use sha2::{Digest, Sha256};
+use std::sync::OnceLock;
+
+use crate::field::hash_to_fr;
+use crate::witness::{ChunkWitness, SumWitness, TotalsWitness};
+
+// Number of leaves (transaction hashes) a single proof commits to. The circuit
+// shape is fixed so that parameters only have to be generated once.
+pub const CIRCUIT_CAPACITY: usize = 1024;
+
+fn round_constants() -> &'static [Fr] {
+    static CONSTANTS: OnceLock<Vec<Fr>> = OnceLock::new();
+    CONSTANTS.get_or_init(|| {
+        (0..CIRCUIT_CAPACITY)
+            .map(|round| {
+                let mut hasher = Sha256::new();
+                hasher.update(b"solana-listener/accumulator");
+                hasher.update((round as u64).to_le_bytes());
+                hash_to_fr(&hasher.finalize())
+            })
+            .collect()
+    })
+}
+
+// Constants for the value rounds of the sum circuit, one per leaf
+fn value_constants() -> &'static [Fr] {
+    static CONSTANTS: OnceLock<Vec<Fr>> = OnceLock::new();
+    CONSTANTS.get_or_init(|| {
+        (0..CIRCUIT_CAPACITY)
+            .map(|round| {
+                let mut hasher = Sha256::new();
+                hasher.update(b"solana-listener/value");
+                hasher.update((round as u64).to_le_bytes());
+                hash_to_fr(&hasher.finalize())
+            })
+            .collect()
+    })
+}
+
+fn chain_constant() -> Fr {
+    static CONSTANT: OnceLock<Fr> = OnceLock::new();
+    *CONSTANT.get_or_init(|| hash_to_fr(&Sha256::digest(b"solana-listener/chain")))
+}
+
+fn mimc_round(acc: Fr, leaf: Fr, constant: Fr) -> Fr {
+    let t = acc + leaf + constant;
+    let t4 = t.square().square();
+    t4 * t
+}
+
+// One MiMC-style round of the leaf accumulator: acc' = (acc + leaf + c_round)^5
+pub fn absorb(acc: Fr, leaf: Fr, round: usize) -> Fr {
+    mimc_round(acc, leaf, round_constants()[round])
+}
+
+// Absorbs a leaf and the value attached to it into a sum commitment
+pub fn absorb_valued(acc: Fr, leaf: Fr, value: u64, round: usize) -> Fr {
+    mimc_round(absorb(acc, leaf, round), Fr::from(value), value_constants()[round])
+}
+
+// Advances the cross-block accumulator by one block commitment
+pub fn chain_root(old_root: Fr, commitment: Fr) -> Fr {
+    mimc_round(old_root, commitment, chain_constant())
+}
+
+// Constrains next = (acc + leaf + constant)^5
+fn mimc_round_gadget<CS: ConstraintSystem<Fr>>(
+    cs: &mut CS,
+    acc: (Variable, Option<Fr>),
+    leaf: (Variable, Option<Fr>),
+    constant: Fr,
+) -> Result<(Variable, Option<Fr>), SynthesisError> {
+    let (acc_var, acc_value) = acc;
+    let (leaf_var, leaf_value) = leaf;
+
+    // t = acc + leaf + c
+    let t_value = acc_value.zip(leaf_value).map(|(acc, leaf)| acc + leaf + constant);
+
+    let t2_value = t_value.map(|t| t.square());
+    let t2_var = cs.alloc(|| "t^2", || t2_value.ok_or(SynthesisError::AssignmentMissing))?;
+    cs.enforce(
+        || "t^2 constraint",
+        |lc| lc + acc_var + leaf_var + (constant, CS::one()),
+        |lc| lc + acc_var + leaf_var + (constant, CS::one()),
+        |lc| lc + t2_var,
+    );
+
+    let t4_value = t2_value.map(|t2| t2.square());
+    let t4_var = cs.alloc(|| "t^4", || t4_value.ok_or(SynthesisError::AssignmentMissing))?;
+    cs.enforce(
+        || "t^4 constraint",
+        |lc| lc + t2_var,
+        |lc| lc + t2_var,
+        |lc| lc + t4_var,
+    );
+
+    let next_value = t4_value.zip(t_value).map(|(t4, t)| t4 * t);
+    let next_var = cs.alloc(|| "t^5", || next_value.ok_or(SynthesisError::AssignmentMissing))?;
+    cs.enforce(
+        || "t^5 constraint",
+        |lc| lc + t4_var,
+        |lc| lc + acc_var + leaf_var + (constant, CS::one()),
+        |lc| lc + next_var,
+    );
+
+    Ok((next_var, next_value))
+}
+
+// Number of bits each value of the totals and threshold circuits is constrained to
+const VALUE_BITS: usize = 64;
+
+// Bits of the threshold circuit's margin: enough for the sum of a full chunk of
+// 64-bit values less a 64-bit threshold
+const MARGIN_BITS: usize = 80;
+
+// Allocates the low `bits` bits of `value`, each constrained to 0 or 1, and
+// returns the linear combination they weigh up to
+fn alloc_bits<CS: ConstraintSystem<Fr>>(
+    cs: &mut CS,
+    value: Option<u128>,
+    bits: usize,
+) -> Result<LinearCombination<Fr>, SynthesisError> {
+    let mut packed = LinearCombination::zero();
+    let mut weight = Fr::ONE;
+    for bit in 0..bits {
+        let bit_value = value.map(|value| (value >> bit) & 1 == 1);
+        let bit_var = cs.alloc(
+            || format!("bit {}", bit),
+            || bit_value.map(|bit| Fr::from(bit as u64)).ok_or(SynthesisError::AssignmentMissing),
+        )?;
+        cs.enforce(
+            || format!("bit {} is boolean", bit),
+            |lc| lc + bit_var,
+            |lc| lc + CS::one() - bit_var,
+            |lc| lc,
+        );
+        packed = packed + (weight, bit_var);
+        weight = weight.double();
+    }
+    Ok(packed)
+}
+
+// Commits to a sequence of leaves (seeded with the block hash). Each leaf is
+// absorbed in turn and the output is the accumulator after all rounds. The
+// commitment is then chained onto the cross-block accumulator root.
+//
+// Public inputs: seed, commitment, old root, new root.
+struct BlockCircuit {
+    pub seed: Option<Fr>,
+    pub leaves: Vec<Option<Fr>>,
+    pub old_root: Option<Fr>,
+}
+
+impl Circuit<Fr> for BlockCircuit {
+    fn synthesize<CS: ConstraintSystem<Fr>>(self, cs: &mut CS) -> Result<(), SynthesisError> {
+        // The seed (block hash) is a public input
+        let seed_var = cs.alloc_input(
+            || "seed",
+            || self.seed.ok_or(SynthesisError::AssignmentMissing),
+        )?;
+
+        let mut acc = (seed_var, self.seed);
+
+        for (round, leaf) in self.leaves.iter().enumerate() {
+            let cs = &mut cs.namespace(|| format!("leaf {}", round));
+
+            let leaf_var = cs.alloc(
+                || "leaf",
+                || leaf.ok_or(SynthesisError::AssignmentMissing),
+            )?;
+            acc = mimc_round_gadget(cs, acc, (leaf_var, *leaf), round_constants()[round])?;
+        }
+
+        // The final accumulator is exposed as the commitment
+        let (acc_var, commitment_value) = acc;
+        let commitment_var = cs.alloc_input(
+            || "commitment",
+            || commitment_value.ok_or(SynthesisError::AssignmentMissing),
+        )?;
+        cs.enforce(
+            || "commitment constraint",
+            |lc| lc + acc_var,
+            |lc| lc + CS::one(),
+            |lc| lc + commitment_var,
+        );
+
+        // new root = chain(old root, commitment)
+        let old_root_var = cs.alloc_input(
+            || "old root",
+            || self.old_root.ok_or(SynthesisError::AssignmentMissing),
+        )?;
+        let (chained_var, new_root_value) = mimc_round_gadget(
+            &mut cs.namespace(|| "chain"),
+            (old_root_var, self.old_root),
+            (commitment_var, commitment_value),
+            chain_constant(),
+        )?;
+        let new_root_var = cs.alloc_input(
+            || "new root",
+            || new_root_value.ok_or(SynthesisError::AssignmentMissing),
+        )?;
+        cs.enforce(
+            || "new root constraint",
+            |lc| lc + chained_var,
+            |lc| lc + CS::one(),
+            |lc| lc + new_root_var,
+        );
+
+        Ok(())
+    }
+}
+
+// Exposes an accumulator as a public input named `name`
+fn expose<CS: ConstraintSystem<Fr>>(
+    cs: &mut CS,
+    name: &'static str,
+    acc: (Variable, Option<Fr>),
+) -> Result<Variable, SynthesisError> {
+    let (acc_var, value) = acc;
+    let input_var = cs.alloc_input(|| name, || value.ok_or(SynthesisError::AssignmentMissing))?;
+    cs.enforce(
+        || format!("{} constraint", name),
+        |lc| lc + acc_var,
+        |lc| lc + CS::one(),
+        |lc| lc + input_var,
+    );
+    Ok(input_var)
+}
+
+// The leaves of a valued circuit, absorbed by `valued_leaves`
+struct ValuedLeaves {
+    // Accumulator over the leaves alone, as the block circuit computes it
+    commitment: (Variable, Option<Fr>),
+    // Accumulator over the leaves and their values
+    values_commitment: (Variable, Option<Fr>),
+    present_vars: Vec<Variable>,
+    value_vars: Vec<Variable>,
+}
+
+// Absorbs the leaves of a block (or of one of its chunks) exactly as the block
+// circuit does from `seed`, and each leaf followed by a private 64-bit value
+// into a second accumulator from `values_seed`, which binds each value to its
+// leaf. Every leaf is flagged as present or padding: present leaves are
+// nonzero, padding leaves and their values are zero, and padding comes after
+// all present leaves.
+fn valued_leaves<CS: ConstraintSystem<Fr>>(
+    cs: &mut CS,
+    seed: (Variable, Option<Fr>),
+    values_seed: (Variable, Option<Fr>),
+    leaves: &[(Option<Fr>, Option<u64>, Option<bool>)],
+) -> Result<ValuedLeaves, SynthesisError> {
+    let mut acc = seed;
+    let mut valued_acc = values_seed;
+    let mut present_vars = Vec::with_capacity(leaves.len());
+    let mut value_vars = Vec::with_capacity(leaves.len());
+
+    for (round, (leaf, value, present)) in leaves.iter().enumerate() {
+        let cs = &mut cs.namespace(|| format!("leaf {}", round));
+
+        let leaf_var = cs.alloc(
+            || "leaf",
+            || leaf.ok_or(SynthesisError::AssignmentMissing),
+        )?;
+        let value_fr = value.map(Fr::from);
+        let value_var = cs.alloc(
+            || "value",
+            || value_fr.ok_or(SynthesisError::AssignmentMissing),
+        )?;
+        // value < 2^64, so the total of a chunk cannot wrap around the field
+        let bits = alloc_bits(&mut cs.namespace(|| "value bits"), value.map(u128::from), VALUE_BITS)?;
+        cs.enforce(
+            || "value range",
+            |lc| lc + &bits,
+            |lc| lc + CS::one(),
+            |lc| lc + value_var,
+        );
+        let present_value = present.map(|present| if present { Fr::ONE } else { Fr::ZERO });
+        let present_var = cs.alloc(
+            || "present",
+            || present_value.ok_or(SynthesisError::AssignmentMissing),
+        )?;
+
+        // present is 0 or 1
+        cs.enforce(
+            || "present is boolean",
+            |lc| lc + present_var,
+            |lc| lc + CS::one() - present_var,
+            |lc| lc,
+        );
+        // Present leaves are nonzero: leaf * inverse = present has no
+        // solution for a zero leaf flagged as present
+        let inverse_value = leaf.map(|leaf| leaf.invert().unwrap_or(Fr::ZERO));
+        let inverse_var = cs.alloc(
+            || "leaf inverse",
+            || inverse_value.ok_or(SynthesisError::AssignmentMissing),
+        )?;
+        cs.enforce(
+            || "present leaf",
+            |lc| lc + leaf_var,
+            |lc| lc + inverse_var,
+            |lc| lc + present_var,
+        );
+        // Padding leaves and their values are zero
+        cs.enforce(
+            || "padding leaf",
+            |lc| lc + leaf_var,
+            |lc| lc + CS::one() - present_var,
+            |lc| lc,
+        );
+        cs.enforce(
+            || "padding value",
+            |lc| lc + value_var,
+            |lc| lc + CS::one() - present_var,
+            |lc| lc,
+        );
+        // A present leaf only follows present leaves
+        if let Some(&previous_var) = present_vars.last() {
+            cs.enforce(
+                || "padding comes last",
+                |lc| lc + present_var,
+                |lc| lc + CS::one() - previous_var,
+                |lc| lc,
+            );
+        }
+
+        acc = mimc_round_gadget(cs, acc, (leaf_var, *leaf), round_constants()[round])?;
+        let valued = mimc_round_gadget(
+            &mut cs.namespace(|| "valued leaf"),
+            valued_acc,
+            (leaf_var, *leaf),
+            round_constants()[round],
+        )?;
+        valued_acc = mimc_round_gadget(
+            &mut cs.namespace(|| "valued value"),
+            valued,
+            (value_var, value_fr),
+            value_constants()[round],
+        )?;
+
+        present_vars.push(present_var);
+        value_vars.push(value_var);
+    }
+
+    Ok(ValuedLeaves { commitment: acc, values_commitment: valued_acc, present_vars, value_vars })
+}
+
+// Commits to the leaves of a block (or of one of its chunks) exactly as the
+// block circuit does, so its commitment must equal the block's, and sums a
+// private 64-bit value attached to each leaf as in the totals circuit, such as
+// the lamports a transaction moved, counted on its first leaf. The values
+// commitment starts from its own seed, which tells apart sums over the same
+// leaves.
+//
+// Public inputs: seed, values seed, commitment, values commitment, total.
+struct SumCircuit {
+    pub seed: Option<Fr>,
+    pub values_seed: Option<Fr>,
+    // Leaf, value and whether the leaf is present
+    pub leaves: Vec<(Option<Fr>, Option<u64>, Option<bool>)>,
+}
+
+impl Circuit<Fr> for SumCircuit {
+    fn synthesize<CS: ConstraintSystem<Fr>>(self, cs: &mut CS) -> Result<(), SynthesisError> {
+        let seed_var = cs.alloc_input(
+            || "seed",
+            || self.seed.ok_or(SynthesisError::AssignmentMissing),
+        )?;
+        let values_seed_var = cs.alloc_input(
+            || "values seed",
+            || self.values_seed.ok_or(SynthesisError::AssignmentMissing),
+        )?;
+
+        let leaves = valued_leaves(cs, (seed_var, self.seed), (values_seed_var, self.values_seed), &self.leaves)?;
+        expose(cs, "commitment", leaves.commitment)?;
+        expose(cs, "values commitment", leaves.values_commitment)?;
+
+        // total = sum of the values
+        let total_value =
+            self.leaves.iter().try_fold(Fr::ZERO, |total, (_, value, _)| value.map(|value| total + Fr::from(value)));
+        let total_var = cs.alloc_input(
+            || "total",
+            || total_value.ok_or(SynthesisError::AssignmentMissing),
+        )?;
+        cs.enforce(
+            || "total constraint",
+            |lc| leaves.value_vars.iter().fold(lc, |lc, &value_var| lc + value_var),
+            |lc| lc + CS::one(),
+            |lc| lc + total_var,
+        );
+
+        Ok(())
+    }
+}
+
+// Commits to the leaves of a block (or of one of its chunks) exactly as the
+// block circuit does, so its commitment must equal the block's, and counts the
+// leaves and sums a private 64-bit value attached to each. Padding leaves come
+// after all present ones, so the count is the number of leaves in the block.
+// There is one leaf per transaction signature, so it is a signature count: a
+// transaction with several signers counts once per signer. The values
+// commitment starts from the seed too.
+//
+// Public inputs: seed, commitment, values commitment, signature count, total.
+struct TotalsCircuit {
+    pub seed: Option<Fr>,
+    // Leaf, value and whether the leaf is present
+    pub leaves: Vec<(Option<Fr>, Option<u64>, Option<bool>)>,
+}
+
+impl Circuit<Fr> for TotalsCircuit {
+    fn synthesize<CS: ConstraintSystem<Fr>>(self, cs: &mut CS) -> Result<(), SynthesisError> {
+        let seed_var = cs.alloc_input(
+            || "seed",
+            || self.seed.ok_or(SynthesisError::AssignmentMissing),
+        )?;
+
+        let seed = (seed_var, self.seed);
+        let leaves = valued_leaves(cs, seed, seed, &self.leaves)?;
+        expose(cs, "commitment", leaves.commitment)?;
+        expose(cs, "values commitment", leaves.values_commitment)?;
+
+        // signature count = number of present leaves
+        let signature_count_value = self.leaves.iter().try_fold(Fr::ZERO, |count, (_, _, present)| {
+            present.map(|present| if present { count + Fr::ONE } else { count })
+        });
+        let signature_count_var = cs.alloc_input(
+            || "signature count",
+            || signature_count_value.ok_or(SynthesisError::AssignmentMissing),
+        )?;
+        cs.enforce(
+            || "signature count constraint",
+            |lc| leaves.present_vars.iter().fold(lc, |lc, &present_var| lc + present_var),
+            |lc| lc + CS::one(),
+            |lc| lc + signature_count_var,
+        );
+
+        // total = sum of the values
+        let total_value =
+            self.leaves.iter().try_fold(Fr::ZERO, |total, (_, value, _)| value.map(|value| total + Fr::from(value)));
+        let total_var = cs.alloc_input(
+            || "total",
+            || total_value.ok_or(SynthesisError::AssignmentMissing),
+        )?;
+        cs.enforce(
+            || "total constraint",
+            |lc| leaves.value_vars.iter().fold(lc, |lc, &value_var| lc + value_var),
+            |lc| lc + CS::one(),
+            |lc| lc + total_var,
+        );
+
+        Ok(())
+    }
+}
+
+// Commits to the leaves of a block that fits a single chunk exactly as the
+// block circuit does, so its commitment must equal the block's, with a private
+// 64-bit value attached to each leaf as in the totals circuit, the values
+// commitment starting from its own seed. It proves that the sum of the values
+// is at or above a public threshold (`above` = 1) or below it (`above` = 0)
+// without exposing the sum. The margin between the two, less one when below,
+// must fit in `MARGIN_BITS` bits, which it cannot if the claim is false.
+//
+// Public inputs: seed, values seed, commitment, values commitment, threshold,
+// above.
+struct ThresholdCircuit {
+    pub seed: Option<Fr>,
+    pub values_seed: Option<Fr>,
+    // Leaf, value and whether the leaf is present
+    pub leaves: Vec<(Option<Fr>, Option<u64>, Option<bool>)>,
+    pub threshold: Option<u64>,
+}
+
+impl Circuit<Fr> for ThresholdCircuit {
+    fn synthesize<CS: ConstraintSystem<Fr>>(self, cs: &mut CS) -> Result<(), SynthesisError> {
+        let seed_var = cs.alloc_input(
+            || "seed",
+            || self.seed.ok_or(SynthesisError::AssignmentMissing),
+        )?;
+        let values_seed_var = cs.alloc_input(
+            || "values seed",
+            || self.values_seed.ok_or(SynthesisError::AssignmentMissing),
+        )?;
+
+        let leaves = valued_leaves(cs, (seed_var, self.seed), (values_seed_var, self.values_seed), &self.leaves)?;
+        expose(cs, "commitment", leaves.commitment)?;
+        expose(cs, "values commitment", leaves.values_commitment)?;
+
+        let total = self.leaves.iter().try_fold(0u128, |total, (_, value, _)| value.map(|value| total + value as u128));
+        let threshold_var = cs.alloc_input(
+            || "threshold",
+            || self.threshold.map(Fr::from).ok_or(SynthesisError::AssignmentMissing),
+        )?;
+        let above = total.zip(self.threshold).map(|(total, threshold)| total >= threshold as u128);
+        let above_var = cs.alloc_input(
+            || "above",
+            || above.map(|above| Fr::from(above as u64)).ok_or(SynthesisError::AssignmentMissing),
+        )?;
+        cs.enforce(
+            || "above is boolean",
+            |lc| lc + above_var,
+            |lc| lc + CS::one() - above_var,
+            |lc| lc,
+        );
+
+        // margin = total - threshold when above, threshold - total - 1 when
+        // below, i.e. (2 above - 1)(total - threshold) = margin - above + 1
+        let margin = total.zip(self.threshold).zip(above).map(|((total, threshold), above)| match above {
+            true => total - threshold as u128,
+            false => threshold as u128 - total - 1,
+        });
+        let margin_bits = alloc_bits(&mut cs.namespace(|| "margin bits"), margin, MARGIN_BITS)?;
+        cs.enforce(
+            || "threshold constraint",
+            |lc| lc + (Fr::from(2), above_var) - CS::one(),
+            |lc| leaves.value_vars.iter().fold(lc, |lc, &value_var| lc + value_var) - threshold_var,
+            |lc| lc + &margin_bits - above_var + CS::one(),
+        );
+
+        Ok(())
+    }
+}
+
+// Generate parameters for the fixed-capacity block circuit
+fn empty_block_circuit() -> BlockCircuit {
+    BlockCircuit {
+        seed: None,
+        leaves: vec![None; CIRCUIT_CAPACITY],
+        old_root: None,
+    }
+}
+
+fn empty_sum_circuit() -> SumCircuit {
+    SumCircuit {
+        seed: None,
+        values_seed: None,
+        leaves: vec![(None, None, None); CIRCUIT_CAPACITY],
+    }
+}
+
+fn empty_totals_circuit() -> TotalsCircuit {
+    TotalsCircuit {
+        seed: None,
+        leaves: vec![(None, None, None); CIRCUIT_CAPACITY],
+    }
+}
+
+fn empty_threshold_circuit() -> ThresholdCircuit {
+    ThresholdCircuit {
+        seed: None,
+        values_seed: None,
+        leaves: vec![(None, None, None); CIRCUIT_CAPACITY],
+        threshold: None,
+    }
+}
+
+pub fn generate_parameters() -> groth16::Parameters<Bls12> {
+    groth16::generate_random_parameters::<Bls12, _, _>(empty_block_circuit(), &mut thread_rng()).unwrap()
+}
+
+// Starting point of a parameter ceremony: gamma and delta are one, so every
+// contribution to delta can be checked against these parameters
+pub fn initial_parameters<C: Circuit<Fr>>(circuit: C) -> groth16::Parameters<Bls12> {
+    let mut rng = thread_rng();
+    let (alpha, beta, tau) = (Fr::random(&mut rng), Fr::random(&mut rng), Fr::random(&mut rng));
+    groth16::generate_parameters::<Bls12, _>(
+        circuit,
+        G1Projective::generator(),
+        G2Projective::generator(),
+        alpha,
+        beta,
+        Fr::ONE,
+        Fr::ONE,
+        tau,
+    )
+    .unwrap()
+}
+
+pub fn generate_initial_parameters() -> groth16::Parameters<Bls12> {
+    initial_parameters(empty_block_circuit())
+}
+
+pub fn generate_initial_sum_parameters() -> groth16::Parameters<Bls12> {
+    initial_parameters(empty_sum_circuit())
+}
+
+pub fn generate_initial_totals_parameters() -> groth16::Parameters<Bls12> {
+    initial_parameters(empty_totals_circuit())
+}
+
+pub fn generate_initial_threshold_parameters() -> groth16::Parameters<Bls12> {
+    initial_parameters(empty_threshold_circuit())
+}
+
+// Block circuit instance of a block, one of its chunks, or the aggregate over
+// its chunks. Only the top-level instance of a block carries the real
+// accumulator root; chunks are chained from zero.
+fn block_circuit(witness: &ChunkWitness) -> BlockCircuit {
+    let mut leaves: Vec<Option<Fr>> = witness.leaves.iter().map(|&leaf| Some(leaf)).collect();
+    leaves.resize(CIRCUIT_CAPACITY, Some(Fr::ZERO));
+
+    BlockCircuit {
+        seed: Some(witness.seed),
+        leaves,
+        old_root: Some(witness.old_root),
+    }
+}
+
+// Function to generate a proof for a single circuit instance
+pub fn prove(params: &groth16::Parameters<Bls12>, witness: &ChunkWitness) -> groth16::Proof<Bls12> {
+    groth16::create_random_proof(block_circuit(witness), params, &mut thread_rng()).unwrap()
+}
+
+// Generate parameters for the fixed-capacity sum circuit
+pub fn generate_sum_parameters() -> groth16::Parameters<Bls12> {
+    groth16::generate_random_parameters::<Bls12, _, _>(empty_sum_circuit(), &mut thread_rng()).unwrap()
+}
+
+// Leaves of a chunk with the values attached to them, flagged as present and
+// padded to the circuit's capacity with zero leaves flagged as padding
+fn present_leaves(leaves: &[Fr], values: &[u64]) -> Vec<(Option<Fr>, Option<u64>, Option<bool>)> {
+    let mut leaves: Vec<(Option<Fr>, Option<u64>, Option<bool>)> =
+        leaves.iter().zip(values).map(|(&leaf, &value)| (Some(leaf), Some(value), Some(true))).collect();
+    leaves.resize(CIRCUIT_CAPACITY, (Some(Fr::ZERO), Some(0), Some(false)));
+    leaves
+}
+
+// Sum circuit instance of one chunk of a block
+fn sum_circuit(witness: &SumWitness) -> SumCircuit {
+    SumCircuit {
+        seed: Some(witness.seed),
+        values_seed: Some(witness.values_seed),
+        leaves: present_leaves(&witness.leaves, &witness.values),
+    }
+}
+
+// Proves the total of a value attached to the leaves of one chunk of a block
+pub fn prove_sum(params: &groth16::Parameters<Bls12>, witness: &SumWitness) -> groth16::Proof<Bls12> {
+    groth16::create_random_proof(sum_circuit(witness), params, &mut thread_rng()).unwrap()
+}
+
+// Generate parameters for the fixed-capacity totals circuit
+pub fn generate_totals_parameters() -> groth16::Parameters<Bls12> {
+    groth16::generate_random_parameters::<Bls12, _, _>(empty_totals_circuit(), &mut thread_rng()).unwrap()
+}
+
+// Totals circuit instance of one chunk of a block
+fn totals_circuit(witness: &TotalsWitness) -> TotalsCircuit {
+    TotalsCircuit {
+        seed: Some(witness.seed),
+        leaves: present_leaves(&witness.leaves, &witness.values),
+    }
+}
+
+// Proves the signature count and total of one chunk of a block
+pub fn prove_totals(params: &groth16::Parameters<Bls12>, witness: &TotalsWitness) -> groth16::Proof<Bls12> {
+    groth16::create_random_proof(totals_circuit(witness), params, &mut thread_rng()).unwrap()
+}
+
+// Generate parameters for the fixed-capacity threshold circuit
+pub fn generate_threshold_parameters() -> groth16::Parameters<Bls12> {
+    groth16::generate_random_parameters::<Bls12, _, _>(empty_threshold_circuit(), &mut thread_rng()).unwrap()
+}
+
+// Threshold circuit instance of a block that fits a single chunk, with
+// `values` attached to its leaves, against `threshold`
+fn threshold_circuit(chunk: &ChunkWitness, values_seed: Fr, values: &[u64], threshold: u64) -> ThresholdCircuit {
+    ThresholdCircuit {
+        seed: Some(chunk.seed),
+        values_seed: Some(values_seed),
+        leaves: present_leaves(&chunk.leaves, values),
+        threshold: Some(threshold),
+    }
+}
+
+// Proves that the values attached to the leaves of a block that fits a single
+// chunk add up to at least `threshold`, or to less than it, whichever holds
+pub fn prove_threshold(
+    params: &groth16::Parameters<Bls12>,
+    chunk: &ChunkWitness,
+    values_seed: Fr,
+    values: &[u64],
+    threshold: u64,
+) -> groth16::Proof<Bls12> {
+    let circuit = threshold_circuit(chunk, values_seed, values, threshold);
+    groth16::create_random_proof(circuit, params, &mut thread_rng()).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::witness::{self, BlockWitness, WitnessAccumulator};
+    use bellman::gadgets::test::TestConstraintSystem;
+
+    fn random_leaves(count: usize) -> Vec<Fr> {
+        (0..count).map(|_| Fr::random(thread_rng())).collect()
+    }
+
+    fn block_witness(leaves: &[Fr]) -> BlockWitness {
+        let mut accumulator = WitnessAccumulator::new(Fr::random(thread_rng()), usize::MAX);
+        for &leaf in leaves {
+            accumulator.push(leaf).unwrap();
+        }
+        accumulator.finish(Fr::random(thread_rng())).unwrap()
+    }
+
+    fn synthesize<C: Circuit<Fr>>(circuit: C) -> TestConstraintSystem<Fr> {
+        let mut cs = TestConstraintSystem::new();
+        circuit.synthesize(&mut cs).unwrap();
+        cs
+    }
+
+    #[test]
+    fn block_circuit_commits_to_leaves_and_chains_root() {
+        let witness = block_witness(&random_leaves(3));
+        let chunk = &witness.chunks[0];
+        let mut cs = synthesize(block_circuit(chunk));
+        assert!(cs.is_satisfied(), "{:?}", cs.which_is_unsatisfied());
+        assert!(cs.verify(&[chunk.seed, chunk.commitment, chunk.old_root, chunk.new_root()]));
+        assert!(!cs.verify(&[chunk.seed, chunk.commitment + Fr::ONE, chunk.old_root, chunk.new_root()]));
+        assert!(!cs.verify(&[chunk.seed, chunk.commitment, Fr::ZERO, chunk.new_root()]));
+
+        cs.set("leaf 1/leaf", Fr::random(thread_rng()));
+        assert_eq!(cs.which_is_unsatisfied(), Some("leaf 1/t^2 constraint"));
+    }
+
+    #[test]
+    fn block_circuit_proves_every_chunk_of_a_split_block() {
+        let witness = block_witness(&random_leaves(CIRCUIT_CAPACITY + 1));
+        assert_eq!(witness.chunks.len(), 2);
+        for chunk in witness.chunks.iter().chain(&witness.aggregate) {
+            let cs = synthesize(block_circuit(chunk));
+            assert!(cs.is_satisfied(), "{:?}", cs.which_is_unsatisfied());
+            assert!(cs.verify(&[chunk.seed, chunk.commitment, chunk.old_root, chunk.new_root()]));
+        }
+    }
+
+    #[test]
+    fn sum_circuit_sums_values_over_the_block_leaves() {
+        let witness = block_witness(&random_leaves(3));
+        let sum = witness::sum_witnesses(&witness, Fr::random(thread_rng()), &[3, 4, u64::MAX / 2]).remove(0);
+        let mut cs = synthesize(sum_circuit(&sum));
+        assert!(cs.is_satisfied(), "{:?}", cs.which_is_unsatisfied());
+        let total = Fr::from(7 + u64::MAX / 2);
+        let inputs = [sum.seed, sum.values_seed, witness.chunks[0].commitment, sum.values_commitment, total];
+        assert!(cs.verify(&inputs));
+        for tampered in [2, 3, 4] {
+            let mut tampered_inputs = inputs;
+            tampered_inputs[tampered] += Fr::ONE;
+            assert!(!cs.verify(&tampered_inputs));
+        }
+
+        // Padding leaves carry no value
+        cs.set("leaf 3/value", Fr::ONE);
+        cs.set("leaf 3/value bits/bit 0", Fr::ONE);
+        assert_eq!(cs.which_is_unsatisfied(), Some("leaf 3/padding value"));
+    }
+
+    #[test]
+    fn sum_circuit_range_checks_values() {
+        let witness = block_witness(&random_leaves(2));
+        let sum = witness::sum_witnesses(&witness, Fr::random(thread_rng()), &[15, 8]).remove(0);
+        let mut cs = synthesize(sum_circuit(&sum));
+        cs.set("leaf 0/value", -Fr::ONE);
+        assert_eq!(cs.which_is_unsatisfied(), Some("leaf 0/value range"));
+    }
+
+    #[test]
+    fn threshold_circuit_proves_the_side_of_its_threshold() {
+        let witness = block_witness(&random_leaves(3));
+        let (chunk, values_seed, values) = (&witness.chunks[0], Fr::random(thread_rng()), [15, 0, 8]);
+        let values_commitment = witness::values_commitment(values_seed, &chunk.leaves, &values);
+        for (threshold, above) in [(20, true), (23, true), (24, false), (u64::MAX, false)] {
+            let cs = synthesize(threshold_circuit(chunk, values_seed, &values, threshold));
+            assert!(cs.is_satisfied(), "{:?}", cs.which_is_unsatisfied());
+            let (threshold_input, above_input) = (Fr::from(threshold), Fr::from(above as u64));
+            let inputs = [chunk.seed, values_seed, chunk.commitment, values_commitment, threshold_input, above_input];
+            assert!(cs.verify(&inputs));
+            // The leaves are the block's
+            let mut other_block = inputs;
+            other_block[2] += Fr::ONE;
+            assert!(!cs.verify(&other_block));
+        }
+    }
+
+    #[test]
+    fn threshold_circuit_rejects_a_false_claim() {
+        let witness = block_witness(&random_leaves(2));
+        let (chunk, values_seed) = (&witness.chunks[0], Fr::random(thread_rng()));
+        let mut cs = synthesize(threshold_circuit(chunk, values_seed, &[15, 8], 20));
+        cs.set("above", Fr::ZERO);
+        assert_eq!(cs.which_is_unsatisfied(), Some("threshold constraint"));
+
+        let mut cs = synthesize(threshold_circuit(chunk, values_seed, &[15, 8], 24));
+        cs.set("above", Fr::ONE);
+        assert_eq!(cs.which_is_unsatisfied(), Some("threshold constraint"));
+    }
+
+    #[test]
+    fn threshold_circuit_range_checks_values() {
+        let witness = block_witness(&random_leaves(2));
+        let mut cs = synthesize(threshold_circuit(&witness.chunks[0], Fr::random(thread_rng()), &[15, 8], 20));
+        cs.set("leaf 0/value", -Fr::ONE);
+        assert_eq!(cs.which_is_unsatisfied(), Some("leaf 0/value range"));
+    }
+
+    #[test]
+    fn totals_circuit_counts_leaves_and_sums_values() {
+        let witness = block_witness(&random_leaves(3));
+        let totals = witness::totals_witnesses(&witness, &[7, u64::MAX / 4, 0]).remove(0);
+        let cs = synthesize(totals_circuit(&totals));
+        assert!(cs.is_satisfied(), "{:?}", cs.which_is_unsatisfied());
+        assert!(cs.verify(&[
+            totals.seed,
+            witness.chunks[0].commitment,
+            totals.values_commitment,
+            Fr::from(3),
+            Fr::from(7 + u64::MAX / 4),
+        ]));
+    }
+
+    #[test]
+    fn totals_circuit_does_not_count_padding() {
+        let witness = block_witness(&random_leaves(3));
+        let totals = witness::totals_witnesses(&witness, &[1, 2, 3]).remove(0);
+        let mut cs = synthesize(totals_circuit(&totals));
+        cs.set("leaf 3/present", Fr::ONE);
+        cs.set("leaf 3/leaf inverse", Fr::ONE);
+        assert_eq!(cs.which_is_unsatisfied(), Some("leaf 3/present leaf"));
+    }
+
+    #[test]
+    fn totals_circuit_range_checks_values() {
+        let witness = block_witness(&random_leaves(3));
+        let totals = witness::totals_witnesses(&witness, &[1, 2, 3]).remove(0);
+        let mut cs = synthesize(totals_circuit(&totals));
+        cs.set("leaf 0/value", -Fr::ONE);
+        assert_eq!(cs.which_is_unsatisfied(), Some("leaf 0/value range"));
+    }
+}
@@ -0,0 +1,95 @@
+use clap::ValueEnum;
+use serde::Deserialize;
+use std::fmt;
+
+use crate::config::Config;
+use crate::listener::rpc_client;
+use crate::manifest::Manifest;
+
+// Public Solana clusters, with the endpoints and genesis hash of each.
+// `rpc_url` and `ws_url` in the config override the preset endpoints.
+#[derive(Deserialize, ValueEnum, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub enum Cluster {
+    MainnetBeta,
+    Testnet,
+    Devnet,
+    Localnet,
+}
+
+impl Cluster {
+    pub fn rpc_url(self) -> &'static str {
+        match self {
+            Cluster::MainnetBeta => "https://api.mainnet-beta.solana.com",
+            Cluster::Testnet => "https://api.testnet.solana.com",
+            Cluster::Devnet => "https://api.devnet.solana.com",
+            Cluster::Localnet => "http://127.0.0.1:8899",
+        }
+    }
+
+    pub fn ws_url(self) -> &'static str {
+        match self {
+            Cluster::MainnetBeta => "wss://api.mainnet-beta.solana.com",
+            Cluster::Testnet => "wss://api.testnet.solana.com",
+            Cluster::Devnet => "wss://api.devnet.solana.com",
+            Cluster::Localnet => "ws://127.0.0.1:8900",
+        }
+    }
+
+    // `None` for a local validator, whose genesis is created when it first starts
+    pub fn genesis_hash(self) -> Option<&'static str> {
+        match self {
+            Cluster::MainnetBeta => Some("5eykt4UsFv8P8NJdTREpY1vzqKqZKvdpKuc147dw2N9d"),
+            Cluster::Testnet => Some("4uhcVJyU9pJkvQyS88uRDiswHXSCkY3zQawwpjk2NsNY"),
+            Cluster::Devnet => Some("EtWTRABZaYq6iMfeYKouRu166VU2xqa1wcaWoxPkrZBG"),
+            Cluster::Localnet => None,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum GenesisError {
+    Rpc(String),
+    // The endpoint is not on the configured cluster
+    WrongCluster { cluster: Cluster, actual: String },
+    // The proofs directory was filled from another cluster
+    WrongArchive { archived: String, actual: String },
+}
+
+impl fmt::Display for GenesisError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GenesisError::Rpc(e) => write!(f, "unable to fetch the genesis hash: {}", e),
+            GenesisError::WrongCluster { cluster, actual } => write!(
+                f,
+                "endpoint has genesis hash {}, which is not {}",
+                actual,
+                cluster.to_possible_value().unwrap().get_name()
+            ),
+            GenesisError::WrongArchive { archived, actual } => write!(
+                f,
+                "proofs directory holds blocks of genesis {}, but the endpoint has genesis {}",
+                archived, actual
+            ),
+        }
+    }
+}
+
+// Checks that the RPC endpoint belongs to the configured cluster and to the
+// cluster the proofs directory was filled from, returning its genesis hash
+pub fn validate_genesis(config: &Config) -> Result<String, GenesisError> {
+    let client = rpc_client(config.rpc_url(), config.rpc_timeouts.request_secs);
+    let actual = client.get_genesis_hash().map_err(|e| GenesisError::Rpc(e.to_string()))?.to_string();
+
+    if let Some(cluster) = config.cluster {
+        if cluster.genesis_hash().is_some_and(|expected| expected != actual) {
+            return Err(GenesisError::WrongCluster { cluster, actual });
+        }
+    }
+    if let Some(archived) = Manifest::load(&config.proofs_dir).and_then(|manifest| manifest.genesis_hash) {
+        if archived != actual {
+            return Err(GenesisError::WrongArchive { archived, actual });
+        }
+    }
+    Ok(actual)
+}
@@ -0,0 +1,1109 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::cluster::Cluster;
+
+// Listener configuration, loaded from a TOML file
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub struct Config {
+    // Cluster whose preset endpoints are used and whose genesis hash the
+    // endpoint must have; a local validator if unset
+    pub cluster: Option<Cluster>,
+    // Override the cluster's endpoints, e.g. for a private RPC provider
+    pub rpc_url: Option<String>,
+    pub ws_url: Option<String>,
+    pub rpc_timeouts: RpcTimeoutConfig,
+    // How new slots are discovered: by polling `getSlot`, or from a rooted
+    // slot subscription on `ws_url`
+    pub ingestion: Ingestion,
+    // Request the block of the next slot this many milliseconds before the tip
+    // is expected to reach it, going by the recent slot time, instead of
+    // waiting to see the tip reach it first
+    pub prefetch_lead_ms: Option<u64>,
+    // Blocks fetched ahead of the prover while it catches up
+    pub queue: QueueConfig,
+    // Independent endpoint every block is also fetched from. Slots where the two
+    // disagree are flagged in the index instead of being proved.
+    pub cross_check_rpc_url: Option<String>,
+    // Archival endpoint (e.g. one backed by BigTable long-term storage) that
+    // blocks already purged from the ledger of `rpc_url` are fetched from
+    pub archive_rpc_url: Option<String>,
+    pub proofs_dir: PathBuf,
+    // Groth16 parameters, generated on first run if missing
+    pub params_path: PathBuf,
+    // Parameters of the sum circuit, used by proofs over summed values
+    pub sum_params_path: PathBuf,
+    // Parameters of the totals circuit, used by `prove_block_totals`
+    pub totals_params_path: PathBuf,
+    // Parameters of the threshold circuit, used by `volume_thresholds`
+    pub threshold_params_path: PathBuf,
+    // Parameters of the signature circuit, used by `prove_signatures`
+    pub signature_params_path: PathBuf,
+    // Where to download the parameter files from when they are missing. The
+    // files must match the pinned hash, downloaded or not.
+    pub params_source: Option<ParamsSource>,
+    pub sum_params_source: Option<ParamsSource>,
+    pub totals_params_source: Option<ParamsSource>,
+    pub threshold_params_source: Option<ParamsSource>,
+    pub signature_params_source: Option<ParamsSource>,
+    // Operator keypair that co-signs every proof produced by this instance
+    pub signing_keypair: Option<PathBuf>,
+    // Upper bound on the per-block data held while building its witness, in bytes
+    pub max_block_memory: usize,
+    // Blocks with more transactions than this are handled by `oversized_blocks`
+    pub max_txs_per_block: Option<usize>,
+    pub oversized_blocks: OversizedPolicy,
+    // Mix the slot leader's identity into the circuit seed, making it part of
+    // the proof's public inputs rather than metadata alone
+    pub bind_leader: bool,
+    // Commit each transaction leaf to the hash of its serialized message
+    // (accounts and instructions) as well as its signature
+    pub bind_messages: bool,
+    // Hash function of the Merkle trees over transactions and recorded activity
+    pub commitment_hash: CommitmentHash,
+    // Record the stake that has voted on and rooted each block, from getVoteAccounts
+    pub stake_evidence: bool,
+    // Prove the number of transactions in each block and the compute units
+    // they consumed, tied in the circuit to the block's transaction leaves
+    pub prove_block_totals: bool,
+    // Prove that the fees paid by the block's transactions add up to a total
+    pub prove_total_fees: bool,
+    // Prove that the first signature of each transaction verifies against its
+    // fee payer, with an ed25519 circuit proof per transaction. Needs a build
+    // with the `ed25519` feature.
+    pub prove_signatures: bool,
+    // Keep every block proved, compressed, in the proofs directory, so its
+    // proof can be derived again once the ledger is pruned
+    pub block_snapshots: bool,
+    // Keep the witness of every block proved, compressed, in the proofs
+    // directory, for `reproduce` to check against
+    pub witness_archive: bool,
+    // Selective disclosure: salt every transaction leaf and keep the signatures
+    // and inclusion paths here rather than in the published proofs
+    pub private_dir: Option<PathBuf>,
+    pub tokens: Option<TokenConfig>,
+    pub token_supply: Option<TokenSupplyConfig>,
+    pub sol_transfers: Option<SolTransferConfig>,
+    // Volumes of SOL or of a mint each block is proved to be above or below
+    pub volume_thresholds: Vec<VolumeThresholdConfig>,
+    pub balances: Option<BalanceConfig>,
+    pub account_state: Option<AccountStateConfig>,
+    pub stake_activity: Option<StakeActivityConfig>,
+    pub votes: Option<VoteConfig>,
+    pub filter: Option<FilterConfig>,
+    // Anchor programs whose instructions and events are decoded into the proofs
+    pub anchor: Vec<AnchorProgramConfig>,
+    pub storage: Option<StorageConfig>,
+    pub data_availability: Option<DataAvailabilityConfig>,
+    pub ethereum: Option<EthereumConfig>,
+    pub mqtt: Option<MqttConfig>,
+    pub redis: Option<RedisConfig>,
+    pub amqp: Option<AmqpConfig>,
+    // Further outputs block proofs are fanned out to, alongside the above
+    pub sinks: Vec<SinkConfig>,
+    pub coordination: Option<CoordinationConfig>,
+    pub election: Option<ElectionConfig>,
+    pub gossip: Option<GossipConfig>,
+    // Level of the listener's log output: off, error, warn, info, debug or trace
+    pub log_level: String,
+    pub admin: Option<AdminConfig>,
+    pub metrics: Option<MetricsConfig>,
+    pub api: Option<ApiConfig>,
+    pub alerts: Option<AlertConfig>,
+    // Seconds from a block's timestamp within which its proof should be
+    // complete. Proofs record their latency, and flag it when over the SLO.
+    pub latency_slo_secs: Option<u64>,
+    // Warn when the local clock and a block's timestamp are further apart than
+    // this when the block is processed. Proofs record the drift either way.
+    pub max_clock_drift_secs: Option<u64>,
+    // Slots of recent blocks whose transaction signatures are remembered, to
+    // catch a signature seen in more than one block
+    pub duplicate_signature_window: Option<u64>,
+    // Number of blocks proved at once, across every instance of the process
+    pub prover_threads: usize,
+    // Seconds a block may take to prove before its proof is cancelled and the
+    // slot recorded as timed out
+    pub proving_timeout_secs: Option<u64>,
+    // Heap the process may use, in MiB, before proofs stop running in parallel
+    pub prover_memory_budget_mb: Option<u64>,
+    pub prover_cpu: Option<ProverCpuConfig>,
+    pub schedule: Option<ScheduleConfig>,
+    pub rpc_costs: Option<RpcCostConfig>,
+    // Shared libraries of transaction processors, loaded at startup
+    pub plugins: Vec<PathBuf>,
+    // Clusters followed by this process, each on the settings above with its
+    // own endpoints and archive. Without instances, one listener follows the
+    // top-level endpoints.
+    pub instances: Vec<InstanceConfig>,
+    // Proving profiles run side by side on the one cluster, each on the
+    // settings above with its own filter, circuit settings and sinks. Blocks
+    // are fetched once for all of them.
+    pub profiles: Vec<ProfileConfig>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            cluster: None,
+            rpc_url: None,
+            ws_url: None,
+            rpc_timeouts: RpcTimeoutConfig::default(),
+            ingestion: Ingestion::Poll,
+            prefetch_lead_ms: None,
+            queue: QueueConfig::default(),
+            cross_check_rpc_url: None,
+            archive_rpc_url: None,
+            proofs_dir: PathBuf::from("proofs"),
+            params_path: PathBuf::from("params.bin"),
+            sum_params_path: PathBuf::from("sum_params.bin"),
+            totals_params_path: PathBuf::from("totals_params.bin"),
+            threshold_params_path: PathBuf::from("threshold_params.bin"),
+            signature_params_path: PathBuf::from("signature_params.bin"),
+            params_source: None,
+            sum_params_source: None,
+            totals_params_source: None,
+            threshold_params_source: None,
+            signature_params_source: None,
+            signing_keypair: None,
+            max_block_memory: 64 * 1024 * 1024,
+            max_txs_per_block: None,
+            oversized_blocks: OversizedPolicy::Chunk,
+            bind_leader: false,
+            bind_messages: false,
+            commitment_hash: CommitmentHash::Sha256,
+            stake_evidence: false,
+            prove_block_totals: false,
+            prove_total_fees: false,
+            prove_signatures: false,
+            block_snapshots: false,
+            witness_archive: false,
+            private_dir: None,
+            tokens: None,
+            token_supply: None,
+            sol_transfers: None,
+            volume_thresholds: Vec::new(),
+            balances: None,
+            account_state: None,
+            stake_activity: None,
+            votes: None,
+            filter: None,
+            anchor: Vec::new(),
+            storage: None,
+            data_availability: None,
+            ethereum: None,
+            mqtt: None,
+            redis: None,
+            amqp: None,
+            sinks: Vec::new(),
+            coordination: None,
+            election: None,
+            gossip: None,
+            log_level: "info".to_string(),
+            admin: None,
+            metrics: None,
+            api: None,
+            alerts: None,
+            latency_slo_secs: None,
+            max_clock_drift_secs: None,
+            duplicate_signature_window: None,
+            prover_threads: 1,
+            proving_timeout_secs: None,
+            prover_memory_budget_mb: None,
+            prover_cpu: None,
+            schedule: None,
+            rpc_costs: None,
+            plugins: Vec::new(),
+            instances: Vec::new(),
+            profiles: Vec::new(),
+        }
+    }
+}
+
+// Timeouts of RPC requests, in seconds. A slot or block request that times
+// out is retried on the next poll instead of being recorded as a failure.
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub struct RpcTimeoutConfig {
+    pub get_slot_secs: u64,
+    pub get_block_secs: u64,
+    // Every other request
+    pub request_secs: u64,
+    // Silence after which the websocket connection is considered dropped
+    pub ws_idle_secs: u64,
+}
+
+impl Default for RpcTimeoutConfig {
+    fn default() -> Self {
+        RpcTimeoutConfig {
+            get_slot_secs: 10,
+            get_block_secs: 60,
+            request_secs: 30,
+            ws_idle_secs: 10,
+        }
+    }
+}
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum Ingestion {
+    Poll,
+    Subscribe,
+}
+
+// What is done with a block over `max_txs_per_block`: not proving it, proving
+// only its first `max_txs_per_block` transactions, or proving all of it split
+// into circuit chunks. Proofs record the policy applied to them.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub enum OversizedPolicy {
+    Skip,
+    TruncateWithFlag,
+    Chunk,
+}
+
+// Hash function of the Merkle trees (the transactions root, the sorted root and
+// the roots of recorded activity), for consumers that check inclusion somewhere
+// SHA-256 is costly, such as in a circuit. Proofs record the hash they were
+// made with, and are checked with it.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Default, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum CommitmentHash {
+    #[default]
+    Sha256,
+    Blake3,
+    // Poseidon over BN254, as the Solana runtime computes it
+    Poseidon,
+}
+
+impl CommitmentHash {
+    pub fn is_sha256(&self) -> bool {
+        *self == CommitmentHash::Sha256
+    }
+}
+
+// Up to `capacity` fetched blocks are held in memory. Further blocks are
+// spilled to the proofs directory, and with `spill_limit` set, fetching waits
+// while that many are spilled.
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub struct QueueConfig {
+    pub capacity: usize,
+    pub spill_limit: Option<usize>,
+}
+
+impl Default for QueueConfig {
+    fn default() -> Self {
+        QueueConfig { capacity: 16, spill_limit: None }
+    }
+}
+
+#[derive(Deserialize, Clone)]
+pub struct ParamsSource {
+    pub url: String,
+    // Hex SHA-256 of the parameters file
+    pub sha256: String,
+}
+
+// SPL token transfer extraction. Every transfer is recorded in the proof, and
+// each of `mints` also gets a commitment and proof over its transfers.
+#[derive(Deserialize, Clone)]
+pub struct TokenConfig {
+    #[serde(default)]
+    pub mints: Vec<String>,
+}
+
+// Token supply attestation: the supply of each of `mints` is read when a block
+// is fetched, at its slot or later, and recorded in its proof. With `bind`, it
+// is also mixed into the circuit seed, making it a public input of the proof.
+#[derive(Deserialize, Clone)]
+pub struct TokenSupplyConfig {
+    pub mints: Vec<String>,
+    #[serde(default)]
+    pub bind: bool,
+}
+
+// System-program SOL transfer decoding. The total moved per block is recorded
+// in its proof, with sent/received flows for each of `accounts`. `prove_sum`
+// adds a sum circuit proof of the total.
+#[derive(Deserialize, Clone)]
+pub struct SolTransferConfig {
+    #[serde(default)]
+    pub accounts: Vec<String>,
+    #[serde(default)]
+    pub prove_sum: bool,
+}
+
+// Threshold attestation over transfer volume: every block's proof shows the
+// SOL (or `mint`) moved by its transfers is at or above `threshold`, or below
+// it, without revealing the amounts
+#[derive(Deserialize, Clone)]
+pub struct VolumeThresholdConfig {
+    #[serde(default)]
+    pub mint: Option<String>,
+    pub threshold: u64,
+}
+
+// Lamport balance tracking: the pre/post balance delta of each of `accounts`
+// is recorded per block. `prove_sum` adds sum circuit proofs of the increases
+// and decreases, whose difference is the summed delta.
+#[derive(Deserialize, Clone)]
+pub struct BalanceConfig {
+    pub accounts: Vec<String>,
+    #[serde(default)]
+    pub prove_sum: bool,
+}
+
+// Account state: once each block is proved, the owner, lamports and data hash
+// of each of `accounts` are read at that slot or later and committed to in a
+// Merkle tree, with an inclusion path per account in the proof
+#[derive(Deserialize, Clone)]
+pub struct AccountStateConfig {
+    pub accounts: Vec<String>,
+}
+
+// Stake program activity: delegations and deactivations are recorded per
+// block. With `only_matching`, blocks without activity signed by one of
+// `authorities` are not proved, and are recorded as empty in the slot index.
+#[derive(Deserialize, Clone)]
+pub struct StakeActivityConfig {
+    #[serde(default)]
+    pub authorities: Vec<String>,
+    #[serde(default)]
+    pub only_matching: bool,
+}
+
+// Vote transactions: the votes of `validators` (vote account addresses, or
+// every validator if empty) are recorded per block with a commitment and proof
+// over them. With `only_matching`, blocks without such a vote are not proved,
+// and are recorded as empty in the slot index.
+#[derive(Deserialize, Clone)]
+pub struct VoteConfig {
+    #[serde(default)]
+    pub validators: Vec<String>,
+    #[serde(default)]
+    pub only_matching: bool,
+}
+
+// Transaction filter: a transaction matches when it invokes one of `programs`,
+// references one of `accounts` or holds a token balance in one of `mints`.
+// With `skip_empty`, blocks without a matching transaction are not proved and
+// are recorded as empty in the slot index.
+#[derive(Deserialize, Clone)]
+pub struct FilterConfig {
+    #[serde(default)]
+    pub programs: Vec<String>,
+    #[serde(default)]
+    pub accounts: Vec<String>,
+    #[serde(default)]
+    pub mints: Vec<String>,
+    #[serde(default)]
+    pub skip_empty: bool,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct AnchorProgramConfig {
+    pub program_id: String,
+    pub idl_path: PathBuf,
+}
+
+// Object storage sink for proof files. `options` are passed to the backend
+// builder as-is (e.g. `aws_region`, `google_service_account`, `azure_storage_access_key`),
+// on top of whatever credentials are found in the environment.
+#[derive(Deserialize, Clone, PartialEq)]
+pub struct StorageConfig {
+    pub backend: StorageBackend,
+    pub bucket: String,
+    #[serde(default)]
+    pub prefix: String,
+    #[serde(default)]
+    pub options: HashMap<String, String>,
+}
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum StorageBackend {
+    S3,
+    Gcs,
+    Azure,
+}
+
+// Data-availability layer each block proof is posted to as a blob, under
+// `namespace` (hex, up to 10 bytes). Blobs hold the proof's commitments and
+// roots, or with `full_proofs` the whole proof file.
+#[derive(Deserialize, Clone)]
+pub struct DataAvailabilityConfig {
+    pub backend: DaBackend,
+    // JSON-RPC endpoint of the node blobs are submitted through
+    pub rpc_url: String,
+    #[serde(default)]
+    pub auth_token: Option<String>,
+    pub namespace: String,
+    #[serde(default)]
+    pub full_proofs: bool,
+    // Gas price in utia; the node estimates one if unset
+    #[serde(default)]
+    pub gas_price: Option<f64>,
+}
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum DaBackend {
+    Celestia,
+}
+
+// Ethereum contract the accumulator root is submitted to every `batch_blocks`
+// proved blocks, signed with the hex secp256k1 key in `key_path`. The contract
+// is called as `submitRoot(uint64 fromSlot, uint64 toSlot, bytes32 oldRoot,
+// bytes32 newRoot)` with the roots before and after the batch.
+#[derive(Deserialize, Clone)]
+pub struct EthereumConfig {
+    pub rpc_url: String,
+    pub chain_id: u64,
+    pub contract: String,
+    pub key_path: PathBuf,
+    #[serde(default = "default_batch_blocks")]
+    pub batch_blocks: u64,
+    #[serde(default = "default_gas_limit")]
+    pub gas_limit: u64,
+    #[serde(default)]
+    pub gas: GasStrategy,
+    // Fee caps in gwei. The base fee is doubled for the `node` strategy, with
+    // the node's suggested tip on top, but never above `max_fee_gwei`.
+    #[serde(default)]
+    pub max_fee_gwei: Option<u64>,
+    #[serde(default)]
+    pub priority_fee_gwei: Option<u64>,
+}
+
+fn default_batch_blocks() -> u64 {
+    100
+}
+
+fn default_gas_limit() -> u64 {
+    100_000
+}
+
+// How transaction fees are set: from the base fee and tip the node reports, or
+// fixed at `max_fee_gwei` and `priority_fee_gwei`
+#[derive(Deserialize, Clone, Copy, PartialEq, Default, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum GasStrategy {
+    #[default]
+    Node,
+    Fixed,
+}
+
+// MQTT broker a summary of every block proof is published to, for devices
+// that subscribe to attestations rather than poll for proof files. Summaries
+// carry the URL of the proof file when `proof_url` (the URL proofs_dir is
+// served or uploaded under) is set.
+#[derive(Deserialize, Clone)]
+pub struct MqttConfig {
+    pub host: String,
+    #[serde(default = "default_mqtt_port")]
+    pub port: u16,
+    #[serde(default)]
+    pub tls: bool,
+    #[serde(default = "default_mqtt_client_id")]
+    pub client_id: String,
+    #[serde(default)]
+    pub username: Option<String>,
+    #[serde(default)]
+    pub password: Option<String>,
+    #[serde(default = "default_mqtt_topic")]
+    pub topic: String,
+    // 0 (at most once), 1 (at least once) or 2 (exactly once)
+    #[serde(default = "default_mqtt_qos")]
+    pub qos: u8,
+    // Has the broker keep the latest summary for new subscribers
+    #[serde(default)]
+    pub retain: bool,
+    #[serde(default)]
+    pub proof_url: Option<String>,
+}
+
+fn default_mqtt_port() -> u16 {
+    1883
+}
+
+fn default_mqtt_client_id() -> String {
+    "solana-listener".to_string()
+}
+
+fn default_mqtt_topic() -> String {
+    "solana-listener/proofs".to_string()
+}
+
+fn default_mqtt_qos() -> u8 {
+    1
+}
+
+// Redis server every block proof is published to on `channel`. With
+// `cache_proofs` above 0 the latest that many proofs are also kept in the
+// sorted set `<key_prefix>:proofs`, scored by slot, for readers that want
+// recent proofs without going to the proofs directory or object storage.
+#[derive(Deserialize, Clone)]
+pub struct RedisConfig {
+    pub url: String,
+    #[serde(default = "default_redis_channel")]
+    pub channel: String,
+    #[serde(default)]
+    pub cache_proofs: usize,
+    #[serde(default = "default_redis_key_prefix")]
+    pub key_prefix: String,
+}
+
+fn default_redis_channel() -> String {
+    "solana-listener:proofs".to_string()
+}
+
+fn default_redis_key_prefix() -> String {
+    "solana-listener".to_string()
+}
+
+// AMQP broker (e.g. RabbitMQ) every block proof is published to, as a
+// persistent message on `exchange` with `routing_key`. Publishing waits for
+// the broker's confirmation; a proof it rejects, cannot route or does not
+// confirm within `confirm_timeout_secs` is tried `max_attempts` times, then
+// sent to `dead_letter_exchange`, or kept with the sink's dead letters if that
+// is unset or fails too.
+#[derive(Deserialize, Clone)]
+pub struct AmqpConfig {
+    pub url: String,
+    #[serde(default)]
+    pub exchange: String,
+    #[serde(default = "default_amqp_routing_key")]
+    pub routing_key: String,
+    #[serde(default = "default_confirm_timeout_secs")]
+    pub confirm_timeout_secs: u64,
+    #[serde(default = "default_amqp_max_attempts")]
+    pub max_attempts: u32,
+    #[serde(default)]
+    pub dead_letter_exchange: Option<String>,
+    // Routing key for dead letters, `routing_key` if unset
+    #[serde(default)]
+    pub dead_letter_routing_key: Option<String>,
+}
+
+fn default_amqp_routing_key() -> String {
+    "solana-listener.proofs".to_string()
+}
+
+fn default_confirm_timeout_secs() -> u64 {
+    30
+}
+
+fn default_amqp_max_attempts() -> u32 {
+    3
+}
+
+// An output every block proof is delivered to, with its own queue: a
+// directory the proof files are copied to, a webhook they are POSTed to, or a
+// Kafka topic they are produced to. A delivery that fails is retried up to
+// `max_attempts` times.
+#[derive(Deserialize, Clone)]
+pub struct SinkConfig {
+    pub kind: SinkKind,
+    // Label of the sink in logs and metrics, and the name of its dead-letter
+    // store, its kind if unset. Names must be unique across the sinks.
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub dir: Option<PathBuf>,
+    #[serde(default)]
+    pub url: Option<String>,
+    #[serde(default)]
+    pub bearer_token: Option<String>,
+    // Bootstrap brokers ("host:port"), topic and partition of a Kafka sink
+    #[serde(default)]
+    pub brokers: Vec<String>,
+    #[serde(default)]
+    pub topic: Option<String>,
+    #[serde(default)]
+    pub partition: i32,
+    #[serde(default = "default_sink_max_attempts")]
+    pub max_attempts: u32,
+}
+
+impl SinkConfig {
+    pub fn name(&self) -> &str {
+        self.name.as_deref().unwrap_or(self.kind.name())
+    }
+}
+
+fn default_sink_max_attempts() -> u32 {
+    5
+}
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum SinkKind {
+    File,
+    Webhook,
+    Kafka,
+}
+
+impl SinkKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            SinkKind::File => "file",
+            SinkKind::Webhook => "webhook",
+            SinkKind::Kafka => "kafka",
+        }
+    }
+}
+
+// Slot-sharded proving across several listener instances. Workers claim
+// `shard_size` slot ranges from a lease directory they all share.
+#[derive(Deserialize, Clone)]
+pub struct CoordinationConfig {
+    pub worker_id: String,
+    pub lease_dir: PathBuf,
+    #[serde(default = "default_shard_size")]
+    pub shard_size: u64,
+    #[serde(default)]
+    pub start_slot: u64,
+    #[serde(default = "default_lease_ttl_secs")]
+    pub lease_ttl_secs: u64,
+}
+
+// Hot-standby operation: instances pointed at the same proofs directory elect
+// a single leader through `lock_path`, and only the leader proves.
+#[derive(Deserialize, Clone)]
+pub struct ElectionConfig {
+    pub instance_id: String,
+    pub lock_path: PathBuf,
+    #[serde(default = "default_election_ttl_secs")]
+    pub ttl_secs: u64,
+}
+
+// Proof distribution between listener nodes: proofs are pushed to `peers` as
+// they are produced, and every `sync_interval_secs` each node asks its peers
+// for proofs it is missing among the last `sync_window` slots it knows of.
+#[derive(Deserialize, Clone)]
+pub struct GossipConfig {
+    pub listen_addr: String,
+    #[serde(default)]
+    pub peers: Vec<String>,
+    #[serde(default = "default_sync_interval_secs")]
+    pub sync_interval_secs: u64,
+    #[serde(default = "default_sync_window")]
+    pub sync_window: u64,
+}
+
+// HTTP API for controlling a running listener. Every request must carry
+// `Authorization: Bearer <token>`.
+#[derive(Deserialize, Clone)]
+pub struct AdminConfig {
+    pub listen_addr: String,
+    pub token: String,
+}
+
+// Prometheus endpoint, served at `GET /metrics`
+#[derive(Deserialize, Clone)]
+pub struct MetricsConfig {
+    pub listen_addr: String,
+}
+
+// Cores the proving threads are pinned to, one circuit worker per core, and
+// the nice value they run at (0 to 19, higher yielding more to other processes)
+#[derive(Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct ProverCpuConfig {
+    pub cores: Vec<usize>,
+    pub nice: Option<i32>,
+}
+
+// Times of the UTC day blocks are proved in, as windows such as "02:00-06:00".
+// Blocks more than `backfill_lag_slots` behind the tip are backfill, proved
+// during `backfill_windows`, and the rest during `realtime_windows`; without
+// windows, either is proved at any time. Blocks are proved in slot order, so
+// one held back until its window opens holds back those after it as well.
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub struct ScheduleConfig {
+    pub realtime_windows: Vec<String>,
+    pub backfill_windows: Vec<String>,
+    pub backfill_lag_slots: u64,
+}
+
+impl Default for ScheduleConfig {
+    fn default() -> Self {
+        ScheduleConfig {
+            realtime_windows: Vec::new(),
+            backfill_windows: Vec::new(),
+            // About a minute of slots
+            backfill_lag_slots: 150,
+        }
+    }
+}
+
+// Credits RPC providers charge for the listener's calls, for plans billed by
+// usage. A call costs the weight of its method, or `default_weight`. Providers
+// over `daily_budget` credits in the UTC day have their calls spaced
+// `throttle_interval_ms` apart until the day ends.
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub struct RpcCostConfig {
+    pub default_weight: u64,
+    pub weights: HashMap<String, u64>,
+    pub daily_budget: Option<u64>,
+    pub throttle_interval_ms: u64,
+    // Weights and budgets of particular providers, by the host of their URL
+    pub providers: Vec<RpcProviderCostConfig>,
+}
+
+impl Default for RpcCostConfig {
+    fn default() -> Self {
+        RpcCostConfig {
+            default_weight: 1,
+            weights: HashMap::new(),
+            daily_budget: None,
+            throttle_interval_ms: 1000,
+            providers: Vec::new(),
+        }
+    }
+}
+
+#[derive(Deserialize, Clone)]
+pub struct RpcProviderCostConfig {
+    pub host: String,
+    // Weights by method, over the top-level ones
+    #[serde(default)]
+    pub weights: HashMap<String, u64>,
+    pub daily_budget: Option<u64>,
+}
+
+impl RpcCostConfig {
+    fn provider(&self, host: &str) -> Option<&RpcProviderCostConfig> {
+        self.providers.iter().find(|provider| provider.host == host)
+    }
+
+    pub fn weight(&self, host: &str, method: &str) -> u64 {
+        let weight = self.provider(host).and_then(|provider| provider.weights.get(method));
+        weight.or_else(|| self.weights.get(method)).copied().unwrap_or(self.default_weight)
+    }
+
+    pub fn budget(&self, host: &str) -> Option<u64> {
+        self.provider(host).and_then(|provider| provider.daily_budget).or(self.daily_budget)
+    }
+}
+
+// Read-only HTTP API over the proofs directory, with a web explorer at `/`
+#[derive(Deserialize, Clone)]
+pub struct ApiConfig {
+    pub listen_addr: String,
+    #[serde(default)]
+    pub rate_limit: Option<RateLimitConfig>,
+}
+
+// Requests a second each client IP address can make to the API, after a
+// burst of up to `burst`. Keys sent as `X-API-Key` have limits of their own.
+#[derive(Deserialize, Clone)]
+pub struct RateLimitConfig {
+    pub requests_per_sec: f64,
+    #[serde(default = "default_rate_limit_burst")]
+    pub burst: u32,
+    #[serde(default)]
+    pub keys: Vec<ApiKeyConfig>,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct ApiKeyConfig {
+    pub key: String,
+    pub requests_per_sec: f64,
+    #[serde(default = "default_rate_limit_burst")]
+    pub burst: u32,
+}
+
+fn default_rate_limit_burst() -> u32 {
+    20
+}
+
+// Chat webhooks notified when the listener falls more than `max_lag_slots`
+// behind the tip, a slot is recorded as failed, or a proof fails its
+// self-check. Each kind of alert is repeated at most every `cooldown_secs` per
+// instance.
+#[derive(Deserialize, Clone)]
+pub struct AlertConfig {
+    pub webhooks: Vec<WebhookConfig>,
+    #[serde(default)]
+    pub max_lag_slots: Option<u64>,
+    #[serde(default = "default_alert_cooldown_secs")]
+    pub cooldown_secs: u64,
+}
+
+// A Discord or Slack incoming webhook, or the Telegram Bot API `sendMessage`
+// URL of a bot, posting to `chat_id`
+#[derive(Deserialize, Clone)]
+pub struct WebhookConfig {
+    pub kind: WebhookKind,
+    pub url: String,
+    #[serde(default)]
+    pub chat_id: Option<String>,
+}
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum WebhookKind {
+    Discord,
+    Slack,
+    Telegram,
+}
+
+// One cluster followed by a multi-cluster process. Endpoints are the
+// instance's own rather than inherited. `proofs_dir` and `storage_prefix`
+// default to the top-level ones followed by the instance name.
+#[derive(Deserialize, Clone)]
+pub struct InstanceConfig {
+    pub name: String,
+    pub cluster: Option<Cluster>,
+    pub rpc_url: Option<String>,
+    pub ws_url: Option<String>,
+    pub cross_check_rpc_url: Option<String>,
+    pub archive_rpc_url: Option<String>,
+    pub proofs_dir: Option<PathBuf>,
+    pub storage_prefix: Option<String>,
+}
+
+// One of several proving profiles over the same blocks. Settings left unset
+// are the top-level ones. `proofs_dir` and `storage_prefix` default to the
+// top-level ones followed by the profile name.
+#[derive(Deserialize, Clone)]
+pub struct ProfileConfig {
+    pub name: String,
+    pub proofs_dir: Option<PathBuf>,
+    pub storage_prefix: Option<String>,
+    pub filter: Option<FilterConfig>,
+    pub max_txs_per_block: Option<usize>,
+    pub oversized_blocks: Option<OversizedPolicy>,
+    pub bind_leader: Option<bool>,
+    pub bind_messages: Option<bool>,
+    pub commitment_hash: Option<CommitmentHash>,
+    pub sinks: Option<Vec<SinkConfig>>,
+}
+
+fn default_alert_cooldown_secs() -> u64 {
+    600
+}
+
+fn default_sync_interval_secs() -> u64 {
+    30
+}
+
+fn default_sync_window() -> u64 {
+    1000
+}
+
+fn default_election_ttl_secs() -> u64 {
+    10
+}
+
+fn default_shard_size() -> u64 {
+    1000
+}
+
+fn default_lease_ttl_secs() -> u64 {
+    60
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(io::Error),
+    Parse(toml::de::Error),
+    // Parsed, but with a setting that cannot be acted on
+    Invalid(String),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Io(e) => write!(f, "unable to read config file: {}", e),
+            ConfigError::Parse(e) => write!(f, "unable to parse config file: {}", e),
+            ConfigError::Invalid(reason) => write!(f, "invalid config file: {}", reason),
+        }
+    }
+}
+
+impl Config {
+    pub fn rpc_url(&self) -> String {
+        let preset = self.cluster.unwrap_or(Cluster::Localnet).rpc_url();
+        self.rpc_url.clone().unwrap_or_else(|| preset.to_string())
+    }
+
+    pub fn ws_url(&self) -> String {
+        let preset = self.cluster.unwrap_or(Cluster::Localnet).ws_url();
+        self.ws_url.clone().unwrap_or_else(|| preset.to_string())
+    }
+
+    // Settings of the instance called `name`, `None` if there is no such instance
+    pub fn instance(&self, name: &str) -> Option<Config> {
+        let instance = self.instances.iter().find(|instance| instance.name == name)?;
+        let mut config = self.clone();
+        config.instances = Vec::new();
+        config.profiles = Vec::new();
+        config.cluster = instance.cluster;
+        config.rpc_url = instance.rpc_url.clone();
+        config.ws_url = instance.ws_url.clone();
+        config.cross_check_rpc_url = instance.cross_check_rpc_url.clone();
+        config.archive_rpc_url = instance.archive_rpc_url.clone();
+        config.place_under(name, instance.proofs_dir.as_ref(), instance.storage_prefix.as_ref());
+        Some(config)
+    }
+
+    // Settings of the profile called `name`, `None` if there is no such profile
+    pub fn profile(&self, name: &str) -> Option<Config> {
+        let profile = self.profiles.iter().find(|profile| profile.name == name)?;
+        let mut config = self.clone();
+        config.instances = Vec::new();
+        config.profiles = Vec::new();
+        config.place_under(name, profile.proofs_dir.as_ref(), profile.storage_prefix.as_ref());
+        if let Some(filter) = &profile.filter {
+            config.filter = Some(filter.clone());
+        }
+        if let Some(max_txs_per_block) = profile.max_txs_per_block {
+            config.max_txs_per_block = Some(max_txs_per_block);
+        }
+        config.oversized_blocks = profile.oversized_blocks.unwrap_or(config.oversized_blocks);
+        config.bind_leader = profile.bind_leader.unwrap_or(config.bind_leader);
+        config.bind_messages = profile.bind_messages.unwrap_or(config.bind_messages);
+        config.commitment_hash = profile.commitment_hash.unwrap_or(config.commitment_hash);
+        if let Some(sinks) = &profile.sinks {
+            config.sinks = sinks.clone();
+        }
+        Some(config)
+    }
+
+    // Moves the proofs directory and storage prefix of an instance or profile
+    // called `name` under the top-level ones, unless it has its own
+    fn place_under(&mut self, name: &str, proofs_dir: Option<&PathBuf>, storage_prefix: Option<&String>) {
+        self.proofs_dir = proofs_dir.cloned().unwrap_or_else(|| self.proofs_dir.join(name));
+        if let Some(storage) = &mut self.storage {
+            storage.prefix = match storage_prefix {
+                Some(prefix) => prefix.clone(),
+                None if storage.prefix.is_empty() => name.to_string(),
+                None => format!("{}/{}", storage.prefix.trim_end_matches('/'), name),
+            };
+        }
+    }
+
+    pub fn load(path: &Path) -> Config {
+        Self::try_load(path).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    // Like `load`, for reloading a running listener's config, where a broken
+    // file must not take the process down
+    pub fn try_load(path: &Path) -> Result<Config, ConfigError> {
+        let contents = fs::read_to_string(path).map_err(ConfigError::Io)?;
+        let config: Config = toml::from_str(&contents).map_err(ConfigError::Parse)?;
+        config.validate().map_err(ConfigError::Invalid)?;
+        Ok(config)
+    }
+
+    // Settings that parse but would make the listener fail once running
+    fn validate(&self) -> Result<(), String> {
+        if self.prove_signatures && !cfg!(feature = "ed25519") {
+            return Err("prove_signatures: this build has no signature circuit, needs --features ed25519".to_string());
+        }
+        if self.prove_signatures && self.private_dir.is_some() {
+            return Err("prove_signatures: signature proofs publish what private_dir keeps out".to_string());
+        }
+
+        if let Some(rate_limit) = self.api.as_ref().and_then(|api| api.rate_limit.as_ref()) {
+            let limits = [("api.rate_limit", rate_limit.requests_per_sec, rate_limit.burst)]
+                .into_iter()
+                .chain(rate_limit.keys.iter().map(|key| ("api.rate_limit.keys", key.requests_per_sec, key.burst)));
+            for (what, requests_per_sec, burst) in limits {
+                if !(requests_per_sec > 0.0 && requests_per_sec.is_finite()) {
+                    return Err(format!("{}: requests_per_sec must be positive", what));
+                }
+                if burst == 0 {
+                    return Err(format!("{}: burst must be at least 1", what));
+                }
+            }
+        }
+
+        // Each sink has a dead-letter store of its own, named after it
+        let builtin_sinks = [
+            self.storage.as_ref().map(|_| "storage"),
+            self.data_availability.as_ref().map(|_| "data_availability"),
+            self.mqtt.as_ref().map(|_| "mqtt"),
+            self.redis.as_ref().map(|_| "redis"),
+            self.amqp.as_ref().map(|_| "amqp"),
+        ];
+        let profile_sinks = self.profiles.iter().filter_map(|profile| {
+            profile.sinks.as_ref().map(|sinks| (format!("profiles.{}.sinks", profile.name), sinks))
+        });
+        for (what, sinks) in [("sinks".to_string(), &self.sinks)].into_iter().chain(profile_sinks) {
+            let mut names: HashSet<&str> = builtin_sinks.into_iter().flatten().collect();
+            for sink in sinks {
+                let name = sink.name();
+                if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+                    return Err(format!("{}: sink name {:?} may only hold letters, digits, - and _", what, name));
+                }
+                if !names.insert(name) {
+                    return Err(format!("{}: more than one sink is named {:?}; give each a distinct name", what, name));
+                }
+                let missing = match sink.kind {
+                    SinkKind::File => sink.dir.is_none().then_some("dir"),
+                    SinkKind::Webhook => sink.url.is_none().then_some("url"),
+                    SinkKind::Kafka if sink.brokers.is_empty() => Some("brokers"),
+                    SinkKind::Kafka => sink.topic.is_none().then_some("topic"),
+                };
+                if let Some(field) = missing {
+                    return Err(format!("{}: {} sink {:?} has no {}", what, sink.kind.name(), name, field));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn validate(contents: &str) -> Result<(), String> {
+        toml::from_str::<Config>(contents).unwrap().validate()
+    }
+
+    #[test]
+    fn sinks_do_not_share_a_dead_letter_store() {
+        let distinct = r#"
+            [[sinks]]
+            kind = "file"
+            dir = "a"
+            [[sinks]]
+            kind = "file"
+            name = "mirror"
+            dir = "b"
+        "#;
+        assert_eq!(validate(distinct), Ok(()));
+
+        // Both named after their kind
+        let shared = r#"
+            [[sinks]]
+            kind = "file"
+            dir = "a"
+            [[sinks]]
+            kind = "file"
+            dir = "b"
+        "#;
+        assert!(validate(shared).unwrap_err().contains("more than one sink is named \"file\""));
+        let shared_with_storage = r#"
+            [storage]
+            backend = "s3"
+            bucket = "proofs"
+            [[sinks]]
+            kind = "webhook"
+            name = "storage"
+            url = "http://localhost"
+        "#;
+        assert!(validate(shared_with_storage).unwrap_err().contains("more than one sink is named \"storage\""));
+    }
+}
@@ -0,0 +1,312 @@
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::{Keypair, Signature, Signer};
+use std::collections::HashSet;
+use std::str::FromStr;
+
+use crate::BlockProof;
+
+// An operator's ed25519 attestation over a block proof
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ProofSignature {
+    pub signer: String,
+    pub signature: String,
+}
+
+// The message operators sign: a digest over everything in the proof file
+// that a consumer relies on, excluding the signatures themselves
+fn signing_message(block_proof: &BlockProof) -> Vec<u8> {
+    let mut hasher = Sha256::new();
+    hasher.update(b"solana-listener/block-proof/v1");
+    hasher.update(block_proof.slot.to_le_bytes());
+    for field in [
+        &block_proof.block_hash,
+        &block_proof.transactions_root,
+        &block_proof.commitment,
+        &block_proof.proof,
+        &block_proof.old_root,
+        &block_proof.new_root,
+    ] {
+        hasher.update((field.len() as u64).to_le_bytes());
+        hasher.update(field.as_bytes());
+    }
+    // Optional fields are only hashed when present, so proofs without them keep
+    // their v1 message
+    if let Some(leader) = &block_proof.leader {
+        hasher.update(b"leader");
+        hasher.update((leader.len() as u64).to_le_bytes());
+        hasher.update(leader.as_bytes());
+    }
+    if let Some(genesis_hash) = &block_proof.genesis_hash {
+        hasher.update(b"genesis_hash");
+        hasher.update((genesis_hash.len() as u64).to_le_bytes());
+        hasher.update(genesis_hash.as_bytes());
+    }
+    if let Some(witness_hash) = &block_proof.witness_hash {
+        hasher.update(b"witness_hash");
+        hasher.update(witness_hash.as_bytes());
+    }
+    if let Some(clock_drift) = &block_proof.clock_drift {
+        hasher.update(b"clock_drift");
+        hasher.update(clock_drift.processed_at.to_le_bytes());
+        hasher.update(clock_drift.drift_secs.to_le_bytes());
+    }
+    if let Some(latency) = &block_proof.latency {
+        hasher.update(b"latency");
+        hasher.update(latency.proved_at.to_le_bytes());
+        hasher.update(latency.latency_secs.to_le_bytes());
+        hasher.update(latency.slo_secs.to_le_bytes());
+        hasher.update([latency.slo_violated as u8]);
+    }
+    if let Some(fingerprint) = &block_proof.params_fingerprint {
+        hasher.update(b"params_fingerprint");
+        hasher.update(fingerprint.as_bytes());
+    }
+    if block_proof.messages_bound {
+        hasher.update(b"messages_bound");
+    }
+    if block_proof.hash_domains > 0 {
+        hasher.update(b"hash_domains");
+        hasher.update([block_proof.hash_domains]);
+    }
+    if !block_proof.commitment_hash.is_sha256() {
+        hasher.update(b"commitment_hash");
+        hasher.update([block_proof.commitment_hash as u8]);
+    }
+    if let Some(oversized) = &block_proof.oversized {
+        hasher.update(b"oversized");
+        hasher.update([oversized.policy as u8]);
+        hasher.update((oversized.transactions as u64).to_le_bytes());
+        hasher.update((oversized.max_txs_per_block as u64).to_le_bytes());
+    }
+    if !block_proof.sorted_root.is_empty() {
+        hasher.update(b"sorted_root");
+        hasher.update(block_proof.sorted_root.as_bytes());
+        hasher.update((block_proof.transactions.len() as u64).to_le_bytes());
+    }
+    for mint_proof in &block_proof.mint_proofs {
+        hasher.update(b"mint");
+        for field in [&mint_proof.mint, &mint_proof.transfers_root, &mint_proof.commitment] {
+            hasher.update((field.len() as u64).to_le_bytes());
+            hasher.update(field.as_bytes());
+        }
+    }
+    if let Some(sol_transfers) = &block_proof.sol_transfers {
+        hasher.update(b"sol_transfers");
+        hasher.update(sol_transfers.total_lamports.to_le_bytes());
+        for chunk in sol_transfers.sum_proof.iter().flat_map(|sum_proof| &sum_proof.chunks) {
+            hasher.update(chunk.commitment.as_bytes());
+        }
+    }
+    if let Some(balances) = &block_proof.balances {
+        hasher.update(b"balances");
+        for delta in &balances.deltas {
+            hasher.update((delta.account.len() as u64).to_le_bytes());
+            hasher.update(delta.account.as_bytes());
+            hasher.update(delta.pre.to_le_bytes());
+            hasher.update(delta.post.to_le_bytes());
+        }
+        for chunk in [&balances.credit_proof, &balances.debit_proof]
+            .into_iter()
+            .flatten()
+            .flat_map(|sum_proof| &sum_proof.chunks)
+        {
+            hasher.update(chunk.commitment.as_bytes());
+        }
+    }
+    if let Some(compute_units) = &block_proof.compute_units {
+        hasher.update(b"compute_units");
+        hasher.update(compute_units.signature_count.to_le_bytes());
+        hasher.update(compute_units.total.to_le_bytes());
+        for chunk in &compute_units.chunks {
+            hasher.update(chunk.values_commitment.as_bytes());
+        }
+    }
+    if let Some(total_fees_proof) = block_proof.fees.as_ref().and_then(|fees| fees.total_fees_proof.as_ref()) {
+        hasher.update(b"total_fees");
+        hasher.update(total_fees_proof.total.to_le_bytes());
+        for chunk in &total_fees_proof.chunks {
+            hasher.update(chunk.values_commitment.as_bytes());
+        }
+    }
+    for threshold in &block_proof.volume_thresholds {
+        hasher.update(b"volume_threshold");
+        let mint = threshold.mint.as_deref().unwrap_or_default();
+        hasher.update((mint.len() as u64).to_le_bytes());
+        hasher.update(mint.as_bytes());
+        hasher.update(threshold.threshold.to_le_bytes());
+        hasher.update([threshold.direction as u8]);
+        hasher.update(threshold.commitment.as_bytes());
+    }
+    for signature_proof in &block_proof.signature_proofs {
+        hasher.update(b"signature_proof");
+        for field in [&signature_proof.signature, &signature_proof.fee_payer] {
+            hasher.update((field.len() as u64).to_le_bytes());
+            hasher.update(field.as_bytes());
+        }
+    }
+    if let Some(program_changes) = &block_proof.program_changes {
+        hasher.update(b"program_changes");
+        for field in [&program_changes.changes_root, &program_changes.commitment] {
+            hasher.update((field.len() as u64).to_le_bytes());
+            hasher.update(field.as_bytes());
+        }
+    }
+    if let Some(votes) = &block_proof.votes {
+        hasher.update(b"votes");
+        for field in [&votes.votes_root, &votes.commitment] {
+            hasher.update((field.len() as u64).to_le_bytes());
+            hasher.update(field.as_bytes());
+        }
+    }
+    for reward in &block_proof.rewards {
+        hasher.update(b"reward");
+        hasher.update((reward.pubkey.len() as u64).to_le_bytes());
+        hasher.update(reward.pubkey.as_bytes());
+        hasher.update(reward.lamports.to_le_bytes());
+        hasher.update(reward.post_balance.to_le_bytes());
+        let reward_type = reward.reward_type.map(|reward_type| reward_type.to_string()).unwrap_or_default();
+        hasher.update((reward_type.len() as u64).to_le_bytes());
+        hasher.update(reward_type.as_bytes());
+        hasher.update([reward.commission.unwrap_or(u8::MAX)]);
+    }
+    if let Some(token_supply) = &block_proof.token_supply {
+        hasher.update(b"token_supply");
+        hasher.update(token_supply.context_slot.to_le_bytes());
+        for supply in &token_supply.supplies {
+            hasher.update((supply.mint.len() as u64).to_le_bytes());
+            hasher.update(supply.mint.as_bytes());
+            hasher.update(supply.supply.to_le_bytes());
+            hasher.update([supply.decimals]);
+        }
+        for missing in &token_supply.missing {
+            hasher.update((missing.len() as u64).to_le_bytes());
+            hasher.update(missing.as_bytes());
+        }
+        hasher.update([token_supply.bound as u8]);
+    }
+    if let Some(states) = &block_proof.account_states {
+        hasher.update(b"account_states");
+        hasher.update(states.context_slot.to_le_bytes());
+        hasher.update((states.accounts_root.len() as u64).to_le_bytes());
+        hasher.update(states.accounts_root.as_bytes());
+        for missing in &states.missing {
+            hasher.update((missing.len() as u64).to_le_bytes());
+            hasher.update(missing.as_bytes());
+        }
+    }
+    if let Some(confirmation) = &block_proof.confirmation {
+        hasher.update(b"confirmation");
+        for stake in [confirmation.total_stake, confirmation.voted_stake, confirmation.rooted_stake] {
+            hasher.update(stake.to_le_bytes());
+        }
+        hasher.update((confirmation.voters as u64).to_le_bytes());
+    }
+    hasher.finalize().to_vec()
+}
+
+// Adds (or replaces) this keypair's signature on the proof
+pub fn sign(block_proof: &mut BlockProof, keypair: &Keypair) {
+    let signer = keypair.pubkey().to_string();
+    let signature = keypair.sign_message(&signing_message(block_proof)).to_string();
+
+    block_proof.signatures.retain(|existing| existing.signer != signer);
+    block_proof.signatures.push(ProofSignature { signer, signature });
+}
+
+// Number of distinct signers from `signers` with a valid signature on the proof
+pub fn valid_signers(block_proof: &BlockProof, signers: &[Pubkey]) -> usize {
+    let message = signing_message(block_proof);
+    let mut valid = HashSet::new();
+
+    for proof_signature in &block_proof.signatures {
+        let (Ok(signer), Ok(signature)) = (
+            Pubkey::from_str(&proof_signature.signer),
+            Signature::from_str(&proof_signature.signature),
+        ) else {
+            continue;
+        };
+        if signers.contains(&signer) && signature.verify(signer.as_ref(), &message) {
+            valid.insert(signer);
+        }
+    }
+
+    valid.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn block_proof() -> BlockProof {
+        serde_json::from_value(serde_json::json!({
+            "slot": 1,
+            "block_hash": "hash",
+            "transactions_root": "00",
+            "sorted_root": "00",
+            "commitment": "00",
+            "proof": "00",
+            "old_root": "00",
+            "new_root": "00",
+            "transactions": [],
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn signers_meet_a_threshold() {
+        let keypairs = [Keypair::new(), Keypair::new(), Keypair::new()];
+        let signers: Vec<Pubkey> = keypairs.iter().map(Keypair::pubkey).collect();
+        let mut block_proof = block_proof();
+        assert_eq!(valid_signers(&block_proof, &signers), 0);
+        sign(&mut block_proof, &keypairs[0]);
+        sign(&mut block_proof, &keypairs[2]);
+        assert_eq!(valid_signers(&block_proof, &signers), 2);
+
+        // Signing again replaces the signer's signature
+        sign(&mut block_proof, &keypairs[0]);
+        assert_eq!(block_proof.signatures.len(), 2);
+        assert_eq!(valid_signers(&block_proof, &signers), 2);
+    }
+
+    #[test]
+    fn duplicate_signatures_count_once() {
+        let keypair = Keypair::new();
+        let mut block_proof = block_proof();
+        sign(&mut block_proof, &keypair);
+        let duplicate = block_proof.signatures[0].clone();
+        block_proof.signatures.push(duplicate);
+        assert_eq!(valid_signers(&block_proof, &[keypair.pubkey()]), 1);
+    }
+
+    #[test]
+    fn unknown_signers_are_not_counted() {
+        let (signer, outsider) = (Keypair::new(), Keypair::new());
+        let mut block_proof = block_proof();
+        sign(&mut block_proof, &outsider);
+        assert_eq!(valid_signers(&block_proof, &[signer.pubkey()]), 0);
+
+        // Nor is a known signer's name on another key's signature
+        block_proof.signatures[0].signer = signer.pubkey().to_string();
+        assert_eq!(valid_signers(&block_proof, &[signer.pubkey()]), 0);
+    }
+
+    #[test]
+    fn changing_the_proof_invalidates_its_signatures() {
+        let keypair = Keypair::new();
+        let mut block_proof = block_proof();
+        sign(&mut block_proof, &keypair);
+
+        let mut resorted = block_proof.clone();
+        resorted.sorted_root = "01".to_string();
+        assert_eq!(valid_signers(&resorted, &[keypair.pubkey()]), 0);
+        let mut unsorted = block_proof.clone();
+        unsorted.sorted_root.clear();
+        assert_eq!(valid_signers(&unsorted, &[keypair.pubkey()]), 0);
+        let mut recommitted = block_proof.clone();
+        recommitted.commitment = "01".to_string();
+        assert_eq!(valid_signers(&recommitted, &[keypair.pubkey()]), 0);
+        assert_eq!(valid_signers(&block_proof, &[keypair.pubkey()]), 1);
+    }
+}
@@ -0,0 +1,425 @@
+use async_trait::async_trait;
+use log::warn;
+use serde::{Deserialize, Serialize};
+use solana_client::client_error::Result as ClientResult;
+use solana_client::rpc_request::RpcRequest;
+use solana_client::rpc_sender::{RpcSender, RpcTransportStats};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::config::RpcCostConfig;
+
+static TRACKER: OnceLock<Tracker> = OnceLock::new();
+
+const LEDGER_FILE: &str = "rpc_costs.json";
+// Days of usage kept in the ledger
+const LEDGER_DAYS: usize = 90;
+// Usage is added to the ledger at most this often, and when a command ends
+const SAVE_INTERVAL: Duration = Duration::from_secs(10);
+
+// Calls made to a provider, by method, and the credits they cost
+#[derive(Serialize, Deserialize, Default, Clone)]
+pub struct ProviderUsage {
+    pub calls: BTreeMap<String, u64>,
+    pub credits: u64,
+}
+
+impl ProviderUsage {
+    fn add(&mut self, other: &ProviderUsage) {
+        for (method, calls) in &other.calls {
+            *self.calls.entry(method.clone()).or_default() += calls;
+        }
+        self.credits += other.credits;
+    }
+}
+
+// Usage of every provider by UTC day ("2026-01-31"), kept in the proofs
+// directory and shared by every process pointed at it
+#[derive(Serialize, Deserialize, Default)]
+pub struct Ledger {
+    pub days: BTreeMap<String, BTreeMap<String, ProviderUsage>>,
+}
+
+pub fn ledger_path(proofs_dir: &Path) -> PathBuf {
+    proofs_dir.join(LEDGER_FILE)
+}
+
+pub fn load_ledger(proofs_dir: &Path) -> io::Result<Ledger> {
+    match fs::read_to_string(ledger_path(proofs_dir)) {
+        Ok(contents) => serde_json::from_str(&contents).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Ledger::default()),
+        Err(e) => Err(e),
+    }
+}
+
+#[derive(Serialize)]
+pub struct ProviderCosts {
+    pub provider: String,
+    pub calls: u64,
+    pub credits: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub daily_budget: Option<u64>,
+    pub methods: BTreeMap<String, u64>,
+}
+
+#[derive(Serialize)]
+pub struct DayCosts {
+    pub date: String,
+    pub credits: u64,
+    pub providers: Vec<ProviderCosts>,
+}
+
+// Usage of the last `days` days of the ledger, oldest first, with the budgets
+// of `config`
+pub fn report(ledger: Ledger, config: Option<&RpcCostConfig>, days: usize) -> Vec<DayCosts> {
+    let skip = ledger.days.len().saturating_sub(days);
+    ledger
+        .days
+        .into_iter()
+        .skip(skip)
+        .map(|(date, providers)| {
+            let providers: Vec<ProviderCosts> = providers
+                .into_iter()
+                .map(|(provider, usage)| ProviderCosts {
+                    calls: usage.calls.values().sum(),
+                    credits: usage.credits,
+                    daily_budget: config.and_then(|config| config.budget(&provider)),
+                    methods: usage.calls,
+                    provider,
+                })
+                .collect();
+            DayCosts { date, credits: providers.iter().map(|provider| provider.credits).sum(), providers }
+        })
+        .collect()
+}
+
+struct State {
+    today: String,
+    // Usage of each provider since the process started, for the metrics
+    totals: BTreeMap<String, ProviderUsage>,
+    // Usage of today not yet added to the ledger, and the ledger's usage of
+    // today as of the last save, which includes other processes'
+    pending: BTreeMap<String, ProviderUsage>,
+    saved: BTreeMap<String, u64>,
+    last_saved: Instant,
+    // Providers over their budget today, and when each may next be called
+    over_budget: BTreeSet<String>,
+    next_call: BTreeMap<String, Instant>,
+}
+
+struct Tracker {
+    config: RpcCostConfig,
+    proofs_dir: PathBuf,
+    state: Mutex<State>,
+}
+
+impl Tracker {
+    // Adds the pending usage to the ledger on disk. Other processes may have
+    // added theirs since it was last read, so it is read again first.
+    fn save(&self, state: &mut State) {
+        let path = ledger_path(&self.proofs_dir);
+        state.last_saved = Instant::now();
+        let mut ledger = match load_ledger(&self.proofs_dir) {
+            Ok(ledger) => ledger,
+            Err(e) => {
+                warn!("Unable to read RPC cost ledger {:?}, keeping the usage for the next save: {}", path, e);
+                return;
+            }
+        };
+        let day = ledger.days.entry(state.today.clone()).or_default();
+        for (provider, usage) in std::mem::take(&mut state.pending) {
+            day.entry(provider).or_default().add(&usage);
+        }
+        state.saved = day.iter().map(|(provider, usage)| (provider.clone(), usage.credits)).collect();
+        while ledger.days.len() > LEDGER_DAYS {
+            ledger.days.pop_first();
+        }
+
+        let tmp_path = path.with_extension("json.tmp");
+        let json_data = serde_json::to_string_pretty(&ledger).expect("Unable to serialize RPC cost ledger");
+        if let Err(e) = fs::write(&tmp_path, json_data).and_then(|_| fs::rename(&tmp_path, &path)) {
+            warn!("Unable to write RPC cost ledger {:?}: {}", path, e);
+        }
+    }
+
+    // Counts a call, returning how long to hold it back for if its provider
+    // is over budget
+    fn record(&self, provider: &str, method: &str) -> Option<Duration> {
+        self.record_at(provider, method, SystemTime::now())
+    }
+
+    fn record_at(&self, provider: &str, method: &str, now: SystemTime) -> Option<Duration> {
+        let mut state = self.state.lock().unwrap();
+        let today = date(now);
+        if today != state.today {
+            // Usage still pending belongs to the day that just ended
+            self.save(&mut state);
+            state.today = today;
+            state.saved.clear();
+            state.over_budget.clear();
+            state.next_call.clear();
+        }
+
+        let credits = self.config.weight(provider, method);
+        let total = state.totals.entry(provider.to_string()).or_default();
+        *total.calls.entry(method.to_string()).or_default() += 1;
+        total.credits += credits;
+        let pending = state.pending.entry(provider.to_string()).or_default();
+        *pending.calls.entry(method.to_string()).or_default() += 1;
+        pending.credits += credits;
+        let used = pending.credits + state.saved.get(provider).copied().unwrap_or_default();
+        if state.last_saved.elapsed() >= SAVE_INTERVAL {
+            self.save(&mut state);
+        }
+
+        let budget = self.config.budget(provider)?;
+        if used < budget {
+            return None;
+        }
+        if state.over_budget.insert(provider.to_string()) {
+            warn!(
+                "RPC provider {} used {} of its daily budget of {} credits; spacing calls {} ms apart until the \
+                 UTC day ends",
+                provider, used, budget, self.config.throttle_interval_ms
+            );
+        }
+        let now = Instant::now();
+        let next_call = state.next_call.get(provider).copied().unwrap_or(now).max(now);
+        let interval = Duration::from_millis(self.config.throttle_interval_ms);
+        state.next_call.insert(provider.to_string(), next_call + interval);
+        Some(next_call - now)
+    }
+}
+
+// Counts the RPC calls of the process from now on, as configured
+pub fn configure(config: &RpcCostConfig, proofs_dir: &Path) {
+    let today = date(SystemTime::now());
+    let mut ledger = load_ledger(proofs_dir).unwrap_or_else(|e| {
+        warn!("Unable to read RPC cost ledger {:?}: {}", ledger_path(proofs_dir), e);
+        Ledger::default()
+    });
+    let saved = ledger
+        .days
+        .remove(&today)
+        .unwrap_or_default()
+        .into_iter()
+        .map(|(provider, usage)| (provider, usage.credits))
+        .collect();
+    let state = State {
+        today,
+        totals: BTreeMap::new(),
+        pending: BTreeMap::new(),
+        saved,
+        last_saved: Instant::now(),
+        over_budget: BTreeSet::new(),
+        next_call: BTreeMap::new(),
+    };
+    let tracker = Tracker { config: config.clone(), proofs_dir: proofs_dir.to_path_buf(), state: Mutex::new(state) };
+    if TRACKER.set(tracker).is_err() {
+        panic!("RPC cost accounting configured twice");
+    }
+}
+
+// Adds the usage not yet in the ledger to it
+pub fn flush() {
+    if let Some(tracker) = TRACKER.get() {
+        let mut state = tracker.state.lock().unwrap();
+        if !state.pending.is_empty() {
+            tracker.save(&mut state);
+        }
+    }
+}
+
+// Calls and credits of each provider since the process started, with the
+// credits it has used today and its daily budget
+pub fn usage() -> Vec<(String, ProviderUsage, u64, Option<u64>)> {
+    let Some(tracker) = TRACKER.get() else {
+        return Vec::new();
+    };
+    let state = tracker.state.lock().unwrap();
+    state
+        .totals
+        .iter()
+        .map(|(provider, usage)| {
+            let pending = state.pending.get(provider).map_or(0, |pending| pending.credits);
+            let today = pending + state.saved.get(provider).copied().unwrap_or_default();
+            (provider.clone(), usage.clone(), today, tracker.config.budget(provider))
+        })
+        .collect()
+}
+
+// Providers are told apart by the host of their URL
+pub fn provider(url: &str) -> String {
+    reqwest::Url::parse(url).ok().and_then(|url| url.host_str().map(str::to_string)).unwrap_or(url.to_string())
+}
+
+// UTC date of `time`, as YYYY-MM-DD
+pub fn date(time: SystemTime) -> String {
+    let days = (time.duration_since(UNIX_EPOCH).unwrap().as_secs() / 86_400) as i64;
+    // Days since 1970-01-01 to a civil date, counting from 0000-03-01 so leap
+    // days fall at the end of each year
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 };
+    let year = year_of_era + era * 400 + (month <= 2) as i64;
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+// Transport of the listener's RPC clients, counting every call against its
+// provider and holding calls back once the provider is over budget
+pub struct MeteredSender<S> {
+    inner: S,
+    provider: String,
+}
+
+impl<S: RpcSender> MeteredSender<S> {
+    pub fn new(inner: S) -> Self {
+        let provider = provider(&inner.url());
+        MeteredSender { inner, provider }
+    }
+}
+
+#[async_trait]
+impl<S: RpcSender + Send + Sync> RpcSender for MeteredSender<S> {
+    async fn send(&self, request: RpcRequest, params: serde_json::Value) -> ClientResult<serde_json::Value> {
+        if let Some(wait) = TRACKER.get().and_then(|tracker| tracker.record(&self.provider, &request.to_string())) {
+            tokio::time::sleep(wait).await;
+        }
+        self.inner.send(request, params).await
+    }
+
+    fn get_transport_stats(&self) -> RpcTransportStats {
+        self.inner.get_transport_stats()
+    }
+
+    fn url(&self) -> String {
+        self.inner.url()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Noon UTC of 2026-01-01 and of the day after
+    const JAN_1: u64 = 1_767_268_800;
+    const JAN_2: u64 = JAN_1 + 86_400;
+
+    fn at(secs: u64) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(secs)
+    }
+
+    fn tracker(name: &str, daily_budget: u64) -> Tracker {
+        let proofs_dir = std::env::temp_dir().join(format!("solana-listener-costs-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&proofs_dir);
+        fs::create_dir_all(&proofs_dir).unwrap();
+        let config = RpcCostConfig { daily_budget: Some(daily_budget), ..RpcCostConfig::default() };
+        let state = State {
+            today: date(at(JAN_1)),
+            totals: BTreeMap::new(),
+            pending: BTreeMap::new(),
+            saved: BTreeMap::new(),
+            last_saved: Instant::now(),
+            over_budget: BTreeSet::new(),
+            next_call: BTreeMap::new(),
+        };
+        Tracker { config, proofs_dir, state: Mutex::new(state) }
+    }
+
+    fn credits(ledger: &Ledger, date: &str, provider: &str) -> Option<u64> {
+        ledger.days.get(date).and_then(|day| day.get(provider)).map(|usage| usage.credits)
+    }
+
+    #[test]
+    fn dates_are_utc_days() {
+        assert_eq!(date(UNIX_EPOCH), "1970-01-01");
+        assert_eq!(date(at(JAN_1)), "2026-01-01");
+        assert_eq!(date(at(JAN_1 - 12 * 3600 - 1)), "2025-12-31");
+        // 2024-02-29, a leap day
+        assert_eq!(date(at(1_709_164_800)), "2024-02-29");
+        assert_eq!(date(at(1_709_164_800 + 86_400)), "2024-03-01");
+    }
+
+    #[test]
+    fn exhausted_budgets_space_calls_apart() {
+        let tracker = tracker("exhausted", 3);
+        let interval = Duration::from_millis(tracker.config.throttle_interval_ms);
+        assert_eq!(tracker.record_at("rpc.example", "getSlot", at(JAN_1)), None);
+        assert_eq!(tracker.record_at("rpc.example", "getBlock", at(JAN_1)), None);
+
+        // The call that reaches the budget goes ahead, and each after it
+        // waits for the one before
+        assert_eq!(tracker.record_at("rpc.example", "getBlock", at(JAN_1)), Some(Duration::ZERO));
+        let wait = tracker.record_at("rpc.example", "getBlock", at(JAN_1)).unwrap();
+        assert!(wait > interval / 2 && wait <= interval);
+        let wait = tracker.record_at("rpc.example", "getBlock", at(JAN_1)).unwrap();
+        assert!(wait > interval && wait <= 2 * interval);
+
+        // Budgets are per provider
+        assert_eq!(tracker.record_at("other.example", "getBlock", at(JAN_1)), None);
+
+        let state = tracker.state.lock().unwrap();
+        assert_eq!(state.totals["rpc.example"].credits, 5);
+        assert_eq!(state.totals["rpc.example"].calls["getBlock"], 4);
+        assert!(state.over_budget.contains("rpc.example") && !state.over_budget.contains("other.example"));
+        drop(state);
+        fs::remove_dir_all(&tracker.proofs_dir).unwrap();
+    }
+
+    #[test]
+    fn budgets_roll_over_at_the_end_of_the_day() {
+        let tracker = tracker("rollover", 2);
+        tracker.record_at("rpc.example", "getBlock", at(JAN_1));
+        tracker.record_at("rpc.example", "getBlock", at(JAN_1));
+        assert!(tracker.record_at("rpc.example", "getBlock", at(JAN_1)).is_some());
+
+        // The first call of a new day saves the usage of the old one and is
+        // not held back
+        assert_eq!(tracker.record_at("rpc.example", "getBlock", at(JAN_2)), None);
+        let ledger = load_ledger(&tracker.proofs_dir).unwrap();
+        assert_eq!(credits(&ledger, "2026-01-01", "rpc.example"), Some(3));
+        assert_eq!(credits(&ledger, "2026-01-02", "rpc.example"), None);
+        {
+            let state = tracker.state.lock().unwrap();
+            assert_eq!(state.today, "2026-01-02");
+            assert!(state.over_budget.is_empty() && state.next_call.is_empty());
+            assert_eq!(state.pending["rpc.example"].credits, 1);
+            // Totals are since the process started, across days
+            assert_eq!(state.totals["rpc.example"].credits, 4);
+        }
+
+        // Usage of today saved by another process counts against the budget
+        let mut ledger = load_ledger(&tracker.proofs_dir).unwrap();
+        let usage = ProviderUsage { calls: BTreeMap::new(), credits: 5 };
+        ledger.days.entry("2026-01-02".to_string()).or_default().insert("rpc.example".to_string(), usage);
+        fs::write(ledger_path(&tracker.proofs_dir), serde_json::to_string(&ledger).unwrap()).unwrap();
+        tracker.save(&mut tracker.state.lock().unwrap());
+        assert_eq!(credits(&load_ledger(&tracker.proofs_dir).unwrap(), "2026-01-02", "rpc.example"), Some(6));
+        assert!(tracker.record_at("rpc.example", "getBlock", at(JAN_2)).is_some());
+
+        fs::remove_dir_all(&tracker.proofs_dir).unwrap();
+    }
+
+    #[test]
+    fn ledgers_keep_the_latest_days() {
+        let tracker = tracker("retention", 100);
+        for day in 0..LEDGER_DAYS as u64 + 5 {
+            tracker.record_at("rpc.example", "getBlock", at(JAN_1 + day * 86_400));
+        }
+        tracker.save(&mut tracker.state.lock().unwrap());
+        let ledger = load_ledger(&tracker.proofs_dir).unwrap();
+        assert_eq!(ledger.days.len(), LEDGER_DAYS);
+        assert!(!ledger.days.contains_key("2026-01-01"));
+        assert!(ledger.days.contains_key(&date(at(JAN_1 + (LEDGER_DAYS as u64 + 4) * 86_400))));
+        fs::remove_dir_all(&tracker.proofs_dir).unwrap();
+    }
+}
@@ -0,0 +1,73 @@
+use log::warn;
+use std::io;
+use std::sync::OnceLock;
+
+use crate::config::ProverCpuConfig;
+
+static PROVER_CPU: OnceLock<ProverCpuConfig> = OnceLock::new();
+
+#[cfg(target_os = "linux")]
+fn pin(cores: &[usize]) -> io::Result<()> {
+    // SAFETY: cpu_set_t is a plain bit set, valid when zeroed
+    let mut set: libc::cpu_set_t = unsafe { std::mem::zeroed() };
+    for core in cores {
+        unsafe { libc::CPU_SET(*core, &mut set) };
+    }
+    match unsafe { libc::sched_setaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &set) } {
+        0 => Ok(()),
+        _ => Err(io::Error::last_os_error()),
+    }
+}
+
+// The nice value is kept per thread on Linux
+#[cfg(target_os = "linux")]
+fn lower_priority(nice: i32) -> io::Result<()> {
+    let thread = unsafe { libc::gettid() } as libc::id_t;
+    match unsafe { libc::setpriority(libc::PRIO_PROCESS, thread, nice) } {
+        0 => Ok(()),
+        _ => Err(io::Error::last_os_error()),
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn pin(_cores: &[usize]) -> io::Result<()> {
+    Err(io::Error::new(io::ErrorKind::Unsupported, "CPU affinity is only supported on Linux"))
+}
+
+#[cfg(not(target_os = "linux"))]
+fn lower_priority(_nice: i32) -> io::Result<()> {
+    Err(io::Error::new(io::ErrorKind::Unsupported, "thread priorities are only supported on Linux"))
+}
+
+// Pins the calling thread to the prover cores and lowers its priority, as
+// configured. Only threads that prove call this, so the rest of the
+// listener keeps following the cluster at normal priority.
+pub fn apply() {
+    let Some(config) = PROVER_CPU.get() else {
+        return;
+    };
+    if !config.cores.is_empty() {
+        if let Err(e) = pin(&config.cores) {
+            warn!("Unable to pin prover thread to cores {:?}: {}", config.cores, e);
+        }
+    }
+    if let Some(nice) = config.nice {
+        if let Err(e) = lower_priority(nice) {
+            warn!("Unable to set the nice value of a prover thread to {}: {}", nice, e);
+        }
+    }
+}
+
+// Sets up the thread pool the circuits are proved on, one thread per
+// configured core. Must run before anything is proved or generated.
+pub fn configure(config: &ProverCpuConfig) {
+    if PROVER_CPU.set(config.clone()).is_err() {
+        panic!("Prover CPU settings configured twice");
+    }
+    rayon::ThreadPoolBuilder::new()
+        .num_threads(config.cores.len())
+        .thread_name(|index| format!("prover-{}", index))
+        .start_handler(|_| apply())
+        .build_global()
+        .expect("Unable to configure the prover thread pool");
+}
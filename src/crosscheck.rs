@@ -0,0 +1,141 @@
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+use serde::Serialize;
+use solana_sdk::clock::Slot;
+use solana_transaction_status::EncodedConfirmedBlock;
+
+use crate::config::{Config, OversizedPolicy};
+use crate::field::{fr_from_hex, fr_to_hex, HASH_DOMAINS};
+use crate::keyring::Keyring;
+use crate::verify;
+use crate::{build_block_witness, BlockProof};
+
+#[derive(Serialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum CheckStatus {
+    // The proof verifies and opens to the block served by the endpoint
+    Passed,
+    // The proof does not verify, or commits to other data than the endpoint's block
+    Failed,
+    // Some of the checks could not be made; see the findings
+    Incomplete,
+}
+
+#[derive(Serialize)]
+pub struct SlotCheck {
+    pub slot: Slot,
+    pub status: CheckStatus,
+    pub block_hash: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub findings: Vec<String>,
+}
+
+#[derive(Serialize)]
+pub struct CrosscheckReport {
+    pub rpc_url: String,
+    pub genesis_hash: String,
+    // Seed the slots were drawn with, to draw the same sample again
+    pub seed: u64,
+    pub proved_slots: usize,
+    pub passed: usize,
+    pub failed: usize,
+    pub incomplete: usize,
+    pub checked_at: i64,
+    pub slots: Vec<SlotCheck>,
+}
+
+// Up to `count` of the proved slots, drawn at random with `seed`, in slot order
+pub fn sample(proved: &[Slot], count: usize, seed: u64) -> Vec<Slot> {
+    let mut slots: Vec<Slot> = proved.choose_multiple(&mut StdRng::seed_from_u64(seed), count).copied().collect();
+    slots.sort_unstable();
+    slots
+}
+
+// Checks a stored proof against `block`, its block as fetched from the
+// endpoint: the circuit proofs must verify, and the witness rebuilt from the
+// block must reproduce the proof's commitment and accumulator roots.
+pub fn check(
+    block_proof: &BlockProof,
+    block: Result<EncodedConfirmedBlock, String>,
+    genesis_hash: &str,
+    keyring: &Keyring,
+    config: &Config,
+) -> SlotCheck {
+    let slot = block_proof.slot;
+    let mut failures = Vec::new();
+    let mut gaps = Vec::new();
+
+    if let Err(e) = verify::verify_block(block_proof, keyring) {
+        match e {
+            verify::VerifyError::UnknownKey(_) | verify::VerifyError::NoCurrentKey => gaps.push(e.to_string()),
+            e => failures.push(format!("circuit proof does not verify: {}", e)),
+        }
+    }
+    if let Some(proof_genesis) = block_proof.genesis_hash.as_ref().filter(|hash| *hash != genesis_hash) {
+        failures.push(format!("proved on cluster {}, endpoint is on {}", proof_genesis, genesis_hash));
+    }
+
+    match block {
+        Err(e) => gaps.push(format!("unable to fetch the block: {}", e)),
+        Ok(block) if block.blockhash != block_proof.block_hash => {
+            failures.push(format!("block hash is {} on the endpoint", block.blockhash));
+        }
+        // Salted leaves are drawn afresh, so only the block hash can be compared
+        Ok(_) if block_proof.sorted_root.is_empty() => {
+            gaps.push("proved with salted leaves, so the commitment cannot be rebuilt".to_string());
+        }
+        // Witnesses are only built with the current domain tags
+        Ok(_) if block_proof.hash_domains != HASH_DOMAINS => {
+            gaps.push(format!("proved with version {} of the hash domains", block_proof.hash_domains));
+        }
+        Ok(block) => {
+            let Some(old_root) = fr_from_hex(&block_proof.old_root) else {
+                failures.push("malformed old root".to_string());
+                return finish(block_proof, failures, gaps);
+            };
+            // Rebuilt with the proof's own bindings, whatever the config now says
+            let mut config = config.clone();
+            config.bind_leader = block_proof.leader_bound;
+            config.bind_messages = block_proof.messages_bound;
+            config.private_dir = None;
+            config.commitment_hash = block_proof.commitment_hash;
+            config.max_txs_per_block = block_proof.oversized.as_ref().map(|oversized| oversized.max_txs_per_block);
+            config.oversized_blocks = match &block_proof.oversized {
+                Some(oversized) => oversized.policy,
+                None => OversizedPolicy::Chunk,
+            };
+            let token_supply = block_proof.token_supply.clone();
+            match build_block_witness(slot, block, block_proof.leader.clone(), token_supply, old_root, &config) {
+                Ok(exported) => {
+                    let top_level = exported.witness.top_level();
+                    if fr_to_hex(&top_level.commitment) != block_proof.commitment {
+                        failures.push("commitment does not match the endpoint's transactions".to_string());
+                    }
+                    if fr_to_hex(&top_level.new_root()) != block_proof.new_root {
+                        failures.push("new accumulator root does not match the rebuilt witness".to_string());
+                    }
+                }
+                Err(e) => gaps.push(format!("unable to rebuild the witness: {}", e)),
+            }
+        }
+    }
+
+    finish(block_proof, failures, gaps)
+}
+
+fn finish(block_proof: &BlockProof, failures: Vec<String>, gaps: Vec<String>) -> SlotCheck {
+    let status = if !failures.is_empty() {
+        CheckStatus::Failed
+    } else if !gaps.is_empty() {
+        CheckStatus::Incomplete
+    } else {
+        CheckStatus::Passed
+    };
+    SlotCheck {
+        slot: block_proof.slot,
+        status,
+        block_hash: block_proof.block_hash.clone(),
+        findings: failures.into_iter().chain(gaps).collect(),
+    }
+}
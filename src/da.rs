@@ -0,0 +1,152 @@
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use futures::future::{BoxFuture, FutureExt};
+use log::info;
+use serde::Serialize;
+use serde_json::{json, Value};
+use solana_sdk::clock::Slot;
+use std::fmt;
+use tokio::time::Duration;
+
+use crate::config::{DaBackend, DataAvailabilityConfig};
+use crate::sink::ProofSink;
+use crate::BlockProof;
+
+// Bytes of a namespace ID a user chooses, and of the whole version 0 namespace
+// (a version byte, 18 zero bytes and the ID)
+const NAMESPACE_ID_BYTES: usize = 10;
+const NAMESPACE_BYTES: usize = 29;
+
+#[derive(Debug)]
+pub enum DaError {
+    InvalidNamespace(String),
+    Request(reqwest::Error),
+    Rejected(String),
+}
+
+impl fmt::Display for DaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DaError::InvalidNamespace(namespace) => {
+                write!(f, "namespace {:?} is not hex of at most {} bytes", namespace, NAMESPACE_ID_BYTES)
+            }
+            DaError::Request(e) => write!(f, "request failed: {}", e),
+            DaError::Rejected(message) => write!(f, "blob rejected: {}", message),
+        }
+    }
+}
+
+// What a blob holds without `full_proofs`: enough to check a proof file
+// against, and to chain the accumulator roots from the blobs alone
+#[derive(Serialize)]
+struct ProofCommitments<'a> {
+    slot: Slot,
+    block_hash: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    genesis_hash: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    witness_hash: Option<&'a str>,
+    transactions_root: &'a str,
+    commitment: &'a str,
+    old_root: &'a str,
+    new_root: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    params_fingerprint: Option<&'a str>,
+}
+
+// The version 0 namespace of a hex namespace ID, left-padded with zeroes
+pub fn parse_namespace(namespace: &str) -> Result<[u8; NAMESPACE_BYTES], DaError> {
+    let id = hex::decode(namespace).map_err(|_| DaError::InvalidNamespace(namespace.to_string()))?;
+    if id.is_empty() || id.len() > NAMESPACE_ID_BYTES {
+        return Err(DaError::InvalidNamespace(namespace.to_string()));
+    }
+    let mut bytes = [0u8; NAMESPACE_BYTES];
+    bytes[NAMESPACE_BYTES - id.len()..].copy_from_slice(&id);
+    Ok(bytes)
+}
+
+// Posts block proofs to a data-availability layer. A blob is only delivered
+// once included in a block, whose height is logged.
+pub struct DaPublisher {
+    client: reqwest::Client,
+    config: DataAvailabilityConfig,
+    namespace: [u8; NAMESPACE_BYTES],
+}
+
+impl DaPublisher {
+    pub fn new(config: &DataAvailabilityConfig) -> Result<Self, DaError> {
+        Ok(DaPublisher {
+            client: reqwest::Client::builder()
+                .timeout(Duration::from_secs(120))
+                .build()
+                .expect("Unable to build data-availability client"),
+            config: config.clone(),
+            namespace: parse_namespace(&config.namespace)?,
+        })
+    }
+
+    fn blob_data(&self, block_proof: &BlockProof) -> Vec<u8> {
+        let data = if self.config.full_proofs {
+            serde_json::to_vec(block_proof)
+        } else {
+            serde_json::to_vec(&ProofCommitments {
+                slot: block_proof.slot,
+                block_hash: &block_proof.block_hash,
+                genesis_hash: block_proof.genesis_hash.as_deref(),
+                witness_hash: block_proof.witness_hash.as_deref(),
+                transactions_root: &block_proof.transactions_root,
+                commitment: &block_proof.commitment,
+                old_root: &block_proof.old_root,
+                new_root: &block_proof.new_root,
+                params_fingerprint: block_proof.params_fingerprint.as_deref(),
+            })
+        };
+        data.expect("Unable to serialize blob")
+    }
+}
+
+impl ProofSink for DaPublisher {
+    fn deliver<'a>(&'a self, block_proof: &'a BlockProof) -> BoxFuture<'a, Result<(), String>> {
+        let slot = block_proof.slot;
+        let request = match self.config.backend {
+            DaBackend::Celestia => {
+                let blob = json!({
+                    "namespace": STANDARD.encode(self.namespace),
+                    "data": STANDARD.encode(self.blob_data(block_proof)),
+                    "share_version": 0,
+                });
+                // A negative gas price has the node estimate one
+                let options = json!({ "gas_price": self.config.gas_price.unwrap_or(-1.0) });
+                let body = json!({ "jsonrpc": "2.0", "id": slot, "method": "blob.Submit", "params": [[blob], options] });
+                let request = self.client.post(&self.config.rpc_url).json(&body);
+                match &self.config.auth_token {
+                    Some(token) => request.bearer_auth(token),
+                    None => request,
+                }
+            }
+        };
+
+        async move {
+            let height = submit(request).await.map_err(|e| e.to_string())?;
+            info!("Posted block proof {} to the data-availability layer at height {}", slot, height);
+            Ok(())
+        }
+        .boxed()
+    }
+}
+
+// Height of the block the blob was included in
+async fn submit(request: reqwest::RequestBuilder) -> Result<Value, DaError> {
+    let response: Value = request
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(DaError::Request)?
+        .json()
+        .await
+        .map_err(DaError::Request)?;
+    match response.get("error") {
+        Some(error) => Err(DaError::Rejected(error["message"].as_str().unwrap_or("unknown error").to_string())),
+        None => Ok(response["result"].clone()),
+    }
+}
@@ -0,0 +1,189 @@
+use blstrs::Scalar as Fr;
+use ff::PrimeField;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use solana_sdk::clock::Slot;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::field::{domain_hasher, hash_to_fr, Domain};
+use crate::merkle::{fold_path, path_matches_index};
+use crate::{leaf_data, BlockProof, TransactionProof};
+
+// Selective disclosure: every transaction leaf is salted, and the signatures,
+// salts and inclusion paths are kept in a private directory instead of the
+// published proof. Disclosing one transaction reveals nothing about the others.
+#[derive(Serialize, Deserialize)]
+pub struct Disclosure {
+    pub slot: Slot,
+    pub transaction: TransactionProof,
+}
+
+pub fn random_salt() -> [u8; 32] {
+    let mut salt = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut salt);
+    salt
+}
+
+// Leaf of a salted transaction, tagged unless made under version 0 of the hash domains
+pub fn salted_leaf(salt: &[u8], data: &str, hash_domains: u8) -> Fr {
+    let mut hasher = match hash_domains {
+        0 => Sha256::new(),
+        _ => domain_hasher(Domain::Transaction),
+    };
+    hasher.update(salt);
+    hasher.update(data.as_bytes());
+    hash_to_fr(&hasher.finalize())
+}
+
+pub fn private_path(private_dir: &Path, slot: Slot) -> PathBuf {
+    private_dir.join(format!("transactions_{}.json", slot))
+}
+
+// Moves the transaction list of a proof with salted leaves into the private
+// directory before the proof is published
+pub fn withhold(block_proof: &mut BlockProof, private_dir: Option<&Path>) {
+    if !block_proof.transactions.iter().any(|transaction| transaction.salt.is_some()) {
+        return;
+    }
+    let private_dir = private_dir.expect("private_dir must be set to prove blocks with salted leaves");
+
+    let transactions = std::mem::take(&mut block_proof.transactions);
+    let json_data = serde_json::to_string(&transactions).expect("Unable to serialize transactions");
+    fs::create_dir_all(private_dir).expect("Unable to create private transactions directory");
+    fs::write(private_path(private_dir, block_proof.slot), json_data).expect("Unable to write private transactions");
+}
+
+// Disclosure for one transaction of a withheld block, or `None` if it is not there
+pub fn disclose(private_dir: &Path, slot: Slot, signature: &str) -> Option<Disclosure> {
+    let contents = fs::read_to_string(private_path(private_dir, slot)).expect("Unable to read private transactions");
+    let transactions: Vec<TransactionProof> =
+        serde_json::from_str(&contents).expect("Unable to parse private transactions");
+
+    transactions
+        .into_iter()
+        .find(|transaction| transaction.transaction_hash == signature)
+        .map(|transaction| Disclosure { slot, transaction })
+}
+
+// Checks a disclosed transaction against the salted transactions root of its
+// block. The published proof does not give the number of transactions, so the
+// leaf index is only checked against the sides of the path's siblings.
+pub fn verify(block_proof: &BlockProof, disclosure: &Disclosure) -> bool {
+    let transaction = &disclosure.transaction;
+    let Some(salt) = transaction.salt.as_ref().and_then(|salt| hex::decode(salt).ok()) else {
+        return false;
+    };
+    if block_proof.messages_bound != transaction.message_hash.is_some() {
+        return false;
+    }
+    let data = leaf_data(&transaction.transaction_hash, transaction.message_hash.as_deref());
+    let leaf = salted_leaf(&salt, &data, block_proof.hash_domains).to_repr();
+
+    let root = fold_path(block_proof.commitment_hash, &leaf, &transaction.merkle_path);
+    disclosure.slot == block_proof.slot
+        && path_matches_index(transaction.leaf_index, &transaction.merkle_path)
+        && root.is_some_and(|root| hex::encode(root) == block_proof.transactions_root)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::CommitmentHash;
+    use crate::field::HASH_DOMAINS;
+    use crate::merkle::MerkleTree;
+
+    // A proof file over salted leaves of `signatures`, with its transactions
+    // still listed
+    fn salted_block(signatures: &[&str]) -> BlockProof {
+        let salts: Vec<[u8; 32]> = signatures.iter().map(|_| random_salt()).collect();
+        let leaves: Vec<[u8; 32]> = signatures
+            .iter()
+            .zip(&salts)
+            .map(|(signature, salt)| salted_leaf(salt, &leaf_data(signature, None), HASH_DOMAINS).to_repr())
+            .collect();
+        let tree = MerkleTree::new(CommitmentHash::Sha256, &leaves);
+        let transactions: Vec<_> = signatures
+            .iter()
+            .zip(&salts)
+            .enumerate()
+            .map(|(index, (signature, salt))| {
+                serde_json::json!({
+                    "transaction_hash": signature,
+                    "salt": hex::encode(salt),
+                    "leaf_index": index,
+                    "merkle_path": tree.path(index),
+                })
+            })
+            .collect();
+        serde_json::from_value(serde_json::json!({
+            "slot": 1,
+            "block_hash": "hash",
+            "hash_domains": HASH_DOMAINS,
+            "transactions_root": hex::encode(tree.root()),
+            "commitment": "00",
+            "proof": "00",
+            "old_root": "00",
+            "new_root": "00",
+            "transactions": transactions,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn disclosed_transactions_verify() {
+        let private_dir = std::env::temp_dir().join(format!("solana-listener-disclosure-{}", std::process::id()));
+        let mut block_proof = salted_block(&["a", "b", "c", "d", "e"]);
+        withhold(&mut block_proof, Some(&private_dir));
+        assert!(block_proof.transactions.is_empty());
+
+        for signature in ["a", "b", "c", "d", "e"] {
+            let disclosure = disclose(&private_dir, 1, signature).unwrap();
+            assert_eq!(disclosure.transaction.transaction_hash, signature);
+            assert!(verify(&block_proof, &disclosure));
+        }
+        assert!(disclose(&private_dir, 1, "f").is_none());
+        fs::remove_dir_all(&private_dir).unwrap();
+    }
+
+    #[test]
+    fn leaf_indexes_match_their_paths() {
+        for leaf_count in 1..=17u8 {
+            let leaves: Vec<[u8; 1]> = (0..leaf_count).map(|leaf| [leaf]).collect();
+            let tree = MerkleTree::new(CommitmentHash::Sha256, &leaves);
+            for index in 0..leaf_count as usize {
+                assert!(path_matches_index(index, &tree.path(index)));
+            }
+        }
+    }
+
+    #[test]
+    fn tampered_disclosures_are_rejected() {
+        let block_proof = salted_block(&["a", "b", "c", "d"]);
+        let disclosure = || Disclosure { slot: 1, transaction: block_proof.transactions[1].clone() };
+        assert!(verify(&block_proof, &disclosure()));
+
+        let mut wrong_salt = disclosure();
+        wrong_salt.transaction.salt = Some(hex::encode(random_salt()));
+        assert!(!verify(&block_proof, &wrong_salt));
+        let mut unsalted = disclosure();
+        unsalted.transaction.salt = None;
+        assert!(!verify(&block_proof, &unsalted));
+        for leaf_index in [0, 2, 3, 5] {
+            let mut wrong_index = disclosure();
+            wrong_index.transaction.leaf_index = leaf_index;
+            assert!(!verify(&block_proof, &wrong_index));
+        }
+        let mut other_signature = disclosure();
+        other_signature.transaction.transaction_hash = "c".to_string();
+        assert!(!verify(&block_proof, &other_signature));
+        let mut other_slot = disclosure();
+        other_slot.slot = 2;
+        assert!(!verify(&block_proof, &other_slot));
+
+        let mut tampered_root = block_proof.clone();
+        tampered_root.transactions_root = salted_block(&["a", "b", "c", "d"]).transactions_root;
+        assert!(!verify(&tampered_root, &disclosure()));
+    }
+}
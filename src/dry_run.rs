@@ -0,0 +1,92 @@
+use serde::Serialize;
+use solana_sdk::clock::Slot;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::field::fr_to_hex;
+use crate::witness::{BlockWitness, ExportedWitness};
+
+// State of a dry run, kept apart from the archive in `dry_run/` of the proofs
+// directory so that it never moves the real checkpoint
+pub fn dry_run_dir(proofs_dir: &Path) -> PathBuf {
+    proofs_dir.join("dry_run")
+}
+
+// What the proof of a block would have recorded, and the circuit proofs it
+// would have taken to make
+#[derive(Serialize)]
+pub struct DryRunRecord {
+    pub slot: Slot,
+    pub block_hash: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub leader: Option<String>,
+    pub transactions: usize,
+    // Transactions with decoded memos, NFT events or Anchor records
+    pub annotated_transactions: usize,
+    pub token_transfers: usize,
+    pub stake_activity: usize,
+    pub program_changes: usize,
+    pub votes: usize,
+    pub block_circuit_proofs: usize,
+    pub sum_circuit_proofs: usize,
+    pub totals_circuit_proofs: usize,
+    pub threshold_circuit_proofs: usize,
+    pub signature_circuit_proofs: usize,
+    pub old_root: String,
+    pub new_root: String,
+    // Unix time the block was recorded, for measuring throughput
+    pub recorded_at: i64,
+}
+
+// Instances of the block circuit a witness is proved with
+fn circuit_instances(witness: &BlockWitness) -> usize {
+    witness.chunks.len() + witness.aggregate.is_some() as usize
+}
+
+pub fn record(exported: &ExportedWitness) -> DryRunRecord {
+    let top_level = exported.witness.top_level();
+    let block_circuit_proofs = circuit_instances(&exported.witness)
+        + exported.mint_witnesses.iter().map(|mint| circuit_instances(&mint.witness)).sum::<usize>()
+        + exported.program_changes_witness.as_ref().map(circuit_instances).unwrap_or_default()
+        + exported.votes_witness.as_ref().map(circuit_instances).unwrap_or_default();
+    // One sum and one totals proof per chunk of the block for each of its sums and totals
+    let sums = [&exported.sol_values, &exported.balance_credit_values, &exported.balance_debit_values];
+    let sum_circuit_proofs = sums.iter().filter(|values| values.is_some()).count() * exported.witness.chunks.len();
+    let totals = exported.compute_units.is_some() as usize + exported.fee_values.is_some() as usize;
+    let totals_circuit_proofs = totals * exported.witness.chunks.len();
+
+    DryRunRecord {
+        slot: exported.slot,
+        block_hash: exported.block_hash.clone(),
+        leader: exported.leader.clone(),
+        transactions: exported.signatures.len(),
+        annotated_transactions: exported.annotations.len(),
+        token_transfers: exported.token_transfers.len(),
+        stake_activity: exported.stake_activity.len(),
+        program_changes: exported.program_changes.len(),
+        votes: exported.votes.len(),
+        block_circuit_proofs,
+        sum_circuit_proofs,
+        totals_circuit_proofs,
+        threshold_circuit_proofs: exported.volume_witnesses.len(),
+        signature_circuit_proofs: exported.signed_messages.len(),
+        old_root: fr_to_hex(&top_level.old_root),
+        new_root: fr_to_hex(&top_level.new_root()),
+        recorded_at: SystemTime::now().duration_since(UNIX_EPOCH).expect("System clock before 1970").as_secs() as i64,
+    }
+}
+
+// Appends the record to `records.jsonl` of the dry run directory
+pub fn append(dir: &Path, record: &DryRunRecord) {
+    let mut line = serde_json::to_string(record).expect("Unable to serialize dry run record");
+    line.push('\n');
+
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(dir.join("records.jsonl"))
+        .expect("Unable to open dry run records");
+    file.write_all(line.as_bytes()).expect("Unable to write dry run records");
+}
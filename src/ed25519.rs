@@ -0,0 +1,1117 @@
+use bellman::gadgets::boolean::{AllocatedBit, Boolean};
+use bellman::{groth16, Circuit, ConstraintSystem, LinearCombination, SynthesisError};
+use blstrs::{Bls12, Scalar as Fr};
+use ff::{Field, PrimeField};
+use num_bigint::BigUint;
+use rand::thread_rng;
+use std::sync::OnceLock;
+
+use crate::circuit;
+use crate::signatures::MAX_MESSAGE_LEN;
+
+// Ed25519 signature verification as a circuit. It accepts exactly what
+// `Signature::verify` (ed25519-dalek's verify_strict) accepts: S is below the
+// group order ℓ, A and R decompress to points not of small order, and
+// [S]B = R + [k]A with k = SHA-512(R || A || message) mod ℓ.
+//
+// Elements of GF(2^255 - 19) do not fit the BLS12-381 scalar field, so they
+// are held as limbs of LIMB_BITS bits and every product is reduced with its
+// quotient and carries range-checked. A proof is about five million
+// constraints.
+
+// Bits per limb of a base field element, and limbs per element
+const LIMB_BITS: usize = 85;
+const LIMBS: usize = 3;
+
+// Bits of a scalar below ℓ
+const SCALAR_BITS: usize = 253;
+
+// SHA-512 blocks hashed per signature: R and A, the longest message, the 0x80
+// byte and the 16-byte length
+const SHA512_BLOCKS: usize = (64 + MAX_MESSAGE_LEN + 17).div_ceil(128);
+
+#[rustfmt::skip]
+const SHA512_K: [u64; 80] = [
+    0x428a2f98d728ae22, 0x7137449123ef65cd, 0xb5c0fbcfec4d3b2f, 0xe9b5dba58189dbbc,
+    0x3956c25bf348b538, 0x59f111f1b605d019, 0x923f82a4af194f9b, 0xab1c5ed5da6d8118,
+    0xd807aa98a3030242, 0x12835b0145706fbe, 0x243185be4ee4b28c, 0x550c7dc3d5ffb4e2,
+    0x72be5d74f27b896f, 0x80deb1fe3b1696b1, 0x9bdc06a725c71235, 0xc19bf174cf692694,
+    0xe49b69c19ef14ad2, 0xefbe4786384f25e3, 0x0fc19dc68b8cd5b5, 0x240ca1cc77ac9c65,
+    0x2de92c6f592b0275, 0x4a7484aa6ea6e483, 0x5cb0a9dcbd41fbd4, 0x76f988da831153b5,
+    0x983e5152ee66dfab, 0xa831c66d2db43210, 0xb00327c898fb213f, 0xbf597fc7beef0ee4,
+    0xc6e00bf33da88fc2, 0xd5a79147930aa725, 0x06ca6351e003826f, 0x142929670a0e6e70,
+    0x27b70a8546d22ffc, 0x2e1b21385c26c926, 0x4d2c6dfc5ac42aed, 0x53380d139d95b3df,
+    0x650a73548baf63de, 0x766a0abb3c77b2a8, 0x81c2c92e47edaee6, 0x92722c851482353b,
+    0xa2bfe8a14cf10364, 0xa81a664bbc423001, 0xc24b8b70d0f89791, 0xc76c51a30654be30,
+    0xd192e819d6ef5218, 0xd69906245565a910, 0xf40e35855771202a, 0x106aa07032bbd1b8,
+    0x19a4c116b8d2d0c8, 0x1e376c085141ab53, 0x2748774cdf8eeb99, 0x34b0bcb5e19b48a8,
+    0x391c0cb3c5c95a63, 0x4ed8aa4ae3418acb, 0x5b9cca4f7763e373, 0x682e6ff3d6b2b8a3,
+    0x748f82ee5defb2fc, 0x78a5636f43172f60, 0x84c87814a1f0ab72, 0x8cc702081a6439ec,
+    0x90befffa23631e28, 0xa4506cebde82bde9, 0xbef9a3f7b2c67915, 0xc67178f2e372532b,
+    0xca273eceea26619c, 0xd186b8c721c0c207, 0xeada7dd6cde0eb1e, 0xf57d4f7fee6ed178,
+    0x06f067aa72176fba, 0x0a637dc5a2c898a6, 0x113f9804bef90dae, 0x1b710b35131c471b,
+    0x28db77f523047d84, 0x32caab7b40c72493, 0x3c9ebe0a15c9bebc, 0x431d67c49c100d4c,
+    0x4cc5d4becb3e42b6, 0x597f299cfc657e2a, 0x5fcb6fab3ad6faec, 0x6c44198c4a475817,
+];
+
+#[rustfmt::skip]
+const SHA512_IV: [u64; 8] = [
+    0x6a09e667f3bcc908, 0xbb67ae8584caa73b, 0x3c6ef372fe94f82b, 0xa54ff53a5f1d36f1,
+    0x510e527fade682d1, 0x9b05688c2b3e6c1f, 0x1f83d9abfb41bd6b, 0x5be0cd19137e2179,
+];
+
+struct Curve {
+    // Base field modulus 2^255 - 19
+    p: BigUint,
+    // Order of the base point
+    l: BigUint,
+    d: BigUint,
+    sqrt_m1: BigUint,
+}
+
+fn curve() -> &'static Curve {
+    static CURVE: OnceLock<Curve> = OnceLock::new();
+    CURVE.get_or_init(|| {
+        let p = (BigUint::from(1u32) << 255) - 19u32;
+        let l =
+            (BigUint::from(1u32) << 252) + BigUint::parse_bytes(b"27742317777372353535851937790883648493", 10).unwrap();
+        let inverse = BigUint::from(121666u32).modpow(&(&p - 2u32), &p);
+        let d = (&p - 121665u32) * inverse % &p;
+        let sqrt_m1 = BigUint::from(2u32).modpow(&((&p - 1u32) >> 2), &p);
+        Curve { p, l, d, sqrt_m1 }
+    })
+}
+
+fn invert(x: &BigUint) -> BigUint {
+    let p = &curve().p;
+    x.modpow(&(p - 2u32), p)
+}
+
+// The x-coordinate with lowest bit `sign` of the point with y-coordinate `y`,
+// if there is such a point
+fn recover_x(y: &BigUint, sign: bool) -> Option<BigUint> {
+    let Curve { p, d, sqrt_m1, .. } = curve();
+    let y = y % p;
+    let y2 = &y * &y % p;
+    let u = (&y2 + p - 1u32) % p;
+    let v = (d * &y2 + 1u32) % p;
+    let x2 = u * invert(&v) % p;
+    let mut x = x2.modpow(&((p + 3u32) >> 3), p);
+    if &x * &x % p != x2 {
+        x = x * sqrt_m1 % p;
+    }
+    if &x * &x % p != x2 {
+        return None;
+    }
+    if x.bit(0) != sign {
+        x = (p - x) % p;
+    }
+    Some(x)
+}
+
+fn add_affine((x1, y1): &(BigUint, BigUint), (x2, y2): &(BigUint, BigUint)) -> (BigUint, BigUint) {
+    let Curve { p, d, .. } = curve();
+    let dxy = d * x1 % p * x2 % p * y1 % p * y2 % p;
+    let x = (x1 * y2 + y1 * x2) % p * invert(&((dxy.clone() + 1u32) % p)) % p;
+    let y = (y1 * y2 + x1 * x2) % p * invert(&((p + 1u32 - dxy) % p)) % p;
+    (x, y)
+}
+
+// [2^i]B for every bit of a scalar, in affine coordinates
+fn base_powers() -> &'static [(BigUint, BigUint)] {
+    static POWERS: OnceLock<Vec<(BigUint, BigUint)>> = OnceLock::new();
+    POWERS.get_or_init(|| {
+        let p = &curve().p;
+        let y = BigUint::from(4u32) * invert(&BigUint::from(5u32)) % p;
+        let mut power = (recover_x(&y, false).expect("base point is on the curve"), y);
+        (0..SCALAR_BITS)
+            .map(|_| {
+                let next = add_affine(&power, &power);
+                std::mem::replace(&mut power, next)
+            })
+            .collect()
+    })
+}
+
+// A value below the scalar field modulus as a field element
+fn big_to_fr(value: &BigUint) -> Fr {
+    let mut repr = [0u8; 32];
+    let bytes = value.to_bytes_le();
+    repr[..bytes.len()].copy_from_slice(&bytes);
+    Fr::from_repr(repr).unwrap()
+}
+
+// An integer in the circuit: its linear combination, its value when assigned
+// and a bound on it. Limb arithmetic is sound as long as every bound stays well
+// below the scalar field modulus, which `enforce_carries` asserts.
+#[derive(Clone)]
+struct Num {
+    lc: LinearCombination<Fr>,
+    value: Option<BigUint>,
+    max: BigUint,
+}
+
+impl Num {
+    fn zero() -> Num {
+        Num { lc: LinearCombination::zero(), value: Some(BigUint::default()), max: BigUint::default() }
+    }
+
+    fn constant<CS: ConstraintSystem<Fr>>(value: BigUint) -> Num {
+        Num { lc: LinearCombination::zero() + (big_to_fr(&value), CS::one()), value: Some(value.clone()), max: value }
+    }
+
+    // `if_set` when `bit` is set and `otherwise` when not, for constants; this
+    // costs no constraints
+    fn select_constant<CS: ConstraintSystem<Fr>>(bit: &Boolean, if_set: &BigUint, otherwise: &BigUint) -> Num {
+        Num {
+            lc: bit.lc(CS::one(), big_to_fr(if_set)) + &bit.not().lc(CS::one(), big_to_fr(otherwise)),
+            value: bit.get_value().map(|bit| if bit { if_set.clone() } else { otherwise.clone() }),
+            max: if_set.max(otherwise).clone(),
+        }
+    }
+
+    fn add(&self, other: &Num) -> Num {
+        Num {
+            lc: self.lc.clone() + &other.lc,
+            value: self.value.as_ref().zip(other.value.as_ref()).map(|(a, b)| a + b),
+            max: &self.max + &other.max,
+        }
+    }
+
+    // self - other, where self is known to be the larger
+    fn sub(&self, other: &Num) -> Num {
+        Num {
+            lc: self.lc.clone() - &other.lc,
+            value: self.value.as_ref().zip(other.value.as_ref()).map(|(a, b)| a - b),
+            max: self.max.clone(),
+        }
+    }
+
+    fn scale(&self, factor: &BigUint) -> Num {
+        Num {
+            lc: LinearCombination::zero() + (big_to_fr(factor), &self.lc),
+            value: self.value.as_ref().map(|value| value * factor),
+            max: &self.max * factor,
+        }
+    }
+}
+
+// Allocates the low `bits` bits of `value`, least significant first, each
+// constrained to 0 or 1
+fn alloc_bits<CS: ConstraintSystem<Fr>>(
+    cs: &mut CS,
+    value: Option<&BigUint>,
+    bits: usize,
+) -> Result<Vec<Boolean>, SynthesisError> {
+    let mut allocated = Vec::with_capacity(bits);
+    for bit in 0..bits {
+        let bit_value = value.map(|value| value.bit(bit as u64));
+        allocated.push(Boolean::from(AllocatedBit::alloc(cs.namespace(|| format!("bit {}", bit)), bit_value)?));
+    }
+    Ok(allocated)
+}
+
+// The number `bits` weigh up to, least significant first
+fn pack<CS: ConstraintSystem<Fr>>(bits: &[Boolean]) -> Num {
+    let mut lc = LinearCombination::zero();
+    let mut weight = Fr::ONE;
+    for bit in bits {
+        lc = lc + &bit.lc(CS::one(), weight);
+        weight = weight.double();
+    }
+    let value =
+        bits.iter().rev().try_fold(BigUint::default(), |acc, bit| bit.get_value().map(|bit| (acc << 1) + bit as u32));
+    Num { lc, value, max: (BigUint::from(1u32) << bits.len()) - 1u32 }
+}
+
+fn alloc_num<CS: ConstraintSystem<Fr>>(
+    cs: &mut CS,
+    value: Option<&BigUint>,
+    bits: usize,
+) -> Result<Num, SynthesisError> {
+    Ok(pack::<CS>(&alloc_bits(cs, value, bits)?))
+}
+
+// The value and bound of the integer limbs of LIMB_BITS stand for
+fn combine(limbs: &[Num]) -> (Option<BigUint>, BigUint) {
+    let mut value = Some(BigUint::default());
+    let mut max = BigUint::default();
+    for limb in limbs.iter().rev() {
+        value = value.zip(limb.value.as_ref()).map(|(value, limb)| (value << LIMB_BITS) + limb);
+        max = (max << LIMB_BITS) + &limb.max;
+    }
+    (value, max)
+}
+
+// Constrains the integers two limb sequences (base 2^LIMB_BITS) stand for to
+// be equal. Every limb of `rhs` but the last must already be range-checked
+// below 2^LIMB_BITS. The carry out of each position is range-checked here, so
+// each position's constraint holds over the integers and not just mod the
+// scalar field modulus.
+fn enforce_carries<CS: ConstraintSystem<Fr>>(cs: &mut CS, lhs: &[Num], rhs: &[Num]) -> Result<(), SynthesisError> {
+    let limb = |limbs: &[Num], i: usize| limbs.get(i).cloned().unwrap_or_else(Num::zero);
+    let positions = lhs.len().max(rhs.len());
+    let shift = big_to_fr(&(BigUint::from(1u32) << LIMB_BITS));
+    let mut carry = Num::zero();
+    for i in 0..positions {
+        let cs = &mut cs.namespace(|| format!("position {}", i));
+        let (total, target) = (limb(lhs, i).add(&carry), limb(rhs, i));
+        assert!(total.max.bits() < 253, "limb bound is too close to the field modulus");
+
+        if i + 1 == positions {
+            cs.enforce(|| "top", |lc| lc + &total.lc, |lc| lc + CS::one(), |lc| lc + &target.lc);
+            break;
+        }
+
+        let carry_value = total
+            .value
+            .as_ref()
+            .zip(target.value.as_ref())
+            .and_then(|(total, target)| (total >= target).then(|| (total - target) >> LIMB_BITS));
+        let carry_bits = (&total.max >> LIMB_BITS).bits() as usize;
+        let next = alloc_num(&mut cs.namespace(|| "carry"), carry_value.as_ref(), carry_bits)?;
+        cs.enforce(|| "position", |lc| lc + &total.lc, |lc| lc + CS::one(), |lc| lc + &target.lc + (shift, &next.lc));
+        carry = next;
+    }
+    Ok(())
+}
+
+// Constrains the value of `limbs` to be q·p + the value of `remainder`, which
+// must have LIMBS range-checked limbs. Checked over the integers as
+// value + 19q = remainder + q·2^255.
+fn enforce_congruent<CS: ConstraintSystem<Fr>>(
+    cs: &mut CS,
+    limbs: &[Num],
+    remainder: &[Num],
+) -> Result<(), SynthesisError> {
+    let p = &curve().p;
+    let (value, max) = combine(limbs);
+    let (remainder_value, _) = combine(remainder);
+    let quotient_value =
+        value.zip(remainder_value).and_then(|(value, remainder)| (value >= remainder).then(|| (value - remainder) / p));
+    let quotient = alloc_num(&mut cs.namespace(|| "quotient"), quotient_value.as_ref(), (max / p).bits() as usize)?;
+
+    let mut lhs = limbs.to_vec();
+    lhs[0] = lhs[0].add(&quotient.scale(&BigUint::from(19u32)));
+    let rhs: Vec<Num> = remainder.iter().cloned().chain([quotient]).collect();
+    enforce_carries(&mut cs.namespace(|| "carries"), &lhs, &rhs)
+}
+
+// Limbs of 2^(t - LIMB_BITS)·p, each between 2^(t - 1) and 2^t
+fn multiple_of_p(t: usize) -> [BigUint; LIMBS] {
+    let (high, low) = (BigUint::from(1u32) << t, BigUint::from(1u32) << (t - LIMB_BITS));
+    [&high - &low * 19u32, &high - &low, &high - &low]
+}
+
+// An element of GF(2^255 - 19) as LIMBS limbs, least significant first. Limbs
+// grow past LIMB_BITS bits with additions; products are reduced.
+#[derive(Clone)]
+struct FieldElement {
+    limbs: Vec<Num>,
+}
+
+impl FieldElement {
+    fn constant<CS: ConstraintSystem<Fr>>(value: &BigUint) -> FieldElement {
+        let mask = (BigUint::from(1u32) << LIMB_BITS) - 1u32;
+        FieldElement { limbs: (0..LIMBS).map(|i| Num::constant::<CS>((value >> (i * LIMB_BITS)) & &mask)).collect() }
+    }
+
+    fn one<CS: ConstraintSystem<Fr>>() -> FieldElement {
+        FieldElement::constant::<CS>(&BigUint::from(1u32))
+    }
+
+    fn from_bits<CS: ConstraintSystem<Fr>>(bits: &[Boolean]) -> FieldElement {
+        FieldElement { limbs: bits.chunks(LIMB_BITS).map(pack::<CS>).collect() }
+    }
+
+    // Allocates an element below 2^255
+    fn alloc<CS: ConstraintSystem<Fr>>(cs: &mut CS, value: Option<&BigUint>) -> Result<FieldElement, SynthesisError> {
+        Ok(FieldElement::from_bits::<CS>(&alloc_bits(cs, value, LIMBS * LIMB_BITS)?))
+    }
+
+    fn value(&self) -> Option<BigUint> {
+        combine(&self.limbs).0
+    }
+
+    fn add(&self, other: &FieldElement) -> FieldElement {
+        FieldElement { limbs: self.limbs.iter().zip(&other.limbs).map(|(a, b)| a.add(b)).collect() }
+    }
+
+    // self - other plus a multiple of p large enough that no limb goes negative
+    fn sub<CS: ConstraintSystem<Fr>>(&self, other: &FieldElement) -> FieldElement {
+        let widest = other.limbs.iter().map(|limb| limb.max.bits() as usize).max().unwrap_or(0);
+        let multiple = multiple_of_p(widest.max(LIMB_BITS) + 1);
+        FieldElement {
+            limbs: self
+                .limbs
+                .iter()
+                .zip(&other.limbs)
+                .zip(multiple)
+                .map(|((a, b), m)| a.add(&Num::constant::<CS>(m)).sub(b))
+                .collect(),
+        }
+    }
+
+    fn mul<CS: ConstraintSystem<Fr>>(&self, cs: &mut CS, other: &FieldElement) -> Result<FieldElement, SynthesisError> {
+        // Coefficients of the product of the two limb polynomials, pinned down
+        // by evaluating both sides at as many points
+        let mut products = Vec::with_capacity(2 * LIMBS - 1);
+        for k in 0..2 * LIMBS - 1 {
+            let mut value = Some(BigUint::default());
+            let mut max = BigUint::default();
+            for i in (0..LIMBS).filter(|&i| i <= k && k - i < LIMBS) {
+                let (a, b) = (&self.limbs[i], &other.limbs[k - i]);
+                value = value.zip(a.value.as_ref().zip(b.value.as_ref())).map(|(value, (a, b))| value + a * b);
+                max += &a.max * &b.max;
+            }
+            let var = cs.alloc(
+                || format!("product {}", k),
+                || value.as_ref().map(big_to_fr).ok_or(SynthesisError::AssignmentMissing),
+            )?;
+            products.push(Num { lc: LinearCombination::zero() + var, value, max });
+        }
+        for point in 0..2 * LIMBS - 1 {
+            let powers: Vec<Fr> = (0..2 * LIMBS - 1).map(|k| Fr::from(point as u64).pow_vartime([k as u64])).collect();
+            let evaluate = |limbs: &[Num]| {
+                limbs.iter().zip(&powers).fold(LinearCombination::zero(), |lc, (limb, power)| lc + (*power, &limb.lc))
+            };
+            cs.enforce(
+                || format!("evaluation {}", point),
+                |_| evaluate(&self.limbs),
+                |_| evaluate(&other.limbs),
+                |_| evaluate(&products),
+            );
+        }
+
+        // 2^255 = 19 mod p folds the top coefficients onto the bottom ones
+        let nineteen = BigUint::from(19u32);
+        let folded = [
+            products[0].add(&products[3].scale(&nineteen)),
+            products[1].add(&products[4].scale(&nineteen)),
+            products[2].clone(),
+        ];
+        let p = &curve().p;
+        let (value, _) = combine(&folded);
+        let remainder = FieldElement::alloc(&mut cs.namespace(|| "remainder"), value.map(|value| value % p).as_ref())?;
+        enforce_congruent(&mut cs.namespace(|| "reduction"), &folded, &remainder.limbs)?;
+        Ok(remainder)
+    }
+
+    fn enforce_equal<CS: ConstraintSystem<Fr>>(&self, cs: &mut CS, other: &FieldElement) -> Result<(), SynthesisError> {
+        enforce_congruent(cs, &self.sub::<CS>(other).limbs, &[(); LIMBS].map(|_| Num::zero()))
+    }
+
+    // bit·self, limb by limb
+    fn scale_by_bit<CS: ConstraintSystem<Fr>>(
+        &self,
+        cs: &mut CS,
+        bit: &Boolean,
+    ) -> Result<FieldElement, SynthesisError> {
+        let mut limbs = Vec::with_capacity(self.limbs.len());
+        for (i, limb) in self.limbs.iter().enumerate() {
+            let value =
+                bit.get_value()
+                    .zip(limb.value.as_ref())
+                    .map(|(bit, value)| if bit { value.clone() } else { BigUint::default() });
+            let var = cs.alloc(
+                || format!("limb {}", i),
+                || value.as_ref().map(big_to_fr).ok_or(SynthesisError::AssignmentMissing),
+            )?;
+            cs.enforce(
+                || format!("limb {} selection", i),
+                |_| bit.lc(CS::one(), Fr::ONE),
+                |lc| lc + &limb.lc,
+                |lc| lc + var,
+            );
+            limbs.push(Num { lc: LinearCombination::zero() + var, value, max: limb.max.clone() });
+        }
+        Ok(FieldElement { limbs })
+    }
+}
+
+// Constrains the 255-bit number `bits` weigh up to to be below p
+fn enforce_canonical<CS: ConstraintSystem<Fr>>(cs: &mut CS, bits: &[Boolean]) -> Result<(), SynthesisError> {
+    let p_minus_one = &curve().p - 1u32;
+    let element = FieldElement::from_bits::<CS>(bits);
+    let rest_value = element.value().filter(|value| value <= &p_minus_one).map(|value| &p_minus_one - value);
+    let rest = FieldElement::alloc(&mut cs.namespace(|| "rest"), rest_value.as_ref())?;
+    enforce_carries(cs, &element.add(&rest).limbs, &FieldElement::constant::<CS>(&p_minus_one).limbs)
+}
+
+// Constrains `num`, below 2^SCALAR_BITS, to be below ℓ
+fn enforce_below_order<CS: ConstraintSystem<Fr>>(cs: &mut CS, num: &Num) -> Result<(), SynthesisError> {
+    let l_minus_one = &curve().l - 1u32;
+    let rest_value = num.value.as_ref().filter(|value| *value <= &l_minus_one).map(|value| &l_minus_one - value);
+    let rest = alloc_num(&mut cs.namespace(|| "rest"), rest_value.as_ref(), SCALAR_BITS)?;
+    cs.enforce(
+        || "below order",
+        |lc| lc + &num.lc + &rest.lc,
+        |lc| lc + CS::one(),
+        |lc| lc + (big_to_fr(&l_minus_one), CS::one()),
+    );
+    Ok(())
+}
+
+// A point in extended coordinates (X : Y : Z : T) with x = X/Z, y = Y/Z and
+// xy = T/Z
+#[derive(Clone)]
+struct Point {
+    x: FieldElement,
+    y: FieldElement,
+    z: FieldElement,
+    t: FieldElement,
+}
+
+impl Point {
+    fn identity<CS: ConstraintSystem<Fr>>() -> Point {
+        let zero = FieldElement::constant::<CS>(&BigUint::default());
+        Point { x: zero.clone(), y: FieldElement::one::<CS>(), z: FieldElement::one::<CS>(), t: zero }
+    }
+
+    // The constant point (x, y) if `bit` is set and the identity if not, at no
+    // cost in constraints
+    fn select_constant<CS: ConstraintSystem<Fr>>(bit: &Boolean, (x, y): &(BigUint, BigUint)) -> Point {
+        let p = &curve().p;
+        let select = |if_set: &BigUint, otherwise: u32| {
+            let (if_set, otherwise) =
+                (FieldElement::constant::<CS>(if_set), FieldElement::constant::<CS>(&otherwise.into()));
+            let limbs = if_set
+                .limbs
+                .iter()
+                .zip(&otherwise.limbs)
+                .map(|(a, b)| Num::select_constant::<CS>(bit, a.value.as_ref().unwrap(), b.value.as_ref().unwrap()))
+                .collect();
+            FieldElement { limbs }
+        };
+        Point { x: select(x, 0), y: select(y, 1), z: select(&BigUint::from(1u32), 1), t: select(&(x * y % p), 0) }
+    }
+
+    // Decompresses a 32-byte encoding (y, then the sign of x) given as bits,
+    // constraining the point to be on the curve. Like ed25519-dalek, this
+    // accepts a y that is not reduced.
+    fn decompress<CS: ConstraintSystem<Fr>>(cs: &mut CS, bits: &[Boolean]) -> Result<Point, SynthesisError> {
+        let y = FieldElement::from_bits::<CS>(&bits[..255]);
+        let x_value = y.value().zip(bits[255].get_value()).and_then(|(y, sign)| recover_x(&y, sign));
+        let x_bits = alloc_bits(&mut cs.namespace(|| "x bits"), x_value.as_ref(), 255)?;
+        // With x below p, its lowest bit is its sign
+        enforce_canonical(&mut cs.namespace(|| "x canonical"), &x_bits)?;
+        Boolean::enforce_equal(cs.namespace(|| "x sign"), &x_bits[0], &bits[255])?;
+        let x = FieldElement::from_bits::<CS>(&x_bits);
+
+        // -x^2 + y^2 = 1 + d·x^2·y^2
+        let x2 = x.mul(&mut cs.namespace(|| "x^2"), &x)?;
+        let y2 = y.mul(&mut cs.namespace(|| "y^2"), &y)?;
+        let x2y2 = x2.mul(&mut cs.namespace(|| "x^2 y^2"), &y2)?;
+        let dx2y2 = x2y2.mul(&mut cs.namespace(|| "d x^2 y^2"), &FieldElement::constant::<CS>(&curve().d))?;
+        y2.sub::<CS>(&x2).enforce_equal(&mut cs.namespace(|| "on curve"), &FieldElement::one::<CS>().add(&dx2y2))?;
+
+        let t = x.mul(&mut cs.namespace(|| "t"), &y)?;
+        Ok(Point { x, y, z: FieldElement::one::<CS>(), t })
+    }
+
+    // Unified addition (add-2008-hwcd-3 for a = -1), which is complete on
+    // this curve and so doubles as well
+    fn add<CS: ConstraintSystem<Fr>>(&self, cs: &mut CS, other: &Point) -> Result<Point, SynthesisError> {
+        let two_d = FieldElement::constant::<CS>(&(&curve().d * 2u32 % &curve().p));
+        let a = self.y.sub::<CS>(&self.x).mul(&mut cs.namespace(|| "a"), &other.y.sub::<CS>(&other.x))?;
+        let b = self.y.add(&self.x).mul(&mut cs.namespace(|| "b"), &other.y.add(&other.x))?;
+        let tt = self.t.mul(&mut cs.namespace(|| "t1 t2"), &other.t)?;
+        let c = tt.mul(&mut cs.namespace(|| "c"), &two_d)?;
+        let zz = self.z.mul(&mut cs.namespace(|| "z1 z2"), &other.z)?;
+        let d = zz.add(&zz);
+        let (e, f, g, h) = (b.sub::<CS>(&a), d.sub::<CS>(&c), d.add(&c), b.add(&a));
+        let x = e.mul(&mut cs.namespace(|| "x3"), &f)?;
+        let y = g.mul(&mut cs.namespace(|| "y3"), &h)?;
+        let z = f.mul(&mut cs.namespace(|| "z3"), &g)?;
+        let t = e.mul(&mut cs.namespace(|| "t3"), &h)?;
+        Ok(Point { x, y, z, t })
+    }
+
+    // The point if `bit` is set and the identity if not
+    fn select_or_identity<CS: ConstraintSystem<Fr>>(
+        &self,
+        cs: &mut CS,
+        bit: &Boolean,
+    ) -> Result<Point, SynthesisError> {
+        // (1 - bit) on the lowest limb turns a zero Y or Z into the identity's one
+        let or_one = |element: FieldElement| {
+            let mut limbs = element.limbs;
+            limbs[0] = limbs[0].add(&Num {
+                lc: bit.not().lc(CS::one(), Fr::ONE),
+                value: bit.get_value().map(|bit| BigUint::from(!bit as u32)),
+                max: BigUint::from(1u32),
+            });
+            FieldElement { limbs }
+        };
+        let x = self.x.scale_by_bit(&mut cs.namespace(|| "x"), bit)?;
+        let y = or_one(self.y.scale_by_bit(&mut cs.namespace(|| "y"), bit)?);
+        let z = or_one(self.z.scale_by_bit(&mut cs.namespace(|| "z"), bit)?);
+        let t = self.t.scale_by_bit(&mut cs.namespace(|| "t"), bit)?;
+        Ok(Point { x, y, z, t })
+    }
+
+    // [scalar]self for the scalar `bits` weigh up to, least significant first
+    fn mul_scalar<CS: ConstraintSystem<Fr>>(&self, cs: &mut CS, bits: &[Boolean]) -> Result<Point, SynthesisError> {
+        let mut acc = Point::identity::<CS>();
+        for (i, bit) in bits.iter().enumerate().rev() {
+            let cs = &mut cs.namespace(|| format!("bit {}", i));
+            acc = acc.add(&mut cs.namespace(|| "double"), &acc)?;
+            let addend = self.select_or_identity(&mut cs.namespace(|| "select"), bit)?;
+            acc = acc.add(&mut cs.namespace(|| "add"), &addend)?;
+        }
+        Ok(acc)
+    }
+
+    // Constrains the point not to be of small order: [8]P is the identity
+    // exactly when its X is zero, so X must have an inverse
+    fn enforce_not_small_order<CS: ConstraintSystem<Fr>>(&self, cs: &mut CS) -> Result<(), SynthesisError> {
+        let mut point = self.clone();
+        for i in 0..3 {
+            point = point.add(&mut cs.namespace(|| format!("double {}", i)), &point)?;
+        }
+        let inverse_value = point.x.value().map(|x| invert(&(x % &curve().p)));
+        let inverse = FieldElement::alloc(&mut cs.namespace(|| "inverse"), inverse_value.as_ref())?;
+        let product = point.x.mul(&mut cs.namespace(|| "x inverse"), &inverse)?;
+        product.enforce_equal(&mut cs.namespace(|| "invertible"), &FieldElement::one::<CS>())
+    }
+
+    // Constrains the two points to be equal, comparing x and y projectively
+    fn enforce_equal<CS: ConstraintSystem<Fr>>(&self, cs: &mut CS, other: &Point) -> Result<(), SynthesisError> {
+        let x1 = self.x.mul(&mut cs.namespace(|| "x1 z2"), &other.z)?;
+        let x2 = other.x.mul(&mut cs.namespace(|| "x2 z1"), &self.z)?;
+        x1.enforce_equal(&mut cs.namespace(|| "x"), &x2)?;
+        let y1 = self.y.mul(&mut cs.namespace(|| "y1 z2"), &other.z)?;
+        let y2 = other.y.mul(&mut cs.namespace(|| "y2 z1"), &self.z)?;
+        y1.enforce_equal(&mut cs.namespace(|| "y"), &y2)
+    }
+}
+
+// [scalar]B for the base point B and the scalar `bits` weigh up to, least
+// significant first
+fn mul_base<CS: ConstraintSystem<Fr>>(cs: &mut CS, bits: &[Boolean]) -> Result<Point, SynthesisError> {
+    let mut acc = Point::identity::<CS>();
+    for (i, (bit, power)) in bits.iter().zip(base_powers()).enumerate() {
+        acc = acc.add(&mut cs.namespace(|| format!("bit {}", i)), &Point::select_constant::<CS>(bit, power))?;
+    }
+    Ok(acc)
+}
+
+// A 64-bit SHA-512 word, least significant bit first
+type Word = Vec<Boolean>;
+
+fn word_constant(value: u64) -> Word {
+    (0..64).map(|i| Boolean::constant((value >> i) & 1 == 1)).collect()
+}
+
+fn word_value(word: &[Boolean]) -> Option<u64> {
+    word.iter().rev().try_fold(0u64, |acc, bit| bit.get_value().map(|bit| (acc << 1) | bit as u64))
+}
+
+fn rotr(word: &Word, n: usize) -> Word {
+    (0..64).map(|i| word[(i + n) % 64].clone()).collect()
+}
+
+fn shr(word: &Word, n: usize) -> Word {
+    (0..64).map(|i| word.get(i + n).cloned().unwrap_or(Boolean::constant(false))).collect()
+}
+
+fn xor3<CS: ConstraintSystem<Fr>>(cs: &mut CS, a: &Word, b: &Word, c: &Word) -> Result<Word, SynthesisError> {
+    let mut word = Vec::with_capacity(64);
+    for i in 0..64 {
+        let ab = Boolean::xor(cs.namespace(|| format!("bit {} ab", i)), &a[i], &b[i])?;
+        word.push(Boolean::xor(cs.namespace(|| format!("bit {}", i)), &ab, &c[i])?);
+    }
+    Ok(word)
+}
+
+// Sum of `words` mod 2^64
+fn add_words<CS: ConstraintSystem<Fr>>(cs: &mut CS, words: &[&Word]) -> Result<Word, SynthesisError> {
+    let mut lc = LinearCombination::zero();
+    let mut value = Some(0u128);
+    for word in words {
+        lc = lc + &pack::<CS>(word).lc;
+        value = value.zip(word_value(word)).map(|(sum, word)| sum + word as u128);
+    }
+    // The sum of n words needs 64 bits plus the bit length of n - 1
+    let bits = 64 + (usize::BITS - (words.len() - 1).leading_zeros()) as usize;
+    let sum = alloc_bits(cs, value.map(BigUint::from).as_ref(), bits)?;
+    let packed = pack::<CS>(&sum);
+    cs.enforce(|| "sum", |_| lc, |lc| lc + CS::one(), |lc| lc + &packed.lc);
+    Ok(sum[..64].to_vec())
+}
+
+// The SHA-512 compression function
+fn compress<CS: ConstraintSystem<Fr>>(
+    cs: &mut CS,
+    state: &[Word],
+    block: &[Word],
+) -> Result<Vec<Word>, SynthesisError> {
+    let mut w = block.to_vec();
+    for t in 16..80 {
+        let cs = &mut cs.namespace(|| format!("schedule {}", t));
+        let s0 = xor3(&mut cs.namespace(|| "s0"), &rotr(&w[t - 15], 1), &rotr(&w[t - 15], 8), &shr(&w[t - 15], 7))?;
+        let s1 = xor3(&mut cs.namespace(|| "s1"), &rotr(&w[t - 2], 19), &rotr(&w[t - 2], 61), &shr(&w[t - 2], 6))?;
+        let next = add_words(&mut cs.namespace(|| "sum"), &[&s1, &w[t - 7], &s0, &w[t - 16]])?;
+        w.push(next);
+    }
+
+    // Working variables a to h
+    let mut v = state.to_vec();
+    for (t, w) in w.iter().enumerate() {
+        let cs = &mut cs.namespace(|| format!("round {}", t));
+        let big_s1 = xor3(&mut cs.namespace(|| "S1"), &rotr(&v[4], 14), &rotr(&v[4], 18), &rotr(&v[4], 41))?;
+        let big_s0 = xor3(&mut cs.namespace(|| "S0"), &rotr(&v[0], 28), &rotr(&v[0], 34), &rotr(&v[0], 39))?;
+        let ch = (0..64)
+            .map(|i| Boolean::sha256_ch(cs.namespace(|| format!("ch {}", i)), &v[4][i], &v[5][i], &v[6][i]))
+            .collect::<Result<Word, _>>()?;
+        let maj = (0..64)
+            .map(|i| Boolean::sha256_maj(cs.namespace(|| format!("maj {}", i)), &v[0][i], &v[1][i], &v[2][i]))
+            .collect::<Result<Word, _>>()?;
+        let k = word_constant(SHA512_K[t]);
+        let e = add_words(&mut cs.namespace(|| "e"), &[&v[3], &v[7], &big_s1, &ch, &k, w])?;
+        let a = add_words(&mut cs.namespace(|| "a"), &[&v[7], &big_s1, &ch, &k, w, &big_s0, &maj])?;
+        v.pop();
+        v.insert(0, a);
+        v[4] = e;
+    }
+
+    let mut next = Vec::with_capacity(8);
+    for (i, (word, working)) in state.iter().zip(&v).enumerate() {
+        next.push(add_words(&mut cs.namespace(|| format!("state {}", i)), &[word, working])?);
+    }
+    Ok(next)
+}
+
+// SHA-512 of `prefix` (whole bytes, each least significant bit first) followed
+// by a private message of at most MAX_MESSAGE_LEN bytes. All SHA512_BLOCKS
+// blocks are hashed; which bytes are message, the padding after it and the
+// block whose output is the digest are constrained byte by byte. Returns the
+// digest as the bits of a little-endian integer.
+fn sha512<CS: ConstraintSystem<Fr>>(
+    cs: &mut CS,
+    prefix: &[Boolean],
+    message: Option<&[u8]>,
+) -> Result<Vec<Boolean>, SynthesisError> {
+    let (prefix_len, total) = (prefix.len() / 8, SHA512_BLOCKS * 128);
+    let length = message.map(|message| prefix_len + message.len());
+    let final_block = length.map(|length| (length + 17).div_ceil(128) - 1);
+    let padded = message.zip(final_block).map(|(message, final_block)| {
+        let length = prefix_len + message.len();
+        let mut padded = vec![0u8; total];
+        padded[prefix_len..length].copy_from_slice(message);
+        padded[length] = 0x80;
+        let end = (final_block + 1) * 128;
+        padded[end - 2..end].copy_from_slice(&((8 * length) as u16).to_be_bytes());
+        padded
+    });
+
+    // Which block is the last, one-hot
+    let mut finals = Vec::with_capacity(SHA512_BLOCKS);
+    for block in 0..SHA512_BLOCKS {
+        let value = final_block.map(|final_block| final_block == block);
+        finals.push(Boolean::from(AllocatedBit::alloc(cs.namespace(|| format!("final {}", block)), value)?));
+    }
+    cs.enforce(
+        || "one final block",
+        |_| finals.iter().fold(LinearCombination::zero(), |lc, last| lc + &last.lc(CS::one(), Fr::ONE)),
+        |lc| lc + CS::one(),
+        |lc| lc + CS::one(),
+    );
+
+    let mut bytes: Vec<Vec<Boolean>> = prefix.chunks(8).map(<[Boolean]>::to_vec).collect();
+    let mut in_message = vec![Boolean::constant(true); prefix_len];
+    let mut byte_values = Vec::with_capacity(total);
+    byte_values.extend(bytes.iter().map(|bits| pack::<CS>(bits)));
+    for j in prefix_len..total {
+        let cs = &mut cs.namespace(|| format!("byte {}", j));
+        let value = padded.as_ref().map(|padded| BigUint::from(padded[j]));
+        let bits = alloc_bits(&mut cs.namespace(|| "bits"), value.as_ref(), 8)?;
+        let byte = pack::<CS>(&bits);
+        let flag = Boolean::from(AllocatedBit::alloc(cs.namespace(|| "in message"), length.map(|length| j < length))?);
+        let previous = &in_message[j - 1];
+
+        // The message is a prefix of the bytes: once out, never back in
+        cs.enforce(
+            || "contiguous",
+            |_| flag.lc(CS::one(), Fr::ONE),
+            |_| previous.not().lc(CS::one(), Fr::ONE),
+            |lc| lc,
+        );
+
+        // Bytes after the message are zero, except 0x80 right after it and the
+        // length at the end of the final block
+        let first = previous.lc(CS::one(), Fr::ONE) - &flag.lc(CS::one(), Fr::ONE);
+        let padding = byte.lc.clone() - &(LinearCombination::zero() + (Fr::from(128u64), &first));
+        if j % 128 < 126 {
+            cs.enforce(|| "padding", |_| padding, |_| flag.not().lc(CS::one(), Fr::ONE), |lc| lc);
+        } else {
+            let excess_value =
+                byte.value.as_ref().zip(flag.get_value()).zip(previous.get_value()).map(|((byte, flag), previous)| {
+                    let first = Fr::from(previous as u64) - Fr::from(flag as u64);
+                    (big_to_fr(byte) - first * Fr::from(128u64)) * Fr::from(!flag as u64)
+                });
+            let excess = cs.alloc(|| "excess value", || excess_value.ok_or(SynthesisError::AssignmentMissing))?;
+            cs.enforce(|| "excess", |_| padding, |_| flag.not().lc(CS::one(), Fr::ONE), |lc| lc + excess);
+            let last = &finals[j / 128];
+            cs.enforce(
+                || "length only in the final block",
+                |lc| lc + excess,
+                |_| last.not().lc(CS::one(), Fr::ONE),
+                |lc| lc,
+            );
+        }
+        bytes.push(bits);
+        byte_values.push(byte);
+        in_message.push(flag);
+    }
+
+    // The message ends early enough in the final block to leave room for the
+    // padding, and too late to have fit the block before it
+    let message_length =
+        in_message.iter().fold(LinearCombination::zero(), |lc, flag| lc + &flag.lc(CS::one(), Fr::ONE));
+    for (block, last) in finals.iter().enumerate() {
+        let cs = &mut cs.namespace(|| format!("final block {}", block));
+        let start = block * 128;
+        cs.enforce(
+            || "room for padding",
+            |_| last.lc(CS::one(), Fr::ONE),
+            |_| in_message[start + 111].lc(CS::one(), Fr::ONE),
+            |lc| lc,
+        );
+        if block > 0 {
+            cs.enforce(
+                || "past the previous block",
+                |_| last.lc(CS::one(), Fr::ONE),
+                |_| in_message[start - 17].not().lc(CS::one(), Fr::ONE),
+                |lc| lc,
+            );
+        }
+        cs.enforce(
+            || "length",
+            |_| last.lc(CS::one(), Fr::ONE),
+            |_| {
+                LinearCombination::zero()
+                    + (Fr::from(256u64), &byte_values[start + 126].lc)
+                    + &byte_values[start + 127].lc
+                    - (Fr::from(8u64), &message_length)
+            },
+            |lc| lc,
+        );
+    }
+
+    let mut state: Vec<Word> = SHA512_IV.iter().map(|&word| word_constant(word)).collect();
+    let mut outputs = Vec::with_capacity(SHA512_BLOCKS);
+    for block in 0..SHA512_BLOCKS {
+        let words: Vec<Word> =
+            (0..16).map(|w| (0..64).map(|i| bytes[128 * block + 8 * w + 7 - i / 8][i % 8].clone()).collect()).collect();
+        state = compress(&mut cs.namespace(|| format!("block {}", block)), &state, &words)?;
+        outputs.push(state.clone());
+    }
+
+    // Each digest word is the final block's output word
+    let mut digest = Vec::with_capacity(8);
+    for w in 0..8 {
+        let cs = &mut cs.namespace(|| format!("digest {}", w));
+        let mut selected = LinearCombination::zero();
+        let mut value = Some(0u64);
+        for (block, (output, last)) in outputs.iter().zip(&finals).enumerate() {
+            let word = pack::<CS>(&output[w]);
+            let product_value =
+                last.get_value().zip(word_value(&output[w])).map(|(last, word)| if last { word } else { 0 });
+            let product = cs.alloc(
+                || format!("block {}", block),
+                || product_value.map(Fr::from).ok_or(SynthesisError::AssignmentMissing),
+            )?;
+            cs.enforce(
+                || format!("block {} selection", block),
+                |_| last.lc(CS::one(), Fr::ONE),
+                |lc| lc + &word.lc,
+                |lc| lc + product,
+            );
+            selected = selected + product;
+            value = value.zip(product_value).map(|(value, product)| value + product);
+        }
+        let bits = alloc_bits(&mut cs.namespace(|| "bits"), value.map(BigUint::from).as_ref(), 64)?;
+        let packed = pack::<CS>(&bits);
+        cs.enforce(|| "packing", |_| selected, |lc| lc + CS::one(), |lc| lc + &packed.lc);
+        digest.push(bits);
+    }
+
+    // Words are big-endian, the integer little-endian
+    Ok((0..512).map(|bit| digest[bit / 64][8 * (7 - (bit / 8) % 8) + bit % 8].clone()).collect())
+}
+
+// h mod ℓ for the 512-bit number `bits` weigh up to: h = t·ℓ + k with k below
+// ℓ, checked limb by limb. Returns the bits of k.
+fn reduce_scalar<CS: ConstraintSystem<Fr>>(cs: &mut CS, bits: &[Boolean]) -> Result<Vec<Boolean>, SynthesisError> {
+    let l = &curve().l;
+    let h = pack::<CS>(bits).value;
+    let quotient_bits = ((BigUint::from(1u32) << bits.len()) / l).bits() as usize;
+    let quotient = alloc_bits(&mut cs.namespace(|| "quotient"), h.as_ref().map(|h| h / l).as_ref(), quotient_bits)?;
+    let k = alloc_bits(&mut cs.namespace(|| "k"), h.as_ref().map(|h| h % l).as_ref(), SCALAR_BITS)?;
+
+    let limbs = |bits: &[Boolean]| bits.chunks(LIMB_BITS).map(pack::<CS>).collect::<Vec<Num>>();
+    let (h_limbs, t_limbs, k_limbs) = (limbs(bits), limbs(&quotient), limbs(&k));
+    let mask = (BigUint::from(1u32) << LIMB_BITS) - 1u32;
+    let l_limbs: Vec<BigUint> = (0..LIMBS).map(|i| (l >> (i * LIMB_BITS)) & &mask).collect();
+
+    // ℓ is a constant, so t·ℓ is linear in the limbs of t
+    let mut lhs = vec![Num::zero(); t_limbs.len() + LIMBS - 1];
+    for (i, t) in t_limbs.iter().enumerate() {
+        for (j, l) in l_limbs.iter().enumerate() {
+            lhs[i + j] = lhs[i + j].add(&t.scale(l));
+        }
+    }
+    for (i, k) in k_limbs.iter().enumerate() {
+        lhs[i] = lhs[i].add(k);
+    }
+    enforce_carries(&mut cs.namespace(|| "division"), &lhs, &h_limbs)?;
+    enforce_below_order(&mut cs.namespace(|| "k below order"), &pack::<CS>(&k))?;
+    Ok(k)
+}
+
+// Allocates 32 bytes as two public inputs of 16 bytes each, little-endian, and
+// returns their bits, least significant first
+fn input_bytes<CS: ConstraintSystem<Fr>>(cs: &mut CS, bytes: Option<&[u8]>) -> Result<Vec<Boolean>, SynthesisError> {
+    let mut bits = Vec::with_capacity(256);
+    for half in 0..2 {
+        let cs = &mut cs.namespace(|| format!("half {}", half));
+        let value = bytes.map(|bytes| BigUint::from_bytes_le(&bytes[16 * half..16 * (half + 1)]));
+        let input =
+            cs.alloc_input(|| "input", || value.as_ref().map(big_to_fr).ok_or(SynthesisError::AssignmentMissing))?;
+        let half_bits = alloc_bits(&mut cs.namespace(|| "bits"), value.as_ref(), 128)?;
+        let packed = pack::<CS>(&half_bits);
+        cs.enforce(|| "packing", |lc| lc + &packed.lc, |lc| lc + CS::one(), |lc| lc + input);
+        bits.extend(half_bits);
+    }
+    Ok(bits)
+}
+
+// Proves that a signature (R, then S) verifies against the fee payer's key for
+// a private message of at most MAX_MESSAGE_LEN bytes.
+//
+// Public inputs: fee payer, R and S, each as two 16-byte little-endian halves.
+struct SignatureCircuit {
+    fee_payer: Option<[u8; 32]>,
+    signature: Option<[u8; 64]>,
+    message: Option<Vec<u8>>,
+}
+
+impl Circuit<Fr> for SignatureCircuit {
+    fn synthesize<CS: ConstraintSystem<Fr>>(self, cs: &mut CS) -> Result<(), SynthesisError> {
+        let public_key_bits =
+            input_bytes(&mut cs.namespace(|| "fee payer"), self.fee_payer.as_ref().map(|key| &key[..]))?;
+        let r_bits = input_bytes(&mut cs.namespace(|| "R"), self.signature.as_ref().map(|signature| &signature[..32]))?;
+        let s_bits = input_bytes(&mut cs.namespace(|| "S"), self.signature.as_ref().map(|signature| &signature[32..]))?;
+
+        // S is below ℓ: its top bits are zero and the rest is below ℓ
+        for (i, bit) in s_bits.iter().enumerate().skip(SCALAR_BITS) {
+            Boolean::enforce_equal(cs.namespace(|| format!("S bit {}", i)), bit, &Boolean::constant(false))?;
+        }
+        enforce_below_order(&mut cs.namespace(|| "S below order"), &pack::<CS>(&s_bits[..SCALAR_BITS]))?;
+
+        let public_key = Point::decompress(&mut cs.namespace(|| "A"), &public_key_bits)?;
+        let r = Point::decompress(&mut cs.namespace(|| "R point"), &r_bits)?;
+        public_key.enforce_not_small_order(&mut cs.namespace(|| "A order"))?;
+        r.enforce_not_small_order(&mut cs.namespace(|| "R order"))?;
+
+        let prefix: Vec<Boolean> = r_bits.iter().chain(&public_key_bits).cloned().collect();
+        let digest = sha512(&mut cs.namespace(|| "hash"), &prefix, self.message.as_deref())?;
+        let k = reduce_scalar(&mut cs.namespace(|| "k"), &digest)?;
+
+        // [S]B = R + [k]A
+        let lhs = mul_base(&mut cs.namespace(|| "[S]B"), &s_bits[..SCALAR_BITS])?;
+        let ka = public_key.mul_scalar(&mut cs.namespace(|| "[k]A"), &k)?;
+        let rhs = r.add(&mut cs.namespace(|| "R + [k]A"), &ka)?;
+        lhs.enforce_equal(&mut cs.namespace(|| "verification"), &rhs)
+    }
+}
+
+fn empty_signature_circuit() -> SignatureCircuit {
+    SignatureCircuit { fee_payer: None, signature: None, message: None }
+}
+
+// Generate parameters for the signature circuit, which has one shape for every
+// message up to MAX_MESSAGE_LEN
+pub fn generate_signature_parameters() -> groth16::Parameters<Bls12> {
+    groth16::generate_random_parameters::<Bls12, _, _>(empty_signature_circuit(), &mut thread_rng()).unwrap()
+}
+
+pub fn generate_initial_signature_parameters() -> groth16::Parameters<Bls12> {
+    circuit::initial_parameters(empty_signature_circuit())
+}
+
+// Proves that `signature` verifies against `fee_payer` for `message`, which
+// must already have been checked natively and be at most MAX_MESSAGE_LEN bytes
+pub fn prove_signature(
+    params: &groth16::Parameters<Bls12>,
+    fee_payer: &[u8; 32],
+    signature: &[u8; 64],
+    message: &[u8],
+) -> groth16::Proof<Bls12> {
+    let circuit =
+        SignatureCircuit { fee_payer: Some(*fee_payer), signature: Some(*signature), message: Some(message.to_vec()) };
+    groth16::create_random_proof(circuit, params, &mut thread_rng()).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bellman::gadgets::test::TestConstraintSystem;
+    use bellman::{Index, Variable};
+    use rand::{thread_rng, RngCore};
+    use sha2::{Digest, Sha512};
+    use solana_sdk::signature::{Keypair, Signer};
+
+    fn random_bytes(len: usize) -> Vec<u8> {
+        let mut bytes = vec![0u8; len];
+        thread_rng().fill_bytes(&mut bytes);
+        bytes
+    }
+
+    #[test]
+    fn field_mul_reduces() {
+        let p = &curve().p;
+        let (a, b) = (BigUint::from_bytes_le(&random_bytes(32)) % p, BigUint::from_bytes_le(&random_bytes(32)) % p);
+        let mut cs = TestConstraintSystem::<Fr>::new();
+        let x = FieldElement::alloc(&mut cs.namespace(|| "a"), Some(&a)).unwrap();
+        let y = FieldElement::alloc(&mut cs.namespace(|| "b"), Some(&b)).unwrap();
+        let product = x.sub::<TestConstraintSystem<Fr>>(&y).mul(&mut cs.namespace(|| "product"), &x).unwrap();
+        assert_eq!(product.value(), Some((&a + p - &b) * &a % p));
+        assert!(cs.is_satisfied(), "{:?}", cs.which_is_unsatisfied());
+    }
+
+    #[test]
+    fn sha512_matches_sha2() {
+        for len in [0, 47, 48, MAX_MESSAGE_LEN] {
+            let (prefix, message) = (random_bytes(64), random_bytes(len));
+            let mut cs = TestConstraintSystem::<Fr>::new();
+            let prefix_bits =
+                alloc_bits(&mut cs.namespace(|| "prefix"), Some(&BigUint::from_bytes_le(&prefix)), 512).unwrap();
+            let digest = sha512(&mut cs.namespace(|| "hash"), &prefix_bits, Some(&message)).unwrap();
+            let expected = Sha512::new().chain_update(&prefix).chain_update(&message).finalize();
+            assert_eq!(
+                pack::<TestConstraintSystem<Fr>>(&digest).value,
+                Some(BigUint::from_bytes_le(&expected)),
+                "length {}",
+                len
+            );
+            assert!(cs.is_satisfied(), "length {}: {:?}", len, cs.which_is_unsatisfied());
+        }
+    }
+
+    // Checks each constraint as it is added and keeps only the assignment, so
+    // the whole circuit fits in memory, unlike with TestConstraintSystem
+    struct SatisfactionChecker {
+        inputs: Vec<Fr>,
+        aux: Vec<Fr>,
+        unsatisfied: Option<String>,
+    }
+
+    impl SatisfactionChecker {
+        fn new() -> Self {
+            SatisfactionChecker { inputs: vec![Fr::ONE], aux: Vec::new(), unsatisfied: None }
+        }
+
+        fn eval(&self, lc: &LinearCombination<Fr>) -> Fr {
+            lc.as_ref().iter().fold(Fr::ZERO, |sum, (variable, coeff)| {
+                sum + *coeff
+                    * match variable.get_unchecked() {
+                        Index::Input(i) => self.inputs[i],
+                        Index::Aux(i) => self.aux[i],
+                    }
+            })
+        }
+    }
+
+    impl ConstraintSystem<Fr> for SatisfactionChecker {
+        type Root = Self;
+
+        fn alloc<F, A, AR>(&mut self, _: A, f: F) -> Result<Variable, SynthesisError>
+        where
+            F: FnOnce() -> Result<Fr, SynthesisError>,
+            A: FnOnce() -> AR,
+            AR: Into<String>,
+        {
+            self.aux.push(f()?);
+            Ok(Variable::new_unchecked(Index::Aux(self.aux.len() - 1)))
+        }
+
+        fn alloc_input<F, A, AR>(&mut self, _: A, f: F) -> Result<Variable, SynthesisError>
+        where
+            F: FnOnce() -> Result<Fr, SynthesisError>,
+            A: FnOnce() -> AR,
+            AR: Into<String>,
+        {
+            self.inputs.push(f()?);
+            Ok(Variable::new_unchecked(Index::Input(self.inputs.len() - 1)))
+        }
+
+        fn enforce<A, AR, LA, LB, LC>(&mut self, annotation: A, a: LA, b: LB, c: LC)
+        where
+            A: FnOnce() -> AR,
+            AR: Into<String>,
+            LA: FnOnce(LinearCombination<Fr>) -> LinearCombination<Fr>,
+            LB: FnOnce(LinearCombination<Fr>) -> LinearCombination<Fr>,
+            LC: FnOnce(LinearCombination<Fr>) -> LinearCombination<Fr>,
+        {
+            let (a, b, c) = (a(LinearCombination::zero()), b(LinearCombination::zero()), c(LinearCombination::zero()));
+            if self.unsatisfied.is_none() && self.eval(&a) * self.eval(&b) != self.eval(&c) {
+                self.unsatisfied = Some(annotation().into());
+            }
+        }
+
+        fn push_namespace<NR, N>(&mut self, _: N)
+        where
+            NR: Into<String>,
+            N: FnOnce() -> NR,
+        {
+        }
+
+        fn pop_namespace(&mut self) {}
+
+        fn get_root(&mut self) -> &mut Self::Root {
+            self
+        }
+    }
+
+    // Whether the circuit is satisfied, and its public inputs if so
+    fn check(fee_payer: [u8; 32], signature: [u8; 64], message: &[u8]) -> Option<Vec<Fr>> {
+        let circuit =
+            SignatureCircuit { fee_payer: Some(fee_payer), signature: Some(signature), message: Some(message.to_vec()) };
+        let mut cs = SatisfactionChecker::new();
+        match circuit.synthesize(&mut cs) {
+            Ok(()) if cs.unsatisfied.is_none() => Some(cs.inputs[1..].to_vec()),
+            _ => None,
+        }
+    }
+
+    // Synthesizes the whole circuit, about five million constraints, once per
+    // case; run with `cargo test --release --features ed25519 -- --ignored`
+    #[test]
+    #[ignore]
+    fn verifies_fee_payer_signature() {
+        let keypair = Keypair::new();
+        let fee_payer = keypair.pubkey().to_bytes();
+        let message = random_bytes(300);
+        let signature: [u8; 64] = keypair.sign_message(&message).as_ref().try_into().unwrap();
+
+        let inputs = check(fee_payer, signature, &message).expect("valid signature is not accepted");
+        let expected = solana_block_verifier::signature_inputs(&fee_payer, &signature);
+        assert_eq!(
+            inputs.iter().map(|input| input.to_repr()).collect::<Vec<_>>(),
+            expected.iter().map(|input| input.to_bytes()).collect::<Vec<_>>()
+        );
+
+        let mut tampered_message = message.clone();
+        tampered_message[0] ^= 1;
+        assert!(check(fee_payer, signature, &tampered_message).is_none());
+        // S, then R
+        for byte in [40, 0] {
+            let mut tampered = signature;
+            tampered[byte] ^= 1;
+            assert!(check(fee_payer, tampered, &message).is_none(), "tampered byte {}", byte);
+        }
+        assert!(check(Keypair::new().pubkey().to_bytes(), signature, &message).is_none());
+    }
+}
@@ -0,0 +1,114 @@
+use log::error;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::time::sleep;
+
+use crate::config::ElectionConfig;
+
+// Lease-based leader election between instances sharing the same storage. The
+// leader keeps renewing a lock file; a standby takes the lock over once it has
+// not been renewed for `ttl_secs`.
+pub struct LeaderElection {
+    lock_path: PathBuf,
+    instance_id: String,
+    ttl: Duration,
+}
+
+#[derive(Serialize, Deserialize)]
+struct LeaderRecord {
+    instance_id: String,
+    expires_at: u64,
+}
+
+fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs()
+}
+
+impl LeaderElection {
+    pub fn new(config: &ElectionConfig) -> Self {
+        LeaderElection {
+            lock_path: config.lock_path.clone(),
+            instance_id: config.instance_id.clone(),
+            ttl: Duration::from_secs(config.ttl_secs),
+        }
+    }
+
+    fn record(&self) -> LeaderRecord {
+        LeaderRecord {
+            instance_id: self.instance_id.clone(),
+            expires_at: now() + self.ttl.as_secs(),
+        }
+    }
+
+    fn create(&self) -> io::Result<bool> {
+        match OpenOptions::new().write(true).create_new(true).open(&self.lock_path) {
+            Ok(mut file) => {
+                file.write_all(serde_json::to_string(&self.record())?.as_bytes())?;
+                Ok(true)
+            }
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    // Acquires or renews leadership, returning whether this instance leads
+    pub fn try_lead(&self) -> io::Result<bool> {
+        if self.create()? {
+            return Ok(true);
+        }
+
+        let current: LeaderRecord = match fs::read_to_string(&self.lock_path) {
+            Ok(contents) => serde_json::from_str(&contents)?,
+            // Another instance is in the middle of a takeover
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(false),
+            Err(e) => return Err(e),
+        };
+
+        if current.instance_id == self.instance_id {
+            let tmp_path = self.lock_path.with_extension(format!("{}.tmp", self.instance_id));
+            fs::write(&tmp_path, serde_json::to_string(&self.record())?)?;
+            fs::rename(&tmp_path, &self.lock_path)?;
+            return Ok(true);
+        }
+
+        if current.expires_at < now() {
+            // Move the stale lock aside; only one standby's rename can succeed
+            let stale_path = self.lock_path.with_extension(format!("stale.{}.{}", self.instance_id, now()));
+            if fs::rename(&self.lock_path, &stale_path).is_ok() {
+                let _ = fs::remove_file(&stale_path);
+                return self.create();
+            }
+        }
+
+        Ok(false)
+    }
+
+    // Keeps trying to lead in the background, renewing well within the TTL so
+    // a healthy leader never lapses. The returned flag tracks leadership.
+    pub fn spawn(self) -> Arc<AtomicBool> {
+        let leading = Arc::new(AtomicBool::new(false));
+        let flag = leading.clone();
+        let interval = (self.ttl / 3).max(Duration::from_secs(1));
+
+        tokio::spawn(async move {
+            loop {
+                let is_leader = match self.try_lead() {
+                    Ok(is_leader) => is_leader,
+                    Err(e) => {
+                        error!("Error renewing leadership: {:?}", e);
+                        false
+                    }
+                };
+                flag.store(is_leader, Ordering::SeqCst);
+                sleep(interval).await;
+            }
+        });
+
+        leading
+    }
+}
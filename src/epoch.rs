@@ -0,0 +1,116 @@
+use bellman::groth16;
+use blstrs::{Bls12, Scalar as Fr};
+use ff::Field;
+use log::{error, info};
+use serde::{Deserialize, Serialize};
+use solana_sdk::clock::{Epoch, Slot};
+use solana_sdk::epoch_schedule::EpochSchedule;
+use solana_sdk::hash::Hash;
+use std::fs;
+use std::path::Path;
+
+use crate::config::CommitmentHash;
+use crate::field::{fr_from_hex, fr_to_hex, hash_to_fr};
+use crate::merkle::MerkleTree;
+use crate::serialization;
+use crate::storage::ObjectStorage;
+use crate::witness::WitnessAccumulator;
+use crate::{list_proof_slots, load_proof, proof_file_name, prove_chunks, ChunkProof};
+
+// Roll-up of every block proved within one epoch. The aggregate proof is a
+// regular circuit instance over the block commitments, seeded with
+// `block_hashes_root` so it also binds the set of blocks it covers.
+#[derive(Serialize, Deserialize)]
+pub struct EpochSummary {
+    pub epoch: Epoch,
+    pub first_slot: Slot,
+    pub last_slot: Slot,
+    // Slots of the proved blocks, in order; leaf `i` of the tree and the witness is `slots[i]`
+    pub slots: Vec<Slot>,
+    // Merkle root over the block hashes of those blocks
+    pub block_hashes_root: String,
+    pub commitment: String,
+    pub proof: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub chunks: Vec<ChunkProof>,
+}
+
+pub fn summary_file_name(epoch: Epoch) -> String {
+    format!("epoch_summary_{}.json", epoch)
+}
+
+// Builds the summary of `epoch` from the block proofs in the proofs directory,
+// or `None` if no block of that epoch was proved
+pub fn summarize(
+    epoch: Epoch,
+    schedule: &EpochSchedule,
+    proofs_dir: &Path,
+    params: &groth16::Parameters<Bls12>,
+    memory_cap: usize,
+) -> Option<EpochSummary> {
+    let first_slot = schedule.get_first_slot_in_epoch(epoch);
+    let last_slot = schedule.get_last_slot_in_epoch(epoch);
+    let slots: Vec<Slot> = list_proof_slots(proofs_dir)
+        .into_iter()
+        .filter(|slot| (first_slot..=last_slot).contains(slot))
+        .collect();
+    if slots.is_empty() {
+        return None;
+    }
+
+    let mut block_hashes = Vec::with_capacity(slots.len());
+    let mut commitments = Vec::with_capacity(slots.len());
+    for slot in &slots {
+        let block_proof = load_proof(&proofs_dir.join(proof_file_name(*slot)));
+        let block_hash: Hash = block_proof.block_hash.parse().expect("Invalid block hash in proof file");
+        block_hashes.push(block_hash.to_bytes());
+        commitments.push(fr_from_hex(&block_proof.commitment).expect("Invalid commitment in proof file"));
+    }
+    let block_hashes_root = MerkleTree::new(CommitmentHash::Sha256, &block_hashes).root();
+
+    let mut accumulator = WitnessAccumulator::new(hash_to_fr(&block_hashes_root), memory_cap);
+    for commitment in commitments {
+        if let Err(e) = accumulator.push(commitment) {
+            error!("Unable to build witness for epoch {}: {}", epoch, e);
+            return None;
+        }
+    }
+    // Epoch aggregates stand on their own rather than extending the block accumulator
+    let witness = match accumulator.finish(Fr::ZERO) {
+        Ok(witness) => witness,
+        Err(e) => {
+            error!("Unable to build witness for epoch {}: {}", epoch, e);
+            return None;
+        }
+    };
+
+    info!("Proving epoch {} over {} blocks", epoch, slots.len());
+    let (proof, chunks) = prove_chunks(&witness, params);
+
+    Some(EpochSummary {
+        epoch,
+        first_slot,
+        last_slot,
+        slots,
+        block_hashes_root: hex::encode(block_hashes_root),
+        commitment: fr_to_hex(&witness.top_level().commitment),
+        proof: serialization::proof_to_hex(&proof),
+        chunks,
+    })
+}
+
+// Saves an epoch summary next to the block proofs and uploads it to object
+// storage, if configured
+pub async fn publish(summary: &EpochSummary, proofs_dir: &Path, storage: Option<&ObjectStorage>) {
+    let file_name = summary_file_name(summary.epoch);
+    let json_data = serde_json::to_string_pretty(summary).expect("Unable to serialize epoch summary");
+    fs::write(proofs_dir.join(&file_name), &json_data).expect("Unable to write epoch summary");
+    info!("Saved epoch summary to {:?}", proofs_dir.join(&file_name));
+
+    if let Some(storage) = storage {
+        match storage.put(&file_name, json_data.into_bytes()).await {
+            Ok(()) => info!("Uploaded epoch summary {} to object storage", file_name),
+            Err(e) => error!("Error uploading epoch summary {}: {:?}", file_name, e),
+        }
+    }
+}
@@ -0,0 +1,286 @@
+use libsecp256k1::{Message, PublicKey, SecretKey};
+use log::{error, info, warn};
+use serde_json::{json, Value};
+use solana_sdk::clock::Slot;
+use solana_sdk::keccak;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use tokio::sync::mpsc;
+use tokio::time::Duration;
+
+use crate::config::{EthereumConfig, GasStrategy};
+use crate::BlockProof;
+
+const GWEI: u128 = 1_000_000_000;
+
+// Type byte of EIP-1559 transactions
+const DYNAMIC_FEE_TX_TYPE: u8 = 2;
+
+const SUBMIT_ROOT: &str = "submitRoot(uint64,uint64,bytes32,bytes32)";
+
+#[derive(Debug)]
+pub enum EthereumError {
+    InvalidKey(String),
+    InvalidAddress(String),
+    // `fixed` fees without `max_fee_gwei` and `priority_fee_gwei`
+    MissingFees,
+    Request(reqwest::Error),
+    Rpc(String),
+}
+
+impl fmt::Display for EthereumError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EthereumError::InvalidKey(reason) => write!(f, "invalid signing key: {}", reason),
+            EthereumError::InvalidAddress(address) => write!(f, "{:?} is not a 20-byte hex address", address),
+            EthereumError::MissingFees => write!(f, "fixed gas needs max_fee_gwei and priority_fee_gwei"),
+            EthereumError::Request(e) => write!(f, "request failed: {}", e),
+            EthereumError::Rpc(message) => write!(f, "node returned an error: {}", message),
+        }
+    }
+}
+
+pub fn load_key(path: &Path) -> Result<SecretKey, EthereumError> {
+    let contents = fs::read_to_string(path).map_err(|e| EthereumError::InvalidKey(format!("{:?}: {}", path, e)))?;
+    let contents = contents.trim();
+    let bytes: [u8; 32] = hex::decode(contents.strip_prefix("0x").unwrap_or(contents))
+        .ok()
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(|| EthereumError::InvalidKey(format!("{:?} does not hold a 32-byte hex key", path)))?;
+    SecretKey::parse(&bytes).map_err(|e| EthereumError::InvalidKey(format!("{:?}: {:?}", path, e)))
+}
+
+pub fn parse_address(address: &str) -> Result<[u8; 20], EthereumError> {
+    hex::decode(address.strip_prefix("0x").unwrap_or(address))
+        .ok()
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(|| EthereumError::InvalidAddress(address.to_string()))
+}
+
+fn key_address(key: &SecretKey) -> [u8; 20] {
+    let public_key = PublicKey::from_secret_key(key).serialize();
+    keccak::hash(&public_key[1..]).to_bytes()[12..].try_into().unwrap()
+}
+
+// Recursive length prefix encoding of Ethereum transactions
+fn rlp_length(offset: u8, len: usize) -> Vec<u8> {
+    if len < 56 {
+        return vec![offset + len as u8];
+    }
+    let len = trim_zeroes(&(len as u64).to_be_bytes()).to_vec();
+    [vec![offset + 55 + len.len() as u8], len].concat()
+}
+
+fn rlp_bytes(bytes: &[u8]) -> Vec<u8> {
+    match bytes {
+        [byte] if *byte < 0x80 => vec![*byte],
+        _ => [rlp_length(0x80, bytes.len()), bytes.to_vec()].concat(),
+    }
+}
+
+// Integers are encoded big-endian without leading zeroes
+fn rlp_uint(value: u128) -> Vec<u8> {
+    rlp_bytes(trim_zeroes(&value.to_be_bytes()))
+}
+
+fn rlp_list(items: &[Vec<u8>]) -> Vec<u8> {
+    let payload = items.concat();
+    [rlp_length(0xc0, payload.len()), payload].concat()
+}
+
+fn trim_zeroes(bytes: &[u8]) -> &[u8] {
+    let start = bytes.iter().position(|byte| *byte != 0).unwrap_or(bytes.len());
+    &bytes[start..]
+}
+
+fn parse_quantity(value: &Value) -> Result<u128, EthereumError> {
+    value
+        .as_str()
+        .and_then(|quantity| u128::from_str_radix(quantity.strip_prefix("0x")?, 16).ok())
+        .ok_or_else(|| EthereumError::Rpc(format!("expected a quantity, got {}", value)))
+}
+
+// Consecutive proved blocks whose accumulator root is submitted at once
+struct Batch {
+    from_slot: Slot,
+    to_slot: Slot,
+    old_root: [u8; 32],
+    new_root: [u8; 32],
+    blocks: u64,
+}
+
+impl Batch {
+    // ABI-encoded `submitRoot` call over the batch
+    fn call_data(&self) -> Vec<u8> {
+        let mut data = keccak::hash(SUBMIT_ROOT.as_bytes()).to_bytes()[..4].to_vec();
+        for slot in [self.from_slot, self.to_slot] {
+            data.extend([0u8; 24]);
+            data.extend(slot.to_be_bytes());
+        }
+        data.extend(self.old_root);
+        data.extend(self.new_root);
+        data
+    }
+}
+
+// One proved block: its slot and the accumulator roots before and after it
+struct ChainStep {
+    slot: Slot,
+    old_root: [u8; 32],
+    new_root: [u8; 32],
+}
+
+// Submits the accumulator root to an Ethereum contract every `batch_blocks`
+// proved blocks. Submissions are made one at a time in the background; one
+// that fails is retried with the next block, over the longer batch.
+pub struct EthereumSubmitter {
+    steps: mpsc::UnboundedSender<ChainStep>,
+}
+
+impl EthereumSubmitter {
+    pub fn start(config: &EthereumConfig) -> Result<Self, EthereumError> {
+        let key = load_key(&config.key_path)?;
+        let contract = parse_address(&config.contract)?;
+        if config.gas == GasStrategy::Fixed && (config.max_fee_gwei.is_none() || config.priority_fee_gwei.is_none()) {
+            return Err(EthereumError::MissingFees);
+        }
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(30))
+            .build()
+            .expect("Unable to build Ethereum client");
+        let sender = Sender { client, config: config.clone(), address: key_address(&key), key, contract };
+        info!("Submitting accumulator roots to {} from 0x{}", config.contract, hex::encode(sender.address));
+
+        let (steps, receiver) = mpsc::unbounded_channel();
+        tokio::spawn(sender.run(receiver));
+        Ok(EthereumSubmitter { steps })
+    }
+
+    pub fn record(&self, block_proof: &BlockProof) {
+        let decode = |root: &str| hex::decode(root).ok().and_then(|bytes| <[u8; 32]>::try_from(bytes).ok());
+        let (Some(old_root), Some(new_root)) = (decode(&block_proof.old_root), decode(&block_proof.new_root)) else {
+            warn!("Not submitting block {} to Ethereum: malformed accumulator roots", block_proof.slot);
+            return;
+        };
+        let _ = self.steps.send(ChainStep { slot: block_proof.slot, old_root, new_root });
+    }
+}
+
+struct Sender {
+    client: reqwest::Client,
+    config: EthereumConfig,
+    key: SecretKey,
+    address: [u8; 20],
+    contract: [u8; 20],
+}
+
+impl Sender {
+    async fn run(self, mut steps: mpsc::UnboundedReceiver<ChainStep>) {
+        let mut pending: Option<Batch> = None;
+
+        while let Some(step) = steps.recv().await {
+            let batch = match pending.take() {
+                Some(mut batch) if batch.new_root == step.old_root => {
+                    batch.to_slot = step.slot;
+                    batch.new_root = step.new_root;
+                    batch.blocks += 1;
+                    batch
+                }
+                previous => {
+                    if let Some(previous) = previous {
+                        warn!(
+                            "Block {} does not chain onto block {}, dropping the unsubmitted roots since block {}",
+                            step.slot, previous.to_slot, previous.from_slot
+                        );
+                    }
+                    let ChainStep { slot, old_root, new_root } = step;
+                    Batch { from_slot: slot, to_slot: slot, old_root, new_root, blocks: 1 }
+                }
+            };
+            if batch.blocks < self.config.batch_blocks {
+                pending = Some(batch);
+                continue;
+            }
+
+            match self.submit(&batch).await {
+                Ok(hash) => info!(
+                    "Submitted the accumulator root over blocks {}..={} to Ethereum in {}",
+                    batch.from_slot, batch.to_slot, hash
+                ),
+                Err(e) => {
+                    error!("Unable to submit blocks {}..={} to Ethereum: {}", batch.from_slot, batch.to_slot, e);
+                    pending = Some(batch);
+                }
+            }
+        }
+    }
+
+    async fn call(&self, method: &str, params: Value) -> Result<Value, EthereumError> {
+        let body = json!({ "jsonrpc": "2.0", "id": 1, "method": method, "params": params });
+        let response: Value = self
+            .client
+            .post(&self.config.rpc_url)
+            .json(&body)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(EthereumError::Request)?
+            .json()
+            .await
+            .map_err(EthereumError::Request)?;
+        match response.get("error") {
+            Some(error) => Err(EthereumError::Rpc(error["message"].as_str().unwrap_or("unknown error").to_string())),
+            None => Ok(response["result"].clone()),
+        }
+    }
+
+    // Max fee and priority fee per gas, in wei
+    async fn fees(&self) -> Result<(u128, u128), EthereumError> {
+        let max_fee = self.config.max_fee_gwei.map(|gwei| gwei as u128 * GWEI);
+        let priority_fee = self.config.priority_fee_gwei.map(|gwei| gwei as u128 * GWEI);
+        if let (GasStrategy::Fixed, Some(max_fee), Some(priority_fee)) = (self.config.gas, max_fee, priority_fee) {
+            return Ok((max_fee, priority_fee.min(max_fee)));
+        }
+
+        let latest = self.call("eth_getBlockByNumber", json!(["latest", false])).await?;
+        let base_fee = parse_quantity(&latest["baseFeePerGas"])?;
+        let priority_fee = match priority_fee {
+            Some(priority_fee) => priority_fee,
+            None => parse_quantity(&self.call("eth_maxPriorityFeePerGas", json!([])).await?)?,
+        };
+        let suggested = 2 * base_fee + priority_fee;
+        let max_fee = max_fee.map_or(suggested, |max_fee| suggested.min(max_fee));
+        Ok((max_fee, priority_fee.min(max_fee)))
+    }
+
+    // Signs and sends the `submitRoot` transaction of a batch, returning its hash
+    async fn submit(&self, batch: &Batch) -> Result<Value, EthereumError> {
+        let address = format!("0x{}", hex::encode(self.address));
+        let nonce = parse_quantity(&self.call("eth_getTransactionCount", json!([address, "pending"])).await?)?;
+        let (max_fee, priority_fee) = self.fees().await?;
+
+        let fields = vec![
+            rlp_uint(self.config.chain_id as u128),
+            rlp_uint(nonce),
+            rlp_uint(priority_fee),
+            rlp_uint(max_fee),
+            rlp_uint(self.config.gas_limit as u128),
+            rlp_bytes(&self.contract),
+            rlp_uint(0),
+            rlp_bytes(&batch.call_data()),
+            rlp_list(&[]),
+        ];
+        let signing_hash = keccak::hashv(&[&[DYNAMIC_FEE_TX_TYPE], &rlp_list(&fields)]).to_bytes();
+        let (signature, recovery_id) = libsecp256k1::sign(&Message::parse(&signing_hash), &self.key);
+        let signature = signature.serialize();
+
+        let mut signed_fields = fields;
+        signed_fields.push(rlp_uint(recovery_id.serialize() as u128));
+        signed_fields.push(rlp_bytes(trim_zeroes(&signature[..32])));
+        signed_fields.push(rlp_bytes(trim_zeroes(&signature[32..])));
+        let raw = [vec![DYNAMIC_FEE_TX_TYPE], rlp_list(&signed_fields)].concat();
+
+        self.call("eth_sendRawTransaction", json!([format!("0x{}", hex::encode(raw))])).await
+    }
+}
@@ -0,0 +1,143 @@
+use serde::{Deserialize, Serialize};
+use solana_sdk::clock::Slot;
+use solana_sdk::{compute_budget, vote};
+use solana_transaction_status::option_serializer::OptionSerializer;
+use solana_transaction_status::{EncodedConfirmedBlock, EncodedTransactionWithStatusMeta};
+
+use crate::{instructions, TotalsProof};
+
+// Base fee charged per transaction signature, in lamports. Anything a
+// transaction paid above it is counted as its priority fee.
+const LAMPORTS_PER_SIGNATURE: u64 = 5000;
+
+// Compute budget instruction tag
+const SET_COMPUTE_UNIT_PRICE: u8 = 3;
+
+// Nearest-rank percentiles of a distribution
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct Percentiles {
+    pub p50: u64,
+    pub p75: u64,
+    pub p90: u64,
+    pub p99: u64,
+}
+
+impl Percentiles {
+    fn new(mut values: Vec<u64>) -> Self {
+        values.sort_unstable();
+        let rank = |percent: usize| match values.len() {
+            0 => 0,
+            len => values[(len * percent).div_ceil(100).max(1) - 1],
+        };
+        Percentiles { p50: rank(50), p75: rank(75), p90: rank(90), p99: rank(99) }
+    }
+}
+
+// Fees paid in a block. Priority fees and compute-unit prices are taken over
+// non-vote transactions only, as votes never set a price.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct FeeStats {
+    pub total_fees: u64,
+    pub total_priority_fees: u64,
+    pub median_priority_fee: u64,
+    // Micro-lamports per compute unit set with SetComputeUnitPrice (0 if unset)
+    pub compute_unit_price: Percentiles,
+    // Totals circuit proof that the fees of the block's transaction leaves add
+    // up to its total, when proved. Unlike `total_fees` it leaves out the
+    // transactions a processor kept out of the proof.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub total_fees_proof: Option<TotalsProof>,
+}
+
+// Compute units the transaction consumed, or 0 if the node did not report them
+pub fn compute_units(transaction: &EncodedTransactionWithStatusMeta) -> u64 {
+    match transaction.meta.as_ref().map(|meta| &meta.compute_units_consumed) {
+        Some(OptionSerializer::Some(units)) => *units,
+        _ => 0,
+    }
+}
+
+// Fee the transaction paid, in lamports
+pub fn fee(transaction: &EncodedTransactionWithStatusMeta) -> u64 {
+    transaction.meta.as_ref().map_or(0, |meta| meta.fee)
+}
+
+pub fn block_stats(block: &EncodedConfirmedBlock) -> FeeStats {
+    let mut total_fees = 0;
+    let mut priority_fees = Vec::new();
+    let mut prices = Vec::new();
+    let vote_program = vote::program::id().to_string();
+    let compute_budget_program = compute_budget::id().to_string();
+
+    for transaction_with_meta in &block.transactions {
+        let (Some(transaction), Some(meta)) =
+            (instructions::decode(transaction_with_meta), transaction_with_meta.meta.as_ref())
+        else {
+            continue;
+        };
+        total_fees += meta.fee;
+
+        if transaction.instructions.iter().any(|instruction| instruction.program_id == vote_program) {
+            continue;
+        }
+        let price = transaction
+            .instructions
+            .iter()
+            .filter(|instruction| instruction.program_id == compute_budget_program)
+            .find_map(|instruction| match instruction.data.as_slice() {
+                [SET_COMPUTE_UNIT_PRICE, price @ ..] if price.len() == 8 => {
+                    Some(u64::from_le_bytes(price.try_into().unwrap()))
+                }
+                _ => None,
+            })
+            .unwrap_or(0);
+
+        priority_fees.push(meta.fee.saturating_sub(LAMPORTS_PER_SIGNATURE * transaction.signatures.len() as u64));
+        prices.push(price);
+    }
+
+    FeeStats {
+        total_fees,
+        total_priority_fees: priority_fees.iter().sum(),
+        median_priority_fee: Percentiles::new(priority_fees).p50,
+        compute_unit_price: Percentiles::new(prices),
+        total_fees_proof: None,
+    }
+}
+
+// Fee statistics aggregated over the block proofs of an archive
+#[derive(Serialize, Default)]
+pub struct ArchiveStats {
+    pub blocks: usize,
+    pub first_slot: Option<Slot>,
+    pub last_slot: Option<Slot>,
+    pub total_fees: u64,
+    pub total_priority_fees: u64,
+    pub mean_fees_per_block: u64,
+    // Distributions of the per-block median priority fee and median compute-unit price
+    pub median_priority_fee: Percentiles,
+    pub median_compute_unit_price: Percentiles,
+}
+
+pub fn aggregate(blocks: impl IntoIterator<Item = (Slot, FeeStats)>) -> ArchiveStats {
+    let mut stats = ArchiveStats::default();
+    let mut priority_fees = Vec::new();
+    let mut prices = Vec::new();
+
+    for (slot, fees) in blocks {
+        stats.blocks += 1;
+        stats.first_slot = Some(stats.first_slot.map_or(slot, |first| first.min(slot)));
+        stats.last_slot = Some(stats.last_slot.map_or(slot, |last| last.max(slot)));
+        stats.total_fees += fees.total_fees;
+        stats.total_priority_fees += fees.total_priority_fees;
+        priority_fees.push(fees.median_priority_fee);
+        prices.push(fees.compute_unit_price.p50);
+    }
+
+    if stats.blocks > 0 {
+        stats.mean_fees_per_block = stats.total_fees / stats.blocks as u64;
+    }
+    stats.median_priority_fee = Percentiles::new(priority_fees);
+    stats.median_compute_unit_price = Percentiles::new(prices);
+    stats
+}
@@ -0,0 +1,103 @@
+use blstrs::Scalar as Fr;
+use ff::PrimeField;
+use sha2::{Digest, Sha256};
+
+// Maps arbitrary bytes into the scalar field. The top two bits are cleared so
+// the value is always below the field modulus.
+pub fn hash_to_fr(hash: &[u8]) -> Fr {
+    let mut hash_bytes = [0u8; 32];
+    hash_bytes.copy_from_slice(&hash[..32]);
+    hash_bytes[31] &= 0x3f;
+    Fr::from_repr(hash_bytes).unwrap()
+}
+
+// Version of the domain tags, recorded in each proof as `hash_domains`. Proofs
+// without one were made with untagged hashes.
+pub const HASH_DOMAINS: u8 = 1;
+
+// Kinds of data hashed into the field. Each is hashed under its own tag and the
+// tag version, so values of different kinds never map to the same element.
+#[derive(Clone, Copy)]
+pub enum Domain {
+    // Circuit seed of a block
+    Block,
+    // Leaf of a transaction signature, plain or salted
+    Transaction,
+    // Seed of a block's per-mint, program change, vote or sum commitment
+    Commitment,
+    TokenTransfer,
+    ProgramChange,
+    Vote,
+}
+
+impl Domain {
+    pub fn tag(self) -> &'static [u8] {
+        match self {
+            Domain::Block => b"block",
+            Domain::Transaction => b"tx",
+            Domain::Commitment => b"commitment",
+            Domain::TokenTransfer => b"token_transfer",
+            Domain::ProgramChange => b"program_change",
+            Domain::Vote => b"vote",
+        }
+    }
+}
+
+// Hasher started with the tag of `domain`, ended by a zero byte, and the version
+pub fn domain_hasher(domain: Domain) -> Sha256 {
+    let mut hasher = Sha256::new();
+    hasher.update(b"solana-listener/");
+    hasher.update(domain.tag());
+    hasher.update([0, HASH_DOMAINS]);
+    hasher
+}
+
+pub fn str_to_fr(domain: Domain, data: &str) -> Fr {
+    let mut hasher = domain_hasher(domain);
+    hasher.update(data.as_bytes());
+    hash_to_fr(&hasher.finalize())
+}
+
+pub fn fr_to_hex(value: &Fr) -> String {
+    hex::encode(value.to_repr())
+}
+
+pub fn fr_from_hex(data: &str) -> Option<Fr> {
+    let bytes: [u8; 32] = hex::decode(data).ok()?.try_into().ok()?;
+    Fr::from_repr(bytes).into()
+}
+
+// Serde adapters storing field elements as hex strings
+pub mod hex_fr {
+    use super::*;
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(value: &Fr, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&fr_to_hex(value))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Fr, D::Error> {
+        let data = String::deserialize(deserializer)?;
+        fr_from_hex(&data).ok_or_else(|| D::Error::custom("invalid field element"))
+    }
+}
+
+pub mod hex_fr_vec {
+    use super::*;
+    use serde::{de::Error, ser::SerializeSeq, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(values: &[Fr], serializer: S) -> Result<S::Ok, S::Error> {
+        let mut seq = serializer.serialize_seq(Some(values.len()))?;
+        for value in values {
+            seq.serialize_element(&fr_to_hex(value))?;
+        }
+        seq.end()
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<Fr>, D::Error> {
+        Vec::<String>::deserialize(deserializer)?
+            .iter()
+            .map(|data| fr_from_hex(data).ok_or_else(|| D::Error::custom("invalid field element")))
+            .collect()
+    }
+}
@@ -0,0 +1,40 @@
+use solana_transaction_status::option_serializer::OptionSerializer;
+use solana_transaction_status::{EncodedConfirmedBlock, EncodedTransactionWithStatusMeta};
+
+use crate::config::FilterConfig;
+use crate::instructions;
+
+// Whether a transaction invokes one of the filter's programs, references one
+// of its accounts or holds a token balance in one of its mints
+fn matches(filter: &FilterConfig, transaction_with_meta: &EncodedTransactionWithStatusMeta) -> bool {
+    let Some(transaction) = instructions::decode(transaction_with_meta) else {
+        return false;
+    };
+
+    let invokes_program = transaction
+        .instructions
+        .iter()
+        .any(|instruction| filter.programs.contains(&instruction.program_id));
+    let references_account = transaction.account_keys.iter().any(|key| filter.accounts.contains(key));
+    let holds_mint = transaction_with_meta.meta.as_ref().is_some_and(|meta| {
+        [&meta.pre_token_balances, &meta.post_token_balances]
+            .into_iter()
+            .filter_map(|balances| match balances {
+                OptionSerializer::Some(balances) => Some(balances),
+                _ => None,
+            })
+            .flatten()
+            .any(|balance| filter.mints.contains(&balance.mint))
+    });
+
+    invokes_program || references_account || holds_mint
+}
+
+// Number of transactions of the block matching the filter
+pub fn matching_transactions(filter: &FilterConfig, block: &EncodedConfirmedBlock) -> usize {
+    block
+        .transactions
+        .iter()
+        .filter(|transaction_with_meta| matches(filter, transaction_with_meta))
+        .count()
+}
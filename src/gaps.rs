@@ -0,0 +1,102 @@
+use serde::Serialize;
+use solana_sdk::clock::Slot;
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::index::{IndexEntry, SlotStatus};
+use crate::manifest::Manifest;
+
+#[derive(Serialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum GapReason {
+    // The cluster produced no block in the slot
+    SkippedSlot,
+    // Reported as skipped when it was fetched, though the cluster produced a
+    // block in it, e.g. by a node that had jumped to a recent snapshot
+    ReportedSkipped,
+    // Left out by the archive's sampling policy
+    Unsampled,
+    // Led by another validator than the archive's leader identity
+    OtherLeader,
+    FilteredOut,
+    // Over `max_txs_per_block`, with the `skip` policy
+    Oversized,
+    // Refused because the data sources disagreed
+    Flagged,
+    FetchFailure,
+    ProverError,
+    ProvingTimeout,
+    // Processed (or exported as a witness) but no proof file is in the archive
+    Missing,
+    // Produced by the cluster but never processed by the listener
+    Unprocessed,
+}
+
+#[derive(Serialize)]
+pub struct Gap {
+    pub slot: Slot,
+    pub reason: GapReason,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct GapReport {
+    pub from_slot: Slot,
+    pub to_slot: Slot,
+    pub proved: usize,
+    // Slots of the range the cluster produced no block in
+    pub skipped: usize,
+    pub gaps: Vec<Gap>,
+}
+
+// Every slot of `from_slot..=to_slot` without a proof, with the reason it has
+// none. `produced` holds the slots the cluster produced a block in, and
+// `leaders` the identity that led each slot, when the archive only proves one's.
+pub fn report(
+    from_slot: Slot,
+    to_slot: Slot,
+    produced: &BTreeSet<Slot>,
+    proved: &BTreeSet<Slot>,
+    index: &BTreeMap<Slot, IndexEntry>,
+    manifest: &Manifest,
+    leaders: &BTreeMap<Slot, String>,
+) -> GapReport {
+    let mut gaps = Vec::new();
+
+    for slot in (from_slot..=to_slot).filter(|slot| !proved.contains(slot)) {
+        let entry = index.get(&slot);
+        let reason = if !produced.contains(&slot) {
+            GapReason::SkippedSlot
+        } else if !manifest.is_sampled(slot) {
+            GapReason::Unsampled
+        } else if manifest.leader_identity.as_ref().is_some_and(|identity| leaders.get(&slot) != Some(identity)) {
+            GapReason::OtherLeader
+        } else {
+            match entry.map(|entry| entry.status) {
+                Some(SlotStatus::Empty) => GapReason::FilteredOut,
+                Some(SlotStatus::Oversized) => GapReason::Oversized,
+                Some(SlotStatus::Flagged) => GapReason::Flagged,
+                Some(SlotStatus::FetchFailed) => GapReason::FetchFailure,
+                Some(SlotStatus::Failed) => GapReason::ProverError,
+                Some(SlotStatus::TimedOut) => GapReason::ProvingTimeout,
+                Some(SlotStatus::Skipped) => GapReason::ReportedSkipped,
+                Some(SlotStatus::Proved) => GapReason::Missing,
+                None => GapReason::Unprocessed,
+            }
+        };
+        // A skipped slot keeps the endpoint's report of it, but not a stale
+        // failure from before the cluster's ledger was known
+        let detail = entry
+            .filter(|entry| reason != GapReason::SkippedSlot || entry.status == SlotStatus::Skipped)
+            .and_then(|entry| entry.reason.clone());
+        gaps.push(Gap { slot, reason, detail });
+    }
+
+    GapReport {
+        from_slot,
+        to_slot,
+        proved: proved.range(from_slot..=to_slot).count(),
+        skipped: gaps.iter().filter(|gap| gap.reason == GapReason::SkippedSlot).count(),
+        gaps,
+    }
+}
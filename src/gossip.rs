@@ -0,0 +1,380 @@
+use log::{error, info, warn};
+use serde::{Deserialize, Serialize};
+use solana_block_verifier::VerifyingKey;
+use solana_sdk::clock::Slot;
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::time::{sleep, Duration};
+
+use crate::config::GossipConfig;
+use crate::keyring::{self, Keyring};
+use crate::{params, verify};
+use crate::{list_proof_slots, load_proof, proof_file_name, save_proof_to_json, BlockProof};
+
+// Messages exchanged between listener nodes, one JSON object per line
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Message {
+    // A block proof, pushed to peers as soon as it is produced or received
+    Proof { proof: Box<BlockProof> },
+    // Asks a peer for every proof from `from_slot` onwards that is not in `have`
+    Sync { from_slot: Slot, have: Vec<Slot> },
+}
+
+// Gossips block proofs between listener nodes over plain TCP. New proofs are
+// pushed to every peer and forwarded once on receipt, deduplicated by slot;
+// a periodic sync fills in whatever a node missed while it was down. Proofs
+// of another cluster than this node's, or any of whose circuit proofs do not
+// verify against this node's keys, are refused. Peers' proofs are chained onto
+// their own accumulator roots, so they are kept apart from this node's, in
+// `peer_dir`.
+pub struct Gossip {
+    proofs_dir: PathBuf,
+    genesis_hash: String,
+    params_path: PathBuf,
+    // Sum, totals, threshold and signature circuit parameters
+    circuit_params: [PathBuf; 4],
+    keyring: Mutex<Keyring>,
+    circuit_keys: Mutex<[Option<VerifyingKey>; 4]>,
+    peers: Vec<String>,
+    sync_window: u64,
+    seen: Mutex<HashSet<Slot>>,
+}
+
+// The keyring of the proofs directory, with the key of the configured
+// parameters as the current one if they exist
+fn load_keyring(proofs_dir: &Path, params_path: &Path) -> Keyring {
+    let mut keyring = Keyring::load(&keyring::keyring_dir(proofs_dir));
+    if params_path.exists() {
+        keyring.insert_current(&params::load_verifying_key(params_path));
+    }
+    keyring
+}
+
+// Where proofs received from peers are stored
+pub fn peer_dir(proofs_dir: &Path) -> PathBuf {
+    proofs_dir.join("peers")
+}
+
+async fn send(peer: &str, messages: &[Message]) -> io::Result<TcpStream> {
+    let mut stream = TcpStream::connect(peer).await?;
+    for message in messages {
+        let mut line = serde_json::to_string(message)?;
+        line.push('\n');
+        stream.write_all(line.as_bytes()).await?;
+    }
+    stream.flush().await?;
+    Ok(stream)
+}
+
+impl Gossip {
+    pub async fn start(
+        config: &GossipConfig,
+        proofs_dir: PathBuf,
+        genesis_hash: String,
+        params_path: &Path,
+        circuit_params: [&Path; 4],
+    ) -> Arc<Gossip> {
+        let seen = list_proof_slots(&proofs_dir).into_iter().chain(list_proof_slots(&peer_dir(&proofs_dir))).collect();
+        let keyring = load_keyring(&proofs_dir, params_path);
+        let circuit_keys = verify::circuit_keys(circuit_params);
+        let gossip = Arc::new(Gossip {
+            proofs_dir,
+            genesis_hash,
+            params_path: params_path.to_path_buf(),
+            circuit_params: circuit_params.map(Path::to_path_buf),
+            keyring: Mutex::new(keyring),
+            circuit_keys: Mutex::new(circuit_keys),
+            peers: config.peers.clone(),
+            sync_window: config.sync_window,
+            seen: Mutex::new(seen),
+        });
+
+        let listener = TcpListener::bind(&config.listen_addr)
+            .await
+            .expect("Unable to bind gossip listener");
+        info!("Gossiping proofs on {} with {} peers", config.listen_addr, config.peers.len());
+
+        tokio::spawn(gossip.clone().serve(listener));
+        tokio::spawn(gossip.clone().sync_loop(Duration::from_secs(config.sync_interval_secs)));
+
+        gossip
+    }
+
+    // Pushes a proof produced by this node to all peers
+    pub fn broadcast(self: &Arc<Self>, block_proof: &BlockProof) {
+        self.seen.lock().unwrap().insert(block_proof.slot);
+        self.push(block_proof.clone());
+    }
+
+    fn push(self: &Arc<Self>, block_proof: BlockProof) {
+        let message = Arc::new([Message::Proof { proof: Box::new(block_proof) }]);
+        for peer in self.peers.clone() {
+            let message = message.clone();
+            tokio::spawn(async move {
+                if let Err(e) = send(&peer, message.as_ref()).await {
+                    error!("Error sending proof to peer {}: {:?}", peer, e);
+                }
+            });
+        }
+    }
+
+    // The stored proof of a slot, this node's own or else a peer's
+    fn proof_path(&self, slot: Slot) -> Option<PathBuf> {
+        let file_name = proof_file_name(slot);
+        [self.proofs_dir.join(&file_name), peer_dir(&self.proofs_dir).join(&file_name)]
+            .into_iter()
+            .find(|path| path.exists())
+    }
+
+    // Slots of every stored proof, this node's and its peers', in ascending order
+    fn stored_slots(&self) -> Vec<Slot> {
+        let mut slots = list_proof_slots(&self.proofs_dir);
+        slots.extend(list_proof_slots(&peer_dir(&self.proofs_dir)));
+        slots.sort_unstable();
+        slots.dedup();
+        slots
+    }
+
+    // Stores a proof received from a peer in the peer directory unless this
+    // node already has that slot. Returns whether it was new.
+    fn accept(&self, block_proof: &BlockProof) -> bool {
+        if block_proof.genesis_hash.as_ref() != Some(&self.genesis_hash) {
+            warn!("Refusing block proof {} from a peer: not from this node's cluster", block_proof.slot);
+            return false;
+        }
+        if self.seen.lock().unwrap().contains(&block_proof.slot) {
+            return false;
+        }
+        if self.proof_path(block_proof.slot).is_some() {
+            return false;
+        }
+        if let Err(e) = self.verify(block_proof) {
+            warn!("Refusing block proof {} from a peer: {}", block_proof.slot, e);
+            return false;
+        }
+        if !self.seen.lock().unwrap().insert(block_proof.slot) {
+            return false;
+        }
+        let peer_dir = peer_dir(&self.proofs_dir);
+        fs::create_dir_all(&peer_dir).expect("Unable to create peer proofs directory");
+        save_proof_to_json(block_proof, block_proof.slot, &peer_dir);
+        true
+    }
+
+    // Checks every circuit proof of a peer's proof. A key this node does not
+    // know may have been added since it started, e.g. by a key reload or by
+    // parameters generated on first use, so the keys are reread once before
+    // refusing it.
+    fn verify(&self, block_proof: &BlockProof) -> Result<(), verify::VerifyError> {
+        let mut keyring = self.keyring.lock().unwrap();
+        let mut circuit_keys = self.circuit_keys.lock().unwrap();
+        let verify = |keyring: &Keyring, circuit_keys: &[Option<VerifyingKey>; 4]| {
+            verify::verify_block(block_proof, keyring)
+                .and_then(|()| verify::verify_circuits(block_proof, circuit_keys.each_ref().map(Option::as_ref)))
+        };
+        match verify(&keyring, &circuit_keys) {
+            Err(e) if e.missing_key() => {
+                *keyring = load_keyring(&self.proofs_dir, &self.params_path);
+                *circuit_keys = verify::circuit_keys(self.circuit_params.each_ref().map(PathBuf::as_path));
+                verify(&keyring, &circuit_keys)
+            }
+            verified => verified,
+        }
+    }
+
+    async fn serve(self: Arc<Self>, listener: TcpListener) {
+        loop {
+            match listener.accept().await {
+                Ok((stream, addr)) => {
+                    let gossip = self.clone();
+                    tokio::spawn(async move {
+                        if let Err(e) = gossip.handle(stream).await {
+                            error!("Error handling gossip from {}: {:?}", addr, e);
+                        }
+                    });
+                }
+                Err(e) => error!("Error accepting gossip connection: {:?}", e),
+            }
+        }
+    }
+
+    async fn handle(self: Arc<Self>, stream: TcpStream) -> io::Result<()> {
+        let (reader, mut writer) = stream.into_split();
+        let mut lines = BufReader::new(reader).lines();
+
+        while let Some(line) = lines.next_line().await? {
+            match serde_json::from_str(&line)? {
+                Message::Proof { proof } => {
+                    if self.accept(&proof) {
+                        info!("Received block proof {} from a peer", proof.slot);
+                        self.push(*proof);
+                    }
+                }
+                Message::Sync { from_slot, have } => {
+                    let have: HashSet<Slot> = have.into_iter().collect();
+                    for slot in self.stored_slots() {
+                        if slot < from_slot || have.contains(&slot) {
+                            continue;
+                        }
+                        let Some(path) = self.proof_path(slot) else {
+                            continue;
+                        };
+                        let proof = load_proof(&path);
+                        let mut line = serde_json::to_string(&Message::Proof { proof: Box::new(proof) })?;
+                        line.push('\n');
+                        writer.write_all(line.as_bytes()).await?;
+                    }
+                    writer.shutdown().await?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    // Asks every peer for the proofs this node is missing in the recent window
+    async fn sync_loop(self: Arc<Self>, interval: Duration) {
+        loop {
+            let slots = self.stored_slots();
+            let from_slot = slots.last().map_or(0, |last| last.saturating_sub(self.sync_window));
+            let have: Vec<Slot> = slots.into_iter().filter(|&slot| slot >= from_slot).collect();
+
+            for peer in &self.peers {
+                let request = [Message::Sync {
+                    from_slot,
+                    have: have.clone(),
+                }];
+                match self.sync_with(peer, &request).await {
+                    Ok(0) => {}
+                    Ok(received) => info!("Backfilled {} block proofs from peer {}", received, peer),
+                    Err(e) => error!("Error syncing with peer {}: {:?}", peer, e),
+                }
+            }
+
+            sleep(interval).await;
+        }
+    }
+
+    async fn sync_with(&self, peer: &str, request: &[Message]) -> io::Result<usize> {
+        let mut stream = send(peer, request).await?;
+        stream.shutdown().await.ok();
+        let mut lines = BufReader::new(stream).lines();
+        let mut received = 0;
+
+        while let Some(line) = lines.next_line().await? {
+            if let Message::Proof { proof } = serde_json::from_str(&line)? {
+                if self.accept(&proof) {
+                    received += 1;
+                }
+            }
+        }
+
+        Ok(received)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::circuit;
+    use crate::config::CommitmentHash;
+    use crate::field::{fr_to_hex, str_to_fr, Domain, HASH_DOMAINS};
+    use crate::merkle::MerkleTree;
+    use crate::serialization;
+    use crate::witness::WitnessAccumulator;
+    use bellman::groth16;
+    use blstrs::{Bls12, Scalar};
+    use ff::{Field, PrimeField};
+
+    // A proof file of `slot` proved as the listener proves one, listing
+    // `signatures`
+    fn proved(slot: Slot, block_hash: &str, signatures: &[&str], params: &groth16::Parameters<Bls12>) -> BlockProof {
+        let leaves: Vec<Scalar> = signatures.iter().map(|data| str_to_fr(Domain::Transaction, data)).collect();
+        let mut accumulator = WitnessAccumulator::new(crate::block_seed(block_hash, None, None), usize::MAX);
+        for leaf in &leaves {
+            accumulator.push(*leaf).unwrap();
+        }
+        let witness = accumulator.finish(Scalar::ZERO).unwrap();
+        let (proof, chunks) = crate::prove_chunks(&witness, params);
+        let tree = MerkleTree::new(CommitmentHash::Sha256, &leaves.iter().map(Scalar::to_repr).collect::<Vec<_>>());
+        let transactions: Vec<_> = signatures
+            .iter()
+            .enumerate()
+            .map(|(index, signature)| {
+                serde_json::json!({
+                    "transaction_hash": signature,
+                    "leaf_index": index,
+                    "merkle_path": tree.path(index),
+                })
+            })
+            .collect();
+        let top_level = witness.top_level();
+        let block_proof: BlockProof = serde_json::from_value(serde_json::json!({
+            "slot": slot,
+            "block_hash": block_hash,
+            "genesis_hash": "genesis",
+            "hash_domains": HASH_DOMAINS,
+            "transactions_root": hex::encode(tree.root()),
+            "commitment": fr_to_hex(&top_level.commitment),
+            "proof": serialization::proof_to_hex(&proof),
+            "old_root": fr_to_hex(&top_level.old_root),
+            "new_root": fr_to_hex(&top_level.new_root()),
+            "transactions": transactions,
+        }))
+        .unwrap();
+        BlockProof { chunks, ..block_proof }
+    }
+
+    fn gossip(proofs_dir: &Path, params: &groth16::Parameters<Bls12>) -> Gossip {
+        let mut keyring = Keyring::default();
+        keyring.insert_current(&params.vk);
+        Gossip {
+            proofs_dir: proofs_dir.to_path_buf(),
+            genesis_hash: "genesis".to_string(),
+            params_path: proofs_dir.join("params.bin"),
+            circuit_params: ["sum", "totals", "threshold", "signature"].map(|name| proofs_dir.join(name)),
+            keyring: Mutex::new(keyring),
+            circuit_keys: Mutex::new([None, None, None, None]),
+            peers: Vec::new(),
+            sync_window: 0,
+            seen: Mutex::new(HashSet::new()),
+        }
+    }
+
+    #[test]
+    fn peer_proofs_are_checked_and_kept_apart() {
+        let proofs_dir = std::env::temp_dir().join(format!("solana-listener-gossip-{}", std::process::id()));
+        fs::create_dir_all(&proofs_dir).unwrap();
+        let params = circuit::generate_parameters();
+        let gossip = gossip(&proofs_dir, &params);
+        let block_proof = proved(5, "hash", &["a", "b"], &params);
+
+        // A proof whose circuit proof is another block's is refused and not kept
+        let forged = BlockProof { proof: proved(5, "other hash", &["a", "b"], &params).proof, ..block_proof.clone() };
+        assert!(!gossip.accept(&forged));
+        assert!(gossip.proof_path(5).is_none());
+        let other_cluster = BlockProof { genesis_hash: Some("other genesis".to_string()), ..block_proof.clone() };
+        assert!(!gossip.accept(&other_cluster));
+        assert!(gossip.proof_path(5).is_none());
+
+        // Kept in the peer directory, once
+        assert!(gossip.accept(&block_proof));
+        assert_eq!(gossip.proof_path(5), Some(peer_dir(&proofs_dir).join(proof_file_name(5))));
+        assert!(list_proof_slots(&proofs_dir).is_empty());
+        assert!(!gossip.accept(&block_proof));
+
+        // A slot this node proved itself is not taken from a peer
+        save_proof_to_json(&block_proof, 6, &proofs_dir);
+        let own_slot = BlockProof { slot: 6, ..block_proof.clone() };
+        assert!(!gossip.accept(&own_slot));
+        assert!(!peer_dir(&proofs_dir).join(proof_file_name(6)).exists());
+        assert_eq!(gossip.stored_slots(), [5, 6]);
+        fs::remove_dir_all(&proofs_dir).unwrap();
+    }
+}
@@ -0,0 +1,226 @@
+use async_graphql::{ComplexObject, Context, EmptyMutation, EmptySubscription, Object, Schema, SimpleObject};
+use solana_sdk::clock::Slot;
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::merkle::MerkleStep;
+use crate::{list_proof_slots, load_proof, locate_transaction, proof_file_name, BlockProof, TransactionProof};
+
+// Blocks or transactions returned by one field unless `limit` asks for fewer
+const MAX_RESULTS: usize = 1000;
+
+pub type ProofSchema = Schema<Query, EmptyMutation, EmptySubscription>;
+
+pub fn schema(proofs_dir: PathBuf) -> ProofSchema {
+    Schema::build(Query, EmptyMutation, EmptySubscription).data(ProofsDir(proofs_dir)).finish()
+}
+
+struct ProofsDir(PathBuf);
+
+// Accounts a transaction is known to touch: those named by the token and SOL
+// transfers, balance changes and stake activity the proof records for it.
+// Accounts only referenced by its message are not in the proof.
+fn touched_accounts(block_proof: &BlockProof, signature: &str) -> BTreeSet<String> {
+    let mut accounts = BTreeSet::new();
+    for transfer in block_proof.token_transfers.iter().filter(|transfer| transfer.signature == signature) {
+        accounts.extend([&transfer.source, &transfer.destination, &transfer.mint].map(String::clone));
+    }
+    let sol_transfers = block_proof.sol_transfers.iter().flat_map(|summary| &summary.transfers);
+    for transfer in sol_transfers.filter(|transfer| transfer.signature == signature) {
+        accounts.extend([&transfer.source, &transfer.destination].map(String::clone));
+    }
+    let changes = block_proof.balances.iter().flat_map(|balances| &balances.changes);
+    accounts.extend(changes.filter(|change| change.signature == signature).map(|change| change.account.clone()));
+    for activity in block_proof.stake_activity.iter().filter(|activity| activity.signature == signature) {
+        accounts.insert(activity.stake_account.clone());
+        accounts.extend(activity.vote_account.iter().chain(&activity.authority).cloned());
+    }
+    accounts
+}
+
+// Slots of the proofs in `from..=to`, in ascending order
+fn slots_between(proofs_dir: &Path, from: Option<Slot>, to: Option<Slot>) -> impl Iterator<Item = Slot> {
+    let (from, to) = (from.unwrap_or(0), to.unwrap_or(Slot::MAX));
+    list_proof_slots(proofs_dir).into_iter().filter(move |slot| (from..=to).contains(slot))
+}
+
+fn limit(limit: Option<usize>) -> usize {
+    limit.unwrap_or(MAX_RESULTS).min(MAX_RESULTS)
+}
+
+// The circuit proof of a block and the state transition it attests to
+#[derive(SimpleObject)]
+struct Proof {
+    proof: String,
+    commitment: String,
+    old_root: String,
+    new_root: String,
+    transactions_root: String,
+    params_fingerprint: Option<String>,
+    // Operators that co-signed the proof file
+    signers: Vec<String>,
+    // Path of the proof file under the API's /files
+    file: String,
+}
+
+#[derive(SimpleObject)]
+#[graphql(name = "MerkleStep")]
+struct Step {
+    sibling: String,
+    sibling_on_left: bool,
+}
+
+impl From<&MerkleStep> for Step {
+    fn from(step: &MerkleStep) -> Self {
+        Step { sibling: step.sibling.clone(), sibling_on_left: step.sibling_on_left }
+    }
+}
+
+// A proved transaction with its inclusion path to the block's transactions root
+#[derive(SimpleObject)]
+#[graphql(complex)]
+struct Transaction {
+    signature: String,
+    slot: Slot,
+    leaf_index: usize,
+    merkle_path: Vec<Step>,
+    message_hash: Option<String>,
+    accounts: Vec<String>,
+    #[graphql(skip)]
+    block: Arc<BlockProof>,
+}
+
+impl Transaction {
+    fn new(block: &Arc<BlockProof>, transaction: &TransactionProof) -> Self {
+        Transaction {
+            signature: transaction.transaction_hash.clone(),
+            slot: block.slot,
+            leaf_index: transaction.leaf_index,
+            merkle_path: transaction.merkle_path.iter().map(Step::from).collect(),
+            message_hash: transaction.message_hash.clone(),
+            accounts: touched_accounts(block, &transaction.transaction_hash).into_iter().collect(),
+            block: block.clone(),
+        }
+    }
+}
+
+#[ComplexObject]
+impl Transaction {
+    async fn block(&self) -> Block {
+        Block(self.block.clone())
+    }
+}
+
+struct Block(Arc<BlockProof>);
+
+#[Object]
+impl Block {
+    async fn slot(&self) -> Slot {
+        self.0.slot
+    }
+
+    async fn block_hash(&self) -> &str {
+        &self.0.block_hash
+    }
+
+    async fn genesis_hash(&self) -> Option<&str> {
+        self.0.genesis_hash.as_deref()
+    }
+
+    async fn leader(&self) -> Option<&str> {
+        self.0.leader.as_deref()
+    }
+
+    async fn transaction_count(&self) -> usize {
+        self.0.transactions.len()
+    }
+
+    // Unix time the proof was completed
+    async fn proved_at(&self) -> Option<i64> {
+        self.0.latency.as_ref().map(|latency| latency.proved_at)
+    }
+
+    async fn proof(&self) -> Proof {
+        let block_proof = &self.0;
+        Proof {
+            proof: block_proof.proof.clone(),
+            commitment: block_proof.commitment.clone(),
+            old_root: block_proof.old_root.clone(),
+            new_root: block_proof.new_root.clone(),
+            transactions_root: block_proof.transactions_root.clone(),
+            params_fingerprint: block_proof.params_fingerprint.clone(),
+            signers: block_proof.signatures.iter().map(|signature| signature.signer.clone()).collect(),
+            file: format!("/files/{}", proof_file_name(block_proof.slot)),
+        }
+    }
+
+    // Transactions of the block, only those touching `account` if given
+    async fn transactions(&self, account: Option<String>) -> Vec<Transaction> {
+        let block_proof = &self.0;
+        block_proof
+            .transactions
+            .iter()
+            .map(|transaction| Transaction::new(block_proof, transaction))
+            .filter(|transaction| account.as_ref().is_none_or(|account| transaction.accounts.contains(account)))
+            .collect()
+    }
+}
+
+pub struct Query;
+
+#[Object]
+impl Query {
+    async fn block(&self, ctx: &Context<'_>, slot: Slot) -> Option<Block> {
+        let path = ctx.data_unchecked::<ProofsDir>().0.join(proof_file_name(slot));
+        path.exists().then(|| Block(Arc::new(load_proof(&path))))
+    }
+
+    // Proved blocks between two slots, both included, in ascending order
+    async fn blocks(
+        &self,
+        ctx: &Context<'_>,
+        from_slot: Option<Slot>,
+        to_slot: Option<Slot>,
+        limit: Option<usize>,
+    ) -> Vec<Block> {
+        let proofs_dir = &ctx.data_unchecked::<ProofsDir>().0;
+        slots_between(proofs_dir, from_slot, to_slot)
+            .take(self::limit(limit))
+            .map(|slot| Block(Arc::new(load_proof(&proofs_dir.join(proof_file_name(slot))))))
+            .collect()
+    }
+
+    async fn transaction(&self, ctx: &Context<'_>, signature: String) -> Option<Transaction> {
+        let proofs_dir = &ctx.data_unchecked::<ProofsDir>().0;
+        let (slot, transaction) = locate_transaction(proofs_dir, &signature).ok()?;
+        let block = Arc::new(load_proof(&proofs_dir.join(proof_file_name(slot))));
+        Some(Transaction::new(&block, &transaction))
+    }
+
+    // Proved transactions touching `account` between two slots, both
+    // included, in slot order
+    async fn transactions(
+        &self,
+        ctx: &Context<'_>,
+        account: String,
+        from_slot: Option<Slot>,
+        to_slot: Option<Slot>,
+        limit: Option<usize>,
+    ) -> Vec<Transaction> {
+        let proofs_dir = &ctx.data_unchecked::<ProofsDir>().0;
+        slots_between(proofs_dir, from_slot, to_slot)
+            .flat_map(|slot| {
+                let block = Arc::new(load_proof(&proofs_dir.join(proof_file_name(slot))));
+                let transactions: Vec<_> = block
+                    .transactions
+                    .iter()
+                    .map(|transaction| Transaction::new(&block, transaction))
+                    .filter(|transaction| transaction.accounts.contains(&account))
+                    .collect();
+                transactions
+            })
+            .take(self::limit(limit))
+            .collect()
+    }
+}
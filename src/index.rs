@@ -0,0 +1,72 @@
+use serde::{Deserialize, Serialize};
+use solana_sdk::clock::Slot;
+use std::collections::BTreeMap;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+
+// Append-only record of what happened to each processed slot, kept next to
+// the proofs as `index.jsonl`
+#[derive(Serialize, Deserialize)]
+pub struct IndexEntry {
+    pub slot: Slot,
+    pub status: SlotStatus,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum SlotStatus {
+    Proved,
+    // The endpoint reported that the cluster produced no block in the slot,
+    // in the words of the `reason`
+    Skipped,
+    // Refused because the data sources disagreed
+    Flagged,
+    // Not proved because no transaction matched the configured filters
+    Empty,
+    // Not proved because it has more transactions than `max_txs_per_block`
+    Oversized,
+    // The block could not be fetched
+    FetchFailed,
+    // The block was fetched but its witness or proof could not be built
+    Failed,
+    // Proving took longer than `proving_timeout_secs`
+    TimedOut,
+}
+
+pub fn append(proofs_dir: &Path, slot: Slot, status: SlotStatus, reason: Option<String>) {
+    let entry = IndexEntry { slot, status, reason };
+    let mut line = serde_json::to_string(&entry).expect("Unable to serialize index entry");
+    line.push('\n');
+
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(proofs_dir.join("index.jsonl"))
+        .expect("Unable to open slot index");
+    file.write_all(line.as_bytes()).expect("Unable to write slot index");
+}
+
+// Latest entry of `slot`, without loading the entries of every other slot
+pub fn lookup(proofs_dir: &Path, slot: Slot) -> Option<IndexEntry> {
+    let contents = fs::read_to_string(proofs_dir.join("index.jsonl")).ok()?;
+    let needle = format!("\"slot\":{},", slot);
+    contents
+        .lines()
+        .rev()
+        .filter(|line| line.contains(&needle))
+        .filter_map(|line| serde_json::from_str::<IndexEntry>(line).ok())
+        .find(|entry| entry.slot == slot)
+}
+
+// Latest entry per slot; later entries supersede earlier ones for the same slot
+pub fn load(proofs_dir: &Path) -> BTreeMap<Slot, IndexEntry> {
+    let contents = fs::read_to_string(proofs_dir.join("index.jsonl")).unwrap_or_default();
+    contents
+        .lines()
+        .filter_map(|line| serde_json::from_str::<IndexEntry>(line).ok())
+        .map(|entry| (entry.slot, entry))
+        .collect()
+}
@@ -0,0 +1,85 @@
+use solana_sdk::bs58;
+use solana_sdk::instruction::CompiledInstruction;
+use solana_transaction_status::option_serializer::OptionSerializer;
+use solana_transaction_status::{EncodedTransactionWithStatusMeta, UiInstruction};
+
+// An instruction with its program and accounts resolved to addresses
+pub struct Instruction {
+    pub program_id: String,
+    pub accounts: Vec<String>,
+    pub data: Vec<u8>,
+}
+
+// A decoded transaction: its signatures, the hash of its serialized message,
+// account keys (including those loaded from lookup tables) and every
+// instruction it executed, top-level and inner
+pub struct DecodedTransaction {
+    pub signature: String,
+    pub signatures: Vec<String>,
+    pub message_hash: String,
+    pub account_keys: Vec<String>,
+    pub instructions: Vec<Instruction>,
+    pub succeeded: bool,
+}
+
+fn resolve(account_keys: &[String], program_id_index: u8, accounts: &[u8], data: Vec<u8>) -> Option<Instruction> {
+    let key = |index: u8| account_keys.get(index as usize).cloned();
+    Some(Instruction {
+        program_id: key(program_id_index)?,
+        accounts: accounts.iter().map(|&index| key(index)).collect::<Option<_>>()?,
+        data,
+    })
+}
+
+fn resolve_compiled(account_keys: &[String], instruction: &CompiledInstruction) -> Option<Instruction> {
+    resolve(account_keys, instruction.program_id_index, &instruction.accounts, instruction.data.clone())
+}
+
+// Decodes a binary-encoded transaction from a block; `None` if it is not
+// binary-encoded or does not deserialize
+pub fn decode(transaction_with_meta: &EncodedTransactionWithStatusMeta) -> Option<DecodedTransaction> {
+    let transaction = transaction_with_meta.transaction.decode()?;
+    let meta = transaction_with_meta.meta.as_ref();
+
+    let mut account_keys: Vec<String> =
+        transaction.message.static_account_keys().iter().map(|key| key.to_string()).collect();
+    if let Some(OptionSerializer::Some(loaded)) = meta.map(|meta| &meta.loaded_addresses) {
+        account_keys.extend(loaded.writable.iter().cloned());
+        account_keys.extend(loaded.readonly.iter().cloned());
+    }
+
+    // Inner instructions are listed after the top-level instruction that invoked them
+    let inner = match meta.map(|meta| &meta.inner_instructions) {
+        Some(OptionSerializer::Some(inner)) => inner.as_slice(),
+        _ => &[],
+    };
+    let mut instructions = Vec::new();
+    for (index, instruction) in transaction.message.instructions().iter().enumerate() {
+        instructions.extend(resolve_compiled(&account_keys, instruction));
+        for inner_instruction in inner.iter().filter(|inner| inner.index as usize == index) {
+            for instruction in &inner_instruction.instructions {
+                if let UiInstruction::Compiled(instruction) = instruction {
+                    let Ok(data) = bs58::decode(&instruction.data).into_vec() else {
+                        continue;
+                    };
+                    instructions.extend(resolve(
+                        &account_keys,
+                        instruction.program_id_index,
+                        &instruction.accounts,
+                        data,
+                    ));
+                }
+            }
+        }
+    }
+
+    let signatures: Vec<String> = transaction.signatures.iter().map(|signature| signature.to_string()).collect();
+    Some(DecodedTransaction {
+        signature: signatures.first()?.clone(),
+        signatures,
+        message_hash: transaction.message.hash().to_string(),
+        account_keys,
+        instructions,
+        succeeded: meta.is_some_and(|meta| meta.err.is_none()),
+    })
+}
@@ -0,0 +1,78 @@
+use bellman::groth16;
+use blstrs::Bls12;
+use solana_block_verifier::VerifyingKey;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::{params, serialization, verify};
+
+// Every verifying key the archive's proofs were made with, kept as
+// `keys/<fingerprint>.vk` in the proofs directory so proofs made before a key
+// rotation stay checkable
+pub fn keyring_dir(proofs_dir: &Path) -> PathBuf {
+    proofs_dir.join("keys")
+}
+
+// Adds a verifying key to the keyring of the proofs directory, if not there yet
+pub fn record(proofs_dir: &Path, vk: &groth16::VerifyingKey<Bls12>) {
+    let dir = keyring_dir(proofs_dir);
+    let path = dir.join(format!("{}.vk", params::fingerprint(vk)));
+    if path.exists() {
+        return;
+    }
+    fs::create_dir_all(&dir).expect("Unable to create keyring directory");
+    fs::write(path, serialization::vk_to_bytes(vk)).expect("Unable to write verifying key");
+}
+
+// Verifying keys indexed by fingerprint, plus the key to check proofs against
+// that were written before fingerprints were recorded
+#[derive(Default)]
+pub struct Keyring {
+    keys: BTreeMap<String, VerifyingKey>,
+    current: Option<String>,
+}
+
+impl Keyring {
+    // Every key in `dir`; a missing directory gives an empty keyring
+    pub fn load(dir: &Path) -> Self {
+        let mut keyring = Keyring::default();
+        let Ok(entries) = fs::read_dir(dir) else {
+            return keyring;
+        };
+        for path in entries.filter_map(|entry| Some(entry.ok()?.path())) {
+            if path.extension().is_none_or(|extension| extension != "vk") {
+                continue;
+            }
+            let contents = fs::read(&path).expect("Unable to read verifying key");
+            let vk = serialization::vk_from_bytes(&contents)
+                .unwrap_or_else(|e| panic!("Unable to read verifying key {:?}: {}", path, e));
+            keyring.insert(&vk);
+        }
+        keyring
+    }
+
+    pub fn insert(&mut self, vk: &groth16::VerifyingKey<Bls12>) -> String {
+        let key = verify::verifier_key(vk);
+        let fingerprint = params::fingerprint(vk);
+        self.keys.insert(fingerprint.clone(), key);
+        fingerprint
+    }
+
+    // Adds a key and uses it for proofs that record no fingerprint
+    pub fn insert_current(&mut self, vk: &groth16::VerifyingKey<Bls12>) {
+        self.current = Some(self.insert(vk));
+    }
+
+    pub fn get(&self, fingerprint: &str) -> Option<&VerifyingKey> {
+        self.keys.get(fingerprint)
+    }
+
+    pub fn current(&self) -> Option<&str> {
+        self.current.as_deref()
+    }
+
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+}
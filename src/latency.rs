@@ -0,0 +1,47 @@
+use serde::{Deserialize, Serialize};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// Time from a block's timestamp to the completion of its proof, checked
+// against the operator's latency SLO. The timestamp is the leader's, so the
+// latency includes the time the block took to be finalized.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ProofLatency {
+    // Unix time the proof was completed
+    pub proved_at: i64,
+    pub latency_secs: u64,
+    pub slo_secs: u64,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub slo_violated: bool,
+}
+
+// Local wall-clock time a block was processed at, against the block's
+// timestamp. Positive when the local clock is ahead of the timestamp, which
+// includes the time the block took to be finalized.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ClockDrift {
+    pub processed_at: i64,
+    pub drift_secs: i64,
+}
+
+fn now() -> i64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).expect("System clock before 1970").as_secs() as i64
+}
+
+// `None` for a block without a timestamp
+pub fn drift(block_time: Option<i64>) -> Option<ClockDrift> {
+    let processed_at = now();
+    Some(ClockDrift { processed_at, drift_secs: processed_at - block_time? })
+}
+
+// `None` without an SLO, or for a block without a timestamp
+pub fn measure(block_time: Option<i64>, slo_secs: Option<u64>) -> Option<ProofLatency> {
+    let (block_time, slo_secs) = (block_time?, slo_secs?);
+    let proved_at = now();
+    let latency_secs = proved_at.saturating_sub(block_time).max(0) as u64;
+    Some(ProofLatency {
+        proved_at,
+        latency_secs,
+        slo_secs,
+        slo_violated: latency_secs > slo_secs,
+    })
+}
@@ -0,0 +1,145 @@
+use log::info;
+use serde::{Deserialize, Serialize};
+use solana_sdk::clock::Slot;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::config::CoordinationConfig;
+
+// A directory of lease files, one per slot range, shared by every worker (e.g.
+// on NFS). Ranges are handed out lowest first, so a stalled worker's range is
+// picked up again once its lease expires and no gaps are left behind.
+pub struct LeaseTable {
+    dir: PathBuf,
+    worker_id: String,
+    shard_size: u64,
+    start_slot: Slot,
+    ttl: Duration,
+}
+
+#[derive(Serialize, Deserialize)]
+struct LeaseRecord {
+    worker_id: String,
+    start_slot: Slot,
+    end_slot: Slot,
+    expires_at: u64,
+    completed: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    accumulator_root: Option<String>,
+}
+
+// A claimed range of slots, `start_slot..end_slot`
+pub struct Lease {
+    pub start_slot: Slot,
+    pub end_slot: Slot,
+    path: PathBuf,
+}
+
+fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs()
+}
+
+impl LeaseTable {
+    pub fn new(config: &CoordinationConfig) -> Self {
+        fs::create_dir_all(&config.lease_dir).expect("Unable to create lease directory");
+        LeaseTable {
+            dir: config.lease_dir.clone(),
+            worker_id: config.worker_id.clone(),
+            shard_size: config.shard_size,
+            start_slot: config.start_slot,
+            ttl: Duration::from_secs(config.lease_ttl_secs),
+        }
+    }
+
+    fn record(&self, start_slot: Slot, completed: bool, accumulator_root: Option<String>) -> LeaseRecord {
+        LeaseRecord {
+            worker_id: self.worker_id.clone(),
+            start_slot,
+            end_slot: start_slot + self.shard_size,
+            expires_at: now() + self.ttl.as_secs(),
+            completed,
+            accumulator_root,
+        }
+    }
+
+    fn read(path: &PathBuf) -> io::Result<LeaseRecord> {
+        let contents = fs::read_to_string(path)?;
+        serde_json::from_str(&contents).map_err(io::Error::from)
+    }
+
+    // Creates the lease file only if no other worker holds it
+    fn create(&self, path: &PathBuf, start_slot: Slot) -> io::Result<bool> {
+        match OpenOptions::new().write(true).create_new(true).open(path) {
+            Ok(mut file) => {
+                let record = self.record(start_slot, false, None);
+                file.write_all(serde_json::to_string(&record)?.as_bytes())?;
+                Ok(true)
+            }
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    // Rewrites a lease we own, via a temporary file and an atomic rename
+    fn write(&self, path: &PathBuf, record: &LeaseRecord) -> io::Result<()> {
+        let current = Self::read(path)?;
+        if current.worker_id != self.worker_id {
+            return Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                format!("lease is now held by {}", current.worker_id),
+            ));
+        }
+
+        let tmp_path = path.with_extension(format!("{}.tmp", self.worker_id));
+        fs::write(&tmp_path, serde_json::to_string(record)?)?;
+        fs::rename(&tmp_path, path)
+    }
+
+    // Claims the lowest open range that has started by `tip`
+    pub fn claim(&self, tip: Slot) -> io::Result<Option<Lease>> {
+        let mut start_slot = self.start_slot;
+
+        while start_slot <= tip {
+            let path = self.dir.join(format!("lease_{}.json", start_slot));
+            let lease = Lease {
+                start_slot,
+                end_slot: start_slot + self.shard_size,
+                path: path.clone(),
+            };
+
+            if self.create(&path, start_slot)? {
+                return Ok(Some(lease));
+            }
+
+            match Self::read(&path) {
+                Ok(record) if record.completed => {}
+                // Our own lease from before a restart
+                Ok(record) if record.worker_id == self.worker_id => return Ok(Some(lease)),
+                Ok(record) if record.expires_at < now() => {
+                    // Move the expired lease aside; only one worker's rename can succeed
+                    let expired_path = path.with_extension(format!("expired.{}.{}", self.worker_id, now()));
+                    if fs::rename(&path, &expired_path).is_ok() && self.create(&path, start_slot)? {
+                        info!("Took over expired lease on slots {}..{} from {}", start_slot, lease.end_slot, record.worker_id);
+                        return Ok(Some(lease));
+                    }
+                }
+                // Held by another worker, or being written right now
+                _ => {}
+            }
+
+            start_slot += self.shard_size;
+        }
+
+        Ok(None)
+    }
+
+    pub fn renew(&self, lease: &Lease) -> io::Result<()> {
+        self.write(&lease.path, &self.record(lease.start_slot, false, None))
+    }
+
+    pub fn complete(&self, lease: &Lease, accumulator_root: String) -> io::Result<()> {
+        self.write(&lease.path, &self.record(lease.start_slot, true, Some(accumulator_root)))
+    }
+}
@@ -0,0 +1,1444 @@
+use blstrs::Scalar as Fr;
+use ff::Field;
+use futures::FutureExt;
+use log::{error, info, warn, LevelFilter};
+use solana_client::client_error::{ClientError, ClientErrorKind};
+use solana_client::rpc_client::{RpcClient, RpcClientConfig};
+use solana_client::rpc_config::RpcBlockConfig;
+use solana_rpc_client::http_sender::HttpSender;
+use solana_sdk::clock::{Slot, DEFAULT_MS_PER_SLOT};
+use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::epoch_schedule::EpochSchedule;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Keypair;
+use solana_transaction_status::{EncodedConfirmedBlock, TransactionDetails, UiTransactionEncoding};
+use std::collections::{BTreeSet, HashSet};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::future::Future;
+use std::panic::AssertUnwindSafe;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use tokio::sync::{mpsc, oneshot};
+use tokio::time::{sleep, timeout, Duration, Instant};
+
+use crate::accounts::{self, AccountStates};
+use crate::admin::Control;
+use crate::alerts::{AlertKind, Alerter};
+use crate::anchor::AnchorDecoder;
+use crate::anomaly::{self, SignatureWindow};
+use crate::block_cache::BlockCache;
+use crate::checkpoint::Checkpoint;
+use crate::config::{Config, CoordinationConfig, Ingestion, OversizedPolicy, TokenSupplyConfig};
+use crate::costs::MeteredSender;
+use crate::ethereum::EthereumSubmitter;
+use crate::disclosure;
+use crate::dry_run::{self, DryRunRecord};
+use crate::election::LeaderElection;
+use crate::epoch;
+use crate::field::fr_to_hex;
+use crate::filter;
+use crate::gossip::Gossip;
+use crate::index::{self, SlotStatus};
+use crate::lease::LeaseTable;
+use crate::latency;
+use crate::manifest::Manifest;
+use crate::metrics::Metrics;
+use crate::nullifier;
+use crate::prover::{ProveError, ProverPool};
+use crate::queue::{BlockQueue, QueuedBlock};
+use crate::raw_blocks;
+use crate::schedule;
+use crate::sink::{self, SharedStorage, Sinks};
+use crate::stake;
+use crate::supply::{self, TokenSupply};
+use crate::stake_activity;
+use crate::votes;
+use crate::storage::ObjectStorage;
+use crate::subscription::RootSubscription;
+use crate::systemd::{Heartbeat, Watchdog};
+use crate::witness::{self, ExportedWitness};
+use crate::witness_archive;
+use crate::{
+    block_signatures, build_block_witness, cosign, list_proof_slots, load_proof, load_signing_keypair, proof_file_name,
+    publish_proof, save_witness_to_json, BlockProof,
+};
+
+// Result of processing a single slot
+enum SlotOutcome {
+    // The block was proved (or its witness exported); carries the new accumulator root
+    Proved(Fr),
+    // No proof was produced; the reason has been logged
+    Skipped,
+    // Nothing in the block matched the configured filters, so it was not
+    // proved; the accumulator root is unchanged
+    Empty,
+    // The proof did not verify or timed out, so it was not published; the
+    // accumulator root is unchanged, but later witnesses may have been chained
+    // past it
+    Rejected,
+    // The ledger no longer has this slot; resume from the first available block
+    JumpTo(Slot),
+    // A request timed out; the slot should be tried again on the next poll
+    Retry,
+}
+
+// Result of fetching the block of a slot
+enum Fetched {
+    Block(Box<EncodedConfirmedBlock>),
+    // There is no block to prove; the reason has been logged
+    Skipped,
+    // The ledger no longer has this slot; resume from the first available block
+    JumpTo(Slot),
+    // A request timed out; the slot should be fetched again
+    Retry,
+    // The block is not finalized yet; it should be fetched again shortly
+    Pending,
+}
+
+// A witness ready to be proved, chained onto the accumulator root before it
+struct Job {
+    exported: ExportedWitness,
+    new_root: Fr,
+    block_time: Option<i64>,
+    // Settings the slot started with
+    config: Arc<Config>,
+}
+
+enum Prepared {
+    Job(Box<Job>),
+    // Nothing to prove
+    Done(Done),
+}
+
+// Result of a slot, for the writer to record
+enum Done {
+    // Proof, or witness in witness-only mode, or record in a dry run, with the
+    // new accumulator root
+    Proof(Box<BlockProof>, Fr),
+    Witness(Box<ExportedWitness>, Fr),
+    Record(Box<DryRunRecord>, Fr),
+    // The existing proof of the slot was made from the same witness
+    Duplicate(Fr),
+    // The proof did not verify against the key it was made with
+    Rejected(String),
+    // Proving ran over the timeout and was cancelled
+    TimedOut(Duration),
+    Empty,
+    Skipped,
+}
+
+// Place of a slot in the writer's order, with the result its prover sends
+struct Ticket {
+    generation: u64,
+    slot: Slot,
+    done: oneshot::Receiver<Done>,
+}
+
+// State shared by the tasks of `run`
+struct Pipeline {
+    queue: BlockQueue,
+    // Whether this instance follows the chain, i.e. leads if election is configured
+    following: AtomicBool,
+    // Queue generation of the last restart, and the checkpoint root it resumed from
+    origin: Mutex<(u64, Fr)>,
+    // Generation and root the next witness is chained onto. Held while a prover
+    // takes a block off the queue and builds its witness, so witnesses are
+    // built in order.
+    root: tokio::sync::Mutex<(u64, Fr)>,
+    tickets: mpsc::Sender<Ticket>,
+    receiver: tokio::sync::Mutex<mpsc::Receiver<Ticket>>,
+    seen_blocks: Mutex<HashSet<Slot>>,
+}
+
+// Outcome of comparing a block with the cross-check endpoint's copy
+enum CrossCheck {
+    Agree,
+    Disagree(String),
+    TimedOut,
+}
+
+// How long a measured slot time is used before it is measured again
+const SLOT_TIME_TTL: Duration = Duration::from_secs(60);
+
+// Number of slot leaders fetched per `getSlotLeaders` request, the RPC maximum
+pub const LEADER_WINDOW: u64 = 5000;
+
+// A run of consecutive slot leaders starting at `start_slot`
+#[derive(Default)]
+struct LeaderWindow {
+    start_slot: Slot,
+    leaders: Vec<Pubkey>,
+}
+
+// Blocks are fetched with full transaction details and metadata, with
+// transactions base64-encoded so their messages can be hashed exactly as
+// signed. Versioned transactions are accepted so blocks using lookup tables
+// can be decoded.
+pub fn rpc_client(url: String, timeout_secs: u64) -> RpcClient {
+    let sender = HttpSender::new_with_timeout(url, Duration::from_secs(timeout_secs));
+    let config = RpcClientConfig::with_commitment(CommitmentConfig::default());
+    RpcClient::new_sender(MeteredSender::new(sender), config)
+}
+
+// Whether the node no longer has the block of the slot in its ledger. Nodes
+// report purged blocks and slots missing after a snapshot jump alike.
+pub fn is_purged(e: &ClientError) -> bool {
+    let error_message = e.to_string();
+    error_message.contains("cleaned up") || error_message.contains("missing due to ledger jump")
+}
+
+// First block the ledger still has, from the error of a request for a block
+// it has cleaned up
+fn first_available_block(error_message: &str) -> Option<Slot> {
+    let (_, rest) = error_message.split_once("First available block: ")?;
+    let end = rest.find(|c: char| !c.is_ascii_digit()).unwrap_or(rest.len());
+    rest[..end].parse().ok()
+}
+
+// Whether a request failed by timing out, which is worth retrying
+fn is_timeout(e: &ClientError) -> bool {
+    match e.kind() {
+        ClientErrorKind::Reqwest(e) => e.is_timeout(),
+        ClientErrorKind::Io(e) => e.kind() == io::ErrorKind::TimedOut,
+        _ => false,
+    }
+}
+
+pub fn block_config() -> RpcBlockConfig {
+    RpcBlockConfig {
+        encoding: Some(UiTransactionEncoding::Base64),
+        transaction_details: Some(TransactionDetails::Full),
+        rewards: Some(true),
+        commitment: None,
+        max_supported_transaction_version: Some(0),
+    }
+}
+
+// What the listener instances of a process share
+pub struct Shared {
+    // Where the configuration is reloaded from; `None` when running on the defaults
+    pub config_path: Option<PathBuf>,
+    // `None` in witness-only mode
+    pub prover: Option<ProverPool>,
+    pub control: Arc<Control>,
+    pub metrics: Arc<Metrics>,
+    pub alerts: Option<Arc<Alerter>>,
+    pub sample_rate: u64,
+    // Only prove the blocks led by this validator
+    pub leader_identity: Option<Pubkey>,
+    // Record what would be proved instead of proving it
+    pub dry_run: bool,
+    // `None` unless the service manager watches the process
+    pub watchdog: Option<Arc<Watchdog>>,
+    // Blocks fetched for one proving profile and kept for the others; `None`
+    // unless several profiles are configured
+    pub blocks: Option<Arc<BlockCache>>,
+}
+
+pub struct Listener<'a> {
+    // Settings fixed at startup
+    config: &'a Config,
+    // Name of the instance in a multi-cluster process
+    instance: Option<String>,
+    // Log target of this instance, labelling its log lines with the instance name
+    log_target: String,
+    config_path: Option<PathBuf>,
+    // Latest config file, for the settings applied per slot. Each slot keeps
+    // the copy it started with, so a reload never changes a proof in flight.
+    settings: RwLock<Arc<Config>>,
+    // Clients with the timeout of `getSlot`, of `getBlock`, and of every other request
+    slot_client: RpcClient,
+    block_client: RpcClient,
+    client: RpcClient,
+    cross_check_client: Option<RpcClient>,
+    archive_client: Option<RpcClient>,
+    // Rooted slots streamed over the websocket, with subscription-based
+    // ingestion. They only wake the listener up: the slots to prove are still
+    // read from `getSlot`, whose finalized slot may trail the node's own root.
+    roots: Option<RootSubscription>,
+    storage: SharedStorage,
+    prover: Option<ProverPool>,
+    dry_run: bool,
+    keypair: Option<Keypair>,
+    gossip: Option<Arc<Gossip>>,
+    ethereum: Option<EthereumSubmitter>,
+    sinks: Sinks,
+    // Used to roll block proofs up into epoch summaries; `None` in witness-only mode
+    epoch_schedule: Option<EpochSchedule>,
+    leaders: Mutex<LeaderWindow>,
+    // Recent slot time from the endpoint's performance samples, and when it was measured
+    slot_time: Mutex<Option<(Instant, Duration)>>,
+    anchor: Option<AnchorDecoder>,
+    manifest: Manifest,
+    control: Arc<Control>,
+    metrics: Arc<Metrics>,
+    alerts: Option<Arc<Alerter>>,
+    // Latest tip fetched from the endpoint, for measuring how far behind the listener is
+    last_tip: AtomicU64,
+    // Whether proving is held back until a window of the schedule opens
+    outside_window: AtomicBool,
+    // Flush and reload requests this instance has acted on
+    seen_flushes: AtomicU64,
+    seen_reloads: AtomicU64,
+    // Slots and witness hashes of the proofs published to the proofs directory
+    nullifiers: Mutex<HashSet<(Slot, String)>>,
+    // Transaction signatures of recent blocks, when `duplicate_signature_window` is set
+    signatures: Option<Mutex<SignatureWindow>>,
+    // Beaten by the loop that moves the checkpoint on, for the service manager's watchdog
+    heartbeat: Option<Heartbeat>,
+    blocks: Option<Arc<BlockCache>>,
+}
+
+impl<'a> Listener<'a> {
+    // `instance` names the instance `config` was taken from, if any.
+    // `genesis_hash` is the endpoint's, already checked against the archive.
+    pub async fn new(config: &'a Config, instance: Option<&str>, shared: &Shared, genesis_hash: String) -> Self {
+        let log_target = match instance {
+            Some(name) => format!("{}::{}", module_path!(), name),
+            None => module_path!().to_string(),
+        };
+        let storage = config.storage.as_ref().map(|storage_config| {
+            let storage = ObjectStorage::from_config(storage_config, Some(&genesis_hash));
+            Arc::new(storage.expect("Unable to configure object storage"))
+        });
+        let storage = Arc::new(RwLock::new(storage));
+        fs::create_dir_all(&config.proofs_dir).expect("Unable to create proofs directory");
+        let proof_slots = list_proof_slots(&config.proofs_dir);
+        let proof_bytes = proof_slots
+            .iter()
+            .filter_map(|slot| fs::metadata(config.proofs_dir.join(proof_file_name(*slot))).ok())
+            .map(|metadata| metadata.len())
+            .sum();
+        shared.metrics.record_storage(instance, proof_slots.len() as u64, proof_bytes, proof_slots.last().copied());
+
+        let manifest = Manifest {
+            sample_rate: shared.sample_rate,
+            leader_identity: shared.leader_identity.map(|identity| identity.to_string()),
+            genesis_hash: Some(genesis_hash.clone()),
+        };
+        if let Some(previous) = Manifest::load(&config.proofs_dir) {
+            if previous.sample_rate != manifest.sample_rate {
+                warn!(
+                    target: &log_target,
+                    "Sampling policy changed from every {} to every {} slots",
+                    previous.sample_rate, manifest.sample_rate
+                );
+            }
+            if previous.leader_identity != manifest.leader_identity {
+                warn!(
+                    target: &log_target,
+                    "Leader identity changed from {} to {}",
+                    previous.leader_identity.as_deref().unwrap_or("any validator"),
+                    manifest.leader_identity.as_deref().unwrap_or("any validator")
+                );
+            }
+        }
+        manifest.save(&config.proofs_dir);
+
+        let gossip = match &config.gossip {
+            Some(gossip_config) => {
+                let circuit_params = [
+                    &*config.sum_params_path,
+                    &config.totals_params_path,
+                    &config.threshold_params_path,
+                    &config.signature_params_path,
+                ];
+                let proofs_dir = config.proofs_dir.clone();
+                Some(Gossip::start(gossip_config, proofs_dir, genesis_hash, &config.params_path, circuit_params).await)
+            }
+            None => None,
+        };
+
+        let timeouts = &config.rpc_timeouts;
+        let roots = (config.ingestion == Ingestion::Subscribe).then(|| {
+            RootSubscription::start(config.ws_url(), Duration::from_secs(timeouts.ws_idle_secs), log_target.clone())
+        });
+        let client = rpc_client(config.rpc_url(), timeouts.request_secs);
+        let epoch_schedule = shared
+            .prover
+            .is_some()
+            .then(|| client.get_epoch_schedule().expect("Unable to fetch epoch schedule"));
+        let sinks = sink::configured(config, &storage, instance);
+        let sinks = Sinks::start(sinks, &config.proofs_dir, instance, shared.metrics.clone());
+
+        Listener {
+            config,
+            instance: instance.map(str::to_string),
+            log_target,
+            config_path: shared.config_path.clone(),
+            settings: RwLock::new(Arc::new(config.clone())),
+            slot_client: rpc_client(config.rpc_url(), timeouts.get_slot_secs),
+            block_client: rpc_client(config.rpc_url(), timeouts.get_block_secs),
+            client,
+            cross_check_client: config.cross_check_rpc_url.clone().map(|url| rpc_client(url, timeouts.get_block_secs)),
+            archive_client: config.archive_rpc_url.clone().map(|url| rpc_client(url, timeouts.get_block_secs)),
+            roots,
+            storage,
+            prover: shared.prover.clone(),
+            dry_run: shared.dry_run,
+            keypair: load_signing_keypair(config),
+            gossip,
+            ethereum: config.ethereum.as_ref().map(|ethereum_config| {
+                EthereumSubmitter::start(ethereum_config).expect("Unable to configure the Ethereum submitter")
+            }),
+            sinks,
+            epoch_schedule,
+            leaders: Mutex::new(LeaderWindow::default()),
+            slot_time: Mutex::new(None),
+            anchor: (!config.anchor.is_empty()).then(|| AnchorDecoder::load(&config.anchor)),
+            manifest,
+            control: shared.control.clone(),
+            metrics: shared.metrics.clone(),
+            alerts: shared.alerts.clone(),
+            last_tip: AtomicU64::new(0),
+            outside_window: AtomicBool::new(false),
+            seen_flushes: AtomicU64::new(0),
+            seen_reloads: AtomicU64::new(0),
+            nullifiers: Mutex::new(nullifier::load(&config.proofs_dir)),
+            signatures: config.duplicate_signature_window.map(|window| Mutex::new(SignatureWindow::new(window))),
+            heartbeat: shared.watchdog.as_ref().map(|watchdog| watchdog.register()),
+            blocks: shared.blocks.clone(),
+        }
+    }
+
+    fn proofs_dir(&self) -> &Path {
+        &self.config.proofs_dir
+    }
+
+    fn settings(&self) -> Arc<Config> {
+        self.settings.read().unwrap().clone()
+    }
+
+    fn storage(&self) -> Option<Arc<ObjectStorage>> {
+        self.storage.read().unwrap().clone()
+    }
+
+    fn beat(&self) {
+        if let Some(heartbeat) = &self.heartbeat {
+            heartbeat.beat();
+        }
+    }
+
+    // Current slot of the endpoint; `None`, after logging why, if it could not be fetched
+    fn tip(&self) -> Option<Slot> {
+        match self.slot_client.get_slot() {
+            Ok(slot) => {
+                self.last_tip.fetch_max(slot, Ordering::SeqCst);
+                self.metrics.record_tip(self.instance.as_deref(), slot);
+                Some(slot)
+            }
+            Err(e) if is_timeout(&e) => {
+                warn!(target: &self.log_target, "Timed out fetching the current slot, retrying");
+                None
+            }
+            Err(e) => {
+                error!(target: &self.log_target, "Error fetching the current slot: {}", e);
+                None
+            }
+        }
+    }
+
+    // Fetches the block from the cross-check endpoint and describes any
+    // disagreement with the primary endpoint's copy
+    fn cross_check(&self, client: &RpcClient, slot: Slot, block: &EncodedConfirmedBlock) -> CrossCheck {
+        let other = match client.get_block_with_config(slot, block_config()).map(EncodedConfirmedBlock::from) {
+            Ok(other) => other,
+            Err(e) if is_timeout(&e) => return CrossCheck::TimedOut,
+            Err(e) => return CrossCheck::Disagree(format!("cross-check endpoint failed: {}", e)),
+        };
+
+        if other.blockhash != block.blockhash {
+            return CrossCheck::Disagree(format!("blockhash mismatch: {} vs {}", block.blockhash, other.blockhash));
+        }
+
+        let signatures: BTreeSet<_> = block_signatures(block).into_iter().collect();
+        let other_signatures: BTreeSet<_> = block_signatures(&other).into_iter().collect();
+        if signatures != other_signatures {
+            return CrossCheck::Disagree(format!(
+                "transaction sets differ: {} only on primary, {} only on cross-check endpoint",
+                signatures.difference(&other_signatures).count(),
+                other_signatures.difference(&signatures).count()
+            ));
+        }
+
+        CrossCheck::Agree
+    }
+
+    // Leader identity scheduled for `slot`, fetched from the leader schedule a
+    // window of slots at a time
+    fn slot_leader(&self, slot: Slot) -> Option<Pubkey> {
+        let mut window = self.leaders.lock().unwrap();
+        let in_window = |window: &LeaderWindow| {
+            slot.checked_sub(window.start_slot)
+                .and_then(|offset| window.leaders.get(offset as usize).copied())
+        };
+
+        if let Some(leader) = in_window(&window) {
+            return Some(leader);
+        }
+        match self.client.get_slot_leaders(slot, LEADER_WINDOW) {
+            Ok(leaders) => {
+                *window = LeaderWindow { start_slot: slot, leaders };
+                in_window(&window)
+            }
+            Err(e) => {
+                error!(target: &self.log_target, "Unable to fetch slot leaders from {}: {}", slot, e);
+                None
+            }
+        }
+    }
+
+    // Whether `slot` is to be proved: sampled, and led by the watched validator
+    // if there is one. `None` while its leader cannot be fetched.
+    fn is_selected(&self, slot: Slot) -> Option<bool> {
+        if !self.manifest.is_sampled(slot) {
+            return Some(false);
+        }
+        match &self.manifest.leader_identity {
+            Some(identity) => Some(self.slot_leader(slot)?.to_string() == *identity),
+            None => Some(true),
+        }
+    }
+
+    // Average slot time over the endpoint's recent performance samples, or the
+    // cluster's target slot time if they cannot be fetched
+    fn slot_time(&self) -> Duration {
+        let mut slot_time = self.slot_time.lock().unwrap();
+        if let Some((measured_at, duration)) = *slot_time {
+            if measured_at.elapsed() < SLOT_TIME_TTL {
+                return duration;
+            }
+        }
+        let duration = match self.client.get_recent_performance_samples(Some(5)) {
+            Ok(samples) => {
+                let slots: u64 = samples.iter().map(|sample| sample.num_slots).sum();
+                let secs: u64 = samples.iter().map(|sample| sample.sample_period_secs as u64).sum();
+                match slots {
+                    0 => Duration::from_millis(DEFAULT_MS_PER_SLOT),
+                    slots => Duration::from_secs(secs) / slots as u32,
+                }
+            }
+            Err(e) => {
+                warn!(target: &self.log_target, "Unable to fetch performance samples: {}", e);
+                Duration::from_millis(DEFAULT_MS_PER_SLOT)
+            }
+        };
+        *slot_time = Some((Instant::now(), duration));
+        duration
+    }
+
+    // With prefetching, how long to wait before requesting the block of `slot`
+    // while the tip is at `tip`. `None` when it is not worth prefetching, so the
+    // listener waits to see the tip reach the slot.
+    fn prefetch_wait(&self, slot: Slot, tip: Slot) -> Option<Duration> {
+        let lead = Duration::from_millis(self.config.prefetch_lead_ms?);
+        let slot_time = self.slot_time();
+        let expected_in = slot_time * slot.saturating_sub(tip) as u32;
+        (expected_in <= lead + slot_time).then(|| expected_in.saturating_sub(lead))
+    }
+
+    fn stake_confirmation(&self, slot: Slot) -> Option<stake::StakeConfirmation> {
+        match self.client.get_vote_accounts_with_commitment(CommitmentConfig::finalized()) {
+            Ok(status) => {
+                let confirmation = stake::confirmation(&status, slot);
+                if !confirmation.is_supermajority() {
+                    warn!(target: &self.log_target, 
+                        "Block {} rooted by only {} of {} lamports of stake",
+                        slot, confirmation.rooted_stake, confirmation.total_stake
+                    );
+                }
+                Some(confirmation)
+            }
+            Err(e) => {
+                error!(target: &self.log_target, "Unable to fetch vote accounts for block {}: {}", slot, e);
+                None
+            }
+        }
+    }
+
+    fn token_supply(&self, config: &TokenSupplyConfig, slot: Slot) -> Option<TokenSupply> {
+        match supply::fetch(&self.client, config, slot) {
+            Ok(token_supply) => Some(token_supply),
+            Err(e) => {
+                error!(target: &self.log_target, "Unable to fetch token supply for block {}: {}", slot, e);
+                None
+            }
+        }
+    }
+
+    fn account_states(&self, watched: &[String], slot: Slot) -> Option<AccountStates> {
+        match accounts::fetch(&self.client, watched, slot) {
+            Ok(states) => Some(states),
+            Err(e) => {
+                error!(target: &self.log_target, "Unable to fetch watched accounts for block {}: {}", slot, e);
+                None
+            }
+        }
+    }
+
+    // Whether the schedule lets `slot` be proved now, going by how far behind
+    // the last tip it is. Logs when proving is held back and when it resumes.
+    fn in_window(&self, slot: Slot) -> bool {
+        let Some(schedule_config) = self.settings().schedule.clone() else {
+            return true;
+        };
+        let tip = self.last_tip.load(Ordering::SeqCst);
+        let open = schedule::allows(&schedule_config, slot, tip);
+        if self.outside_window.swap(!open, Ordering::SeqCst) == open {
+            match open {
+                true => info!(target: &self.log_target, "Proving window open, resuming at slot {}", slot),
+                false => info!(
+                    target: &self.log_target,
+                    "Holding back slot {} ({} slots behind the tip) until a {} window opens",
+                    slot,
+                    tip.saturating_sub(slot),
+                    match schedule::is_backfill(&schedule_config, slot, tip) {
+                        true => "backfill",
+                        false => "real-time",
+                    }
+                ),
+            }
+        }
+        open
+    }
+
+    // Writes the summary of every epoch that ended between `previous_slot` and `slot`
+    async fn summarize_epochs(&self, previous_slot: Slot, slot: Slot) {
+        let (Some(schedule), Some(prover)) = (&self.epoch_schedule, &self.prover) else {
+            return;
+        };
+        let params = prover.keys().current();
+
+        for epoch in schedule.get_epoch(previous_slot)..schedule.get_epoch(slot) {
+            if self.proofs_dir().join(epoch::summary_file_name(epoch)).exists() {
+                continue;
+            }
+            if let Some(summary) =
+                epoch::summarize(epoch, schedule, self.proofs_dir(), &params.block, self.settings().max_block_memory)
+            {
+                epoch::publish(&summary, self.proofs_dir(), self.storage().as_deref()).await;
+            }
+        }
+    }
+
+    // Rereads the config file if a reload was requested. Nothing is applied
+    // unless the whole file loads, including its storage sink and log level.
+    fn reload_config(&self) {
+        if !self.control.take_reload_request(&self.seen_reloads) {
+            return;
+        }
+        let Some(path) = &self.config_path else {
+            warn!(
+                target: &self.log_target,
+                "Not reloading the configuration: the listener was started without a config file"
+            );
+            return;
+        };
+        let mut settings = match Config::try_load(path).map(|loaded| match &self.instance {
+            Some(name) if loaded.profiles.is_empty() => loaded.instance(name),
+            Some(name) => loaded.profile(name),
+            None => Some(loaded),
+        }) {
+            Ok(Some(settings)) => settings,
+            Ok(None) => {
+                error!(
+                    target: &self.log_target,
+                    "Instance or profile is no longer in {:?}, keeping the current configuration", path
+                );
+                return;
+            }
+            Err(e) => {
+                error!(
+                    target: &self.log_target,
+                    "Unable to reload {:?}, keeping the current configuration: {}", path, e
+                );
+                return;
+            }
+        };
+        let Ok(log_level) = settings.log_level.parse::<LevelFilter>() else {
+            error!(
+                target: &self.log_target,
+                "Invalid log level {:?}, keeping the current configuration", settings.log_level
+            );
+            return;
+        };
+
+        if self.dry_run {
+            settings.proofs_dir = self.config.proofs_dir.clone();
+            settings.storage = None;
+        }
+
+        let current = self.settings();
+        if settings.storage != current.storage {
+            let genesis_hash = self.manifest.genesis_hash.as_deref();
+            let storage = settings
+                .storage
+                .as_ref()
+                .map(|storage_config| ObjectStorage::from_config(storage_config, genesis_hash));
+            let storage = match storage.transpose() {
+                Ok(storage) => storage.map(Arc::new),
+                Err(e) => {
+                    error!(
+                        target: &self.log_target,
+                        "Unable to configure object storage, keeping the current configuration: {}", e
+                    );
+                    return;
+                }
+            };
+            if self.config.storage.is_none() && storage.is_some() {
+                warn!(
+                    target: &self.log_target,
+                    "Block proofs are uploaded to the new object storage from the next restart"
+                );
+            }
+            *self.storage.write().unwrap() = storage;
+        }
+        if settings.rpc_url() != self.config.rpc_url() || settings.proofs_dir != self.config.proofs_dir {
+            warn!(target: &self.log_target, "RPC endpoint and proofs directory changes only take effect on restart");
+        }
+        log::set_max_level(log_level);
+        *self.settings.write().unwrap() = Arc::new(settings);
+        info!(target: &self.log_target, "Reloaded configuration from {:?}", path);
+    }
+
+    // Swaps in new proving parameters if a reload was requested. Proofs made
+    // with the previous key stay verifiable through the keyring.
+    fn reload_keys(&self) {
+        let Some(params) = self.prover.as_ref().map(ProverPool::keys).filter(|keys| keys.take_reload_request()) else {
+            return;
+        };
+        match params.reload(&self.settings()) {
+            Ok(fingerprint) => info!(
+                target: &self.log_target,
+                "Reloaded proving parameters, now proving with key {}", fingerprint
+            ),
+            Err(e) => error!(
+                target: &self.log_target,
+                "Unable to reload proving parameters, keeping the current key: {}", e
+            ),
+        }
+    }
+
+    // Records a slot that could not be proved in the index, and alerts about it
+    fn record_failure(&self, slot: Slot, status: SlotStatus, reason: String) {
+        if let Some(alerts) = &self.alerts {
+            let message = format!("slot {} recorded as {:?}: {}", slot, status, reason);
+            alerts.alert(self.instance.as_deref(), AlertKind::SlotFailed, message);
+        }
+        self.metrics.record_failed_slot(self.instance.as_deref());
+        index::append(self.proofs_dir(), slot, status, Some(reason));
+    }
+
+    // Records a slot the endpoint reported as skipped, so it is not mistaken
+    // for one the listener failed to prove
+    fn record_skip(&self, slot: Slot, reason: String) {
+        let reason = reason.trim_end().to_string();
+        info!(target: &self.log_target, "Slot {} has no block: {}", slot, reason);
+        self.metrics.record_skipped_slot(self.instance.as_deref());
+        index::append(self.proofs_dir(), slot, SlotStatus::Skipped, Some(reason));
+    }
+
+    // Fetches the block of `slot`, from the archive if the ledger no longer has
+    // it, and checks it against the cross-check endpoint. A block another
+    // profile has already fetched and checked is taken from it instead.
+    fn fetch_slot(&self, slot: Slot) -> Fetched {
+        if let Some(block) = self.blocks.as_ref().and_then(|blocks| blocks.take(slot)) {
+            return Fetched::Block(Box::new(block));
+        }
+        // Requested ahead of the tip, e.g. when prefetching
+        let pending = |e: &ClientError| e.to_string().contains("not available for slot");
+        let fetched = self.block_client.get_block_with_config(slot, block_config()).map(EncodedConfirmedBlock::from);
+        let fetched = match (fetched, &self.archive_client) {
+            (Err(e), Some(archive)) if is_purged(&e) => {
+                info!(target: &self.log_target, "Block {} was purged from the ledger, using the archive", slot);
+                match archive.get_block_with_config(slot, block_config()).map(EncodedConfirmedBlock::from) {
+                    // The archive has every produced block, so the slot was skipped
+                    Err(e) if e.to_string().contains("was skipped") => {
+                        self.record_skip(slot, e.to_string());
+                        return Fetched::Skipped;
+                    }
+                    fetched => fetched,
+                }
+            }
+            (fetched, _) => fetched,
+        };
+        match fetched {
+            Ok(block) => {
+                info!(target: &self.log_target, "New block created! Slot: {}, Block hash: {}", slot, block.blockhash);
+
+                if let Some(client) = &self.cross_check_client {
+                    match self.cross_check(client, slot, &block) {
+                        CrossCheck::Agree => {}
+                        CrossCheck::Disagree(reason) => {
+                            warn!(target: &self.log_target, "Refusing to prove block {}: {}", slot, reason);
+                            index::append(self.proofs_dir(), slot, SlotStatus::Flagged, Some(reason));
+                            return Fetched::Skipped;
+                        }
+                        CrossCheck::TimedOut => {
+                            warn!(target: &self.log_target, "Timed out cross-checking block {}, retrying", slot);
+                            return Fetched::Retry;
+                        }
+                    }
+                }
+
+                if let Some(blocks) = &self.blocks {
+                    blocks.insert(slot, &block);
+                }
+                Fetched::Block(Box::new(block))
+            }
+            Err(e) if is_timeout(&e) => {
+                warn!(target: &self.log_target, "Timed out fetching block {}, retrying", slot);
+                Fetched::Retry
+            }
+            Err(e) if pending(&e) => Fetched::Pending,
+            Err(e) => {
+                let error_message = e.to_string();
+                if let Some(first_available_block) = first_available_block(&error_message) {
+                    info!(target: &self.log_target, "Adjusting to first available block: {}", first_available_block);
+                    return Fetched::JumpTo(first_available_block.max(slot + 1));
+                } else if error_message.contains("was skipped") {
+                    self.record_skip(slot, error_message);
+                } else {
+                    error!(target: &self.log_target, "Error fetching block {}: {:?}", slot, e);
+                    self.record_failure(slot, SlotStatus::FetchFailed, error_message);
+                }
+                Fetched::Skipped
+            }
+        }
+    }
+
+    // Builds the witness of the fetched block of `slot`, chained onto `old_root`,
+    // unless the block is filtered out or its witness cannot be built
+    fn prepare_slot(&self, slot: Slot, block: EncodedConfirmedBlock, old_root: Fr) -> Prepared {
+        self.reload_config();
+        self.reload_keys();
+        let config = self.settings();
+        self.check_signatures(slot, &block);
+
+        let oversized = config.max_txs_per_block.filter(|max| block.transactions.len() > *max);
+        if let Some(max_txs_per_block) = oversized.filter(|_| config.oversized_blocks == OversizedPolicy::Skip) {
+            let reason = format!("{} transactions, over the limit of {}", block.transactions.len(), max_txs_per_block);
+            info!(target: &self.log_target, "Skipping block {}: {}", slot, reason);
+            index::append(self.proofs_dir(), slot, SlotStatus::Oversized, Some(reason));
+            return Prepared::Done(Done::Empty);
+        }
+        if let Some(stake_config) = config.stake_activity.as_ref().filter(|config| config.only_matching) {
+            if !stake_activity::by_any(&stake_activity::activity(&block), &stake_config.authorities) {
+                let reason = "no stake activity by the watched authorities".to_string();
+                info!(target: &self.log_target, "Skipping block {}: {}", slot, reason);
+                index::append(self.proofs_dir(), slot, SlotStatus::Empty, Some(reason));
+                return Prepared::Done(Done::Empty);
+            }
+        }
+        if let Some(vote_config) = config.votes.as_ref().filter(|config| config.only_matching) {
+            if votes::votes(vote_config, &block).is_empty() {
+                let reason = "no votes by the watched validators".to_string();
+                info!(target: &self.log_target, "Skipping block {}: {}", slot, reason);
+                index::append(self.proofs_dir(), slot, SlotStatus::Empty, Some(reason));
+                return Prepared::Done(Done::Empty);
+            }
+        }
+        if let Some(filter) = config.filter.as_ref().filter(|filter| filter.skip_empty) {
+            if filter::matching_transactions(filter, &block) == 0 {
+                info!(target: &self.log_target, "Skipping block {}: no transactions match the filters", slot);
+                index::append(self.proofs_dir(), slot, SlotStatus::Empty, None);
+                return Prepared::Done(Done::Empty);
+            }
+        }
+
+        let leader = self.slot_leader(slot).map(|leader| leader.to_string());
+        if config.bind_leader && leader.is_none() {
+            warn!(target: &self.log_target, "Leader of block {} unknown, proving without binding it", slot);
+        }
+        let token_supply = config.token_supply.as_ref().and_then(|supply_config| {
+            let token_supply = self.token_supply(supply_config, slot);
+            if supply_config.bind && token_supply.is_none() {
+                warn!(target: &self.log_target, "Token supply at block {} unknown, proving without binding it", slot);
+            }
+            token_supply
+        });
+
+        let anchor_records = self.anchor.as_ref().map(|anchor| anchor.decode_block(&block)).unwrap_or_default();
+        let block_time = block.block_time;
+        if config.block_snapshots {
+            raw_blocks::save(self.proofs_dir(), slot, &block);
+        }
+
+        match build_block_witness(slot, block, leader, token_supply, old_root, &config) {
+            Ok(mut exported) => {
+                for (signature, records) in anchor_records {
+                    exported.annotations.entry(signature).or_default().anchor = records;
+                }
+                exported.genesis_hash = self.manifest.genesis_hash.clone();
+                exported.clock_drift = latency::drift(block_time);
+                if let (Some(clock_drift), Some(max_drift)) = (&exported.clock_drift, config.max_clock_drift_secs) {
+                    if clock_drift.drift_secs.unsigned_abs() > max_drift {
+                        warn!(
+                            target: &self.log_target,
+                            "Block {} timestamp is {}s from the local clock, over the {}s drift threshold",
+                            slot, clock_drift.drift_secs, max_drift
+                        );
+                    }
+                }
+                if config.stake_evidence {
+                    exported.confirmation = self.stake_confirmation(slot);
+                }
+                if let Some(account_state) = &config.account_state {
+                    exported.account_states = self.account_states(&account_state.accounts, slot);
+                }
+                let new_root = exported.witness.top_level().new_root();
+                if self.prover.is_some() && self.already_proved(slot, &exported, old_root) {
+                    info!(target: &self.log_target, "Block {} was already proved from the same witness", slot);
+                    return Prepared::Done(Done::Duplicate(new_root));
+                }
+                // Published before, but its proof file is gone or chained onto another root
+                let witness_hash = witness::witness_hash(&exported.block_hash, &exported.signatures);
+                if self.prover.is_some() && self.nullifiers.lock().unwrap().contains(&(slot, witness_hash)) {
+                    warn!(
+                        target: &self.log_target,
+                        "Block {} was already published from the same witness, not publishing it again", slot
+                    );
+                    self.metrics.record_nullifier_conflict(self.instance.as_deref());
+                    return Prepared::Done(Done::Duplicate(new_root));
+                }
+                if config.witness_archive {
+                    witness_archive::save(self.proofs_dir(), &exported);
+                }
+                Prepared::Job(Box::new(Job { exported, new_root, block_time, config }))
+            }
+            Err(e) => {
+                warn!(target: &self.log_target, "Skipping block {}: {}", slot, e);
+                self.record_failure(slot, SlotStatus::Failed, e.to_string());
+                Prepared::Done(Done::Skipped)
+            }
+        }
+    }
+
+    // Logs, records and alerts about every signature of the block of `slot`
+    // already seen in another recent block, or twice in this one. The block is
+    // still proved: the anomaly is in the data source or the listener, and the
+    // proofs show which blocks carried the signature.
+    fn check_signatures(&self, slot: Slot, block: &EncodedConfirmedBlock) {
+        let Some(signatures) = &self.signatures else {
+            return;
+        };
+        let anomalies = signatures.lock().unwrap().check(slot, block_signatures(block));
+        for anomaly in anomalies {
+            let slots = anomaly.slots.iter().map(Slot::to_string).collect::<Vec<_>>().join(", ");
+            error!(
+                target: &self.log_target,
+                "Signature {} appears in more than one block: slots {}", anomaly.signature, slots
+            );
+            self.metrics.record_duplicate_signature(self.instance.as_deref());
+            if let Some(alerts) = &self.alerts {
+                let message = format!("signature {} appears in blocks of slots {}", anomaly.signature, slots);
+                alerts.alert(self.instance.as_deref(), AlertKind::DuplicateSignature, message);
+            }
+            anomaly::append(self.proofs_dir(), &anomaly);
+        }
+    }
+
+    // Whether the proof file of `slot` was made from the same witness, chained
+    // onto the same root, e.g. by a run stopped before the checkpoint moved on
+    fn already_proved(&self, slot: Slot, exported: &ExportedWitness, old_root: Fr) -> bool {
+        let path = self.proofs_dir().join(proof_file_name(slot));
+        if !path.exists() {
+            return false;
+        }
+        let existing = load_proof(&path);
+        let witness_hash = witness::witness_hash(&exported.block_hash, &exported.signatures);
+        existing.witness_hash == Some(witness_hash) && existing.old_root == fr_to_hex(&old_root)
+    }
+
+    // Proves the witness of a job and signs the proof; in witness-only mode
+    // the witness is passed on as is
+    async fn prove_job(&self, job: Job) -> Done {
+        let Job { exported, new_root, block_time, config } = job;
+        if self.dry_run {
+            return Done::Record(Box::new(dry_run::record(&exported)), new_root);
+        }
+        let Some(prover) = &self.prover else {
+            return Done::Witness(Box::new(exported), new_root);
+        };
+        let slot = exported.slot;
+
+        let limit = config.proving_timeout_secs.map(Duration::from_secs);
+        let mut block_proof = match prover.prove(exported, self.proofs_dir(), limit).await {
+            Ok(block_proof) => block_proof,
+            Err(ProveError::TimedOut(limit)) => return Done::TimedOut(limit),
+            Err(ProveError::Rejected(e)) => return Done::Rejected(e.to_string()),
+        };
+        block_proof.latency = latency::measure(block_time, config.latency_slo_secs);
+        let late = block_proof.latency.as_ref().filter(|latency| latency.slo_violated);
+        if let Some(latency) = late {
+            warn!(
+                target: &self.log_target,
+                "Block {} proved {}s after its timestamp, over the {}s latency SLO",
+                slot, latency.latency_secs, latency.slo_secs
+            );
+        }
+        self.metrics.record_proof(self.instance.as_deref(), slot, block_proof.latency.as_ref());
+        disclosure::withhold(&mut block_proof, config.private_dir.as_deref());
+        if let Some(keypair) = &self.keypair {
+            cosign::sign(&mut block_proof, keypair);
+        }
+        Done::Proof(Box::new(block_proof), new_root)
+    }
+
+    // Publishes the result of `slot` and records it in the slot index
+    async fn write_done(&self, slot: Slot, done: Done) -> SlotOutcome {
+        let new_root = match done {
+            Done::Proof(block_proof, new_root) => {
+                publish_proof(&block_proof, self.proofs_dir());
+                let proof_path = self.proofs_dir().join(proof_file_name(slot));
+                let bytes = fs::metadata(proof_path).map(|metadata| metadata.len()).unwrap_or_default();
+                self.metrics.record_proof_file(self.instance.as_deref(), bytes);
+                if let Some(witness_hash) = &block_proof.witness_hash {
+                    self.nullifiers.lock().unwrap().insert((slot, witness_hash.clone()));
+                }
+                if let Some(gossip) = &self.gossip {
+                    gossip.broadcast(&block_proof);
+                }
+                if let Some(ethereum) = &self.ethereum {
+                    ethereum.record(&block_proof);
+                }
+                self.sinks.publish(&block_proof);
+                new_root
+            }
+            Done::Witness(exported, new_root) => {
+                save_witness_to_json(&exported, self.proofs_dir());
+                new_root
+            }
+            Done::Record(record, new_root) => {
+                dry_run::append(self.proofs_dir(), &record);
+                new_root
+            }
+            Done::Duplicate(new_root) => {
+                let reason = "already proved from the same witness".to_string();
+                index::append(self.proofs_dir(), slot, SlotStatus::Proved, Some(reason));
+                return SlotOutcome::Proved(new_root);
+            }
+            Done::Rejected(reason) => {
+                error!(
+                    target: &self.log_target,
+                    "Proof of block {} does not verify, not publishing it: {}", slot, reason
+                );
+                if let Some(alerts) = &self.alerts {
+                    let message = format!("proof of slot {} does not verify: {}", slot, reason);
+                    alerts.alert(self.instance.as_deref(), AlertKind::VerificationFailed, message);
+                }
+                let reason = format!("proof does not verify: {}", reason);
+                self.metrics.record_failed_slot(self.instance.as_deref());
+                index::append(self.proofs_dir(), slot, SlotStatus::Failed, Some(reason));
+                return SlotOutcome::Rejected;
+            }
+            Done::TimedOut(limit) => {
+                let reason = ProveError::TimedOut(limit).to_string();
+                error!(target: &self.log_target, "Cancelled the proof of block {}: {}", slot, reason);
+                self.record_failure(slot, SlotStatus::TimedOut, reason);
+                return SlotOutcome::Rejected;
+            }
+            Done::Empty => return SlotOutcome::Empty,
+            Done::Skipped => return SlotOutcome::Skipped,
+        };
+        index::append(self.proofs_dir(), slot, SlotStatus::Proved, None);
+        SlotOutcome::Proved(new_root)
+    }
+
+    async fn process_slot(&self, slot: Slot, old_root: Fr) -> SlotOutcome {
+        let block = match self.fetch_slot(slot) {
+            Fetched::Block(block) => block,
+            Fetched::Skipped => return SlotOutcome::Skipped,
+            Fetched::JumpTo(next_slot) => return SlotOutcome::JumpTo(next_slot),
+            Fetched::Retry | Fetched::Pending => return SlotOutcome::Retry,
+        };
+        let done = match self.prepare_slot(slot, *block, old_root) {
+            Prepared::Job(job) => self.prove_job(*job).await,
+            Prepared::Done(done) => done,
+        };
+        self.write_done(slot, done).await
+    }
+
+    // Runs `start()` until it returns, starting it again whenever it panics.
+    // With a pipeline, the pipeline is also restarted from the checkpoint,
+    // since the work the task had in flight is lost.
+    async fn supervise<F, Fut>(&self, task: &'static str, pipeline: Option<&Pipeline>, mut start: F)
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = ()>,
+    {
+        while let Err(panic) = AssertUnwindSafe(start()).catch_unwind().await {
+            let message = panic
+                .downcast_ref::<&str>()
+                .copied()
+                .or_else(|| panic.downcast_ref::<String>().map(String::as_str))
+                .unwrap_or("unknown panic");
+            error!(target: &self.log_target, "The {} task panicked, restarting it: {}", task, message);
+            self.metrics.record_restart(self.instance.as_deref(), task);
+            sleep(Duration::from_secs(1)).await;
+            if let Some(pipeline) = pipeline {
+                self.restart_pipeline(pipeline);
+            }
+        }
+    }
+
+    // Runs until the process is stopped, sharded if coordination is configured
+    pub async fn start(&self) {
+        match &self.config.coordination {
+            Some(coordination) => self.supervise("shard_worker", None, || self.run_sharded(coordination)).await,
+            None => self.run().await,
+        }
+    }
+
+    // Follows the chain from the checkpoint, proving every new block in order.
+    // A fetcher fills the block queue, `prover_threads` provers build and prove
+    // the witnesses, and a writer publishes the proofs in slot order and moves
+    // the checkpoint on. Each of them is restarted if it panics.
+    pub async fn run(&self) {
+        let provers = self.config.prover_threads.max(1);
+        let (tickets, receiver) = mpsc::channel(provers);
+        let pipeline = Pipeline {
+            queue: BlockQueue::new(&self.config.queue, self.proofs_dir()),
+            following: AtomicBool::new(self.config.election.is_none()),
+            origin: Mutex::new((0, Fr::ZERO)),
+            root: tokio::sync::Mutex::new((0, Fr::ZERO)),
+            tickets,
+            receiver: tokio::sync::Mutex::new(receiver),
+            seen_blocks: Mutex::new(HashSet::new()),
+        };
+        self.restart_pipeline(&pipeline);
+
+        tokio::join!(
+            self.supervise("fetcher", None, || self.fetch_ahead(&pipeline.queue)),
+            futures::future::join_all(
+                (0..provers).map(|_| self.supervise("prover", Some(&pipeline), || self.prove_queued(&pipeline)))
+            ),
+            self.supervise("writer", Some(&pipeline), || self.write_queued(&pipeline)),
+            self.follow_leadership(&pipeline),
+        );
+    }
+
+    // Drops the work in flight and, while following the chain, fetches again
+    // from the checkpoint. Returns the checkpoint's slot.
+    fn restart_pipeline(&self, pipeline: &Pipeline) -> Slot {
+        let checkpoint = Checkpoint::load(self.proofs_dir());
+        let following = pipeline.following.load(Ordering::SeqCst);
+        let mut origin = pipeline.origin.lock().unwrap();
+        let generation = pipeline.queue.restart(following.then_some(checkpoint.last_slot + 1));
+        *origin = (generation, checkpoint.root());
+        checkpoint.last_slot
+    }
+
+    // With leader election configured, only follows the chain while this
+    // instance leads
+    async fn follow_leadership(&self, pipeline: &Pipeline) {
+        let Some(election) = &self.config.election else {
+            return;
+        };
+        let leading = LeaderElection::new(election).spawn();
+
+        loop {
+            let is_leader = leading.load(Ordering::SeqCst);
+            if is_leader != pipeline.following.swap(is_leader, Ordering::SeqCst) {
+                // The previous leader may have moved the checkpoint on
+                let last_slot = self.restart_pipeline(pipeline);
+                match is_leader {
+                    true => info!(target: &self.log_target, "Became leader, resuming after slot {}", last_slot),
+                    false => warn!(target: &self.log_target, "Lost leadership, standing by"),
+                }
+            }
+            sleep(Duration::from_secs(1)).await;
+        }
+    }
+
+    // Fetches every sampled slot up to the tip into `queue`, from wherever the
+    // pipeline last restarted it
+    async fn fetch_ahead(&self, queue: &BlockQueue) {
+        let mut tip: Slot = 0;
+
+        loop {
+            let Some((generation, slot)) = queue.cursor().filter(|_| !self.control.is_paused()) else {
+                sleep(Duration::from_secs(1)).await;
+                continue;
+            };
+            if slot > tip {
+                match self.tip() {
+                    Some(current_slot) if current_slot >= slot => tip = current_slot,
+                    Some(current_slot) if let Some(wait) = self.prefetch_wait(slot, current_slot) => sleep(wait).await,
+                    Some(_) => {
+                        match &self.roots {
+                            Some(roots) => roots.wait_for_root(Duration::from_secs(1)).await,
+                            None => sleep(Duration::from_secs(1)).await, // Adjust the delay as needed
+                        }
+                        continue;
+                    }
+                    None => {
+                        sleep(Duration::from_secs(1)).await;
+                        continue;
+                    }
+                }
+            }
+            match self.is_selected(slot) {
+                Some(true) => {}
+                Some(false) => {
+                    queue.push(generation, slot, None, slot + 1);
+                    continue;
+                }
+                // Decided again on the next pass
+                None => {
+                    sleep(Duration::from_secs(1)).await;
+                    continue;
+                }
+            }
+
+            match self.fetch_slot(slot) {
+                Fetched::Block(block) => queue.push(generation, slot, Some(*block), slot + 1),
+                Fetched::Skipped => queue.push(generation, slot, None, slot + 1),
+                Fetched::JumpTo(next_slot) => queue.push(generation, slot, None, next_slot),
+                // Fetched again on the next pass
+                Fetched::Retry => sleep(Duration::from_secs(1)).await,
+                Fetched::Pending => sleep(self.slot_time() / 4).await,
+            }
+            self.metrics.record_queue_depth(self.instance.as_deref(), queue.depth());
+        }
+    }
+
+    // Takes blocks off the queue one at a time, chaining each witness onto the
+    // previous one, and proves them concurrently with the other provers
+    async fn prove_queued(&self, pipeline: &Pipeline) {
+        loop {
+            if self.control.is_paused() {
+                sleep(Duration::from_secs(1)).await;
+                continue;
+            }
+
+            let mut root = pipeline.root.lock().await;
+            let Some(QueuedBlock { generation, slot, block }) = pipeline.queue.pop(Duration::from_secs(1)).await else {
+                continue;
+            };
+            self.metrics.record_queue_depth(self.instance.as_deref(), pipeline.queue.depth());
+            if root.0 != generation {
+                // The first block since a restart chains onto the checkpoint
+                let origin = *pipeline.origin.lock().unwrap();
+                if origin.0 != generation {
+                    // Fetched before the last restart
+                    continue;
+                }
+                *root = origin;
+            }
+            if pipeline.seen_blocks.lock().unwrap().contains(&slot) {
+                continue;
+            }
+            // Later blocks chain onto this one, so they wait along with it
+            while !self.in_window(slot) {
+                sleep(Duration::from_secs(1)).await;
+            }
+
+            let (sender, done) = oneshot::channel();
+            let prepared = self.prepare_slot(slot, block, root.1);
+            pipeline.tickets.send(Ticket { generation, slot, done }).await.expect("Writer queue closed");
+            let job = match prepared {
+                Prepared::Job(job) => job,
+                Prepared::Done(done) => {
+                    let _ = sender.send(done);
+                    continue;
+                }
+            };
+            root.1 = job.new_root;
+            drop(root);
+
+            let _ = sender.send(self.prove_job(*job).await);
+        }
+    }
+
+    // Records the results of the provers in slot order, moving the checkpoint on
+    async fn write_queued(&self, pipeline: &Pipeline) {
+        let mut receiver = pipeline.receiver.lock().await;
+        let mut generation = pipeline.queue.generation();
+        let mut checkpoint = Checkpoint::load(self.proofs_dir());
+
+        loop {
+            self.beat();
+            if self.control.take_flush_request(&self.seen_flushes) {
+                checkpoint.save(self.proofs_dir());
+                info!(target: &self.log_target, "Flushed checkpoint at slot {}", checkpoint.last_slot);
+            }
+            if let Some(alerts) = &self.alerts {
+                if pipeline.following.load(Ordering::SeqCst) && !self.control.is_paused() {
+                    let tip = self.last_tip.load(Ordering::SeqCst);
+                    alerts.check_lag(self.instance.as_deref(), checkpoint.last_slot, tip);
+                }
+            }
+            let Ok(Some(ticket)) = timeout(Duration::from_secs(1), receiver.recv()).await else {
+                continue;
+            };
+            let Ok(done) = ticket.done.await else {
+                // Its prover panicked. Later slots must not be recorded before it.
+                if ticket.generation == pipeline.queue.generation() {
+                    self.restart_pipeline(pipeline);
+                }
+                continue;
+            };
+            // Work from before a restart of the pipeline, which resumes from the checkpoint
+            let current = pipeline.queue.generation();
+            if ticket.generation != current {
+                continue;
+            }
+            if generation != current {
+                checkpoint = Checkpoint::load(self.proofs_dir());
+                generation = current;
+            }
+
+            let slot = ticket.slot;
+            match self.write_done(slot, done).await {
+                SlotOutcome::Proved(new_root) => {
+                    pipeline.seen_blocks.lock().unwrap().insert(slot);
+                    self.summarize_epochs(checkpoint.last_slot, slot).await;
+                    checkpoint.advance(slot, fr_to_hex(&new_root));
+                    checkpoint.save(self.proofs_dir());
+                }
+                // Recorded in the index, so the checkpoint can move past it
+                SlotOutcome::Empty => {
+                    pipeline.seen_blocks.lock().unwrap().insert(slot);
+                    self.summarize_epochs(checkpoint.last_slot, slot).await;
+                    let root = checkpoint.accumulator_root.clone();
+                    checkpoint.advance(slot, root);
+                    checkpoint.save(self.proofs_dir());
+                }
+                // Recorded as failed. Witnesses chained onto its root are built
+                // again from the checkpoint.
+                SlotOutcome::Rejected => {
+                    pipeline.seen_blocks.lock().unwrap().insert(slot);
+                    let root = checkpoint.accumulator_root.clone();
+                    checkpoint.advance(slot, root);
+                    checkpoint.save(self.proofs_dir());
+                    self.restart_pipeline(pipeline);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    // Proves slot ranges claimed from a lease table shared with other workers.
+    // Each range is chained onto its own accumulator starting from zero, and
+    // the final root is recorded in the completed lease.
+    pub async fn run_sharded(&self, coordination: &CoordinationConfig) {
+        let table = LeaseTable::new(coordination);
+
+        loop {
+            self.beat();
+            let Some(tip) = self.tip() else {
+                sleep(Duration::from_secs(1)).await;
+                continue;
+            };
+            let lease = match table.claim(tip) {
+                Ok(Some(lease)) => lease,
+                Ok(None) => {
+                    sleep(Duration::from_secs(1)).await;
+                    continue;
+                }
+                Err(e) => {
+                    error!(target: &self.log_target, "Error claiming a slot range: {:?}", e);
+                    sleep(Duration::from_secs(1)).await;
+                    continue;
+                }
+            };
+            info!(target: &self.log_target, "Claimed slots {}..{}", lease.start_slot, lease.end_slot);
+
+            let mut root = Fr::ZERO;
+            let mut slot = lease.start_slot;
+            let mut lost = false;
+
+            while slot < lease.end_slot {
+                self.beat();
+                // Wait for the chain to reach the slot, for the listener to be
+                // resumed, or for a window of the schedule to open
+                while self.control.is_paused() || self.tip().is_none_or(|tip| tip < slot) || !self.in_window(slot) {
+                    sleep(Duration::from_secs(1)).await;
+                    self.beat();
+                    if let Err(e) = table.renew(&lease) {
+                        warn!(
+                            target: &self.log_target,
+                            "Lost lease on slots {}..{}: {:?}", lease.start_slot, lease.end_slot, e
+                        );
+                        lost = true;
+                        break;
+                    }
+                }
+                if lost {
+                    break;
+                }
+                match self.is_selected(slot) {
+                    Some(true) => {}
+                    Some(false) => {
+                        slot += 1;
+                        continue;
+                    }
+                    None => {
+                        sleep(Duration::from_secs(1)).await;
+                        continue;
+                    }
+                }
+
+                match self.process_slot(slot, root).await {
+                    SlotOutcome::Proved(new_root) => {
+                        root = new_root;
+                        slot += 1;
+                    }
+                    SlotOutcome::Skipped | SlotOutcome::Empty | SlotOutcome::Rejected => slot += 1,
+                    SlotOutcome::JumpTo(next_slot) => slot = next_slot,
+                    SlotOutcome::Retry => sleep(Duration::from_secs(1)).await,
+                }
+
+                if let Err(e) = table.renew(&lease) {
+                    warn!(
+                        target: &self.log_target,
+                        "Lost lease on slots {}..{}: {:?}", lease.start_slot, lease.end_slot, e
+                    );
+                    lost = true;
+                    break;
+                }
+            }
+
+            if !lost {
+                match table.complete(&lease, fr_to_hex(&root)) {
+                    Ok(()) => info!(
+                        target: &self.log_target,
+                        "Completed slots {}..{}", lease.start_slot, lease.end_slot
+                    ),
+                    Err(e) => error!(
+                        target: &self.log_target,
+                        "Error completing slots {}..{}: {:?}", lease.start_slot, lease.end_slot, e
+                    ),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_client::rpc_request::{RpcError, RpcResponseErrorData};
+
+    fn response_error(message: &str) -> String {
+        let error = RpcError::RpcResponseError {
+            code: -32001,
+            message: message.to_string(),
+            data: RpcResponseErrorData::Empty,
+        };
+        ClientError::from(error).to_string()
+    }
+
+    #[test]
+    fn reads_the_first_available_block() {
+        let cleaned_up = response_error("Block 5 cleaned up, does not exist on node. First available block: 1200");
+        assert_eq!(first_available_block(&cleaned_up), Some(1200));
+        assert_eq!(first_available_block("First available block: 1200"), Some(1200));
+        assert_eq!(first_available_block(&response_error("Block not available for slot 5")), None);
+        let skipped = response_error("Slot 5 was skipped, or missing due to ledger jump to recent snapshot");
+        assert_eq!(first_available_block(&skipped), None);
+    }
+}
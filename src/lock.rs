@@ -0,0 +1,68 @@
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Seek, Write};
+use std::os::fd::AsRawFd;
+use std::path::{Path, PathBuf};
+
+const LOCK_FILE: &str = "listener.lock";
+
+pub enum LockError {
+    // With the process ID recorded by the holder, if it could be read
+    Held(PathBuf, Option<u32>),
+    Io(PathBuf, io::Error),
+}
+
+impl fmt::Display for LockError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LockError::Held(dir, Some(pid)) if *pid == std::process::id() => {
+                write!(f, "{:?} is used by another instance of this process", dir)
+            }
+            LockError::Held(dir, Some(pid)) => write!(f, "{:?} is in use by process {}", dir, pid),
+            LockError::Held(dir, None) => write!(f, "{:?} is in use by another process", dir),
+            LockError::Io(dir, e) => write!(f, "unable to lock {:?}: {}", dir, e),
+        }
+    }
+}
+
+// An exclusive lock on a proofs directory, so that only one listener at a
+// time writes its checkpoint, index and proofs. The lock file records the
+// holder's process ID. The kernel releases the lock when the process exits,
+// however it exits, so a stale lock file never blocks a restart.
+pub struct DirLock {
+    _file: File,
+}
+
+impl DirLock {
+    pub fn acquire(dir: &Path) -> Result<Self, LockError> {
+        let failed = |e| LockError::Io(dir.to_path_buf(), e);
+        fs::create_dir_all(dir).map_err(failed)?;
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(dir.join(LOCK_FILE))
+            .map_err(failed)?;
+        // SAFETY: the descriptor belongs to `file`, which outlives the call
+        if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) } != 0 {
+            let e = io::Error::last_os_error();
+            if e.kind() != io::ErrorKind::WouldBlock {
+                return Err(failed(e));
+            }
+            let mut holder = String::new();
+            let pid = file.read_to_string(&mut holder).ok().and_then(|_| holder.trim().parse().ok());
+            return Err(LockError::Held(dir.to_path_buf(), pid));
+        }
+        file.set_len(0).map_err(failed)?;
+        file.rewind().map_err(failed)?;
+        writeln!(file, "{}", std::process::id()).map_err(failed)?;
+        Ok(DirLock { _file: file })
+    }
+}
+
+// Writes the process ID to `path`, for service managers and scripts that
+// track the listener by its pid file
+pub fn write_pid_file(path: &Path) {
+    fs::write(path, format!("{}\n", std::process::id())).expect("Unable to write pid file");
+}
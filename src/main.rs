@@ -1,207 +1,2145 @@
-use bellman::{groth16, Circuit, ConstraintSystem, SynthesisError};
+mod absence;
+mod accounts;
+mod admin;
+mod amqp;
+mod api;
+mod alerts;
+mod anchor;
+mod anomaly;
+mod balance;
+mod block_cache;
+mod bloom;
+mod bundle;
+mod ceremony;
+mod check;
+mod checkpoint;
+mod circuit;
+mod cluster;
+mod config;
+mod cosign;
+mod costs;
+mod da;
+mod cpu;
+mod crosscheck;
+mod disclosure;
+mod dry_run;
+#[cfg(feature = "ed25519")]
+mod ed25519;
+mod election;
+mod epoch;
+mod ethereum;
+mod fees;
+mod field;
+mod gaps;
+mod filter;
+mod gossip;
+mod graphql;
+mod index;
+mod instructions;
+mod keyring;
+mod latency;
+mod lease;
+mod listener;
+mod lock;
+mod manifest;
+mod memo;
+mod memory;
+mod merkle;
+mod metrics;
+mod mqtt;
+mod nft;
+mod nullifier;
+mod params;
+mod plugin;
+mod programs;
+mod prover;
+mod queue;
+mod raw_blocks;
+mod ratelimit;
+mod redis_cache;
+mod schedule;
+mod serialization;
+mod signatures;
+mod sink;
+mod snapshot;
+mod stake;
+mod stake_activity;
+mod storage;
+mod subscription;
+mod supply;
+mod system;
+mod systemd;
+mod token;
+mod verify;
+mod volume;
+mod votes;
+mod witness;
+mod witness_archive;
+
+use bellman::groth16;
 use blstrs::{Bls12, Scalar as Fr};
-use ff::{Field, PrimeField};
-use rand::thread_rng;
+use accounts::AccountStateProof;
+use anchor::{AnchorDecoder, AnchorRecord};
+use balance::BalanceSummary;
+use bloom::BloomFilter;
+use clap::{CommandFactory, Parser, Subcommand, ValueEnum};
+use clap_complete::Shell;
+use config::{CommitmentHash, Config, OversizedPolicy};
+use cosign::ProofSignature;
+use fees::FeeStats;
+use ff::PrimeField;
+use field::{fr_from_hex, fr_to_hex, str_to_fr, Domain, HASH_DOMAINS};
+use futures::stream::{self, StreamExt};
+use keyring::Keyring;
+use latency::{ClockDrift, ProofLatency};
+use admin::Control;
+use alerts::Alerter;
+use block_cache::BlockCache;
+use lock::DirLock;
+use listener::{block_config, is_purged, rpc_client, Listener, Shared, LEADER_WINDOW};
+use log::{debug, error, info, warn};
+use manifest::Manifest;
+use memo::Memo;
+use metrics::Metrics;
+use merkle::{MerkleStep, MerkleTree};
+use nft::NftEvent;
 use serde::{Serialize, Deserialize};
-use sha2::{Digest, Sha256};
+use signatures::SignatureProof;
+use sink::{SharedStorage, Sinks};
+use solana_client::client_error::ClientError;
 use solana_client::rpc_client::RpcClient;
-use solana_sdk::clock::Slot;
-use solana_transaction_status::{EncodedTransaction};
-use std::collections::HashSet;
+use solana_sdk::clock::{Epoch, Slot};
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::{read_keypair_file, Keypair, Signature};
+use solana_transaction_status::{EncodedConfirmedBlock, Reward};
+use stake::StakeConfirmation;
+use stake_activity::StakeActivity;
+use systemd::Watchdog;
+use std::collections::{BTreeMap, BTreeSet, HashSet};
 use std::fs::{self, File};
 use std::io::Write;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+use storage::ObjectStorage;
+use supply::TokenSupply;
+use system::SolTransferSummary;
+use token::{MintProof, TokenTransfer};
 use tokio::time::{sleep, Duration};
+use params::{ProvingKeys, SharedKeys};
+use plugin::ExcludedTransaction;
+use prover::{Cancellation, Cancelled, ProveError, ProverPool};
+use programs::ProgramChanges;
+use volume::VolumeThresholdProof;
+use votes::BlockVotes;
+use witness::{BlockWitness, ExportedWitness, OversizedBlock, WitnessAccumulator, WitnessError};
+
+// Decoded contents attached to a transaction's entry in the proof
+#[derive(Serialize, Deserialize, Clone, Default)]
+struct TransactionAnnotations {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    anchor: Vec<AnchorRecord>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    memos: Vec<Memo>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    nft: Vec<NftEvent>,
+    // Fields attached by transaction processors, by processor name
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    custom: BTreeMap<String, serde_json::Value>,
+}
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Clone)]
 struct TransactionProof {
     transaction_hash: String,
+    // Leaf salt (hex) for blocks proved with selective disclosure
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    salt: Option<String>,
+    // Hash of the transaction's serialized message, for blocks proved with
+    // `messages_bound`; the leaf then commits to it as well as the signature
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    message_hash: Option<String>,
+    // Position of the transaction in the block and its path to `transactions_root`
+    leaf_index: usize,
+    merkle_path: Vec<MerkleStep>,
+    #[serde(flatten)]
+    annotations: TransactionAnnotations,
+}
+
+// Proof for one chunk of a block too large for a single circuit instance
+#[derive(Serialize, Deserialize, Clone)]
+struct ChunkProof {
+    index: usize,
+    commitment: String,
+    proof: String,
+}
+
+// Sum circuit proofs over a value attached to each of a block's transaction
+// leaves, one per chunk of the block; `total` is the sum of the chunk totals
+#[derive(Serialize, Deserialize, Clone)]
+struct SumProof {
+    total: u128,
+    chunks: Vec<SumChunkProof>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+struct SumChunkProof {
+    // Commitment over the chunk's leaves and their values
+    commitment: String,
+    total: u128,
     proof: String,
 }
 
-#[derive(Serialize, Deserialize)]
+// Totals circuit proofs over a block's transaction leaves, one per chunk:
+// the number of leaves and the total of a value attached to each. Leaves are
+// per signature, so proofs written as `tx_count` counted signatures too.
+#[derive(Serialize, Deserialize, Clone)]
+struct TotalsProof {
+    #[serde(alias = "tx_count")]
+    signature_count: u64,
+    total: u64,
+    chunks: Vec<TotalsChunkProof>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+struct TotalsChunkProof {
+    values_commitment: String,
+    #[serde(alias = "count")]
+    signature_count: u64,
+    total: u64,
+    proof: String,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
 struct BlockProof {
     slot: Slot,
     block_hash: String,
+    // Genesis hash of the cluster the block was produced on, so proofs of
+    // different networks cannot be mistaken for one another
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    genesis_hash: Option<String>,
+    // Hash of the block hash and transaction hashes the proof was made from,
+    // so a block is not proved again from the same witness
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    witness_hash: Option<String>,
+    // Validator identity scheduled to produce the slot. With `leader_bound` the
+    // circuit seed is derived from it as well as the block hash.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    leader: Option<String>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    leader_bound: bool,
+    // Every transaction leaf binds the message (accounts and instructions) as
+    // well as the signature, so the proof commits to transaction contents
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    messages_bound: bool,
+    // Version of the domain tags hashed into the seed and leaves; 0 for proofs
+    // made before hashes were tagged
+    #[serde(default)]
+    hash_domains: u8,
+    // Hash function of the Merkle roots below and of their inclusion paths
+    #[serde(default, skip_serializing_if = "CommitmentHash::is_sha256")]
+    commitment_hash: CommitmentHash,
+    // Supply of the configured mints at the block's slot or later; with
+    // `bound`, mixed into the circuit seed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    token_supply: Option<TokenSupply>,
+    // Stake that had voted on and rooted the block when it was proved
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    confirmation: Option<StakeConfirmation>,
+    // Watched accounts as read at or after the block's slot, with inclusion
+    // paths under their Merkle root
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    account_states: Option<AccountStateProof>,
+    // Set when the block had more transactions than `max_txs_per_block`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    oversized: Option<OversizedBlock>,
+    // Merkle root over the transaction hashes, for per-transaction inclusion
+    transactions_root: String,
+    // Merkle root over the transaction signatures sorted by their bytes, for
+    // proofs that a signature is absent from the block
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    sorted_root: String,
+    // Pre-screen for transaction lookups; may report false positives
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    bloom: Option<BloomFilter>,
+    // SPL token transfers, and commitments over those of the configured mints
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    token_transfers: Vec<TokenTransfer>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    mint_proofs: Vec<MintProof>,
+    // SOL moved by system transfers
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    sol_transfers: Option<SolTransferSummary>,
+    // Proofs that the volume of SOL or of a mint is above or below a threshold
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    volume_thresholds: Vec<VolumeThresholdProof>,
+    // Fees and priority fees paid in the block
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    fees: Option<FeeStats>,
+    // Lamport balance deltas of the watched accounts
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    balances: Option<BalanceSummary>,
+    // Stake delegations and deactivations
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    stake_activity: Vec<StakeActivity>,
+    // Upgradeable program deploys, upgrades, extensions and closures, with a
+    // commitment and proof over them
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    program_changes: Option<ProgramChanges>,
+    // Votes cast by the watched validators, with a commitment and proof over them
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    votes: Option<BlockVotes>,
+    // Rewards credited with the block (leader fees, rent, and staking and
+    // voting rewards at epoch boundaries), as reported by the node
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    rewards: Vec<Reward>,
+    // Circuit commitment to the block hash and transaction hashes, and its proof.
+    // For chunked blocks these are the aggregate over the chunk commitments.
+    commitment: String,
+    proof: String,
+    // Number of transaction leaves and the compute units they consumed, proved
+    // against the commitment above
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    compute_units: Option<TotalsProof>,
+    // Local clock against the block's timestamp when the block was processed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    clock_drift: Option<ClockDrift>,
+    // How long after the block's timestamp the proof was completed, with a latency SLO
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    latency: Option<ProofLatency>,
+    // Fingerprint of the verifying key the circuit proofs were made with
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    params_fingerprint: Option<String>,
+    // Cross-block accumulator before and after this block (public inputs)
+    old_root: String,
+    new_root: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    chunks: Vec<ChunkProof>,
     transactions: Vec<TransactionProof>,
+    // Proofs that the fee payer's signature of each transaction verifies
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    signature_proofs: Vec<SignatureProof>,
+    // Transactions of the block left out of `transactions_root` by a transaction processor
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    excluded: Vec<ExcludedTransaction>,
+    // Co-signatures from listener operators attesting to this proof
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    signatures: Vec<ProofSignature>,
+}
+
+// Transaction signatures of a block, in block order
+fn block_signatures(block: &EncodedConfirmedBlock) -> Vec<String> {
+    block
+        .transactions
+        .iter()
+        .filter_map(instructions::decode)
+        .flat_map(|transaction| transaction.signatures)
+        .collect()
+}
+
+// Data hashed into a transaction's leaf: its signature, followed by its message
+// hash when messages are bound
+fn leaf_data(signature: &str, message_hash: Option<&str>) -> String {
+    match message_hash {
+        Some(message_hash) => format!("{}:{}", signature, message_hash),
+        None => signature.to_string(),
+    }
+}
+
+// Circuit seed of a block: its hash, and the leader identity and token supply
+// when bound
+fn block_seed(block_hash: &str, leader: Option<&str>, supply: Option<&str>) -> Fr {
+    let data: Vec<&str> = [Some(block_hash), leader, supply].into_iter().flatten().collect();
+    str_to_fr(Domain::Block, &data.join(":"))
 }
 
-// Define the circuit for block validation
-struct BlockCircuit {
-    pub block_hash: Option<Fr>,
-    pub transaction_hashes: Vec<Option<Fr>>,
+// Values seed of a block's sum named `sum` (sol, credits or debits)
+fn sum_seed(block_hash: &str, sum: &str) -> Fr {
+    str_to_fr(Domain::Commitment, &format!("{}:{}", block_hash, sum))
 }
 
-impl Circuit<Fr> for BlockCircuit {
-    fn synthesize<CS: ConstraintSystem<Fr>>(self, cs: &mut CS) -> Result<(), SynthesisError> {
-        // Allocate the block hash
-        let block_hash_var = cs.alloc(
-            || "block hash",
-            || self.block_hash.ok_or(SynthesisError::AssignmentMissing),
-        )?;
+// Values of a sum over a block's leaves, from amounts keyed by transaction
+// signature; None when a transaction's amounts overflow a circuit value, in
+// which case the sum is not proved
+fn sum_values<'a>(
+    slot: Slot,
+    sum: &str,
+    signatures: &[String],
+    amounts: impl IntoIterator<Item = (&'a str, u64)>,
+) -> Option<Vec<u64>> {
+    let values = witness::leaf_values(signatures, amounts);
+    if values.is_none() {
+        warn!("A transaction in block {} moves more {} than a sum proof holds; not proving them", slot, sum);
+    }
+    values
+}
+
+// Builds the witness for a block on top of the accumulator root `old_root`
+fn build_block_witness(
+    slot: Slot,
+    mut block: EncodedConfirmedBlock,
+    leader: Option<String>,
+    token_supply: Option<TokenSupply>,
+    old_root: Fr,
+    config: &Config,
+) -> Result<ExportedWitness, WitnessError> {
+    let block_hash_str = block.blockhash.clone();
+    let transactions = block.transactions.len();
+    let oversized = config.max_txs_per_block.filter(|max| transactions > *max).map(|max_txs_per_block| {
+        // Blocks are only built under `skip` when proved again, and are then chunked
+        let policy = match config.oversized_blocks {
+            OversizedPolicy::TruncateWithFlag => {
+                block.transactions.truncate(max_txs_per_block);
+                OversizedPolicy::TruncateWithFlag
+            }
+            OversizedPolicy::Skip | OversizedPolicy::Chunk => OversizedPolicy::Chunk,
+        };
+        OversizedBlock { policy, transactions, max_txs_per_block }
+    });
+    let leader_bound = config.bind_leader && leader.is_some();
+    let supply_binding = token_supply.as_ref().filter(|token_supply| token_supply.bound).map(supply::binding);
+    let seed = block_seed(&block_hash_str, leader.as_deref().filter(|_| leader_bound), supply_binding.as_deref());
+    let mut witness = WitnessAccumulator::new(seed, config.max_block_memory);
+
+    let mut annotations: BTreeMap<String, TransactionAnnotations> = BTreeMap::new();
+    for (signature, memos) in memo::block_memos(&block) {
+        annotations.entry(signature).or_default().memos = memos;
+    }
+    for (signature, events) in nft::block_events(&block) {
+        annotations.entry(signature).or_default().nft = events;
+    }
+
+    let fees = fees::block_stats(&block);
+
+    let stake_activity = config.stake_activity.as_ref().map(|_| stake_activity::activity(&block)).unwrap_or_default();
+
+    let program_changes = programs::changes(&block);
+    let program_changes_witness = if program_changes.is_empty() {
+        None
+    } else {
+        Some(programs::changes_witness(&block_hash_str, &program_changes, config.max_block_memory)?)
+    };
+
+    let votes = config.votes.as_ref().map(|vote_config| votes::votes(vote_config, &block)).unwrap_or_default();
+    let votes_witness = if votes.is_empty() {
+        None
+    } else {
+        Some(votes::votes_witness(&block_hash_str, &votes, config.max_block_memory)?)
+    };
+
+    let token_transfers = config.tokens.as_ref().map(|_| token::transfers(&block)).unwrap_or_default();
+    let mint_witnesses = config
+        .tokens
+        .iter()
+        .flat_map(|tokens| &tokens.mints)
+        .filter(|mint| token_transfers.iter().any(|transfer| &transfer.mint == *mint))
+        .map(|mint| token::mint_witness(&block_hash_str, mint, &token_transfers, config.max_block_memory))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let sol_transfers = config.sol_transfers.as_ref().map(|sol_config| {
+        system::summarize(system::transfers(&block), &sol_config.accounts, sol_config.prove_sum)
+    });
+
+    let balances = config.balances.as_ref().map(|balance_config| {
+        balance::summarize(balance::changes(&block, &balance_config.accounts), balance_config.prove_sum)
+    });
+
+    let mut signatures = Vec::new();
+    let mut salts = Vec::new();
+    let mut message_hashes = Vec::new();
+    let mut excluded = Vec::new();
+    let mut compute_units = config.prove_block_totals.then(Vec::new);
+    let mut fee_values = config.prove_total_fees.then(Vec::new);
+    let mut signed_messages = Vec::new();
+    let mut seen = HashSet::new();
+    let processors = plugin::processors();
+
+    // Leaves are in canonical order: block order of the transactions, then the
+    // order of each transaction's signatures, with every signature after its
+    // first occurrence left out. Independent provers of a block so derive the
+    // same commitment even from a response that repeats a transaction.
+    for raw in &block.transactions {
+        let Some(transaction) = instructions::decode(raw) else {
+            continue;
+        };
+        if let Some(processor) = processors.iter().find(|processor| !processor.include(slot, &transaction, raw)) {
+            debug!("Transaction {} left out by processor {}", transaction.signature, processor.name());
+            excluded.push(ExcludedTransaction {
+                signature: transaction.signature.clone(),
+                processor: processor.name().to_string(),
+            });
+            continue;
+        }
+        for processor in processors {
+            if let Some(fields) = processor.annotate(slot, &transaction, raw) {
+                let annotation = annotations.entry(transaction.signature.clone()).or_default();
+                annotation.custom.insert(processor.name().to_string(), fields);
+            }
+        }
+        let message_hash = Some(transaction.message_hash).filter(|_| config.bind_messages);
+        // Counted on the transaction's first leaf, so each transaction adds to the totals once
+        let (mut units, mut fee) = (fees::compute_units(raw), fees::fee(raw));
+        // Likewise the fee payer's signature, which is the transaction's first
+        if config.prove_signatures && !seen.contains(&transaction.signature) {
+            if let Some(signed) = signatures::signed_message(raw) {
+                witness.reserve(signed.message.len())?;
+                signed_messages.push(signed);
+            }
+        }
+        for signature in transaction.signatures {
+            if !seen.insert(signature.clone()) {
+                warn!("Leaving out repeated signature {} in block {}", signature, slot);
+                continue;
+            }
+            debug!("Transaction hash: {}", signature);
 
-        // Hash the transaction hashes
-        let mut hasher = Sha256::new();
-        for tx_hash in self.transaction_hashes.iter() {
-            if let Some(hash) = tx_hash {
-                hasher.update(hash.to_repr());
+            let data = leaf_data(&signature, message_hash.as_deref());
+            if config.private_dir.is_some() {
+                let salt = disclosure::random_salt();
+                witness.push(disclosure::salted_leaf(&salt, &data, HASH_DOMAINS))?;
+                witness.reserve(salt.len())?;
+                salts.push(hex::encode(salt));
+            } else {
+                witness.push(str_to_fr(Domain::Transaction, &data))?;
+            }
+            witness.reserve(data.len())?;
+            signatures.push(signature);
+            message_hashes.extend(message_hash.clone());
+            if let Some(compute_units) = &mut compute_units {
+                compute_units.push(std::mem::take(&mut units));
+            }
+            if let Some(fee_values) = &mut fee_values {
+                fee_values.push(std::mem::take(&mut fee));
             }
         }
+    }
 
-        // Convert the final hash to a field element
-        let result_hash = hasher.finalize();
-        let mut result_hash_bytes = [0u8; 32];
-        result_hash_bytes.copy_from_slice(&result_hash);
-        let result_hash_fr = Fr::from_repr(result_hash_bytes).unwrap_or_else(||Fr::ZERO);
+    let volume_witnesses = volume::witnesses(&block, &signatures, &config.volume_thresholds);
+    // Transfers and changes are kept in the summaries when their sums are proved
+    let sol_values = match (&sol_transfers, &config.sol_transfers) {
+        (Some(summary), Some(sol_config)) if sol_config.prove_sum => {
+            let transfers = summary.transfers.iter().map(|transfer| (transfer.signature.as_str(), transfer.lamports));
+            sum_values(slot, "SOL", &signatures, transfers)
+        }
+        _ => None,
+    };
+    let (balance_credit_values, balance_debit_values) = match (&balances, &config.balances) {
+        (Some(summary), Some(balance_config)) if balance_config.prove_sum => (
+            sum_values(slot, "balance credits", &signatures, balance::credits(&summary.changes)),
+            sum_values(slot, "balance debits", &signatures, balance::debits(&summary.changes)),
+        ),
+        _ => (None, None),
+    };
 
-        // Constrain the computed hash to be equal to the given block hash
-        cs.enforce(
-            || "block hash constraint",
-            |lc| lc + block_hash_var,
-            |lc| lc + CS::one(),
-            |lc| lc + (result_hash_fr, CS::one()),
-        );
+    info!("Built witness for block {} over {} transactions", slot, witness.transaction_count());
+
+    Ok(ExportedWitness {
+        slot,
+        block_hash: block_hash_str,
+        genesis_hash: None,
+        leader,
+        leader_bound,
+        token_supply,
+        confirmation: None,
+        account_states: None,
+        messages_bound: config.bind_messages,
+        hash_domains: HASH_DOMAINS,
+        commitment_hash: config.commitment_hash,
+        oversized,
+        clock_drift: None,
+        signatures,
+        message_hashes,
+        salts,
+        token_transfers,
+        mint_witnesses,
+        sol_transfers,
+        sol_values,
+        volume_witnesses,
+        fees: Some(fees),
+        balances,
+        balance_credit_values,
+        balance_debit_values,
+        stake_activity,
+        program_changes,
+        program_changes_witness,
+        votes,
+        votes_witness,
+        rewards: block.rewards,
+        annotations,
+        excluded,
+        compute_units,
+        fee_values,
+        signed_messages,
+        witness: witness.finish(old_root)?,
+    })
+}
 
-        Ok(())
+// Proves every circuit instance of a witness, returning the top-level proof and
+// the per-chunk proofs (empty unless the witness was split)
+fn prove_chunks(
+    witness: &BlockWitness,
+    params: &groth16::Parameters<Bls12>,
+) -> (groth16::Proof<Bls12>, Vec<ChunkProof>) {
+    try_prove_chunks(witness, params, &Cancellation::default()).expect("Proving was cancelled")
+}
+
+// Stops before the next chunk once cancelled
+fn try_prove_chunks(
+    witness: &BlockWitness,
+    params: &groth16::Parameters<Bls12>,
+    cancel: &Cancellation,
+) -> Result<(groth16::Proof<Bls12>, Vec<ChunkProof>), Cancelled> {
+    match &witness.aggregate {
+        None => Ok((circuit::prove(params, &witness.chunks[0]), Vec::new())),
+        Some(aggregate) => {
+            let chunks = witness
+                .chunks
+                .iter()
+                .enumerate()
+                .map(|(index, chunk)| {
+                    cancel.check()?;
+                    Ok(ChunkProof {
+                        index,
+                        commitment: fr_to_hex(&chunk.commitment),
+                        proof: serialization::proof_to_hex(&circuit::prove(params, chunk)),
+                    })
+                })
+                .collect::<Result<_, _>>()?;
+            cancel.check()?;
+            Ok((circuit::prove(params, aggregate), chunks))
+        }
     }
 }
 
-// Function to generate a proof for a block
-fn generate_block_proof(block_hash: Fr, transaction_hashes: Vec<Fr>) -> String {
-    // Create an instance of the circuit with the block data
-    let circuit = BlockCircuit {
-        block_hash: Some(block_hash),
-        transaction_hashes: transaction_hashes.iter().map(|&x| Some(x)).collect(),
+fn prove_totals(witness: &BlockWitness, values: &[u64], params: &groth16::Parameters<Bls12>) -> TotalsProof {
+    let witnesses = witness::totals_witnesses(witness, values);
+    TotalsProof {
+        signature_count: witnesses.iter().map(|witness| witness.signature_count).sum(),
+        total: witnesses.iter().map(|witness| witness.total).sum(),
+        chunks: witnesses
+            .iter()
+            .map(|witness| TotalsChunkProof {
+                values_commitment: fr_to_hex(&witness.values_commitment),
+                signature_count: witness.signature_count,
+                total: witness.total,
+                proof: serialization::proof_to_hex(&circuit::prove_totals(params, witness)),
+            })
+            .collect(),
+    }
+}
+
+// Proves the total of `values`, attached to the leaves of a block witness, with
+// the sum circuit over each chunk of the block
+fn prove_sums(
+    witness: &BlockWitness,
+    values_seed: Fr,
+    values: &[u64],
+    params: &groth16::Parameters<Bls12>,
+) -> SumProof {
+    let witnesses = witness::sum_witnesses(witness, values_seed, values);
+    SumProof {
+        total: witnesses.iter().map(|witness| witness.total).sum(),
+        chunks: witnesses
+            .iter()
+            .map(|witness| SumChunkProof {
+                commitment: fr_to_hex(&witness.values_commitment),
+                total: witness.total,
+                proof: serialization::proof_to_hex(&circuit::prove_sum(params, witness)),
+            })
+            .collect(),
+    }
+}
+
+// Proves a block witness and attaches an inclusion path for every transaction
+fn prove_block(exported: ExportedWitness, keys: &ProvingKeys, cancel: &Cancellation) -> Result<BlockProof, Cancelled> {
+    let ExportedWitness {
+        slot,
+        block_hash,
+        genesis_hash,
+        leader,
+        leader_bound,
+        token_supply,
+        confirmation,
+        account_states,
+        messages_bound,
+        hash_domains,
+        commitment_hash,
+        oversized,
+        clock_drift,
+        signatures,
+        message_hashes,
+        salts,
+        token_transfers,
+        mint_witnesses,
+        mut sol_transfers,
+        sol_values,
+        volume_witnesses,
+        mut fees,
+        mut balances,
+        balance_credit_values,
+        balance_debit_values,
+        stake_activity,
+        program_changes,
+        program_changes_witness,
+        votes,
+        votes_witness,
+        rewards,
+        mut annotations,
+        excluded,
+        compute_units,
+        fee_values,
+        signed_messages,
+        witness,
+    } = exported;
+
+    let witness_hash = witness::witness_hash(&block_hash, &signatures);
+
+    // Generate block proof, proving each chunk first if the block was split
+    if witness.aggregate.is_some() {
+        info!("Block {} split into {} chunks", slot, witness.chunks.len());
+    }
+    let (proof, chunks) = try_prove_chunks(&witness, &keys.block, cancel)?;
+    if let (Some(summary), Some(values)) = (&mut sol_transfers, &sol_values) {
+        cancel.check()?;
+        summary.sum_proof = Some(prove_sums(&witness, sum_seed(&block_hash, "sol"), values, keys.sum()));
+    }
+    let mut volume_thresholds = Vec::with_capacity(volume_witnesses.len());
+    for volume_witness in &volume_witnesses {
+        cancel.check()?;
+        volume_thresholds.push(volume::prove(volume_witness, &block_hash, &witness.chunks[0], keys.threshold()));
+    }
+    if let (Some(summary), Some(values)) = (&mut balances, &balance_credit_values) {
+        cancel.check()?;
+        summary.credit_proof = Some(prove_sums(&witness, sum_seed(&block_hash, "credits"), values, keys.sum()));
+    }
+    if let (Some(summary), Some(values)) = (&mut balances, &balance_debit_values) {
+        cancel.check()?;
+        summary.debit_proof = Some(prove_sums(&witness, sum_seed(&block_hash, "debits"), values, keys.sum()));
+    }
+    let compute_units = match compute_units {
+        Some(values) => {
+            cancel.check()?;
+            Some(prove_totals(&witness, &values, keys.totals()))
+        }
+        None => None,
     };
+    if let (Some(stats), Some(values)) = (&mut fees, fee_values) {
+        cancel.check()?;
+        stats.total_fees_proof = Some(prove_totals(&witness, &values, keys.totals()));
+    }
+    cancel.check()?;
+    let mint_proofs = mint_witnesses
+        .iter()
+        .map(|mint_witness| token::prove_mint(mint_witness, commitment_hash, &keys.block))
+        .collect();
+    cancel.check()?;
+    let program_changes = program_changes_witness
+        .map(|witness| programs::prove_changes(program_changes, &witness, commitment_hash, &keys.block));
+    cancel.check()?;
+    let votes = votes_witness.map(|witness| votes::prove_votes(votes, &witness, commitment_hash, &keys.block));
+    let account_states = account_states.map(|states| accounts::prove(states, commitment_hash));
+    let signature_proofs = signatures::prove_all(&signed_messages, keys, cancel)?;
 
-    // Generate parameters
-    let rng = &mut thread_rng();
-    let params = {
-        let empty_circuit = BlockCircuit {
-            block_hash: None,
-            transaction_hashes: vec![None; transaction_hashes.len()],
-        };
-        groth16::generate_random_parameters::<Bls12, _, _>(empty_circuit, rng).unwrap()
+    let top_level = witness.top_level();
+    let leaves: Vec<_> = witness.leaves().map(|leaf| leaf.to_repr()).collect();
+    let tree = MerkleTree::new(commitment_hash, &leaves);
+    // The sorted root and bloom filter would let anyone test for a signature,
+    // so blocks proved with salted leaves go without them
+    let private = !salts.is_empty();
+    let (sorted_root, bloom) = if private {
+        (String::new(), None)
+    } else {
+        (hex::encode(absence::sorted_root(commitment_hash, &signatures)), Some(BloomFilter::new(&signatures)))
     };
 
-    // Create a proof
-    let proof = groth16::create_random_proof(circuit, &params, rng).unwrap();
+    let mut salts = salts.into_iter();
+    let mut message_hashes = message_hashes.into_iter();
+    let transactions = signatures
+        .into_iter()
+        .enumerate()
+        .map(|(leaf_index, transaction_hash)| TransactionProof {
+            annotations: annotations.remove(&transaction_hash).unwrap_or_default(),
+            transaction_hash,
+            salt: salts.next(),
+            message_hash: message_hashes.next(),
+            leaf_index,
+            merkle_path: tree.path(leaf_index),
+        })
+        .collect();
 
-    // Serialize the proof
-    format!("{:?}", proof)
+    Ok(BlockProof {
+        slot,
+        block_hash,
+        genesis_hash,
+        witness_hash: Some(witness_hash),
+        leader,
+        leader_bound,
+        messages_bound,
+        hash_domains,
+        commitment_hash,
+        token_supply,
+        confirmation,
+        account_states,
+        oversized,
+        transactions_root: hex::encode(tree.root()),
+        sorted_root,
+        bloom,
+        token_transfers,
+        mint_proofs,
+        sol_transfers,
+        volume_thresholds,
+        fees,
+        balances,
+        stake_activity,
+        program_changes,
+        votes,
+        rewards,
+        commitment: fr_to_hex(&top_level.commitment),
+        proof: serialization::proof_to_hex(&proof),
+        compute_units,
+        clock_drift,
+        latency: None,
+        params_fingerprint: Some(keys.block_fingerprint.clone()),
+        old_root: fr_to_hex(&top_level.old_root),
+        new_root: fr_to_hex(&top_level.new_root()),
+        chunks,
+        transactions,
+        signature_proofs,
+        excluded,
+        signatures: Vec::new(),
+    })
 }
 
-fn str_to_fr(data: &str) -> Option<Fr> {
-    // Convert string to bytes and then to Fr (handling errors)
-    let hash = Sha256::digest(data.as_bytes());
-    let mut hash_bytes = [0u8; 32];
-    hash_bytes.copy_from_slice(&hash);
-    println!("Converting hash to field element: {:?}", hash_bytes);
-    Some(Fr::from_repr(hash_bytes).unwrap_or_else(||Fr::ZERO))
+// Operator keypair used to sign every proof this instance produces, if configured
+fn load_signing_keypair(config: &Config) -> Option<Keypair> {
+    config.signing_keypair.as_ref().map(|path| {
+        read_keypair_file(path).unwrap_or_else(|e| panic!("Unable to read signing keypair {:?}: {}", path, e))
+    })
+}
+
+// Slots that have a file named `<prefix><slot>.json` in the proofs directory, in ascending order
+fn list_slots(proofs_dir: &Path, prefix: &str) -> Vec<Slot> {
+    let mut slots: Vec<Slot> = fs::read_dir(proofs_dir)
+        .map(|entries| {
+            entries
+                .filter_map(|entry| {
+                    let file_name = entry.ok()?.file_name().into_string().ok()?;
+                    file_name.strip_prefix(prefix)?.strip_suffix(".json")?.parse().ok()
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    slots.sort_unstable();
+    slots
+}
+
+// Slots that have a proof file in the proofs directory, in ascending order
+fn list_proof_slots(proofs_dir: &Path) -> Vec<Slot> {
+    list_slots(proofs_dir, "block_proof_")
+}
+
+fn load_proof(path: &Path) -> BlockProof {
+    let contents = fs::read_to_string(path).expect("Unable to read proof file");
+    serde_json::from_str(&contents).expect("Unable to parse proof file")
+}
+
+// Saves a block proof locally. Uploads to object storage go through the
+// storage sink, like every other output.
+fn publish_proof(block_proof: &BlockProof, proofs_dir: &Path) {
+    // Save the block proof to a JSON file
+    save_proof_to_json(block_proof, block_proof.slot, proofs_dir);
+    if let Some(witness_hash) = &block_proof.witness_hash {
+        nullifier::append(proofs_dir, block_proof.slot, witness_hash);
+    }
+}
+
+#[derive(Parser)]
+struct Cli {
+    /// Path to a TOML configuration file
+    #[arg(long)]
+    config: Option<PathBuf>,
+
+    /// Cluster to follow, overriding `cluster` in the configuration file
+    #[arg(long, value_enum)]
+    cluster: Option<cluster::Cluster>,
+
+    /// Only build witnesses and write them to the proofs directory, leaving
+    /// proving to `prove-pending` or `prove-witness`
+    #[arg(long)]
+    witness_only: bool,
+
+    /// Fetch, filter and decode blocks and build their witnesses without
+    /// proving, recording what each proof would hold under dry_run/ of the
+    /// proofs directory
+    #[arg(long, conflicts_with = "witness_only")]
+    dry_run: bool,
+
+    /// Only prove slots divisible by N, recording the policy in the manifest
+    #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u64).range(1..))]
+    sample_rate: u64,
+
+    /// Only prove blocks produced by the validator with identity PUBKEY, by
+    /// the leader schedule, recording it in the manifest
+    #[arg(long, value_name = "PUBKEY")]
+    leader_identity: Option<Pubkey>,
+
+    /// Write the process ID to PATH once started
+    #[arg(long, value_name = "PATH")]
+    pid_file: Option<PathBuf>,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Check the config file and everything it points at (endpoints, proofs
+    /// directories, object storage, parameter files and filter public keys),
+    /// exiting with an error if any check fails
+    CheckConfig,
+    /// Print the shell completion script for SHELL
+    Completions {
+        #[arg(value_enum)]
+        shell: Shell,
+    },
+    /// Print the man page, or with --out-dir write one page per command
+    Man {
+        #[arg(long)]
+        out_dir: Option<PathBuf>,
+    },
+    /// Prove witness files exported with --witness-only
+    ProveWitness {
+        /// Witness files to prove
+        #[arg(required = true)]
+        witnesses: Vec<PathBuf>,
+    },
+    /// Prove the witnesses in the proofs directory that have no proof yet, in
+    /// slot order, removing each witness file once its proof is published
+    ProvePending {
+        /// Keep waiting for new witnesses instead of exiting once none are left
+        #[arg(long)]
+        follow: bool,
+    },
+    /// Add this operator's co-signature to existing proof files
+    Sign {
+        /// Operator keypair file
+        #[arg(long)]
+        keypair: PathBuf,
+        #[arg(required = true)]
+        proofs: Vec<PathBuf>,
+    },
+    /// Check that proof files carry at least THRESHOLD valid co-signatures
+    /// from the given signers
+    VerifySignatures {
+        #[arg(long)]
+        threshold: usize,
+        /// Accepted signer public key (repeat for each operator)
+        #[arg(long = "signer", required = true)]
+        signers: Vec<Pubkey>,
+        #[arg(required = true)]
+        proofs: Vec<PathBuf>,
+    },
+    /// Check the circuit proofs of proof files against the verifying key each
+    /// was made with, from the keyring or the block circuit parameters,
+    /// refusing proofs made with a key found in neither
+    Verify {
+        /// Parameters file to read the current verifying key from (defaults to params_path)
+        #[arg(long)]
+        params: Option<PathBuf>,
+        /// Sum circuit parameters to check SOL transfer and balance sums with
+        /// (defaults to sum_params_path)
+        #[arg(long)]
+        sum_params: Option<PathBuf>,
+        /// Totals circuit parameters to check compute-unit and fee proofs with
+        /// (defaults to totals_params_path)
+        #[arg(long)]
+        totals_params: Option<PathBuf>,
+        /// Threshold circuit parameters to check volume threshold proofs with
+        /// (defaults to threshold_params_path)
+        #[arg(long)]
+        threshold_params: Option<PathBuf>,
+        /// Signature circuit parameters to check signature proofs with
+        /// (defaults to signature_params_path)
+        #[arg(long)]
+        signature_params: Option<PathBuf>,
+        /// Directory of historical verifying keys (defaults to the proofs
+        /// directory's keyring)
+        #[arg(long)]
+        keyring: Option<PathBuf>,
+        #[arg(required = true)]
+        proofs: Vec<PathBuf>,
+    },
+    /// Prove that SIGNATURE is not in the block of a proof file, printing the
+    /// absence proof as JSON
+    ProveAbsence {
+        proof: PathBuf,
+        signature: Signature,
+    },
+    /// Check an absence proof against the proof file of its block
+    VerifyAbsence {
+        proof: PathBuf,
+        absence: PathBuf,
+    },
+    /// Check the state of ACCOUNT recorded in a proof file against its
+    /// accounts root, printing it with its inclusion path as JSON
+    AccountState { proof: PathBuf, account: String },
+    /// Find the block proof containing SIGNATURE in the proofs directory
+    FindTransaction { signature: String },
+    /// Disclose one transaction of a block proved with selective disclosure,
+    /// printing its salt and inclusion path as JSON
+    Disclose { slot: Slot, signature: String },
+    /// Check a disclosed transaction against the proof file of its block
+    VerifyDisclosure {
+        proof: PathBuf,
+        disclosure: PathBuf,
+    },
+    /// Write the summary of EPOCH from the block proofs in the proofs directory
+    SummarizeEpoch { epoch: Epoch },
+    /// List the supply of MINT recorded across the block proofs in the proofs
+    /// directory, one line per slot
+    SupplyHistory { mint: String },
+    /// List the recorded deploys, upgrades and closures of PROGRAM_ID across
+    /// the block proofs in the proofs directory
+    ProgramHistory { program_id: String },
+    /// List the recorded votes of the validator with vote account VOTE_ACCOUNT
+    /// across the block proofs in the proofs directory
+    VoteHistory { vote_account: String },
+    /// Report, as JSON, every slot of a range that has no proof in the proofs
+    /// directory and why
+    Gaps {
+        #[arg(long)]
+        from_slot: Slot,
+        #[arg(long)]
+        to_slot: Slot,
+    },
+    /// Report, as JSON, every transaction signature found in more than one
+    /// block proof in the proofs directory, exiting with an error if any is
+    DuplicateSignatures,
+    /// Check a random sample of proved slots against the blocks the endpoint
+    /// serves now, printing an audit report as JSON and exiting with an error
+    /// if any proof fails
+    Crosscheck {
+        /// Number of proved slots to check
+        #[arg(long)]
+        slots: usize,
+        /// Seed to draw the slots with, to repeat an earlier sample (random by default)
+        #[arg(long)]
+        seed: Option<u64>,
+    },
+    /// Build the witness of SLOT again from its block snapshot and compare it
+    /// with the archived witness, exiting with an error if they differ
+    Reproduce { slot: Slot },
+    /// Prove the blocks of existing proofs again with new parameters, keeping
+    /// the previous proof files under a version suffix
+    Reprove {
+        #[arg(long)]
+        from_slot: Slot,
+        #[arg(long)]
+        to_slot: Slot,
+        /// Block circuit parameters to prove with
+        #[arg(long)]
+        params: PathBuf,
+        /// Sum circuit parameters to prove with (defaults to sum_params_path)
+        #[arg(long)]
+        sum_params: Option<PathBuf>,
+    },
+    /// Generate proving parameters through a multi-party ceremony
+    Ceremony {
+        #[command(subcommand)]
+        step: CeremonyStep,
+    },
+    /// Deliver the block proofs kept in the dead-letter stores of the configured
+    /// sinks, removing those delivered and exiting with an error if any remain
+    Redeliver {
+        /// Only redeliver the proofs of the sink with this name
+        #[arg(long)]
+        sink: Option<String>,
+    },
+    /// Capture the prover state of the proofs directory in one file, or
+    /// restore it there, to move a stopped listener to another host
+    Snapshot {
+        #[command(subcommand)]
+        action: SnapshotAction,
+    },
+    /// Print fee statistics aggregated over the block proofs in the proofs
+    /// directory, optionally limited to a slot range
+    Stats {
+        #[arg(long)]
+        from_slot: Option<Slot>,
+        #[arg(long)]
+        to_slot: Option<Slot>,
+    },
+    /// Print, as JSON, the RPC calls made to each provider and the credits
+    /// they cost, by UTC day, from the cost ledger in the proofs directory
+    Costs {
+        /// Number of most recent days to report
+        #[arg(long, default_value_t = 7)]
+        days: usize,
+    },
+}
+
+#[derive(Subcommand)]
+enum SnapshotAction {
+    /// Write the checkpoint, accumulator root, manifest, slot index (with its
+    /// failed slots), nullifiers, keyring, spilled queue and sink dead letters
+    /// to OUTPUT as a tar.zst
+    Create { output: PathBuf },
+    /// Check every file of SNAPSHOT against its hash and replace the prover
+    /// state of the proofs directory with it
+    Restore {
+        snapshot: PathBuf,
+        /// Replace a proofs directory that already has a checkpoint
+        #[arg(long)]
+        force: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum CeremonyStep {
+    /// Write the initial parameters of CIRCUIT to PARAMS and start its transcript
+    Init {
+        #[arg(value_enum)]
+        circuit: CeremonyCircuit,
+        params: PathBuf,
+    },
+    /// Apply a fresh secret contribution to PARAMS
+    Contribute { params: PathBuf },
+    /// Check PARAMS and its transcript against the initial parameters
+    Verify {
+        #[arg(long)]
+        initial: PathBuf,
+        params: PathBuf,
+    },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum CeremonyCircuit {
+    Block,
+    Sum,
+    Totals,
+    Threshold,
+    #[cfg(feature = "ed25519")]
+    Signature,
+}
+
+#[global_allocator]
+static ALLOCATOR: memory::CountingAllocator = memory::CountingAllocator;
+
+fn memory_budget(config: &Config) -> Option<usize> {
+    config.prover_memory_budget_mb.map(|budget_mb| (budget_mb as usize) << 20)
 }
 
 #[tokio::main]
 async fn main() {
-    let rpc_url = "http://127.0.0.1:8899"; // URL of the local Solana validator
-    let client = RpcClient::new(rpc_url.to_string());
+    let cli = Cli::parse();
+    // Commands that run without loading the config
+    match &cli.command {
+        Some(Command::CheckConfig) => {
+            let passed = check::check_config(cli.config.as_deref(), cli.cluster).await;
+            std::process::exit(if passed { 0 } else { 1 });
+        }
+        Some(Command::Completions { shell }) => return print_completions(*shell),
+        Some(Command::Man { out_dir }) => return write_man_pages(out_dir.as_deref()),
+        _ => {}
+    }
+    let mut config = cli.config.as_deref().map(Config::load).unwrap_or_default();
+    if let Some(cluster) = cli.cluster {
+        config.cluster = Some(cluster);
+    }
+    init_logging(&config.log_level);
+    if let Some(cpu_config) = &config.prover_cpu {
+        cpu::configure(cpu_config);
+    }
+    if let Some(cost_config) = &config.rpc_costs {
+        costs::configure(cost_config, &config.proofs_dir);
+    }
+    if let Err(e) = plugin::load(&config.plugins) {
+        refuse_to_start(e);
+    }
+    if let Err(e) = params::fetch_pinned(&config).await {
+        refuse_to_start(e);
+    }
+    if let Some(path) = &cli.pid_file {
+        lock::write_pid_file(path);
+    }
+
+    match cli.command {
+        // Handled before the config is loaded
+        Some(Command::CheckConfig | Command::Completions { .. } | Command::Man { .. }) => unreachable!(),
+        None => {
+            let config_path = cli.config.as_deref();
+            run_listeners(
+                &config,
+                config_path,
+                cli.witness_only,
+                cli.dry_run,
+                cli.sample_rate,
+                cli.leader_identity,
+            )
+            .await
+        }
+        Some(Command::ProveWitness { witnesses }) => prove_witness_files(&config, &witnesses).await,
+        Some(Command::ProvePending { follow }) => prove_pending(&config, follow).await,
+        Some(Command::Sign { keypair, proofs }) => sign_proof_files(&keypair, &proofs),
+        Some(Command::VerifySignatures { threshold, signers, proofs }) => {
+            verify_proof_signatures(threshold, &signers, &proofs)
+        }
+        Some(Command::Verify {
+            params,
+            sum_params,
+            totals_params,
+            threshold_params,
+            signature_params,
+            keyring,
+            proofs,
+        }) => {
+            let keyring = keyring.unwrap_or_else(|| keyring::keyring_dir(&config.proofs_dir));
+            let params = params.as_deref().unwrap_or(&config.params_path);
+            let sum_params = sum_params.as_deref().unwrap_or(&config.sum_params_path);
+            let totals_params = totals_params.as_deref().unwrap_or(&config.totals_params_path);
+            let threshold_params = threshold_params.as_deref().unwrap_or(&config.threshold_params_path);
+            let signature_params = signature_params.as_deref().unwrap_or(&config.signature_params_path);
+            let circuit_params = [sum_params, totals_params, threshold_params, signature_params];
+            verify_proofs(params, circuit_params, &keyring, &proofs)
+        }
+        Some(Command::ProveAbsence { proof, signature }) => prove_absence(&proof, &signature),
+        Some(Command::VerifyAbsence { proof, absence }) => verify_absence(&proof, &absence),
+        Some(Command::AccountState { proof, account }) => account_state(&proof, &account),
+        Some(Command::FindTransaction { signature }) => find_transaction(&config.proofs_dir, &signature),
+        Some(Command::Disclose { slot, signature }) => disclose_transaction(&config, slot, &signature),
+        Some(Command::VerifyDisclosure { proof, disclosure }) => verify_disclosure(&proof, &disclosure),
+        Some(Command::SummarizeEpoch { epoch }) => summarize_epoch(&config, epoch).await,
+        Some(Command::SupplyHistory { mint }) => supply_history(&config.proofs_dir, &mint),
+        Some(Command::ProgramHistory { program_id }) => program_history(&config.proofs_dir, &program_id),
+        Some(Command::VoteHistory { vote_account }) => vote_history(&config.proofs_dir, &vote_account),
+        Some(Command::Gaps { from_slot, to_slot }) => report_gaps(&config, from_slot, to_slot),
+        Some(Command::DuplicateSignatures) => report_duplicate_signatures(&config.proofs_dir),
+        Some(Command::Crosscheck { slots, seed }) => crosscheck_proofs(&config, slots, seed),
+        Some(Command::Reproduce { slot }) => reproduce_witness(&config, slot),
+        Some(Command::Reprove { from_slot, to_slot, params, sum_params }) => {
+            let sum_params = sum_params.unwrap_or_else(|| config.sum_params_path.clone());
+            let (totals_params, threshold_params) = (&config.totals_params_path, &config.threshold_params_path);
+            let signature_params = &config.signature_params_path;
+            let keys = ProvingKeys::from_paths(&params, &sum_params, totals_params, threshold_params, signature_params);
+            keyring::record(&config.proofs_dir, &keys.block.vk);
+            reprove(&config, from_slot, to_slot, &keys).await
+        }
+        Some(Command::Ceremony { step }) => run_ceremony(step),
+        Some(Command::Redeliver { sink }) => redeliver_dead_letters(&config, sink.as_deref()).await,
+        Some(Command::Snapshot { action }) => run_snapshot(&config.proofs_dir, action),
+        Some(Command::Stats { from_slot, to_slot }) => print_stats(&config.proofs_dir, from_slot, to_slot),
+        Some(Command::Costs { days }) => print_costs(&config, days),
+    }
+    // Usage since the last save, which a listener makes every few seconds
+    costs::flush();
+}
+
+fn print_completions(shell: Shell) {
+    let mut command = Cli::command();
+    let name = command.get_name().to_string();
+    clap_complete::generate(shell, &mut command, name, &mut std::io::stdout());
+}
 
-    // Create and clean the proofs directory
-    let proofs_dir = Path::new("proofs");
-    if proofs_dir.exists() {
-        fs::remove_dir_all(proofs_dir).expect("Unable to clean proofs directory");
+fn write_man_pages(out_dir: Option<&Path>) {
+    let command = Cli::command();
+    match out_dir {
+        Some(out_dir) => {
+            fs::create_dir_all(out_dir).expect("Unable to create man page directory");
+            clap_mangen::generate_to(command, out_dir).expect("Unable to write man pages");
+            println!("Wrote man pages to {:?}", out_dir);
+        }
+        None => clap_mangen::Man::new(command).render(&mut std::io::stdout()).expect("Unable to write man page"),
     }
-    fs::create_dir(proofs_dir).expect("Unable to create proofs directory");
+}
+
+// Dependencies only log warnings and errors; the listener's own output follows
+// `log_level`, which the admin API can change at runtime
+fn init_logging(level: &str) {
+    env_logger::Builder::new()
+        .filter_level(log::LevelFilter::Warn)
+        .filter_module("solana_block_listener", log::LevelFilter::Trace)
+        .init();
+    log::set_max_level(admin::parse_level(level));
+}
+
+fn sign_proof_files(keypair_path: &Path, proofs: &[PathBuf]) {
+    let keypair = read_keypair_file(keypair_path)
+        .unwrap_or_else(|e| panic!("Unable to read keypair {:?}: {}", keypair_path, e));
+
+    for path in proofs {
+        let mut block_proof = load_proof(path);
+        cosign::sign(&mut block_proof, &keypair);
+        let json_data = serde_json::to_string_pretty(&block_proof).expect("Unable to serialize proof");
+        fs::write(path, json_data).expect("Unable to write proof file");
+
+        println!("Signed {:?} ({} signatures)", path, block_proof.signatures.len());
+    }
+}
+
+fn verify_proof_signatures(threshold: usize, signers: &[Pubkey], proofs: &[PathBuf]) {
+    let mut failed = false;
 
-    let mut last_slot: Slot = 0;
-    let mut seen_blocks: HashSet<Slot> = HashSet::new();
+    for path in proofs {
+        let block_proof = load_proof(path);
+        let valid = cosign::valid_signers(&block_proof, signers);
+        if valid >= threshold {
+            println!("{:?}: OK ({} of {} signers)", path, valid, signers.len());
+        } else {
+            println!("{:?}: FAILED ({} valid signatures, {} required)", path, valid, threshold);
+            failed = true;
+        }
+    }
+
+    if failed {
+        std::process::exit(1);
+    }
+}
+
+// `circuit_params` are the sum, totals, threshold and signature circuit parameters
+fn verify_proofs(params_path: &Path, circuit_params: [&Path; 4], keyring_dir: &Path, proofs: &[PathBuf]) {
+    let mut keyring = Keyring::load(keyring_dir);
+    if params_path.exists() {
+        keyring.insert_current(&params::load_verifying_key(params_path));
+    }
+    let circuit_keys = verify::circuit_keys(circuit_params);
+    println!("Verifying against {} keys", keyring.len());
+    let mut failed = false;
+
+    for path in proofs {
+        let block_proof = load_proof(path);
+        let verified = verify::verify_block(&block_proof, &keyring)
+            .and_then(|()| verify::verify_circuits(&block_proof, circuit_keys.each_ref().map(Option::as_ref)));
+        match verified {
+            Ok(()) if block_proof.params_fingerprint.is_none() => {
+                println!("{:?}: OK (proof records no key fingerprint)", path)
+            }
+            Ok(()) => println!("{:?}: OK", path),
+            Err(e) => {
+                println!("{:?}: FAILED ({})", path, e);
+                failed = true;
+            }
+        }
+    }
+
+    if failed {
+        std::process::exit(1);
+    }
+}
+
+// Looks a transaction up across the proof files, using each proof's bloom
+// filter to skip scanning blocks that cannot contain it. Returns its slot and
+// inclusion proof, or the number of block proofs scanned without finding it.
+fn locate_transaction(proofs_dir: &Path, signature: &str) -> Result<(Slot, TransactionProof), usize> {
+    let mut scanned = 0;
+
+    for slot in list_proof_slots(proofs_dir) {
+        let block_proof = load_proof(&proofs_dir.join(proof_file_name(slot)));
+        if block_proof.bloom.as_ref().is_some_and(|bloom| !bloom.might_contain(signature)) {
+            continue;
+        }
+        scanned += 1;
+
+        if let Some(transaction) = block_proof.transactions.into_iter().find(|tx| tx.transaction_hash == signature) {
+            return Ok((slot, transaction));
+        }
+    }
+    Err(scanned)
+}
+
+fn find_transaction(proofs_dir: &Path, signature: &str) {
+    match locate_transaction(proofs_dir, signature) {
+        Ok((slot, transaction)) => {
+            println!("Found in block {} at index {}", slot, transaction.leaf_index);
+            println!("{}", serde_json::to_string_pretty(&transaction).expect("Unable to serialize transaction proof"));
+        }
+        Err(scanned) => {
+            eprintln!("Signature {} not found ({} block proofs scanned)", signature, scanned);
+            std::process::exit(1);
+        }
+    }
+}
+
+fn disclose_transaction(config: &Config, slot: Slot, signature: &str) {
+    let private_dir = config.private_dir.as_ref().expect("private_dir is not configured");
+
+    match disclosure::disclose(private_dir, slot, signature) {
+        Some(disclosure) => {
+            println!("{}", serde_json::to_string_pretty(&disclosure).expect("Unable to serialize disclosure"))
+        }
+        None => {
+            eprintln!("Signature {} is not in block {}", signature, slot);
+            std::process::exit(1);
+        }
+    }
+}
+
+fn verify_disclosure(proof_path: &Path, disclosure_path: &Path) {
+    let block_proof = load_proof(proof_path);
+    let contents = fs::read_to_string(disclosure_path).expect("Unable to read disclosure");
+    let disclosure: disclosure::Disclosure = serde_json::from_str(&contents).expect("Unable to parse disclosure");
+
+    if disclosure::verify(&block_proof, &disclosure) {
+        println!("OK: {} is in block {}", disclosure.transaction.transaction_hash, disclosure.slot);
+    } else {
+        println!("FAILED: disclosure does not verify against {:?}", proof_path);
+        std::process::exit(1);
+    }
+}
+
+fn prove_absence(proof_path: &Path, signature: &Signature) {
+    let block_proof = load_proof(proof_path);
+    if block_proof.sorted_root.is_empty() {
+        eprintln!("{:?} has no sorted signature root", proof_path);
+        std::process::exit(1);
+    }
+
+    match absence::prove(&block_proof, signature) {
+        Some(absence) => {
+            println!("{}", serde_json::to_string_pretty(&absence).expect("Unable to serialize absence proof"))
+        }
+        None => {
+            eprintln!("Signature {} is in block {}", signature, block_proof.slot);
+            std::process::exit(1);
+        }
+    }
+}
+
+// Accounts that did not exist are listed beside the tree rather than in it,
+// so their absence rests on the proof's signatures alone
+fn account_state(proof_path: &Path, account: &str) {
+    let block_proof = load_proof(proof_path);
+    let Some(states) = &block_proof.account_states else {
+        eprintln!("Block {} has no account states; account_state must be set when it is proved", block_proof.slot);
+        std::process::exit(1);
+    };
+    if states.missing.iter().any(|missing| missing == account) {
+        println!("{} did not exist at slot {}", account, states.context_slot);
+        return;
+    }
+    let Some(inclusion) = states.accounts.iter().find(|inclusion| inclusion.state.pubkey == account) else {
+        eprintln!("{} is not a watched account in block {}", account, block_proof.slot);
+        std::process::exit(1);
+    };
+    if !accounts::verify(states, inclusion, block_proof.commitment_hash) {
+        println!("FAILED: state of {} does not verify against its accounts root", account);
+        std::process::exit(1);
+    }
+    println!("{}", serde_json::to_string_pretty(inclusion).expect("Unable to serialize account state"));
+}
+
+fn verify_absence(proof_path: &Path, absence_path: &Path) {
+    let block_proof = load_proof(proof_path);
+    let contents = fs::read_to_string(absence_path).expect("Unable to read absence proof");
+    let absence: absence::AbsenceProof = serde_json::from_str(&contents).expect("Unable to parse absence proof");
+
+    if absence::verify(&block_proof, &absence) {
+        println!("OK: {} is not in block {}", absence.signature, absence.slot);
+    } else {
+        println!("FAILED: absence proof does not verify against {:?}", proof_path);
+        std::process::exit(1);
+    }
+}
+
+// Object storage of the archive, under the namespace of the cluster recorded in its manifest
+fn open_storage(config: &Config) -> Option<ObjectStorage> {
+    let genesis_hash = Manifest::load(&config.proofs_dir).and_then(|manifest| manifest.genesis_hash);
+    config.storage.as_ref().map(|storage_config| {
+        let storage = ObjectStorage::from_config(storage_config, genesis_hash.as_deref());
+        storage.expect("Unable to configure object storage")
+    })
+}
+
+// Uploads of the proofs a command publishes to object storage, retried and
+// kept with the storage sink's dead letters like the listener's
+fn storage_uploads(config: &Config) -> Sinks {
+    let storage: SharedStorage = Arc::new(RwLock::new(open_storage(config).map(Arc::new)));
+    let sinks = storage.read().unwrap().is_some().then(|| sink::storage_sink(&storage));
+    Sinks::start(sinks.into_iter().collect(), &config.proofs_dir, None, Arc::new(Metrics::default()))
+}
+
+async fn prove_witness_files(config: &Config, witnesses: &[PathBuf]) {
+    let uploads = storage_uploads(config);
+    fs::create_dir_all(&config.proofs_dir).expect("Unable to create proofs directory");
+    let keys = ProvingKeys::load(config);
+    let keypair = load_signing_keypair(config);
+
+    for path in witnesses {
+        let contents = fs::read_to_string(path).expect("Unable to read witness file");
+        let exported: ExportedWitness = serde_json::from_str(&contents).expect("Unable to parse witness file");
+        println!("Proving witness for block {}", exported.slot);
+
+        let mut block_proof = prove_block(exported, &keys, &Cancellation::default()).expect("Proving was cancelled");
+        if let Err(e) = verify::self_check(&block_proof, &keys) {
+            eprintln!("Not publishing the proof of block {}, it does not verify: {}", block_proof.slot, e);
+            continue;
+        }
+        disclosure::withhold(&mut block_proof, config.private_dir.as_deref());
+        if let Some(keypair) = &keypair {
+            cosign::sign(&mut block_proof, keypair);
+        }
+        publish_proof(&block_proof, &config.proofs_dir);
+        uploads.publish(&block_proof);
+    }
+    uploads.close().await;
+}
+
+// Second phase of a listener run with --witness-only: proves its witnesses,
+// `prover_threads` at a time, publishing the proofs in slot order
+async fn prove_pending(config: &Config, follow: bool) {
+    let uploads = storage_uploads(config);
+    let keys = SharedKeys::new(ProvingKeys::load(config));
+    let prover = ProverPool::new(keys, config.prover_threads, memory_budget(config));
+    let keypair = load_signing_keypair(config);
+    let proofs_dir = &config.proofs_dir;
 
     loop {
-        let current_slot = client.get_slot().unwrap();
-        if current_slot > last_slot {
-            for slot in (last_slot + 1)..=current_slot {
-                if seen_blocks.contains(&slot) {
-                    continue;
-                }
+        let nullifiers = nullifier::load(proofs_dir);
+        let mut pending = Vec::new();
+        for slot in list_slots(proofs_dir, "witness_") {
+            // Proved by an earlier run that stopped before removing the witness
+            if proofs_dir.join(proof_file_name(slot)).exists() {
+                fs::remove_file(proofs_dir.join(witness_file_name(slot))).expect("Unable to remove witness file");
+            } else {
+                pending.push(slot);
+            }
+        }
+        if !pending.is_empty() {
+            info!("Proving {} pending witnesses", pending.len());
+        }
 
-                match client.get_block(slot) {
-                    Ok(block) => {
-                        let block_hash_str = block.blockhash.to_string();
-                        println!("New block created! Slot: {}, Block hash: {}", slot, block_hash_str);
-
-                        if let Some(block_hash) = str_to_fr(&block_hash_str) {
-                            let mut block_proof = BlockProof {
-                                slot,
-                                block_hash: block_hash_str.clone(),
-                                transactions: Vec::new(),
-                            };
-
-                            let mut transaction_hashes = vec![];
-
-                            for transaction_with_meta in block.transactions {
-                                if let EncodedTransaction::Json(transaction) = &transaction_with_meta.transaction {
-                                    for signature in &transaction.signatures {
-                                        let transaction_hash_str = signature.to_string();
-                                        println!("Transaction hash: {}", transaction_hash_str);
-
-                                        if let Some(transaction_hash) = str_to_fr(&transaction_hash_str) {
-                                            transaction_hashes.push(transaction_hash);
-
-                                            // Generate ZKP proof for the transaction (dummy example)
-                                            let proof = generate_block_proof(transaction_hash, transaction_hashes.clone());
-
-                                            // Add transaction proof to block proof
-                                            block_proof.transactions.push(TransactionProof {
-                                                transaction_hash: transaction_hash_str,
-                                                proof,
-                                            });
-                                        } else {
-                                            println!("Error converting transaction hash to field element: {}", transaction_hash_str);
-                                        }
-                                    }
-                                }
-                            }
-
-                            // Generate block proof
-                            let block_proof_str = generate_block_proof(block_hash, transaction_hashes);
-
-                            // Save the block proof to a JSON file
-                            save_proof_to_json(&block_proof, slot, &proofs_dir);
-
-                            seen_blocks.insert(slot);
-                        } else {
-                            println!("Error converting block hash to field element: {}", block_hash_str);
-                        }
-                    }
-                    Err(e) => {
-                        let error_message = e.to_string();
-                        if error_message.contains("Slot was skipped") || error_message.contains("Block cleaned up") {
-                            if let Some(start_index) = error_message.find("First available block: ") {
-                                if let Some(end_index) = error_message[start_index..].find(',') {
-                                    if let Ok(first_available_block) = error_message[start_index + 23..start_index + end_index].parse::<Slot>() {
-                                        last_slot = first_available_block;
-                                        println!("Adjusting to first available block: {}", first_available_block);
-                                        break;
-                                    }
-                                }
-                            }
-                        } else {
-                            eprintln!("Error fetching block {}: {:?}", slot, e);
-                        }
+        let mut proofs = stream::iter(pending)
+            .map(|slot| {
+                let contents =
+                    fs::read_to_string(proofs_dir.join(witness_file_name(slot))).expect("Unable to read witness file");
+                let exported: ExportedWitness = serde_json::from_str(&contents).expect("Unable to parse witness file");
+                let witness_hash = witness::witness_hash(&exported.block_hash, &exported.signatures);
+                let published = nullifiers.contains(&(slot, witness_hash));
+                let prover = &prover;
+                let limit = config.proving_timeout_secs.map(Duration::from_secs);
+                async move {
+                    match published {
+                        true => (slot, None),
+                        false => (slot, Some(prover.prove(exported, proofs_dir, limit).await)),
                     }
                 }
+            })
+            .buffered(config.prover_threads.max(1));
+        while let Some((slot, proved)) = proofs.next().await {
+            let witness_path = proofs_dir.join(witness_file_name(slot));
+            let mut block_proof = match proved {
+                Some(Ok(block_proof)) => block_proof,
+                None => {
+                    warn!("Block {} was already published from the same witness, removing its witness", slot);
+                    fs::remove_file(witness_path).expect("Unable to remove witness file");
+                    continue;
+                }
+                Some(Err(e @ ProveError::TimedOut(_))) => {
+                    let timed_out_path = witness_path.with_extension("timed_out.json");
+                    error!("Cancelled the proof of block {}, witness kept as {:?}: {}", slot, timed_out_path, e);
+                    fs::rename(&witness_path, timed_out_path).expect("Unable to set aside witness file");
+                    continue;
+                }
+                Some(Err(e)) => {
+                    // Set aside so it is not proved again on every pass
+                    let rejected_path = witness_path.with_extension("rejected.json");
+                    error!("Proof of block {} does not verify, witness kept as {:?}: {}", slot, rejected_path, e);
+                    fs::rename(&witness_path, rejected_path).expect("Unable to set aside witness file");
+                    continue;
+                }
+            };
+            disclosure::withhold(&mut block_proof, config.private_dir.as_deref());
+            if let Some(keypair) = &keypair {
+                cosign::sign(&mut block_proof, keypair);
+            }
+            publish_proof(&block_proof, proofs_dir);
+            uploads.publish(&block_proof);
+            fs::remove_file(witness_path).expect("Unable to remove witness file");
+        }
+
+        if !follow {
+            return uploads.close().await;
+        }
+        sleep(Duration::from_secs(1)).await;
+    }
+}
+
+fn refuse_to_start(reason: impl std::fmt::Display) -> ! {
+    error!("Refusing to start: {}", reason);
+    std::process::exit(1);
+}
+
+// Follows the configured cluster, or each of the configured instances, until
+// the process is stopped. Instances share the proving keys, the prover pool
+// and the admin API.
+async fn run_listeners(
+    config: &Config,
+    config_path: Option<&Path>,
+    witness_only: bool,
+    dry_run: bool,
+    sample_rate: u64,
+    leader_identity: Option<Pubkey>,
+) {
+    if !config.instances.is_empty() && !config.profiles.is_empty() {
+        refuse_to_start("[[profiles]] conflict with [[instances]]");
+    }
+    // Sections that act for the whole process rather than one listener
+    let whole_process = config.gossip.is_some()
+        || config.election.is_some()
+        || config.coordination.is_some()
+        || config.ethereum.is_some()
+        || config.api.is_some();
+    let mut names = HashSet::new();
+    let mut instances: Vec<(Option<&str>, Config)> = if !config.instances.is_empty() {
+        if whole_process {
+            refuse_to_start("[gossip], [election], [coordination], [ethereum] and [api] conflict with [[instances]]");
+        }
+        if let Some(instance) = config.instances.iter().find(|instance| !names.insert(&instance.name)) {
+            refuse_to_start(format!("instance {:?} is configured more than once", instance.name));
+        }
+        config
+            .instances
+            .iter()
+            .map(|instance| (Some(instance.name.as_str()), config.instance(&instance.name).unwrap()))
+            .collect()
+    } else if !config.profiles.is_empty() {
+        if whole_process {
+            refuse_to_start("[gossip], [election], [coordination], [ethereum] and [api] conflict with [[profiles]]");
+        }
+        if let Some(profile) = config.profiles.iter().find(|profile| !names.insert(&profile.name)) {
+            refuse_to_start(format!("profile {:?} is configured more than once", profile.name));
+        }
+        config
+            .profiles
+            .iter()
+            .map(|profile| (Some(profile.name.as_str()), config.profile(&profile.name).unwrap()))
+            .collect()
+    } else {
+        vec![(None, config.clone())]
+    };
+
+    // A dry run keeps its own checkpoint and index, and publishes nothing
+    if dry_run {
+        for (_, instance_config) in &mut instances {
+            instance_config.proofs_dir = dry_run::dry_run_dir(&instance_config.proofs_dir);
+            instance_config.storage = None;
+            instance_config.gossip = None;
+            instance_config.ethereum = None;
+            instance_config.mqtt = None;
+            instance_config.redis = None;
+            instance_config.amqp = None;
+            instance_config.sinks.clear();
+            info!("Dry run: recording blocks to {:?} without proving them", instance_config.proofs_dir);
+        }
+    }
+
+    // Held until the process exits, so no other listener writes to the same archive
+    let mut locks = Vec::new();
+    for (_, instance_config) in &instances {
+        match DirLock::acquire(&instance_config.proofs_dir) {
+            Ok(lock) => locks.push(lock),
+            Err(e) => refuse_to_start(e),
+        }
+    }
+
+    let mut genesis_hashes = Vec::new();
+    for (name, instance_config) in &instances {
+        match cluster::validate_genesis(instance_config) {
+            Ok(genesis_hash) => genesis_hashes.push(genesis_hash),
+            Err(e) => match name {
+                Some(name) => refuse_to_start(format!("{}: {}", name, e)),
+                None => refuse_to_start(e),
+            },
+        }
+    }
+
+    // The circuit has a fixed shape, so one set of parameters serves every block
+    let keys = (!witness_only && !dry_run).then(|| SharedKeys::new(ProvingKeys::load(config)));
+    let control = Arc::new(Control::new(keys.clone()));
+    control.reload_on_hangup();
+    if let Some(admin_config) = &config.admin {
+        admin::spawn(admin_config, control.clone());
+    }
+    let metrics = Arc::new(Metrics::default());
+    if let Some(metrics_config) = &config.metrics {
+        metrics::spawn(metrics_config, metrics.clone());
+    }
+    if let Some(api_config) = &config.api {
+        api::spawn(api_config, config.proofs_dir.clone());
+    }
+    let shared = Shared {
+        config_path: config_path.map(Path::to_path_buf),
+        prover: keys.map(|keys| ProverPool::new(keys, config.prover_threads, memory_budget(config))),
+        control,
+        metrics,
+        alerts: config.alerts.as_ref().map(|alert_config| Arc::new(Alerter::new(alert_config))),
+        sample_rate,
+        leader_identity,
+        dry_run,
+        watchdog: Watchdog::from_env(),
+        blocks: (config.profiles.len() > 1)
+            .then(|| Arc::new(BlockCache::new(config.profiles.len(), config.queue.capacity * config.profiles.len()))),
+    };
+
+    let mut listeners = Vec::new();
+    for ((name, instance_config), genesis_hash) in instances.iter().zip(genesis_hashes) {
+        info!(
+            "Following {} (websocket {}){}, genesis {}",
+            instance_config.rpc_url(),
+            instance_config.ws_url(),
+            name.map(|name| format!(" as {}", name)).unwrap_or_default(),
+            genesis_hash
+        );
+        listeners.push(Listener::new(instance_config, *name, &shared, genesis_hash).await);
+    }
+    if let Some(watchdog) = &shared.watchdog {
+        watchdog.spawn();
+    }
+    systemd::ready();
+    futures::future::join_all(listeners.iter().map(Listener::start)).await;
+}
+
+async fn summarize_epoch(config: &Config, epoch: Epoch) {
+    let storage = open_storage(config);
+    let client = rpc_client(config.rpc_url(), config.rpc_timeouts.request_secs);
+    let schedule = client.get_epoch_schedule().expect("Unable to fetch epoch schedule");
+    let params = params::load_or_generate(&config.params_path, circuit::generate_parameters);
+
+    match epoch::summarize(epoch, &schedule, &config.proofs_dir, &params, config.max_block_memory) {
+        Some(summary) => epoch::publish(&summary, &config.proofs_dir, storage.as_ref()).await,
+        None => eprintln!("No block proofs found for epoch {}", epoch),
+    }
+}
+
+async fn redeliver_dead_letters(config: &Config, only: Option<&str>) {
+    let storage: SharedStorage = Arc::new(RwLock::new(open_storage(config).map(Arc::new)));
+    let sinks: Vec<_> = sink::configured(config, &storage, None)
+        .into_iter()
+        .filter(|sink| only.is_none_or(|name| sink.name == name))
+        .collect();
+    if let Some(name) = only.filter(|_| sinks.is_empty()) {
+        eprintln!("No sink is named {:?}", name);
+        std::process::exit(1);
+    }
+
+    let mut emptied = true;
+    for sink in &sinks {
+        let store = sink::DeadLetterStore::open(&config.proofs_dir, &sink.name);
+        let pending = store.slots().len();
+        if pending == 0 {
+            println!("{}: no block proofs to redeliver", sink.name);
+            continue;
+        }
+        let (delivered, sink_emptied) = sink::redeliver(sink, &store).await;
+        println!("{}: redelivered {} of {} block proofs", sink.name, delivered, pending);
+        emptied &= sink_emptied;
+    }
+    if !emptied {
+        std::process::exit(1);
+    }
+}
+
+fn supply_history(proofs_dir: &Path, mint: &str) {
+    let mut found = false;
+
+    for slot in list_proof_slots(proofs_dir) {
+        let block_proof = load_proof(&proofs_dir.join(proof_file_name(slot)));
+        let Some(token_supply) = &block_proof.token_supply else {
+            continue;
+        };
+        if let Some(supply) = token_supply.supplies.iter().find(|supply| supply.mint == mint) {
+            let bound = if token_supply.bound { ", bound" } else { "" };
+            println!(
+                "Slot {}: {} (read at slot {}, {} decimals{})",
+                slot, supply.supply, token_supply.context_slot, supply.decimals, bound
+            );
+            found = true;
+        }
+    }
+
+    if !found {
+        eprintln!("No supply of mint {} found", mint);
+        std::process::exit(1);
+    }
+}
+
+fn program_history(proofs_dir: &Path, program_id: &str) {
+    let mut found = false;
+
+    for slot in list_proof_slots(proofs_dir) {
+        let block_proof = load_proof(&proofs_dir.join(proof_file_name(slot)));
+        for change in block_proof.program_changes.iter().flat_map(|program_changes| &program_changes.changes) {
+            if change.program_id == program_id {
+                println!("Slot {}: {:?} by {}", slot, change.kind, change.signature);
+                found = true;
             }
-            last_slot = current_slot;
         }
-        sleep(Duration::from_secs(1)).await; // Adjust the delay as needed
     }
+
+    if !found {
+        eprintln!("No changes to program {} found", program_id);
+        std::process::exit(1);
+    }
+}
+
+fn vote_history(proofs_dir: &Path, vote_account: &str) {
+    let mut found = false;
+
+    for slot in list_proof_slots(proofs_dir) {
+        let block_proof = load_proof(&proofs_dir.join(proof_file_name(slot)));
+        for vote in block_proof.votes.iter().flat_map(|votes| &votes.votes) {
+            if vote.vote_account == vote_account {
+                println!("Slot {}: voted on slot {} ({}) by {}", slot, vote.voted_slot, vote.hash, vote.signature);
+                found = true;
+            }
+        }
+    }
+
+    if !found {
+        eprintln!("No votes by {} found", vote_account);
+        std::process::exit(1);
+    }
+}
+
+// Moves `path` (`name.json`) aside to the first free `name.vN.json`
+fn archive_version(path: &Path) -> PathBuf {
+    let stem = path.file_stem().and_then(|stem| stem.to_str()).expect("Invalid artifact path");
+    let archived = (1..)
+        .map(|version| path.with_file_name(format!("{}.v{}.json", stem, version)))
+        .find(|archived| !archived.exists())
+        .unwrap();
+    fs::rename(path, &archived).expect("Unable to archive previous artifact");
+    archived
 }
 
-fn save_proof_to_json(block_proof: &BlockProof, slot: Slot, proofs_dir: &Path) {
-    let file_name = proofs_dir.join(format!("block_proof_{}.json", slot));
+// Fetches the block of `slot` from `client`, or from the archive if the
+// ledger no longer has it
+fn fetch_block(
+    client: &RpcClient,
+    archive: Option<&RpcClient>,
+    slot: Slot,
+) -> Result<EncodedConfirmedBlock, Box<ClientError>> {
+    match (client.get_block_with_config(slot, block_config()), archive) {
+        (Err(e), Some(archive)) if is_purged(&e) => archive.get_block_with_config(slot, block_config()),
+        (fetched, _) => fetched,
+    }
+    .map(EncodedConfirmedBlock::from)
+    .map_err(Box::new)
+}
+
+// Rebuilds the witness of every proved block in the range, from its snapshot
+// if one was kept and from the chain otherwise, and proves it with `keys`.
+// The leader and stake evidence of the previous proof are kept, and the new
+// proof must chain onto the same accumulator roots.
+async fn reprove(config: &Config, from_slot: Slot, to_slot: Slot, keys: &ProvingKeys) {
+    let uploads = storage_uploads(config);
+    let client = rpc_client(config.rpc_url(), config.rpc_timeouts.get_block_secs);
+    let archive = config.archive_rpc_url.clone().map(|url| rpc_client(url, config.rpc_timeouts.get_block_secs));
+    let anchor = (!config.anchor.is_empty()).then(|| AnchorDecoder::load(&config.anchor));
+    let keypair = load_signing_keypair(config);
+
+    for slot in list_proof_slots(&config.proofs_dir).into_iter().filter(|slot| (from_slot..=to_slot).contains(slot)) {
+        let proof_path = config.proofs_dir.join(proof_file_name(slot));
+        let previous = load_proof(&proof_path);
+        let old_root = fr_from_hex(&previous.old_root).expect("Invalid accumulator root in proof");
+
+        let block = match raw_blocks::load(&config.proofs_dir, slot).map(Ok) {
+            Some(snapshot) => snapshot,
+            None => fetch_block(&client, archive.as_ref(), slot),
+        };
+        let block = match block {
+            Ok(block) => block,
+            Err(e) => {
+                eprintln!("Keeping proof of block {}: unable to fetch it: {:?}", slot, e);
+                continue;
+            }
+        };
+        let anchor_records = anchor.as_ref().map(|anchor| anchor.decode_block(&block)).unwrap_or_default();
+        let (leader, token_supply) = (previous.leader.clone(), previous.token_supply.clone());
+        let mut exported = match build_block_witness(slot, block, leader, token_supply, old_root, config) {
+            Ok(exported) => exported,
+            Err(e) => {
+                eprintln!("Keeping proof of block {}: {}", slot, e);
+                continue;
+            }
+        };
+        // Salted leaves are drawn afresh, so blocks proved with selective
+        // disclosure can never reproduce their accumulator root
+        if fr_to_hex(&exported.witness.top_level().new_root()) != previous.new_root {
+            eprintln!("Keeping proof of block {}: the rebuilt witness does not match its accumulator root", slot);
+            continue;
+        }
+        for (signature, records) in anchor_records {
+            exported.annotations.entry(signature).or_default().anchor = records;
+        }
+        exported.genesis_hash = previous.genesis_hash;
+        exported.confirmation = previous.confirmation;
+        exported.account_states = previous.account_states.map(accounts::observed);
+
+        let mut block_proof = prove_block(exported, keys, &Cancellation::default()).expect("Proving was cancelled");
+        if let Err(e) = verify::self_check(&block_proof, keys) {
+            eprintln!("Keeping proof of block {}: the new proof does not verify: {}", slot, e);
+            continue;
+        }
+        let archived = archive_version(&proof_path);
+        if let Some(private_dir) = &config.private_dir {
+            let private_path = disclosure::private_path(private_dir, slot);
+            if private_path.exists() {
+                archive_version(&private_path);
+            }
+        }
+        disclosure::withhold(&mut block_proof, config.private_dir.as_deref());
+        if let Some(keypair) = &keypair {
+            cosign::sign(&mut block_proof, keypair);
+        }
+        publish_proof(&block_proof, &config.proofs_dir);
+        uploads.publish(&block_proof);
+        println!("Reproved block {}, previous proof kept as {:?}", slot, archived);
+    }
+    uploads.close().await;
+}
+
+// Holds the directory lock throughout, so a running listener is never captured
+// or overwritten halfway
+fn run_snapshot(proofs_dir: &Path, action: SnapshotAction) {
+    let _lock = DirLock::acquire(proofs_dir).unwrap_or_else(|e| {
+        eprintln!("{}; stop the listener first", e);
+        std::process::exit(1);
+    });
+    match action {
+        SnapshotAction::Create { output } => match snapshot::create(proofs_dir, &output) {
+            Ok(header) => println!(
+                "Wrote snapshot of {:?} at slot {} ({} files) to {:?}",
+                proofs_dir,
+                header.last_slot,
+                header.files.len(),
+                output
+            ),
+            Err(e) => {
+                eprintln!("Unable to create snapshot: {}", e);
+                std::process::exit(1);
+            }
+        },
+        SnapshotAction::Restore { snapshot, force } => match snapshot::restore(&snapshot, proofs_dir, force) {
+            Ok(header) => println!(
+                "Restored {:?} to slot {} with accumulator root {} ({} files)",
+                proofs_dir,
+                header.last_slot,
+                header.accumulator_root,
+                header.files.len()
+            ),
+            Err(e) => {
+                eprintln!("Unable to restore snapshot: {}", e);
+                std::process::exit(1);
+            }
+        },
+    }
+}
+
+fn run_ceremony(step: CeremonyStep) {
+    match step {
+        CeremonyStep::Init { circuit, params } => match circuit {
+            CeremonyCircuit::Block => ceremony::init(&params, circuit::generate_initial_parameters),
+            CeremonyCircuit::Sum => ceremony::init(&params, circuit::generate_initial_sum_parameters),
+            CeremonyCircuit::Totals => ceremony::init(&params, circuit::generate_initial_totals_parameters),
+            CeremonyCircuit::Threshold => ceremony::init(&params, circuit::generate_initial_threshold_parameters),
+            #[cfg(feature = "ed25519")]
+            CeremonyCircuit::Signature => ceremony::init(&params, ed25519::generate_initial_signature_parameters),
+        },
+        CeremonyStep::Contribute { params } => ceremony::contribute(&params),
+        CeremonyStep::Verify { initial, params } => match ceremony::verify(&initial, &params) {
+            Ok(contributions) => println!("OK: {:?} verifies with {} contributions", params, contributions),
+            Err(e) => {
+                println!("FAILED: {}", e);
+                std::process::exit(1);
+            }
+        },
+    }
+}
+
+// Rebuilds the witness of `slot` from its block snapshot, on the settings the
+// archived witness records, and lists where the two differ. What the listener
+// observed rather than derived from the block (genesis hash, clock drift,
+// token supply, stake confirmation and account states) is taken from the
+// archived witness.
+fn reproduce_witness(config: &Config, slot: Slot) {
+    let Some(archived) = witness_archive::load(&config.proofs_dir, slot) else {
+        eprintln!("No archived witness of block {}; witness_archive must be enabled when it is proved", slot);
+        std::process::exit(1);
+    };
+    let Some(block) = raw_blocks::load(&config.proofs_dir, slot) else {
+        eprintln!("No snapshot of block {}; block_snapshots must be enabled when it is proved", slot);
+        std::process::exit(1);
+    };
+    if !archived.salts.is_empty() {
+        eprintln!("Block {} was built with salted leaves, which are drawn afresh and cannot be reproduced", slot);
+        std::process::exit(1);
+    }
+
+    let mut settings = config.clone();
+    settings.bind_leader = archived.leader_bound;
+    settings.bind_messages = archived.messages_bound;
+    settings.commitment_hash = archived.commitment_hash;
+    settings.max_txs_per_block = archived.oversized.as_ref().map(|oversized| oversized.max_txs_per_block);
+    if let Some(oversized) = &archived.oversized {
+        settings.oversized_blocks = oversized.policy;
+    }
+    let anchor = (!config.anchor.is_empty()).then(|| AnchorDecoder::load(&config.anchor));
+    let anchor_records = anchor.as_ref().map(|anchor| anchor.decode_block(&block)).unwrap_or_default();
+    let old_root = archived.witness.top_level().old_root;
+    let (leader, token_supply) = (archived.leader.clone(), archived.token_supply.clone());
+    let mut rebuilt = match build_block_witness(slot, block, leader, token_supply, old_root, &settings) {
+        Ok(rebuilt) => rebuilt,
+        Err(e) => {
+            eprintln!("Unable to rebuild the witness of block {}: {}", slot, e);
+            std::process::exit(1);
+        }
+    };
+    for (signature, records) in anchor_records {
+        rebuilt.annotations.entry(signature).or_default().anchor = records;
+    }
+    rebuilt.genesis_hash = archived.genesis_hash.clone();
+    rebuilt.clock_drift = archived.clock_drift.clone();
+    rebuilt.confirmation = archived.confirmation.clone();
+    rebuilt.account_states = archived.account_states.clone();
+
+    let differences = witness_archive::differences(&archived, &rebuilt);
+    if differences.is_empty() {
+        println!("Witness of block {} reproduced exactly", slot);
+        return;
+    }
+    println!("Witness of block {} differs from the archived one:", slot);
+    for difference in differences {
+        println!("  {}", difference);
+    }
+    std::process::exit(1);
+}
+
+fn report_duplicate_signatures(proofs_dir: &Path) {
+    let blocks = list_proof_slots(proofs_dir).into_iter().map(|slot| {
+        let block_proof = load_proof(&proofs_dir.join(proof_file_name(slot)));
+        (slot, block_proof.transactions.into_iter().map(|transaction| transaction.transaction_hash).collect())
+    });
+    let anomalies = anomaly::scan(blocks);
+
+    println!("{}", serde_json::to_string_pretty(&anomalies).expect("Unable to serialize duplicate signatures"));
+    if !anomalies.is_empty() {
+        std::process::exit(1);
+    }
+}
+
+fn crosscheck_proofs(config: &Config, count: usize, seed: Option<u64>) {
+    let genesis_hash = cluster::validate_genesis(config).unwrap_or_else(|e| refuse_to_start(e));
+    let client = rpc_client(config.rpc_url(), config.rpc_timeouts.get_block_secs);
+    let archive = config.archive_rpc_url.clone().map(|url| rpc_client(url, config.rpc_timeouts.get_block_secs));
+    let mut keyring = Keyring::load(&keyring::keyring_dir(&config.proofs_dir));
+    if config.params_path.exists() {
+        keyring.insert_current(&params::load_verifying_key(&config.params_path));
+    }
+
+    let proved = list_proof_slots(&config.proofs_dir);
+    let seed = seed.unwrap_or_else(rand::random);
+    let mut slots = Vec::new();
+    for slot in crosscheck::sample(&proved, count, seed) {
+        let block_proof = load_proof(&config.proofs_dir.join(proof_file_name(slot)));
+        let block = fetch_block(&client, archive.as_ref(), slot).map_err(|e| e.to_string());
+        let check = crosscheck::check(&block_proof, block, &genesis_hash, &keyring, config);
+        info!("Block {}: {:?}", slot, check.status);
+        slots.push(check);
+    }
+
+    let count = |status| slots.iter().filter(|check| check.status == status).count();
+    let report = crosscheck::CrosscheckReport {
+        rpc_url: config.rpc_url(),
+        genesis_hash,
+        seed,
+        proved_slots: proved.len(),
+        passed: count(crosscheck::CheckStatus::Passed),
+        failed: count(crosscheck::CheckStatus::Failed),
+        incomplete: count(crosscheck::CheckStatus::Incomplete),
+        checked_at: SystemTime::now().duration_since(UNIX_EPOCH).expect("System clock before 1970").as_secs() as i64,
+        slots,
+    };
+    println!("{}", serde_json::to_string_pretty(&report).expect("Unable to serialize crosscheck report"));
+    if report.failed > 0 {
+        std::process::exit(1);
+    }
+}
+
+// Largest range a single getBlocks request may span
+const GET_BLOCKS_RANGE: u64 = 500_000;
+
+fn report_gaps(config: &Config, from_slot: Slot, to_slot: Slot) {
+    let client = rpc_client(config.rpc_url(), config.rpc_timeouts.request_secs);
+    let mut produced = BTreeSet::new();
+    let mut start = from_slot;
+    while start <= to_slot {
+        let end = to_slot.min(start + GET_BLOCKS_RANGE - 1);
+        produced.extend(client.get_blocks(start, Some(end)).expect("Unable to fetch produced blocks"));
+        start = end + 1;
+    }
+
+    let proved: BTreeSet<Slot> = list_proof_slots(&config.proofs_dir).into_iter().collect();
+    let index = index::load(&config.proofs_dir);
+    let manifest = Manifest::load(&config.proofs_dir).unwrap_or_default();
+    let mut leaders = BTreeMap::new();
+    if manifest.leader_identity.is_some() {
+        let mut start = from_slot;
+        while start <= to_slot {
+            let limit = LEADER_WINDOW.min(to_slot - start + 1);
+            let window = client.get_slot_leaders(start, limit).expect("Unable to fetch slot leaders");
+            leaders.extend((start..).zip(window.iter().map(Pubkey::to_string)));
+            start += limit;
+        }
+    }
+    let report = gaps::report(from_slot, to_slot, &produced, &proved, &index, &manifest, &leaders);
+
+    println!("{}", serde_json::to_string_pretty(&report).expect("Unable to serialize gap report"));
+}
+
+fn print_stats(proofs_dir: &Path, from_slot: Option<Slot>, to_slot: Option<Slot>) {
+    // Proofs written before fee statistics were recorded are skipped
+    let blocks = list_proof_slots(proofs_dir)
+        .into_iter()
+        .filter(|slot| from_slot.is_none_or(|from| *slot >= from) && to_slot.is_none_or(|to| *slot <= to))
+        .filter_map(|slot| load_proof(&proofs_dir.join(proof_file_name(slot))).fees.map(|fees| (slot, fees)));
+    let stats = fees::aggregate(blocks);
+
+    println!("{}", serde_json::to_string_pretty(&stats).expect("Unable to serialize statistics"));
+}
+
+fn print_costs(config: &Config, days: usize) {
+    let ledger = costs::load_ledger(&config.proofs_dir).unwrap_or_else(|e| {
+        eprintln!("Unable to read {:?}: {}", costs::ledger_path(&config.proofs_dir), e);
+        std::process::exit(1);
+    });
+    let report = costs::report(ledger, config.rpc_costs.as_ref(), days);
+
+    println!("{}", serde_json::to_string_pretty(&report).expect("Unable to serialize cost report"));
+}
+
+fn proof_file_name(slot: Slot) -> String {
+    format!("block_proof_{}.json", slot)
+}
+
+fn save_proof_to_json(block_proof: &BlockProof, slot: Slot, proofs_dir: &Path) -> String {
+    let file_name = proofs_dir.join(proof_file_name(slot));
     let mut file = File::create(&file_name).expect("Unable to create file");
     let json_data = serde_json::to_string_pretty(&block_proof).expect("Unable to serialize proof");
 
     file.write_all(json_data.as_bytes()).expect("Unable to write data to file");
 
-    println!("Saved block proof to {:?}", file_name);
+    info!("Saved block proof to {:?}", file_name);
+
+    json_data
+}
+
+fn witness_file_name(slot: Slot) -> String {
+    format!("witness_{}.json", slot)
+}
+
+fn save_witness_to_json(exported: &ExportedWitness, proofs_dir: &Path) {
+    let file_name = proofs_dir.join(witness_file_name(exported.slot));
+    let json_data = serde_json::to_string(exported).expect("Unable to serialize witness");
+    fs::write(&file_name, json_data).expect("Unable to write witness file");
+
+    info!("Saved block witness to {:?}", file_name);
 }
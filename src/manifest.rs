@@ -0,0 +1,45 @@
+use serde::{Deserialize, Serialize};
+use solana_sdk::clock::Slot;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// Proving policy of the archive, kept as `manifest.json` in the proofs
+// directory so consumers know which slots are expected to have a proof
+#[derive(Serialize, Deserialize, PartialEq)]
+pub struct Manifest {
+    // Only slots divisible by `sample_rate` are proved; 1 proves every slot
+    pub sample_rate: u64,
+    // Only slots led by this validator identity are proved, when set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub leader_identity: Option<String>,
+    // Genesis hash of the cluster the archive's blocks come from
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub genesis_hash: Option<String>,
+}
+
+impl Default for Manifest {
+    fn default() -> Self {
+        Manifest { sample_rate: 1, leader_identity: None, genesis_hash: None }
+    }
+}
+
+fn manifest_path(proofs_dir: &Path) -> PathBuf {
+    proofs_dir.join("manifest.json")
+}
+
+impl Manifest {
+    // `None` for an archive written before manifests were kept
+    pub fn load(proofs_dir: &Path) -> Option<Manifest> {
+        let contents = fs::read_to_string(manifest_path(proofs_dir)).ok()?;
+        Some(serde_json::from_str(&contents).expect("Unable to parse manifest"))
+    }
+
+    pub fn save(&self, proofs_dir: &Path) {
+        let json_data = serde_json::to_string_pretty(self).expect("Unable to serialize manifest");
+        fs::write(manifest_path(proofs_dir), json_data).expect("Unable to write manifest");
+    }
+
+    pub fn is_sampled(&self, slot: Slot) -> bool {
+        slot.is_multiple_of(self.sample_rate)
+    }
+}
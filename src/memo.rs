@@ -0,0 +1,39 @@
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use solana_transaction_status::EncodedConfirmedBlock;
+
+use crate::instructions;
+
+const MEMO_PROGRAM_IDS: [&str; 2] = [
+    "MemoSq4gqABAXKb96qnH8TysNcWxMyWCqXgDLGmfcHr",
+    "Memo1UhkJRfHyvLMcVucJwxXeuD8YZEv7Qm",
+];
+
+// Data posted through the memo program. `hash` is the SHA-256 of the raw memo
+// bytes, so the content can be checked even where the text is not valid UTF-8.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Memo {
+    pub text: String,
+    pub hash: String,
+}
+
+// Memos per transaction signature, for the transactions that posted any
+pub fn block_memos(block: &EncodedConfirmedBlock) -> Vec<(String, Vec<Memo>)> {
+    block
+        .transactions
+        .iter()
+        .filter_map(|transaction_with_meta| {
+            let transaction = instructions::decode(transaction_with_meta)?;
+            let memos: Vec<Memo> = transaction
+                .instructions
+                .iter()
+                .filter(|instruction| MEMO_PROGRAM_IDS.contains(&instruction.program_id.as_str()))
+                .map(|instruction| Memo {
+                    text: String::from_utf8_lossy(&instruction.data).into_owned(),
+                    hash: hex::encode(Sha256::digest(&instruction.data)),
+                })
+                .collect();
+            (!memos.is_empty()).then_some((transaction.signature, memos))
+        })
+        .collect()
+}
@@ -0,0 +1,175 @@
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use solana_sdk::blake3;
+use solana_sdk::poseidon::{self, Endianness, Parameters};
+
+use crate::config::CommitmentHash;
+
+// One step of an inclusion path, from the leaf towards the root
+#[derive(Serialize, Deserialize, Clone)]
+pub struct MerkleStep {
+    pub sibling: String,
+    pub sibling_on_left: bool,
+}
+
+// Merkle tree over a block's transaction hashes. Leaves and inner nodes are
+// hashed with distinct prefixes, and an unpaired node is carried up to the next
+// level unchanged rather than being duplicated.
+pub struct MerkleTree {
+    levels: Vec<Vec<[u8; 32]>>,
+}
+
+// Bytes of each Poseidon input, small enough to always be below the BN254 modulus
+const POSEIDON_INPUT_BYTES: usize = 16;
+
+// Poseidon of `prefix` and then `data` in inputs of `POSEIDON_INPUT_BYTES`. The
+// prefix holds the leaf or node tag and the data length, so data with leading
+// zeroes gives other inputs than data without.
+fn poseidon_hash(prefix: u8, data: &[u8]) -> [u8; 32] {
+    let prefix = [[prefix].as_slice(), &(data.len() as u64).to_be_bytes()].concat();
+    let inputs: Vec<&[u8]> = [prefix.as_slice()].into_iter().chain(data.chunks(POSEIDON_INPUT_BYTES)).collect();
+    poseidon::hashv(Parameters::Bn254X5, Endianness::BigEndian, &inputs)
+        .expect("Data too long for a Poseidon leaf")
+        .to_bytes()
+}
+
+fn hash_prefixed(hash: CommitmentHash, prefix: u8, data: &[u8]) -> [u8; 32] {
+    match hash {
+        CommitmentHash::Sha256 => {
+            let mut hasher = Sha256::new();
+            hasher.update([prefix]);
+            hasher.update(data);
+            hasher.finalize().into()
+        }
+        CommitmentHash::Blake3 => blake3::hashv(&[&[prefix], data]).to_bytes(),
+        CommitmentHash::Poseidon => poseidon_hash(prefix, data),
+    }
+}
+
+fn hash_leaf(hash: CommitmentHash, leaf: &[u8]) -> [u8; 32] {
+    hash_prefixed(hash, 0, leaf)
+}
+
+fn hash_nodes(hash: CommitmentHash, left: &[u8; 32], right: &[u8; 32]) -> [u8; 32] {
+    hash_prefixed(hash, 1, &[left.as_slice(), right].concat())
+}
+
+impl MerkleTree {
+    pub fn new<T: AsRef<[u8]>>(hash: CommitmentHash, leaves: &[T]) -> Self {
+        let mut levels = vec![leaves.iter().map(|leaf| hash_leaf(hash, leaf.as_ref())).collect::<Vec<_>>()];
+
+        while levels.last().unwrap().len() > 1 {
+            let level = levels.last().unwrap();
+            let next = level
+                .chunks(2)
+                .map(|pair| match pair {
+                    [left, right] => hash_nodes(hash, left, right),
+                    [single] => *single,
+                    _ => unreachable!(),
+                })
+                .collect();
+            levels.push(next);
+        }
+
+        MerkleTree { levels }
+    }
+
+    // Root of the tree; all zeroes for an empty block
+    pub fn root(&self) -> [u8; 32] {
+        self.levels.last().and_then(|level| level.first().copied()).unwrap_or([0u8; 32])
+    }
+
+    pub fn path(&self, mut index: usize) -> Vec<MerkleStep> {
+        let mut path = Vec::new();
+
+        for level in &self.levels[..self.levels.len() - 1] {
+            let sibling_index = index ^ 1;
+            if let Some(sibling) = level.get(sibling_index) {
+                path.push(MerkleStep {
+                    sibling: hex::encode(sibling),
+                    sibling_on_left: sibling_index < index,
+                });
+            }
+            index /= 2;
+        }
+
+        path
+    }
+}
+
+fn decode_sibling(step: &MerkleStep) -> Option<[u8; 32]> {
+    hex::decode(&step.sibling).ok()?.try_into().ok()
+}
+
+fn hash_step(hash: CommitmentHash, node: &[u8; 32], sibling: &[u8; 32], sibling_on_left: bool) -> [u8; 32] {
+    if sibling_on_left {
+        hash_nodes(hash, sibling, node)
+    } else {
+        hash_nodes(hash, node, sibling)
+    }
+}
+
+// Root reached by following `path` up from `leaf`, without checking the
+// leaf's position
+pub fn fold_path(hash: CommitmentHash, leaf: &[u8], path: &[MerkleStep]) -> Option<[u8; 32]> {
+    path.iter().try_fold(hash_leaf(hash, leaf), |node, step| {
+        Some(hash_step(hash, &node, &decode_sibling(step)?, step.sibling_on_left))
+    })
+}
+
+// Whether the sides of the siblings along `path` agree with a leaf at `index`,
+// for when the size of the tree is not known. A node without a sibling is the
+// last of its level and carried up without a step, and so are its ancestors,
+// which have no right siblings.
+pub fn path_matches_index(mut index: usize, path: &[MerkleStep]) -> bool {
+    let mut steps = path.iter().peekable();
+    let mut last = false;
+    for _ in 0..usize::BITS {
+        match steps.peek() {
+            Some(step) if step.sibling_on_left == (index % 2 == 1) && (step.sibling_on_left || !last) => {
+                steps.next();
+            }
+            _ if index % 2 == 1 => return false,
+            _ => last = true,
+        }
+        index /= 2;
+    }
+    steps.next().is_none()
+}
+
+// Checks that `path` leads from `leaf` at `index` to `root` in a tree of
+// `leaf_count` leaves. The shape of a path is fixed by the index and the tree
+// size, so a valid path also proves the leaf's position.
+pub fn verify_path(
+    hash: CommitmentHash,
+    root: &[u8; 32],
+    leaf: &[u8],
+    mut index: usize,
+    mut leaf_count: usize,
+    path: &[MerkleStep],
+) -> bool {
+    if index >= leaf_count {
+        return false;
+    }
+
+    let mut node = hash_leaf(hash, leaf);
+    let mut steps = path.iter();
+    while leaf_count > 1 {
+        if index ^ 1 < leaf_count {
+            let Some(step) = steps.next() else {
+                return false;
+            };
+            let Some(sibling) = decode_sibling(step) else {
+                return false;
+            };
+            if step.sibling_on_left != (index % 2 == 1) {
+                return false;
+            }
+            node = hash_step(hash, &node, &sibling, step.sibling_on_left);
+        }
+        index /= 2;
+        leaf_count = leaf_count.div_ceil(2);
+    }
+
+    steps.next().is_none() && node == *root
+}
@@ -0,0 +1,501 @@
+use hyper::header::CONTENT_TYPE;
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Method, Request, Response, Server, StatusCode};
+use log::{error, info};
+use serde_json::json;
+use solana_sdk::clock::Slot;
+use std::collections::BTreeMap;
+use std::convert::Infallible;
+use std::fmt::Write;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use crate::config::MetricsConfig;
+use crate::costs::{self, ProviderUsage};
+use crate::memory;
+use crate::latency::ProofLatency;
+
+#[derive(Default)]
+struct InstanceMetrics {
+    proofs: u64,
+    slo_violations: u64,
+    last_latency_secs: Option<u64>,
+    // Blocks fetched ahead of the prover; `None` for instances without a queue
+    queued_blocks: Option<u64>,
+    spilled_blocks: Option<u64>,
+    // Blocks not proved again because their slot and witness were already published
+    nullifier_conflicts: u64,
+    // Transaction signatures seen in more than one block since the start
+    duplicate_signatures: u64,
+    // Latest slot of the endpoint, and the highest slot proved
+    tip_slot: Option<Slot>,
+    proved_slot: Option<Slot>,
+    // Slots recorded as failed or unfetchable in the index since the start
+    failed_slots: u64,
+    // Slots the endpoint reported as skipped since the start
+    skipped_slots: u64,
+    // Proof files in the proofs directory and their total size
+    proof_files: Option<u64>,
+    proof_bytes: Option<u64>,
+    // Deliveries to each proof sink, by sink name
+    sinks: BTreeMap<String, SinkMetrics>,
+    // Restarts of the listener's tasks after a panic, by task
+    restarts: BTreeMap<&'static str, u64>,
+}
+
+#[derive(Default)]
+struct SinkMetrics {
+    // Proofs waiting for the sink, including the one being delivered
+    queued: u64,
+    delivered: u64,
+    failed_attempts: u64,
+    // Proofs given up on after every attempt and sent to the sink's own
+    // dead-letter destination
+    dead_lettered: u64,
+    // Proofs kept in the sink's dead-letter store, those since redelivered
+    // from it and those still there
+    stored: u64,
+    redelivered: u64,
+    dead_letters: u64,
+}
+
+pub enum SinkEvent {
+    Queued,
+    Delivered,
+    FailedAttempt,
+    DeadLettered,
+    Stored,
+    Redelivered,
+}
+
+// Families labelled by sink
+struct SinkFamily {
+    name: &'static str,
+    kind: &'static str,
+    help: &'static str,
+    value: fn(&SinkMetrics) -> u64,
+}
+
+const SINK_FAMILIES: [SinkFamily; 7] = [
+    SinkFamily {
+        name: "solana_listener_sink_queued_proofs",
+        kind: "gauge",
+        help: "Block proofs waiting for a sink",
+        value: |sink| sink.queued,
+    },
+    SinkFamily {
+        name: "solana_listener_sink_delivered_total",
+        kind: "counter",
+        help: "Block proofs a sink accepted",
+        value: |sink| sink.delivered,
+    },
+    SinkFamily {
+        name: "solana_listener_sink_failed_attempts_total",
+        kind: "counter",
+        help: "Attempts to deliver a block proof to a sink that failed",
+        value: |sink| sink.failed_attempts,
+    },
+    SinkFamily {
+        name: "solana_listener_sink_dead_lettered_total",
+        kind: "counter",
+        help: "Block proofs sent to a sink's dead-letter destination after every attempt failed",
+        value: |sink| sink.dead_lettered,
+    },
+    SinkFamily {
+        name: "solana_listener_sink_stored_total",
+        kind: "counter",
+        help: "Block proofs kept in a sink's dead-letter store while it was down",
+        value: |sink| sink.stored,
+    },
+    SinkFamily {
+        name: "solana_listener_sink_redelivered_total",
+        kind: "counter",
+        help: "Block proofs redelivered from a sink's dead-letter store",
+        value: |sink| sink.redelivered,
+    },
+    SinkFamily {
+        name: "solana_listener_sink_dead_letters",
+        kind: "gauge",
+        help: "Block proofs in a sink's dead-letter store",
+        value: |sink| sink.dead_letters,
+    },
+];
+
+// Families labelled by RPC provider, from its usage since the process
+// started, the credits it has used today and its daily budget
+struct ProviderFamily {
+    name: &'static str,
+    kind: &'static str,
+    help: &'static str,
+    value: fn(&ProviderUsage, u64, Option<u64>) -> Option<u64>,
+}
+
+const PROVIDER_FAMILIES: [ProviderFamily; 3] = [
+    ProviderFamily {
+        name: "solana_listener_rpc_credits_total",
+        kind: "counter",
+        help: "Credits the RPC calls made to a provider cost",
+        value: |usage, _, _| Some(usage.credits),
+    },
+    ProviderFamily {
+        name: "solana_listener_rpc_daily_credits",
+        kind: "gauge",
+        help: "Credits used at an RPC provider in the UTC day so far, by every process sharing the ledger",
+        value: |_, today, _| Some(today),
+    },
+    ProviderFamily {
+        name: "solana_listener_rpc_daily_budget",
+        kind: "gauge",
+        help: "Credits an RPC provider may use in a UTC day before calls to it are spaced out",
+        value: |_, _, budget| budget,
+    },
+];
+
+struct Family {
+    name: &'static str,
+    kind: &'static str,
+    help: &'static str,
+    // `None` while the instance has no value to report
+    value: fn(&InstanceMetrics) -> Option<u64>,
+}
+
+const FAMILIES: [Family; 13] = [
+    Family {
+        name: "solana_listener_proofs_total",
+        kind: "counter",
+        help: "Block proofs produced",
+        value: |metrics| Some(metrics.proofs),
+    },
+    Family {
+        name: "solana_listener_latency_slo_violations_total",
+        kind: "counter",
+        help: "Block proofs completed later than the latency SLO allows",
+        value: |metrics| Some(metrics.slo_violations),
+    },
+    Family {
+        name: "solana_listener_proof_latency_seconds",
+        kind: "gauge",
+        help: "Seconds from the timestamp of the last proved block to its proof",
+        value: |metrics| metrics.last_latency_secs,
+    },
+    Family {
+        name: "solana_listener_queued_blocks",
+        kind: "gauge",
+        help: "Fetched blocks held in memory waiting for the prover",
+        value: |metrics| metrics.queued_blocks,
+    },
+    Family {
+        name: "solana_listener_spilled_blocks",
+        kind: "gauge",
+        help: "Fetched blocks spilled to disk waiting for the prover",
+        value: |metrics| metrics.spilled_blocks,
+    },
+    Family {
+        name: "solana_listener_nullifier_conflicts_total",
+        kind: "counter",
+        help: "Blocks not proved again because a proof from the same witness was already published",
+        value: |metrics| Some(metrics.nullifier_conflicts),
+    },
+    Family {
+        name: "solana_listener_duplicate_signatures_total",
+        kind: "counter",
+        help: "Transaction signatures seen in more than one block, or twice in one block",
+        value: |metrics| Some(metrics.duplicate_signatures),
+    },
+    Family {
+        name: "solana_listener_tip_slot",
+        kind: "gauge",
+        help: "Latest slot of the RPC endpoint",
+        value: |metrics| metrics.tip_slot,
+    },
+    Family {
+        name: "solana_listener_proved_slot",
+        kind: "gauge",
+        help: "Highest slot proved",
+        value: |metrics| metrics.proved_slot,
+    },
+    Family {
+        name: "solana_listener_failed_slots_total",
+        kind: "counter",
+        help: "Slots recorded as failed or unfetchable in the slot index",
+        value: |metrics| Some(metrics.failed_slots),
+    },
+    Family {
+        name: "solana_listener_skipped_slots_total",
+        kind: "counter",
+        help: "Slots recorded in the slot index as skipped by the cluster, with no block to prove",
+        value: |metrics| Some(metrics.skipped_slots),
+    },
+    Family {
+        name: "solana_listener_proof_files",
+        kind: "gauge",
+        help: "Block proof files in the proofs directory",
+        value: |metrics| metrics.proof_files,
+    },
+    Family {
+        name: "solana_listener_proof_bytes",
+        kind: "gauge",
+        help: "Total size of the block proof files in the proofs directory",
+        value: |metrics| metrics.proof_bytes,
+    },
+];
+
+// Counters of the listeners of the process, served in the Prometheus text
+// format and summarized as JSON by `GET /status`. Instances of a
+// multi-cluster process are told apart by an `instance` label.
+pub struct Metrics {
+    started: Instant,
+    instances: Mutex<BTreeMap<Option<String>, InstanceMetrics>>,
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Metrics { started: Instant::now(), instances: Mutex::default() }
+    }
+}
+
+impl Metrics {
+    pub fn record_proof(&self, instance: Option<&str>, slot: Slot, latency: Option<&ProofLatency>) {
+        let mut instances = self.instances.lock().unwrap();
+        let metrics = instances.entry(instance.map(str::to_string)).or_default();
+        metrics.proofs += 1;
+        metrics.proved_slot = metrics.proved_slot.max(Some(slot));
+        if let Some(latency) = latency {
+            metrics.last_latency_secs = Some(latency.latency_secs);
+            metrics.slo_violations += latency.slo_violated as u64;
+        }
+    }
+
+    pub fn record_queue_depth(&self, instance: Option<&str>, (queued, spilled): (usize, usize)) {
+        let mut instances = self.instances.lock().unwrap();
+        let metrics = instances.entry(instance.map(str::to_string)).or_default();
+        metrics.queued_blocks = Some(queued as u64);
+        metrics.spilled_blocks = Some(spilled as u64);
+    }
+
+    pub fn record_tip(&self, instance: Option<&str>, slot: Slot) {
+        let mut instances = self.instances.lock().unwrap();
+        let metrics = instances.entry(instance.map(str::to_string)).or_default();
+        metrics.tip_slot = metrics.tip_slot.max(Some(slot));
+    }
+
+    pub fn record_failed_slot(&self, instance: Option<&str>) {
+        let mut instances = self.instances.lock().unwrap();
+        instances.entry(instance.map(str::to_string)).or_default().failed_slots += 1;
+    }
+
+    pub fn record_skipped_slot(&self, instance: Option<&str>) {
+        let mut instances = self.instances.lock().unwrap();
+        instances.entry(instance.map(str::to_string)).or_default().skipped_slots += 1;
+    }
+
+    // Proof files found in the proofs directory at startup, with the highest
+    // slot among them
+    pub fn record_storage(&self, instance: Option<&str>, files: u64, bytes: u64, proved_slot: Option<Slot>) {
+        let mut instances = self.instances.lock().unwrap();
+        let metrics = instances.entry(instance.map(str::to_string)).or_default();
+        metrics.proof_files = Some(files);
+        metrics.proof_bytes = Some(bytes);
+        metrics.proved_slot = metrics.proved_slot.max(proved_slot);
+    }
+
+    pub fn record_proof_file(&self, instance: Option<&str>, bytes: u64) {
+        let mut instances = self.instances.lock().unwrap();
+        let metrics = instances.entry(instance.map(str::to_string)).or_default();
+        metrics.proof_files = Some(metrics.proof_files.unwrap_or_default() + 1);
+        metrics.proof_bytes = Some(metrics.proof_bytes.unwrap_or_default() + bytes);
+    }
+
+    pub fn record_nullifier_conflict(&self, instance: Option<&str>) {
+        let mut instances = self.instances.lock().unwrap();
+        instances.entry(instance.map(str::to_string)).or_default().nullifier_conflicts += 1;
+    }
+
+    pub fn record_duplicate_signature(&self, instance: Option<&str>) {
+        let mut instances = self.instances.lock().unwrap();
+        instances.entry(instance.map(str::to_string)).or_default().duplicate_signatures += 1;
+    }
+
+    pub fn record_sink(&self, instance: Option<&str>, sink: &str, event: SinkEvent) {
+        let mut instances = self.instances.lock().unwrap();
+        let metrics = instances.entry(instance.map(str::to_string)).or_default();
+        let sink = metrics.sinks.entry(sink.to_string()).or_default();
+        match event {
+            SinkEvent::Queued => sink.queued += 1,
+            SinkEvent::Delivered => {
+                sink.queued -= 1;
+                sink.delivered += 1;
+            }
+            SinkEvent::FailedAttempt => sink.failed_attempts += 1,
+            SinkEvent::DeadLettered => {
+                sink.queued -= 1;
+                sink.dead_lettered += 1;
+            }
+            SinkEvent::Stored => {
+                sink.queued -= 1;
+                sink.stored += 1;
+            }
+            SinkEvent::Redelivered => sink.redelivered += 1,
+        }
+    }
+
+    pub fn record_sink_dead_letters(&self, instance: Option<&str>, sink: &str, dead_letters: usize) {
+        let mut instances = self.instances.lock().unwrap();
+        let metrics = instances.entry(instance.map(str::to_string)).or_default();
+        metrics.sinks.entry(sink.to_string()).or_default().dead_letters = dead_letters as u64;
+    }
+
+    pub fn record_restart(&self, instance: Option<&str>, task: &'static str) {
+        let mut instances = self.instances.lock().unwrap();
+        let metrics = instances.entry(instance.map(str::to_string)).or_default();
+        *metrics.restarts.entry(task).or_default() += 1;
+    }
+
+    fn render(&self) -> String {
+        let instances = self.instances.lock().unwrap();
+        let mut output = String::new();
+        for family in &FAMILIES {
+            writeln!(output, "# HELP {} {}", family.name, family.help).unwrap();
+            writeln!(output, "# TYPE {} {}", family.name, family.kind).unwrap();
+            for (instance, metrics) in instances.iter() {
+                if let Some(value) = (family.value)(metrics) {
+                    write_sample(&mut output, family.name, instance.as_deref(), &[], value);
+                }
+            }
+        }
+
+        let name = "solana_listener_allocated_bytes";
+        writeln!(output, "# HELP {} Bytes allocated on the heap by the process", name).unwrap();
+        writeln!(output, "# TYPE {} gauge", name).unwrap();
+        write_sample(&mut output, name, None, &[], memory::allocated() as u64);
+
+        let name = "solana_listener_task_restarts_total";
+        writeln!(output, "# HELP {} Listener tasks restarted after panicking", name).unwrap();
+        writeln!(output, "# TYPE {} counter", name).unwrap();
+        for (instance, metrics) in instances.iter() {
+            for (task, restarts) in &metrics.restarts {
+                write_sample(&mut output, name, instance.as_deref(), &[("task", task)], *restarts);
+            }
+        }
+
+        for family in &SINK_FAMILIES {
+            writeln!(output, "# HELP {} {}", family.name, family.help).unwrap();
+            writeln!(output, "# TYPE {} {}", family.name, family.kind).unwrap();
+            for (instance, metrics) in instances.iter() {
+                for (sink, sink_metrics) in &metrics.sinks {
+                    let value = (family.value)(sink_metrics);
+                    write_sample(&mut output, family.name, instance.as_deref(), &[("sink", sink)], value);
+                }
+            }
+        }
+
+        // RPC usage is counted for the whole process, by provider
+        let usage = costs::usage();
+        let name = "solana_listener_rpc_calls_total";
+        writeln!(output, "# HELP {} RPC calls made to a provider, by method", name).unwrap();
+        writeln!(output, "# TYPE {} counter", name).unwrap();
+        for (provider, totals, _, _) in &usage {
+            for (method, calls) in &totals.calls {
+                write_sample(&mut output, name, None, &[("provider", provider), ("method", method)], *calls);
+            }
+        }
+        for family in &PROVIDER_FAMILIES {
+            writeln!(output, "# HELP {} {}", family.name, family.help).unwrap();
+            writeln!(output, "# TYPE {} {}", family.name, family.kind).unwrap();
+            for (provider, totals, today, budget) in &usage {
+                if let Some(value) = (family.value)(totals, *today, *budget) {
+                    write_sample(&mut output, family.name, None, &[("provider", provider)], value);
+                }
+            }
+        }
+        output
+    }
+}
+
+impl Metrics {
+    // Catch-up state of each instance, for dashboards
+    fn status(&self) -> serde_json::Value {
+        let instances = self.instances.lock().unwrap();
+        let instances: Vec<_> = instances
+            .iter()
+            .map(|(instance, metrics)| {
+                let sinks: BTreeMap<_, _> = metrics
+                    .sinks
+                    .iter()
+                    .map(|(name, sink)| (name, json!({ "queued": sink.queued, "dead_letters": sink.dead_letters })))
+                    .collect();
+                let lag_slots = metrics.tip_slot.zip(metrics.proved_slot).map(|(tip, proved)| tip - proved.min(tip));
+                json!({
+                    "instance": instance,
+                    "tip_slot": metrics.tip_slot,
+                    "proved_slot": metrics.proved_slot,
+                    "lag_slots": lag_slots,
+                    "proofs": metrics.proofs,
+                    "failed_slots": metrics.failed_slots,
+                    "skipped_slots": metrics.skipped_slots,
+                    "duplicate_signatures": metrics.duplicate_signatures,
+                    "queued_blocks": metrics.queued_blocks,
+                    "spilled_blocks": metrics.spilled_blocks,
+                    "sinks": sinks,
+                    "storage": { "proof_files": metrics.proof_files, "proof_bytes": metrics.proof_bytes },
+                })
+            })
+            .collect();
+        let rpc_costs: BTreeMap<_, _> = costs::usage()
+            .into_iter()
+            .map(|(provider, _, today, budget)| (provider, json!({ "daily_credits": today, "daily_budget": budget })))
+            .collect();
+        json!({
+            "uptime_secs": self.started.elapsed().as_secs(),
+            "allocated_bytes": memory::allocated(),
+            "instances": instances,
+            "rpc_costs": rpc_costs,
+        })
+    }
+}
+
+// A sample labelled with its instance, if any, and `labels`
+fn write_sample(output: &mut String, name: &str, instance: Option<&str>, labels: &[(&str, &str)], value: u64) {
+    let labels: Vec<String> = instance
+        .map(|instance| ("instance", instance))
+        .into_iter()
+        .chain(labels.iter().copied())
+        .map(|(label, value)| format!("{}=\"{}\"", label, value))
+        .collect();
+    match labels.is_empty() {
+        true => writeln!(output, "{} {}", name, value),
+        false => writeln!(output, "{}{{{}}} {}", name, labels.join(","), value),
+    }
+    .unwrap();
+}
+
+async fn handle(request: Request<Body>, metrics: Arc<Metrics>) -> Result<Response<Body>, Infallible> {
+    let response = match (request.method(), request.uri().path()) {
+        (&Method::GET, "/metrics") => Response::builder()
+            .header(CONTENT_TYPE, "text/plain; version=0.0.4")
+            .body(Body::from(metrics.render())),
+        (&Method::GET, "/status") => Response::builder()
+            .header(CONTENT_TYPE, "application/json")
+            .body(Body::from(metrics.status().to_string())),
+        _ => Response::builder().status(StatusCode::NOT_FOUND).body(Body::empty()),
+    };
+    Ok(response.unwrap())
+}
+
+// Serves `GET /metrics` and `GET /status` in the background
+pub fn spawn(config: &MetricsConfig, metrics: Arc<Metrics>) {
+    let addr: SocketAddr = config.listen_addr.parse().expect("Invalid metrics listen address");
+    let make_service = make_service_fn(move |_| {
+        let metrics = metrics.clone();
+        async move { Ok::<_, Infallible>(service_fn(move |request| handle(request, metrics.clone()))) }
+    });
+    let server = Server::try_bind(&addr).expect("Unable to bind metrics endpoint").serve(make_service);
+    info!("Serving metrics on {}", addr);
+
+    tokio::spawn(async move {
+        if let Err(e) = server.await {
+            error!("Metrics endpoint stopped: {:?}", e);
+        }
+    });
+}
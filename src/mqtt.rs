@@ -0,0 +1,114 @@
+use futures::future::{BoxFuture, FutureExt};
+use log::error;
+use rumqttc::{AsyncClient, ClientError, MqttOptions, QoS, Transport};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use solana_sdk::clock::Slot;
+use std::fmt;
+use tokio::time::{sleep, Duration};
+
+use crate::config::MqttConfig;
+use crate::sink::ProofSink;
+use crate::{proof_file_name, BlockProof};
+
+// Summaries queued for the broker before delivery waits for room
+const QUEUE_CAPACITY: usize = 64;
+
+#[derive(Debug)]
+pub enum MqttError {
+    InvalidQos(u8),
+    Publish(ClientError),
+}
+
+impl fmt::Display for MqttError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MqttError::InvalidQos(qos) => write!(f, "QoS {} is not 0, 1 or 2", qos),
+            MqttError::Publish(e) => write!(f, "unable to publish: {}", e),
+        }
+    }
+}
+
+pub fn parse_qos(qos: u8) -> Result<QoS, MqttError> {
+    rumqttc::qos(qos).map_err(|_| MqttError::InvalidQos(qos))
+}
+
+// What devices receive for each block proof: enough to fetch the proof file
+// and check it is the one attested to
+#[derive(Serialize)]
+struct ProofSummary<'a> {
+    slot: Slot,
+    block_hash: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    genesis_hash: Option<&'a str>,
+    // SHA-256 of the proof file
+    proof_hash: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    url: Option<String>,
+}
+
+// Publishes block proof summaries to an MQTT broker. The connection is driven
+// in the background and re-established after the broker goes away; summaries
+// made while it is down are queued, up to QUEUE_CAPACITY, and delivery waits
+// beyond that.
+pub struct MqttPublisher {
+    client: AsyncClient,
+    config: MqttConfig,
+    qos: QoS,
+}
+
+impl MqttPublisher {
+    // Instances of a multi-cluster listener connect with their name appended
+    // to the client ID, as a broker drops a client whose ID connects again
+    pub fn start(config: &MqttConfig, instance: Option<&str>) -> Result<Self, MqttError> {
+        let qos = parse_qos(config.qos)?;
+        let client_id = match instance {
+            Some(instance) => format!("{}-{}", config.client_id, instance),
+            None => config.client_id.clone(),
+        };
+        let mut options = MqttOptions::new(client_id, &config.host, config.port);
+        options.set_keep_alive(Duration::from_secs(30));
+        if let Some(username) = &config.username {
+            options.set_credentials(username, config.password.as_deref().unwrap_or_default());
+        }
+        if config.tls {
+            options.set_transport(Transport::tls_with_default_config());
+        }
+
+        let (client, mut event_loop) = AsyncClient::new(options, QUEUE_CAPACITY);
+        let broker = format!("{}:{}", config.host, config.port);
+        tokio::spawn(async move {
+            loop {
+                if let Err(e) = event_loop.poll().await {
+                    error!("Lost the connection to MQTT broker {}: {}", broker, e);
+                    sleep(Duration::from_secs(5)).await;
+                }
+            }
+        });
+
+        Ok(MqttPublisher { client, config: config.clone(), qos })
+    }
+}
+
+impl ProofSink for MqttPublisher {
+    fn deliver<'a>(&'a self, block_proof: &'a BlockProof) -> BoxFuture<'a, Result<(), String>> {
+        // Hashed as written to the proofs directory
+        let json_data = serde_json::to_string_pretty(block_proof).expect("Unable to serialize proof");
+        let summary = ProofSummary {
+            slot: block_proof.slot,
+            block_hash: &block_proof.block_hash,
+            genesis_hash: block_proof.genesis_hash.as_deref(),
+            proof_hash: hex::encode(Sha256::digest(json_data.as_bytes())),
+            url: self.config.proof_url.as_ref().map(|base| {
+                format!("{}/{}", base.trim_end_matches('/'), proof_file_name(block_proof.slot))
+            }),
+        };
+        let payload = serde_json::to_vec(&summary).expect("Unable to serialize proof summary");
+
+        async move {
+            let publish = self.client.publish(&self.config.topic, self.qos, self.config.retain, payload);
+            publish.await.map_err(|e| MqttError::Publish(e).to_string())
+        }
+        .boxed()
+    }
+}
@@ -0,0 +1,182 @@
+use serde::{Deserialize, Serialize};
+use solana_sdk::pubkey::Pubkey;
+use solana_transaction_status::EncodedConfirmedBlock;
+
+use crate::instructions::{self, Instruction};
+use crate::token::TOKEN_PROGRAM_IDS;
+
+const TOKEN_METADATA_PROGRAM_ID: &str = "metaqbxxUerdq28cj1RbAWkYQm3ybzjb6a8bt518x1s";
+
+// Token metadata program instruction tags
+const CREATE_MASTER_EDITION_V3: u8 = 17;
+const VERIFY_COLLECTION: u8 = 18;
+const SET_AND_VERIFY_COLLECTION: u8 = 25;
+const VERIFY_SIZED_COLLECTION_ITEM: u8 = 30;
+const SET_AND_VERIFY_SIZED_COLLECTION_ITEM: u8 = 32;
+const CREATE_METADATA_ACCOUNT_V3: u8 = 33;
+const CREATE: u8 = 42;
+const MINT: u8 = 43;
+const VERIFY: u8 = 52;
+
+// Token program instruction tags
+const INITIALIZE_MINT: u8 = 0;
+const MINT_TO: u8 = 7;
+const MINT_TO_CHECKED: u8 = 14;
+const INITIALIZE_MINT_2: u8 = 20;
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum NftEventKind {
+    CreateMetadata,
+    CreateMasterEdition,
+    Mint,
+    VerifyCollection,
+    // Token program events, recorded for zero-decimal mints and for
+    // transactions that also used the token metadata program
+    InitializeMint,
+    MintTo,
+}
+
+// NFT activity of a transaction: the mint and metadata account it concerns
+// and, where the instruction names it, the collection (mint) it belongs to.
+// Collection verification only lists the metadata account, not the mint.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct NftEvent {
+    pub kind: NftEventKind,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mint: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub collection: Option<String>,
+}
+
+// Borsh fields of metadata instruction arguments, read up to the collection
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Option<&'a [u8]> {
+        if self.0.len() < len {
+            return None;
+        }
+        let (head, tail) = self.0.split_at(len);
+        self.0 = tail;
+        Some(head)
+    }
+
+    fn skip_string(&mut self) -> Option<()> {
+        let len = u32::from_le_bytes(self.take(4)?.try_into().ok()?);
+        self.take(len as usize).map(|_| ())
+    }
+
+    fn option(&mut self) -> Option<bool> {
+        Some(self.take(1)?[0] != 0)
+    }
+
+    // name, symbol, uri, seller fee and creators, shared by DataV2 and AssetData
+    fn skip_data_prefix(&mut self) -> Option<()> {
+        self.skip_string()?;
+        self.skip_string()?;
+        self.skip_string()?;
+        self.take(2)?;
+        if self.option()? {
+            let creators = u32::from_le_bytes(self.take(4)?.try_into().ok()?);
+            // address, verified, share
+            self.take(creators as usize * 34)?;
+        }
+        Some(())
+    }
+
+    fn collection(&mut self) -> Option<String> {
+        if !self.option()? {
+            return None;
+        }
+        self.take(1)?;
+        Some(Pubkey::try_from(self.take(32)?).ok()?.to_string())
+    }
+}
+
+// Collection named in a CreateMetadataAccountV3 or Create (V1) instruction
+fn declared_collection(tag: u8, data: &[u8]) -> Option<String> {
+    let mut reader = Reader(data);
+    match tag {
+        CREATE_METADATA_ACCOUNT_V3 => {
+            reader.skip_data_prefix()?;
+        }
+        CREATE => {
+            // CreateArgs::V1
+            if reader.take(1)? != [0] {
+                return None;
+            }
+            reader.skip_data_prefix()?;
+            // primary sale happened, is mutable, token standard
+            reader.take(3)?;
+        }
+        _ => return None,
+    }
+    reader.collection()
+}
+
+fn metadata_event(instruction: &Instruction) -> Option<NftEvent> {
+    let (&tag, args) = instruction.data.split_first()?;
+    let account = |index: usize| instruction.accounts.get(index).cloned();
+    let (kind, mint, metadata, collection) = match tag {
+        CREATE_METADATA_ACCOUNT_V3 => {
+            (NftEventKind::CreateMetadata, account(1), account(0), declared_collection(tag, args))
+        }
+        CREATE => (NftEventKind::CreateMetadata, account(2), account(0), declared_collection(tag, args)),
+        CREATE_MASTER_EDITION_V3 => (NftEventKind::CreateMasterEdition, account(1), account(5), None),
+        MINT => (NftEventKind::Mint, account(5), account(2), None),
+        VERIFY_COLLECTION | VERIFY_SIZED_COLLECTION_ITEM => {
+            (NftEventKind::VerifyCollection, None, account(0), account(3))
+        }
+        SET_AND_VERIFY_COLLECTION | SET_AND_VERIFY_SIZED_COLLECTION_ITEM => {
+            (NftEventKind::VerifyCollection, None, account(0), account(4))
+        }
+        // VerificationArgs::CollectionV1
+        VERIFY if args.first() == Some(&1) => (NftEventKind::VerifyCollection, None, account(2), account(3)),
+        _ => return None,
+    };
+    Some(NftEvent { kind, mint, metadata, collection })
+}
+
+// Mint initializations with zero decimals, and mints of a single token
+fn token_event(instruction: &Instruction, with_metadata: bool) -> Option<NftEvent> {
+    let data = instruction.data.as_slice();
+    let amount = |data: &[u8]| Some(u64::from_le_bytes(data.get(1..9)?.try_into().ok()?));
+    let (kind, mint) = match *data.first()? {
+        INITIALIZE_MINT | INITIALIZE_MINT_2 if data.get(1) == Some(&0) => {
+            (NftEventKind::InitializeMint, instruction.accounts.first()?)
+        }
+        MINT_TO if with_metadata && amount(data)? == 1 => (NftEventKind::MintTo, instruction.accounts.first()?),
+        MINT_TO_CHECKED if data.get(9) == Some(&0) && amount(data)? == 1 => {
+            (NftEventKind::MintTo, instruction.accounts.first()?)
+        }
+        _ => return None,
+    };
+    Some(NftEvent { kind, mint: Some(mint.clone()), metadata: None, collection: None })
+}
+
+// NFT activity per transaction signature, for the successful transactions that had any
+pub fn block_events(block: &EncodedConfirmedBlock) -> Vec<(String, Vec<NftEvent>)> {
+    block
+        .transactions
+        .iter()
+        .filter_map(|transaction_with_meta| {
+            let transaction = instructions::decode(transaction_with_meta).filter(|transaction| transaction.succeeded)?;
+            let with_metadata =
+                transaction.instructions.iter().any(|instruction| instruction.program_id == TOKEN_METADATA_PROGRAM_ID);
+
+            let events: Vec<NftEvent> = transaction
+                .instructions
+                .iter()
+                .filter_map(|instruction| match instruction.program_id.as_str() {
+                    TOKEN_METADATA_PROGRAM_ID => metadata_event(instruction),
+                    program_id if TOKEN_PROGRAM_IDS.contains(&program_id) => token_event(instruction, with_metadata),
+                    _ => None,
+                })
+                .collect();
+            (!events.is_empty()).then_some((transaction.signature, events))
+        })
+        .collect()
+}
@@ -0,0 +1,38 @@
+use serde::{Deserialize, Serialize};
+use solana_sdk::clock::Slot;
+use std::collections::HashSet;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+
+// Append-only record of the slot and witness hash of every published proof,
+// kept next to the proofs as `nullifiers.jsonl`. A block proved again from the
+// same witness differs only in the proof's randomness, so it is not published
+// a second time.
+#[derive(Serialize, Deserialize)]
+struct Nullifier {
+    slot: Slot,
+    witness_hash: String,
+}
+
+pub fn append(proofs_dir: &Path, slot: Slot, witness_hash: &str) {
+    let nullifier = Nullifier { slot, witness_hash: witness_hash.to_string() };
+    let mut line = serde_json::to_string(&nullifier).expect("Unable to serialize nullifier");
+    line.push('\n');
+
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(proofs_dir.join("nullifiers.jsonl"))
+        .expect("Unable to open nullifier set");
+    file.write_all(line.as_bytes()).expect("Unable to write nullifier set");
+}
+
+pub fn load(proofs_dir: &Path) -> HashSet<(Slot, String)> {
+    let contents = fs::read_to_string(proofs_dir.join("nullifiers.jsonl")).unwrap_or_default();
+    contents
+        .lines()
+        .filter_map(|line| serde_json::from_str::<Nullifier>(line).ok())
+        .map(|nullifier| (nullifier.slot, nullifier.witness_hash))
+        .collect()
+}
@@ -0,0 +1,289 @@
+use bellman::groth16;
+use blstrs::Bls12;
+use log::info;
+use sha2::{Digest, Sha256};
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, OnceLock, RwLock};
+
+use crate::circuit;
+use crate::config::{Config, ParamsSource};
+#[cfg(feature = "ed25519")]
+use crate::ed25519;
+use crate::keyring;
+use crate::serialization;
+
+// Parameters for the block circuit, and for the sum, totals, threshold and
+// signature circuits once a proof needs them. Those are only loaded (or
+// generated) on first use.
+pub struct ProvingKeys {
+    pub block: groth16::Parameters<Bls12>,
+    // Fingerprint of the block circuit's verifying key, stamped into every proof
+    pub block_fingerprint: String,
+    sum_path: PathBuf,
+    sum: OnceLock<groth16::Parameters<Bls12>>,
+    totals_path: PathBuf,
+    totals: OnceLock<groth16::Parameters<Bls12>>,
+    threshold_path: PathBuf,
+    threshold: OnceLock<groth16::Parameters<Bls12>>,
+    #[cfg(feature = "ed25519")]
+    signature_path: PathBuf,
+    #[cfg(feature = "ed25519")]
+    signature: OnceLock<groth16::Parameters<Bls12>>,
+}
+
+impl ProvingKeys {
+    // Loads the configured keys and records their verifying key in the keyring
+    // of the proofs directory
+    pub fn load(config: &Config) -> Self {
+        let keys = Self::from_paths(
+            &config.params_path,
+            &config.sum_params_path,
+            &config.totals_params_path,
+            &config.threshold_params_path,
+            &config.signature_params_path,
+        );
+        keyring::record(&config.proofs_dir, &keys.block.vk);
+        keys
+    }
+
+    pub fn from_paths(
+        block_path: &Path,
+        sum_path: &Path,
+        totals_path: &Path,
+        threshold_path: &Path,
+        signature_path: &Path,
+    ) -> Self {
+        let block = load_or_generate(block_path, circuit::generate_parameters);
+        Self::new(block, sum_path, totals_path, threshold_path, signature_path)
+    }
+
+    #[cfg_attr(not(feature = "ed25519"), allow(unused_variables))]
+    fn new(
+        block: groth16::Parameters<Bls12>,
+        sum_path: &Path,
+        totals_path: &Path,
+        threshold_path: &Path,
+        signature_path: &Path,
+    ) -> Self {
+        ProvingKeys {
+            block_fingerprint: fingerprint(&block.vk),
+            block,
+            sum_path: sum_path.to_path_buf(),
+            sum: OnceLock::new(),
+            totals_path: totals_path.to_path_buf(),
+            totals: OnceLock::new(),
+            threshold_path: threshold_path.to_path_buf(),
+            threshold: OnceLock::new(),
+            #[cfg(feature = "ed25519")]
+            signature_path: signature_path.to_path_buf(),
+            #[cfg(feature = "ed25519")]
+            signature: OnceLock::new(),
+        }
+    }
+
+    pub fn sum(&self) -> &groth16::Parameters<Bls12> {
+        self.sum.get_or_init(|| load_or_generate(&self.sum_path, circuit::generate_sum_parameters))
+    }
+
+    pub fn totals(&self) -> &groth16::Parameters<Bls12> {
+        self.totals.get_or_init(|| load_or_generate(&self.totals_path, circuit::generate_totals_parameters))
+    }
+
+    pub fn threshold(&self) -> &groth16::Parameters<Bls12> {
+        self.threshold.get_or_init(|| load_or_generate(&self.threshold_path, circuit::generate_threshold_parameters))
+    }
+
+    #[cfg(feature = "ed25519")]
+    pub fn signature(&self) -> &groth16::Parameters<Bls12> {
+        self.signature.get_or_init(|| load_or_generate(&self.signature_path, ed25519::generate_signature_parameters))
+    }
+}
+
+// Proving keys that can be swapped while the listener runs. A block keeps the
+// keys it started proving with, so a reload never interrupts a proof in flight;
+// blocks started after it use the new keys.
+#[derive(Clone)]
+pub struct SharedKeys {
+    keys: Arc<RwLock<Arc<ProvingKeys>>>,
+    reload_requested: Arc<AtomicBool>,
+}
+
+impl SharedKeys {
+    pub fn new(keys: ProvingKeys) -> Self {
+        SharedKeys {
+            keys: Arc::new(RwLock::new(Arc::new(keys))),
+            reload_requested: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    pub fn current(&self) -> Arc<ProvingKeys> {
+        self.keys.read().unwrap().clone()
+    }
+
+    // Asks for the parameters to be reloaded before the next block
+    pub fn request_reload(&self) {
+        self.reload_requested.store(true, Ordering::SeqCst);
+    }
+
+    pub fn take_reload_request(&self) -> bool {
+        self.reload_requested.swap(false, Ordering::SeqCst)
+    }
+
+    // Reads the configured parameters again and swaps them in, leaving the
+    // current keys in place if they cannot be read. Unlike startup, a missing
+    // file is an error rather than a reason to generate new parameters.
+    pub fn reload(&self, config: &Config) -> io::Result<String> {
+        let block = read_parameters(&config.params_path)?;
+        let keys = ProvingKeys::new(
+            block,
+            &config.sum_params_path,
+            &config.totals_params_path,
+            &config.threshold_params_path,
+            &config.signature_params_path,
+        );
+        keyring::record(&config.proofs_dir, &keys.block.vk);
+        let fingerprint = keys.block_fingerprint.clone();
+        *self.keys.write().unwrap() = Arc::new(keys);
+        Ok(fingerprint)
+    }
+}
+
+fn read_parameters(path: &Path) -> io::Result<groth16::Parameters<Bls12>> {
+    groth16::Parameters::read(BufReader::new(File::open(path)?), true)
+}
+
+// Loads the proving parameters from `path`, generating and saving them on first
+// use. Every listener and external prover has to share the same file for their
+// proofs to verify against one key.
+pub fn load_or_generate(path: &Path, generate: fn() -> groth16::Parameters<Bls12>) -> groth16::Parameters<Bls12> {
+    if path.exists() {
+        info!("Loading proving parameters from {:?}", path);
+        return read_parameters(path).expect("Unable to read parameters file");
+    }
+
+    info!("Generating proving parameters...");
+    let params = generate();
+    let mut writer = BufWriter::new(File::create(path).expect("Unable to create parameters file"));
+    params.write(&mut writer).expect("Unable to write parameters file");
+    writer.flush().expect("Unable to write parameters file");
+    info!("Saved proving parameters to {:?}", path);
+
+    params
+}
+
+// SHA-256 (hex) of a verifying key in its serialized form. Parameters only
+// match a proof if their verifying keys have the same fingerprint.
+pub fn fingerprint(vk: &groth16::VerifyingKey<Bls12>) -> String {
+    hex::encode(Sha256::digest(serialization::vk_to_bytes(vk)))
+}
+
+// Reads only the verifying key at the start of a parameters file, which is all
+// a verifier needs
+pub fn load_verifying_key(path: &Path) -> groth16::VerifyingKey<Bls12> {
+    let file = File::open(path).unwrap_or_else(|e| panic!("Unable to open parameters file {:?}: {}", path, e));
+    groth16::VerifyingKey::read(BufReader::new(file)).expect("Unable to read verifying key")
+}
+
+#[derive(Debug)]
+pub enum FetchError {
+    Http(reqwest::Error),
+    Io(io::Error),
+    HashMismatch { path: PathBuf, expected: String, actual: String },
+}
+
+impl fmt::Display for FetchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FetchError::Http(e) => write!(f, "parameter download failed: {}", e),
+            FetchError::Io(e) => write!(f, "parameter file error: {}", e),
+            FetchError::HashMismatch { path, expected, actual } => {
+                write!(f, "{:?} has SHA-256 {}, expected {}", path, actual, expected)
+            }
+        }
+    }
+}
+
+impl From<reqwest::Error> for FetchError {
+    fn from(e: reqwest::Error) -> Self {
+        FetchError::Http(e)
+    }
+}
+
+impl From<io::Error> for FetchError {
+    fn from(e: io::Error) -> Self {
+        FetchError::Io(e)
+    }
+}
+
+fn file_sha256(path: &Path) -> io::Result<String> {
+    let mut hasher = Sha256::new();
+    io::copy(&mut BufReader::new(File::open(path)?), &mut hasher)?;
+    Ok(hex::encode(hasher.finalize()))
+}
+
+fn check_pin(path: &Path, source: &ParamsSource) -> Result<(), FetchError> {
+    let actual = file_sha256(path)?;
+    if !actual.eq_ignore_ascii_case(&source.sha256) {
+        return Err(FetchError::HashMismatch { path: path.to_path_buf(), expected: source.sha256.clone(), actual });
+    }
+    Ok(())
+}
+
+// Downloads to a temporary file, which only replaces `path` once its hash checks out
+async fn download(source: &ParamsSource, path: &Path) -> Result<(), FetchError> {
+    info!("Downloading proving parameters from {}", source.url);
+    let tmp_path = path.with_extension("download");
+    let mut response = reqwest::get(&source.url).await?.error_for_status()?;
+    let mut writer = BufWriter::new(File::create(&tmp_path)?);
+    while let Some(chunk) = response.chunk().await? {
+        writer.write_all(&chunk)?;
+    }
+    writer.flush()?;
+    drop(writer);
+
+    if let Err(e) = check_pin(&tmp_path, source) {
+        fs::remove_file(&tmp_path)?;
+        return Err(e);
+    }
+    fs::rename(&tmp_path, path)?;
+    info!("Saved proving parameters to {:?}", path);
+    Ok(())
+}
+
+// Checks a parameters file without reading its proving key: that it starts
+// with a verifying key and, when pinned, matches its hash. Returns the key's
+// fingerprint.
+pub fn check_file(path: &Path, source: Option<&ParamsSource>) -> Result<String, FetchError> {
+    let vk = groth16::VerifyingKey::<Bls12>::read(BufReader::new(File::open(path)?))?;
+    if let Some(source) = source {
+        check_pin(path, source)?;
+    }
+    Ok(fingerprint(&vk))
+}
+
+// Makes sure every parameter file with a configured source is present and
+// matches its pinned hash, downloading the missing ones
+pub async fn fetch_pinned(config: &Config) -> Result<(), FetchError> {
+    let files = [
+        (&config.params_source, &config.params_path),
+        (&config.sum_params_source, &config.sum_params_path),
+        (&config.totals_params_source, &config.totals_params_path),
+        (&config.threshold_params_source, &config.threshold_params_path),
+        (&config.signature_params_source, &config.signature_params_path),
+    ];
+    for (source, path) in files {
+        let Some(source) = source else {
+            continue;
+        };
+        if path.exists() {
+            check_pin(path, source)?;
+        } else {
+            download(source, path).await?;
+        }
+    }
+    Ok(())
+}
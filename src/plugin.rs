@@ -0,0 +1,199 @@
+use libloading::Library;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use solana_sdk::clock::Slot;
+use solana_transaction_status::EncodedTransactionWithStatusMeta;
+use std::ffi::{c_char, c_int, CStr};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use crate::instructions::DecodedTransaction;
+
+// Extends the witness of every block: a processor sees each transaction as
+// it is added, may keep it out of the block's transaction tree, and may
+// attach fields of its own to its entry in the proof. The rest of the proof
+// (token transfers, fees, balances and so on) still covers every transaction.
+pub trait TxProcessor: Send + Sync {
+    // Labels the processor's fields in the proof and its vetoes in the logs
+    fn name(&self) -> &str;
+
+    // Whether the transaction goes into the proof
+    fn include(&self, _slot: Slot, _transaction: &DecodedTransaction, _raw: &EncodedTransactionWithStatusMeta) -> bool {
+        true
+    }
+
+    // Fields attached to the transaction's entry in the proof, under the
+    // processor's name
+    fn annotate(
+        &self,
+        _slot: Slot,
+        _transaction: &DecodedTransaction,
+        _raw: &EncodedTransactionWithStatusMeta,
+    ) -> Option<serde_json::Value> {
+        None
+    }
+}
+
+// A transaction left out of a proof by a processor
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ExcludedTransaction {
+    pub signature: String,
+    pub processor: String,
+}
+
+// Leaves failed transactions out of the proofs
+#[cfg(feature = "exclude-failed")]
+struct ExcludeFailed;
+
+#[cfg(feature = "exclude-failed")]
+impl TxProcessor for ExcludeFailed {
+    fn name(&self) -> &str {
+        "exclude-failed"
+    }
+
+    fn include(&self, _slot: Slot, transaction: &DecodedTransaction, _raw: &EncodedTransactionWithStatusMeta) -> bool {
+        transaction.succeeded
+    }
+}
+
+// Processors compiled in, each behind a cargo feature of its own
+fn builtin() -> Vec<Box<dyn TxProcessor>> {
+    vec![
+        #[cfg(feature = "exclude-failed")]
+        Box::new(ExcludeFailed),
+    ]
+}
+
+type NameFn = unsafe extern "C" fn() -> *const c_char;
+type IncludeFn = unsafe extern "C" fn(Slot, *const u8, usize) -> c_int;
+type AnnotateFn = unsafe extern "C" fn(Slot, *const u8, usize, *mut usize) -> *mut u8;
+type FreeFn = unsafe extern "C" fn(*mut u8, usize);
+
+// A processor loaded from a shared library. The library exports
+//
+//   const char *solana_listener_processor_name(void);
+//   int solana_listener_processor_include(uint64_t slot, const uint8_t *tx, size_t len);
+//   uint8_t *solana_listener_processor_annotate(uint64_t slot, const uint8_t *tx, size_t len, size_t *out_len);
+//   void solana_listener_processor_free(uint8_t *fields, size_t len);
+//
+// where `tx` is the transaction as JSON, encoded as `getBlock` returns it.
+// `include` returns 0 to leave the transaction out. `annotate` returns JSON
+// fields, or null for none, which are handed back to `free`. Only `name` is
+// required, and `free` is required with `annotate`.
+struct DynamicProcessor {
+    name: String,
+    include: Option<IncludeFn>,
+    annotate: Option<(AnnotateFn, FreeFn)>,
+    path: PathBuf,
+    // Keeps the functions above loaded
+    _library: Library,
+}
+
+#[derive(Debug)]
+pub enum PluginError {
+    Load(PathBuf, libloading::Error),
+    Invalid(PathBuf, &'static str),
+}
+
+impl fmt::Display for PluginError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PluginError::Load(path, e) => write!(f, "unable to load plugin {:?}: {}", path, e),
+            PluginError::Invalid(path, reason) => write!(f, "plugin {:?} {}", path, reason),
+        }
+    }
+}
+
+impl DynamicProcessor {
+    fn load(path: &Path) -> Result<Self, PluginError> {
+        let failed = |e| PluginError::Load(path.to_path_buf(), e);
+        // SAFETY: loading a plugin runs its initializers; plugins are trusted
+        // like the listener itself
+        unsafe {
+            let library = Library::new(path).map_err(failed)?;
+            let name_fn = *library.get::<NameFn>(b"solana_listener_processor_name\0").map_err(failed)?;
+            let name = name_fn();
+            if name.is_null() {
+                return Err(PluginError::Invalid(path.to_path_buf(), "has no name"));
+            }
+            let name = CStr::from_ptr(name).to_string_lossy().into_owned();
+            let include = library.get::<IncludeFn>(b"solana_listener_processor_include\0").ok().map(|f| *f);
+            let annotate = match library.get::<AnnotateFn>(b"solana_listener_processor_annotate\0") {
+                Ok(annotate) => {
+                    let free = library
+                        .get::<FreeFn>(b"solana_listener_processor_free\0")
+                        .map_err(|_| PluginError::Invalid(path.to_path_buf(), "annotates without a free function"))?;
+                    Some((*annotate, *free))
+                }
+                Err(_) => None,
+            };
+            Ok(DynamicProcessor { name, include, annotate, path: path.to_path_buf(), _library: library })
+        }
+    }
+}
+
+impl TxProcessor for DynamicProcessor {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn include(&self, slot: Slot, _transaction: &DecodedTransaction, raw: &EncodedTransactionWithStatusMeta) -> bool {
+        let Some(include) = self.include else {
+            return true;
+        };
+        let json = serde_json::to_vec(raw).expect("Unable to serialize transaction");
+        // SAFETY: the plugin only reads `len` bytes of `json`, which outlives the call
+        unsafe { include(slot, json.as_ptr(), json.len()) != 0 }
+    }
+
+    fn annotate(
+        &self,
+        slot: Slot,
+        _transaction: &DecodedTransaction,
+        raw: &EncodedTransactionWithStatusMeta,
+    ) -> Option<serde_json::Value> {
+        let (annotate, free) = self.annotate?;
+        let json = serde_json::to_vec(raw).expect("Unable to serialize transaction");
+        let mut len = 0;
+        // SAFETY: as for `include`. The fields returned are `len` bytes owned
+        // by the plugin until handed back to `free`.
+        let fields = unsafe {
+            let ptr = annotate(slot, json.as_ptr(), json.len(), &mut len);
+            if ptr.is_null() {
+                return None;
+            }
+            let fields = serde_json::from_slice(std::slice::from_raw_parts(ptr, len));
+            free(ptr, len);
+            fields
+        };
+        match fields {
+            Ok(fields) => Some(fields),
+            Err(e) => {
+                warn!("Plugin {:?} returned invalid fields for slot {}: {}", self.path, slot, e);
+                None
+            }
+        }
+    }
+}
+
+static PROCESSORS: OnceLock<Vec<Box<dyn TxProcessor>>> = OnceLock::new();
+
+// Registers the compiled-in processors and those of the plugins at `paths`.
+// Must run before any witness is built.
+pub fn load(paths: &[PathBuf]) -> Result<(), PluginError> {
+    let mut processors = builtin();
+    for path in paths {
+        let processor = DynamicProcessor::load(path)?;
+        info!("Loaded transaction processor {:?} from {:?}", processor.name, path);
+        processors.push(Box::new(processor));
+    }
+    if PROCESSORS.set(processors).is_err() {
+        panic!("Transaction processors loaded twice");
+    }
+    Ok(())
+}
+
+pub fn processors() -> &'static [Box<dyn TxProcessor>] {
+    PROCESSORS.get_or_init(builtin)
+}
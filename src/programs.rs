@@ -0,0 +1,137 @@
+use bellman::groth16;
+use blstrs::{Bls12, Scalar as Fr};
+use ff::{Field, PrimeField};
+use serde::{Deserialize, Serialize};
+use solana_sdk::bpf_loader_upgradeable;
+use solana_sdk::loader_upgradeable_instruction::UpgradeableLoaderInstruction;
+use solana_sdk::program_utils::limited_deserialize;
+use solana_transaction_status::EncodedConfirmedBlock;
+
+use crate::config::CommitmentHash;
+use crate::field::{fr_to_hex, str_to_fr, Domain};
+use crate::instructions;
+use crate::merkle::MerkleTree;
+use crate::serialization;
+use crate::witness::{BlockWitness, WitnessAccumulator, WitnessError};
+use crate::{prove_chunks, ChunkProof};
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum ProgramChangeKind {
+    Deployed,
+    Upgraded,
+    Extended,
+    Closed,
+}
+
+// A change to an upgradeable program's bytecode (or its program data account)
+// made by a successful transaction of the block. `buffer` holds the bytecode
+// written by deploys and upgrades.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ProgramChange {
+    pub signature: String,
+    pub program_id: String,
+    pub kind: ProgramChangeKind,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub buffer: Option<String>,
+}
+
+impl ProgramChange {
+    // Leaf of this change in the block's program change commitment and Merkle tree
+    pub fn leaf(&self) -> Fr {
+        str_to_fr(Domain::ProgramChange, &self.leaf_data())
+    }
+
+    pub fn leaf_data(&self) -> String {
+        let buffer = self.buffer.as_deref().unwrap_or_default();
+        format!("{}:{}:{:?}:{}", self.signature, self.program_id, self.kind, buffer)
+    }
+}
+
+// The program changes of a block with a commitment and proof over them, in
+// block order, so a program's bytecode change can be tied to its slot
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ProgramChanges {
+    pub changes: Vec<ProgramChange>,
+    pub changes_root: String,
+    pub commitment: String,
+    pub proof: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub chunks: Vec<ChunkProof>,
+}
+
+pub fn changes(block: &EncodedConfirmedBlock) -> Vec<ProgramChange> {
+    let mut changes = Vec::new();
+    let loader = bpf_loader_upgradeable::id().to_string();
+
+    for transaction_with_meta in &block.transactions {
+        let Some(transaction) = instructions::decode(transaction_with_meta) else {
+            continue;
+        };
+        if !transaction.succeeded {
+            continue;
+        }
+
+        for instruction in transaction.instructions.iter().filter(|instruction| instruction.program_id == loader) {
+            let accounts = instruction.accounts.as_slice();
+            let (kind, program_id, buffer) = match (limited_deserialize(&instruction.data), accounts) {
+                (Ok(UpgradeableLoaderInstruction::DeployWithMaxDataLen { .. }), [_, _, program, buffer, ..]) => {
+                    (ProgramChangeKind::Deployed, program, Some(buffer.clone()))
+                }
+                (Ok(UpgradeableLoaderInstruction::Upgrade), [_, program, buffer, ..]) => {
+                    (ProgramChangeKind::Upgraded, program, Some(buffer.clone()))
+                }
+                (Ok(UpgradeableLoaderInstruction::ExtendProgram { .. }), [_, program, ..]) => {
+                    (ProgramChangeKind::Extended, program, None)
+                }
+                // Closing a buffer rather than a program names no program account
+                (Ok(UpgradeableLoaderInstruction::Close), [_, _, _, program, ..]) => {
+                    (ProgramChangeKind::Closed, program, None)
+                }
+                _ => continue,
+            };
+
+            changes.push(ProgramChange {
+                signature: transaction.signature.clone(),
+                program_id: program_id.clone(),
+                kind,
+                buffer,
+            });
+        }
+    }
+
+    changes
+}
+
+// Witness over a block's program changes, seeded with the block hash
+pub fn changes_witness(
+    block_hash: &str,
+    changes: &[ProgramChange],
+    memory_cap: usize,
+) -> Result<BlockWitness, WitnessError> {
+    let seed = str_to_fr(Domain::Commitment, &format!("{}:programs", block_hash));
+    let mut accumulator = WitnessAccumulator::new(seed, memory_cap);
+    for change in changes {
+        accumulator.push(change.leaf())?;
+    }
+    // Like mint proofs, this stands on its own rather than extending the block accumulator
+    accumulator.finish(Fr::ZERO)
+}
+
+pub fn prove_changes(
+    changes: Vec<ProgramChange>,
+    witness: &BlockWitness,
+    hash: CommitmentHash,
+    params: &groth16::Parameters<Bls12>,
+) -> ProgramChanges {
+    let leaves: Vec<_> = witness.leaves().map(|leaf| leaf.to_repr()).collect();
+    let (proof, chunks) = prove_chunks(witness, params);
+
+    ProgramChanges {
+        changes,
+        changes_root: hex::encode(MerkleTree::new(hash, &leaves).root()),
+        commitment: fr_to_hex(&witness.top_level().commitment),
+        proof: serialization::proof_to_hex(&proof),
+        chunks,
+    }
+}
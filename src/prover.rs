@@ -0,0 +1,167 @@
+use log::info;
+use std::fmt;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use tokio::sync::{oneshot, Semaphore};
+use tokio::time::{sleep, timeout, Duration};
+
+use crate::cpu;
+use crate::keyring;
+use crate::memory;
+use crate::params::SharedKeys;
+use crate::verify::{self, VerifyError};
+use crate::witness::ExportedWitness;
+use crate::{prove_block, BlockProof};
+
+// Asks a proof in progress to stop. Circuits can't be interrupted, so
+// proving stops before the next circuit it would start.
+#[derive(Clone, Default)]
+pub struct Cancellation(Arc<AtomicBool>);
+
+#[derive(Debug)]
+pub struct Cancelled;
+
+impl Cancellation {
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn check(&self) -> Result<(), Cancelled> {
+        match self.0.load(Ordering::Relaxed) {
+            true => Err(Cancelled),
+            false => Ok(()),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ProveError {
+    // Over the proving timeout, after which the proof was cancelled
+    TimedOut(Duration),
+    // The proof did not verify against the key it was made with
+    Rejected(VerifyError),
+}
+
+impl fmt::Display for ProveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProveError::TimedOut(limit) => write!(f, "proving took over {}s", limit.as_secs()),
+            ProveError::Rejected(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+// How often a proof held back by the memory budget checks it again
+const MEMORY_POLL: Duration = Duration::from_millis(200);
+
+// Counts a proof as running until dropped
+struct Running(Arc<AtomicUsize>);
+
+impl Running {
+    fn start(running: &Arc<AtomicUsize>) -> Self {
+        running.fetch_add(1, Ordering::SeqCst);
+        Running(running.clone())
+    }
+}
+
+impl Drop for Running {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+// Proving shared by every listener instance of the process. Blocks are proved
+// on threads of their own, at most `threads` at a time, so listeners keep
+// following their clusters while proofs are made. With a memory budget, no
+// further proof is started while the process is within a tenth of it and
+// another is running, so parallelism is shed before the budget is reached.
+#[derive(Clone)]
+pub struct ProverPool {
+    keys: SharedKeys,
+    permits: Arc<Semaphore>,
+    memory_budget: Option<usize>,
+    running: Arc<AtomicUsize>,
+}
+
+impl ProverPool {
+    pub fn new(keys: SharedKeys, threads: usize, memory_budget: Option<usize>) -> Self {
+        ProverPool {
+            keys,
+            permits: Arc::new(Semaphore::new(threads.max(1))),
+            memory_budget,
+            running: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    // Waits while the memory budget holds back another proof. A proof is
+    // always started when none is running, however much memory is in use.
+    async fn wait_for_memory(&self) {
+        let Some(budget) = self.memory_budget else {
+            return;
+        };
+        let mut held_back = false;
+        while self.running.load(Ordering::SeqCst) > 0 && memory::allocated() >= budget - budget / 10 {
+            if !held_back {
+                let allocated_mb = memory::allocated() >> 20;
+                info!("{} MiB allocated, near the memory budget: waiting for a proof to finish", allocated_mb);
+                held_back = true;
+            }
+            sleep(MEMORY_POLL).await;
+        }
+    }
+
+    pub fn keys(&self) -> &SharedKeys {
+        &self.keys
+    }
+
+    // Proves the block with the current keys, recording their verifying key in
+    // the keyring of the archive the proof goes to. The proof is checked
+    // against that key before it is returned. A proof that takes longer than
+    // `limit` is cancelled; its thread is only handed back to the pool once it
+    // has stopped.
+    pub async fn prove(
+        &self,
+        exported: ExportedWitness,
+        proofs_dir: &Path,
+        limit: Option<Duration>,
+    ) -> Result<BlockProof, ProveError> {
+        let permit = self.permits.clone().acquire_owned().await.expect("Prover pool closed");
+        self.wait_for_memory().await;
+        let running = Running::start(&self.running);
+        let keys = self.keys.current();
+        keyring::record(proofs_dir, &keys.block.vk);
+        let cancellation = Cancellation::default();
+        let cancel = cancellation.clone();
+        let (done, proving) = oneshot::channel();
+        // A thread of its own rather than one of the blocking pool, whose
+        // threads would keep the prover CPU settings for the tasks after it
+        thread::Builder::new()
+            .name("prover".to_string())
+            .spawn(move || {
+                let _permit = (permit, running);
+                cpu::apply();
+                let proved = prove_block(exported, &keys, &cancel)
+                    .map(|block_proof| verify::self_check(&block_proof, &keys).map(|()| block_proof));
+                let _ = done.send(proved);
+            })
+            .expect("Unable to start prover thread");
+
+        let proved = match limit {
+            None => proving.await,
+            Some(limit) => match timeout(limit, proving).await {
+                Ok(proved) => proved,
+                Err(_) => {
+                    cancellation.cancel();
+                    return Err(ProveError::TimedOut(limit));
+                }
+            },
+        };
+        match proved.expect("Prover thread panicked") {
+            Ok(checked) => checked.map_err(ProveError::Rejected),
+            // Only cancelled once timed out, which has been answered already
+            Err(Cancelled) => unreachable!("Proof cancelled before its timeout"),
+        }
+    }
+}
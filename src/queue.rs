@@ -0,0 +1,144 @@
+use solana_sdk::clock::Slot;
+use solana_transaction_status::EncodedConfirmedBlock;
+use std::collections::{BTreeSet, VecDeque};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tokio::sync::Notify;
+use tokio::time::{timeout, Duration};
+
+use crate::config::QueueConfig;
+
+// Blocks spilled by the queue, kept as `queue/<slot>.json` in the proofs directory
+pub fn spill_dir(proofs_dir: &Path) -> PathBuf {
+    proofs_dir.join("queue")
+}
+
+// Blocks fetched ahead of the prover, in slot order. Up to `capacity` are
+// held in memory; past that they are spilled to disk and read back once the
+// prover catches up, so a burst costs disk space rather than memory.
+pub struct BlockQueue {
+    capacity: usize,
+    spill_limit: Option<usize>,
+    dir: PathBuf,
+    state: Mutex<QueueState>,
+    pushed: Notify,
+}
+
+pub struct QueuedBlock {
+    // Generation of the queue the block was fetched in
+    pub generation: u64,
+    pub slot: Slot,
+    pub block: EncodedConfirmedBlock,
+}
+
+#[derive(Default)]
+struct QueueState {
+    // Bumped on every restart, so blocks fetched before it are dropped
+    generation: u64,
+    // Next slot to fetch; `None` while fetching is stopped
+    cursor: Option<Slot>,
+    memory: VecDeque<(Slot, EncodedConfirmedBlock)>,
+    // Slots of the spilled blocks, all later than those held in memory
+    spilled: BTreeSet<Slot>,
+}
+
+impl BlockQueue {
+    // Starts stopped. Blocks spilled by a previous run are dropped, since
+    // the listener fetches again from its checkpoint.
+    pub fn new(config: &QueueConfig, proofs_dir: &Path) -> Self {
+        let dir = spill_dir(proofs_dir);
+        if dir.exists() {
+            fs::remove_dir_all(&dir).expect("Unable to clear queue directory");
+        }
+        fs::create_dir_all(&dir).expect("Unable to create queue directory");
+        BlockQueue {
+            capacity: config.capacity,
+            spill_limit: config.spill_limit,
+            dir,
+            state: Mutex::new(QueueState::default()),
+            pushed: Notify::new(),
+        }
+    }
+
+    fn spill_path(&self, slot: Slot) -> PathBuf {
+        self.dir.join(format!("{}.json", slot))
+    }
+
+    // Drops every queued block and fetches from `slot` on, or stops fetching.
+    // Returns the generation blocks are now queued under.
+    pub fn restart(&self, slot: Option<Slot>) -> u64 {
+        let mut state = self.state.lock().unwrap();
+        state.generation += 1;
+        state.cursor = slot;
+        state.memory.clear();
+        for slot in std::mem::take(&mut state.spilled) {
+            fs::remove_file(self.spill_path(slot)).expect("Unable to remove spilled block");
+        }
+        state.generation
+    }
+
+    pub fn generation(&self) -> u64 {
+        self.state.lock().unwrap().generation
+    }
+
+    // The next slot to fetch, with the generation to queue it under. `None`
+    // while stopped, or while the spill limit is reached.
+    pub fn cursor(&self) -> Option<(u64, Slot)> {
+        let state = self.state.lock().unwrap();
+        if self.spill_limit.is_some_and(|limit| state.spilled.len() >= limit) {
+            return None;
+        }
+        state.cursor.map(|slot| (state.generation, slot))
+    }
+
+    // Queues the block fetched for `slot`, if it has one, and moves the cursor
+    // on to `next_slot`. Ignored if the queue was restarted since the fetch began.
+    pub fn push(&self, generation: u64, slot: Slot, block: Option<EncodedConfirmedBlock>, next_slot: Slot) {
+        let mut state = self.state.lock().unwrap();
+        if state.generation != generation {
+            return;
+        }
+        state.cursor = Some(next_slot);
+        let Some(block) = block else {
+            return;
+        };
+
+        if state.spilled.is_empty() && state.memory.len() < self.capacity {
+            state.memory.push_back((slot, block));
+        } else {
+            let json_data = serde_json::to_vec(&block).expect("Unable to serialize queued block");
+            fs::write(self.spill_path(slot), json_data).expect("Unable to spill queued block");
+            state.spilled.insert(slot);
+        }
+        self.pushed.notify_one();
+    }
+
+    fn take(&self) -> Option<QueuedBlock> {
+        let mut state = self.state.lock().unwrap();
+        if let Some((slot, block)) = state.memory.pop_front() {
+            return Some(QueuedBlock { generation: state.generation, slot, block });
+        }
+        let slot = state.spilled.pop_first()?;
+        let path = self.spill_path(slot);
+        let contents = fs::read(&path).expect("Unable to read spilled block");
+        fs::remove_file(&path).expect("Unable to remove spilled block");
+        let block = serde_json::from_slice(&contents).expect("Unable to parse spilled block");
+        Some(QueuedBlock { generation: state.generation, slot, block })
+    }
+
+    // The earliest queued block, waiting up to `limit` for one
+    pub async fn pop(&self, limit: Duration) -> Option<QueuedBlock> {
+        if let Some(entry) = self.take() {
+            return Some(entry);
+        }
+        let _ = timeout(limit, self.pushed.notified()).await;
+        self.take()
+    }
+
+    // Number of blocks held in memory and spilled to disk
+    pub fn depth(&self) -> (usize, usize) {
+        let state = self.state.lock().unwrap();
+        (state.memory.len(), state.spilled.len())
+    }
+}
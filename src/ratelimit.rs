@@ -0,0 +1,291 @@
+use futures::future::{self, Either, Ready};
+use hyper::header::{CONTENT_TYPE, RETRY_AFTER};
+use hyper::{Body, Request, Response, StatusCode};
+use serde_json::json;
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+use tower::{Layer, Service};
+
+use crate::config::RateLimitConfig;
+
+// Clients tracked before those whose bucket has refilled are forgotten. If
+// that is not enough, the least recently seen are forgotten as well, down to
+// `RETAINED_CLIENTS`, so the map is not swept again on every new client.
+const MAX_TRACKED_CLIENTS: usize = 100_000;
+const RETAINED_CLIENTS: usize = MAX_TRACKED_CLIENTS * 3 / 4;
+
+// Holds up to `burst` requests, refilled at `rate` requests a second
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl Bucket {
+    fn full(burst: f64, now: Instant) -> Self {
+        Bucket { tokens: burst, updated: now }
+    }
+
+    fn tokens_at(&self, rate: f64, burst: f64, now: Instant) -> f64 {
+        let elapsed = now.duration_since(self.updated).as_secs_f64();
+        (self.tokens + elapsed * rate).min(burst)
+    }
+
+    fn refill(&mut self, rate: f64, burst: f64, now: Instant) {
+        self.tokens = self.tokens_at(rate, burst, now);
+        self.updated = now;
+    }
+
+    // Takes one request, or returns how long until one could be taken
+    fn take(&mut self, rate: f64, burst: f64, now: Instant) -> Result<(), Duration> {
+        self.refill(rate, burst, now);
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            return Ok(());
+        }
+        Err(Duration::from_secs_f64((1.0 - self.tokens) / rate))
+    }
+}
+
+struct KeyLimit {
+    rate: f64,
+    burst: f64,
+    bucket: Mutex<Bucket>,
+}
+
+pub enum Refusal {
+    UnknownKey,
+    // With how long the client should wait before trying again
+    Limited(Duration),
+}
+
+impl Refusal {
+    fn response(&self) -> Response<Body> {
+        let (status, error) = match self {
+            Refusal::UnknownKey => (StatusCode::UNAUTHORIZED, "unknown API key"),
+            Refusal::Limited(_) => (StatusCode::TOO_MANY_REQUESTS, "rate limited"),
+        };
+        let mut response = Response::builder()
+            .status(status)
+            .header(CONTENT_TYPE, "application/json")
+            .body(Body::from(json!({ "error": error }).to_string()))
+            .unwrap();
+        if let Refusal::Limited(wait) = self {
+            let retry_after = wait.as_secs_f64().ceil() as u64;
+            response.headers_mut().insert(RETRY_AFTER, retry_after.into());
+        }
+        response
+    }
+}
+
+// Rate limits on API requests: anonymous requests share a bucket per client
+// IP address, and requests made with an API key use that key's bucket, with
+// the rates configured for it, wherever they come from
+pub struct RateLimiter {
+    rate: f64,
+    burst: f64,
+    clients: Mutex<HashMap<IpAddr, Bucket>>,
+    keys: HashMap<String, KeyLimit>,
+}
+
+impl RateLimiter {
+    pub fn new(config: &RateLimitConfig) -> Self {
+        let now = Instant::now();
+        let keys = config
+            .keys
+            .iter()
+            .map(|key| {
+                let burst = key.burst as f64;
+                let bucket = Mutex::new(Bucket::full(burst, now));
+                (key.key.clone(), KeyLimit { rate: key.requests_per_sec, burst, bucket })
+            })
+            .collect();
+        RateLimiter {
+            rate: config.requests_per_sec,
+            burst: config.burst as f64,
+            clients: Mutex::new(HashMap::new()),
+            keys,
+        }
+    }
+
+    pub fn check(&self, client: IpAddr, key: Option<&str>) -> Result<(), Refusal> {
+        self.check_at(client, key, Instant::now())
+    }
+
+    fn check_at(&self, client: IpAddr, key: Option<&str>, now: Instant) -> Result<(), Refusal> {
+        if let Some(key) = key {
+            let limit = self.keys.get(key).ok_or(Refusal::UnknownKey)?;
+            let mut bucket = limit.bucket.lock().unwrap();
+            return bucket.take(limit.rate, limit.burst, now).map_err(Refusal::Limited);
+        }
+
+        let mut clients = self.clients.lock().unwrap();
+        if clients.len() >= MAX_TRACKED_CLIENTS && !clients.contains_key(&client) {
+            // A client whose bucket is full again is no different from a new
+            // one. The others keep when they were last seen, which decides
+            // who is forgotten next.
+            clients.retain(|_, bucket| bucket.tokens_at(self.rate, self.burst, now) < self.burst);
+        }
+        if clients.len() >= MAX_TRACKED_CLIENTS && !clients.contains_key(&client) {
+            // Forgotten clients start again from a full bucket, which is the
+            // price of bounding the memory the limiter holds
+            let mut seen: Vec<Instant> = clients.values().map(|bucket| bucket.updated).collect();
+            let (_, cutoff, _) = seen.select_nth_unstable_by(RETAINED_CLIENTS, |a, b| b.cmp(a));
+            let cutoff = *cutoff;
+            clients.retain(|_, bucket| bucket.updated > cutoff);
+        }
+        let bucket = clients.entry(client).or_insert_with(|| Bucket::full(self.burst, now));
+        bucket.take(self.rate, self.burst, now).map_err(Refusal::Limited)
+    }
+}
+
+// Tower middleware refusing the requests of a connection from `client` over
+// the rate limit of their API key or of the client's address
+pub struct RateLimitLayer {
+    limiter: Arc<RateLimiter>,
+    client: IpAddr,
+}
+
+impl RateLimitLayer {
+    pub fn new(limiter: Arc<RateLimiter>, client: IpAddr) -> Self {
+        RateLimitLayer { limiter, client }
+    }
+}
+
+impl<S> Layer<S> for RateLimitLayer {
+    type Service = RateLimit<S>;
+
+    fn layer(&self, inner: S) -> RateLimit<S> {
+        RateLimit { inner, limiter: self.limiter.clone(), client: self.client }
+    }
+}
+
+#[derive(Clone)]
+pub struct RateLimit<S> {
+    inner: S,
+    limiter: Arc<RateLimiter>,
+    client: IpAddr,
+}
+
+impl<S> Service<Request<Body>> for RateLimit<S>
+where
+    S: Service<Request<Body>, Response = Response<Body>>,
+{
+    type Response = Response<Body>;
+    type Error = S::Error;
+    type Future = Either<Ready<Result<Response<Body>, S::Error>>, S::Future>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), S::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request<Body>) -> Self::Future {
+        let key = request.headers().get("x-api-key").map(|key| key.to_str().unwrap_or_default());
+        match self.limiter.check(self.client, key) {
+            Ok(()) => Either::Right(self.inner.call(request)),
+            Err(refusal) => Either::Left(future::ready(Ok(refusal.response()))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ApiKeyConfig;
+    use std::net::Ipv4Addr;
+
+    fn limiter(requests_per_sec: f64, burst: u32) -> RateLimiter {
+        let key = ApiKeyConfig { key: "key".to_string(), requests_per_sec: 10.0, burst: 3 };
+        RateLimiter::new(&RateLimitConfig { requests_per_sec, burst, keys: vec![key] })
+    }
+
+    fn client(index: u32) -> IpAddr {
+        IpAddr::V4(Ipv4Addr::from(index))
+    }
+
+    fn wait(refusal: Result<(), Refusal>) -> Option<Duration> {
+        match refusal {
+            Err(Refusal::Limited(wait)) => Some(wait),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn buckets_refill_at_their_rate() {
+        let start = Instant::now();
+        let mut bucket = Bucket::full(2.0, start);
+        assert!(bucket.take(2.0, 2.0, start).is_ok());
+        assert!(bucket.take(2.0, 2.0, start).is_ok());
+        assert_eq!(bucket.take(2.0, 2.0, start), Err(Duration::from_millis(500)));
+        assert_eq!(bucket.take(2.0, 2.0, start + Duration::from_millis(250)), Err(Duration::from_millis(250)));
+        assert!(bucket.take(2.0, 2.0, start + Duration::from_millis(500)).is_ok());
+
+        // Refilled up to the burst and no further
+        let later = start + Duration::from_secs(60);
+        assert!((0..2).all(|_| bucket.take(2.0, 2.0, later).is_ok()));
+        assert!(bucket.take(2.0, 2.0, later).is_err());
+    }
+
+    #[test]
+    fn clients_and_keys_have_their_own_buckets() {
+        let limiter = limiter(1.0, 1);
+        let now = Instant::now();
+        assert!(limiter.check_at(client(1), None, now).is_ok());
+        assert_eq!(wait(limiter.check_at(client(1), None, now)), Some(Duration::from_secs(1)));
+        assert!(limiter.check_at(client(2), None, now).is_ok());
+
+        // A key's bucket is shared wherever its requests come from
+        for index in 0..3 {
+            assert!(limiter.check_at(client(index), Some("key"), now).is_ok());
+        }
+        assert!(wait(limiter.check_at(client(4), Some("key"), now)).is_some());
+        assert!(limiter.check_at(client(4), None, now).is_ok());
+        assert!(matches!(limiter.check_at(client(1), Some("other"), now), Err(Refusal::UnknownKey)));
+    }
+
+    #[test]
+    fn refilled_clients_are_forgotten_first() {
+        let limiter = limiter(1.0, 1);
+        let now = Instant::now();
+        for index in 0..MAX_TRACKED_CLIENTS as u32 {
+            assert!(limiter.check_at(client(index), None, now).is_ok());
+        }
+        let later = now + Duration::from_secs(2);
+        assert!(limiter.check_at(client(0), None, later).is_ok());
+        assert_eq!(limiter.clients.lock().unwrap().len(), MAX_TRACKED_CLIENTS);
+        // Every bucket but the one just drained has refilled
+        assert!(limiter.check_at(client(u32::MAX), None, later).is_ok());
+        let clients = limiter.clients.lock().unwrap();
+        assert_eq!(clients.len(), 2);
+        assert!(clients.contains_key(&client(0)) && clients.contains_key(&client(u32::MAX)));
+    }
+
+    #[test]
+    fn least_recently_seen_clients_are_forgotten() {
+        let limiter = limiter(0.001, 1);
+        let now = Instant::now();
+        for index in 0..MAX_TRACKED_CLIENTS as u32 {
+            let seen = now + Duration::from_micros(index as u64);
+            assert!(limiter.check_at(client(index), None, seen).is_ok());
+        }
+        let later = now + Duration::from_secs(1);
+        assert!(limiter.check_at(client(u32::MAX), None, later).is_ok());
+        let clients = limiter.clients.lock().unwrap();
+        assert_eq!(clients.len(), RETAINED_CLIENTS + 1);
+        assert!(!clients.contains_key(&client(0)));
+        assert!(clients.contains_key(&client(MAX_TRACKED_CLIENTS as u32 - 1)));
+        assert!(clients.contains_key(&client(u32::MAX)));
+    }
+
+    #[test]
+    fn limited_responses_say_when_to_retry() {
+        let response = Refusal::Limited(Duration::from_millis(1500)).response();
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(response.headers()[RETRY_AFTER], "2");
+        let response = Refusal::UnknownKey.response();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        assert!(response.headers().get(RETRY_AFTER).is_none());
+    }
+}
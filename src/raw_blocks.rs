@@ -0,0 +1,36 @@
+use solana_sdk::clock::Slot;
+use solana_transaction_status::EncodedConfirmedBlock;
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+
+const COMPRESSION_LEVEL: i32 = 3;
+
+// Fetched blocks kept with their proofs, as `blocks/<slot>.json.zst` in the
+// proofs directory. They are kept as the JSON the node returned, since a
+// binary encoding could not read its untagged transaction encodings back.
+fn dir(proofs_dir: &Path) -> PathBuf {
+    proofs_dir.join("blocks")
+}
+
+fn path(proofs_dir: &Path, slot: Slot) -> PathBuf {
+    dir(proofs_dir).join(format!("{}.json.zst", slot))
+}
+
+// Written to a temporary file first so a crash never leaves a torn block
+pub fn save(proofs_dir: &Path, slot: Slot, block: &EncodedConfirmedBlock) {
+    let path = path(proofs_dir, slot);
+    let tmp_path = path.with_extension("zst.tmp");
+    fs::create_dir_all(dir(proofs_dir)).expect("Unable to create blocks directory");
+    let file = File::create(&tmp_path).expect("Unable to write block snapshot");
+    let mut encoder = zstd::Encoder::new(file, COMPRESSION_LEVEL).expect("Unable to write block snapshot");
+    serde_json::to_writer(&mut encoder, block).expect("Unable to write block snapshot");
+    encoder.finish().expect("Unable to write block snapshot");
+    fs::rename(&tmp_path, &path).expect("Unable to write block snapshot");
+}
+
+// The block kept for `slot`, if any
+pub fn load(proofs_dir: &Path, slot: Slot) -> Option<EncodedConfirmedBlock> {
+    let file = File::open(path(proofs_dir, slot)).ok()?;
+    let decoder = zstd::Decoder::new(file).expect("Unable to read block snapshot");
+    Some(serde_json::from_reader(decoder).expect("Unable to parse block snapshot"))
+}
@@ -0,0 +1,70 @@
+use futures::future::{BoxFuture, FutureExt};
+use log::info;
+use redis::aio::ConnectionManager;
+use redis::RedisError;
+use tokio::sync::OnceCell;
+
+use crate::config::RedisConfig;
+use crate::sink::ProofSink;
+use crate::BlockProof;
+
+// Publishes block proofs on a Redis channel and caches the latest ones. The
+// connection is made with the first delivery, and re-established by the
+// connection manager after it is lost.
+pub struct RedisPublisher {
+    client: redis::Client,
+    config: RedisConfig,
+    connection: OnceCell<ConnectionManager>,
+}
+
+impl RedisPublisher {
+    // Instances of a multi-cluster listener publish on `<channel>:<name>` and
+    // cache under `<key_prefix>:<name>`
+    pub fn new(config: &RedisConfig, instance: Option<&str>) -> Result<Self, RedisError> {
+        let client = redis::Client::open(config.url.as_str())?;
+        let mut config = config.clone();
+        if let Some(instance) = instance {
+            config.channel = format!("{}:{}", config.channel, instance);
+            config.key_prefix = format!("{}:{}", config.key_prefix, instance);
+        }
+        Ok(RedisPublisher { client, config, connection: OnceCell::new() })
+    }
+
+    async fn connection(&self) -> Result<ConnectionManager, RedisError> {
+        let connection = self
+            .connection
+            .get_or_try_init(|| async {
+                let connection = ConnectionManager::new(self.client.clone()).await?;
+                info!("Publishing block proofs to Redis channel {}", self.config.channel);
+                Ok::<_, RedisError>(connection)
+            })
+            .await?;
+        Ok(connection.clone())
+    }
+}
+
+impl ProofSink for RedisPublisher {
+    fn deliver<'a>(&'a self, block_proof: &'a BlockProof) -> BoxFuture<'a, Result<(), String>> {
+        async move {
+            let json_data = serde_json::to_string(block_proof).expect("Unable to serialize proof");
+            let slot = block_proof.slot;
+            let cache_key = format!("{}:proofs", self.config.key_prefix);
+
+            let mut pipeline = redis::pipe();
+            pipeline.atomic().publish(&self.config.channel, &json_data).ignore();
+            if self.config.cache_proofs > 0 {
+                // A slot proved again replaces its cached proof
+                pipeline
+                    .zrembyscore(&cache_key, slot, slot)
+                    .ignore()
+                    .zadd(&cache_key, &json_data, slot)
+                    .ignore()
+                    .zremrangebyrank(&cache_key, 0, -(self.config.cache_proofs as isize) - 1)
+                    .ignore();
+            }
+            let mut connection = self.connection().await.map_err(|e| format!("unable to connect: {}", e))?;
+            pipeline.query_async::<_, ()>(&mut connection).await.map_err(|e| e.to_string())
+        }
+        .boxed()
+    }
+}
@@ -0,0 +1,132 @@
+use solana_sdk::clock::Slot;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::config::ScheduleConfig;
+
+const MINUTES_PER_DAY: u32 = 24 * 60;
+
+fn minute(time: &str) -> Option<u32> {
+    let (hours, minutes) = time.split_once(':')?;
+    let hours: u32 = hours.parse().ok().filter(|hours| *hours < 24)?;
+    let minutes: u32 = minutes.parse().ok().filter(|minutes| *minutes < 60)?;
+    Some(hours * 60 + minutes)
+}
+
+// Start and end of a window such as "02:00-06:00" as minutes of the UTC day.
+// The end is exclusive, and a window that ends before it starts runs past
+// midnight.
+pub fn parse_window(window: &str) -> Result<(u32, u32), String> {
+    let invalid = || format!("{:?} is not a window of UTC times such as 02:00-06:00", window);
+    let (start, end) = window.split_once('-').ok_or_else(invalid)?;
+    let (start, end) = minute(start.trim()).zip(minute(end.trim())).ok_or_else(invalid)?;
+    if start == end {
+        return Err(format!("window {:?} starts and ends at the same time", window));
+    }
+    Ok((start, end))
+}
+
+fn minute_of_day() -> u32 {
+    let secs = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+    ((secs / 60) % MINUTES_PER_DAY as u64) as u32
+}
+
+// Whether one of `windows` is open at `minute`. No windows means any time;
+// windows that do not parse are never open, and are reported by `check-config`.
+fn is_open(windows: &[String], minute: u32) -> bool {
+    windows.is_empty()
+        || windows.iter().filter_map(|window| parse_window(window).ok()).any(|(start, end)| match start < end {
+            true => (start..end).contains(&minute),
+            false => minute >= start || minute < end,
+        })
+}
+
+// Whether `slot` is backfill when the tip is at `tip`
+pub fn is_backfill(schedule: &ScheduleConfig, slot: Slot, tip: Slot) -> bool {
+    tip.saturating_sub(slot) > schedule.backfill_lag_slots
+}
+
+// Whether `slot` may be proved now, during the backfill windows if it is
+// backfill and the real-time ones otherwise
+pub fn allows(schedule: &ScheduleConfig, slot: Slot, tip: Slot) -> bool {
+    let windows = match is_backfill(schedule, slot, tip) {
+        true => &schedule.backfill_windows,
+        false => &schedule.realtime_windows,
+    };
+    is_open(windows, minute_of_day())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn windows(windows: &[&str]) -> Vec<String> {
+        windows.iter().map(|window| window.to_string()).collect()
+    }
+
+    #[test]
+    fn windows_parse_as_minutes_of_the_day() {
+        assert_eq!(parse_window("02:00-06:30"), Ok((120, 390)));
+        assert_eq!(parse_window(" 22:00 - 04:00 "), Ok((1320, 240)));
+        assert_eq!(parse_window("00:00-23:59"), Ok((0, 1439)));
+        for window in ["02:00", "2-6", "24:00-06:00", "02:60-06:00", "02:00-06:00-08:00", "06:00-06:00"] {
+            assert!(parse_window(window).is_err(), "{}", window);
+        }
+    }
+
+    #[test]
+    fn windows_are_open_from_their_start_until_their_end() {
+        let daytime = windows(&["02:00-06:00"]);
+        assert!(!is_open(&daytime, 119));
+        assert!(is_open(&daytime, 120));
+        assert!(is_open(&daytime, 359));
+        assert!(!is_open(&daytime, 360));
+        assert!(!is_open(&daytime, 1320));
+    }
+
+    #[test]
+    fn windows_may_cross_midnight() {
+        let overnight = windows(&["22:00-04:00"]);
+        assert!(!is_open(&overnight, 1319));
+        assert!(is_open(&overnight, 1320));
+        assert!(is_open(&overnight, MINUTES_PER_DAY - 1));
+        assert!(is_open(&overnight, 0));
+        assert!(is_open(&overnight, 239));
+        assert!(!is_open(&overnight, 240));
+        assert!(!is_open(&overnight, 720));
+
+        // Ending at midnight is the same as running until the day ends
+        let evening = windows(&["20:00-00:00"]);
+        assert!(is_open(&evening, MINUTES_PER_DAY - 1));
+        assert!(!is_open(&evening, 0));
+    }
+
+    #[test]
+    fn any_window_may_be_open() {
+        assert!((0..MINUTES_PER_DAY).all(|minute| is_open(&[], minute)));
+
+        let split = windows(&["01:00-02:00", "23:00-00:30"]);
+        assert!(is_open(&split, 60));
+        assert!(is_open(&split, 15));
+        assert!(!is_open(&split, 45));
+        // A window that does not parse is never open, rather than always
+        let broken = windows(&["not a window"]);
+        assert!((0..MINUTES_PER_DAY).all(|minute| !is_open(&broken, minute)));
+        assert!(is_open(&windows(&["not a window", "01:00-02:00"]), 90));
+    }
+
+    #[test]
+    fn backfill_is_the_slots_behind_the_lag() {
+        let schedule = ScheduleConfig {
+            realtime_windows: Vec::new(),
+            backfill_windows: windows(&["not a window"]),
+            backfill_lag_slots: 150,
+        };
+        assert!(!is_backfill(&schedule, 1_000, 1_150));
+        assert!(is_backfill(&schedule, 1_000, 1_151));
+        // Slots past the tip, when the tip is stale, are real time
+        assert!(!is_backfill(&schedule, 1_200, 1_150));
+
+        assert!(allows(&schedule, 1_000, 1_150));
+        assert!(!allows(&schedule, 1_000, 1_151));
+    }
+}
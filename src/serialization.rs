@@ -0,0 +1,303 @@
+// Encodings of Groth16 proofs, verifying keys and public inputs. Proof files,
+// the keyring and the standalone verifier all read what is written here.
+// Proofs are canonically compressed, as bellman writes them, and verifying
+// keys uncompressed, which is what their fingerprints are taken over. Field
+// elements are 32 little-endian bytes.
+//
+// The listener itself only writes compressed proofs; the rest is here for
+// tools built on the proof files, such as those handing proofs to on-chain
+// verifiers, which want uncompressed points.
+#![allow(dead_code)]
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use bellman::groth16;
+use blstrs::{Bls12, G1Affine, G2Affine, Scalar as Fr};
+use ff::PrimeField;
+use group::prime::PrimeCurveAffine;
+use std::fmt;
+use std::io;
+
+pub const FR_LEN: usize = 32;
+pub const G1_COMPRESSED_LEN: usize = 48;
+pub const G2_COMPRESSED_LEN: usize = 96;
+pub const G1_UNCOMPRESSED_LEN: usize = 96;
+pub const G2_UNCOMPRESSED_LEN: usize = 192;
+// A, B and C
+pub const PROOF_COMPRESSED_LEN: usize = 2 * G1_COMPRESSED_LEN + G2_COMPRESSED_LEN;
+pub const PROOF_UNCOMPRESSED_LEN: usize = 2 * G1_UNCOMPRESSED_LEN + G2_UNCOMPRESSED_LEN;
+// alpha, beta and delta in G1, beta, gamma and delta in G2, and the count of IC
+// points that follow
+const VK_FIXED_LEN: usize = 3 * G1_UNCOMPRESSED_LEN + 3 * G2_UNCOMPRESSED_LEN + 4;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Encoding {
+    Compressed,
+    Uncompressed,
+}
+
+impl Encoding {
+    pub fn proof_len(self) -> usize {
+        match self {
+            Encoding::Compressed => PROOF_COMPRESSED_LEN,
+            Encoding::Uncompressed => PROOF_UNCOMPRESSED_LEN,
+        }
+    }
+}
+
+// Length of a serialized verifying key for a circuit with `public_inputs`
+pub fn vk_len(public_inputs: usize) -> usize {
+    VK_FIXED_LEN + (public_inputs + 1) * G1_UNCOMPRESSED_LEN
+}
+
+#[derive(Debug)]
+pub enum SerializationError {
+    Hex(hex::FromHexError),
+    Base64(base64::DecodeError),
+    Length { what: &'static str, expected: usize, actual: usize },
+    // Not on the curve, outside the prime-order subgroup, or the identity
+    InvalidPoint(&'static str),
+    InvalidFieldElement(usize),
+    InvalidKey(io::Error),
+}
+
+impl fmt::Display for SerializationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SerializationError::Hex(e) => write!(f, "invalid hex: {}", e),
+            SerializationError::Base64(e) => write!(f, "invalid base64: {}", e),
+            SerializationError::Length { what, expected, actual } => {
+                write!(f, "{} is {} bytes, expected {}", what, actual, expected)
+            }
+            SerializationError::InvalidPoint(point) => write!(f, "invalid proof point {}", point),
+            SerializationError::InvalidFieldElement(index) => {
+                write!(f, "public input {} is not below the field modulus", index)
+            }
+            SerializationError::InvalidKey(e) => write!(f, "invalid verifying key: {}", e),
+        }
+    }
+}
+
+fn check_len(what: &'static str, bytes: &[u8], expected: usize) -> Result<(), SerializationError> {
+    match bytes.len() == expected {
+        true => Ok(()),
+        false => Err(SerializationError::Length { what, expected, actual: bytes.len() }),
+    }
+}
+
+// Proof points may not be the identity, as bellman requires when reading them
+fn g1(point: Option<G1Affine>, name: &'static str) -> Result<G1Affine, SerializationError> {
+    point.filter(|point| !bool::from(point.is_identity())).ok_or(SerializationError::InvalidPoint(name))
+}
+
+fn g2(point: Option<G2Affine>, name: &'static str) -> Result<G2Affine, SerializationError> {
+    point.filter(|point| !bool::from(point.is_identity())).ok_or(SerializationError::InvalidPoint(name))
+}
+
+pub fn proof_to_bytes(proof: &groth16::Proof<Bls12>, encoding: Encoding) -> Vec<u8> {
+    match encoding {
+        Encoding::Compressed => {
+            [&proof.a.to_compressed()[..], &proof.b.to_compressed(), &proof.c.to_compressed()].concat()
+        }
+        Encoding::Uncompressed => {
+            [&proof.a.to_uncompressed()[..], &proof.b.to_uncompressed(), &proof.c.to_uncompressed()].concat()
+        }
+    }
+}
+
+pub fn proof_from_bytes(bytes: &[u8], encoding: Encoding) -> Result<groth16::Proof<Bls12>, SerializationError> {
+    check_len("proof", bytes, encoding.proof_len())?;
+    let proof = match encoding {
+        Encoding::Compressed => {
+            let (a, rest) = bytes.split_at(G1_COMPRESSED_LEN);
+            let (b, c) = rest.split_at(G2_COMPRESSED_LEN);
+            groth16::Proof {
+                a: g1(G1Affine::from_compressed(a.try_into().unwrap()).into(), "A")?,
+                b: g2(G2Affine::from_compressed(b.try_into().unwrap()).into(), "B")?,
+                c: g1(G1Affine::from_compressed(c.try_into().unwrap()).into(), "C")?,
+            }
+        }
+        Encoding::Uncompressed => {
+            let (a, rest) = bytes.split_at(G1_UNCOMPRESSED_LEN);
+            let (b, c) = rest.split_at(G2_UNCOMPRESSED_LEN);
+            groth16::Proof {
+                a: g1(G1Affine::from_uncompressed(a.try_into().unwrap()).into(), "A")?,
+                b: g2(G2Affine::from_uncompressed(b.try_into().unwrap()).into(), "B")?,
+                c: g1(G1Affine::from_uncompressed(c.try_into().unwrap()).into(), "C")?,
+            }
+        }
+    };
+    Ok(proof)
+}
+
+// The form proofs take in proof files
+pub fn proof_to_hex(proof: &groth16::Proof<Bls12>) -> String {
+    hex::encode(proof_to_bytes(proof, Encoding::Compressed))
+}
+
+pub fn proof_from_hex(data: &str) -> Result<groth16::Proof<Bls12>, SerializationError> {
+    proof_from_bytes(&hex::decode(data).map_err(SerializationError::Hex)?, Encoding::Compressed)
+}
+
+pub fn proof_to_base64(proof: &groth16::Proof<Bls12>) -> String {
+    STANDARD.encode(proof_to_bytes(proof, Encoding::Compressed))
+}
+
+pub fn proof_from_base64(data: &str) -> Result<groth16::Proof<Bls12>, SerializationError> {
+    proof_from_bytes(&STANDARD.decode(data).map_err(SerializationError::Base64)?, Encoding::Compressed)
+}
+
+pub fn vk_to_bytes(vk: &groth16::VerifyingKey<Bls12>) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(vk_len(vk.ic.len().saturating_sub(1)));
+    vk.write(&mut bytes).expect("Unable to serialize verifying key");
+    bytes
+}
+
+pub fn vk_from_bytes(bytes: &[u8]) -> Result<groth16::VerifyingKey<Bls12>, SerializationError> {
+    let mut reader = bytes;
+    let vk = groth16::VerifyingKey::read(&mut reader).map_err(SerializationError::InvalidKey)?;
+    check_len("verifying key", bytes, bytes.len() - reader.len())?;
+    Ok(vk)
+}
+
+pub fn vk_to_hex(vk: &groth16::VerifyingKey<Bls12>) -> String {
+    hex::encode(vk_to_bytes(vk))
+}
+
+pub fn vk_from_hex(data: &str) -> Result<groth16::VerifyingKey<Bls12>, SerializationError> {
+    vk_from_bytes(&hex::decode(data).map_err(SerializationError::Hex)?)
+}
+
+pub fn vk_to_base64(vk: &groth16::VerifyingKey<Bls12>) -> String {
+    STANDARD.encode(vk_to_bytes(vk))
+}
+
+pub fn vk_from_base64(data: &str) -> Result<groth16::VerifyingKey<Bls12>, SerializationError> {
+    vk_from_bytes(&STANDARD.decode(data).map_err(SerializationError::Base64)?)
+}
+
+pub fn inputs_to_bytes(inputs: &[Fr]) -> Vec<u8> {
+    inputs.iter().flat_map(|input| input.to_repr()).collect()
+}
+
+pub fn inputs_from_bytes(bytes: &[u8]) -> Result<Vec<Fr>, SerializationError> {
+    if !bytes.len().is_multiple_of(FR_LEN) {
+        let expected = bytes.len().next_multiple_of(FR_LEN);
+        return Err(SerializationError::Length { what: "public inputs", expected, actual: bytes.len() });
+    }
+    bytes
+        .chunks(FR_LEN)
+        .enumerate()
+        .map(|(index, repr)| {
+            Option::from(Fr::from_repr(repr.try_into().unwrap())).ok_or(SerializationError::InvalidFieldElement(index))
+        })
+        .collect()
+}
+
+pub fn inputs_to_hex(inputs: &[Fr]) -> String {
+    hex::encode(inputs_to_bytes(inputs))
+}
+
+pub fn inputs_from_hex(data: &str) -> Result<Vec<Fr>, SerializationError> {
+    inputs_from_bytes(&hex::decode(data).map_err(SerializationError::Hex)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ff::Field;
+    use group::{Curve, Group};
+    use rand::thread_rng;
+    use solana_block_verifier::{Proof as VerifierProof, VerifyingKey as VerifierKey};
+
+    fn random_proof() -> groth16::Proof<Bls12> {
+        let mut rng = thread_rng();
+        groth16::Proof {
+            a: blstrs::G1Projective::random(&mut rng).to_affine(),
+            b: blstrs::G2Projective::random(&mut rng).to_affine(),
+            c: blstrs::G1Projective::random(&mut rng).to_affine(),
+        }
+    }
+
+    fn random_vk(public_inputs: usize) -> groth16::VerifyingKey<Bls12> {
+        let g1 = || blstrs::G1Projective::random(thread_rng()).to_affine();
+        let g2 = || blstrs::G2Projective::random(thread_rng()).to_affine();
+        let (alpha_g1, beta_g1, delta_g1) = (g1(), g1(), g1());
+        let ic = (0..=public_inputs).map(|_| g1()).collect();
+        groth16::VerifyingKey { alpha_g1, beta_g1, beta_g2: g2(), gamma_g2: g2(), delta_g1, delta_g2: g2(), ic }
+    }
+
+    fn same_proof(left: &groth16::Proof<Bls12>, right: &groth16::Proof<Bls12>) -> bool {
+        left.a == right.a && left.b == right.b && left.c == right.c
+    }
+
+    #[test]
+    fn proof_round_trips() {
+        let proof = random_proof();
+        for encoding in [Encoding::Compressed, Encoding::Uncompressed] {
+            let bytes = proof_to_bytes(&proof, encoding);
+            assert_eq!(bytes.len(), encoding.proof_len());
+            assert!(same_proof(&proof_from_bytes(&bytes, encoding).unwrap(), &proof));
+        }
+        assert!(same_proof(&proof_from_hex(&proof_to_hex(&proof)).unwrap(), &proof));
+        assert!(same_proof(&proof_from_base64(&proof_to_base64(&proof)).unwrap(), &proof));
+    }
+
+    #[test]
+    fn compressed_proof_matches_bellman() {
+        let proof = random_proof();
+        let mut bytes = Vec::new();
+        proof.write(&mut bytes).unwrap();
+        assert_eq!(proof_to_bytes(&proof, Encoding::Compressed), bytes);
+        assert!(same_proof(&groth16::Proof::read(&bytes[..]).unwrap(), &proof));
+    }
+
+    #[test]
+    fn verifier_reads_proofs() {
+        assert!(VerifierProof::from_hex(&proof_to_hex(&random_proof())).is_some());
+    }
+
+    #[test]
+    fn malformed_proofs_are_rejected() {
+        let bytes = proof_to_bytes(&random_proof(), Encoding::Compressed);
+        assert!(matches!(
+            proof_from_bytes(&bytes[1..], Encoding::Compressed),
+            Err(SerializationError::Length { expected: PROOF_COMPRESSED_LEN, .. })
+        ));
+        let identity = [&G1Affine::identity().to_compressed()[..], &bytes[G1_COMPRESSED_LEN..]].concat();
+        assert!(matches!(
+            proof_from_bytes(&identity, Encoding::Compressed),
+            Err(SerializationError::InvalidPoint("A"))
+        ));
+        assert!(matches!(proof_from_hex("zz"), Err(SerializationError::Hex(_))));
+        assert!(matches!(proof_from_base64("!"), Err(SerializationError::Base64(_))));
+    }
+
+    #[test]
+    fn verifying_key_round_trips() {
+        let vk = random_vk(4);
+        let bytes = vk_to_bytes(&vk);
+        assert_eq!(bytes.len(), vk_len(4));
+        for decoded in [vk_from_bytes(&bytes), vk_from_hex(&vk_to_hex(&vk)), vk_from_base64(&vk_to_base64(&vk))] {
+            assert_eq!(vk_to_bytes(&decoded.unwrap()), bytes);
+        }
+        assert!(VerifierKey::read(&bytes).is_some());
+        assert!(vk_from_bytes(&bytes[..bytes.len() - 1]).is_err());
+        assert!(matches!(
+            vk_from_bytes(&[&bytes[..], &[0]].concat()),
+            Err(SerializationError::Length { what: "verifying key", .. })
+        ));
+    }
+
+    #[test]
+    fn public_inputs_round_trip() {
+        let inputs: Vec<Fr> = (0..5).map(|_| Fr::random(thread_rng())).collect();
+        let bytes = inputs_to_bytes(&inputs);
+        assert_eq!(bytes.len(), inputs.len() * FR_LEN);
+        assert_eq!(inputs_from_bytes(&bytes).unwrap(), inputs);
+        assert_eq!(inputs_from_hex(&inputs_to_hex(&inputs)).unwrap(), inputs);
+        assert_eq!(hex::encode(inputs[0].to_repr()), crate::field::fr_to_hex(&inputs[0]));
+        assert!(matches!(inputs_from_bytes(&bytes[1..]), Err(SerializationError::Length { .. })));
+        assert!(matches!(inputs_from_bytes(&[0xff; FR_LEN]), Err(SerializationError::InvalidFieldElement(0))));
+    }
+}
@@ -0,0 +1,120 @@
+#[cfg(feature = "ed25519")]
+use bellman::groth16;
+#[cfg(feature = "ed25519")]
+use blstrs::Bls12;
+use log::warn;
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "ed25519")]
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Signature;
+use solana_transaction_status::EncodedTransactionWithStatusMeta;
+#[cfg(feature = "ed25519")]
+use std::str::FromStr;
+
+#[cfg(feature = "ed25519")]
+use crate::ed25519;
+use crate::params::ProvingKeys;
+use crate::prover::{Cancellation, Cancelled};
+#[cfg(feature = "ed25519")]
+use crate::serialization;
+
+// Longest message the signature circuit hashes: ten SHA-512 blocks hold R, the
+// fee payer's key, the message and its padding. Transactions are capped at
+// 1232 bytes, so only messages close to that cap are left out.
+pub const MAX_MESSAGE_LEN: usize = 10 * 128 - 17 - 64;
+
+// A transaction's first signature, which is the fee payer's, with the message
+// it signs, for proving with the signature circuit
+#[derive(Serialize, Deserialize, Clone)]
+pub struct SignedMessage {
+    pub signature: String,
+    pub fee_payer: String,
+    // Serialized transaction message, hex
+    pub message: String,
+}
+
+// Proof that `signature` verifies against `fee_payer` for a message that stays
+// private. The block proof lists the signature among its transactions.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct SignatureProof {
+    pub signature: String,
+    pub fee_payer: String,
+    pub proof: String,
+}
+
+// The fee payer's signature over a transaction's message; `None` if the
+// message does not fit the circuit or the signature does not verify, since the
+// circuit can only prove signatures that do
+pub fn signed_message(raw: &EncodedTransactionWithStatusMeta) -> Option<SignedMessage> {
+    let transaction = raw.transaction.decode()?;
+    let signature: Signature = *transaction.signatures.first()?;
+    let fee_payer = *transaction.message.static_account_keys().first()?;
+    let message = transaction.message.serialize();
+    if message.len() > MAX_MESSAGE_LEN {
+        warn!(
+            "Message of transaction {} is {} bytes, over the {} the signature circuit holds; not proving its signature",
+            signature,
+            message.len(),
+            MAX_MESSAGE_LEN
+        );
+        return None;
+    }
+    if !signature.verify(fee_payer.as_ref(), &message) {
+        warn!("Signature {} does not verify against fee payer {}; not proving it", signature, fee_payer);
+        return None;
+    }
+    Some(SignedMessage {
+        signature: signature.to_string(),
+        fee_payer: fee_payer.to_string(),
+        message: hex::encode(message),
+    })
+}
+
+#[cfg(feature = "ed25519")]
+fn prove(signed: &SignedMessage, params: &groth16::Parameters<Bls12>) -> SignatureProof {
+    let signature = Signature::from_str(&signed.signature).expect("Malformed signature in witness");
+    let fee_payer = Pubkey::from_str(&signed.fee_payer).expect("Malformed fee payer in witness");
+    let message = hex::decode(&signed.message).expect("Malformed message in witness");
+    let signature_bytes: [u8; 64] = signature.as_ref().try_into().expect("Signatures are 64 bytes");
+    SignatureProof {
+        signature: signed.signature.clone(),
+        fee_payer: signed.fee_payer.clone(),
+        proof: serialization::proof_to_hex(&ed25519::prove_signature(
+            params,
+            &fee_payer.to_bytes(),
+            &signature_bytes,
+            &message,
+        )),
+    }
+}
+
+// Proves each signed message with the signature circuit, stopping before the
+// next proof once cancelled
+#[cfg(feature = "ed25519")]
+pub fn prove_all(
+    signed: &[SignedMessage],
+    keys: &ProvingKeys,
+    cancel: &Cancellation,
+) -> Result<Vec<SignatureProof>, Cancelled> {
+    signed
+        .iter()
+        .map(|signed| {
+            cancel.check()?;
+            Ok(prove(signed, keys.signature()))
+        })
+        .collect()
+}
+
+// Without the signature circuit, a witness's signed messages (made by a build
+// that has it) are left unproved
+#[cfg(not(feature = "ed25519"))]
+pub fn prove_all(
+    signed: &[SignedMessage],
+    _keys: &ProvingKeys,
+    _cancel: &Cancellation,
+) -> Result<Vec<SignatureProof>, Cancelled> {
+    if !signed.is_empty() {
+        warn!("Not proving {} signatures: this build has no signature circuit", signed.len());
+    }
+    Ok(Vec::new())
+}
@@ -0,0 +1,541 @@
+use futures::future::{self, BoxFuture, FutureExt};
+use log::{info, warn};
+use rskafka::chrono::Utc;
+use rskafka::client::partition::{Compression, PartitionClient, UnknownTopicHandling};
+use rskafka::client::ClientBuilder;
+use rskafka::record::Record;
+use rskafka::BackoffConfig;
+use solana_sdk::clock::Slot;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use tokio::sync::{mpsc, Mutex};
+use tokio::task::JoinHandle;
+use tokio::time::{sleep, timeout, Duration};
+
+use crate::amqp::AmqpPublisher;
+use crate::config::{Config, SinkConfig, SinkKind};
+use crate::da::DaPublisher;
+use crate::metrics::{Metrics, SinkEvent};
+use crate::mqtt::MqttPublisher;
+use crate::redis_cache::RedisPublisher;
+use crate::storage::ObjectStorage;
+use crate::{list_slots, load_proof, proof_file_name, BlockProof};
+
+// Attempts at a delivery for sinks that don't configure their own
+const DEFAULT_MAX_ATTEMPTS: u32 = 5;
+
+// Name of the sink uploading proofs to the archive's object storage
+pub const STORAGE_SINK: &str = "storage";
+
+// Wait before the first retry of a delivery, doubled after each further
+// failure up to MAX_BACKOFF
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+// How often a sink that is down is tried again with its oldest dead letter
+const REPLAY_INTERVAL: Duration = Duration::from_secs(60);
+
+// How long a Kafka sink keeps retrying to reach its brokers within one attempt
+const KAFKA_DEADLINE: Duration = Duration::from_secs(30);
+
+// An output block proofs are delivered to once proved and saved
+pub trait ProofSink: Send + Sync {
+    // Delivers one proof; an error has the delivery retried
+    fn deliver<'a>(&'a self, block_proof: &'a BlockProof) -> BoxFuture<'a, Result<(), String>>;
+
+    // Hands over a proof every attempt failed to deliver. Proofs a sink has no
+    // dead-letter destination of its own for are kept in its dead-letter store.
+    fn dead_letter<'a>(&'a self, _block_proof: &'a BlockProof, _reason: &'a str) -> BoxFuture<'a, Result<(), String>> {
+        future::ready(Err("no dead-letter destination".to_string())).boxed()
+    }
+}
+
+// Copies proof files to another directory, such as a mount shared with readers
+struct FileSink {
+    dir: PathBuf,
+}
+
+impl ProofSink for FileSink {
+    fn deliver<'a>(&'a self, block_proof: &'a BlockProof) -> BoxFuture<'a, Result<(), String>> {
+        let json_data = serde_json::to_string_pretty(block_proof).expect("Unable to serialize proof");
+        let result = fs::create_dir_all(&self.dir)
+            .and_then(|()| fs::write(self.dir.join(proof_file_name(block_proof.slot)), json_data))
+            .map_err(|e| format!("unable to write to {:?}: {}", self.dir, e));
+        future::ready(result).boxed()
+    }
+}
+
+// POSTs proofs as JSON to a URL, which must answer with a success status
+struct WebhookSink {
+    client: reqwest::Client,
+    url: String,
+    bearer_token: Option<String>,
+}
+
+impl ProofSink for WebhookSink {
+    fn deliver<'a>(&'a self, block_proof: &'a BlockProof) -> BoxFuture<'a, Result<(), String>> {
+        async move {
+            let request = self.client.post(&self.url).json(block_proof);
+            let request = match &self.bearer_token {
+                Some(token) => request.bearer_auth(token),
+                None => request,
+            };
+            request.send().await.and_then(|response| response.error_for_status()).map_err(|e| e.to_string())?;
+            Ok(())
+        }
+        .boxed()
+    }
+}
+
+// Object storage of the archive, shared with the listener, which swaps it
+// when [storage] is reloaded
+pub type SharedStorage = Arc<RwLock<Option<Arc<ObjectStorage>>>>;
+
+// Uploads proof files to the archive's object storage
+struct StorageSink {
+    storage: SharedStorage,
+}
+
+impl ProofSink for StorageSink {
+    fn deliver<'a>(&'a self, block_proof: &'a BlockProof) -> BoxFuture<'a, Result<(), String>> {
+        async move {
+            // [storage] was removed by a reload since the proof was queued
+            let Some(storage) = self.storage.read().unwrap().clone() else {
+                return Ok(());
+            };
+            let json_data = serde_json::to_string_pretty(block_proof).expect("Unable to serialize proof");
+            storage.put(&proof_file_name(block_proof.slot), json_data.into_bytes()).await.map_err(|e| e.to_string())
+        }
+        .boxed()
+    }
+}
+
+// Produces proofs as JSON to a partition of a Kafka topic, keyed by slot.
+// Connects on the first delivery, and again after one fails.
+struct KafkaSink {
+    brokers: Vec<String>,
+    topic: String,
+    partition: i32,
+    client: Mutex<Option<Arc<PartitionClient>>>,
+}
+
+impl KafkaSink {
+    async fn partition_client(&self) -> Result<Arc<PartitionClient>, String> {
+        let mut client = self.client.lock().await;
+        if let Some(client) = client.as_ref() {
+            return Ok(client.clone());
+        }
+        let backoff = BackoffConfig { deadline: Some(KAFKA_DEADLINE), ..Default::default() };
+        let connected = ClientBuilder::new(self.brokers.clone())
+            .backoff_config(backoff)
+            .build()
+            .await
+            .map_err(|e| format!("unable to reach the brokers: {}", e))?;
+        let partition_client = connected
+            .partition_client(self.topic.clone(), self.partition, UnknownTopicHandling::Error)
+            .await
+            .map_err(|e| format!("unable to open partition {} of {}: {}", self.partition, self.topic, e))?;
+        Ok(client.insert(Arc::new(partition_client)).clone())
+    }
+}
+
+impl ProofSink for KafkaSink {
+    fn deliver<'a>(&'a self, block_proof: &'a BlockProof) -> BoxFuture<'a, Result<(), String>> {
+        async move {
+            let record = Record {
+                key: Some(block_proof.slot.to_string().into_bytes()),
+                value: Some(serde_json::to_vec(block_proof).expect("Unable to serialize proof")),
+                headers: BTreeMap::new(),
+                timestamp: Utc::now(),
+            };
+            let client = self.partition_client().await?;
+            if let Err(e) = client.produce(vec![record], Compression::NoCompression).await {
+                self.client.lock().await.take();
+                return Err(e.to_string());
+            }
+            Ok(())
+        }
+        .boxed()
+    }
+}
+
+// Required settings of each kind were checked when the config was loaded
+fn configured_sink(sink_config: &SinkConfig) -> Box<dyn ProofSink> {
+    match sink_config.kind {
+        SinkKind::File => Box::new(FileSink { dir: sink_config.dir.clone().expect("File sink has no dir") }),
+        SinkKind::Webhook => Box::new(WebhookSink {
+            client: reqwest::Client::builder()
+                .timeout(Duration::from_secs(30))
+                .build()
+                .expect("Unable to build webhook sink client"),
+            url: sink_config.url.clone().expect("Webhook sink has no url"),
+            bearer_token: sink_config.bearer_token.clone(),
+        }),
+        SinkKind::Kafka => Box::new(KafkaSink {
+            brokers: sink_config.brokers.clone(),
+            topic: sink_config.topic.clone().expect("Kafka sink has no topic"),
+            partition: sink_config.partition,
+            client: Mutex::new(None),
+        }),
+    }
+}
+
+pub struct ConfiguredSink {
+    pub name: String,
+    sink: Box<dyn ProofSink>,
+    max_attempts: u32,
+}
+
+pub fn storage_sink(storage: &SharedStorage) -> ConfiguredSink {
+    let sink = Box::new(StorageSink { storage: storage.clone() });
+    ConfiguredSink { name: STORAGE_SINK.to_string(), sink, max_attempts: DEFAULT_MAX_ATTEMPTS }
+}
+
+// Every sink of the config, the built-in ones first, uploading to `storage`
+// if it is set
+pub fn configured(config: &Config, storage: &SharedStorage, instance: Option<&str>) -> Vec<ConfiguredSink> {
+    let mut sinks = Vec::new();
+    if storage.read().unwrap().is_some() {
+        sinks.push(storage_sink(storage));
+    }
+    let mut add = |name: &str, sink: Box<dyn ProofSink>, max_attempts| {
+        sinks.push(ConfiguredSink { name: name.to_string(), sink, max_attempts });
+    };
+    if let Some(da_config) = &config.data_availability {
+        let publisher = DaPublisher::new(da_config).expect("Unable to configure the data-availability layer");
+        add("data_availability", Box::new(publisher), DEFAULT_MAX_ATTEMPTS);
+    }
+    if let Some(mqtt_config) = &config.mqtt {
+        let publisher = MqttPublisher::start(mqtt_config, instance).expect("Unable to configure MQTT");
+        add("mqtt", Box::new(publisher), DEFAULT_MAX_ATTEMPTS);
+    }
+    if let Some(redis_config) = &config.redis {
+        let publisher = RedisPublisher::new(redis_config, instance).expect("Unable to configure Redis");
+        add("redis", Box::new(publisher), DEFAULT_MAX_ATTEMPTS);
+    }
+    if let Some(amqp_config) = &config.amqp {
+        add("amqp", Box::new(AmqpPublisher::new(amqp_config)), amqp_config.max_attempts);
+    }
+    for sink_config in &config.sinks {
+        add(sink_config.name(), configured_sink(sink_config), sink_config.max_attempts);
+    }
+    sinks
+}
+
+// Proofs a sink could not be given, kept as `sinks/<name>/<slot>.json` in the
+// proofs directory until they are redelivered
+pub struct DeadLetterStore {
+    dir: PathBuf,
+}
+
+impl DeadLetterStore {
+    pub fn open(proofs_dir: &Path, sink: &str) -> Self {
+        let dir = proofs_dir.join("sinks").join(sink);
+        fs::create_dir_all(&dir).expect("Unable to create dead-letter directory");
+        DeadLetterStore { dir }
+    }
+
+    fn path(&self, slot: Slot) -> PathBuf {
+        self.dir.join(format!("{}.json", slot))
+    }
+
+    fn put(&self, block_proof: &BlockProof) {
+        let json_data = serde_json::to_vec(block_proof).expect("Unable to serialize proof");
+        fs::write(self.path(block_proof.slot), json_data).expect("Unable to write dead letter");
+    }
+
+    // Slots of the stored proofs, in ascending order
+    pub fn slots(&self) -> Vec<Slot> {
+        list_slots(&self.dir, "")
+    }
+}
+
+// Delivers the stored proofs of a sink in slot order, once each, removing
+// those the sink takes. Stops at the first that fails, returning how many
+// were delivered and whether the store is now empty.
+pub async fn redeliver(sink: &ConfiguredSink, store: &DeadLetterStore) -> (usize, bool) {
+    let mut delivered = 0;
+    for slot in store.slots() {
+        let path = store.path(slot);
+        let block_proof = load_proof(&path);
+        if let Err(e) = sink.sink.deliver(&block_proof).await {
+            warn!("Unable to redeliver block proof {} to {}: {}", slot, sink.name, e);
+            return (delivered, false);
+        }
+        fs::remove_file(&path).expect("Unable to remove dead letter");
+        delivered += 1;
+    }
+    (delivered, true)
+}
+
+struct SinkWorker {
+    sink: ConfiguredSink,
+    store: DeadLetterStore,
+    instance: Option<String>,
+    metrics: Arc<Metrics>,
+}
+
+impl SinkWorker {
+    // While the sink is down, proofs are stored rather than attempted, and
+    // every REPLAY_INTERVAL the store is replayed to find out whether it is
+    // back. Proofs stored by an earlier run are replayed first.
+    async fn run(self, mut proofs: mpsc::UnboundedReceiver<Arc<BlockProof>>) {
+        let mut down = !self.replay().await;
+        loop {
+            let block_proof = match timeout(REPLAY_INTERVAL, proofs.recv()).await {
+                Ok(Some(block_proof)) => block_proof,
+                Ok(None) => return,
+                Err(_) => {
+                    if down {
+                        down = !self.replay().await;
+                    }
+                    continue;
+                }
+            };
+            if down {
+                self.store(&block_proof);
+                continue;
+            }
+            down = !self.deliver(&block_proof).await;
+        }
+    }
+
+    fn record(&self, event: SinkEvent) {
+        self.metrics.record_sink(self.instance.as_deref(), &self.sink.name, event);
+    }
+
+    fn store(&self, block_proof: &BlockProof) {
+        self.store.put(block_proof);
+        self.record(SinkEvent::Stored);
+        let dead_letters = self.store.slots().len();
+        self.metrics.record_sink_dead_letters(self.instance.as_deref(), &self.sink.name, dead_letters);
+    }
+
+    // Whether the store was emptied
+    async fn replay(&self) -> bool {
+        let pending = self.store.slots().len();
+        if pending == 0 {
+            return true;
+        }
+        let (delivered, emptied) = redeliver(&self.sink, &self.store).await;
+        for _ in 0..delivered {
+            self.record(SinkEvent::Redelivered);
+        }
+        self.metrics.record_sink_dead_letters(self.instance.as_deref(), &self.sink.name, pending - delivered);
+        if delivered > 0 {
+            info!("Redelivered {} of {} stored block proofs to {}", delivered, pending, self.sink.name);
+        }
+        emptied
+    }
+
+    // Whether the sink is still up: `false` once every attempt failed and the
+    // proof was stored
+    async fn deliver(&self, block_proof: &BlockProof) -> bool {
+        let mut backoff = INITIAL_BACKOFF;
+        let mut reason = String::new();
+        for attempt in 1..=self.sink.max_attempts.max(1) {
+            if attempt > 1 {
+                sleep(backoff).await;
+                backoff = (backoff * 2).min(MAX_BACKOFF);
+            }
+            match self.sink.sink.deliver(block_proof).await {
+                Ok(()) => {
+                    self.record(SinkEvent::Delivered);
+                    return true;
+                }
+                Err(e) => {
+                    let slot = block_proof.slot;
+                    warn!("Attempt {} to deliver block proof {} to {} failed: {}", attempt, slot, self.sink.name, e);
+                    self.record(SinkEvent::FailedAttempt);
+                    reason = e;
+                }
+            }
+        }
+
+        match self.sink.sink.dead_letter(block_proof, &reason).await {
+            Ok(()) => {
+                warn!("Dead-lettered block proof {} for {}: {}", block_proof.slot, self.sink.name, reason);
+                self.record(SinkEvent::DeadLettered);
+                true
+            }
+            Err(e) => {
+                warn!("Storing block proof {} until {} is back: {} ({})", block_proof.slot, self.sink.name, reason, e);
+                self.store(block_proof);
+                false
+            }
+        }
+    }
+}
+
+// Fans block proofs out to every configured sink. Each sink has a queue and
+// a task of its own, so one that is slow or down holds up no other; proofs
+// reach each sink in the order they were proved, with those it missed while
+// down redelivered once it is back.
+pub struct Sinks {
+    queues: Vec<(String, mpsc::UnboundedSender<Arc<BlockProof>>)>,
+    workers: Vec<JoinHandle<()>>,
+    instance: Option<String>,
+    metrics: Arc<Metrics>,
+}
+
+impl Sinks {
+    pub fn start(sinks: Vec<ConfiguredSink>, proofs_dir: &Path, instance: Option<&str>, metrics: Arc<Metrics>) -> Self {
+        let mut workers = Vec::new();
+        let queues = sinks
+            .into_iter()
+            .map(|sink| {
+                let (queue, receiver) = mpsc::unbounded_channel();
+                let name = sink.name.clone();
+                let worker = SinkWorker {
+                    store: DeadLetterStore::open(proofs_dir, &name),
+                    sink,
+                    instance: instance.map(str::to_string),
+                    metrics: metrics.clone(),
+                };
+                workers.push(tokio::spawn(worker.run(receiver)));
+                (name, queue)
+            })
+            .collect();
+        Sinks { queues, workers, instance: instance.map(str::to_string), metrics }
+    }
+
+    // Waits until every proof published has been delivered or stored
+    pub async fn close(self) {
+        drop(self.queues);
+        for worker in self.workers {
+            let _ = worker.await;
+        }
+    }
+
+    pub fn publish(&self, block_proof: &BlockProof) {
+        if self.queues.is_empty() {
+            return;
+        }
+        let block_proof = Arc::new(block_proof.clone());
+        for (name, queue) in &self.queues {
+            self.metrics.record_sink(self.instance.as_deref(), name, SinkEvent::Queued);
+            let _ = queue.send(block_proof.clone());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    #[derive(Default)]
+    struct Deliveries {
+        failing: AtomicBool,
+        // Whether the sink has a dead-letter destination of its own
+        destination: bool,
+        delivered: std::sync::Mutex<Vec<Slot>>,
+        dead_lettered: std::sync::Mutex<Vec<Slot>>,
+    }
+
+    struct TestSink(Arc<Deliveries>);
+
+    impl ProofSink for TestSink {
+        fn deliver<'a>(&'a self, block_proof: &'a BlockProof) -> BoxFuture<'a, Result<(), String>> {
+            let result = if self.0.failing.load(Ordering::SeqCst) {
+                Err("down".to_string())
+            } else {
+                self.0.delivered.lock().unwrap().push(block_proof.slot);
+                Ok(())
+            };
+            future::ready(result).boxed()
+        }
+
+        fn dead_letter<'a>(
+            &'a self,
+            block_proof: &'a BlockProof,
+            _reason: &'a str,
+        ) -> BoxFuture<'a, Result<(), String>> {
+            let result = if self.0.destination {
+                self.0.dead_lettered.lock().unwrap().push(block_proof.slot);
+                Ok(())
+            } else {
+                Err("no dead-letter destination".to_string())
+            };
+            future::ready(result).boxed()
+        }
+    }
+
+    fn configured(deliveries: &Arc<Deliveries>) -> ConfiguredSink {
+        ConfiguredSink { name: "test".to_string(), sink: Box::new(TestSink(deliveries.clone())), max_attempts: 1 }
+    }
+
+    fn block_proof(slot: Slot) -> BlockProof {
+        serde_json::from_value(serde_json::json!({
+            "slot": slot,
+            "block_hash": "hash",
+            "transactions_root": "00",
+            "commitment": "00",
+            "proof": "00",
+            "old_root": "00",
+            "new_root": "00",
+            "transactions": [],
+        }))
+        .unwrap()
+    }
+
+    fn proofs_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("solana-listener-sink-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    // Publishes proofs of `slots` to a sink and waits for them to be delivered
+    // or stored
+    async fn publish(deliveries: &Arc<Deliveries>, proofs_dir: &Path, slots: &[Slot]) {
+        let sinks = Sinks::start(vec![configured(deliveries)], proofs_dir, None, Arc::new(Metrics::default()));
+        for slot in slots {
+            sinks.publish(&block_proof(*slot));
+        }
+        sinks.close().await;
+    }
+
+    #[tokio::test]
+    async fn failed_deliveries_are_stored_and_replayed() {
+        let proofs_dir = proofs_dir("replay");
+        let deliveries = Arc::new(Deliveries::default());
+        deliveries.failing.store(true, Ordering::SeqCst);
+        publish(&deliveries, &proofs_dir, &[3, 1, 2]).await;
+        let store = DeadLetterStore::open(&proofs_dir, "test");
+        assert_eq!(store.slots(), [1, 2, 3]);
+        assert!(deliveries.delivered.lock().unwrap().is_empty());
+
+        // Still down: nothing is taken from the store
+        assert_eq!(redeliver(&configured(&deliveries), &store).await, (0, false));
+        assert_eq!(store.slots(), [1, 2, 3]);
+
+        deliveries.failing.store(false, Ordering::SeqCst);
+        assert_eq!(redeliver(&configured(&deliveries), &store).await, (3, true));
+        assert!(store.slots().is_empty());
+        assert_eq!(*deliveries.delivered.lock().unwrap(), [1, 2, 3]);
+
+        // Proofs stored by an earlier run are replayed before new ones
+        deliveries.failing.store(true, Ordering::SeqCst);
+        publish(&deliveries, &proofs_dir, &[4]).await;
+        deliveries.failing.store(false, Ordering::SeqCst);
+        publish(&deliveries, &proofs_dir, &[5]).await;
+        assert!(store.slots().is_empty());
+        assert_eq!(*deliveries.delivered.lock().unwrap(), [1, 2, 3, 4, 5]);
+        fs::remove_dir_all(&proofs_dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn failed_deliveries_go_to_the_dead_letter_destination() {
+        let proofs_dir = proofs_dir("destination");
+        let deliveries = Arc::new(Deliveries { destination: true, ..Default::default() });
+        deliveries.failing.store(true, Ordering::SeqCst);
+        publish(&deliveries, &proofs_dir, &[1, 2]).await;
+
+        // Each proof was attempted and handed over rather than stored
+        assert_eq!(*deliveries.dead_lettered.lock().unwrap(), [1, 2]);
+        assert!(DeadLetterStore::open(&proofs_dir, "test").slots().is_empty());
+        fs::remove_dir_all(&proofs_dir).unwrap();
+    }
+}
@@ -0,0 +1,353 @@
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use solana_sdk::clock::Slot;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::{Component, Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::checkpoint::Checkpoint;
+use crate::manifest::Manifest;
+
+const VERSION: u8 = 1;
+const COMPRESSION_LEVEL: i32 = 3;
+const HEADER: &str = "snapshot.json";
+
+// Prover state of a proofs directory, for moving a listener to another host:
+// its checkpoint (and so accumulator root), manifest, slot index with the
+// slots that failed, nullifiers, keyring, spilled queue and the dead letters
+// of its sinks. Proofs, block snapshots and witnesses are archive data and
+// move with the proofs directory or object storage instead.
+const STATE_FILES: [&str; 4] = ["checkpoint.json", "manifest.json", "index.jsonl", "nullifiers.jsonl"];
+const STATE_DIRS: [&str; 3] = ["keys", "queue", "sinks"];
+
+// Restored last, so an interrupted restore never looks like a complete one
+const CHECKPOINT: &str = "checkpoint.json";
+
+// First entry of a snapshot
+#[derive(Serialize, Deserialize)]
+pub struct SnapshotHeader {
+    pub version: u8,
+    pub created_at: u64,
+    pub last_slot: Slot,
+    pub accumulator_root: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub genesis_hash: Option<String>,
+    // SHA-256 (hex) of every file that follows, by path in the proofs directory
+    pub files: BTreeMap<String, String>,
+}
+
+#[derive(Debug)]
+pub enum SnapshotError {
+    Io(io::Error),
+    Invalid(String),
+    // The proofs directory has no checkpoint to take a snapshot of
+    NoState(PathBuf),
+    // The proofs directory already has a checkpoint, and restoring was not forced
+    HasState(PathBuf),
+}
+
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SnapshotError::Io(e) => write!(f, "{}", e),
+            SnapshotError::Invalid(reason) => write!(f, "invalid snapshot: {}", reason),
+            SnapshotError::NoState(dir) => write!(f, "{:?} has no checkpoint to take a snapshot of", dir),
+            SnapshotError::HasState(dir) => {
+                write!(f, "{:?} already has a checkpoint; pass --force to replace its state", dir)
+            }
+        }
+    }
+}
+
+impl From<io::Error> for SnapshotError {
+    fn from(e: io::Error) -> Self {
+        SnapshotError::Io(e)
+    }
+}
+
+fn collect(proofs_dir: &Path, relative: &Path, files: &mut Vec<PathBuf>) -> io::Result<()> {
+    let path = proofs_dir.join(relative);
+    if path.is_dir() {
+        let mut names = fs::read_dir(&path)?.map(|entry| Ok(entry?.file_name())).collect::<io::Result<Vec<_>>>()?;
+        names.sort();
+        for name in names {
+            collect(proofs_dir, &relative.join(name), files)?;
+        }
+    } else if path.is_file() && path.extension().is_none_or(|extension| extension != "tmp") {
+        files.push(relative.to_path_buf());
+    }
+    Ok(())
+}
+
+// Paths a snapshot may hold, so restoring one never writes outside the state
+fn is_state_path(name: &str) -> bool {
+    let path = Path::new(name);
+    let Some(Component::Normal(first)) = path.components().next() else {
+        return false;
+    };
+    let first = first.to_string_lossy();
+    path.components().all(|component| matches!(component, Component::Normal(_)))
+        && (STATE_FILES.contains(&name) || (STATE_DIRS.contains(&first.as_ref()) && path.components().count() > 1))
+}
+
+// Written to a temporary file first so a crash never leaves a torn snapshot
+pub fn create(proofs_dir: &Path, output: &Path) -> Result<SnapshotHeader, SnapshotError> {
+    if !proofs_dir.join(CHECKPOINT).exists() {
+        return Err(SnapshotError::NoState(proofs_dir.to_path_buf()));
+    }
+    let mut paths = Vec::new();
+    for name in STATE_FILES.iter().chain(&STATE_DIRS) {
+        collect(proofs_dir, Path::new(name), &mut paths)?;
+    }
+    let mut contents = BTreeMap::new();
+    for path in paths {
+        let name = path.to_string_lossy().replace('\\', "/");
+        contents.insert(name, fs::read(proofs_dir.join(&path))?);
+    }
+
+    let checkpoint = Checkpoint::load(proofs_dir);
+    let header = SnapshotHeader {
+        version: VERSION,
+        created_at: SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs(),
+        last_slot: checkpoint.last_slot,
+        accumulator_root: checkpoint.accumulator_root,
+        genesis_hash: Manifest::load(proofs_dir).and_then(|manifest| manifest.genesis_hash),
+        files: contents.iter().map(|(name, data)| (name.clone(), hex::encode(Sha256::digest(data)))).collect(),
+    };
+
+    let tmp_path = output.with_extension("tmp");
+    let encoder = zstd::Encoder::new(File::create(&tmp_path)?, COMPRESSION_LEVEL)?;
+    let mut archive = tar::Builder::new(encoder);
+    let header_json = serde_json::to_vec_pretty(&header).expect("Unable to serialize snapshot header");
+    let entries = contents.iter().map(|(name, data)| (name.as_str(), data));
+    for (name, data) in [(HEADER, &header_json)].into_iter().chain(entries) {
+        let mut entry = tar::Header::new_gnu();
+        entry.set_size(data.len() as u64);
+        entry.set_mode(0o644);
+        entry.set_mtime(header.created_at);
+        archive.append_data(&mut entry, name, data.as_slice())?;
+    }
+    archive.into_inner()?.finish()?;
+    fs::rename(&tmp_path, output)?;
+    Ok(header)
+}
+
+fn read(snapshot: &Path) -> Result<(SnapshotHeader, BTreeMap<String, Vec<u8>>), SnapshotError> {
+    let mut archive = tar::Archive::new(zstd::Decoder::new(File::open(snapshot)?)?);
+    let mut header = None;
+    let mut contents = BTreeMap::new();
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let name = entry.path()?.to_string_lossy().into_owned();
+        let mut data = Vec::new();
+        entry.read_to_end(&mut data)?;
+        match (name.as_str(), &header) {
+            (HEADER, None) => {
+                let parsed: SnapshotHeader = serde_json::from_slice(&data)
+                    .map_err(|e| SnapshotError::Invalid(format!("unreadable header: {}", e)))?;
+                if parsed.version != VERSION {
+                    return Err(SnapshotError::Invalid(format!("unknown version {}", parsed.version)));
+                }
+                header = Some(parsed);
+            }
+            (_, None) => return Err(SnapshotError::Invalid(format!("{} comes before the header", name))),
+            (_, Some(_)) if !is_state_path(&name) => {
+                return Err(SnapshotError::Invalid(format!("{:?} is not prover state", name)))
+            }
+            (_, Some(_)) => {
+                contents.insert(name, data);
+            }
+        }
+    }
+    let header = header.ok_or_else(|| SnapshotError::Invalid("no header".to_string()))?;
+
+    for (name, hash) in &header.files {
+        let data = contents.get(name).ok_or_else(|| SnapshotError::Invalid(format!("{} is missing", name)))?;
+        if &hex::encode(Sha256::digest(data)) != hash {
+            return Err(SnapshotError::Invalid(format!("{} does not match its hash", name)));
+        }
+    }
+    if let Some(name) = contents.keys().find(|name| !header.files.contains_key(*name)) {
+        return Err(SnapshotError::Invalid(format!("{} is not listed in the header", name)));
+    }
+    Ok((header, contents))
+}
+
+// Replaces the prover state of `proofs_dir` with the snapshot's. Nothing is
+// written unless the whole snapshot checks out.
+pub fn restore(snapshot: &Path, proofs_dir: &Path, force: bool) -> Result<SnapshotHeader, SnapshotError> {
+    let (header, mut contents) = read(snapshot)?;
+    if proofs_dir.join(CHECKPOINT).exists() && !force {
+        return Err(SnapshotError::HasState(proofs_dir.to_path_buf()));
+    }
+
+    // State left from before would not match the snapshot's checkpoint
+    for dir in STATE_DIRS {
+        match fs::remove_dir_all(proofs_dir.join(dir)) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e.into()),
+            _ => {}
+        }
+    }
+    for name in STATE_FILES.iter().filter(|name| !contents.contains_key(**name)) {
+        match fs::remove_file(proofs_dir.join(name)) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e.into()),
+            _ => {}
+        }
+    }
+    let checkpoint = contents.remove(CHECKPOINT);
+    for (name, data) in &contents {
+        write(proofs_dir, name, data)?;
+    }
+    if let Some(data) = checkpoint {
+        write(proofs_dir, CHECKPOINT, &data)?;
+    }
+    Ok(header)
+}
+
+fn write(proofs_dir: &Path, name: &str, data: &[u8]) -> io::Result<()> {
+    let path = proofs_dir.join(name);
+    let tmp_path = path.with_extension("restore.tmp");
+    fs::create_dir_all(path.parent().unwrap())?;
+    fs::write(&tmp_path, data)?;
+    fs::rename(&tmp_path, &path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type Entries<'a> = &'a [(&'a str, &'a [u8])];
+
+    fn state_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("solana-listener-snapshot-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn write_state(dir: &Path) {
+        let mut checkpoint = Checkpoint::default();
+        checkpoint.advance(42, checkpoint.accumulator_root.clone());
+        checkpoint.save(dir);
+        fs::write(dir.join("index.jsonl"), "{\"slot\":42}\n").unwrap();
+        fs::create_dir_all(dir.join("keys")).unwrap();
+        fs::write(dir.join("keys/signer.json"), "{}").unwrap();
+        fs::create_dir_all(dir.join("sinks/webhook")).unwrap();
+        fs::write(dir.join("sinks/webhook/dead_letters.jsonl"), "41\n").unwrap();
+        // Archive data stays out of a snapshot
+        fs::create_dir_all(dir.join("proofs")).unwrap();
+        fs::write(dir.join("proofs/42.json"), "{}").unwrap();
+    }
+
+    // A snapshot with the given header files and entries, for archives create would never write
+    fn archive(path: &Path, files: Entries, entries: Entries) {
+        let header = SnapshotHeader {
+            version: VERSION,
+            created_at: 0,
+            last_slot: 42,
+            accumulator_root: Checkpoint::default().accumulator_root,
+            genesis_hash: None,
+            files: files.iter().map(|(name, data)| (name.to_string(), hex::encode(Sha256::digest(data)))).collect(),
+        };
+        let header_json = serde_json::to_vec(&header).unwrap();
+        let mut archive = tar::Builder::new(zstd::Encoder::new(File::create(path).unwrap(), 0).unwrap());
+        for (name, data) in [(HEADER, header_json.as_slice())].into_iter().chain(entries.iter().copied()) {
+            let mut entry = tar::Header::new_gnu();
+            entry.set_size(data.len() as u64);
+            entry.set_mode(0o644);
+            archive.append_data(&mut entry, name, data).unwrap();
+        }
+        archive.into_inner().unwrap().finish().unwrap();
+    }
+
+    #[test]
+    fn snapshots_round_trip() {
+        let source = state_dir("source");
+        let target = state_dir("target");
+        write_state(&source);
+        let snapshot = source.join("state.snapshot");
+
+        let header = create(&source, &snapshot).unwrap();
+        assert_eq!(header.last_slot, 42);
+        assert!(header.files.contains_key("keys/signer.json"));
+        assert!(!header.files.keys().any(|name| name.starts_with("proofs")));
+
+        // State left in the target from before is replaced, not merged
+        fs::create_dir_all(target.join("keys")).unwrap();
+        fs::write(target.join("keys/stale.json"), "{}").unwrap();
+        fs::write(target.join("nullifiers.jsonl"), "stale\n").unwrap();
+        let restored = restore(&snapshot, &target, false).unwrap();
+        assert_eq!(restored.files, header.files);
+        for name in header.files.keys() {
+            assert_eq!(fs::read(source.join(name)).unwrap(), fs::read(target.join(name)).unwrap(), "{}", name);
+        }
+        assert!(!target.join("keys/stale.json").exists());
+        assert!(!target.join("nullifiers.jsonl").exists());
+        assert!(!target.join("proofs").exists());
+        assert_eq!(Checkpoint::load(&target).last_slot, 42);
+
+        // A restored directory has a checkpoint, so replacing it must be forced
+        assert!(matches!(restore(&snapshot, &target, false), Err(SnapshotError::HasState(_))));
+        assert!(restore(&snapshot, &target, true).is_ok());
+
+        fs::remove_dir_all(&source).unwrap();
+        fs::remove_dir_all(&target).unwrap();
+    }
+
+    #[test]
+    fn snapshots_need_a_checkpoint() {
+        let dir = state_dir("empty");
+        let snapshot = dir.join("state.snapshot");
+        assert!(matches!(create(&dir, &snapshot), Err(SnapshotError::NoState(_))));
+        assert!(!snapshot.exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn partial_snapshots_are_rejected() {
+        let source = state_dir("partial-source");
+        let target = state_dir("partial-target");
+        write_state(&source);
+        let snapshot = source.join("state.snapshot");
+        create(&source, &snapshot).unwrap();
+
+        let data = fs::read(&snapshot).unwrap();
+        fs::write(&snapshot, &data[..data.len() / 2]).unwrap();
+        assert!(restore(&snapshot, &target, false).is_err());
+        // Nothing is written unless the whole snapshot checks out
+        assert_eq!(fs::read_dir(&target).unwrap().count(), 0);
+
+        fs::remove_dir_all(&source).unwrap();
+        fs::remove_dir_all(&target).unwrap();
+    }
+
+    #[test]
+    fn corrupt_snapshots_are_rejected() {
+        let dir = state_dir("corrupt");
+        let target = dir.join("target");
+        let snapshot = dir.join("state.snapshot");
+        let checkpoint = serde_json::to_vec(&Checkpoint::default()).unwrap();
+        let checkpoint = checkpoint.as_slice();
+        let cases: [(&str, Entries, Entries); 4] = [
+            ("tampered", &[(CHECKPOINT, checkpoint)], &[(CHECKPOINT, b"{}")]),
+            ("missing", &[(CHECKPOINT, checkpoint), ("index.jsonl", b"")], &[(CHECKPOINT, checkpoint)]),
+            ("unlisted", &[(CHECKPOINT, checkpoint)], &[(CHECKPOINT, checkpoint), ("index.jsonl", b"")]),
+            ("not state", &[("proofs/42.json", b"{}")], &[("proofs/42.json", b"{}")]),
+        ];
+        for (case, files, entries) in cases {
+            archive(&snapshot, files, entries);
+            assert!(matches!(restore(&snapshot, &target, false), Err(SnapshotError::Invalid(_))), "{}", case);
+            assert!(!target.exists(), "{}", case);
+        }
+
+        // Not a snapshot at all
+        fs::write(&snapshot, b"not a snapshot").unwrap();
+        assert!(restore(&snapshot, &target, false).is_err());
+        assert!(!target.exists());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,45 @@
+use serde::{Deserialize, Serialize};
+use solana_client::rpc_response::RpcVoteAccountStatus;
+use solana_sdk::clock::Slot;
+
+// Stake-weighted evidence that the cluster confirmed a block, taken from the
+// vote accounts when the block is processed. Stakes are in lamports.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct StakeConfirmation {
+    // Active stake across all vote accounts, delinquent ones included
+    pub total_stake: u64,
+    // Stake whose latest vote is at or beyond the slot
+    pub voted_stake: u64,
+    // Stake that has rooted the slot
+    pub rooted_stake: u64,
+    pub voters: usize,
+}
+
+impl StakeConfirmation {
+    // Whether more than two thirds of the stake has rooted the slot
+    pub fn is_supermajority(&self) -> bool {
+        self.rooted_stake as u128 * 3 > self.total_stake as u128 * 2
+    }
+}
+
+pub fn confirmation(status: &RpcVoteAccountStatus, slot: Slot) -> StakeConfirmation {
+    let mut summary = StakeConfirmation {
+        total_stake: 0,
+        voted_stake: 0,
+        rooted_stake: 0,
+        voters: 0,
+    };
+
+    for account in status.current.iter().chain(&status.delinquent) {
+        summary.total_stake += account.activated_stake;
+        if account.last_vote >= slot {
+            summary.voted_stake += account.activated_stake;
+            summary.voters += 1;
+        }
+        if account.root_slot >= slot {
+            summary.rooted_stake += account.activated_stake;
+        }
+    }
+
+    summary
+}
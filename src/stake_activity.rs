@@ -0,0 +1,79 @@
+use serde::{Deserialize, Serialize};
+use solana_sdk::program_utils::limited_deserialize;
+use solana_sdk::stake::{self, instruction::StakeInstruction};
+use solana_transaction_status::EncodedConfirmedBlock;
+
+use crate::instructions;
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum StakeActivityKind {
+    Delegated,
+    Redelegated,
+    Deactivated,
+    // Deactivated by anyone because the vote account stopped voting
+    DeactivatedDelinquent,
+}
+
+// A stake delegation or deactivation made by a successful transaction of the
+// block. `authority` is the stake authority that signed it, if one was required.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct StakeActivity {
+    pub signature: String,
+    pub kind: StakeActivityKind,
+    pub stake_account: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vote_account: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub authority: Option<String>,
+}
+
+pub fn activity(block: &EncodedConfirmedBlock) -> Vec<StakeActivity> {
+    let mut activity = Vec::new();
+    let stake_program = stake::program::id().to_string();
+
+    for transaction_with_meta in &block.transactions {
+        let Some(transaction) = instructions::decode(transaction_with_meta) else {
+            continue;
+        };
+        if !transaction.succeeded {
+            continue;
+        }
+
+        for instruction in transaction.instructions.iter().filter(|instruction| instruction.program_id == stake_program) {
+            let accounts = instruction.accounts.as_slice();
+            let (kind, stake_account, vote_account, authority) = match (limited_deserialize(&instruction.data), accounts) {
+                (Ok(StakeInstruction::DelegateStake), [stake, vote, _, _, _, authority, ..]) => {
+                    (StakeActivityKind::Delegated, stake, Some(vote), Some(authority))
+                }
+                (Ok(StakeInstruction::Redelegate), [stake, _, vote, _, authority, ..]) => {
+                    (StakeActivityKind::Redelegated, stake, Some(vote), Some(authority))
+                }
+                (Ok(StakeInstruction::Deactivate), [stake, _, authority, ..]) => {
+                    (StakeActivityKind::Deactivated, stake, None, Some(authority))
+                }
+                (Ok(StakeInstruction::DeactivateDelinquent), [stake, vote, ..]) => {
+                    (StakeActivityKind::DeactivatedDelinquent, stake, Some(vote), None)
+                }
+                _ => continue,
+            };
+
+            activity.push(StakeActivity {
+                signature: transaction.signature.clone(),
+                kind,
+                stake_account: stake_account.clone(),
+                vote_account: vote_account.cloned(),
+                authority: authority.cloned(),
+            });
+        }
+    }
+
+    activity
+}
+
+// Whether any of the activity was signed by one of `authorities`
+pub fn by_any(activity: &[StakeActivity], authorities: &[String]) -> bool {
+    activity
+        .iter()
+        .any(|activity| activity.authority.as_ref().is_some_and(|authority| authorities.contains(authority)))
+}
@@ -0,0 +1,63 @@
+use crate::config::{StorageBackend, StorageConfig};
+use object_store::aws::AmazonS3Builder;
+use object_store::azure::MicrosoftAzureBuilder;
+use object_store::gcp::GoogleCloudStorageBuilder;
+use object_store::path::Path as ObjectPath;
+use object_store::ObjectStore;
+
+// Uploads proof files to S3, Google Cloud Storage or Azure Blob Storage. Files
+// of an archive with a known cluster go under `<prefix>/<genesis hash>/`.
+pub struct ObjectStorage {
+    store: Box<dyn ObjectStore>,
+    prefix: ObjectPath,
+}
+
+impl ObjectStorage {
+    pub fn from_config(
+        config: &StorageConfig,
+        genesis_hash: Option<&str>,
+    ) -> Result<ObjectStorage, object_store::Error> {
+        let store: Box<dyn ObjectStore> = match config.backend {
+            StorageBackend::S3 => {
+                let mut builder = AmazonS3Builder::from_env().with_bucket_name(&config.bucket);
+                for (key, value) in &config.options {
+                    builder = builder.with_config(key.parse()?, value);
+                }
+                Box::new(builder.build()?)
+            }
+            StorageBackend::Gcs => {
+                let mut builder = GoogleCloudStorageBuilder::from_env().with_bucket_name(&config.bucket);
+                for (key, value) in &config.options {
+                    builder = builder.with_config(key.parse()?, value);
+                }
+                Box::new(builder.build()?)
+            }
+            StorageBackend::Azure => {
+                let mut builder = MicrosoftAzureBuilder::from_env().with_container_name(&config.bucket);
+                for (key, value) in &config.options {
+                    builder = builder.with_config(key.parse()?, value);
+                }
+                Box::new(builder.build()?)
+            }
+        };
+
+        let prefix = ObjectPath::from(config.prefix.as_str());
+        Ok(ObjectStorage {
+            store,
+            prefix: match genesis_hash {
+                Some(genesis_hash) => prefix.child(genesis_hash),
+                None => prefix,
+            },
+        })
+    }
+
+    pub async fn put(&self, file_name: &str, data: Vec<u8>) -> Result<(), object_store::Error> {
+        let location = self.prefix.child(file_name);
+        self.store.put(&location, data.into()).await?;
+        Ok(())
+    }
+
+    pub async fn delete(&self, file_name: &str) -> Result<(), object_store::Error> {
+        self.store.delete(&self.prefix.child(file_name)).await
+    }
+}
@@ -0,0 +1,75 @@
+use futures::StreamExt;
+use log::{info, warn};
+use solana_client::nonblocking::pubsub_client::PubsubClient;
+use solana_sdk::clock::Slot;
+use tokio::sync::watch;
+use tokio::time::{sleep, timeout, Duration};
+
+// Delay before the first reconnection attempt, doubled after every failure
+const MIN_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+// Rooted slots streamed from the websocket endpoint. The connection is
+// considered dropped when it closes or stays silent for `idle`, and is
+// reopened with exponential backoff. While it is down the listener falls back
+// to polling, and every slot it has not processed yet, including those rooted
+// during the outage, is fetched over RPC before it waits on the stream again.
+pub struct RootSubscription {
+    roots: watch::Receiver<Option<Slot>>,
+}
+
+impl RootSubscription {
+    pub fn start(ws_url: String, idle: Duration, log_target: String) -> Self {
+        let (sender, roots) = watch::channel(None);
+        tokio::spawn(follow(ws_url, idle, sender, log_target));
+        RootSubscription { roots }
+    }
+
+    // Waits up to `limit` for the next rooted slot, or for all of `limit`
+    // while disconnected
+    pub async fn wait_for_root(&self, limit: Duration) {
+        let mut roots = self.roots.clone();
+        let current = *roots.borrow();
+        if current.is_none() {
+            sleep(limit).await;
+            return;
+        }
+        let _ = timeout(limit, roots.wait_for(|root| *root != current)).await;
+    }
+}
+
+async fn follow(ws_url: String, idle: Duration, sender: watch::Sender<Option<Slot>>, log_target: String) {
+    let mut backoff = MIN_BACKOFF;
+    loop {
+        match PubsubClient::new(&ws_url).await {
+            Ok(client) => match client.root_subscribe().await {
+                Ok((mut stream, _unsubscribe)) => {
+                    info!(target: &log_target, "Streaming rooted slots from {}", ws_url);
+                    backoff = MIN_BACKOFF;
+                    loop {
+                        match timeout(idle, stream.next()).await {
+                            Ok(Some(root)) => {
+                                sender.send_replace(Some(root));
+                            }
+                            Ok(None) => {
+                                warn!(target: &log_target, "Websocket connection to {} closed", ws_url);
+                                break;
+                            }
+                            Err(_) => {
+                                warn!(target: &log_target, "No rooted slot from {} in {:?}", ws_url, idle);
+                                break;
+                            }
+                        }
+                    }
+                }
+                Err(e) => warn!(target: &log_target, "Unable to subscribe to rooted slots on {}: {}", ws_url, e),
+            },
+            Err(e) => warn!(target: &log_target, "Unable to connect to {}: {}", ws_url, e),
+        }
+
+        sender.send_replace(None);
+        warn!(target: &log_target, "Polling over RPC until the websocket reconnects, retrying in {:?}", backoff);
+        sleep(backoff).await;
+        backoff = (backoff * 2).min(MAX_BACKOFF);
+    }
+}
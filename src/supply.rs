@@ -0,0 +1,66 @@
+use serde::{Deserialize, Serialize};
+use solana_client::client_error::ClientError;
+use solana_client::rpc_client::RpcClient;
+use solana_sdk::clock::Slot;
+
+use crate::accounts;
+use crate::config::TokenSupplyConfig;
+use crate::token::TOKEN_PROGRAM_IDS;
+
+// Token and Token-2022 mints both begin with the base mint layout: an optional
+// mint authority, then the supply and the decimals
+const MINT_LEN: usize = 82;
+const SUPPLY_OFFSET: usize = 36;
+const DECIMALS_OFFSET: usize = 44;
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct MintSupply {
+    pub mint: String,
+    pub supply: u64,
+    pub decimals: u8,
+}
+
+// Supply of the configured mints as read when a block was fetched, at
+// `context_slot`, no earlier than the block's slot. With `bound`, it is mixed
+// into the block's circuit seed as given by `binding`.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct TokenSupply {
+    pub context_slot: Slot,
+    pub supplies: Vec<MintSupply>,
+    // Configured mints with no account, or whose account is not a token mint
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub missing: Vec<String>,
+    #[serde(default)]
+    pub bound: bool,
+}
+
+pub fn fetch(client: &RpcClient, config: &TokenSupplyConfig, slot: Slot) -> Result<TokenSupply, Box<ClientError>> {
+    let response = accounts::read(client, &config.mints, slot)?;
+    let mut token_supply = TokenSupply {
+        context_slot: response.context.slot,
+        supplies: Vec::new(),
+        missing: Vec::new(),
+        bound: config.bind,
+    };
+    for (mint, account) in config.mints.iter().zip(response.value) {
+        let account = account.filter(|account| {
+            TOKEN_PROGRAM_IDS.contains(&account.owner.to_string().as_str()) && account.data.len() >= MINT_LEN
+        });
+        match account {
+            Some(account) => token_supply.supplies.push(MintSupply {
+                mint: mint.clone(),
+                supply: u64::from_le_bytes(account.data[SUPPLY_OFFSET..DECIMALS_OFFSET].try_into().unwrap()),
+                decimals: account.data[DECIMALS_OFFSET],
+            }),
+            None => token_supply.missing.push(mint.clone()),
+        }
+    }
+    Ok(token_supply)
+}
+
+// What a bound supply adds to the block seed, after the block hash and leader
+pub fn binding(token_supply: &TokenSupply) -> String {
+    let supplies: Vec<String> =
+        token_supply.supplies.iter().map(|supply| format!("{}={}", supply.mint, supply.supply)).collect();
+    format!("supply@{}:{}", token_supply.context_slot, supplies.join(","))
+}
@@ -0,0 +1,96 @@
+use serde::{Deserialize, Serialize};
+use solana_sdk::program_utils::limited_deserialize;
+use solana_sdk::system_instruction::SystemInstruction;
+use solana_sdk::system_program;
+use solana_transaction_status::EncodedConfirmedBlock;
+
+use crate::instructions;
+use crate::SumProof;
+
+// A system-program SOL transfer executed by a successful transaction of the block
+#[derive(Serialize, Deserialize, Clone)]
+pub struct SolTransfer {
+    pub signature: String,
+    pub source: String,
+    pub destination: String,
+    pub lamports: u64,
+}
+
+// Lamports a watched account sent and received within the block
+#[derive(Serialize, Deserialize, Clone)]
+pub struct AccountFlow {
+    pub account: String,
+    pub sent: u64,
+    pub received: u64,
+}
+
+// SOL moved by system transfers in a block. The transfer list is kept when
+// the total is proved, so consumers can recompute the values commitment.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct SolTransferSummary {
+    pub total_lamports: u64,
+    pub transfer_count: usize,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub account_flows: Vec<AccountFlow>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub transfers: Vec<SolTransfer>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sum_proof: Option<SumProof>,
+}
+
+pub fn transfers(block: &EncodedConfirmedBlock) -> Vec<SolTransfer> {
+    let mut transfers = Vec::new();
+
+    for transaction_with_meta in &block.transactions {
+        let Some(transaction) = instructions::decode(transaction_with_meta) else {
+            continue;
+        };
+        if !transaction.succeeded {
+            continue;
+        }
+
+        for instruction in &transaction.instructions {
+            if instruction.program_id != system_program::id().to_string() {
+                continue;
+            }
+            let (lamports, source, destination) =
+                match (limited_deserialize(&instruction.data), instruction.accounts.as_slice()) {
+                    (Ok(SystemInstruction::Transfer { lamports }), [source, destination, ..]) => {
+                        (lamports, source, destination)
+                    }
+                    (Ok(SystemInstruction::TransferWithSeed { lamports, .. }), [source, _, destination, ..]) => {
+                        (lamports, source, destination)
+                    }
+                    _ => continue,
+                };
+
+            transfers.push(SolTransfer {
+                signature: transaction.signature.clone(),
+                source: source.clone(),
+                destination: destination.clone(),
+                lamports,
+            });
+        }
+    }
+
+    transfers
+}
+
+pub fn summarize(transfers: Vec<SolTransfer>, accounts: &[String], keep_transfers: bool) -> SolTransferSummary {
+    let account_flows = accounts
+        .iter()
+        .map(|account| AccountFlow {
+            account: account.clone(),
+            sent: transfers.iter().filter(|t| &t.source == account).map(|t| t.lamports).sum(),
+            received: transfers.iter().filter(|t| &t.destination == account).map(|t| t.lamports).sum(),
+        })
+        .collect();
+
+    SolTransferSummary {
+        total_lamports: transfers.iter().map(|transfer| transfer.lamports).sum(),
+        transfer_count: transfers.len(),
+        account_flows,
+        transfers: if keep_transfers { transfers } else { Vec::new() },
+        sum_proof: None,
+    }
+}
@@ -0,0 +1,104 @@
+use log::{info, warn};
+use std::env;
+use std::io;
+use std::os::unix::net::{SocketAddr, UnixDatagram};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+fn socket_addr(path: &str) -> io::Result<SocketAddr> {
+    match path.strip_prefix('@') {
+        #[cfg(target_os = "linux")]
+        Some(name) => <SocketAddr as std::os::linux::net::SocketAddrExt>::from_abstract_name(name),
+        _ => SocketAddr::from_pathname(path),
+    }
+}
+
+// Sends `state` to the service manager, if the listener runs under one that
+// asked to be notified
+fn notify(state: &str) {
+    let Ok(path) = env::var("NOTIFY_SOCKET") else {
+        return;
+    };
+    let sent = socket_addr(&path).and_then(|addr| UnixDatagram::unbound()?.send_to_addr(state.as_bytes(), &addr));
+    if let Err(e) = sent {
+        warn!("Unable to notify the service manager at {}: {}", path, e);
+    }
+}
+
+// Tells the service manager the listener is up and following the chain
+pub fn ready() {
+    notify("READY=1");
+}
+
+// How often the service manager expects to hear from the listener, when it
+// watches this process
+fn watchdog_interval() -> Option<Duration> {
+    if let Ok(pid) = env::var("WATCHDOG_PID") {
+        if pid.parse() != Ok(std::process::id()) {
+            return None;
+        }
+    }
+    let usec: u64 = env::var("WATCHDOG_USEC").ok()?.parse().ok()?;
+    (usec > 0).then(|| Duration::from_micros(usec))
+}
+
+// Milliseconds since the watchdog started when a loop last made progress
+pub struct Heartbeat {
+    started: Instant,
+    last: Arc<AtomicU64>,
+}
+
+impl Heartbeat {
+    pub fn beat(&self) {
+        self.last.store(self.started.elapsed().as_millis() as u64, Ordering::Relaxed);
+    }
+}
+
+// Keeps the service manager's watchdog fed for as long as every registered
+// loop keeps making progress. Once one of them stalls for longer than the
+// watchdog interval, the pings stop and the service manager restarts the
+// listener. WatchdogSec should allow for the slowest block to be proved,
+// since the writer waits on it.
+pub struct Watchdog {
+    interval: Duration,
+    started: Instant,
+    heartbeats: Mutex<Vec<Arc<AtomicU64>>>,
+}
+
+impl Watchdog {
+    // `None` unless the service manager enabled its watchdog for this process
+    pub fn from_env() -> Option<Arc<Self>> {
+        let interval = watchdog_interval()?;
+        info!("Service manager watchdog enabled, expecting progress every {:?}", interval);
+        Some(Arc::new(Watchdog { interval, started: Instant::now(), heartbeats: Mutex::new(Vec::new()) }))
+    }
+
+    pub fn register(&self) -> Heartbeat {
+        let last = Arc::new(AtomicU64::new(self.started.elapsed().as_millis() as u64));
+        self.heartbeats.lock().unwrap().push(last.clone());
+        Heartbeat { started: self.started, last }
+    }
+
+    // Pings the service manager twice per interval, as it recommends
+    pub fn spawn(self: &Arc<Self>) {
+        let watchdog = self.clone();
+        tokio::spawn(async move {
+            let mut ticks = tokio::time::interval(watchdog.interval / 2);
+            let mut stalled = false;
+            loop {
+                ticks.tick().await;
+                let now = watchdog.started.elapsed().as_millis() as u64;
+                let limit = watchdog.interval.as_millis() as u64;
+                let heartbeats = watchdog.heartbeats.lock().unwrap().clone();
+                if heartbeats.iter().all(|last| now.saturating_sub(last.load(Ordering::Relaxed)) < limit) {
+                    stalled = false;
+                    notify("WATCHDOG=1");
+                } else if !stalled {
+                    stalled = true;
+                    warn!("The listener stopped making progress, letting the service manager's watchdog expire");
+                }
+            }
+        });
+    }
+}
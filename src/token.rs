@@ -0,0 +1,153 @@
+use bellman::groth16;
+use blstrs::{Bls12, Scalar as Fr};
+use ff::{Field, PrimeField};
+use serde::{Deserialize, Serialize};
+use solana_transaction_status::option_serializer::OptionSerializer;
+use solana_transaction_status::EncodedConfirmedBlock;
+
+use crate::config::CommitmentHash;
+use crate::field::{fr_to_hex, str_to_fr, Domain};
+use crate::instructions;
+use crate::merkle::MerkleTree;
+use crate::serialization;
+use crate::witness::{BlockWitness, WitnessAccumulator, WitnessError};
+use crate::{prove_chunks, ChunkProof};
+
+pub const TOKEN_PROGRAM_IDS: [&str; 2] = [
+    "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA",
+    "TokenzQdBNbLqP5VEhdkAS6EPFLC1PHnBqCXEpPxuEb",
+];
+
+// Token program instruction tags
+const TRANSFER: u8 = 3;
+const TRANSFER_CHECKED: u8 = 12;
+
+// An SPL token transfer executed by a successful transaction of the block
+#[derive(Serialize, Deserialize, Clone)]
+pub struct TokenTransfer {
+    pub signature: String,
+    pub mint: String,
+    pub source: String,
+    pub destination: String,
+    pub amount: u64,
+}
+
+impl TokenTransfer {
+    // Leaf of this transfer in its mint's commitment and Merkle tree
+    pub fn leaf(&self) -> Fr {
+        str_to_fr(Domain::TokenTransfer, &self.leaf_data())
+    }
+
+    pub fn leaf_data(&self) -> String {
+        format!("{}:{}:{}:{}:{}", self.signature, self.mint, self.source, self.destination, self.amount)
+    }
+}
+
+// Witness for a mint's transfers in one block, seeded with the block hash and
+// the mint so a proof cannot be replayed for another block or mint
+#[derive(Serialize, Deserialize)]
+pub struct MintWitness {
+    pub mint: String,
+    pub witness: BlockWitness,
+}
+
+// Commitment and proof over every transfer of one mint in the block, in block order
+#[derive(Serialize, Deserialize, Clone)]
+pub struct MintProof {
+    pub mint: String,
+    pub transfers_root: String,
+    pub commitment: String,
+    pub proof: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub chunks: Vec<ChunkProof>,
+}
+
+pub fn transfers(block: &EncodedConfirmedBlock) -> Vec<TokenTransfer> {
+    let mut transfers = Vec::new();
+
+    for transaction_with_meta in &block.transactions {
+        let Some(transaction) = instructions::decode(transaction_with_meta) else {
+            continue;
+        };
+        if !transaction.succeeded {
+            continue;
+        }
+
+        // Plain transfers do not name the mint; take it from the source's token balance
+        let mint_of = |account: &str| {
+            let index = transaction.account_keys.iter().position(|key| key == account)?;
+            let meta = transaction_with_meta.meta.as_ref()?;
+            [&meta.pre_token_balances, &meta.post_token_balances]
+                .into_iter()
+                .filter_map(|balances| match balances {
+                    OptionSerializer::Some(balances) => Some(balances),
+                    _ => None,
+                })
+                .flatten()
+                .find(|balance| balance.account_index as usize == index)
+                .map(|balance| balance.mint.clone())
+        };
+
+        for instruction in &transaction.instructions {
+            if !TOKEN_PROGRAM_IDS.contains(&instruction.program_id.as_str()) || instruction.data.len() < 9 {
+                continue;
+            }
+            let amount = u64::from_le_bytes(instruction.data[1..9].try_into().unwrap());
+            let (source, mint, destination) = match (instruction.data[0], instruction.accounts.as_slice()) {
+                (TRANSFER, [source, destination, ..]) => (source, mint_of(source), destination),
+                (TRANSFER_CHECKED, [source, mint, destination, ..]) => (source, Some(mint.clone()), destination),
+                _ => continue,
+            };
+            let Some(mint) = mint else {
+                continue;
+            };
+
+            transfers.push(TokenTransfer {
+                signature: transaction.signature.clone(),
+                mint,
+                source: source.clone(),
+                destination: destination.clone(),
+                amount,
+            });
+        }
+    }
+
+    transfers
+}
+
+pub fn mint_witness(
+    block_hash: &str,
+    mint: &str,
+    transfers: &[TokenTransfer],
+    memory_cap: usize,
+) -> Result<MintWitness, WitnessError> {
+    let seed = str_to_fr(Domain::Commitment, &format!("{}:{}", block_hash, mint));
+    let mut accumulator = WitnessAccumulator::new(seed, memory_cap);
+    for transfer in transfers.iter().filter(|transfer| transfer.mint == mint) {
+        accumulator.push(transfer.leaf())?;
+    }
+
+    Ok(MintWitness {
+        mint: mint.to_string(),
+        // Mint proofs stand on their own rather than extending the block accumulator
+        witness: accumulator.finish(Fr::ZERO)?,
+    })
+}
+
+pub fn prove_mint(
+    mint_witness: &MintWitness,
+    hash: CommitmentHash,
+    params: &groth16::Parameters<Bls12>,
+) -> MintProof {
+    let witness = &mint_witness.witness;
+    let leaves: Vec<_> = witness.leaves().map(|leaf| leaf.to_repr()).collect();
+    let (proof, chunks) = prove_chunks(witness, params);
+
+    MintProof {
+        mint: mint_witness.mint.clone(),
+        transfers_root: hex::encode(MerkleTree::new(hash, &leaves).root()),
+        commitment: fr_to_hex(&witness.top_level().commitment),
+        proof: serialization::proof_to_hex(&proof),
+        chunks,
+    }
+}
@@ -0,0 +1,1011 @@
+use bellman::groth16;
+use blstrs::Bls12;
+#[cfg(feature = "ed25519")]
+use solana_block_verifier::SignatureStatement;
+use solana_block_verifier::{
+    self as verifier, BlockStatement, CommitmentStatement, Fr, Proof, SumChunk, SumStatement, ThresholdStatement,
+    TotalsChunk, TotalsStatement, VerifyingKey,
+};
+#[cfg(feature = "ed25519")]
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Signature;
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::absence;
+use crate::balance;
+use crate::field::Domain;
+use crate::keyring::Keyring;
+use crate::merkle::{self, MerkleTree};
+use crate::params::{self, ProvingKeys};
+use crate::serialization;
+use crate::supply;
+use crate::volume::Direction;
+use crate::witness;
+use crate::{leaf_data, BlockProof, ChunkProof, SumProof, TotalsProof};
+
+#[derive(Debug)]
+pub enum VerifyError {
+    // The proof was made with a verifying key that is not in the keyring
+    UnknownKey(String),
+    // The proof records no fingerprint and no current key was given
+    NoCurrentKey,
+    // The proof has sum proofs and no sum circuit key was given
+    NoSumKey,
+    // The proof has totals proofs and no totals circuit key was given
+    NoTotalsKey,
+    // The transactions root is not the root of the Merkle tree over the leaves
+    TransactionsRootMismatch,
+    // The sorted signature root is not over the listed transactions
+    SortedRootMismatch,
+    // A transaction's leaf index or inclusion path does not lead to the transactions root
+    InvalidMerklePath(String),
+    // The Merkle root of the named commitment is not over the items the proof lists
+    ItemsRootMismatch(String),
+    // The proof has volume threshold proofs and no threshold circuit key was given
+    NoThresholdKey,
+    // The proof has signature proofs and no signature circuit key was given
+    #[cfg(feature = "ed25519")]
+    NoSignatureKey,
+    // A signature proof is for a signature the block does not list
+    #[cfg(feature = "ed25519")]
+    UnlistedSignature(String),
+    // The proof has signature proofs and this build cannot check them
+    #[cfg(not(feature = "ed25519"))]
+    NoSignatureSupport,
+    Malformed(&'static str),
+    Rejected(verifier::Error),
+    // A proof other than the block's, named by the first field, was rejected
+    RejectedProof(String, verifier::Error),
+}
+
+impl fmt::Display for VerifyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VerifyError::UnknownKey(fingerprint) => {
+                write!(f, "proof was made with key {}, which is not in the keyring", fingerprint)
+            }
+            VerifyError::NoCurrentKey => write!(f, "proof records no key fingerprint and no parameters were given"),
+            VerifyError::NoSumKey => write!(f, "proof has sum proofs and no sum parameters were given"),
+            VerifyError::NoTotalsKey => write!(f, "proof has totals proofs and no totals parameters were given"),
+            VerifyError::TransactionsRootMismatch => write!(f, "transactions root is not over the listed transactions"),
+            VerifyError::SortedRootMismatch => write!(f, "sorted signature root is not over the listed transactions"),
+            VerifyError::InvalidMerklePath(signature) => {
+                write!(f, "inclusion path of transaction {} does not lead to the transactions root", signature)
+            }
+            VerifyError::ItemsRootMismatch(name) => write!(f, "{} root is not over the listed items", name),
+            VerifyError::NoThresholdKey => {
+                write!(f, "proof has volume threshold proofs and no threshold parameters were given")
+            }
+            #[cfg(feature = "ed25519")]
+            VerifyError::NoSignatureKey => {
+                write!(f, "proof has signature proofs and no signature parameters were given")
+            }
+            #[cfg(feature = "ed25519")]
+            VerifyError::UnlistedSignature(signature) => {
+                write!(f, "signature {} is proved but not among the block's transactions", signature)
+            }
+            #[cfg(not(feature = "ed25519"))]
+            VerifyError::NoSignatureSupport => {
+                write!(f, "proof has signature proofs and this build has no signature circuit (--features ed25519)")
+            }
+            VerifyError::Malformed(field) => write!(f, "malformed {}", field),
+            VerifyError::Rejected(e) => write!(f, "{}", e),
+            VerifyError::RejectedProof(name, e) => write!(f, "{}: {}", name, e),
+        }
+    }
+}
+
+impl VerifyError {
+    // Whether the proof needs a verifying key that was not given
+    pub fn missing_key(&self) -> bool {
+        match self {
+            VerifyError::UnknownKey(_)
+            | VerifyError::NoCurrentKey
+            | VerifyError::NoSumKey
+            | VerifyError::NoTotalsKey
+            | VerifyError::NoThresholdKey => true,
+            #[cfg(feature = "ed25519")]
+            VerifyError::NoSignatureKey => true,
+            _ => false,
+        }
+    }
+}
+
+fn parse_fr(data: &str, field: &'static str) -> Result<Fr, VerifyError> {
+    Fr::from_hex(data).ok_or(VerifyError::Malformed(field))
+}
+
+fn parse_proof(data: &str, field: &'static str) -> Result<Proof, VerifyError> {
+    Proof::from_hex(data).ok_or(VerifyError::Malformed(field))
+}
+
+// Leaves of the transactions a proof lists, in block order. A proof with
+// salted leaves and no transactions has them withheld in its operator's
+// private_dir, so there are no leaves to check.
+fn transaction_leaves(block_proof: &BlockProof) -> Result<Option<Vec<Fr>>, VerifyError> {
+    if block_proof.transactions.is_empty() && block_proof.sorted_root.is_empty() {
+        return Ok(None);
+    }
+    let leaves = block_proof
+        .transactions
+        .iter()
+        .map(|transaction| {
+            let data = leaf_data(&transaction.transaction_hash, transaction.message_hash.as_deref());
+            let salt = match &transaction.salt {
+                Some(salt) => Some(hex::decode(salt).map_err(|_| VerifyError::Malformed("salt"))?),
+                None => None,
+            };
+            Ok(verifier::transaction_leaf(&data, salt.as_deref(), block_proof.hash_domains))
+        })
+        .collect::<Result<_, VerifyError>>()?;
+    Ok(Some(leaves))
+}
+
+// Checks that the transactions root is the Merkle root over the leaves, and
+// that every transaction's inclusion path leads to it from its position
+fn verify_transactions(block_proof: &BlockProof, leaves: &[Fr]) -> Result<(), VerifyError> {
+    let leaves: Vec<[u8; 32]> = leaves.iter().map(Fr::to_bytes).collect();
+    let root = MerkleTree::new(block_proof.commitment_hash, &leaves).root();
+    if hex::encode(root) != block_proof.transactions_root {
+        return Err(VerifyError::TransactionsRootMismatch);
+    }
+    for (index, (transaction, leaf)) in block_proof.transactions.iter().zip(&leaves).enumerate() {
+        let hash = block_proof.commitment_hash;
+        if transaction.leaf_index != index
+            || !merkle::verify_path(hash, &root, leaf, index, leaves.len(), &transaction.merkle_path)
+        {
+            return Err(VerifyError::InvalidMerklePath(transaction.transaction_hash.clone()));
+        }
+    }
+    Ok(())
+}
+
+// Checks that the sorted signature root, which absence proofs are checked
+// against, is over the listed transactions. Blocks proved with salted leaves
+// have none.
+fn verify_sorted_root(block_proof: &BlockProof) -> Result<(), VerifyError> {
+    let salted = block_proof.transactions.iter().any(|transaction| transaction.salt.is_some());
+    if block_proof.sorted_root.is_empty() || salted {
+        return Ok(());
+    }
+    let signatures: Vec<String> =
+        block_proof.transactions.iter().map(|transaction| transaction.transaction_hash.clone()).collect();
+    if signatures.iter().any(|signature| Signature::from_str(signature).is_err()) {
+        return Err(VerifyError::Malformed("transaction signature"));
+    }
+    if hex::encode(absence::sorted_root(block_proof.commitment_hash, &signatures)) != block_proof.sorted_root {
+        return Err(VerifyError::SortedRootMismatch);
+    }
+    Ok(())
+}
+
+fn chunk_statements(chunks: &[ChunkProof]) -> Result<Vec<(Fr, Proof)>, VerifyError> {
+    let mut statements = Vec::with_capacity(chunks.len());
+    for (index, chunk) in chunks.iter().enumerate() {
+        if chunk.index != index {
+            return Err(VerifyError::Malformed("chunk index"));
+        }
+        statements.push((parse_fr(&chunk.commitment, "chunk commitment")?, parse_proof(&chunk.proof, "chunk proof")?));
+    }
+    Ok(statements)
+}
+
+fn block_statement(block_proof: &BlockProof) -> Result<BlockStatement<'_>, VerifyError> {
+    Ok(BlockStatement {
+        block_hash: &block_proof.block_hash,
+        leader: block_proof.leader.as_deref().filter(|_| block_proof.leader_bound),
+        supply: block_proof.token_supply.as_ref().filter(|token_supply| token_supply.bound).map(supply::binding),
+        hash_domains: block_proof.hash_domains,
+        commitment: parse_fr(&block_proof.commitment, "commitment")?,
+        old_root: parse_fr(&block_proof.old_root, "old root")?,
+        new_root: parse_fr(&block_proof.new_root, "new root")?,
+        proof: parse_proof(&block_proof.proof, "proof")?,
+        chunks: chunk_statements(&block_proof.chunks)?,
+        leaves: transaction_leaves(block_proof)?,
+    })
+}
+
+fn totals_statement(totals: &TotalsProof) -> Result<TotalsStatement, VerifyError> {
+    let chunks = totals
+        .chunks
+        .iter()
+        .map(|chunk| {
+            Ok(TotalsChunk {
+                values_commitment: parse_fr(&chunk.values_commitment, "totals values commitment")?,
+                signature_count: chunk.signature_count,
+                total: chunk.total,
+                proof: parse_proof(&chunk.proof, "totals proof")?,
+            })
+        })
+        .collect::<Result<_, VerifyError>>()?;
+    Ok(TotalsStatement { signature_count: totals.signature_count, total: totals.total, chunks })
+}
+
+// A block circuit commitment of a proof file other than the block's own,
+// over items the proof lists
+struct ListedCommitment<'a> {
+    name: String,
+    // Seed data, naming the block and what is committed to
+    seed_data: String,
+    leaves: Vec<Fr>,
+    root: &'a str,
+    commitment: &'a str,
+    proof: &'a str,
+    chunks: &'a [ChunkProof],
+}
+
+// The mint, program change and vote commitments of a proof file, with the
+// leaves of the items they are over
+fn listed_commitments(block_proof: &BlockProof) -> Vec<ListedCommitment<'_>> {
+    let leaves = |domain: Domain, items: Vec<String>| -> Vec<Fr> {
+        items.iter().map(|data| verifier::item_leaf(domain.tag(), data, block_proof.hash_domains)).collect()
+    };
+    let block_hash = &block_proof.block_hash;
+    let mut commitments: Vec<ListedCommitment> = block_proof
+        .mint_proofs
+        .iter()
+        .map(|mint_proof| {
+            let transfers = block_proof.token_transfers.iter().filter(|transfer| transfer.mint == mint_proof.mint);
+            ListedCommitment {
+                name: format!("mint {} transfers", mint_proof.mint),
+                seed_data: format!("{}:{}", block_hash, mint_proof.mint),
+                leaves: leaves(Domain::TokenTransfer, transfers.map(|transfer| transfer.leaf_data()).collect()),
+                root: &mint_proof.transfers_root,
+                commitment: &mint_proof.commitment,
+                proof: &mint_proof.proof,
+                chunks: &mint_proof.chunks,
+            }
+        })
+        .collect();
+    if let Some(changes) = &block_proof.program_changes {
+        commitments.push(ListedCommitment {
+            name: "program changes".to_string(),
+            seed_data: format!("{}:programs", block_hash),
+            leaves: leaves(Domain::ProgramChange, changes.changes.iter().map(|change| change.leaf_data()).collect()),
+            root: &changes.changes_root,
+            commitment: &changes.commitment,
+            proof: &changes.proof,
+            chunks: &changes.chunks,
+        });
+    }
+    if let Some(votes) = &block_proof.votes {
+        commitments.push(ListedCommitment {
+            name: "votes".to_string(),
+            seed_data: format!("{}:votes", block_hash),
+            leaves: leaves(Domain::Vote, votes.votes.iter().map(|vote| vote.leaf_data()).collect()),
+            root: &votes.votes_root,
+            commitment: &votes.commitment,
+            proof: &votes.proof,
+            chunks: &votes.chunks,
+        });
+    }
+    commitments
+}
+
+// Checks the mint, program change and vote commitments of a proof file: the
+// listed items must make up each commitment and its Merkle root
+fn verify_commitments(block_proof: &BlockProof, vk: &VerifyingKey) -> Result<(), VerifyError> {
+    for listed in listed_commitments(block_proof) {
+        let leaves: Vec<[u8; 32]> = listed.leaves.iter().map(Fr::to_bytes).collect();
+        if hex::encode(MerkleTree::new(block_proof.commitment_hash, &leaves).root()) != listed.root {
+            return Err(VerifyError::ItemsRootMismatch(listed.name));
+        }
+        let statement = CommitmentStatement {
+            seed: verifier::commitment_seed(&listed.seed_data, block_proof.hash_domains),
+            commitment: parse_fr(listed.commitment, "commitment")?,
+            proof: parse_proof(listed.proof, "proof")?,
+            chunks: chunk_statements(listed.chunks)?,
+            leaves: listed.leaves,
+        };
+        verifier::verify_commitment(vk, &statement).map_err(|e| VerifyError::RejectedProof(listed.name, e))?;
+    }
+    Ok(())
+}
+
+// Checks the block circuit proofs of a proof file with the standalone
+// verifier, against the keyring's verifying key with the fingerprint the proof
+// records. Proofs written before fingerprints were recorded are checked
+// against the current key. The listed transactions must make up the proved
+// commitment, and the transactions root and inclusion paths must be over them,
+// so neither can be swapped out without invalidating the proof. So must the
+// sorted signature root of a block proved without salted leaves. The mint,
+// program change and vote commitments are checked likewise.
+pub fn verify_block(block_proof: &BlockProof, keyring: &Keyring) -> Result<(), VerifyError> {
+    let fingerprint = match &block_proof.params_fingerprint {
+        Some(fingerprint) => fingerprint.as_str(),
+        None => keyring.current().ok_or(VerifyError::NoCurrentKey)?,
+    };
+    let vk = keyring.get(fingerprint).ok_or_else(|| VerifyError::UnknownKey(fingerprint.to_string()))?;
+
+    let statement = block_statement(block_proof)?;
+    if let Some(leaves) = &statement.leaves {
+        verify_transactions(block_proof, leaves)?;
+        verify_sorted_root(block_proof)?;
+    }
+    verifier::verify_block(vk, &statement).map_err(VerifyError::Rejected)?;
+    verify_commitments(block_proof, vk)
+}
+
+// Amounts a proof lists, keyed by the signature of their transaction
+type Amounts<'a> = Vec<(&'a str, u64)>;
+
+// The sum proofs of a proof file (its SOL transfer total and balance credits
+// and debits), by the name their values seed is derived from, each with the
+// amounts it is over
+fn sum_proofs(block_proof: &BlockProof) -> Vec<(&'static str, &SumProof, Amounts<'_>)> {
+    let mut sums = Vec::new();
+    if let Some(summary) = &block_proof.sol_transfers {
+        if let Some(sum_proof) = &summary.sum_proof {
+            let amounts = summary.transfers.iter().map(|transfer| (transfer.signature.as_str(), transfer.lamports));
+            sums.push(("sol", sum_proof, amounts.collect()));
+        }
+    }
+    if let Some(summary) = &block_proof.balances {
+        if let Some(credit_proof) = &summary.credit_proof {
+            sums.push(("credits", credit_proof, balance::credits(&summary.changes).collect()));
+        }
+        if let Some(debit_proof) = &summary.debit_proof {
+            sums.push(("debits", debit_proof, balance::debits(&summary.changes).collect()));
+        }
+    }
+    sums
+}
+
+fn sum_statement(block_proof: &BlockProof, name: &str, sum_proof: &SumProof) -> Result<SumStatement, VerifyError> {
+    let chunks = sum_proof
+        .chunks
+        .iter()
+        .map(|chunk| {
+            Ok(SumChunk {
+                values_commitment: parse_fr(&chunk.commitment, "sum commitment")?,
+                total: chunk.total,
+                proof: parse_proof(&chunk.proof, "sum proof")?,
+            })
+        })
+        .collect::<Result<_, VerifyError>>()?;
+    Ok(SumStatement {
+        values_seed: verifier::sum_seed(&block_proof.block_hash, name, block_proof.hash_domains),
+        total: sum_proof.total,
+        chunks,
+        values: None,
+    })
+}
+
+// Checks the sum proofs of a proof file, if it has any, against the sum
+// circuit's verifying key. When the proof lists its transactions, the values
+// of their leaves are recomputed from the amounts it lists, so the total is
+// that of the listed amounts.
+pub fn verify_sums(block_proof: &BlockProof, vk: Option<&VerifyingKey>) -> Result<(), VerifyError> {
+    let sums = sum_proofs(block_proof);
+    if sums.is_empty() {
+        return Ok(());
+    }
+    let vk = vk.ok_or(VerifyError::NoSumKey)?;
+    let block = block_statement(block_proof)?;
+    let signatures: Vec<String> =
+        block_proof.transactions.iter().map(|transaction| transaction.transaction_hash.clone()).collect();
+    for (name, sum_proof, amounts) in sums {
+        let mut statement = sum_statement(block_proof, name, sum_proof)?;
+        if block.leaves.is_some() {
+            let values = witness::leaf_values(&signatures, amounts).ok_or(VerifyError::Malformed("sum amounts"))?;
+            statement.values = Some(values);
+        }
+        verifier::verify_sum(vk, &block, &statement)
+            .map_err(|e| VerifyError::RejectedProof(format!("{} sum", name), e))?;
+    }
+    Ok(())
+}
+
+// The totals circuit proofs of a proof file: its compute units and total fees
+fn totals_proofs(block_proof: &BlockProof) -> impl Iterator<Item = &TotalsProof> {
+    let total_fees = block_proof.fees.as_ref().and_then(|fees| fees.total_fees_proof.as_ref());
+    block_proof.compute_units.iter().chain(total_fees)
+}
+
+// Checks the totals circuit proofs of a proof file, if it has any, against
+// the totals circuit's verifying key
+pub fn verify_totals(block_proof: &BlockProof, vk: Option<&VerifyingKey>) -> Result<(), VerifyError> {
+    for totals in totals_proofs(block_proof) {
+        let vk = vk.ok_or(VerifyError::NoTotalsKey)?;
+        verifier::verify_totals(vk, &block_statement(block_proof)?, &totals_statement(totals)?)
+            .map_err(VerifyError::Rejected)?;
+    }
+    Ok(())
+}
+
+// Checks the volume threshold proofs of a proof file, if it has any, against
+// the threshold circuit's verifying key
+pub fn verify_thresholds(block_proof: &BlockProof, vk: Option<&VerifyingKey>) -> Result<(), VerifyError> {
+    for threshold in &block_proof.volume_thresholds {
+        let vk = vk.ok_or(VerifyError::NoThresholdKey)?;
+        let statement = ThresholdStatement {
+            mint: threshold.mint.as_deref(),
+            threshold: threshold.threshold,
+            above: threshold.direction == Direction::Above,
+            values_commitment: parse_fr(&threshold.commitment, "volume commitment")?,
+            proof: parse_proof(&threshold.proof, "volume threshold proof")?,
+        };
+        verifier::verify_threshold(vk, &block_statement(block_proof)?, &statement).map_err(VerifyError::Rejected)?;
+    }
+    Ok(())
+}
+
+// Checks the signature proofs of a proof file, if it has any, against the
+// signature circuit's verifying key. Each proved signature must be one of the
+// block's transactions, which ties the proof to the block proof.
+#[cfg(feature = "ed25519")]
+pub fn verify_signatures(block_proof: &BlockProof, vk: Option<&VerifyingKey>) -> Result<(), VerifyError> {
+    for signature_proof in &block_proof.signature_proofs {
+        let vk = vk.ok_or(VerifyError::NoSignatureKey)?;
+        let mut hashes = block_proof.transactions.iter().map(|transaction| &transaction.transaction_hash);
+        if !hashes.any(|hash| *hash == signature_proof.signature) {
+            return Err(VerifyError::UnlistedSignature(signature_proof.signature.clone()));
+        }
+        let signature =
+            Signature::from_str(&signature_proof.signature).map_err(|_| VerifyError::Malformed("signature"))?;
+        let fee_payer =
+            Pubkey::from_str(&signature_proof.fee_payer).map_err(|_| VerifyError::Malformed("fee payer"))?;
+        let statement = SignatureStatement {
+            signature: signature.as_ref().try_into().map_err(|_| VerifyError::Malformed("signature"))?,
+            fee_payer: fee_payer.to_bytes(),
+            proof: parse_proof(&signature_proof.proof, "signature proof")?,
+        };
+        verifier::verify_signature(vk, &statement).map_err(VerifyError::Rejected)?;
+    }
+    Ok(())
+}
+
+// Signature proofs cannot be checked without the signature circuit, so a proof
+// file that has them fails rather than passing unchecked
+#[cfg(not(feature = "ed25519"))]
+pub fn verify_signatures(block_proof: &BlockProof, _vk: Option<&VerifyingKey>) -> Result<(), VerifyError> {
+    match block_proof.signature_proofs.is_empty() {
+        true => Ok(()),
+        false => Err(VerifyError::NoSignatureSupport),
+    }
+}
+
+// Checks the sum, totals, threshold and signature circuit proofs of a proof
+// file against the verifying keys of those circuits
+pub fn verify_circuits(block_proof: &BlockProof, circuit_keys: [Option<&VerifyingKey>; 4]) -> Result<(), VerifyError> {
+    let [sum_vk, totals_vk, threshold_vk, signature_vk] = circuit_keys;
+    verify_sums(block_proof, sum_vk)?;
+    verify_totals(block_proof, totals_vk)?;
+    verify_thresholds(block_proof, threshold_vk)?;
+    verify_signatures(block_proof, signature_vk)
+}
+
+// Verifying keys of the sum, totals, threshold and signature circuits, from
+// those of the given parameter files that exist
+pub fn circuit_keys(params: [&Path; 4]) -> [Option<VerifyingKey>; 4] {
+    params.map(|path| path.exists().then(|| verifier_key(&params::load_verifying_key(path))))
+}
+
+// The standalone verifier's form of a verifying key
+pub fn verifier_key(vk: &groth16::VerifyingKey<Bls12>) -> VerifyingKey {
+    VerifyingKey::read(&serialization::vk_to_bytes(vk)).expect("Unable to parse verifying key")
+}
+
+// Checks a proof just made against the verifying keys it was made with, so a
+// witness that does not satisfy the circuits is caught before it is published.
+// The mint, program change and vote commitments are checked with the block.
+pub fn self_check(block_proof: &BlockProof, keys: &ProvingKeys) -> Result<(), VerifyError> {
+    let mut keyring = Keyring::default();
+    keyring.insert_current(&keys.block.vk);
+    verify_block(block_proof, &keyring)?;
+    let sum_vk = sum_proofs(block_proof).first().map(|_| verifier_key(&keys.sum().vk));
+    verify_sums(block_proof, sum_vk.as_ref())?;
+    let totals_vk = totals_proofs(block_proof).next().map(|_| verifier_key(&keys.totals().vk));
+    verify_totals(block_proof, totals_vk.as_ref())?;
+    let threshold_vk = block_proof.volume_thresholds.first().map(|_| verifier_key(&keys.threshold().vk));
+    verify_thresholds(block_proof, threshold_vk.as_ref())?;
+    #[cfg(feature = "ed25519")]
+    let signature_vk = block_proof.signature_proofs.first().map(|_| verifier_key(&keys.signature().vk));
+    #[cfg(not(feature = "ed25519"))]
+    let signature_vk: Option<VerifyingKey> = None;
+    verify_signatures(block_proof, signature_vk.as_ref())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::CommitmentHash;
+    use crate::balance::{BalanceChange, BalanceSummary};
+    use crate::disclosure;
+    use crate::field::{fr_to_hex, str_to_fr, HASH_DOMAINS};
+    use crate::system::{SolTransfer, SolTransferSummary};
+    use crate::token::{self, TokenTransfer};
+    use crate::volume::{self, VolumeWitness};
+    use crate::witness::{BlockWitness, WitnessAccumulator};
+    use crate::{circuit, ChunkProof};
+    use blstrs::Scalar;
+    use ff::{Field, PrimeField};
+    use rand::thread_rng;
+    use std::sync::OnceLock;
+
+    // A proof file listing `signatures`, with the transactions root and
+    // inclusion paths the prover would write
+    fn listed_block(signatures: &[&str]) -> BlockProof {
+        let leaves: Vec<[u8; 32]> = signatures
+            .iter()
+            .map(|signature| verifier::transaction_leaf(signature, None, HASH_DOMAINS).to_bytes())
+            .collect();
+        let tree = MerkleTree::new(CommitmentHash::Sha256, &leaves);
+        let transactions: Vec<_> = signatures
+            .iter()
+            .enumerate()
+            .map(|(index, signature)| {
+                serde_json::json!({
+                    "transaction_hash": signature,
+                    "leaf_index": index,
+                    "merkle_path": tree.path(index),
+                })
+            })
+            .collect();
+        serde_json::from_value(serde_json::json!({
+            "slot": 1,
+            "block_hash": "hash",
+            "hash_domains": HASH_DOMAINS,
+            "transactions_root": hex::encode(tree.root()),
+            "sorted_root": "00",
+            "commitment": "00",
+            "proof": "00",
+            "old_root": "00",
+            "new_root": "00",
+            "transactions": transactions,
+        }))
+        .unwrap()
+    }
+
+    fn check(block_proof: &BlockProof) -> Result<(), VerifyError> {
+        verify_transactions(block_proof, &transaction_leaves(block_proof)?.unwrap())
+    }
+
+    #[test]
+    fn transaction_leaves_match_the_prover() {
+        let data = leaf_data("signature", Some("message hash"));
+        assert_eq!(
+            verifier::transaction_leaf(&data, None, HASH_DOMAINS).to_bytes(),
+            str_to_fr(Domain::Transaction, &data).to_repr()
+        );
+        let salt = disclosure::random_salt();
+        for hash_domains in [0, HASH_DOMAINS] {
+            assert_eq!(
+                verifier::transaction_leaf(&data, Some(&salt), hash_domains).to_bytes(),
+                disclosure::salted_leaf(&salt, &data, hash_domains).to_repr()
+            );
+        }
+    }
+
+    #[test]
+    fn transactions_are_checked_against_their_root() {
+        let block_proof = listed_block(&["a", "b", "c"]);
+        assert!(check(&block_proof).is_ok());
+
+        let mut replaced = block_proof.clone();
+        replaced.transactions[1].transaction_hash = "d".to_string();
+        assert!(matches!(check(&replaced), Err(VerifyError::TransactionsRootMismatch)));
+
+        let mut rooted_elsewhere = block_proof.clone();
+        rooted_elsewhere.transactions_root = listed_block(&["a", "b"]).transactions_root;
+        assert!(matches!(check(&rooted_elsewhere), Err(VerifyError::TransactionsRootMismatch)));
+
+        let mut reordered = block_proof.clone();
+        reordered.transactions.swap(0, 1);
+        reordered.transactions_root = listed_block(&["b", "a", "c"]).transactions_root;
+        assert!(matches!(check(&reordered), Err(VerifyError::InvalidMerklePath(signature)) if signature == "b"));
+    }
+
+    #[test]
+    fn sorted_root_is_checked_against_the_transactions() {
+        let signatures: Vec<String> = (1..=3u8).map(|byte| Signature::from([byte; 64]).to_string()).collect();
+        let mut block_proof = listed_block(&signatures.iter().map(String::as_str).collect::<Vec<_>>());
+        block_proof.sorted_root = hex::encode(absence::sorted_root(CommitmentHash::Sha256, &signatures));
+        assert!(verify_sorted_root(&block_proof).is_ok());
+
+        let mut rooted_elsewhere = block_proof.clone();
+        rooted_elsewhere.sorted_root = hex::encode(absence::sorted_root(CommitmentHash::Sha256, &signatures[..2]));
+        assert!(matches!(verify_sorted_root(&rooted_elsewhere), Err(VerifyError::SortedRootMismatch)));
+        let mut emptied = block_proof.clone();
+        emptied.sorted_root = hex::encode([0u8; 32]);
+        assert!(matches!(verify_sorted_root(&emptied), Err(VerifyError::SortedRootMismatch)));
+        let mut malformed = block_proof.clone();
+        malformed.transactions[0].transaction_hash = "a".to_string();
+        assert!(matches!(verify_sorted_root(&malformed), Err(VerifyError::Malformed(_))));
+
+        // Salted blocks have no sorted root to check
+        rooted_elsewhere.transactions[0].salt = Some("00".to_string());
+        assert!(verify_sorted_root(&rooted_elsewhere).is_ok());
+    }
+
+    fn block_params() -> &'static groth16::Parameters<Bls12> {
+        static PARAMS: OnceLock<groth16::Parameters<Bls12>> = OnceLock::new();
+        PARAMS.get_or_init(circuit::generate_parameters)
+    }
+
+    // Block circuit parameters from another setup, whose proofs the first
+    // setup's key must not accept
+    fn other_block_params() -> &'static groth16::Parameters<Bls12> {
+        static PARAMS: OnceLock<groth16::Parameters<Bls12>> = OnceLock::new();
+        PARAMS.get_or_init(circuit::generate_parameters)
+    }
+
+    fn totals_params() -> &'static groth16::Parameters<Bls12> {
+        static PARAMS: OnceLock<groth16::Parameters<Bls12>> = OnceLock::new();
+        PARAMS.get_or_init(circuit::generate_totals_parameters)
+    }
+
+    fn threshold_params() -> &'static groth16::Parameters<Bls12> {
+        static PARAMS: OnceLock<groth16::Parameters<Bls12>> = OnceLock::new();
+        PARAMS.get_or_init(circuit::generate_threshold_parameters)
+    }
+
+    fn sum_params() -> &'static groth16::Parameters<Bls12> {
+        static PARAMS: OnceLock<groth16::Parameters<Bls12>> = OnceLock::new();
+        PARAMS.get_or_init(circuit::generate_sum_parameters)
+    }
+
+    fn to_verifier(value: &Scalar) -> Fr {
+        Fr::from_bytes(&value.to_repr()).unwrap()
+    }
+
+    // A block of `count` random leaves proved as the listener proves one
+    struct ProvedBlock {
+        witness: BlockWitness,
+        proof: String,
+        chunks: Vec<ChunkProof>,
+    }
+
+    impl ProvedBlock {
+        fn new(block_hash: &str, count: usize) -> ProvedBlock {
+            ProvedBlock::with_leaves(block_hash, (0..count).map(|_| Scalar::random(thread_rng())))
+        }
+
+        fn with_leaves(block_hash: &str, leaves: impl IntoIterator<Item = Scalar>) -> ProvedBlock {
+            let mut accumulator = WitnessAccumulator::new(crate::block_seed(block_hash, None, None), usize::MAX);
+            for leaf in leaves {
+                accumulator.push(leaf).unwrap();
+            }
+            let witness = accumulator.finish(Scalar::random(thread_rng())).unwrap();
+            let (proof, chunks) = crate::prove_chunks(&witness, block_params());
+            ProvedBlock { proof: serialization::proof_to_hex(&proof), chunks, witness }
+        }
+
+        // A block split into two chunks, shared by the tests that need one
+        fn chunked() -> &'static ProvedBlock {
+            static BLOCK: OnceLock<ProvedBlock> = OnceLock::new();
+            BLOCK.get_or_init(|| ProvedBlock::new("hash", circuit::CIRCUIT_CAPACITY + 1))
+        }
+
+        fn leaves(&self) -> Vec<Fr> {
+            self.witness.chunks.iter().flat_map(|chunk| &chunk.leaves).map(to_verifier).collect()
+        }
+
+        // The block's statement, without its leaves, as proved for `block_hash`
+        fn statement<'a>(&self, block_hash: &'a str) -> BlockStatement<'a> {
+            let top_level = self.witness.aggregate.as_ref().unwrap_or(&self.witness.chunks[0]);
+            BlockStatement {
+                block_hash,
+                leader: None,
+                supply: None,
+                hash_domains: HASH_DOMAINS,
+                commitment: to_verifier(&top_level.commitment),
+                old_root: to_verifier(&top_level.old_root),
+                new_root: to_verifier(&top_level.new_root()),
+                proof: Proof::from_hex(&self.proof).unwrap(),
+                chunks: self
+                    .chunks
+                    .iter()
+                    .map(|chunk| (Fr::from_hex(&chunk.commitment).unwrap(), Proof::from_hex(&chunk.proof).unwrap()))
+                    .collect(),
+                leaves: None,
+            }
+        }
+    }
+
+    // A proof file listing `signatures`, with the block proof over them
+    fn proved_block(signatures: &[&str]) -> (ProvedBlock, BlockProof) {
+        let block = ProvedBlock::with_leaves("hash", signatures.iter().map(|s| str_to_fr(Domain::Transaction, s)));
+        let top_level = &block.witness.chunks[0];
+        let block_proof = BlockProof {
+            commitment: fr_to_hex(&top_level.commitment),
+            old_root: fr_to_hex(&top_level.old_root),
+            new_root: fr_to_hex(&top_level.new_root()),
+            proof: block.proof.clone(),
+            ..listed_block(signatures)
+        };
+        (block, block_proof)
+    }
+
+    #[test]
+    fn item_leaves_and_seeds_match_the_prover() {
+        let transfer = TokenTransfer {
+            signature: "signature".to_string(),
+            mint: "mint".to_string(),
+            source: "source".to_string(),
+            destination: "destination".to_string(),
+            amount: 5,
+        };
+        let leaf = verifier::item_leaf(Domain::TokenTransfer.tag(), &transfer.leaf_data(), HASH_DOMAINS);
+        assert_eq!(leaf.to_bytes(), transfer.leaf().to_repr());
+        let seed = verifier::commitment_seed("hash:mint", HASH_DOMAINS);
+        assert_eq!(seed.to_bytes(), str_to_fr(Domain::Commitment, "hash:mint").to_repr());
+        let seed = verifier::sum_seed("hash", "sol", HASH_DOMAINS);
+        assert_eq!(seed.to_bytes(), crate::sum_seed("hash", "sol").to_repr());
+
+        let leaves = [Scalar::random(thread_rng()), Scalar::random(thread_rng())];
+        let values_seed = Scalar::random(thread_rng());
+        assert_eq!(
+            verifier::values_commitment(to_verifier(&values_seed), &leaves.map(|leaf| to_verifier(&leaf)), &[3, 4]),
+            to_verifier(&witness::values_commitment(values_seed, &leaves, &[3, 4]))
+        );
+    }
+
+    #[test]
+    fn verifier_checks_mint_commitments() {
+        let transfer = |signature: &str, mint: &str, amount| TokenTransfer {
+            signature: signature.to_string(),
+            mint: mint.to_string(),
+            source: "source".to_string(),
+            destination: "destination".to_string(),
+            amount,
+        };
+        let transfers = vec![transfer("a", "mint", 5), transfer("b", "other mint", 6), transfer("c", "mint", 7)];
+        let mint_witness = token::mint_witness("hash", "mint", &transfers, usize::MAX).unwrap();
+        let block_proof = BlockProof {
+            mint_proofs: vec![token::prove_mint(&mint_witness, CommitmentHash::Sha256, block_params())],
+            token_transfers: transfers,
+            ..listed_block(&["a", "b", "c"])
+        };
+        let vk = verifier_key(&block_params().vk);
+        assert!(verify_commitments(&block_proof, &vk).is_ok());
+
+        let mut tampered = block_proof.clone();
+        tampered.token_transfers[2].amount = 8;
+        let rejected = verify_commitments(&tampered, &vk);
+        assert!(matches!(rejected, Err(VerifyError::ItemsRootMismatch(name)) if name == "mint mint transfers"));
+        let mut dropped = block_proof.clone();
+        dropped.token_transfers.remove(0);
+        assert!(matches!(verify_commitments(&dropped, &vk), Err(VerifyError::ItemsRootMismatch(_))));
+        let mut other_block = block_proof.clone();
+        other_block.block_hash = "other hash".to_string();
+        assert!(matches!(
+            verify_commitments(&other_block, &vk),
+            Err(VerifyError::RejectedProof(_, verifier::Error::LeavesMismatch))
+        ));
+        let other_vk = verifier_key(&other_block_params().vk);
+        assert!(matches!(verify_commitments(&block_proof, &other_vk), Err(VerifyError::RejectedProof(..))));
+    }
+
+    #[test]
+    fn verifier_checks_sum_proofs() {
+        let (block, block_proof) = proved_block(&["a", "b", "c"]);
+        let transfer = |signature: &str, lamports| SolTransfer {
+            signature: signature.to_string(),
+            source: "source".to_string(),
+            destination: "destination".to_string(),
+            lamports,
+        };
+        let transfers = vec![transfer("a", 5), transfer("c", 7), transfer("a", 1)];
+        let sum_proof = crate::prove_sums(&block.witness, crate::sum_seed("hash", "sol"), &[6, 0, 7], sum_params());
+        assert_eq!(sum_proof.total, 13);
+        let block_proof = BlockProof {
+            sol_transfers: Some(SolTransferSummary {
+                total_lamports: 13,
+                transfer_count: 3,
+                account_flows: Vec::new(),
+                transfers,
+                sum_proof: Some(sum_proof),
+            }),
+            ..block_proof
+        };
+        let vk = verifier_key(&sum_params().vk);
+        assert!(verify_sums(&block_proof, Some(&vk)).is_ok());
+        assert!(matches!(verify_sums(&block_proof, None), Err(VerifyError::NoSumKey)));
+
+        // The values are recomputed from the listed transfers
+        let mut tampered = block_proof.clone();
+        tampered.sol_transfers.as_mut().unwrap().transfers[1].lamports = 8;
+        assert!(matches!(
+            verify_sums(&tampered, Some(&vk)),
+            Err(VerifyError::RejectedProof(name, verifier::Error::SumMismatch)) if name == "sol sum"
+        ));
+        let mut tampered = block_proof.clone();
+        tampered.sol_transfers.as_mut().unwrap().sum_proof.as_mut().unwrap().total = 14;
+        assert!(matches!(
+            verify_sums(&tampered, Some(&vk)),
+            Err(VerifyError::RejectedProof(_, verifier::Error::SumMismatch))
+        ));
+        // Without its transactions the proof is still over the block's commitment
+        let mut unlisted = block_proof.clone();
+        unlisted.transactions.clear();
+        unlisted.sorted_root.clear();
+        assert!(verify_sums(&unlisted, Some(&vk)).is_ok());
+        unlisted.block_hash = "other hash".to_string();
+        assert!(matches!(
+            verify_sums(&unlisted, Some(&vk)),
+            Err(VerifyError::RejectedProof(_, verifier::Error::InvalidSumProof(0)))
+        ));
+        let mut forged = BlockProof { transactions: Vec::new(), sorted_root: String::new(), ..block_proof };
+        let sum_proof = forged.sol_transfers.as_mut().unwrap().sum_proof.as_mut().unwrap();
+        (sum_proof.chunks[0].total, sum_proof.total) = (14, 14);
+        assert!(matches!(
+            verify_sums(&forged, Some(&vk)),
+            Err(VerifyError::RejectedProof(_, verifier::Error::InvalidSumProof(0)))
+        ));
+    }
+
+    #[test]
+    fn verifier_checks_balance_sums() {
+        let (block, block_proof) = proved_block(&["a", "b", "c"]);
+        let change = |signature: &str, account: &str, pre, post| BalanceChange {
+            signature: signature.to_string(),
+            account: account.to_string(),
+            pre,
+            post,
+        };
+        let changes = vec![change("a", "x", 10, 4), change("b", "x", 4, 9), change("b", "y", 0, 3)];
+        let prove = |name, values: &[u64]| {
+            crate::prove_sums(&block.witness, crate::sum_seed("hash", name), values, sum_params())
+        };
+        let block_proof = BlockProof {
+            balances: Some(BalanceSummary {
+                deltas: Vec::new(),
+                changes,
+                credit_proof: Some(prove("credits", &[0, 8, 0])),
+                debit_proof: Some(prove("debits", &[6, 0, 0])),
+            }),
+            ..block_proof
+        };
+        let vk = verifier_key(&sum_params().vk);
+        assert!(verify_sums(&block_proof, Some(&vk)).is_ok());
+
+        let mut tampered = block_proof.clone();
+        tampered.balances.as_mut().unwrap().changes[0].post = 5;
+        assert!(matches!(
+            verify_sums(&tampered, Some(&vk)),
+            Err(VerifyError::RejectedProof(name, verifier::Error::SumMismatch)) if name == "debits sum"
+        ));
+        // Each sum is bound to its own values seed
+        let mut swapped = block_proof.clone();
+        let summary = swapped.balances.as_mut().unwrap();
+        std::mem::swap(&mut summary.credit_proof, &mut summary.debit_proof);
+        summary.changes.clear();
+        swapped.transactions.clear();
+        swapped.sorted_root.clear();
+        assert!(matches!(
+            verify_sums(&swapped, Some(&vk)),
+            Err(VerifyError::RejectedProof(name, verifier::Error::InvalidSumProof(0))) if name == "credits sum"
+        ));
+    }
+
+    #[test]
+    fn verifier_checks_block_proofs() {
+        let block = ProvedBlock::new("hash", 3);
+        let vk = verifier_key(&block_params().vk);
+        assert_eq!(verifier::verify_block(&vk, &block.statement("hash")), Ok(()));
+        let listed = BlockStatement { leaves: Some(block.leaves()), ..block.statement("hash") };
+        assert_eq!(verifier::verify_block(&vk, &listed), Ok(()));
+
+        let tampered = BlockStatement { commitment: Fr::from_u64(1), ..block.statement("hash") };
+        assert_eq!(verifier::verify_block(&vk, &tampered), Err(verifier::Error::InvalidProof(None)));
+        let tampered = BlockStatement { leaves: Some(block.leaves()), ..tampered };
+        assert_eq!(verifier::verify_block(&vk, &tampered), Err(verifier::Error::LeavesMismatch));
+        let mut reordered = block.leaves();
+        reordered.swap(0, 1);
+        let reordered = BlockStatement { leaves: Some(reordered), ..block.statement("hash") };
+        assert_eq!(verifier::verify_block(&vk, &reordered), Err(verifier::Error::LeavesMismatch));
+
+        let wrong_seed = block.statement("other hash");
+        assert_eq!(verifier::verify_block(&vk, &wrong_seed), Err(verifier::Error::InvalidProof(None)));
+        let other_vk = verifier_key(&other_block_params().vk);
+        assert_eq!(
+            verifier::verify_block(&other_vk, &block.statement("hash")),
+            Err(verifier::Error::InvalidProof(None))
+        );
+    }
+
+    #[test]
+    fn verifier_checks_chunked_block_proofs() {
+        let block = ProvedBlock::chunked();
+        assert_eq!(block.chunks.len(), 2);
+        let vk = verifier_key(&block_params().vk);
+        let listed = BlockStatement { leaves: Some(block.leaves()), ..block.statement("hash") };
+        assert_eq!(verifier::verify_block(&vk, &listed), Ok(()));
+
+        let mut reordered = block.statement("hash");
+        reordered.chunks.swap(0, 1);
+        assert_eq!(verifier::verify_block(&vk, &reordered), Err(verifier::Error::InvalidProof(Some(0))));
+        let reordered = BlockStatement { leaves: Some(block.leaves()), ..reordered };
+        assert_eq!(verifier::verify_block(&vk, &reordered), Err(verifier::Error::LeavesMismatch));
+        let mut dropped = block.statement("hash");
+        dropped.chunks.pop();
+        assert_eq!(verifier::verify_block(&vk, &dropped), Err(verifier::Error::CommitmentMismatch));
+
+        let wrong_seed = block.statement("other hash");
+        assert_eq!(verifier::verify_block(&vk, &wrong_seed), Err(verifier::Error::InvalidProof(Some(0))));
+        let other_vk = verifier_key(&other_block_params().vk);
+        let wrong_key = verifier::verify_block(&other_vk, &block.statement("hash"));
+        assert_eq!(wrong_key, Err(verifier::Error::InvalidProof(Some(0))));
+    }
+
+    #[test]
+    fn verifier_checks_totals_proofs() {
+        let block = ProvedBlock::chunked();
+        let values: Vec<u64> = (0..=circuit::CIRCUIT_CAPACITY as u64).collect();
+        let totals = crate::prove_totals(&block.witness, &values, totals_params());
+        let vk = verifier_key(&totals_params().vk);
+        let statement = totals_statement(&totals).unwrap();
+        assert_eq!((statement.signature_count, statement.total), (1025, 1025 * 1024 / 2));
+        assert_eq!(verifier::verify_totals(&vk, &block.statement("hash"), &statement), Ok(()));
+
+        let mut tampered = totals_statement(&totals).unwrap();
+        tampered.chunks[1].total += 1;
+        tampered.total += 1;
+        let rejected = verifier::verify_totals(&vk, &block.statement("hash"), &tampered);
+        assert_eq!(rejected, Err(verifier::Error::InvalidTotalsProof(1)));
+        let mut inconsistent = totals_statement(&totals).unwrap();
+        inconsistent.signature_count += 1;
+        let rejected = verifier::verify_totals(&vk, &block.statement("hash"), &inconsistent);
+        assert_eq!(rejected, Err(verifier::Error::TotalsMismatch));
+        let mut reordered = totals_statement(&totals).unwrap();
+        reordered.chunks.swap(0, 1);
+        let rejected = verifier::verify_totals(&vk, &block.statement("hash"), &reordered);
+        assert_eq!(rejected, Err(verifier::Error::InvalidTotalsProof(0)));
+
+        let mut tampered_block = block.statement("hash");
+        tampered_block.chunks[0].0 = Fr::from_u64(1);
+        let rejected = verifier::verify_totals(&vk, &tampered_block, &totals_statement(&totals).unwrap());
+        assert_eq!(rejected, Err(verifier::Error::InvalidTotalsProof(0)));
+        let rejected =
+            verifier::verify_totals(&vk, &block.statement("other hash"), &totals_statement(&totals).unwrap());
+        assert_eq!(rejected, Err(verifier::Error::InvalidTotalsProof(0)));
+        let block_vk = verifier_key(&block_params().vk);
+        let rejected =
+            verifier::verify_totals(&block_vk, &block.statement("hash"), &totals_statement(&totals).unwrap());
+        assert_eq!(rejected, Err(verifier::Error::InvalidTotalsProof(0)));
+    }
+
+    #[test]
+    fn verifier_checks_threshold_proofs() {
+        let block = ProvedBlock::new("hash", 3);
+        let volume = VolumeWitness { mint: Some("mint".to_string()), threshold: 20, values: vec![15, 0, 8] };
+        let proof = volume::prove(&volume, "hash", &block.witness.chunks[0], threshold_params());
+        assert_eq!(proof.direction, Direction::Above);
+        let statement = || ThresholdStatement {
+            mint: Some("mint"),
+            threshold: 20,
+            above: true,
+            values_commitment: Fr::from_hex(&proof.commitment).unwrap(),
+            proof: Proof::from_hex(&proof.proof).unwrap(),
+        };
+        let vk = verifier_key(&threshold_params().vk);
+        assert_eq!(verifier::verify_threshold(&vk, &block.statement("hash"), &statement()), Ok(()));
+
+        let rejected = [
+            ThresholdStatement { above: false, ..statement() },
+            ThresholdStatement { threshold: 24, ..statement() },
+            ThresholdStatement { values_commitment: Fr::from_u64(1), ..statement() },
+            ThresholdStatement { mint: None, ..statement() },
+        ];
+        for statement in &rejected {
+            let rejected = verifier::verify_threshold(&vk, &block.statement("hash"), statement);
+            assert_eq!(rejected, Err(verifier::Error::InvalidThresholdProof));
+        }
+        // The proof is over this block's leaves and no other's
+        let other_blocks = [
+            block.statement("other hash"),
+            BlockStatement { commitment: Fr::from_u64(1), ..block.statement("hash") },
+            ProvedBlock::chunked().statement("hash"),
+        ];
+        for other_block in &other_blocks {
+            let rejected = verifier::verify_threshold(&vk, other_block, &statement());
+            assert_eq!(rejected, Err(verifier::Error::InvalidThresholdProof));
+        }
+        let block_vk = verifier_key(&block_params().vk);
+        let rejected = verifier::verify_threshold(&block_vk, &block.statement("hash"), &statement());
+        assert_eq!(rejected, Err(verifier::Error::InvalidThresholdProof));
+    }
+}
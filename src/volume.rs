@@ -0,0 +1,122 @@
+use bellman::groth16;
+use blstrs::Bls12;
+use log::warn;
+use serde::{Deserialize, Serialize};
+use solana_transaction_status::EncodedConfirmedBlock;
+
+use crate::circuit::{self, CIRCUIT_CAPACITY};
+use crate::config::VolumeThresholdConfig;
+use crate::field::{fr_to_hex, str_to_fr, Domain};
+use crate::serialization;
+use crate::witness::{self, ChunkWitness};
+use crate::{system, token};
+
+// Which side of its threshold a block's volume was proved to be on
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum Direction {
+    // At or above the threshold
+    Above,
+    Below,
+}
+
+// The SOL, or `mint`, that each leaf's transaction moved by transfers in a
+// block, counted on its first leaf, for proving their volume against
+// `threshold`
+#[derive(Serialize, Deserialize)]
+pub struct VolumeWitness {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mint: Option<String>,
+    pub threshold: u64,
+    pub values: Vec<u64>,
+}
+
+// Proof that the volume of SOL, or of `mint`, moved by transfers in a block is
+// on `direction` of `threshold`. The amounts and their total stay private.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct VolumeThresholdProof {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mint: Option<String>,
+    pub threshold: u64,
+    pub direction: Direction,
+    // Commitment to the block's leaves and the amounts attached to them
+    pub commitment: String,
+    pub proof: String,
+}
+
+// Threshold circuit values seed of a block's SOL or mint volume
+pub fn seed_data(block_hash: &str, mint: Option<&str>) -> String {
+    format!("{}:volume:{}", block_hash, mint.unwrap_or("sol"))
+}
+
+// Witnesses for every configured threshold, over the block's leaves in the
+// order of `signatures`. A threshold circuit holds a single chunk, so
+// thresholds are not proved for blocks with more leaves than
+// `CIRCUIT_CAPACITY`.
+pub fn witnesses(
+    block: &EncodedConfirmedBlock,
+    signatures: &[String],
+    thresholds: &[VolumeThresholdConfig],
+) -> Vec<VolumeWitness> {
+    if thresholds.is_empty() {
+        return Vec::new();
+    }
+    if signatures.len() > CIRCUIT_CAPACITY {
+        warn!("Block {} has more transactions than a threshold proof holds; not proving its volumes", block.blockhash);
+        return Vec::new();
+    }
+    let sol_transfers = system::transfers(block);
+    let token_transfers = token::transfers(block);
+
+    thresholds
+        .iter()
+        .filter_map(|config| {
+            let values = match &config.mint {
+                Some(mint) => witness::leaf_values(
+                    signatures,
+                    token_transfers
+                        .iter()
+                        .filter(|transfer| &transfer.mint == mint)
+                        .map(|transfer| (transfer.signature.as_str(), transfer.amount)),
+                ),
+                None => witness::leaf_values(
+                    signatures,
+                    sol_transfers.iter().map(|transfer| (transfer.signature.as_str(), transfer.lamports)),
+                ),
+            };
+            let Some(values) = values else {
+                warn!(
+                    "A transaction in block {} moves more {} than a threshold proof holds; not proving its volume",
+                    block.blockhash,
+                    config.mint.as_deref().unwrap_or("SOL")
+                );
+                return None;
+            };
+            Some(VolumeWitness { mint: config.mint.clone(), threshold: config.threshold, values })
+        })
+        .collect()
+}
+
+// Proves a block's volume against its threshold, over the single chunk of the
+// block's witness
+pub fn prove(
+    volume: &VolumeWitness,
+    block_hash: &str,
+    chunk: &ChunkWitness,
+    params: &groth16::Parameters<Bls12>,
+) -> VolumeThresholdProof {
+    let values_seed = str_to_fr(Domain::Commitment, &seed_data(block_hash, volume.mint.as_deref()));
+    let total: u128 = volume.values.iter().map(|&value| u128::from(value)).sum();
+    let direction = match total >= volume.threshold as u128 {
+        true => Direction::Above,
+        false => Direction::Below,
+    };
+    let proof = circuit::prove_threshold(params, chunk, values_seed, &volume.values, volume.threshold);
+    VolumeThresholdProof {
+        mint: volume.mint.clone(),
+        threshold: volume.threshold,
+        direction,
+        commitment: fr_to_hex(&witness::values_commitment(values_seed, &chunk.leaves, &volume.values)),
+        proof: serialization::proof_to_hex(&proof),
+    }
+}
@@ -0,0 +1,141 @@
+use bellman::groth16;
+use blstrs::{Bls12, Scalar as Fr};
+use ff::{Field, PrimeField};
+use serde::{Deserialize, Serialize};
+use solana_sdk::clock::Slot;
+use solana_sdk::hash::Hash;
+use solana_sdk::program_utils::limited_deserialize;
+use solana_sdk::vote::instruction::VoteInstruction;
+use solana_sdk::vote::program as vote_program;
+use solana_transaction_status::EncodedConfirmedBlock;
+
+use crate::config::{CommitmentHash, VoteConfig};
+use crate::field::{fr_to_hex, str_to_fr, Domain};
+use crate::instructions;
+use crate::merkle::MerkleTree;
+use crate::serialization;
+use crate::witness::{BlockWitness, WitnessAccumulator, WitnessError};
+use crate::{prove_chunks, ChunkProof};
+
+// A vote cast by a successful transaction of the block: the vote account, the
+// latest slot voted on and the bank hash it voted for
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ValidatorVote {
+    pub signature: String,
+    pub vote_account: String,
+    pub voted_slot: Slot,
+    pub hash: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub root: Option<Slot>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timestamp: Option<i64>,
+}
+
+impl ValidatorVote {
+    // Leaf of this vote in the block's vote commitment and Merkle tree
+    pub fn leaf(&self) -> Fr {
+        str_to_fr(Domain::Vote, &self.leaf_data())
+    }
+
+    pub fn leaf_data(&self) -> String {
+        format!("{}:{}:{}:{}", self.signature, self.vote_account, self.voted_slot, self.hash)
+    }
+}
+
+// The votes of a block with a commitment and proof over them, in block order,
+// so a validator's voting can be tied to the slots it landed in
+#[derive(Serialize, Deserialize, Clone)]
+pub struct BlockVotes {
+    pub votes: Vec<ValidatorVote>,
+    pub votes_root: String,
+    pub commitment: String,
+    pub proof: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub chunks: Vec<ChunkProof>,
+}
+
+// Latest slot voted on, with the root, bank hash and timestamp of a vote instruction
+fn decode_vote(data: &[u8]) -> Option<(Slot, Option<Slot>, Hash, Option<i64>)> {
+    match limited_deserialize(data).ok()? {
+        VoteInstruction::Vote(vote) | VoteInstruction::VoteSwitch(vote, _) => {
+            Some((vote.last_voted_slot()?, None, vote.hash, vote.timestamp))
+        }
+        VoteInstruction::UpdateVoteState(update)
+        | VoteInstruction::UpdateVoteStateSwitch(update, _)
+        | VoteInstruction::CompactUpdateVoteState(update)
+        | VoteInstruction::CompactUpdateVoteStateSwitch(update, _) => {
+            Some((update.last_voted_slot()?, update.root, update.hash, update.timestamp))
+        }
+        _ => None,
+    }
+}
+
+// Votes of the configured validators in the block, or of every validator if none are listed
+pub fn votes(config: &VoteConfig, block: &EncodedConfirmedBlock) -> Vec<ValidatorVote> {
+    let mut votes = Vec::new();
+    let program_id = vote_program::id().to_string();
+
+    for transaction_with_meta in &block.transactions {
+        let Some(transaction) = instructions::decode(transaction_with_meta) else {
+            continue;
+        };
+        if !transaction.succeeded {
+            continue;
+        }
+
+        for instruction in transaction.instructions.iter().filter(|instruction| instruction.program_id == program_id) {
+            let Some(vote_account) = instruction.accounts.first() else {
+                continue;
+            };
+            if !config.validators.is_empty() && !config.validators.contains(vote_account) {
+                continue;
+            }
+            let Some((voted_slot, root, hash, timestamp)) = decode_vote(&instruction.data) else {
+                continue;
+            };
+            votes.push(ValidatorVote {
+                signature: transaction.signature.clone(),
+                vote_account: vote_account.clone(),
+                voted_slot,
+                hash: hash.to_string(),
+                root,
+                timestamp,
+            });
+        }
+    }
+
+    votes
+}
+
+// Witness over a block's votes, seeded with the block hash
+pub fn votes_witness(
+    block_hash: &str,
+    votes: &[ValidatorVote],
+    memory_cap: usize,
+) -> Result<BlockWitness, WitnessError> {
+    let seed = str_to_fr(Domain::Commitment, &format!("{}:votes", block_hash));
+    let mut accumulator = WitnessAccumulator::new(seed, memory_cap);
+    for vote in votes {
+        accumulator.push(vote.leaf())?;
+    }
+    // Like mint proofs, this stands on its own rather than extending the block accumulator
+    accumulator.finish(Fr::ZERO)
+}
+
+pub fn prove_votes(
+    votes: Vec<ValidatorVote>,
+    witness: &BlockWitness,
+    hash: CommitmentHash,
+    params: &groth16::Parameters<Bls12>,
+) -> BlockVotes {
+    let leaves: Vec<_> = witness.leaves().map(|leaf| leaf.to_repr()).collect();
+    let (proof, chunks) = prove_chunks(witness, params);
+
+    BlockVotes {
+        votes,
+        votes_root: hex::encode(MerkleTree::new(hash, &leaves).root()),
+        commitment: fr_to_hex(&witness.top_level().commitment),
+        proof: serialization::proof_to_hex(&proof),
+        chunks,
+    }
+}
@@ -0,0 +1,535 @@
+use blstrs::Scalar as Fr;
+use ff::{Field, PrimeField};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use solana_sdk::clock::Slot;
+use solana_transaction_status::Reward;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+
+use crate::accounts::AccountStates;
+use crate::balance::BalanceSummary;
+use crate::config::{CommitmentHash, OversizedPolicy};
+use crate::circuit::{absorb, absorb_valued, chain_root, CIRCUIT_CAPACITY};
+use crate::fees::FeeStats;
+use crate::latency::ClockDrift;
+use crate::plugin::ExcludedTransaction;
+use crate::field::{hash_to_fr, hex_fr, hex_fr_vec};
+use crate::programs::ProgramChange;
+use crate::signatures::SignedMessage;
+use crate::stake::StakeConfirmation;
+use crate::supply::TokenSupply;
+use crate::stake_activity::StakeActivity;
+use crate::system::SolTransferSummary;
+use crate::token::{MintWitness, TokenTransfer};
+use crate::volume::VolumeWitness;
+use crate::votes::ValidatorVote;
+use crate::TransactionAnnotations;
+
+// Builds a block's witness one transaction at a time. The circuit commitment is
+// folded in as each transaction hash arrives, so nothing has to be recomputed
+// over the transactions seen so far, and the retained data is capped.
+//
+// Blocks with more transactions than the circuit holds are split into chunks of
+// `CIRCUIT_CAPACITY` leaves. Each chunk is proved on its own and a top-level
+// proof commits to the chunk commitments.
+pub struct WitnessAccumulator {
+    seed: Fr,
+    chunks: Vec<ChunkWitness>,
+    current: ChunkWitness,
+    retained_bytes: usize,
+    memory_cap: usize,
+}
+
+// A single circuit instance: the seed, the leaves and the resulting commitment,
+// plus the accumulator root it is chained onto
+#[derive(Serialize, Deserialize)]
+pub struct ChunkWitness {
+    #[serde(with = "hex_fr")]
+    pub seed: Fr,
+    #[serde(with = "hex_fr_vec")]
+    pub leaves: Vec<Fr>,
+    #[serde(with = "hex_fr")]
+    pub commitment: Fr,
+    #[serde(with = "hex_fr")]
+    pub old_root: Fr,
+}
+
+// Everything needed to prove a block. A block that fits the circuit is one
+// chunk seeded with the block hash; otherwise `aggregate` binds the chunks.
+#[derive(Serialize, Deserialize)]
+pub struct BlockWitness {
+    pub chunks: Vec<ChunkWitness>,
+    pub aggregate: Option<ChunkWitness>,
+}
+
+// One instance of the sum circuit: a chunk of a block's leaves with a value
+// attached to each, the commitment over the leaves and values, started from
+// the chunk's values seed, and the total of the values. Its other commitment,
+// over the leaves alone, is the chunk's.
+pub struct SumWitness {
+    pub seed: Fr,
+    pub leaves: Vec<Fr>,
+    pub values: Vec<u64>,
+    pub values_seed: Fr,
+    pub values_commitment: Fr,
+    // A chunk of raw token amounts can add up past u64::MAX
+    pub total: u128,
+}
+
+// One instance of the totals circuit: a chunk of a block's leaves with a
+// value attached to each, the commitment over the leaves and values, and the
+// number of leaves (transaction signatures) and total of the values. Its other commitment, over the
+// leaves alone, is the chunk's.
+pub struct TotalsWitness {
+    pub seed: Fr,
+    pub leaves: Vec<Fr>,
+    pub values: Vec<u64>,
+    pub values_commitment: Fr,
+    pub signature_count: u64,
+    pub total: u64,
+}
+
+// How a block over `max_txs_per_block` was proved. With `truncate-with-flag`
+// only its first `max_txs_per_block` transactions are in the proof.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct OversizedBlock {
+    pub policy: OversizedPolicy,
+    pub transactions: usize,
+    pub max_txs_per_block: usize,
+}
+
+// A block's witness together with what is needed to assemble its proof file.
+// Written by `--witness-only` and proved later by `prove-witness`.
+#[derive(Serialize, Deserialize)]
+pub struct ExportedWitness {
+    pub slot: Slot,
+    pub block_hash: String,
+    // Genesis hash of the cluster the block was produced on
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub genesis_hash: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub leader: Option<String>,
+    #[serde(default)]
+    pub leader_bound: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token_supply: Option<TokenSupply>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub confirmation: Option<StakeConfirmation>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub account_states: Option<AccountStates>,
+    #[serde(default)]
+    pub messages_bound: bool,
+    #[serde(default)]
+    pub hash_domains: u8,
+    #[serde(default, skip_serializing_if = "CommitmentHash::is_sha256")]
+    pub commitment_hash: CommitmentHash,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub oversized: Option<OversizedBlock>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub clock_drift: Option<ClockDrift>,
+    pub signatures: Vec<String>,
+    // Per-transaction message hashes (one per signature), when messages are bound
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub message_hashes: Vec<String>,
+    // Per-transaction leaf salts (hex), when built for selective disclosure
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub salts: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub token_transfers: Vec<TokenTransfer>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub mint_witnesses: Vec<MintWitness>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sol_transfers: Option<SolTransferSummary>,
+    // Lamports each leaf's transaction moved, counted on its first leaf, when
+    // the SOL total is proved
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sol_values: Option<Vec<u64>>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub volume_witnesses: Vec<VolumeWitness>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fees: Option<FeeStats>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub balances: Option<BalanceSummary>,
+    // Increases and decreases of the watched balances by each leaf's
+    // transaction, likewise, when their totals are proved
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub balance_credit_values: Option<Vec<u64>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub balance_debit_values: Option<Vec<u64>>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub stake_activity: Vec<StakeActivity>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub program_changes: Vec<ProgramChange>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub program_changes_witness: Option<BlockWitness>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub votes: Vec<ValidatorVote>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub votes_witness: Option<BlockWitness>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub rewards: Vec<Reward>,
+    // Decoded contents per transaction signature
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub annotations: BTreeMap<String, TransactionAnnotations>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub excluded: Vec<ExcludedTransaction>,
+    // Compute units consumed by each leaf's transaction, counted on its first
+    // leaf, when the block totals are proved
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub compute_units: Option<Vec<u64>>,
+    // Fee paid by each leaf's transaction, likewise, when the total fees are proved
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fee_values: Option<Vec<u64>>,
+    // Fee payer signature and message of each transaction, when signatures are proved
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub signed_messages: Vec<SignedMessage>,
+    pub witness: BlockWitness,
+}
+
+#[derive(Debug)]
+pub enum WitnessError {
+    MemoryCapExceeded { retained_bytes: usize, memory_cap: usize },
+    CapacityExceeded { transactions: usize },
+}
+
+impl fmt::Display for WitnessError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WitnessError::MemoryCapExceeded { retained_bytes, memory_cap } => write!(
+                f,
+                "block witness needs {} bytes, over the configured cap of {} bytes",
+                retained_bytes, memory_cap
+            ),
+            WitnessError::CapacityExceeded { transactions } => write!(
+                f,
+                "block has {} transactions, more than {} chunks of {} can hold",
+                transactions, CIRCUIT_CAPACITY, CIRCUIT_CAPACITY
+            ),
+        }
+    }
+}
+
+// Identifies the contents of a block's witness: the block hash and its
+// transaction hashes, in block order
+pub fn witness_hash(block_hash: &str, signatures: &[String]) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(b"solana-listener/witness");
+    for field in std::iter::once(block_hash).chain(signatures.iter().map(String::as_str)) {
+        hasher.update((field.len() as u64).to_le_bytes());
+        hasher.update(field.as_bytes());
+    }
+    hex::encode(hasher.finalize())
+}
+
+fn derive_seed(tag: &[u8], seed: Fr, index: u64) -> Fr {
+    let mut hasher = Sha256::new();
+    hasher.update(tag);
+    hasher.update(seed.to_repr());
+    hasher.update(index.to_le_bytes());
+    hash_to_fr(&hasher.finalize())
+}
+
+// Seed of the chunk at `index` of a block seeded with `seed`
+fn chunk_seed(seed: Fr, index: usize) -> Fr {
+    match index {
+        0 => seed,
+        index => derive_seed(b"solana-listener/chunk", seed, index as u64),
+    }
+}
+
+// Seed of the aggregate instance over a chunked block's commitments
+fn aggregate_seed(seed: Fr) -> Fr {
+    derive_seed(b"solana-listener/aggregate", seed, 0)
+}
+
+impl ChunkWitness {
+    fn new(seed: Fr) -> Self {
+        ChunkWitness {
+            seed,
+            leaves: Vec::new(),
+            commitment: seed,
+            old_root: Fr::ZERO,
+        }
+    }
+
+    fn push(&mut self, leaf: Fr) {
+        self.commitment = absorb(self.commitment, leaf, self.leaves.len());
+        self.leaves.push(leaf);
+    }
+
+    // Pads the remaining rounds with zero leaves, matching the circuit
+    fn finish(mut self) -> Self {
+        for round in self.leaves.len()..CIRCUIT_CAPACITY {
+            self.commitment = absorb(self.commitment, Fr::ZERO, round);
+        }
+        self
+    }
+
+    pub fn new_root(&self) -> Fr {
+        chain_root(self.old_root, self.commitment)
+    }
+}
+
+// Commitment over a chunk's leaves and the values attached to them, started
+// from `seed` and padded with zero leaves and values, matching the circuits
+pub fn values_commitment(seed: Fr, leaves: &[Fr], values: &[u64]) -> Fr {
+    (0..CIRCUIT_CAPACITY).fold(seed, |acc, round| {
+        let leaf = leaves.get(round).copied().unwrap_or(Fr::ZERO);
+        absorb_valued(acc, leaf, values.get(round).copied().unwrap_or(0), round)
+    })
+}
+
+// Values attached to a block's leaves, in leaf order, from amounts keyed by
+// transaction signature. A transaction's amounts add up on its first leaf,
+// whose signature is the transaction's, and its other leaves carry zero, as
+// do the leaves of transactions without amounts. None if a transaction's
+// amounts add up past u64::MAX, more than a circuit value holds.
+pub fn leaf_values<'a>(signatures: &[String], amounts: impl IntoIterator<Item = (&'a str, u64)>) -> Option<Vec<u64>> {
+    let mut by_transaction: HashMap<&str, u64> = HashMap::new();
+    for (signature, amount) in amounts {
+        let total = by_transaction.entry(signature).or_default();
+        *total = total.checked_add(amount)?;
+    }
+    Some(signatures.iter().map(|signature| by_transaction.get(signature.as_str()).copied().unwrap_or(0)).collect())
+}
+
+// Totals circuit instances over the chunks of a block witness, with `values`
+// attached to its leaves in order. Each instance is seeded like its chunk.
+pub fn totals_witnesses(witness: &BlockWitness, values: &[u64]) -> Vec<TotalsWitness> {
+    let mut values = values.iter().copied();
+    witness
+        .chunks
+        .iter()
+        .map(|chunk| {
+            let chunk_values: Vec<u64> = values.by_ref().take(chunk.leaves.len()).collect();
+            TotalsWitness {
+                seed: chunk.seed,
+                leaves: chunk.leaves.clone(),
+                values_commitment: values_commitment(chunk.seed, &chunk.leaves, &chunk_values),
+                signature_count: chunk.leaves.len() as u64,
+                total: chunk_values.iter().sum(),
+                values: chunk_values,
+            }
+        })
+        .collect()
+}
+
+// Sum circuit instances over the chunks of a block witness, with `values`
+// attached to its leaves in order. Each instance is seeded like its chunk, and
+// its values commitment starts from the chunk's seed derived from
+// `values_seed`. Chunk totals add up to the overall total.
+pub fn sum_witnesses(witness: &BlockWitness, values_seed: Fr, values: &[u64]) -> Vec<SumWitness> {
+    let mut values = values.iter().copied();
+    witness
+        .chunks
+        .iter()
+        .enumerate()
+        .map(|(index, chunk)| {
+            let chunk_values: Vec<u64> = values.by_ref().take(chunk.leaves.len()).collect();
+            let values_seed = chunk_seed(values_seed, index);
+            SumWitness {
+                seed: chunk.seed,
+                leaves: chunk.leaves.clone(),
+                values_commitment: values_commitment(values_seed, &chunk.leaves, &chunk_values),
+                values_seed,
+                total: chunk_values.iter().map(|&value| u128::from(value)).sum(),
+                values: chunk_values,
+            }
+        })
+        .collect()
+}
+
+impl BlockWitness {
+    // All transaction hashes of the block, in order
+    pub fn leaves(&self) -> impl Iterator<Item = &Fr> {
+        self.chunks.iter().flat_map(|chunk| chunk.leaves.iter())
+    }
+
+    // The instance whose commitment stands for the whole block and which is
+    // chained onto the accumulator root
+    pub fn top_level(&self) -> &ChunkWitness {
+        self.aggregate.as_ref().unwrap_or(&self.chunks[0])
+    }
+}
+
+impl WitnessAccumulator {
+    pub fn new(seed: Fr, memory_cap: usize) -> Self {
+        WitnessAccumulator {
+            seed,
+            chunks: Vec::new(),
+            current: ChunkWitness::new(seed),
+            retained_bytes: 0,
+            memory_cap,
+        }
+    }
+
+    pub fn push(&mut self, transaction_hash: Fr) -> Result<(), WitnessError> {
+        self.reserve(std::mem::size_of::<Fr>())?;
+
+        if self.current.leaves.len() == CIRCUIT_CAPACITY {
+            let next = ChunkWitness::new(chunk_seed(self.seed, self.chunks.len() + 1));
+            self.chunks.push(std::mem::replace(&mut self.current, next).finish());
+        }
+        self.current.push(transaction_hash);
+        Ok(())
+    }
+
+    // Accounts for per-block data that has to be held until the block proof is
+    // written (signatures, inclusion paths), failing once the cap is reached.
+    pub fn reserve(&mut self, bytes: usize) -> Result<(), WitnessError> {
+        self.retained_bytes += bytes;
+        if self.retained_bytes > self.memory_cap {
+            return Err(WitnessError::MemoryCapExceeded {
+                retained_bytes: self.retained_bytes,
+                memory_cap: self.memory_cap,
+            });
+        }
+        Ok(())
+    }
+
+    pub fn transaction_count(&self) -> usize {
+        self.chunks.len() * CIRCUIT_CAPACITY + self.current.leaves.len()
+    }
+
+    // Closes the last chunk and chains the block onto `old_root`
+    pub fn finish(mut self, old_root: Fr) -> Result<BlockWitness, WitnessError> {
+        let transactions = self.transaction_count();
+        self.chunks.push(self.current.finish());
+
+        if self.chunks.len() > CIRCUIT_CAPACITY {
+            return Err(WitnessError::CapacityExceeded { transactions });
+        }
+
+        let aggregate = if self.chunks.len() > 1 {
+            let mut aggregate = ChunkWitness::new(aggregate_seed(self.seed));
+            for chunk in &self.chunks {
+                aggregate.push(chunk.commitment);
+            }
+            aggregate.old_root = old_root;
+            Some(aggregate.finish())
+        } else {
+            self.chunks[0].old_root = old_root;
+            None
+        };
+
+        Ok(BlockWitness {
+            chunks: self.chunks,
+            aggregate,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::thread_rng;
+
+    fn accumulate(seed: Fr, leaves: &[Fr], memory_cap: usize) -> Result<BlockWitness, WitnessError> {
+        let mut accumulator = WitnessAccumulator::new(seed, memory_cap);
+        for &leaf in leaves {
+            accumulator.push(leaf)?;
+        }
+        accumulator.finish(Fr::from(7))
+    }
+
+    // Commitment of one circuit instance over `leaves`, absorbed from scratch
+    fn commitment(seed: Fr, leaves: &[Fr]) -> Fr {
+        let padded = leaves.iter().copied().chain(std::iter::repeat(Fr::ZERO));
+        padded.take(CIRCUIT_CAPACITY).enumerate().fold(seed, |acc, (round, leaf)| absorb(acc, leaf, round))
+    }
+
+    #[test]
+    fn blocks_are_split_at_circuit_capacity() {
+        let seed = Fr::random(thread_rng());
+        for count in [0, 1, CIRCUIT_CAPACITY, CIRCUIT_CAPACITY + 1] {
+            let leaves: Vec<Fr> = (0..count).map(|_| Fr::random(thread_rng())).collect();
+            let witness = accumulate(seed, &leaves, usize::MAX).unwrap();
+            assert_eq!(witness.leaves().copied().collect::<Vec<_>>(), leaves);
+            assert_eq!(witness.chunks.len(), count.div_ceil(CIRCUIT_CAPACITY).max(1));
+
+            for (index, (chunk, leaves)) in witness.chunks.iter().zip(leaves.chunks(CIRCUIT_CAPACITY)).enumerate() {
+                assert_eq!(chunk.seed, chunk_seed(seed, index));
+                assert_eq!(chunk.commitment, commitment(chunk.seed, leaves));
+            }
+            match &witness.aggregate {
+                None => {
+                    assert!(count <= CIRCUIT_CAPACITY);
+                    assert_eq!(witness.chunks[0].commitment, commitment(seed, &leaves));
+                    assert_eq!(witness.chunks[0].old_root, Fr::from(7));
+                }
+                Some(aggregate) => {
+                    let commitments: Vec<Fr> = witness.chunks.iter().map(|chunk| chunk.commitment).collect();
+                    assert_eq!(aggregate.commitment, commitment(aggregate_seed(seed), &commitments));
+                    assert_eq!(aggregate.old_root, Fr::from(7));
+                    assert!(witness.chunks.iter().all(|chunk| chunk.old_root == Fr::ZERO));
+                }
+            }
+            assert_eq!(witness.top_level().new_root(), chain_root(Fr::from(7), witness.top_level().commitment));
+        }
+    }
+
+    #[test]
+    fn blocks_over_capacity_are_rejected() {
+        let leaves = vec![Fr::ONE; CIRCUIT_CAPACITY * CIRCUIT_CAPACITY + 1];
+        let result = accumulate(Fr::ONE, &leaves, usize::MAX);
+        assert!(matches!(result, Err(WitnessError::CapacityExceeded { transactions }) if transactions == leaves.len()));
+    }
+
+    #[test]
+    fn retained_data_is_capped() {
+        let leaves = [Fr::ONE; 3];
+        let memory_cap = 2 * std::mem::size_of::<Fr>();
+        assert!(accumulate(Fr::ONE, &leaves[..2], memory_cap).is_ok());
+        let result = accumulate(Fr::ONE, &leaves, memory_cap);
+        assert!(matches!(result, Err(WitnessError::MemoryCapExceeded { retained_bytes: 96, memory_cap: 64 })));
+    }
+
+    #[test]
+    fn sums_follow_the_block_chunks() {
+        let leaves: Vec<Fr> = (0..=CIRCUIT_CAPACITY).map(|_| Fr::random(thread_rng())).collect();
+        let witness = accumulate(Fr::random(thread_rng()), &leaves, usize::MAX).unwrap();
+        let values_seed = Fr::random(thread_rng());
+        let values: Vec<u64> = (0..=CIRCUIT_CAPACITY as u64).collect();
+        let sums = sum_witnesses(&witness, values_seed, &values);
+        assert_eq!(sums.len(), 2);
+        for (index, (sum, chunk)) in sums.iter().zip(&witness.chunks).enumerate() {
+            assert_eq!((sum.seed, &sum.leaves), (chunk.seed, &chunk.leaves));
+            assert_eq!(sum.values_seed, chunk_seed(values_seed, index));
+            assert_eq!(sum.values_commitment, values_commitment(sum.values_seed, &sum.leaves, &sum.values));
+        }
+        assert_eq!(sums[0].values_seed, values_seed);
+        assert_eq!(sums[1].values, [CIRCUIT_CAPACITY as u64]);
+        assert_eq!(sums[0].total + sums[1].total, values.iter().map(|&value| u128::from(value)).sum::<u128>());
+    }
+
+    #[test]
+    fn sums_of_token_amounts_do_not_overflow() {
+        let witness = accumulate(Fr::ONE, &[Fr::ONE, Fr::ONE], usize::MAX).unwrap();
+        let sums = sum_witnesses(&witness, Fr::ONE, &[u64::MAX, u64::MAX]);
+        assert_eq!(sums[0].total, 2 * u128::from(u64::MAX));
+    }
+
+    #[test]
+    fn totals_follow_the_block_chunks() {
+        let leaves: Vec<Fr> = (0..=CIRCUIT_CAPACITY).map(|_| Fr::random(thread_rng())).collect();
+        let witness = accumulate(Fr::random(thread_rng()), &leaves, usize::MAX).unwrap();
+        let values: Vec<u64> = (0..=CIRCUIT_CAPACITY as u64).collect();
+        let totals = totals_witnesses(&witness, &values);
+        assert_eq!(totals.len(), 2);
+        for (totals, chunk) in totals.iter().zip(&witness.chunks) {
+            assert_eq!((totals.seed, &totals.leaves), (chunk.seed, &chunk.leaves));
+            assert_eq!(totals.signature_count, chunk.leaves.len() as u64);
+        }
+        assert_eq!(totals[1].values, [CIRCUIT_CAPACITY as u64]);
+        assert_eq!(totals[0].total + totals[1].total, values.iter().sum::<u64>());
+    }
+
+    #[test]
+    fn values_are_counted_on_the_first_leaf_of_their_transaction() {
+        let signatures: Vec<String> = ["a", "a2", "b", "c"].map(String::from).to_vec();
+        let values = leaf_values(&signatures, [("a", 5), ("c", 2), ("a", 3), ("unlisted", 9)]);
+        assert_eq!(values, Some(vec![8, 0, 0, 2]));
+        assert_eq!(leaf_values(&signatures, [("b", u64::MAX), ("b", 1)]), None);
+    }
+}
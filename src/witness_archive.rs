@@ -0,0 +1,78 @@
+use serde_json::Value;
+use solana_sdk::clock::Slot;
+use std::collections::BTreeSet;
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+
+use crate::witness::ExportedWitness;
+
+const COMPRESSION_LEVEL: i32 = 3;
+
+// Differences listed by `differences` before the rest are counted instead
+const MAX_DIFFERENCES: usize = 20;
+
+// Witnesses of the blocks proved, as `witnesses/<slot>.json.zst` in the
+// proofs directory, kept exactly as they were handed to the prover
+fn dir(proofs_dir: &Path) -> PathBuf {
+    proofs_dir.join("witnesses")
+}
+
+fn path(proofs_dir: &Path, slot: Slot) -> PathBuf {
+    dir(proofs_dir).join(format!("{}.json.zst", slot))
+}
+
+// Written to a temporary file first so a crash never leaves a torn witness
+pub fn save(proofs_dir: &Path, exported: &ExportedWitness) {
+    let path = path(proofs_dir, exported.slot);
+    let tmp_path = path.with_extension("zst.tmp");
+    fs::create_dir_all(dir(proofs_dir)).expect("Unable to create witness archive directory");
+    let file = File::create(&tmp_path).expect("Unable to archive witness");
+    let mut encoder = zstd::Encoder::new(file, COMPRESSION_LEVEL).expect("Unable to archive witness");
+    serde_json::to_writer(&mut encoder, exported).expect("Unable to archive witness");
+    encoder.finish().expect("Unable to archive witness");
+    fs::rename(&tmp_path, &path).expect("Unable to archive witness");
+}
+
+// The archived witness of `slot`, if any
+pub fn load(proofs_dir: &Path, slot: Slot) -> Option<ExportedWitness> {
+    let file = File::open(path(proofs_dir, slot)).ok()?;
+    let decoder = zstd::Decoder::new(file).expect("Unable to read archived witness");
+    Some(serde_json::from_reader(decoder).expect("Unable to parse archived witness"))
+}
+
+fn collect(path: String, archived: Option<&Value>, rebuilt: Option<&Value>, found: &mut Vec<String>, total: &mut usize) {
+    match (archived, rebuilt) {
+        (Some(Value::Object(archived)), Some(Value::Object(rebuilt))) => {
+            let keys: BTreeSet<&String> = archived.keys().chain(rebuilt.keys()).collect();
+            for key in keys {
+                collect(format!("{}.{}", path, key), archived.get(key), rebuilt.get(key), found, total);
+            }
+        }
+        (Some(Value::Array(archived)), Some(Value::Array(rebuilt))) => {
+            for index in 0..archived.len().max(rebuilt.len()) {
+                collect(format!("{}[{}]", path, index), archived.get(index), rebuilt.get(index), found, total);
+            }
+        }
+        (archived, rebuilt) if archived == rebuilt => {}
+        (archived, rebuilt) => {
+            *total += 1;
+            if found.len() < MAX_DIFFERENCES {
+                let show = |value: Option<&Value>| value.map_or("nothing".to_string(), Value::to_string);
+                found.push(format!("{}: archived {}, rebuilt {}", path, show(archived), show(rebuilt)));
+            }
+        }
+    }
+}
+
+// Paths at which the rebuilt witness differs from the archived one, with both
+// values, followed by the number of further differences if there are many
+pub fn differences(archived: &ExportedWitness, rebuilt: &ExportedWitness) -> Vec<String> {
+    let archived = serde_json::to_value(archived).expect("Unable to serialize witness");
+    let rebuilt = serde_json::to_value(rebuilt).expect("Unable to serialize witness");
+    let (mut found, mut total) = (Vec::new(), 0);
+    collect("witness".to_string(), Some(&archived), Some(&rebuilt), &mut found, &mut total);
+    if total > found.len() {
+        found.push(format!("and {} more", total - found.len()));
+    }
+    found
+}
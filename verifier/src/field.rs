@@ -0,0 +1,260 @@
+use blst::{
+    blst_fr, blst_fr_add, blst_fr_from_scalar, blst_fr_mul, blst_fr_sqr, blst_lendian_from_scalar, blst_scalar,
+    blst_scalar_fr_check, blst_scalar_from_fr, blst_scalar_from_lendian,
+};
+use sha2::{Digest, Sha256};
+
+// Number of leaves one circuit instance absorbs, as in the prover
+pub(crate) const CIRCUIT_CAPACITY: usize = 1024;
+
+// Element of the BLS12-381 scalar field. Bytes are little-endian, the order
+// the prover writes field elements in.
+#[derive(Clone, Copy, Debug)]
+pub struct Fr(blst_fr);
+
+impl Fr {
+    pub fn zero() -> Fr {
+        Fr(blst_fr::default())
+    }
+
+    // `None` unless the bytes encode a value below the field modulus
+    pub fn from_bytes(bytes: &[u8; 32]) -> Option<Fr> {
+        let mut scalar = blst_scalar::default();
+        let mut value = blst_fr::default();
+        unsafe {
+            blst_scalar_from_lendian(&mut scalar, bytes.as_ptr());
+            if !blst_scalar_fr_check(&scalar) {
+                return None;
+            }
+            blst_fr_from_scalar(&mut value, &scalar);
+        }
+        Some(Fr(value))
+    }
+
+    pub fn from_u64(value: u64) -> Fr {
+        Fr::from_u128(value.into())
+    }
+
+    pub fn from_u128(value: u128) -> Fr {
+        let mut bytes = [0u8; 32];
+        bytes[..16].copy_from_slice(&value.to_le_bytes());
+        Fr::from_bytes(&bytes).expect("u128 is below the field modulus")
+    }
+
+    pub fn from_hex(data: &str) -> Option<Fr> {
+        let mut bytes = [0u8; 32];
+        hex::decode_to_slice(data, &mut bytes).ok()?;
+        Fr::from_bytes(&bytes)
+    }
+
+    pub fn to_bytes(&self) -> [u8; 32] {
+        let scalar = self.to_scalar();
+        let mut bytes = [0u8; 32];
+        unsafe { blst_lendian_from_scalar(bytes.as_mut_ptr(), &scalar) };
+        bytes
+    }
+
+    pub(crate) fn to_scalar(self) -> blst_scalar {
+        let mut scalar = blst_scalar::default();
+        unsafe { blst_scalar_from_fr(&mut scalar, &self.0) };
+        scalar
+    }
+
+    fn add(self, other: Fr) -> Fr {
+        let mut sum = blst_fr::default();
+        unsafe { blst_fr_add(&mut sum, &self.0, &other.0) };
+        Fr(sum)
+    }
+
+    fn mul(self, other: Fr) -> Fr {
+        let mut product = blst_fr::default();
+        unsafe { blst_fr_mul(&mut product, &self.0, &other.0) };
+        Fr(product)
+    }
+
+    fn square(self) -> Fr {
+        let mut square = blst_fr::default();
+        unsafe { blst_fr_sqr(&mut square, &self.0) };
+        Fr(square)
+    }
+}
+
+impl PartialEq for Fr {
+    fn eq(&self, other: &Fr) -> bool {
+        self.to_bytes() == other.to_bytes()
+    }
+}
+
+// Maps a SHA-256 digest into the field, clearing the top two bits so the value
+// is always below the modulus
+fn hash_to_fr(hash: [u8; 32]) -> Fr {
+    let mut bytes = hash;
+    bytes[31] &= 0x3f;
+    Fr::from_bytes(&bytes).unwrap()
+}
+
+fn hash_parts(parts: &[&[u8]]) -> Fr {
+    let mut hasher = Sha256::new();
+    for part in parts {
+        hasher.update(part);
+    }
+    hash_to_fr(hasher.finalize().into())
+}
+
+fn round_constant(tag: &[u8], round: usize) -> Fr {
+    hash_parts(&[tag, &(round as u64).to_le_bytes()])
+}
+
+// (acc + leaf + constant)^5
+fn mimc_round(acc: Fr, leaf: Fr, constant: Fr) -> Fr {
+    let t = acc.add(leaf).add(constant);
+    t.square().square().mul(t)
+}
+
+fn absorb(acc: Fr, leaf: Fr, round: usize) -> Fr {
+    mimc_round(acc, leaf, round_constant(b"solana-listener/accumulator", round))
+}
+
+// Absorbs a leaf and the value attached to it into a values commitment
+fn absorb_valued(acc: Fr, leaf: Fr, value: u64, round: usize) -> Fr {
+    mimc_round(absorb(acc, leaf, round), Fr::from_u64(value), round_constant(b"solana-listener/value", round))
+}
+
+// Advances the cross-block accumulator by one block commitment
+pub fn chain_root(old_root: Fr, commitment: Fr) -> Fr {
+    mimc_round(old_root, commitment, hash_parts(&[b"solana-listener/chain"]))
+}
+
+// Circuit seed of a block: its hash, then the leader identity and the token
+// supply binding when bound, separated by colons. From version 1 of the hash
+// domains it is hashed under the block tag.
+pub fn block_seed(block_hash: &str, leader: Option<&str>, supply: Option<&str>, hash_domains: u8) -> Fr {
+    let version = [0, hash_domains];
+    let tag: &[&[u8]] = match hash_domains {
+        0 => &[],
+        _ => &[b"solana-listener/block", &version],
+    };
+    let mut data: alloc::vec::Vec<&[u8]> = alloc::vec![block_hash.as_bytes()];
+    for part in [leader, supply].into_iter().flatten() {
+        data.extend([b":".as_slice(), part.as_bytes()]);
+    }
+    hash_parts(&[tag, &data].concat())
+}
+
+// Leaf of a transaction: its signature, then its message hash when messages
+// are bound, separated by a colon, after its salt when salted. From version 1
+// of the hash domains it is hashed under the transaction tag.
+pub fn transaction_leaf(data: &str, salt: Option<&[u8]>, hash_domains: u8) -> Fr {
+    let version = [0, hash_domains];
+    let tag: &[&[u8]] = match hash_domains {
+        0 => &[],
+        _ => &[b"solana-listener/tx", &version],
+    };
+    hash_parts(&[tag, &[salt.unwrap_or_default(), data.as_bytes()]].concat())
+}
+
+// Leaf of a listed item of a block other than a transaction, such as a token
+// transfer, its fields joined by colons. From version 1 of the hash domains
+// it is hashed under the tag of its kind (`token_transfer`, `program_change`
+// or `vote`).
+pub fn item_leaf(tag: &[u8], data: &str, hash_domains: u8) -> Fr {
+    let version = [0, hash_domains];
+    let tag: &[&[u8]] = match hash_domains {
+        0 => &[],
+        _ => &[b"solana-listener/", tag, &version],
+    };
+    hash_parts(&[tag, &[data.as_bytes()]].concat())
+}
+
+// Seed of a commitment other than the block's: `data` names the block and
+// what is committed to. From version 1 of the hash domains it is hashed under
+// the commitment tag.
+pub fn commitment_seed(data: &str, hash_domains: u8) -> Fr {
+    item_leaf(b"commitment", data, hash_domains)
+}
+
+fn derive_seed(tag: &[u8], seed: Fr, index: u64) -> Fr {
+    hash_parts(&[tag, &seed.to_bytes(), &index.to_le_bytes()])
+}
+
+// Seed of the chunk at `index` of a block seeded with `seed`
+pub fn chunk_seed(seed: Fr, index: usize) -> Fr {
+    match index {
+        0 => seed,
+        index => derive_seed(b"solana-listener/chunk", seed, index as u64),
+    }
+}
+
+// Threshold circuit values seed of the SOL volume of a block, or that of `mint`
+pub fn volume_seed(block_hash: &str, mint: Option<&str>, hash_domains: u8) -> Fr {
+    commitment_seed(&[block_hash, ":volume:", mint.unwrap_or("sol")].concat(), hash_domains)
+}
+
+// Sum circuit values seed of a block's sum named `sum` (sol, credits or debits)
+pub fn sum_seed(block_hash: &str, sum: &str, hash_domains: u8) -> Fr {
+    commitment_seed(&[block_hash, ":", sum].concat(), hash_domains)
+}
+
+// Seed of the aggregate instance over a chunked block's commitments
+pub fn aggregate_seed(seed: Fr) -> Fr {
+    derive_seed(b"solana-listener/aggregate", seed, 0)
+}
+
+// Commitment of one circuit instance seeded with `seed` over `leaves`, padded
+// with zero leaves to the circuit's capacity
+pub fn leaves_commitment(seed: Fr, leaves: &[Fr]) -> Fr {
+    let leaves = leaves.iter().copied().chain(core::iter::repeat(Fr::zero()));
+    leaves.take(CIRCUIT_CAPACITY).enumerate().fold(seed, |acc, (round, leaf)| absorb(acc, leaf, round))
+}
+
+// Commitment of one sum, totals or threshold circuit instance over `leaves`
+// and the values attached to them, from `seed`, padded with zero leaves and
+// values to the circuit's capacity
+pub fn values_commitment(seed: Fr, leaves: &[Fr], values: &[u64]) -> Fr {
+    let leaves = leaves.iter().copied().chain(core::iter::repeat(Fr::zero()));
+    let values = values.iter().copied().chain(core::iter::repeat(0));
+    let valued = leaves.zip(values).take(CIRCUIT_CAPACITY).enumerate();
+    valued.fold(seed, |acc, (round, (leaf, value))| absorb_valued(acc, leaf, value, round))
+}
+
+// Commitment of the aggregate instance over the given chunk commitments
+pub fn aggregate_commitment(seed: Fr, chunk_commitments: &[Fr]) -> Fr {
+    leaves_commitment(aggregate_seed(seed), chunk_commitments)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The field modulus, little-endian
+    const MODULUS: [u8; 32] = [
+        0x01, 0x00, 0x00, 0x00, 0xff, 0xff, 0xff, 0xff, 0xfe, 0x5b, 0xfe, 0xff, 0x02, 0xa4, 0xbd, 0x53, 0x05, 0xd8,
+        0xa1, 0x09, 0x08, 0xd8, 0x39, 0x33, 0x48, 0x7d, 0x9d, 0x29, 0x53, 0xa7, 0xed, 0x73,
+    ];
+
+    #[test]
+    fn from_bytes_rejects_values_outside_the_field() {
+        let mut largest = MODULUS;
+        largest[0] -= 1;
+        assert_eq!(Fr::from_bytes(&largest).map(|value| value.to_bytes()), Some(largest));
+        assert!(Fr::from_bytes(&MODULUS).is_none());
+        assert!(Fr::from_bytes(&[0xff; 32]).is_none());
+    }
+
+    #[test]
+    fn hex_round_trips() {
+        let value = Fr::from_u64(u64::MAX);
+        assert_eq!(Fr::from_hex(&hex::encode(value.to_bytes())), Some(value));
+        assert!(Fr::from_hex("00").is_none());
+        assert!(Fr::from_hex(&hex::encode(MODULUS)).is_none());
+    }
+
+    #[test]
+    fn seeds_are_derived_per_chunk() {
+        let seed = block_seed("hash", None, None, 1);
+        assert_eq!(chunk_seed(seed, 0), seed);
+        assert!(chunk_seed(seed, 1) != seed && chunk_seed(seed, 1) != chunk_seed(seed, 2));
+        assert!(aggregate_seed(seed) != chunk_seed(seed, 1));
+        assert!(block_seed("hash", None, None, 0) != seed);
+    }
+}
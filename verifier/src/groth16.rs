@@ -0,0 +1,178 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+use blst::{
+    blst_final_exp, blst_fp12, blst_fp12_is_equal, blst_fp12_mul, blst_miller_loop, blst_p1, blst_p1_add_or_double,
+    blst_p1_affine, blst_p1_affine_in_g1, blst_p1_affine_is_inf, blst_p1_cneg, blst_p1_deserialize,
+    blst_p1_from_affine, blst_p1_mult, blst_p1_to_affine, blst_p1_uncompress, blst_p2_affine, blst_p2_affine_in_g2,
+    blst_p2_affine_is_inf, blst_p2_deserialize, blst_p2_uncompress, BLST_ERROR,
+};
+use sha2::{Digest, Sha256};
+
+use crate::field::Fr;
+
+// Groth16 verifying key of the block circuit, with e(alpha, beta) precomputed
+pub struct VerifyingKey {
+    alpha_g1_beta_g2: blst_fp12,
+    gamma_g2: blst_p2_affine,
+    delta_g2: blst_p2_affine,
+    ic: Vec<blst_p1_affine>,
+}
+
+// A Groth16 proof in the compressed encoding of the proof files
+pub struct Proof {
+    a: blst_p1_affine,
+    b: blst_p2_affine,
+    c: blst_p1_affine,
+}
+
+// SHA-256 (hex) of a serialized verifying key; the fingerprint proof
+// files record for the key they were made with
+pub fn fingerprint(vk_bytes: &[u8]) -> String {
+    hex::encode(Sha256::digest(vk_bytes))
+}
+
+fn g1_uncompressed(bytes: &[u8]) -> Option<blst_p1_affine> {
+    let mut point = blst_p1_affine::default();
+    let valid = unsafe {
+        blst_p1_deserialize(&mut point, bytes.as_ptr()) == BLST_ERROR::BLST_SUCCESS && blst_p1_affine_in_g1(&point)
+    };
+    valid.then_some(point)
+}
+
+fn g2_uncompressed(bytes: &[u8]) -> Option<blst_p2_affine> {
+    let mut point = blst_p2_affine::default();
+    let valid = unsafe {
+        blst_p2_deserialize(&mut point, bytes.as_ptr()) == BLST_ERROR::BLST_SUCCESS && blst_p2_affine_in_g2(&point)
+    };
+    valid.then_some(point)
+}
+
+// Proof points are compressed and may not be the identity
+fn g1_compressed(bytes: &[u8]) -> Option<blst_p1_affine> {
+    let mut point = blst_p1_affine::default();
+    let valid = unsafe {
+        blst_p1_uncompress(&mut point, bytes.as_ptr()) == BLST_ERROR::BLST_SUCCESS
+            && blst_p1_affine_in_g1(&point)
+            && !blst_p1_affine_is_inf(&point)
+    };
+    valid.then_some(point)
+}
+
+fn g2_compressed(bytes: &[u8]) -> Option<blst_p2_affine> {
+    let mut point = blst_p2_affine::default();
+    let valid = unsafe {
+        blst_p2_uncompress(&mut point, bytes.as_ptr()) == BLST_ERROR::BLST_SUCCESS
+            && blst_p2_affine_in_g2(&point)
+            && !blst_p2_affine_is_inf(&point)
+    };
+    valid.then_some(point)
+}
+
+fn pairing(p: &blst_p1_affine, q: &blst_p2_affine) -> blst_fp12 {
+    let mut result = blst_fp12::default();
+    unsafe {
+        blst_miller_loop(&mut result, q, p);
+        let product = result;
+        blst_final_exp(&mut result, &product);
+    }
+    result
+}
+
+// Splits `len` bytes off the front of `bytes`
+fn take<'a>(bytes: &mut &'a [u8], len: usize) -> Option<&'a [u8]> {
+    if bytes.len() < len {
+        return None;
+    }
+    let (head, tail) = bytes.split_at(len);
+    *bytes = tail;
+    Some(head)
+}
+
+impl VerifyingKey {
+    // Parses a verifying key as bellman writes it, with uncompressed points.
+    // Parameter files start with their verifying key, so one can be read from
+    // the front of a parameters file as well.
+    pub fn read(mut bytes: &[u8]) -> Option<VerifyingKey> {
+        let bytes = &mut bytes;
+        let alpha_g1 = g1_uncompressed(take(bytes, 96)?)?;
+        let _beta_g1 = g1_uncompressed(take(bytes, 96)?)?;
+        let beta_g2 = g2_uncompressed(take(bytes, 192)?)?;
+        let gamma_g2 = g2_uncompressed(take(bytes, 192)?)?;
+        let _delta_g1 = g1_uncompressed(take(bytes, 96)?)?;
+        let delta_g2 = g2_uncompressed(take(bytes, 192)?)?;
+        let ic_len = u32::from_be_bytes(take(bytes, 4)?.try_into().ok()?) as usize;
+        let ic = (0..ic_len)
+            .map(|_| g1_uncompressed(take(bytes, 96)?))
+            .collect::<Option<Vec<_>>>()?;
+
+        Some(VerifyingKey {
+            alpha_g1_beta_g2: pairing(&alpha_g1, &beta_g2),
+            gamma_g2,
+            delta_g2,
+            ic,
+        })
+    }
+}
+
+impl Proof {
+    pub fn from_bytes(bytes: &[u8; 192]) -> Option<Proof> {
+        Some(Proof {
+            a: g1_compressed(&bytes[..48])?,
+            b: g2_compressed(&bytes[48..144])?,
+            c: g1_compressed(&bytes[144..])?,
+        })
+    }
+
+    pub fn from_hex(data: &str) -> Option<Proof> {
+        let mut bytes = [0u8; 192];
+        hex::decode_to_slice(data, &mut bytes).ok()?;
+        Proof::from_bytes(&bytes)
+    }
+}
+
+fn negate(point: &blst_p1) -> blst_p1_affine {
+    let mut negated = *point;
+    let mut affine = blst_p1_affine::default();
+    unsafe {
+        blst_p1_cneg(&mut negated, true);
+        blst_p1_to_affine(&mut affine, &negated);
+    }
+    affine
+}
+
+// Checks e(A, B) = e(alpha, beta) · e(inputs, gamma) · e(C, delta), where
+// `inputs` combines the verifying key's IC points with the public inputs
+pub fn verify_proof(vk: &VerifyingKey, proof: &Proof, inputs: &[Fr]) -> bool {
+    if inputs.len() + 1 != vk.ic.len() {
+        return false;
+    }
+
+    let mut acc = blst_p1::default();
+    let mut c = blst_p1::default();
+    let mut result = blst_fp12::default();
+    unsafe {
+        blst_p1_from_affine(&mut acc, &vk.ic[0]);
+        for (input, base) in inputs.iter().zip(&vk.ic[1..]) {
+            let scalar = input.to_scalar();
+            let mut point = blst_p1::default();
+            let mut term = blst_p1::default();
+            blst_p1_from_affine(&mut point, base);
+            blst_p1_mult(&mut term, &point, scalar.b.as_ptr(), 255);
+            let sum = acc;
+            blst_p1_add_or_double(&mut acc, &sum, &term);
+        }
+        blst_p1_from_affine(&mut c, &proof.c);
+
+        let mut loops = [blst_fp12::default(); 3];
+        blst_miller_loop(&mut loops[0], &proof.b, &proof.a);
+        blst_miller_loop(&mut loops[1], &vk.gamma_g2, &negate(&acc));
+        blst_miller_loop(&mut loops[2], &vk.delta_g2, &negate(&c));
+        let mut product = loops[0];
+        for factor in &loops[1..] {
+            let partial = product;
+            blst_fp12_mul(&mut product, &partial, factor);
+        }
+        blst_final_exp(&mut result, &product);
+        blst_fp12_is_equal(&result, &vk.alpha_g1_beta_g2)
+    }
+}
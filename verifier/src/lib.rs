@@ -0,0 +1,360 @@
+// Verifier for the block proofs written by solana_block_listener, without any
+// of the prover's dependencies. It is `no_std` (with `alloc`), so it builds for
+// wasm32-unknown-unknown and for runtimes without an operating system:
+//
+//     cargo build -p solana_block_verifier --target wasm32-unknown-unknown
+//
+// blst compiles its C sources for the target, which needs a clang that can
+// emit wasm32.
+#![no_std]
+
+extern crate alloc;
+
+mod field;
+mod groth16;
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+
+pub use field::{
+    aggregate_commitment, aggregate_seed, block_seed, chain_root, chunk_seed, commitment_seed, item_leaf,
+    leaves_commitment, sum_seed, transaction_leaf, values_commitment, volume_seed, Fr,
+};
+
+use field::CIRCUIT_CAPACITY;
+pub use groth16::{fingerprint, verify_proof, Proof, VerifyingKey};
+
+// The public data of a block proof that its circuit proofs are checked against
+pub struct BlockStatement<'a> {
+    pub block_hash: &'a str,
+    // Leader identity, for blocks proved with the leader bound into the seed
+    pub leader: Option<&'a str>,
+    // Token supply binding, for blocks proved with the supply bound into the seed
+    pub supply: Option<String>,
+    // Version of the domain tags the seed was hashed with; 0 for untagged seeds
+    pub hash_domains: u8,
+    pub commitment: Fr,
+    pub old_root: Fr,
+    pub new_root: Fr,
+    pub proof: Proof,
+    // Commitment and proof of every chunk, in order; empty unless the block was split
+    pub chunks: Vec<(Fr, Proof)>,
+    // Leaf of every transaction, in block order, when the proof lists its
+    // transactions. The leaves must make up the commitment (of each chunk).
+    pub leaves: Option<Vec<Fr>>,
+}
+
+// A commitment over listed items of a block other than its transactions (a
+// mint's transfers, its program changes or its votes), proved with the block
+// circuit. It stands on its own: it is seeded with `seed` and chained from
+// zero rather than onto the block accumulator.
+pub struct CommitmentStatement {
+    pub seed: Fr,
+    pub commitment: Fr,
+    pub proof: Proof,
+    // Commitment and proof of every chunk, in order; empty unless it was split
+    pub chunks: Vec<(Fr, Proof)>,
+    // Leaf of every item, in order, which must make up the commitment
+    pub leaves: Vec<Fr>,
+}
+
+// Total of a value attached to each leaf of a block, proved with the sum
+// circuit over every chunk of the block (a single one when it was not split).
+// The values commitment of each chunk starts from its chunk seed derived from
+// `values_seed`.
+pub struct SumStatement {
+    pub values_seed: Fr,
+    pub total: u128,
+    pub chunks: Vec<SumChunk>,
+    // Value of every leaf, in block order, when the proof lists what they are
+    // computed from. With the block's leaves they must make up the values
+    // commitments.
+    pub values: Option<Vec<u64>>,
+}
+
+pub struct SumChunk {
+    // Commitment over the chunk's leaves and their values
+    pub values_commitment: Fr,
+    pub total: u128,
+    pub proof: Proof,
+}
+
+// Signature count (the number of transaction leaves, one per signature) and
+// total of a value attached to each leaf of a block, proved with the totals
+// circuit over every chunk of the block (a single one when it was not split)
+pub struct TotalsStatement {
+    pub signature_count: u64,
+    pub total: u64,
+    pub chunks: Vec<TotalsChunk>,
+}
+
+pub struct TotalsChunk {
+    // Commitment over the chunk's leaves and their values
+    pub values_commitment: Fr,
+    pub signature_count: u64,
+    pub total: u64,
+    pub proof: Proof,
+}
+
+// A proof that the SOL, or `mint`, moved by transfers in a block adds up to at
+// least `threshold` (`above`) or to less than it. The amount each transaction
+// moved is attached to its first leaf, so the proof is over the block's leaves.
+pub struct ThresholdStatement<'a> {
+    pub mint: Option<&'a str>,
+    pub threshold: u64,
+    pub above: bool,
+    // Commitment over the block's leaves and the amounts attached to them
+    pub values_commitment: Fr,
+    pub proof: Proof,
+}
+
+// A proof that `signature` (R, then S) verifies against `fee_payer`'s key for
+// a message the proof keeps private
+#[cfg(feature = "ed25519")]
+pub struct SignatureStatement {
+    pub signature: [u8; 64],
+    pub fee_payer: [u8; 32],
+    pub proof: Proof,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Error {
+    // The aggregate commitment does not match the chunk commitments
+    CommitmentMismatch,
+    // The transaction leaves do not make up the commitment
+    LeavesMismatch,
+    // The top-level proof, or the proof of the chunk at the given index, is invalid
+    InvalidProof(Option<usize>),
+    // The chunk counts or totals do not add up to the block's, or there is not
+    // one totals proof per chunk
+    TotalsMismatch,
+    // The totals proof of the chunk at the given index is invalid
+    InvalidTotalsProof(usize),
+    // The chunk totals do not add up to the sum's total, there is not one sum
+    // proof per chunk, or the values do not make up the values commitments
+    SumMismatch,
+    // The sum proof of the chunk at the given index is invalid
+    InvalidSumProof(usize),
+    InvalidThresholdProof,
+    InvalidSignatureProof,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::CommitmentMismatch => write!(f, "commitment does not aggregate the chunk commitments"),
+            Error::LeavesMismatch => write!(f, "transactions do not make up the commitment"),
+            Error::InvalidProof(None) => write!(f, "block proof does not verify"),
+            Error::InvalidProof(Some(index)) => write!(f, "proof of chunk {} does not verify", index),
+            Error::TotalsMismatch => write!(f, "chunk totals do not add up to the block totals"),
+            Error::InvalidTotalsProof(index) => write!(f, "totals proof of chunk {} does not verify", index),
+            Error::SumMismatch => write!(f, "chunk sums do not add up to the total or the listed values"),
+            Error::InvalidSumProof(index) => write!(f, "sum proof of chunk {} does not verify", index),
+            Error::InvalidThresholdProof => write!(f, "volume threshold proof does not verify"),
+            Error::InvalidSignatureProof => write!(f, "signature proof does not verify"),
+        }
+    }
+}
+
+// Checks that the leaves, in chunks of the circuit's capacity, make up the
+// commitment seeded with `seed`, or each of its chunk commitments
+fn verify_leaves(seed: Fr, commitment: Fr, chunks: &[(Fr, Proof)], leaves: &[Fr]) -> Result<(), Error> {
+    let matches = if chunks.is_empty() {
+        leaves.len() <= CIRCUIT_CAPACITY && leaves_commitment(seed, leaves) == commitment
+    } else {
+        leaves.len().div_ceil(CIRCUIT_CAPACITY) == chunks.len()
+            && chunks.iter().zip(leaves.chunks(CIRCUIT_CAPACITY)).enumerate().all(
+                |(index, ((commitment, _), leaves))| leaves_commitment(chunk_seed(seed, index), leaves) == *commitment,
+            )
+    };
+    match matches {
+        true => Ok(()),
+        false => Err(Error::LeavesMismatch),
+    }
+}
+
+// Checks the top-level block circuit proof of a commitment seeded with `seed`
+// against the accumulator roots and, for chunked commitments, every chunk
+// proof and the aggregate commitment over them
+fn verify_instances(
+    vk: &VerifyingKey,
+    seed: Fr,
+    commitment: Fr,
+    (old_root, new_root): (Fr, Fr),
+    proof: &Proof,
+    chunks: &[(Fr, Proof)],
+) -> Result<(), Error> {
+    let top_level_seed = if chunks.is_empty() {
+        seed
+    } else {
+        for (index, (commitment, proof)) in chunks.iter().enumerate() {
+            // Chunks are chained from zero; only the aggregate carries the real roots
+            let inputs = [chunk_seed(seed, index), *commitment, Fr::zero(), chain_root(Fr::zero(), *commitment)];
+            if !verify_proof(vk, proof, &inputs) {
+                return Err(Error::InvalidProof(Some(index)));
+            }
+        }
+        let commitments: Vec<Fr> = chunks.iter().map(|(commitment, _)| *commitment).collect();
+        if aggregate_commitment(seed, &commitments) != commitment {
+            return Err(Error::CommitmentMismatch);
+        }
+        aggregate_seed(seed)
+    };
+
+    let inputs = [top_level_seed, commitment, old_root, new_root];
+    if !verify_proof(vk, proof, &inputs) {
+        return Err(Error::InvalidProof(None));
+    }
+    Ok(())
+}
+
+// Checks the top-level proof of a block against its seed and accumulator
+// roots, for chunked blocks every chunk proof and the aggregate commitment
+// over them, and that the block's leaves, if given, make up the commitments
+pub fn verify_block(vk: &VerifyingKey, block: &BlockStatement) -> Result<(), Error> {
+    let seed = block_seed(block.block_hash, block.leader, block.supply.as_deref(), block.hash_domains);
+    if let Some(leaves) = &block.leaves {
+        verify_leaves(seed, block.commitment, &block.chunks, leaves)?;
+    }
+    verify_instances(vk, seed, block.commitment, (block.old_root, block.new_root), &block.proof, &block.chunks)
+}
+
+// Checks the proofs of a standalone commitment, like those of a block chained
+// from zero, and that its leaves make up the commitments
+pub fn verify_commitment(vk: &VerifyingKey, statement: &CommitmentStatement) -> Result<(), Error> {
+    verify_leaves(statement.seed, statement.commitment, &statement.chunks, &statement.leaves)?;
+    let roots = (Fr::zero(), chain_root(Fr::zero(), statement.commitment));
+    verify_instances(vk, statement.seed, statement.commitment, roots, &statement.proof, &statement.chunks)
+}
+
+// Seed and commitment of every chunk of a block, the block itself when it was
+// not split
+fn block_chunks(block: &BlockStatement) -> Vec<(Fr, Fr)> {
+    let seed = block_seed(block.block_hash, block.leader, block.supply.as_deref(), block.hash_domains);
+    if block.chunks.is_empty() {
+        alloc::vec![(seed, block.commitment)]
+    } else {
+        block.chunks.iter().enumerate().map(|(index, (commitment, _))| (chunk_seed(seed, index), *commitment)).collect()
+    }
+}
+
+// Checks the sum proofs of a block against the seed and commitment of each of
+// its chunks, which ties the values to the leaves the block proof commits to,
+// and that the chunk totals add up to the sum's total. When the values are
+// given, with the block's leaves they must make up each chunk's values
+// commitment. The block proof itself is checked by `verify_block`.
+pub fn verify_sum(vk: &VerifyingKey, block: &BlockStatement, sum: &SumStatement) -> Result<(), Error> {
+    let chunks = block_chunks(block);
+    if chunks.len() != sum.chunks.len() {
+        return Err(Error::SumMismatch);
+    }
+    if let (Some(leaves), Some(values)) = (&block.leaves, &sum.values) {
+        let listed = leaves.len() == values.len()
+            && sum.chunks.iter().enumerate().all(|(index, chunk)| {
+                let range = index * CIRCUIT_CAPACITY..leaves.len().min((index + 1) * CIRCUIT_CAPACITY);
+                let values_seed = chunk_seed(sum.values_seed, index);
+                leaves.get(range.clone()).zip(values.get(range)).is_some_and(|(leaves, values)| {
+                    values_commitment(values_seed, leaves, values) == chunk.values_commitment
+                })
+            });
+        if !listed {
+            return Err(Error::SumMismatch);
+        }
+    }
+
+    let mut total = 0u128;
+    for (index, ((seed, commitment), chunk)) in chunks.iter().zip(&sum.chunks).enumerate() {
+        let values_seed = chunk_seed(sum.values_seed, index);
+        let inputs = [*seed, values_seed, *commitment, chunk.values_commitment, Fr::from_u128(chunk.total)];
+        if !verify_proof(vk, &chunk.proof, &inputs) {
+            return Err(Error::InvalidSumProof(index));
+        }
+        total = total.checked_add(chunk.total).ok_or(Error::SumMismatch)?;
+    }
+    if total != sum.total {
+        return Err(Error::SumMismatch);
+    }
+    Ok(())
+}
+
+// Checks the totals proofs of a block against the seed and commitment of each
+// of its chunks, which ties the signature count and total to the leaves the
+// block proof commits to, and that the chunks add up to the block's signature
+// count and total. The
+// block proof itself is checked by `verify_block`.
+pub fn verify_totals(vk: &VerifyingKey, block: &BlockStatement, totals: &TotalsStatement) -> Result<(), Error> {
+    let chunks = block_chunks(block);
+    if chunks.len() != totals.chunks.len() {
+        return Err(Error::TotalsMismatch);
+    }
+
+    let (mut signature_count, mut total) = (0u64, 0u64);
+    for (index, ((seed, commitment), chunk)) in chunks.iter().zip(&totals.chunks).enumerate() {
+        let (count_input, total_input) = (Fr::from_u64(chunk.signature_count), Fr::from_u64(chunk.total));
+        let inputs = [*seed, *commitment, chunk.values_commitment, count_input, total_input];
+        if !verify_proof(vk, &chunk.proof, &inputs) {
+            return Err(Error::InvalidTotalsProof(index));
+        }
+        signature_count = signature_count.checked_add(chunk.signature_count).ok_or(Error::TotalsMismatch)?;
+        total = total.checked_add(chunk.total).ok_or(Error::TotalsMismatch)?;
+    }
+    if signature_count != totals.signature_count || total != totals.total {
+        return Err(Error::TotalsMismatch);
+    }
+    Ok(())
+}
+
+// Checks a volume threshold proof against the seed and commitment of its
+// block, which ties the amounts to the leaves the block proof commits to, and
+// the values seed of its mint. A threshold circuit holds a single chunk, so the
+// proof of a split block is rejected. The block proof itself is checked by
+// `verify_block`.
+pub fn verify_threshold(
+    vk: &VerifyingKey,
+    block: &BlockStatement,
+    statement: &ThresholdStatement,
+) -> Result<(), Error> {
+    if !block.chunks.is_empty() {
+        return Err(Error::InvalidThresholdProof);
+    }
+    let seed = block_seed(block.block_hash, block.leader, block.supply.as_deref(), block.hash_domains);
+    let values_seed = volume_seed(block.block_hash, statement.mint, block.hash_domains);
+    let inputs = [
+        seed,
+        values_seed,
+        block.commitment,
+        statement.values_commitment,
+        Fr::from_u64(statement.threshold),
+        Fr::from_u64(statement.above as u64),
+    ];
+    if !verify_proof(vk, &statement.proof, &inputs) {
+        return Err(Error::InvalidThresholdProof);
+    }
+    Ok(())
+}
+
+// Public inputs of a signature proof: the fee payer's key, R and S, two each,
+// their 16-byte halves as little-endian integers
+#[cfg(feature = "ed25519")]
+pub fn signature_inputs(fee_payer: &[u8; 32], signature: &[u8; 64]) -> Vec<Fr> {
+    let (r, s) = signature.split_at(32);
+    let halves = fee_payer.chunks(16).chain(r.chunks(16)).chain(s.chunks(16));
+    halves
+        .map(|half| {
+            let mut bytes = [0u8; 32];
+            bytes[..16].copy_from_slice(half);
+            Fr::from_bytes(&bytes).expect("128-bit values are below the field modulus")
+        })
+        .collect()
+}
+
+// Checks a signature proof
+#[cfg(feature = "ed25519")]
+pub fn verify_signature(vk: &VerifyingKey, statement: &SignatureStatement) -> Result<(), Error> {
+    let inputs = signature_inputs(&statement.fee_payer, &statement.signature);
+    if !verify_proof(vk, &statement.proof, &inputs) {
+        return Err(Error::InvalidSignatureProof);
+    }
+    Ok(())
+}